@@ -95,6 +95,11 @@ pub trait DagNode {
     /// ID を持たない「末端」ノードとして扱われる。
     fn id(&self) -> Option<&str>;
     fn depends(&self) -> impl IntoIterator<Item = &impl AsRef<str>>;
+    /// スケジューリング上の重み（前回の実行時間など）。[`DagTree::schedule`] は同じ深さの
+    /// ノードを重い順に並べる。既定は 0（重みなし）。
+    fn weight(&self) -> u64 {
+        0
+    }
 }
 
 /// Dag Resolution Error
@@ -103,12 +108,74 @@ pub enum DagError {
     #[error("duplicate node: {0}")]
     DuplicateName(String),
     #[error(
-        "unknown dependency: {dep} (referred by {by})",
-        by = by.as_deref().unwrap_or("<unnamed>")
+        "unknown dependency: {dep} (referred by {by}){hint}",
+        by = by.as_deref().unwrap_or("<unnamed>"),
+        hint = did_you_mean(suggestions)
     )]
-    UnknownDependency { dep: String, by: Option<String> },
+    UnknownDependency {
+        dep: String,
+        by: Option<String>,
+        /// 綴りの近い既存ノードの id（近い順）。
+        suggestions: Vec<String>,
+    },
     #[error("cycle detected; remaining: {0:?}")]
     CycleDetected(Vec<String>),
+    #[error("unknown node: {0}")]
+    UnknownNode(String),
+    #[error("node {id} is still depended on by {}", dependents.join(", "))]
+    StillDepended { id: String, dependents: Vec<String> },
+}
+
+/// `UnknownDependency` に添える候補の数の上限。
+const MAX_SUGGESTIONS: usize = 3;
+
+/// 大文字小文字を区別しない編集距離（Levenshtein）。依存名・イベント名の typo 候補探しに使う。
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().flat_map(char::to_lowercase).collect();
+    let b: Vec<char> = b.chars().flat_map(char::to_lowercase).collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// 未知の依存 `dep` の書き損じに見える既存の id を近い順（同じ距離は名前順）に返す。
+fn suggestions<'a>(dep: &str, ids: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let tolerance = if dep.chars().count() < 6 { 1 } else { 2 };
+    let mut close: Vec<(usize, &str)> = ids
+        .into_iter()
+        .map(|id| (edit_distance(dep, id), id))
+        .filter(|(distance, _)| *distance <= tolerance)
+        .collect();
+    close.sort_unstable();
+    close.dedup();
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, id)| id.to_string())
+        .collect()
+}
+
+/// `UnknownDependency` の表示に添える `; did you mean ...?`。候補が無ければ空。
+fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [id] => format!("; did you mean `{id}`?"),
+        [init @ .., last] => format!(
+            "; did you mean {} or `{last}`?",
+            init.iter()
+                .map(|id| format!("`{id}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
 }
 
 pub mod tree {
@@ -173,6 +240,7 @@ pub trait TryDag<D: DagNode>: IntoIterator<Item = D> + Sized {
                             .ok_or_else(|| DagError::UnknownDependency {
                                 dep: dep.to_string(),
                                 by: item.inner.id().map(str::to_string),
+                                suggestions: suggestions(dep, id_to_index.keys().copied()),
                             })?;
                     references[dep_idx].push(idx);
                 }
@@ -252,7 +320,174 @@ pub trait TryDag<D: DagNode>: IntoIterator<Item = D> + Sized {
 // Automatic implementation for all IntoIterator<Item = D>.
 impl<D: DagNode, I: IntoIterator<Item = D>> TryDag<D> for I {}
 
+/// Serializable view of a resolved [`DagTree`]: ids, edges, and the topological order.
+/// Serialize and Deserialize are implemented with the `serde` feature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DagSnapshot {
+    /// Nodes in topological order (every node after its dependencies)
+    pub nodes: Vec<DagSnapshotNode>,
+}
+
+/// One node of a [`DagSnapshot`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DagSnapshotNode {
+    /// [`DagNode::id`]
+    pub id: Option<String>,
+    /// Index within the original input order
+    pub index: usize,
+    /// Longest dependency chain depth (0 if no dependencies)
+    pub depth: usize,
+    /// Positions of the direct dependencies within [`DagSnapshot::nodes`], ascending
+    pub depends: Vec<usize>,
+}
+
 impl<D: DagNode> DagTree<D> {
+    /// Take a [`DagSnapshot`] of the resolved graph.
+    pub fn snapshot(&self) -> DagSnapshot {
+        // `inner` は依存元が先（取り出しは末尾から）なので、逆順が依存先からの topo 順。
+        let last = self.inner.len().saturating_sub(1);
+        let mut nodes: Vec<DagSnapshotNode> = self
+            .inner
+            .iter()
+            .rev()
+            .map(|item| DagSnapshotNode {
+                id: item.inner.id().map(str::to_string),
+                index: item.original_index,
+                depth: item.depth,
+                depends: Vec::new(),
+            })
+            .collect();
+        for (position, item) in self.inner.iter().rev().enumerate() {
+            for &dependent in &item.dependents_indexes {
+                nodes[last - dependent].depends.push(position);
+            }
+        }
+        for node in &mut nodes {
+            node.depends.sort_unstable();
+        }
+        DagSnapshot { nodes }
+    }
+
+    /// Insert a node into the resolved graph without resolving it again and return its
+    /// index, which follows every index in use. Only the new node is checked: its id
+    /// must be new and its dependencies must be in the graph. It cannot close a cycle,
+    /// since no node in the graph can depend on an id that was not there.
+    pub fn insert(&mut self, node: D) -> Result<usize, DagError> {
+        if let Some(id) = node.id()
+            && self.position(id).is_some()
+        {
+            return Err(DagError::DuplicateName(id.to_string()));
+        }
+        let mut dependencies = Vec::new();
+        for dep in node.depends() {
+            let dep = dep.as_ref();
+            let position = self
+                .position(dep)
+                .ok_or_else(|| DagError::UnknownDependency {
+                    dep: dep.to_string(),
+                    by: node.id().map(str::to_string),
+                    suggestions: suggestions(
+                        dep,
+                        self.inner.iter().filter_map(|item| item.inner.id()),
+                    ),
+                })?;
+            dependencies.push(position);
+        }
+        let depth = dependencies
+            .iter()
+            .map(|&position| self.inner[position].depth + 1)
+            .max()
+            .unwrap_or(0);
+        let original_index = self
+            .inner
+            .iter()
+            .map(|item| item.original_index + 1)
+            .max()
+            .unwrap_or(0);
+        // 依存元を持たないので先頭（最後に取り出される位置）に置けば topo 順を保つ。
+        for item in &mut self.inner {
+            for dependent in &mut item.dependents_indexes {
+                *dependent += 1;
+            }
+        }
+        self.inner.insert(
+            0,
+            DagItem {
+                inner: node,
+                original_index,
+                depth,
+                dependents_indexes: Vec::new(),
+            },
+        );
+        for position in dependencies {
+            self.inner[position + 1].dependents_indexes.push(0);
+        }
+        Ok(original_index)
+    }
+
+    /// Remove the node named `id` and return it. Nothing may depend on it any more;
+    /// remove its dependents first. The rest of the graph keeps its order and depths.
+    pub fn remove(&mut self, id: &str) -> Result<D, DagError> {
+        let position = self
+            .position(id)
+            .ok_or_else(|| DagError::UnknownNode(id.to_string()))?;
+        let dependents = &self.inner[position].dependents_indexes;
+        if !dependents.is_empty() {
+            return Err(DagError::StillDepended {
+                id: id.to_string(),
+                dependents: dependents
+                    .iter()
+                    .map(|&i| self.inner[i].inner.id().unwrap_or("<unnamed>").to_string())
+                    .collect(),
+            });
+        }
+        let removed = self.inner.remove(position);
+        for item in &mut self.inner {
+            item.dependents_indexes.retain(|&i| i != position);
+            for dependent in &mut item.dependents_indexes {
+                if *dependent > position {
+                    *dependent -= 1;
+                }
+            }
+        }
+        Ok(removed.inner)
+    }
+
+    /// `id` のノードの `inner` 内の位置。
+    fn position(&self, id: &str) -> Option<usize> {
+        self.inner
+            .iter()
+            .position(|item| item.inner.id() == Some(id))
+    }
+
+    /// Plan an execution in waves of at most `max_parallel` nodes (0 is treated as 1).
+    /// Each wave holds nodes of one depth, so every dependency of a node is in an earlier
+    /// wave. Within a depth, heavier nodes ([`DagNode::weight`]) come first and equal
+    /// weights keep the input order, so long tasks are started as early as possible.
+    /// Nodes are given as their indexes within the original input order.
+    pub fn schedule(&self, max_parallel: usize) -> Vec<Vec<usize>> {
+        let max_parallel = max_parallel.max(1);
+        let mut levels: Vec<Vec<&DagItem<D>>> = Vec::new();
+        for item in &self.inner {
+            if levels.len() <= item.depth {
+                levels.resize_with(item.depth + 1, Vec::new);
+            }
+            levels[item.depth].push(item);
+        }
+        let mut waves = Vec::new();
+        for mut level in levels {
+            level.sort_by_key(|item| (std::cmp::Reverse(item.inner.weight()), item.original_index));
+            waves.extend(
+                level
+                    .chunks(max_parallel)
+                    .map(|wave| wave.iter().map(|item| item.original_index).collect()),
+            );
+        }
+        waves
+    }
+
     /// Iterate with mapping
     pub fn into_map_iter<T, F: FnMut(DagIteratorMapFuncArgs<D>) -> T>(
         self,
@@ -265,6 +500,13 @@ impl<D: DagNode> DagTree<D> {
     }
 }
 
+#[cfg(feature = "serde")]
+impl<D: DagNode> serde::Serialize for DagTree<D> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.snapshot().serialize(serializer)
+    }
+}
+
 impl<D: DagNode> IntoIterator for DagTree<D> {
     type Item = D;
 
@@ -248,3 +248,214 @@ fn map_iter_receives_index_and_depth() {
     assert_eq!(collected["leaf"], (0, 0));
     assert_eq!(collected["root"], (1, 1));
 }
+
+struct WeightedNode {
+    id: &'static str,
+    depends: &'static [&'static str],
+    weight: u64,
+}
+
+impl DagNode for WeightedNode {
+    fn id(&self) -> Option<&str> {
+        Some(self.id)
+    }
+    fn depends(&self) -> impl IntoIterator<Item = &impl AsRef<str>> {
+        self.depends
+    }
+    fn weight(&self) -> u64 {
+        self.weight
+    }
+}
+
+#[test]
+fn schedule_orders_heavy_nodes_first_within_each_level() {
+    let node = |id, depends, weight| WeightedNode {
+        id,
+        depends,
+        weight,
+    };
+    let nodes = vec![
+        node("light", &[], 1),
+        node("heavy", &[], 30),
+        node("medium", &[], 10),
+        node("tie", &[], 10),
+        node("top", &["light", "heavy"], 0),
+    ];
+    let tree = nodes.try_dag().unwrap();
+    // 同じ深さは重い順（同じ重みは入力順）、各 wave は max_parallel 個まで。
+    assert_eq!(tree.schedule(3), [vec![1, 2, 3], vec![0], vec![4]]);
+    assert_eq!(
+        tree.schedule(0),
+        [vec![1], vec![2], vec![3], vec![0], vec![4]]
+    );
+    assert_eq!(tree.schedule(8), [vec![1, 2, 3, 0], vec![4]]);
+}
+
+#[test]
+fn schedule_defaults_to_input_order_without_weights() {
+    let nodes = vec![
+        Node {
+            id: Some("A"),
+            depends: &["B"],
+        },
+        Node {
+            id: Some("B"),
+            depends: &[],
+        },
+        Node {
+            id: None,
+            depends: &[],
+        },
+    ];
+    let tree = nodes.try_dag().unwrap();
+    assert_eq!(tree.schedule(4), [vec![1, 2], vec![0]]);
+}
+
+#[test]
+fn snapshot_lists_nodes_in_topological_order_with_edges() {
+    let nodes = vec![
+        Node {
+            id: Some("A"),
+            depends: &["B", "C"],
+        },
+        Node {
+            id: Some("B"),
+            depends: &["C"],
+        },
+        Node {
+            id: Some("C"),
+            depends: &[],
+        },
+    ];
+    let snapshot = nodes.try_dag().unwrap().snapshot();
+    let ids: Vec<_> = snapshot.nodes.iter().map(|n| n.id.as_deref()).collect();
+    assert_eq!(ids, [Some("C"), Some("B"), Some("A")]);
+    let edges: Vec<_> = snapshot
+        .nodes
+        .iter()
+        .map(|n| (n.index, n.depth, n.depends.clone()))
+        .collect();
+    assert_eq!(edges, [(2, 0, vec![]), (1, 1, vec![0]), (0, 2, vec![0, 1])]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn snapshot_round_trips_through_serde() {
+    let nodes = vec![
+        Node {
+            id: Some("A"),
+            depends: &["B"],
+        },
+        Node {
+            id: Some("B"),
+            depends: &[],
+        },
+    ];
+    let tree = nodes.try_dag().unwrap();
+    let json = serde_json::to_value(&tree).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "nodes": [
+                { "id": "B", "index": 1, "depth": 0, "depends": [] },
+                { "id": "A", "index": 0, "depth": 1, "depends": [0] },
+            ]
+        })
+    );
+    let snapshot: DagSnapshot = serde_json::from_value(json).unwrap();
+    assert_eq!(snapshot, tree.snapshot());
+}
+
+#[test]
+fn nodes_can_be_inserted_and_removed_without_resolving_again() {
+    let nodes = vec![
+        Node {
+            id: Some("A"),
+            depends: &["B"],
+        },
+        Node {
+            id: Some("B"),
+            depends: &[],
+        },
+    ];
+    let mut tree = nodes.try_dag().unwrap();
+    let index = tree
+        .insert(Node {
+            id: Some("C"),
+            depends: &["A", "B"],
+        })
+        .unwrap();
+    assert_eq!(index, 2);
+    let depths: Vec<_> = tree
+        .snapshot()
+        .nodes
+        .iter()
+        .map(|n| (n.id.clone().unwrap(), n.depth, n.depends.len()))
+        .collect();
+    assert_eq!(
+        depths,
+        [
+            ("B".to_string(), 0, 0),
+            ("A".to_string(), 1, 1),
+            ("C".to_string(), 2, 2)
+        ]
+    );
+
+    assert!(matches!(
+        tree.insert(Node {
+            id: Some("C"),
+            depends: &[],
+        }),
+        Err(DagError::DuplicateName(_))
+    ));
+    assert!(matches!(
+        tree.insert(Node {
+            id: Some("D"),
+            depends: &["missing"],
+        }),
+        Err(DagError::UnknownDependency { .. })
+    ));
+    assert!(matches!(
+        tree.remove("A"),
+        Err(DagError::StillDepended { ref dependents, .. }) if dependents == &["C"]
+    ));
+    assert!(matches!(tree.remove("Z"), Err(DagError::UnknownNode(_))));
+
+    assert_eq!(tree.remove("C").unwrap().id, Some("C"));
+    assert_eq!(tree.remove("A").unwrap().id, Some("A"));
+    let remaining: Vec<_> = tree.into_iter().map(|n| n.id).collect();
+    assert_eq!(remaining, [Some("B")]);
+}
+
+#[test]
+fn unknown_dependency_suggests_close_ids() {
+    let nodes = vec![
+        Node {
+            id: Some("plenary.nvim"),
+            depends: &[],
+        },
+        Node {
+            id: Some("telescope.nvim"),
+            depends: &["plenery.nvim"],
+        },
+    ];
+    let err = nodes.try_dag().err().unwrap();
+    assert!(
+        matches!(&err, DagError::UnknownDependency { suggestions, .. } if suggestions == &["plenary.nvim"])
+    );
+    assert_eq!(
+        err.to_string(),
+        "unknown dependency: plenery.nvim (referred by telescope.nvim); did you mean `plenary.nvim`?"
+    );
+
+    // 遠い名前は候補にしない。
+    let nodes = vec![Node {
+        id: Some("A"),
+        depends: &["something-else"],
+    }];
+    let err = nodes.try_dag().err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "unknown dependency: something-else (referred by A)"
+    );
+}
@@ -10,8 +10,23 @@ pub struct FileSpecifier(Vec<FileSpecifierPattern>, String);
 struct FileSpecifierPattern {
     matcher: WildMatch,
     matcher_for_any_depth: Option<WildMatch>,
-    path_only: bool,
-    negated: bool,
+    rule: FileSpecifierRule,
+}
+
+/// パース済みの 1 行分のルール
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSpecifierRule {
+    /// 前後の空白を除いた元の行
+    pub line: String,
+    /// `!`・先頭と末尾の `/` を除いたパターン本体
+    pub body: String,
+    pub negated: bool,
+    /// `/` で始まり、ルート直下にだけマッチする
+    pub anchored: bool,
+    /// `/` を含み、パス全体に対してマッチする（含まなければパスの各要素に対してマッチする）
+    pub path_only: bool,
+    /// `/` で終わり、ディレクトリ配下にだけマッチする
+    pub directory_only: bool,
 }
 
 impl std::fmt::Debug for FileSpecifier {
@@ -21,6 +36,16 @@ impl std::fmt::Debug for FileSpecifier {
 }
 
 impl FileSpecifier {
+    /// パース前の文字列
+    pub fn as_str(&self) -> &str {
+        &self.1
+    }
+
+    /// 記述順のルール。コメントと空行は含まない。
+    pub fn rules(&self) -> impl Iterator<Item = &FileSpecifierRule> {
+        self.0.iter().map(|pat| &pat.rule)
+    }
+
     pub fn matched(&self, filepath: impl AsRef<Path>) -> bool {
         let path = filepath.as_ref().to_string_lossy();
         let path = if path.contains('\\') {
@@ -31,7 +56,7 @@ impl FileSpecifier {
         let mut ignored = false;
 
         for pat in &self.0 {
-            let matches = if pat.path_only {
+            let matches = if pat.rule.path_only {
                 pat.matcher.matches(&path)
                     || pat
                         .matcher_for_any_depth
@@ -42,7 +67,7 @@ impl FileSpecifier {
             };
 
             if matches {
-                ignored = !pat.negated;
+                ignored = !pat.rule.negated;
             }
         }
 
@@ -54,8 +79,9 @@ impl FromStr for FileSpecifier {
     type Err = Infallible;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut patterns = Vec::new();
-        for line in s.lines() {
-            let mut line = line.trim();
+        for original in s.lines() {
+            let original = original.trim();
+            let mut line = original;
             if line.is_empty() || line.starts_with('#') {
                 continue;
             }
@@ -97,8 +123,14 @@ impl FromStr for FileSpecifier {
             patterns.push(FileSpecifierPattern {
                 matcher: WildMatch::new(&body),
                 matcher_for_any_depth,
-                path_only,
-                negated,
+                rule: FileSpecifierRule {
+                    line: original.to_string(),
+                    body: line.to_string(),
+                    negated,
+                    anchored: anchored_to_root,
+                    path_only,
+                    directory_only,
+                },
             });
         }
         Ok(FileSpecifier(patterns, s.to_string()))
@@ -136,6 +168,19 @@ mod tests {
         assert!(!spec.matched(Path::new("README.md")));
     }
 
+    #[test]
+    fn file_specifier_exposes_parsed_rules() {
+        let spec = FileSpecifier::from_str("# comment\n!/doc/\nREADME.md").expect("must parse");
+        let rules: Vec<_> = spec.rules().collect();
+
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].line, "!/doc/");
+        assert_eq!(rules[0].body, "doc");
+        assert!(rules[0].negated && rules[0].anchored && rules[0].directory_only);
+        assert_eq!(rules[1].body, "README.md");
+        assert!(!rules[1].path_only);
+    }
+
     #[test]
     fn file_specifier_supports_root_anchored_path() {
         let spec = FileSpecifier::from_str("/doc/*.txt").expect("must parse");
@@ -0,0 +1,355 @@
+//! Combined changelog of pending updates (`rsplug changelog`).
+//!
+//! For every repository plugin the update range runs from the revision in
+//! the lockfile to the head its `repo` revision currently resolves to. Plugins
+//! that are up to date, pinned to a commit, or not installed are skipped. The
+//! range's commits are fetched into the repository cache, deepened only as far
+//! as `--max-commits` needs, and their subjects are listed per plugin. For
+//! GitHub repositories, release notes of tags that point into the range are
+//! included above the commits. The result is Markdown, written to standard
+//! output or to `--output`.
+
+use std::{collections::HashSet, fmt::Write as _, path::Path, sync::Arc};
+
+use git2::Oid;
+use rsplug::plugin::RepoSource;
+use rsplug::util::git;
+use rsplug::util::github::{self, Release};
+use tokio::task::JoinSet;
+
+use super::*;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct ChangelogArgs {
+    /// Write the Markdown to this file instead of standard output
+    #[arg(long, short)]
+    pub(crate) output: Option<PathBuf>,
+    /// List at most this many commits per plugin
+    #[arg(long, default_value_t = 50, value_parser = clap::value_parser!(u32).range(1..))]
+    pub(crate) max_commits: u32,
+}
+
+/// 1 回の取得で読む release の件数。範囲外の release も混ざるので多めに取る。
+const RELEASES_PER_PAGE: usize = 30;
+
+/// 1 プラグイン分の更新範囲と、その中の commit・release。
+#[derive(Debug, Default)]
+struct Section {
+    name: String,
+    from: Option<Oid>,
+    to: Option<Oid>,
+    /// 新しい順の (id, 件名)。
+    commits: Vec<(Oid, String)>,
+    /// `--max-commits` で打ち切ったか。
+    truncated: bool,
+    releases: Vec<Release>,
+    /// 範囲を取得できなかった理由。
+    error: Option<String>,
+}
+
+/// GitHub HTTPS のリモートにだけ送る token。
+fn token(repo: &RepoSource) -> Option<Arc<str>> {
+    repo.is_github_https()
+        .then(github::token)
+        .flatten()
+        .map(Arc::<str>::from)
+}
+
+/// `installed` から現在の head までの commit と release を集める。更新が無ければ `None`。
+async fn collect(
+    source_git: PathBuf,
+    name: String,
+    repo: RepoSource,
+    installed: Oid,
+    max_commits: usize,
+) -> Option<Section> {
+    let url = Arc::<str>::from(repo.url());
+    let mut section = Section {
+        name,
+        from: Some(installed),
+        ..Default::default()
+    };
+    let head = match git::ls_remote(Arc::clone(&url), repo.rev(), token(&repo)).await {
+        Ok(head) if head == installed => return None,
+        Ok(head) => head,
+        Err(e) => {
+            section.error = Some(format!("could not resolve the latest revision ({e})"));
+            return Some(section);
+        }
+    };
+    section.to = Some(head);
+    // 打ち切りを判定するため 1 件多く読む。depth も同じだけあれば足りる。
+    let limit = max_commits + 1;
+    let commits = async {
+        let cache = git::open_source(&source_git).await?;
+        cache
+            .deepen(Arc::clone(&url), head, limit as u32, token(&repo))
+            .await?;
+        cache.log(installed, head, limit).await
+    }
+    .await;
+    let mut commits = match commits {
+        Ok(commits) => commits,
+        Err(e) => {
+            section.error = Some(format!("could not read the commits ({e})"));
+            return Some(section);
+        }
+    };
+    section.releases = releases(&repo, &url, &commits).await;
+    section.truncated = commits.len() > max_commits;
+    commits.truncate(max_commits);
+    section.commits = commits;
+    Some(section)
+}
+
+/// GitHub の release のうち、タグが `commits` のどれかを指すもの。取得に失敗したら空。
+async fn releases(repo: &RepoSource, url: &Arc<str>, commits: &[(Oid, String)]) -> Vec<Release> {
+    let (owner, name) = match repo {
+        RepoSource::GitHub { owner, repo, .. } => (owner.clone(), repo.to_string()),
+        RepoSource::Git { url, .. } => match github::parse_github_url(url) {
+            Some(parsed) => parsed,
+            None => return Vec::new(),
+        },
+    };
+    let client = reqwest::Client::new();
+    let fetched = github::releases(&client, &owner, &name, RELEASES_PER_PAGE, github::token());
+    let (Ok(releases), Ok(tags)) =
+        tokio::join!(fetched, git::ls_remote_tags(Arc::clone(url), token(repo)))
+    else {
+        return Vec::new();
+    };
+    in_range(releases, &tags, commits)
+}
+
+/// タグの指す commit が範囲内にある release だけを残す。
+fn in_range(
+    releases: Vec<Release>,
+    tags: &[(String, Oid)],
+    commits: &[(Oid, String)],
+) -> Vec<Release> {
+    let range: HashSet<Oid> = commits.iter().map(|(id, _)| *id).collect();
+    let tagged: HashSet<&str> = tags
+        .iter()
+        .filter(|(_, id)| range.contains(id))
+        .map(|(tag, _)| tag.as_str())
+        .collect();
+    releases
+        .into_iter()
+        .filter(|release| tagged.contains(release.tag.as_str()))
+        .collect()
+}
+
+/// 表示用の短い commit hash。
+fn short(oid: Oid) -> String {
+    oid.to_string()[..7].to_string()
+}
+
+/// プラグインごとの節にまとめた Markdown。
+fn render(sections: &[Section]) -> String {
+    let mut out = String::from("# Changelog\n");
+    if sections.is_empty() {
+        out.push_str("\nEverything is up to date.\n");
+    }
+    for section in sections {
+        let range = match (section.from, section.to) {
+            (Some(from), Some(to)) => format!(" ({}..{})", short(from), short(to)),
+            _ => String::new(),
+        };
+        let _ = writeln!(out, "\n## {}{range}", section.name);
+        if let Some(error) = &section.error {
+            let _ = writeln!(out, "\n{error}");
+            continue;
+        }
+        for release in &section.releases {
+            let title = match &release.name {
+                Some(name) if *name != release.tag => format!("{} — {name}", release.tag),
+                _ => release.tag.clone(),
+            };
+            let _ = writeln!(out, "\n### {title}");
+            if let Some(body) = &release.body {
+                let _ = writeln!(out, "\n{body}");
+            }
+        }
+        if !section.releases.is_empty() {
+            out.push_str("\n### Commits\n");
+        }
+        out.push('\n');
+        for (id, summary) in &section.commits {
+            let _ = writeln!(out, "- `{}` {summary}", short(*id));
+        }
+        if section.truncated {
+            out.push_str("- …\n");
+        }
+    }
+    out
+}
+
+/// `rsplug changelog`: lockfile の rev から最新までの変更をプラグインごとに出力する。
+pub(crate) async fn print_changelog(
+    repo_cache_dir: &Path,
+    lockfile: &Path,
+    config_files: Vec<String>,
+    args: &ChangelogArgs,
+) -> Result<(), Error> {
+    let locked = match rsplug::LockFile::read(lockfile).await {
+        Ok(lock) => lock.normalize_keys()?.locked,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let mut tasks = JoinSet::new();
+    let mut index = 0;
+    for (_, config) in read_configs(config_files).await? {
+        for plugin in &config.plugins {
+            let Some(repo) = plugin.cache.repo.clone() else {
+                continue;
+            };
+            // dev checkout と commit 固定は更新されない。
+            if plugin.cache.dev
+                || repo
+                    .rev()
+                    .as_deref()
+                    .is_some_and(rsplug::util::github::is_full_hex_hash)
+            {
+                continue;
+            }
+            let Some(installed) = locked
+                .get(&repo.canonical())
+                .and_then(|entry| Oid::from_str(&entry.rev).ok())
+            else {
+                continue;
+            };
+            let name = plugin.dep_name().unwrap_or(repo.basename()).to_string();
+            let source_git = repo_cache_dir
+                .join(repo.default_cachedir())
+                .join("source.git");
+            let max_commits = args.max_commits as usize;
+            tasks.spawn(async move {
+                (
+                    index,
+                    collect(source_git, name, repo, installed, max_commits).await,
+                )
+            });
+            index += 1;
+        }
+    }
+    let mut sections = BTreeMap::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (index, section) = joined.map_err(task::panicked("changelog"))?;
+        if let Some(section) = section {
+            sections.insert(index, section);
+        }
+    }
+    let content = render(&sections.into_values().collect::<Vec<_>>());
+    match &args.output {
+        Some(path) => tokio::fs::write(path, content).await?,
+        None => {
+            use std::io::Write;
+            std::io::stdout().write_all(content.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oid(digit: char) -> Oid {
+        Oid::from_str(&digit.to_string().repeat(40)).unwrap()
+    }
+
+    fn release(tag: &str, name: Option<&str>, body: Option<&str>) -> Release {
+        Release {
+            tag: tag.into(),
+            name: name.map(Into::into),
+            body: body.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn only_releases_tagged_inside_the_range_are_kept() {
+        let commits = [(oid('2'), "b".to_string()), (oid('3'), "c".to_string())];
+        let tags = [
+            ("v1.0".to_string(), oid('1')),
+            ("v1.1".to_string(), oid('3')),
+        ];
+        let releases = vec![
+            release("v1.2", None, None),
+            release("v1.1", None, None),
+            release("v1.0", None, None),
+        ];
+        assert_eq!(
+            in_range(releases, &tags, &commits),
+            [release("v1.1", None, None)]
+        );
+    }
+
+    #[test]
+    fn renders_releases_above_commits_per_plugin() {
+        let sections = [
+            Section {
+                name: "foo.nvim".into(),
+                from: Some(oid('1')),
+                to: Some(oid('3')),
+                commits: vec![(oid('3'), "Fix b".into()), (oid('2'), "Add a".into())],
+                truncated: true,
+                releases: vec![release("v2.0", Some("Big one"), Some("- notes"))],
+                error: None,
+            },
+            Section {
+                name: "bar.nvim".into(),
+                from: Some(oid('4')),
+                error: Some("could not resolve the latest revision (offline)".into()),
+                ..Default::default()
+            },
+        ];
+        assert_eq!(
+            render(&sections),
+            "# Changelog\n\
+             \n## foo.nvim (1111111..3333333)\n\
+             \n### v2.0 — Big one\n\
+             \n- notes\n\
+             \n### Commits\n\
+             \n- `3333333` Fix b\n\
+             - `2222222` Add a\n\
+             - …\n\
+             \n## bar.nvim\n\
+             \ncould not resolve the latest revision (offline)\n"
+        );
+        assert_eq!(render(&[]), "# Changelog\n\nEverything is up to date.\n");
+    }
+
+    #[tokio::test]
+    async fn log_lists_the_commits_after_the_installed_revision() {
+        use std::process::Command;
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = tmp.path().join("source.git");
+        let run = |args: &[&str]| {
+            let out = Command::new("git")
+                .current_dir(&repo)
+                .args(["-c", "user.email=t@t", "-c", "user.name=t"])
+                .args(["-c", "commit.gpgsign=false"])
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(out.status.success(), "git {args:?} failed");
+            String::from_utf8(out.stdout).unwrap().trim().to_string()
+        };
+        std::fs::create_dir_all(&repo).unwrap();
+        run(&["init", "-q", "--bare"]);
+        let empty = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+        let base = run(&["commit-tree", empty, "-m", "base"]);
+        let one = run(&["commit-tree", empty, "-p", &base, "-m", "one\n\nbody"]);
+        let two = run(&["commit-tree", empty, "-p", &one, "-m", "two"]);
+        let id = |s: &str| Oid::from_str(s).unwrap();
+
+        let cache = git::open_source(&repo).await.unwrap();
+        let log = cache.log(id(&base), id(&two), 10).await.unwrap();
+        assert_eq!(
+            log,
+            [(id(&two), "two".to_string()), (id(&one), "one".to_string())]
+        );
+        assert_eq!(cache.log(id(&base), id(&two), 1).await.unwrap().len(), 1);
+        assert!(cache.log(id(&two), id(&two), 10).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,289 @@
+//! Removing cached repositories that the configuration no longer uses (`rsplug clean`).
+//!
+//! A repository is cached below `repos/<canonical>/`: its `source.git`, the
+//! snapshots under `worktrees/`, and `latest-snapshot`. Removing a plugin from
+//! the config files leaves that cache behind. `rsplug clean` keeps the cache of
+//! every `repo` and `upstream` named in the config files, whatever their `when`
+//! or `compat`, and of every repository in the lockfile, whose snapshots the
+//! installed pack may still use until the next run. Every other cached
+//! repository is removed. `rsplug sync` does the same after its run.
+//!
+//! `rsplug clean` also removes the packages below `pack/_gen/` that the
+//! published generation does not use. A publication keeps the packages of a
+//! few older generations and bounds its own cleanup; `rsplug clean` drops
+//! those generations too and checks every package.
+//!
+//! An interrupted run can leave the staging directory of a checkout or a
+//! tarball extraction (`worktrees/.rsplug-checkout-*`,
+//! `worktrees/.rsplug-tarball-*`) behind. `rsplug clean` removes those that are
+//! older than an hour, so that the staging of a run in progress is kept.
+//!
+//! The removed repositories are printed one per line, relative to `repos/`,
+//! followed by the removed stagings and packages as full paths. With
+//! `--dry-run` they are printed and kept.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use super::*;
+
+#[derive(clap::Args, Debug, Default)]
+pub(crate) struct CleanArgs {
+    /// Print the cached repositories that would be removed, removing nothing
+    #[arg(long)]
+    pub(crate) dry_run: bool,
+}
+
+/// 中にあれば repo の cache とみなすエントリ（`rsplug du` の帰属と同じ）。
+const REPOSITORY_ENTRIES: &[&str] = &["source.git", "worktrees", "latest-snapshot"];
+/// checkout・tarball 展開の staging の接頭辞（`worktrees/` の中に作る）。
+const STAGING_PREFIXES: &[&str] = &[".rsplug-checkout-", ".rsplug-tarball-"];
+/// これより古い staging は、実行中の同期のものではなく中断で残ったものとみなす。
+pub(crate) const STALE_STAGING: Duration = Duration::from_secs(60 * 60);
+
+/// 残す repo の cache ディレクトリ（`repos` 相対）。設定中の `repo`・`upstream` と lock の key。
+pub(crate) async fn kept_cachedirs(
+    lockfile: &Path,
+    config_files: Vec<String>,
+) -> Result<BTreeSet<PathBuf>, Error> {
+    let mut kept = BTreeSet::new();
+    for (_, config) in read_configs(config_files).await? {
+        for plugin in &config.plugins {
+            let repos = plugin.cache.repo.iter().chain(&plugin.cache.upstream);
+            kept.extend(repos.map(rsplug::plugin::RepoSource::default_cachedir));
+        }
+    }
+    match rsplug::LockFile::read(lockfile).await {
+        Ok(lock) => kept.extend(
+            lock.normalize_keys()?
+                .locked
+                .keys()
+                .map(|canonical| canonical.split('/').collect::<PathBuf>()),
+        ),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    Ok(kept)
+}
+
+/// `repo_cache_dir` の中の repo の cache を `repos` 相対で集める。repo の cache の中へは降りない。
+async fn cached_repositories(repo_cache_dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        let mut entries = match tokio::fs::read_dir(repo_cache_dir.join(&relative)).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        let mut children = Vec::new();
+        let mut is_repository = false;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            if REPOSITORY_ENTRIES.iter().any(|entry| name == *entry) {
+                is_repository = true;
+            } else if entry.file_type().await?.is_dir() {
+                children.push(relative.join(name));
+            }
+        }
+        if is_repository && !relative.as_os_str().is_empty() {
+            found.push(relative);
+        } else {
+            pending.extend(children);
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// 使われていない repo の cache を消し（`dry_run` なら挙げるだけ）、`repos` 相対のパスを返す。
+pub(crate) async fn clean(
+    repo_cache_dir: &Path,
+    lockfile: &Path,
+    config_files: Vec<String>,
+    args: &CleanArgs,
+) -> Result<Vec<PathBuf>, Error> {
+    // 設定が無いと全部が「使われていない」扱いになる。
+    if config_files.is_empty() {
+        return Err(Error::CleanNoConfigFiles);
+    }
+    let kept = kept_cachedirs(lockfile, config_files).await?;
+    let unused: Vec<PathBuf> = cached_repositories(repo_cache_dir)
+        .await?
+        .into_iter()
+        .filter(|repository| !kept.contains(repository))
+        .collect();
+    if args.dry_run {
+        return Ok(unused);
+    }
+    for repository in &unused {
+        tokio::fs::remove_dir_all(repo_cache_dir.join(repository)).await?;
+        // 空になった host・owner のディレクトリも消す。中身が残っていれば remove_dir が失敗する。
+        for parent in repository.ancestors().skip(1) {
+            if parent.as_os_str().is_empty()
+                || tokio::fs::remove_dir(repo_cache_dir.join(parent))
+                    .await
+                    .is_err()
+            {
+                break;
+            }
+        }
+    }
+    Ok(unused)
+}
+
+/// 中断で残った checkout・tarball の staging のうち `stale_after` より古いものを消し
+/// （`dry_run` なら挙げるだけ）、そのパスを返す。
+pub(crate) async fn clean_stagings(
+    repo_cache_dir: &Path,
+    args: &CleanArgs,
+    stale_after: Duration,
+) -> std::io::Result<Vec<PathBuf>> {
+    let now = SystemTime::now();
+    let mut stale = Vec::new();
+    for repository in cached_repositories(repo_cache_dir).await? {
+        let worktrees = repo_cache_dir.join(repository).join("worktrees");
+        let mut entries = match tokio::fs::read_dir(&worktrees).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if !STAGING_PREFIXES
+                .iter()
+                .any(|prefix| name.starts_with(prefix))
+            {
+                continue;
+            }
+            let modified = entry.metadata().await?.modified()?;
+            if now.duration_since(modified).unwrap_or_default() >= stale_after {
+                stale.push(entry.path());
+            }
+        }
+    }
+    stale.sort();
+    if !args.dry_run {
+        for staging in &stale {
+            tokio::fs::remove_dir_all(staging).await?;
+        }
+    }
+    Ok(stale)
+}
+
+/// 各 packpath の `pack/_gen` から公開中の世代が使わないパッケージを消し（`dry_run` なら
+/// 挙げるだけ）、そのパスを返す。
+pub(crate) async fn prune_packpaths(
+    packpaths: &[&Path],
+    args: &CleanArgs,
+) -> Result<Vec<PathBuf>, Error> {
+    let mut pruned = Vec::new();
+    for packpath in packpaths {
+        let packages = rsplug::pack_plan::prune_packages(packpath, args.dry_run).await?;
+        pruned.extend(packages.into_iter().map(|package| packpath.join(package)));
+    }
+    Ok(pruned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn clean_keeps_configured_and_locked_repositories() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repos = tmp.path().join("repos");
+        for (repository, entry) in [
+            ("github.com/o/configured", "source.git"),
+            ("github.com/o/locked", "worktrees"),
+            ("github.com/o/removed", "latest-snapshot"),
+            ("gitlab.com/p/gone", "source.git"),
+        ] {
+            std::fs::create_dir_all(repos.join(repository).join(entry)).unwrap();
+        }
+        // repo の cache でないもの（`.rsplugignore` だけの置き場）は消さない。
+        std::fs::create_dir_all(repos.join("github.com/o/ignore-only")).unwrap();
+        std::fs::write(repos.join("github.com/o/ignore-only/.rsplugignore"), "").unwrap();
+        let config = tmp.path().join("plugins.toml");
+        std::fs::write(&config, "[[plugins]]\nrepo = \"o/configured\"\n").unwrap();
+        let lockfile = tmp.path().join("rsplug.lock.json");
+        std::fs::write(
+            &lockfile,
+            r#"{"version":"2","locked":{"github.com/o/locked":{"type":"git","rev":"0123456789abcdef0123456789abcdef01234567"}}}"#,
+        )
+        .unwrap();
+        let config_files = vec![config.to_string_lossy().into_owned()];
+
+        let dry_run = CleanArgs { dry_run: true };
+        assert!(matches!(
+            clean(&repos, &lockfile, Vec::new(), &dry_run).await,
+            Err(Error::CleanNoConfigFiles)
+        ));
+        let planned = clean(&repos, &lockfile, config_files.clone(), &dry_run).await;
+        assert_eq!(
+            planned.unwrap(),
+            [
+                PathBuf::from("github.com/o/removed"),
+                PathBuf::from("gitlab.com/p/gone"),
+            ]
+        );
+        assert!(repos.join("github.com/o/removed").exists());
+
+        let removed = clean(&repos, &lockfile, config_files, &CleanArgs::default()).await;
+        assert_eq!(removed.unwrap().len(), 2);
+        assert_eq!(
+            cached_repositories(&repos).await.unwrap(),
+            [
+                PathBuf::from("github.com/o/configured"),
+                PathBuf::from("github.com/o/locked"),
+            ]
+        );
+        assert!(
+            repos
+                .join("github.com/o/ignore-only/.rsplugignore")
+                .exists()
+        );
+        assert!(!repos.join("gitlab.com").exists());
+    }
+
+    #[tokio::test]
+    async fn clean_stagings_removes_interrupted_checkouts() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repos = tmp.path().join("repos");
+        let worktrees = repos.join("github.com/o/r/worktrees");
+        for dir in [
+            ".rsplug-checkout-a1/tree",
+            ".rsplug-tarball-b2/extract",
+            "0123abcd",
+        ] {
+            std::fs::create_dir_all(worktrees.join(dir)).unwrap();
+        }
+
+        // 実行中の同期の staging かもしれない新しいものは残す。
+        let fresh = clean_stagings(&repos, &CleanArgs::default(), STALE_STAGING).await;
+        assert!(fresh.unwrap().is_empty());
+
+        let dry_run = CleanArgs { dry_run: true };
+        let planned = clean_stagings(&repos, &dry_run, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(
+            planned,
+            [
+                worktrees.join(".rsplug-checkout-a1"),
+                worktrees.join(".rsplug-tarball-b2"),
+            ]
+        );
+        assert!(worktrees.join(".rsplug-checkout-a1").exists());
+
+        let removed = clean_stagings(&repos, &CleanArgs::default(), Duration::ZERO).await;
+        assert_eq!(removed.unwrap(), planned);
+        assert!(!worktrees.join(".rsplug-checkout-a1").exists());
+        assert!(!worktrees.join(".rsplug-tarball-b2").exists());
+        assert!(worktrees.join("0123abcd").exists());
+    }
+}
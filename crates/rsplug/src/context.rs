@@ -0,0 +1,218 @@
+//! Per-process context of the command line (`AppContext`).
+//!
+//! The directories rsplug reads and writes, the logger for the messages of a
+//! run, and the clock used for timestamps and cache ages are resolved once in
+//! `main` and handed to the commands, instead of being read from statics. A
+//! test builds its own context below a temporary directory with a pinned clock,
+//! so tests touching these paths can run in parallel.
+
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::log::Logger;
+
+use super::*;
+
+/// 1 回の実行で使う置き場所・logger・時計。
+#[derive(Clone)]
+pub(crate) struct AppContext {
+    /// pack・lockfile・provenance index・メタデータのキャッシュを置く場所（`~/.cache/rsplug`）。
+    pub(crate) app_dir: PathBuf,
+    /// pack と `init.lua` を公開する packpath。既定は `app_dir`、`--packpath user=<DIR>` で変わる。
+    pub(crate) packpath: PathBuf,
+    /// repo キャッシュ（`<app_dir>/repos`）。
+    pub(crate) repo_cache_dir: PathBuf,
+    /// 組み込みの Lua ランタイムを差し替えるテンプレートの置き場所（`~/.config/rsplug/templates`）。
+    pub(crate) template_dir: PathBuf,
+    /// `dev = true` のプラグインの checkout の既定の親（`~/projects`）。
+    pub(crate) dev_dir: PathBuf,
+    /// 実行そのものの進捗・結果の送り先。ライブラリ側は `log::msg` で送り、本番では同じ logger に届く。
+    pub(crate) logger: Logger,
+    pub(crate) clock: Clock,
+}
+
+impl AppContext {
+    /// ホームディレクトリ以下の既定の置き場所を使う。
+    pub(crate) fn from_home(logger: Logger) -> Result<Self, Error> {
+        let home = std::env::home_dir().filter(|home| !home.as_os_str().is_empty());
+        Self::from_dirs(home, |var| std::env::var_os(var).map(PathBuf::from), logger)
+    }
+
+    /// `home` 以下の既定の置き場所を使う。ホームの無い環境（scratch コンテナ等）では
+    /// `$XDG_CACHE_HOME`・`$XDG_CONFIG_HOME` を `~/.cache`・`~/.config` の代わりにし、
+    /// どちらも無ければエラーにする。
+    fn from_dirs(
+        home: Option<PathBuf>,
+        env: impl Fn(&str) -> Option<PathBuf>,
+        logger: Logger,
+    ) -> Result<Self, Error> {
+        let xdg = |var| env(var).filter(|dir| dir.is_absolute());
+        let (cache, config, dev_dir) = match home {
+            Some(home) => (
+                home.join(".cache"),
+                home.join(".config"),
+                home.join("projects"),
+            ),
+            None => {
+                let cache = xdg("XDG_CACHE_HOME").ok_or(Error::NoHomeDirectory)?;
+                let config = xdg("XDG_CONFIG_HOME").unwrap_or_else(|| cache.clone());
+                let dev_dir = cache.join("rsplug").join("projects");
+                (cache, config, dev_dir)
+            }
+        };
+        let app_dir = cache.join("rsplug");
+        Ok(Self {
+            repo_cache_dir: app_dir.join("repos"),
+            packpath: app_dir.clone(),
+            app_dir,
+            template_dir: config.join("rsplug").join("templates"),
+            dev_dir,
+            logger,
+            clock: Clock::system(),
+        })
+    }
+
+    /// すべての置き場所を `root` 以下に取る（テスト用）。
+    #[cfg(test)]
+    pub(crate) fn under(root: &Path, logger: Logger) -> Self {
+        let app_dir = root.join("cache");
+        Self {
+            repo_cache_dir: app_dir.join("repos"),
+            packpath: app_dir.clone(),
+            app_dir,
+            template_dir: root.join("templates"),
+            dev_dir: root.join("projects"),
+            logger,
+            clock: Clock::system(),
+        }
+    }
+
+    /// pack を `app_dir` ではなく `packpath` に公開する。
+    pub(crate) fn with_packpath(mut self, packpath: PathBuf) -> Self {
+        self.packpath = packpath;
+        self
+    }
+
+    #[cfg(test)]
+    pub(crate) fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// `--lockfile` が無いときの lockfile。
+    pub(crate) fn default_lockfile(&self) -> PathBuf {
+        self.app_dir.join("rsplug.lock.json")
+    }
+
+    /// 同期で使う packpath。
+    pub(crate) fn packpath(&self) -> &Path {
+        &self.packpath
+    }
+}
+
+/// 現在時刻の取り出し口。テストでは固定して、時刻に依存する出力を再現可能にする。
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Clock {
+    fixed: Option<SystemTime>,
+}
+
+impl Clock {
+    pub(crate) fn system() -> Self {
+        Self { fixed: None }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn fixed(at: SystemTime) -> Self {
+        Self { fixed: Some(at) }
+    }
+
+    pub(crate) fn now(self) -> SystemTime {
+        self.fixed.unwrap_or_else(SystemTime::now)
+    }
+
+    /// Unix epoch からの秒数。epoch より前なら 0。
+    pub(crate) fn unix_secs(self) -> u64 {
+        self.now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use indicatif::ProgressDrawTarget;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn contexts_under_different_roots_share_nothing() {
+        let logger = Logger::spawn(ProgressDrawTarget::hidden());
+        let a = tempfile::tempdir().unwrap();
+        let b = tempfile::tempdir().unwrap();
+        let at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let ctx_a = AppContext::under(a.path(), logger.clone()).with_clock(Clock::fixed(at));
+        let ctx_b = AppContext::under(b.path(), logger);
+
+        for path in [
+            ctx_a.packpath(),
+            ctx_a.repo_cache_dir.as_path(),
+            ctx_a.template_dir.as_path(),
+            ctx_a.dev_dir.as_path(),
+        ] {
+            assert!(path.starts_with(a.path()), "{}", path.display());
+        }
+        assert!(ctx_a.repo_cache_dir.starts_with(&ctx_a.app_dir));
+        assert_ne!(ctx_a.default_lockfile(), ctx_b.default_lockfile());
+        assert_eq!(ctx_a.clock.unix_secs(), 1_700_000_000);
+        assert_eq!(ctx_a.clock.now(), at);
+    }
+
+    #[tokio::test]
+    async fn without_a_home_the_xdg_directories_are_used() {
+        let logger = Logger::spawn(ProgressDrawTarget::hidden());
+        let root = tempfile::tempdir().unwrap();
+        let (cache, config) = (root.path().join("cache"), root.path().join("config"));
+        let env = |vars: Vec<(&'static str, PathBuf)>| {
+            move |var: &str| {
+                vars.iter()
+                    .find(|(name, _)| *name == var)
+                    .map(|(_, value)| value.clone())
+            }
+        };
+
+        let ctx = AppContext::from_dirs(
+            None,
+            env(vec![
+                ("XDG_CACHE_HOME", cache.clone()),
+                ("XDG_CONFIG_HOME", config.clone()),
+            ]),
+            logger.clone(),
+        )
+        .unwrap();
+        assert_eq!(ctx.app_dir, cache.join("rsplug"));
+        assert_eq!(ctx.template_dir, config.join("rsplug").join("templates"));
+        assert_eq!(ctx.dev_dir, cache.join("rsplug").join("projects"));
+
+        // ホームがあれば XDG は見ない（既存の `~/.cache/rsplug` を動かさない）。
+        let home = root.path().join("home");
+        let ctx = AppContext::from_dirs(
+            Some(home.clone()),
+            env(vec![("XDG_CACHE_HOME", cache)]),
+            logger.clone(),
+        )
+        .unwrap();
+        assert_eq!(ctx.app_dir, home.join(".cache").join("rsplug"));
+
+        // 相対パスの XDG は使わない。
+        let relative = AppContext::from_dirs(
+            None,
+            env(vec![("XDG_CACHE_HOME", PathBuf::from("cache"))]),
+            logger,
+        );
+        assert!(matches!(relative, Err(Error::NoHomeDirectory)));
+    }
+}
@@ -0,0 +1,740 @@
+//! Long-running sync server (`rsplug daemon`).
+//!
+//! The daemon listens on a Unix socket below the application directory and
+//! answers newline-delimited JSON requests: `{"command":"install"}`,
+//! `{"command":"update"}`, or `{"command":"status"}`, each with one JSON line.
+//! Syncs run inside the daemon, one at a time, with the options it was started
+//! with. A sync request may add `"reload":"<server>"` to reload the loader in
+//! that Neovim afterwards, as `--reload` does, and `"progress":true` to
+//! receive the sync's progress events (see `progress.rs`) as lines tagged
+//! `"event":"progress"` before the reply. The HTTP client keeps its pooled connections between syncs, and config
+//! files whose contents did not change are not parsed again; nothing else is
+//! kept warm, so every sync walks the config globs and opens the Git
+//! repositories again. A request line is limited to [`MAX_REQUEST_BYTES`], at
+//! most [`MAX_QUEUED_SYNCS`] syncs wait for their turn, and a sync that panics
+//! is answered as a failed sync. `--send` is a small client for the same
+//! protocol.
+
+use std::{
+    any::Any,
+    panic::AssertUnwindSafe,
+    path::Path,
+    sync::Mutex,
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::*;
+use crate::progress::ProgressEvent;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct DaemonArgs {
+    /// Socket path [default: ~/.cache/rsplug/daemon.sock]
+    #[arg(long)]
+    pub(crate) socket: Option<PathBuf>,
+    /// Send one request to the running daemon, print its reply, and exit
+    #[arg(long, value_enum)]
+    pub(crate) send: Option<DaemonCommand>,
+    /// With --send, also print the sync's progress events before the reply
+    #[arg(long, requires = "send")]
+    pub(crate) progress: bool,
+}
+
+#[derive(clap::ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DaemonCommand {
+    Install,
+    Update,
+    Status,
+}
+
+impl DaemonCommand {
+    fn as_str(self) -> &'static str {
+        match self {
+            DaemonCommand::Install => "install",
+            DaemonCommand::Update => "update",
+            DaemonCommand::Status => "status",
+        }
+    }
+}
+
+/// 1 行の要求の上限。超えた接続には誤りを返して閉じる。
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+/// 順番待ちできる同期の数。超えた要求にはその場で誤りを返す。
+const MAX_QUEUED_SYNCS: usize = 8;
+
+/// 1 行分の要求。
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Request {
+    command: DaemonCommand,
+    /// 同期の後にローダを読み込み直させる Neovim のアドレス（`--reload`）。
+    #[serde(default)]
+    reload: Option<String>,
+    /// 応答の前に同期の進捗イベントを流す。
+    #[serde(default)]
+    progress: bool,
+}
+
+/// daemon 起動時の CLI オプション。要求ごとの同期はすべてこれで行う。
+pub(crate) struct SyncOptions {
+    pub(crate) force: bool,
+    pub(crate) locked: bool,
+    pub(crate) offline: bool,
+    pub(crate) lockfile: PathBuf,
+    pub(crate) dev_path: Option<PathBuf>,
+    pub(crate) pack: PackOptions,
+    pub(crate) config_files: Vec<String>,
+}
+
+impl SyncOptions {
+    /// 要求に対応する実行モード。`--locked`・`--offline` で起動した daemon は update を受けない。
+    fn mode(&self, command: DaemonCommand) -> Result<RunMode, String> {
+        let update = command == DaemonCommand::Update;
+        if update && (self.locked || self.offline) {
+            let flag = if self.locked { "--locked" } else { "--offline" };
+            return Err(format!(
+                "update is not available: the daemon was started with {flag}"
+            ));
+        }
+        Ok(RunMode::from_flags(
+            !update,
+            update,
+            self.locked,
+            self.offline,
+        ))
+    }
+}
+
+/// 直近の同期の結果。
+struct LastSync {
+    command: DaemonCommand,
+    error: Option<String>,
+    duration: Duration,
+}
+
+/// `status` で返す daemon の状態。
+#[derive(Default)]
+struct DaemonState {
+    /// 実行中の同期。
+    busy: Option<DaemonCommand>,
+    /// 完了した同期の数（失敗を含む）。
+    syncs: usize,
+    last: Option<LastSync>,
+}
+
+impl DaemonState {
+    fn status(&self, cached_configs: usize) -> Value {
+        let last = self.last.as_ref().map(|last| {
+            json!({
+                "command": last.command.as_str(),
+                "ok": last.error.is_none(),
+                "error": last.error,
+                "duration_ms": last.duration.as_millis() as u64,
+            })
+        });
+        json!({
+            "ok": true,
+            "pid": std::process::id(),
+            "busy": self.busy.map(DaemonCommand::as_str),
+            "syncs": self.syncs,
+            "cached_configs": cached_configs,
+            "last": last,
+        })
+    }
+}
+
+fn error_reply(message: impl Into<String>) -> Value {
+    json!({ "ok": false, "error": message.into() })
+}
+
+/// 同期の要求。worker が処理し、`reply` へ応答を返す。
+struct Job {
+    command: DaemonCommand,
+    reload: Option<String>,
+    /// 進捗イベントの送り先。要求が `progress` を求めたときだけ持つ。
+    progress: Option<tokio::sync::mpsc::UnboundedSender<Value>>,
+    reply: tokio::sync::oneshot::Sender<Value>,
+}
+
+/// 1 行の要求を処理する。`status` はその場で答え、同期は worker へ回して完了を待つ。
+/// 要求が `progress` を求めれば、同期の進捗イベントを応答より先に `progress` へ送る。
+async fn handle_line(
+    line: &str,
+    state: &Mutex<DaemonState>,
+    jobs: &tokio::sync::mpsc::Sender<Job>,
+    progress: &tokio::sync::mpsc::UnboundedSender<Value>,
+) -> Value {
+    let request = match serde_json::from_str::<Request>(line) {
+        Ok(request) => request,
+        Err(e) => return error_reply(format!("invalid request: {e}")),
+    };
+    if request.command == DaemonCommand::Status {
+        return state.lock().unwrap().status(cached_configs());
+    }
+    let (reply, response) = tokio::sync::oneshot::channel();
+    let job = Job {
+        command: request.command,
+        reload: request.reload,
+        progress: request.progress.then(|| progress.clone()),
+        reply,
+    };
+    match jobs.try_send(job) {
+        Ok(()) => {}
+        Err(tokio::sync::mpsc::error::TrySendError::Full(_)) => {
+            return error_reply(format!(
+                "too many syncs are waiting (at most {MAX_QUEUED_SYNCS})"
+            ));
+        }
+        Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) => {
+            return error_reply("the daemon is shutting down");
+        }
+    }
+    response
+        .await
+        .unwrap_or_else(|_| error_reply("the sync was cancelled"))
+}
+
+/// 要求の 1 行を読む。接続が閉じていれば `None`、行が [`MAX_REQUEST_BYTES`] を超えれば
+/// `Some(Err(_))`。
+async fn read_request(
+    reader: &mut (impl tokio::io::AsyncBufRead + Unpin),
+) -> std::io::Result<Option<Result<String, Value>>> {
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+    let mut line = Vec::new();
+    let read = reader
+        .take(MAX_REQUEST_BYTES as u64 + 1)
+        .read_until(b'\n', &mut line)
+        .await?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    } else if line.len() > MAX_REQUEST_BYTES {
+        return Ok(Some(Err(error_reply(format!(
+            "request is longer than {MAX_REQUEST_BYTES} bytes"
+        )))));
+    }
+    Ok(Some(Ok(String::from_utf8_lossy(&line).into_owned())))
+}
+
+/// `future` を完了まで poll し、panic したらその内容を返す。1 つの同期の panic で daemon を
+/// 止めない。
+async fn catch_panic<F: Future>(future: F) -> Result<F::Output, Box<dyn Any + Send>> {
+    let mut future = std::pin::pin!(future);
+    std::future::poll_fn(|cx| {
+        match std::panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(panic) => Poll::Ready(Err(panic)),
+        }
+    })
+    .await
+}
+
+/// キャッシュ済みの設定ファイルの数。
+fn cached_configs() -> usize {
+    PARSED_CONFIGS
+        .get()
+        .map_or(0, |cache| cache.lock().unwrap().len())
+}
+
+/// 同期を 1 つずつ順に実行する。
+async fn run_jobs(
+    ctx: &AppContext,
+    options: SyncOptions,
+    state: Arc<Mutex<DaemonState>>,
+    mut jobs: tokio::sync::mpsc::Receiver<Job>,
+) {
+    while let Some(Job {
+        command,
+        reload,
+        progress,
+        reply,
+    }) = jobs.recv().await
+    {
+        let mode = match options.mode(command) {
+            Ok(mode) => mode,
+            Err(message) => {
+                let _ = reply.send(error_reply(message));
+                continue;
+            }
+        };
+        state.lock().unwrap().busy = Some(command);
+        ctx.logger.send(Message::SyncBegin);
+        let started = Instant::now();
+        // 要求に `reload` があれば、起動時の `--reload` より優先する。
+        let mut pack = options.pack.clone();
+        if reload.is_some() {
+            pack.reload = reload;
+        }
+        // SyncBegin より後のイベントだけを流す。
+        let mut events = ctx.logger.subscribe_progress();
+        let forward = |event: ProgressEvent| {
+            if let Some(progress) = &progress {
+                let _ = progress.send(event.to_json());
+            }
+        };
+        let running = catch_panic(sync(
+            ctx,
+            mode,
+            options.force,
+            options.lockfile.clone(),
+            options.dev_path.clone(),
+            pack,
+            options.config_files.clone(),
+            &[],
+        ));
+        tokio::pin!(running);
+        let result = loop {
+            tokio::select! {
+                result = &mut running => break result,
+                Ok(event) = events.recv(), if progress.is_some() => forward(event),
+            }
+        };
+        if progress.is_some() {
+            // 同期中に送られたメッセージの変換を待ち、残りを応答より先に流す。
+            ctx.logger.flush().await;
+            while let Ok(event) = events.try_recv() {
+                forward(event);
+            }
+        }
+        let duration = started.elapsed();
+        let error = match result {
+            Ok(Ok(_)) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(panic) => Some(task::caught("sync", panic).to_string()),
+        }
+        .map(|e| redact::redact(&e).into_owned());
+        let response = json!({
+            "ok": error.is_none(),
+            "command": command.as_str(),
+            "error": error,
+            "duration_ms": duration.as_millis() as u64,
+        });
+        {
+            let mut state = state.lock().unwrap();
+            state.busy = None;
+            state.syncs += 1;
+            state.last = Some(LastSync {
+                command,
+                error,
+                duration,
+            });
+        }
+        let _ = reply.send(response);
+    }
+}
+
+/// `rsplug daemon`: `--send` なら要求を 1 つ送り、それ以外は daemon として待ち受ける。
+pub(crate) async fn run(
+    ctx: &AppContext,
+    args: &DaemonArgs,
+    options: SyncOptions,
+) -> Result<(), Error> {
+    let socket = args
+        .socket
+        .clone()
+        .unwrap_or_else(|| ctx.app_dir.join("daemon.sock"));
+    match args.send {
+        Some(command) => {
+            send(
+                &socket,
+                command,
+                options.pack.reload.as_deref(),
+                args.progress,
+            )
+            .await
+        }
+        None => serve(ctx, &socket, options).await,
+    }
+}
+
+#[cfg(unix)]
+async fn serve(ctx: &AppContext, socket: &Path, options: SyncOptions) -> Result<(), Error> {
+    use tokio::io::{AsyncWriteExt, BufReader};
+    use tokio::net::{UnixListener, UnixStream};
+
+    // 空のまま同期すると pack が空になる。
+    if options.config_files.is_empty() {
+        return Err(Error::DaemonNoConfigFiles);
+    }
+    // `-` は標準入力を 1 度しか読めないので、同期を繰り返す daemon では使えない。
+    if options.config_files.iter().any(|pattern| pattern == "-") {
+        return Err(Error::DaemonStdinConfig);
+    }
+    tokio::fs::create_dir_all(&ctx.app_dir).await?;
+    // 応答する daemon がいれば譲り、いなければ前回の残骸として消す。
+    if UnixStream::connect(socket).await.is_ok() {
+        return Err(Error::DaemonRunning {
+            path: socket.to_path_buf(),
+        });
+    }
+    match tokio::fs::remove_file(socket).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    let listener = UnixListener::bind(socket)?;
+    let _ = PARSED_CONFIGS.set(Default::default());
+    println!(
+        "{} listening on {}",
+        style("rsplug daemon").bold(),
+        socket.display()
+    );
+
+    let state = Arc::new(Mutex::new(DaemonState::default()));
+    let (jobs_tx, jobs_rx) = tokio::sync::mpsc::channel(MAX_QUEUED_SYNCS);
+    // 接続の受け付けは別タスクで行い、同期の最中でも `status` に答えられるようにする。
+    let acceptor = tokio::spawn({
+        let state = Arc::clone(&state);
+        let logger = ctx.logger.clone();
+        async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        logger.send(Message::Error(Box::new(e)));
+                        continue;
+                    }
+                };
+                let state = Arc::clone(&state);
+                let jobs = jobs_tx.clone();
+                tokio::spawn(async move {
+                    let (read, mut write) = stream.into_split();
+                    let mut read = BufReader::new(read);
+                    while let Ok(Some(line)) = read_request(&mut read).await {
+                        let line = match line {
+                            Ok(line) => line,
+                            Err(reply) => {
+                                let _ = write.write_all(format!("{reply}\n").as_bytes()).await;
+                                break;
+                            }
+                        };
+                        if line.trim().is_empty() {
+                            continue;
+                        }
+                        let (progress, mut events) = tokio::sync::mpsc::unbounded_channel();
+                        let handled = handle_line(&line, &state, &jobs, &progress);
+                        tokio::pin!(handled);
+                        let mut out = String::new();
+                        let response = loop {
+                            tokio::select! {
+                                response = &mut handled => break response,
+                                Some(event) = events.recv() => {
+                                    let event = format!("{event}\n");
+                                    if write.write_all(event.as_bytes()).await.is_err() {
+                                        return;
+                                    }
+                                }
+                            }
+                        };
+                        // 応答と同時に届いた残りのイベントを応答より先に書く。
+                        while let Ok(event) = events.try_recv() {
+                            out.push_str(&format!("{event}\n"));
+                        }
+                        out.push_str(&format!("{response}\n"));
+                        if write.write_all(out.as_bytes()).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        }
+    });
+    run_jobs(ctx, options, state, jobs_rx).await;
+    acceptor.abort();
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn send(
+    socket: &Path,
+    command: DaemonCommand,
+    reload: Option<&str>,
+    progress: bool,
+) -> Result<(), Error> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let stream = match tokio::net::UnixStream::connect(socket).await {
+        Ok(stream) => stream,
+        Err(e)
+            if matches!(
+                e.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused
+            ) =>
+        {
+            return Err(Error::DaemonNotRunning {
+                path: socket.to_path_buf(),
+            });
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let (read, mut write) = stream.into_split();
+    let mut request = json!({ "command": command.as_str() });
+    if let Some(server) = reload {
+        request["reload"] = json!(server);
+    }
+    if progress {
+        request["progress"] = json!(true);
+    }
+    let mut request = request.to_string();
+    request.push('\n');
+    write.write_all(request.as_bytes()).await?;
+    // 進捗イベントは `"event"` を持ち、最後の 1 行が応答。
+    let mut lines = BufReader::new(read).lines();
+    let reply = loop {
+        let line = lines
+            .next_line()
+            .await?
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof))?;
+        let value: Value = serde_json::from_str(&line).map_err(std::io::Error::other)?;
+        println!("{value}");
+        if value.get("event").is_none() {
+            break value;
+        }
+    };
+    if reply["ok"] != true {
+        return Err(Error::DaemonRequest {
+            message: reply["error"]
+                .as_str()
+                .unwrap_or("request failed")
+                .to_string(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn serve(_ctx: &AppContext, _socket: &Path, _options: SyncOptions) -> Result<(), Error> {
+    Err(unsupported())
+}
+
+#[cfg(not(unix))]
+async fn send(
+    _socket: &Path,
+    _command: DaemonCommand,
+    _reload: Option<&str>,
+    _progress: bool,
+) -> Result<(), Error> {
+    Err(unsupported())
+}
+
+#[cfg(not(unix))]
+fn unsupported() -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "rsplug daemon needs Unix domain sockets",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(locked: bool, offline: bool) -> SyncOptions {
+        SyncOptions {
+            force: false,
+            locked,
+            offline,
+            lockfile: PathBuf::from("rsplug.lock.json"),
+            dev_path: None,
+            pack: PackOptions {
+                merge: Default::default(),
+                no_merge: false,
+                verbose_install: false,
+                stable_names: false,
+                strict: false,
+                debug_loader: false,
+                sparse: false,
+                post_process: None,
+                emit_lua: None,
+                diff_loader: false,
+                fetch_only: false,
+                system_packpath: None,
+                reload: None,
+                record: None,
+                plan: None,
+                select: None,
+            },
+            config_files: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn requests_map_to_the_cli_run_modes() {
+        let plain = options(false, false);
+        assert_eq!(
+            plain.mode(DaemonCommand::Install),
+            Ok(RunMode::from_flags(true, false, false, false))
+        );
+        assert_eq!(
+            plain.mode(DaemonCommand::Update),
+            Ok(RunMode::from_flags(false, true, false, false))
+        );
+        assert_eq!(
+            options(true, false).mode(DaemonCommand::Install),
+            Ok(RunMode::from_flags(true, false, true, false))
+        );
+        assert!(
+            options(false, true)
+                .mode(DaemonCommand::Update)
+                .unwrap_err()
+                .contains("--offline")
+        );
+    }
+
+    #[tokio::test]
+    async fn status_and_malformed_requests_are_answered_without_a_sync() {
+        let state = Mutex::new(DaemonState::default());
+        let (jobs, mut queued) = tokio::sync::mpsc::channel(MAX_QUEUED_SYNCS);
+
+        let (progress, _) = tokio::sync::mpsc::unbounded_channel();
+        let status = handle_line(r#"{"command":"status"}"#, &state, &jobs, &progress).await;
+        assert_eq!(status["ok"], true);
+        assert_eq!(status["busy"], Value::Null);
+        assert_eq!(status["syncs"], 0);
+
+        let invalid = handle_line(r#"{"command":"reboot"}"#, &state, &jobs, &progress).await;
+        assert_eq!(invalid["ok"], false);
+        assert!(
+            invalid["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("invalid request")
+        );
+        assert!(queued.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn sync_requests_carry_the_reload_server() {
+        let state = Arc::new(Mutex::new(DaemonState::default()));
+        let (jobs, mut queued) = tokio::sync::mpsc::channel(MAX_QUEUED_SYNCS);
+        let request = tokio::spawn({
+            let state = Arc::clone(&state);
+            async move {
+                let (progress, _) = tokio::sync::mpsc::unbounded_channel();
+                handle_line(
+                    r#"{"command":"install","reload":"/tmp/nvim.sock"}"#,
+                    &state,
+                    &jobs,
+                    &progress,
+                )
+                .await
+            }
+        });
+
+        let job = queued.recv().await.unwrap();
+        assert_eq!(job.command, DaemonCommand::Install);
+        assert_eq!(job.reload.as_deref(), Some("/tmp/nvim.sock"));
+        assert!(job.progress.is_none());
+        job.reply.send(json!({ "ok": true })).unwrap();
+        assert_eq!(request.await.unwrap()["ok"], true);
+    }
+
+    #[tokio::test]
+    async fn progress_requests_carry_an_event_sink() {
+        let state = Arc::new(Mutex::new(DaemonState::default()));
+        let (jobs, mut queued) = tokio::sync::mpsc::channel(MAX_QUEUED_SYNCS);
+        let (progress, mut events) = tokio::sync::mpsc::unbounded_channel();
+        let request = tokio::spawn({
+            let state = Arc::clone(&state);
+            async move {
+                handle_line(
+                    r#"{"command":"update","progress":true}"#,
+                    &state,
+                    &jobs,
+                    &progress,
+                )
+                .await
+            }
+        });
+
+        let job = queued.recv().await.unwrap();
+        job.progress
+            .unwrap()
+            .send(json!({ "event": "progress" }))
+            .unwrap();
+        job.reply.send(json!({ "ok": true })).unwrap();
+        assert_eq!(request.await.unwrap()["ok"], true);
+        assert_eq!(events.recv().await.unwrap()["event"], "progress");
+    }
+
+    #[tokio::test]
+    async fn syncs_beyond_the_queue_are_refused() {
+        let state = Mutex::new(DaemonState::default());
+        let (jobs, _queued) = tokio::sync::mpsc::channel(1);
+        let (reply, _response) = tokio::sync::oneshot::channel();
+        jobs.try_send(Job {
+            command: DaemonCommand::Install,
+            reload: None,
+            progress: None,
+            reply,
+        })
+        .unwrap();
+
+        let (progress, _) = tokio::sync::mpsc::unbounded_channel();
+        let refused = handle_line(r#"{"command":"install"}"#, &state, &jobs, &progress).await;
+        assert_eq!(refused["ok"], false);
+        assert!(
+            refused["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("too many syncs")
+        );
+    }
+
+    #[tokio::test]
+    async fn overlong_requests_are_refused() {
+        let mut input = &b"{\"command\":\"status\"}\nlast"[..];
+        assert_eq!(
+            read_request(&mut input).await.unwrap().unwrap().unwrap(),
+            r#"{"command":"status"}"#
+        );
+        assert_eq!(
+            read_request(&mut input).await.unwrap().unwrap().unwrap(),
+            "last"
+        );
+        assert!(read_request(&mut input).await.unwrap().is_none());
+
+        let long = vec![b' '; MAX_REQUEST_BYTES + 1];
+        let refused = read_request(&mut &long[..]).await.unwrap().unwrap();
+        assert_eq!(refused.unwrap_err()["ok"], false);
+    }
+
+    #[tokio::test]
+    async fn panics_in_a_sync_are_caught() {
+        assert_eq!(catch_panic(async { 1 }).await.unwrap(), 1);
+        let panicked = catch_panic(async {
+            tokio::task::yield_now().await;
+            panic!("boom")
+        })
+        .await;
+        assert_eq!(*panicked.unwrap_err().downcast::<&str>().unwrap(), "boom");
+    }
+
+    #[test]
+    fn status_reports_the_last_sync() {
+        let state = DaemonState {
+            busy: Some(DaemonCommand::Update),
+            syncs: 2,
+            last: Some(LastSync {
+                command: DaemonCommand::Install,
+                error: Some("boom".to_string()),
+                duration: Duration::from_millis(1_500),
+            }),
+        };
+        let status = state.status(3);
+        assert_eq!(status["busy"], "update");
+        assert_eq!(status["cached_configs"], 3);
+        assert_eq!(status["last"]["command"], "install");
+        assert_eq!(status["last"]["ok"], false);
+        assert_eq!(status["last"]["error"], "boom");
+        assert_eq!(status["last"]["duration_ms"], 1_500);
+    }
+}
@@ -0,0 +1,365 @@
+//! Disk usage report for the rsplug cache (`rsplug du`).
+//!
+//! One walk of the application root through the `walker` crate collects every
+//! regular file, and sizes are read with batched `lstat` calls on blocking
+//! threads. Each file is attributed by its path alone to a cached repository,
+//! an installed package, or a generated artifact. Hard links (the install
+//! content store and the hard-link copy strategy) are counted once per entry
+//! and once in the total, so the sections can add up to more than the total.
+
+use std::{
+    ffi::OsStr,
+    path::{Component, Path},
+};
+
+use tokio::task::JoinSet;
+use walker::{
+    compiled_glob::CompiledGlob,
+    walker::{Walker, WalkerOptions},
+};
+
+use super::*;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct DuArgs {
+    /// Show only the N largest entries of each section
+    #[arg(long, value_name = "N")]
+    pub(crate) top: Option<usize>,
+}
+
+/// 1 回の `spawn_blocking` で stat するファイル数。
+const STAT_BATCH: usize = 512;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Section {
+    Repositories,
+    Packages,
+    Generated,
+}
+
+impl Section {
+    fn title(self) -> &'static str {
+        match self {
+            Section::Repositories => "Repositories",
+            Section::Packages => "Packages",
+            Section::Generated => "Generated",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Usage {
+    files: u64,
+    bytes: u64,
+}
+
+/// 同じ inode を 2 度数えないための (device, inode)。inode を持たない環境では `None`。
+type InodeKey = Option<(u64, u64)>;
+
+#[derive(Default)]
+struct Tally {
+    usage: Usage,
+    seen: HashSet<(u64, u64)>,
+}
+
+impl Tally {
+    fn add(&mut self, bytes: u64, inode: InodeKey) {
+        self.usage.files += 1;
+        if inode.is_none_or(|inode| self.seen.insert(inode)) {
+            self.usage.bytes += bytes;
+        }
+    }
+}
+
+struct DiskUsage {
+    entries: BTreeMap<(Section, PathBuf), Tally>,
+    total: Tally,
+    /// パッケージ id → 併合された設定上の名前（provenance index があれば）。
+    names: BTreeMap<String, Vec<String>>,
+}
+
+/// `relative`（アプリ root 相対のファイルパス）の帰属先を決める。
+///
+/// - `repos/<host>/<path>/{source.git,worktrees,latest-snapshot}…` → その repo
+/// - `pack/_gen/opt/<id>/…` → そのパッケージ
+/// - それ以外 → `pack/_gen/<name>` または root 直下の名前ごとの生成物
+fn classify(relative: &Path) -> (Section, PathBuf) {
+    let components: Vec<&OsStr> = relative
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name),
+            _ => None,
+        })
+        .collect();
+    match components.as_slice() {
+        [repos, rest @ ..] if *repos == "repos" && rest.len() > 1 => {
+            let end = rest
+                .iter()
+                .position(|name| {
+                    *name == "source.git" || *name == "worktrees" || *name == "latest-snapshot"
+                })
+                .unwrap_or(rest.len() - 1)
+                .max(1);
+            (Section::Repositories, rest[..end].iter().collect())
+        }
+        [pack, generated, opt, id, ..]
+            if *pack == "pack" && *generated == "_gen" && *opt == "opt" =>
+        {
+            (Section::Packages, PathBuf::from(id))
+        }
+        [pack, generated, name, ..] if *pack == "pack" && *generated == "_gen" => {
+            (Section::Generated, Path::new("pack/_gen").join(name))
+        }
+        [first, ..] => (Section::Generated, PathBuf::from(first)),
+        [] => (Section::Generated, PathBuf::new()),
+    }
+}
+
+fn stat_file(path: &Path) -> std::io::Result<(u64, InodeKey)> {
+    let meta = std::fs::symlink_metadata(path)?;
+    #[cfg(unix)]
+    let inode = {
+        use std::os::unix::fs::MetadataExt;
+        (meta.nlink() > 1).then(|| (meta.dev(), meta.ino()))
+    };
+    #[cfg(not(unix))]
+    let inode = None;
+    Ok((meta.len(), inode))
+}
+
+type StatBatch = Vec<(PathBuf, (u64, InodeKey))>;
+
+fn spawn_stat_batch(stats: &mut JoinSet<StatBatch>, batch: Vec<PathBuf>) {
+    stats.spawn_blocking(move || {
+        batch
+            .into_iter()
+            .filter_map(|path| {
+                // walk と stat の間に消えたファイルは数えない。
+                let stat = stat_file(&path).ok()?;
+                Some((path, stat))
+            })
+            .collect()
+    });
+}
+
+/// `root` 配下の全通常ファイルを walk し、帰属先ごとに集計する。
+async fn measure(root: &Path) -> Result<DiskUsage, Error> {
+    let mut report = DiskUsage {
+        entries: BTreeMap::new(),
+        total: Tally::default(),
+        names: rsplug::pack_plan::package_names(root)
+            .await
+            .unwrap_or_default(),
+    };
+    if !tokio::fs::try_exists(root).await? {
+        return Ok(report);
+    }
+    // root をパターンに埋め込むと、パス中の `*` や `?` がワイルドカードになる。
+    let glob = CompiledGlob::new("/**")?;
+    let mut rx = Walker::spawn_with_options(
+        glob,
+        WalkerOptions {
+            files_only: true,
+            roots: vec![root.to_path_buf()],
+            ..Default::default()
+        },
+    );
+    let mut stats = JoinSet::new();
+    let mut batch = Vec::with_capacity(STAT_BATCH);
+    while let Some(event) = rx.recv().await {
+        let event = event.map_err(std::io::Error::other)?;
+        batch.push(event.path);
+        if batch.len() == STAT_BATCH {
+            spawn_stat_batch(
+                &mut stats,
+                std::mem::replace(&mut batch, Vec::with_capacity(STAT_BATCH)),
+            );
+        }
+    }
+    if !batch.is_empty() {
+        spawn_stat_batch(&mut stats, batch);
+    }
+    while let Some(done) = stats.join_next().await {
+        for (path, (bytes, inode)) in done.map_err(rsplug::util::task::panicked("stat"))? {
+            let Ok(relative) = path.strip_prefix(root) else {
+                continue;
+            };
+            report
+                .entries
+                .entry(classify(relative))
+                .or_default()
+                .add(bytes, inode);
+            report.total.add(bytes, inode);
+        }
+    }
+    Ok(report)
+}
+
+impl DiskUsage {
+    /// セクションごとに大きい順で並べ、`top` があれば各セクションを上位 N 件に絞る。
+    fn render(&self, top: Option<usize>) -> String {
+        let mut sections: BTreeMap<Section, Vec<(&PathBuf, Usage)>> = BTreeMap::new();
+        for ((section, key), tally) in &self.entries {
+            sections
+                .entry(*section)
+                .or_default()
+                .push((key, tally.usage));
+        }
+        let mut out = String::new();
+        for (section, mut entries) in sections {
+            let bytes: u64 = entries.iter().map(|(_, usage)| usage.bytes).sum();
+            out.push_str(&format!(
+                "{}  {} entries, {}\n",
+                section.title(),
+                entries.len(),
+                rsplug::util::format_bytes(bytes)
+            ));
+            entries.sort_by(|(lkey, lusage), (rkey, rusage)| {
+                rusage.bytes.cmp(&lusage.bytes).then_with(|| lkey.cmp(rkey))
+            });
+            for (key, usage) in entries.into_iter().take(top.unwrap_or(usize::MAX)) {
+                let label = match section {
+                    Section::Packages => {
+                        let id = key.to_string_lossy();
+                        match self.names.get(id.as_ref()) {
+                            Some(names) if !names.is_empty() => {
+                                format!("opt/{id}  ({})", names.join(", "))
+                            }
+                            _ => format!("opt/{id}"),
+                        }
+                    }
+                    _ => key.display().to_string(),
+                };
+                out.push_str(&format!(
+                    "  {:>10}  {label}\n",
+                    rsplug::util::format_bytes(usage.bytes)
+                ));
+            }
+        }
+        out.push_str(&format!(
+            "Total  {} files, {} (hard links counted once)\n",
+            self.total.usage.files,
+            rsplug::util::format_bytes(self.total.usage.bytes)
+        ));
+        out
+    }
+}
+
+/// `rsplug du`: アプリ root（repo cache・pack・生成物）の使用量を表示する。
+pub(crate) async fn print_disk_usage(root: &Path, args: &DuArgs) -> Result<(), Error> {
+    let report = measure(root).await?;
+    print!("{}", report.render(args.top));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_groups_files_by_repository_package_and_artifact() {
+        let cases = [
+            (
+                "repos/github.com/owner/repo/worktrees/abc/lua/x.lua",
+                Section::Repositories,
+                "github.com/owner/repo",
+            ),
+            (
+                "repos/gitlab.com/group/sub/repo/source.git/HEAD",
+                Section::Repositories,
+                "gitlab.com/group/sub/repo",
+            ),
+            (
+                "repos/github.com/owner/repo/latest-snapshot",
+                Section::Repositories,
+                "github.com/owner/repo",
+            ),
+            ("pack/_gen/opt/0123/plugin/a.lua", Section::Packages, "0123"),
+            (
+                "pack/_gen/generations/registry.json",
+                Section::Generated,
+                "pack/_gen/generations",
+            ),
+            ("init.lua", Section::Generated, "init.lua"),
+        ];
+        for (path, section, key) in cases {
+            assert_eq!(
+                classify(Path::new(path)),
+                (section, PathBuf::from(key)),
+                "{path}"
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn measure_attributes_sizes_and_counts_hard_links_once() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let worktree = root.join("repos/github.com/owner/repo/worktrees/abc");
+        std::fs::create_dir_all(worktree.join("lua")).unwrap();
+        std::fs::write(worktree.join("lua/x.lua"), vec![b'x'; 3000]).unwrap();
+        std::fs::create_dir_all(root.join("repos/github.com/owner/repo/source.git")).unwrap();
+        std::fs::write(
+            root.join("repos/github.com/owner/repo/source.git/packed"),
+            vec![0; 1000],
+        )
+        .unwrap();
+        let package = root.join("pack/_gen/opt/0123");
+        std::fs::create_dir_all(package.join("lua")).unwrap();
+        std::fs::hard_link(worktree.join("lua/x.lua"), package.join("lua/x.lua")).unwrap();
+        std::fs::write(root.join("init.lua"), vec![b'-'; 10]).unwrap();
+
+        let report = measure(root).await.unwrap();
+        let usage = |section, key: &str| report.entries[&(section, PathBuf::from(key))].usage;
+        assert_eq!(
+            usage(Section::Repositories, "github.com/owner/repo"),
+            Usage {
+                files: 2,
+                bytes: 4000
+            }
+        );
+        assert_eq!(
+            usage(Section::Packages, "0123"),
+            Usage {
+                files: 1,
+                bytes: 3000
+            }
+        );
+        assert_eq!(
+            report.total.usage,
+            Usage {
+                files: 4,
+                bytes: 4010
+            }
+        );
+
+        let rendered = report.render(Some(1));
+        assert!(rendered.contains("Repositories  1 entries, 3.9 KiB\n"));
+        assert!(rendered.contains("opt/0123\n"));
+        assert!(rendered.ends_with("Total  4 files, 3.9 KiB (hard links counted once)\n"));
+    }
+
+    #[tokio::test]
+    async fn measure_handles_roots_with_glob_characters() {
+        let tmp = tempfile::tempdir().unwrap();
+        // パターンとして解釈されると `[a]` は `a` に、`*` と `?` は任意の名前に一致する。
+        let root = tmp.path().join("cache [a] *?");
+        std::fs::create_dir_all(root.join("pack/_gen/opt/0123")).unwrap();
+        std::fs::write(root.join("pack/_gen/opt/0123/x.lua"), vec![b'x'; 100]).unwrap();
+        std::fs::write(root.join("init.lua"), vec![b'-'; 10]).unwrap();
+        // 同じパターンに一致する兄弟ディレクトリは数えない。
+        let sibling = tmp.path().join("cache a xy");
+        std::fs::create_dir_all(&sibling).unwrap();
+        std::fs::write(sibling.join("init.lua"), vec![b'-'; 1000]).unwrap();
+
+        let report = measure(&root).await.unwrap();
+        assert_eq!(
+            report.total.usage,
+            Usage {
+                files: 2,
+                bytes: 110
+            }
+        );
+    }
+}
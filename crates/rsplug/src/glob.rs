@@ -0,0 +1,242 @@
+//! Glob walks from the command line (`rsplug glob`).
+//!
+//! The patterns go through the same `walker` crate that finds the config files,
+//! so the syntax and speed match: later patterns win, `!` excludes, `**` spans
+//! directories, and relative patterns start at the working directory. Paths are
+//! printed as they are found, absolute and unsorted. `--gitignore` drops paths
+//! excluded by the `.gitignore` files of the Git work tree they are in.
+
+use std::{
+    io::Write as _,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use file_specifier::FileSpecifier;
+use walker::{
+    compiled_glob::CompiledGlob,
+    walker::{EntryKind, Walker, WalkerOptions},
+};
+
+use super::*;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct GlobArgs {
+    /// Glob patterns; a later pattern wins and `!` excludes
+    #[arg(required = true)]
+    pub(crate) patterns: Vec<String>,
+    /// Print regular files only
+    #[arg(long)]
+    pub(crate) files_only: bool,
+    /// Descend at most N levels below the literal prefix of each pattern
+    #[arg(long, value_name = "N")]
+    pub(crate) max_depth: Option<usize>,
+    /// Skip paths excluded by `.gitignore` files, and `.git` directories
+    #[arg(long)]
+    pub(crate) gitignore: bool,
+    /// Output format
+    #[arg(long, value_enum, default_value_t)]
+    pub(crate) format: GlobFormat,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum GlobFormat {
+    /// One path per line
+    #[default]
+    Lines,
+    /// Paths terminated by NUL, for `xargs -0`
+    Nul,
+    /// One `{"path": ..., "kind": ...}` object per line
+    Json,
+}
+
+/// `--format json` の 1 行。
+#[derive(serde::Serialize)]
+struct JsonEntry<'a> {
+    path: std::borrow::Cow<'a, str>,
+    kind: &'static str,
+}
+
+fn kind_name(kind: EntryKind) -> &'static str {
+    match kind {
+        EntryKind::File => "file",
+        EntryKind::Dir => "dir",
+        EntryKind::Symlink => "symlink",
+        EntryKind::Other => "other",
+    }
+}
+
+/// ディレクトリごとの `.gitignore` と、そこが work tree の root かどうか。
+struct GitDir {
+    ignore: Option<FileSpecifier>,
+    repo_root: bool,
+}
+
+/// 読んだディレクトリの `.gitignore` を覚えておき、パスが除外されるかを答える。
+#[derive(Default)]
+struct GitIgnores {
+    dirs: HashMap<PathBuf, GitDir>,
+}
+
+impl GitIgnores {
+    async fn dir(&mut self, dir: &Path) -> &GitDir {
+        if !self.dirs.contains_key(dir) {
+            let ignore = tokio::fs::read_to_string(dir.join(".gitignore"))
+                .await
+                .ok()
+                .map(|text| FileSpecifier::from_str(&text).unwrap_or_else(|never| match never {}));
+            let repo_root = tokio::fs::symlink_metadata(dir.join(".git")).await.is_ok();
+            self.dirs
+                .insert(dir.to_path_buf(), GitDir { ignore, repo_root });
+        }
+        &self.dirs[dir]
+    }
+
+    /// `path` が `.git` の中にあるか、work tree の root までのどれかの `.gitignore` に除外される。
+    /// work tree の外のパスは除外しない。
+    async fn ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        if path.components().any(|c| c.as_os_str() == ".git") {
+            return true;
+        }
+        let mut ignored = false;
+        for dir in path.ancestors().skip(1) {
+            let git_dir = self.dir(dir).await;
+            if let Some(ignore) = &git_dir.ignore
+                && let Ok(relative) = path.strip_prefix(dir)
+            {
+                // ディレクトリ限定のルール（`build/`）はディレクトリ自身にも効かせる。
+                let mut relative = relative.to_string_lossy().into_owned();
+                if is_dir {
+                    relative.push('/');
+                }
+                ignored |= ignore.matched(relative);
+            }
+            if git_dir.repo_root {
+                return ignored;
+            }
+        }
+        false
+    }
+}
+
+fn write_entry(
+    out: &mut impl std::io::Write,
+    format: GlobFormat,
+    path: &Path,
+    kind: EntryKind,
+) -> std::io::Result<()> {
+    match format {
+        GlobFormat::Lines | GlobFormat::Nul => {
+            #[cfg(unix)]
+            out.write_all(std::os::unix::ffi::OsStrExt::as_bytes(path.as_os_str()))?;
+            #[cfg(not(unix))]
+            out.write_all(path.to_string_lossy().as_bytes())?;
+            out.write_all(if format == GlobFormat::Nul {
+                b"\0"
+            } else {
+                b"\n"
+            })
+        }
+        GlobFormat::Json => {
+            let line = serde_json::to_string(&JsonEntry {
+                path: path.to_string_lossy(),
+                kind: kind_name(kind),
+            })
+            .map_err(std::io::Error::other)?;
+            writeln!(out, "{line}")
+        }
+    }
+}
+
+/// `rsplug glob`: パターンに合うパスを見つけた順に書き出す。
+pub(crate) async fn print_glob(args: &GlobArgs) -> Result<(), Error> {
+    let globs = args
+        .patterns
+        .iter()
+        .map(|pattern| CompiledGlob::new(pattern))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut rx = Walker::spawn_many_with_options(
+        globs,
+        WalkerOptions {
+            files_only: args.files_only,
+            max_depth: args.max_depth,
+            ..Default::default()
+        },
+    );
+    let mut gitignores = args.gitignore.then(GitIgnores::default);
+    let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+    let mut errors = 0usize;
+    while let Some(msg) = rx.recv().await {
+        let event = match msg {
+            Ok(event) => event,
+            Err(e) => {
+                errors += 1;
+                eprintln!("{} {e}", style("warning:").yellow().bold());
+                continue;
+            }
+        };
+        if let Some(gitignores) = &mut gitignores
+            && gitignores
+                .ignored(&event.path, event.kind == EntryKind::Dir)
+                .await
+        {
+            continue;
+        }
+        match write_entry(&mut out, args.format, &event.path, event.kind) {
+            Ok(()) => {}
+            // `| head` などで読み手が先に閉じたら静かに終わる。
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    if let Err(e) = out.flush()
+        && e.kind() != std::io::ErrorKind::BrokenPipe
+    {
+        return Err(e.into());
+    }
+    if errors > 0 {
+        eprintln!("{errors} path(s) could not be read");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn gitignore_applies_inside_the_work_tree_only() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = tmp.path().join("repo");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(repo.join("sub/build")).unwrap();
+        std::fs::write(repo.join(".gitignore"), "*.log\nbuild/\n").unwrap();
+        std::fs::write(repo.join("sub/.gitignore"), "secret.txt\n").unwrap();
+        // work tree の外の `.gitignore` は効かない。
+        std::fs::write(tmp.path().join(".gitignore"), "*.lua\n").unwrap();
+
+        let mut ignores = GitIgnores::default();
+        assert!(ignores.ignored(&repo.join("a.log"), false).await);
+        assert!(ignores.ignored(&repo.join("sub/x/a.log"), false).await);
+        assert!(ignores.ignored(&repo.join("sub/build"), true).await);
+        assert!(ignores.ignored(&repo.join("sub/build/out.o"), false).await);
+        assert!(ignores.ignored(&repo.join("sub/secret.txt"), false).await);
+        assert!(!ignores.ignored(&repo.join("secret.txt"), false).await);
+        assert!(!ignores.ignored(&repo.join("init.lua"), false).await);
+        assert!(ignores.ignored(&repo.join(".git/config"), false).await);
+        assert!(!ignores.ignored(&tmp.path().join("init.log"), false).await);
+    }
+
+    #[test]
+    fn entries_are_written_in_the_requested_format() {
+        let path = Path::new("/tmp/a b.lua");
+        let mut out = Vec::new();
+        write_entry(&mut out, GlobFormat::Lines, path, EntryKind::File).unwrap();
+        write_entry(&mut out, GlobFormat::Nul, path, EntryKind::File).unwrap();
+        write_entry(&mut out, GlobFormat::Json, path, EntryKind::Dir).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "/tmp/a b.lua\n/tmp/a b.lua\0{\"path\":\"/tmp/a b.lua\",\"kind\":\"dir\"}\n"
+        );
+    }
+}
@@ -0,0 +1,292 @@
+//! Per-plugin summary (`rsplug info <PLUGIN>`).
+//!
+//! The configured entry is looked up in the config files by name or
+//! repository, and its repository, triggers, and dependencies are printed
+//! together with GitHub metadata: description, homepage, stars, and latest
+//! tag. Metadata is fetched when online and cached below the application
+//! directory, so `--offline` or a failed request falls back to the last
+//! successful answer.
+
+use std::{path::Path, str::FromStr};
+
+use rsplug::plugin::RepoSource;
+use rsplug::util::github::{self, RepositoryInfo};
+
+use super::*;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct InfoArgs {
+    /// Plugin name (`name` or repository basename), GitHub shorthand, or Git URL
+    pub(crate) plugin: String,
+    /// Do not contact GitHub; show cached metadata only
+    #[arg(long)]
+    pub(crate) offline: bool,
+}
+
+/// 設定中で一致したエントリ。
+struct Entry {
+    path: PathBuf,
+    name: Option<String>,
+    repo: Option<RepoSource>,
+    triggers: Vec<String>,
+    depends: Vec<String>,
+}
+
+/// `query` が設定エントリを指すか。名前（`name` ?? repo 名）か、同一リポジトリで一致させる。
+fn refers_to(plugin: &rsplug::PluginConfig, query: &str) -> bool {
+    if plugin.dep_name() == Some(query) {
+        return true;
+    }
+    let Ok(wanted) = RepoSource::from_str(query) else {
+        return false;
+    };
+    plugin
+        .cache
+        .repo
+        .as_ref()
+        .is_some_and(|repo| repo.canonical() == wanted.canonical())
+}
+
+/// キャッシュしたメタデータの置き場所（`<app>/info/<canonical>.json`）。
+fn cache_path(app_dir: &Path, repo: &RepoSource) -> PathBuf {
+    let mut path = app_dir.join("info").join(repo.default_cachedir());
+    path.as_mut_os_string().push(".json");
+    path
+}
+
+async fn read_cache(path: &Path) -> Option<RepositoryInfo> {
+    let bytes = tokio::fs::read(path).await.ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+async fn write_cache(path: &Path, info: &RepositoryInfo) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let content = serde_json::to_vec_pretty(info).map_err(std::io::Error::other)?;
+    tokio::fs::write(path, content).await
+}
+
+/// 取得からの経過時間を「3 hours ago」の形で返す。
+fn describe_age(fetched_at: u64, now: u64) -> String {
+    let elapsed = now.saturating_sub(fetched_at);
+    let (count, unit) = match elapsed {
+        0..60 => return "just now".to_string(),
+        60..3_600 => (elapsed / 60, "minute"),
+        3_600..86_400 => (elapsed / 3_600, "hour"),
+        _ => (elapsed / 86_400, "day"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{count} {unit}{plural} ago")
+}
+
+/// 設定ファイルを読み、`query` に一致するエントリを返す。
+async fn find_entries(config_files: Vec<String>, query: &str) -> Result<Vec<Entry>, Error> {
+    let mut entries = Vec::new();
+    for (path, config) in read_configs(config_files).await? {
+        for plugin in config
+            .plugins
+            .iter()
+            .filter(|plugin| refers_to(plugin, query))
+        {
+            entries.push(Entry {
+                path: path.clone(),
+                name: plugin.dep_name().map(str::to_string),
+                repo: plugin.cache.repo.clone(),
+                triggers: plugin.lazy_type.describe(),
+                depends: plugin.depends.clone(),
+            });
+        }
+    }
+    Ok(entries)
+}
+
+/// GitHub のメタデータを取得してキャッシュを更新する。オフライン・取得失敗時はキャッシュを返す。
+/// GitHub 以外の repo は `None`。戻り値の `bool` はキャッシュから読んだか。
+async fn metadata(
+    app_dir: &Path,
+    repo: &RepoSource,
+    offline: bool,
+) -> Option<(RepositoryInfo, bool)> {
+    let (owner, name) = match repo {
+        RepoSource::GitHub { owner, repo, .. } => (owner.clone(), repo.to_string()),
+        RepoSource::Git { url, .. } => github::parse_github_url(url)?,
+    };
+    let cache = cache_path(app_dir, repo);
+    if !offline {
+        let client = reqwest::Client::new();
+        let token = github::token();
+        let mut attempt = 0;
+        loop {
+            match github::repository_info(&client, &owner, &name, token).await {
+                Ok(info) => {
+                    if let Err(e) = write_cache(&cache, &info).await {
+                        msg(Message::Error(Box::new(e)));
+                    }
+                    return Some((info, false));
+                }
+                Err(e) => {
+                    // 短い `Retry-After` だけは待って 1 度やり直す。
+                    if let github::ApiFallbackPolicy::Wait(wait) =
+                        github::api_error_policy(&e, attempt, 1)
+                    {
+                        tokio::time::sleep(wait).await;
+                        attempt += 1;
+                        continue;
+                    }
+                    println!(
+                        "{}: {}; using the cache",
+                        style("warning").yellow().bold(),
+                        fetch_failure(&e, token.is_some())
+                    );
+                    break;
+                }
+            }
+        }
+    }
+    read_cache(&cache).await.map(|info| (info, true))
+}
+
+/// メタデータを取得できなかった理由。匿名のレートリミットなら token の設定を促す。
+fn fetch_failure(err: &github::ApiError, authenticated: bool) -> String {
+    match err {
+        github::ApiError::RateLimited | github::ApiError::RetryAfter(_) if !authenticated => {
+            "the anonymous GitHub API rate limit (60 requests per hour) is used up; \
+             set RSPLUG_GITHUB_TOKEN or GITHUB_TOKEN to raise it"
+                .to_string()
+        }
+        github::ApiError::RateLimited | github::ApiError::RetryAfter(_) => {
+            "the GitHub API rate limit of the token is used up".to_string()
+        }
+        err => format!("could not fetch GitHub metadata ({err:?})"),
+    }
+}
+
+/// `rsplug info`: 設定エントリと GitHub のメタデータを表示する。
+pub(crate) async fn print_info(
+    ctx: &AppContext,
+    config_files: Vec<String>,
+    args: &InfoArgs,
+) -> Result<(), Error> {
+    let entries = find_entries(config_files, &args.plugin).await?;
+    if entries.is_empty() {
+        return Err(Error::NotConfigured {
+            canonical: args.plugin.clone(),
+        });
+    }
+    let now = ctx.clock.unix_secs();
+    for (index, entry) in entries.iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+        let title = entry.name.as_deref().unwrap_or("<unnamed>");
+        println!("{}", style(title).bold());
+        println!("  config:      {}", entry.path.display());
+        if let Some(repo) = &entry.repo {
+            let url = match repo {
+                RepoSource::GitHub { owner, repo, .. } => github::url(owner, repo),
+                RepoSource::Git { url, .. } => url.to_string(),
+            };
+            println!("  repository:  {url}");
+        }
+        println!("  load:        {}", entry.triggers.join(", "));
+        if !entry.depends.is_empty() {
+            println!("  depends:     {}", entry.depends.join(", "));
+        }
+        let Some(repo) = &entry.repo else {
+            continue;
+        };
+        let Some((info, cached)) = metadata(&ctx.app_dir, repo, args.offline).await else {
+            if args.offline {
+                println!("  metadata:    not cached; run without --offline");
+            }
+            continue;
+        };
+        if let Some(description) = &info.description {
+            println!("  description: {description}");
+        }
+        if let Some(homepage) = &info.homepage {
+            println!("  homepage:    {homepage}");
+        }
+        println!("  stars:       {}", info.stars);
+        println!(
+            "  latest tag:  {}",
+            info.latest_tag.as_deref().unwrap_or("(none)")
+        );
+        if cached {
+            println!(
+                "  metadata:    cached {}",
+                describe_age(info.fetched_at, now)
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin(toml: &str) -> rsplug::PluginConfig {
+        toml::from_str::<rsplug::Config>(toml)
+            .unwrap()
+            .plugins
+            .remove(0)
+    }
+
+    #[test]
+    fn matches_by_name_and_repository() {
+        let entry = plugin("[[plugins]]\nrepo = 'owner/foo.nvim'\n");
+        assert!(refers_to(&entry, "foo.nvim"));
+        assert!(refers_to(&entry, "owner/foo.nvim"));
+        assert!(refers_to(&entry, "https://github.com/owner/foo.nvim.git"));
+        assert!(!refers_to(&entry, "other/foo.nvim"));
+        assert!(!refers_to(&entry, "foo"));
+
+        let named = plugin("[[plugins]]\nrepo = 'owner/foo.nvim'\nname = 'foo'\n");
+        assert!(refers_to(&named, "foo"));
+        assert!(!refers_to(&named, "foo.nvim"));
+    }
+
+    #[test]
+    fn describe_age_uses_the_largest_unit() {
+        assert_eq!(describe_age(100, 130), "just now");
+        assert_eq!(describe_age(0, 60), "1 minute ago");
+        assert_eq!(describe_age(0, 7_300), "2 hours ago");
+        assert_eq!(describe_age(0, 3 * 86_400 + 5), "3 days ago");
+        assert_eq!(describe_age(10, 0), "just now");
+    }
+
+    #[test]
+    fn anonymous_rate_limits_suggest_a_token() {
+        let limited = github::ApiError::RateLimited;
+        assert!(fetch_failure(&limited, false).contains("GITHUB_TOKEN"));
+        assert!(!fetch_failure(&limited, true).contains("GITHUB_TOKEN"));
+        assert!(fetch_failure(&github::ApiError::NotFound, false).contains("NotFound"));
+    }
+
+    #[tokio::test]
+    async fn offline_metadata_reads_the_cache() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = RepoSource::from_str("owner/foo.nvim").unwrap();
+        assert!(metadata(tmp.path(), &repo, true).await.is_none());
+
+        let info = RepositoryInfo {
+            description: Some("Foo".into()),
+            stars: 3,
+            ..Default::default()
+        };
+        write_cache(&cache_path(tmp.path(), &repo), &info)
+            .await
+            .unwrap();
+        assert!(
+            tmp.path()
+                .join("info/github.com/owner/foo.nvim.json")
+                .is_file()
+        );
+        assert_eq!(metadata(tmp.path(), &repo, true).await, Some((info, true)));
+
+        let git = RepoSource::from_str("https://example.com/foo.git").unwrap();
+        assert!(metadata(tmp.path(), &git, false).await.is_none());
+    }
+}
@@ -0,0 +1,28 @@
+//! fuzz 用のライブラリ target（`fuzz` feature 有効時のみ中身を持つ）。
+//!
+//! rsplug 本体はバイナリ crate なので、`fuzz/` の cargo-fuzz crate から設定 TOML と
+//! `RepoSource` のパーサを呼べるよう、バイナリと同じモジュールをここでも取り込んで
+//! 入口だけを公開する。バイナリ側はこの target に依存しない。
+#![cfg(feature = "fuzz")]
+// バイナリ向けのモジュールを丸ごと取り込むため、ここから使わない項目が大半になる。
+#![allow(dead_code, unused_imports)]
+
+mod log;
+mod osc94;
+mod progress;
+mod redact;
+mod rsplug;
+
+use std::str::FromStr;
+
+/// TOML 文字列を設定ファイル（`Config`）としてデシリアライズする。
+pub fn parse_config(text: &str) -> Result<(), String> {
+    toml::from_str::<rsplug::Config>(text)
+        .map(drop)
+        .map_err(|e| e.to_string())
+}
+
+/// `owner/repo[@rev]` または URL 形式の文字列を `RepoSource` として解釈する。
+pub fn parse_repo_source(s: &str) -> Result<(), &'static str> {
+    rsplug::plugin::RepoSource::from_str(s).map(drop)
+}
@@ -6,20 +6,28 @@ use indicatif::{
 };
 use once_cell::sync::Lazy;
 use std::{
+    borrow::Cow,
     fmt::{Display, Formatter, Result as FmtResult},
     io::Write,
     path::{Path, PathBuf},
     sync::{
-        Arc, RwLock,
-        atomic::{AtomicUsize, Ordering},
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
     },
     time::{Duration, Instant},
 };
-use tokio::sync::{Mutex, mpsc};
+use tokio::runtime::RuntimeFlavor;
+use tokio::sync::{
+    broadcast,
+    mpsc::{self, error::TrySendError},
+    oneshot,
+};
 use unicode_width::UnicodeWidthStr;
 
 use crate::osc94::OSC94;
-use crate::rsplug::util::truncate;
+use crate::progress::{ProgressEvent, ProgressEvents};
+use crate::redact::redact;
+use crate::rsplug::util::{format_bytes, truncate};
 
 pub enum Message {
     ConfigFound(PathBuf),
@@ -61,6 +69,29 @@ pub enum Message {
     PluginInstalled(Arc<str>),
     /// `dotgit=true` なのに snapshot に `.git` が無いプラグイン（表示名）。
     PluginDotgitMissing(Arc<str>),
+    /// `--force`/`allow_dirty` でローカル変更のある snapshot を置き去りにして更新したプラグイン。
+    PluginDirtyDiscarded(Arc<str>),
+    /// `compat.nvim` を満たさず（またはそれに依存して）読み込まなかったプラグインと理由。
+    PluginIncompatible {
+        id: String,
+        reason: String,
+    },
+    /// 遅延トリガを持つプラグインが、起動時に読まれる依存元 `dependent` のために起動時に
+    /// 読まれる。`triggers` は効かなくなったトリガ。
+    LazyTriggersIgnored {
+        plugin: String,
+        dependent: String,
+        triggers: Vec<String>,
+    },
+    /// Neovim のバージョンを判別できず、`compat` を検査しなかった。
+    NvimVersionUnknown {
+        reason: String,
+    },
+    /// spawn したタスクが panic した。`payload` は panic の内容（cancel ならその旨）。
+    TaskPanicked {
+        context: &'static str,
+        payload: String,
+    },
     MergeFinished {
         total: usize,
         merged: usize,
@@ -70,12 +101,16 @@ pub enum Message {
     GraphQLBatchFailed {
         reason: String,
     },
+    /// GitHub API のレートリミットに達し、残りの rev 解決を git protocol に切り替えた。
+    GitHubRateLimited,
     /// GraphQL rev 解決の進捗（resolved/total リポジトリ）。resolved>=total で完了。
     GraphQLResolveProgress {
         resolved: usize,
         total: usize,
     },
     InstallSkipped(Arc<str>),
+    /// `--sparse`: パッケージをスタブだけで公開した。中身は初回の読み込みで配置される。
+    InstallDeferred(Arc<str>),
     InstallYank {
         id: Arc<str>,
         which: PathBuf,
@@ -83,15 +118,278 @@ pub enum Message {
     InstallHelp {
         help_dir: PathBuf,
     },
+    /// 新しいパッケージの配置が終わった。配置にかかった時間と、reflink（`clonefile` /
+    /// `FICLONE`）で配置したバイト数・ディレクトリ数、内容複製したバイト数。
+    InstallPlaced {
+        elapsed: Duration,
+        cloned_bytes: u64,
+        cloned_dirs: u64,
+        copied_bytes: u64,
+    },
     InstallDone,
+    /// パッケージ外を指す（絶対パス・`..` を含む）ため配置しなかったエントリ。
+    InstallRejectedPath {
+        id: Arc<str>,
+        path: PathBuf,
+    },
+    /// 公開した generation で、複数のパッケージが異なる内容の同じ Lua モジュールを持つ。
+    /// `require` はそのうち 'runtimepath' で先に見つかる 1 つしか読まない。
+    ModuleCollision {
+        module: String,
+        plugins: Vec<String>,
+    },
+    /// 別々のパッケージが同じ `on_cmd` のコマンドや同じ `on_map` のマッピングを登録した。
+    /// どちらも読み込まれるが、後から定義した側が先のコマンド・マッピングを上書きする。
+    DuplicateTrigger {
+        trigger: String,
+        plugins: Vec<String>,
+    },
+    /// lazy パッケージに、読み込みにつながるトリガーが 1 つも残っていない。
+    /// `plugin` は設定上の名前（併合されたものは `, ` で結合）。
+    PluginUnloadable {
+        plugin: String,
+    },
+    /// `--record`: リモートでの解決・取得の結果を書き出した。
+    RunRecorded {
+        path: PathBuf,
+    },
+    /// `rsplug emit-lua`: 生成した Lua ローダを書き出した。
+    LuaEmitted {
+        dir: PathBuf,
+        files: usize,
+    },
+    /// `diff-loader`: 生成したローダと公開中のローダで内容の異なるファイル数。
+    LoaderDiffed {
+        files: usize,
+    },
+    /// `rsplug plan`・`--dry-run`: 予定を表示した。用意する snapshot、配置するパッケージ、外すパッケージの数。
+    RunPlanned {
+        snapshots: usize,
+        packages: usize,
+        removed: usize,
+    },
+    /// `rsplug clean`・`sync`: 設定でも lock でも使われていない repo の cache を消した数
+    /// （`dry_run` なら消す予定の数）。
+    CacheCleaned {
+        repositories: usize,
+        dry_run: bool,
+    },
+    /// `rsplug clean`: 公開中の世代から辿れない `pack/_gen` のパッケージを消した数
+    /// （`dry_run` なら消す予定の数）。
+    PackagesPruned {
+        packages: usize,
+        dry_run: bool,
+    },
+    /// `rsplug remove --purge`: 他のエントリや lock がまだ使うため消さなかった repo の cache
+    /// （`repos` 相対）。
+    PurgeSkipped {
+        repository: PathBuf,
+    },
+    /// `--fetch-only` により pack の生成と install を省いた。
+    PackSkipped,
+    /// `--reload`: 起動中の Neovim にローダを読み込み直させた結果。失敗しても同期は成功扱い。
+    NvimReloaded {
+        server: String,
+        error: Option<String>,
+    },
+    /// Ctrl-C で中断した。進捗表示を消して中断を知らせる。
+    Interrupted,
+    /// `rsplug daemon`: 次の同期を始める。前回の同期の進捗表示と集計を捨てる。
+    SyncBegin,
+    /// テンプレートディレクトリの差し替えを使う。
+    TemplateOverridden(PathBuf),
+    /// 版が合わない・差し替えられないため無視したテンプレート。
+    TemplateOverrideIgnored {
+        path: PathBuf,
+        reason: String,
+    },
+    /// `--verbose-install`: 公開されたユーザパッケージとその設定上の名前。
+    InstallPackage {
+        id: Arc<str>,
+        names: Vec<String>,
+    },
     Error(Box<dyn std::error::Error + 'static + Send + Sync>),
 }
 
-type MessageSender = RwLock<Option<mpsc::UnboundedSender<Message>>>;
-type LoggerCloser = Mutex<mpsc::UnboundedReceiver<()>>;
-type Logger = (MessageSender, LoggerCloser);
+impl Message {
+    /// 後続の同種メッセージで上書きされる進捗。channel が満杯なら捨ててよい。
+    fn is_progress(&self) -> bool {
+        matches!(
+            self,
+            Message::CacheFetchObjectsProgress { .. }
+                | Message::CacheBuildProgress { .. }
+                | Message::GraphQLResolveProgress { .. }
+        )
+    }
+}
+
+/// `Copied` の要約に添える配置の時間と方式。例: `in 0.41s · 23.5 MiB cloned, 1.2 MiB copied`。
+/// 小さいファイルは content store でまとめて書くので、バイト数は大きいファイルの分だけ。
+fn placement_summary(
+    elapsed: Duration,
+    cloned_bytes: u64,
+    cloned_dirs: u64,
+    copied_bytes: u64,
+) -> String {
+    let mut ways = Vec::new();
+    if cloned_dirs != 0 {
+        let unit = if cloned_dirs == 1 {
+            "directory"
+        } else {
+            "directories"
+        };
+        ways.push(format!("{cloned_dirs} {unit} cloned"));
+    }
+    if cloned_bytes != 0 {
+        ways.push(format!("{} cloned", format_bytes(cloned_bytes)));
+    }
+    if copied_bytes != 0 {
+        ways.push(format!("{} copied", format_bytes(copied_bytes)));
+    }
+    let mut summary = format!("in {:.2}s", elapsed.as_secs_f64());
+    if !ways.is_empty() {
+        summary.push_str(" · ");
+        summary.push_str(&ways.join(", "));
+    }
+    summary
+}
 
-static LOGGER: Lazy<Logger> = Lazy::new(init);
+/// 描画タスクへの channel の容量。
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// 描画タスクへ送るもの。
+enum Envelope {
+    Message(Message),
+    /// それより前に送られたメッセージをすべて描画してから応答する。
+    Flush(oneshot::Sender<()>),
+    /// それより前に送られたメッセージをすべて描画してから応答し、描画タスクを止める。
+    Close(oneshot::Sender<()>),
+}
+
+/// ログの送り先。`Message` を bounded channel で描画タスク（`ProgressManager`）へ送る。
+///
+/// channel が満杯のときは次のように振る舞う。
+/// - 進捗（`Message::is_progress`）は捨てる。次の進捗が上書きするため。
+/// - それ以外は空くまで送信側が待つ。multi-thread ランタイムのワーカーでは
+///   `block_in_place` で他のタスクを逃がしてから、ランタイム外ではそのまま待つ。
+///   current-thread ランタイムでは描画タスクが同じスレッドで動くため待てず、捨てる。
+///
+/// 描画タスクは各メッセージを [`ProgressEvent`] にも変換し、`subscribe_progress` の購読者へ流す。
+/// `print_progress_json` の後は JSON 行として標準出力へも書く。
+///
+/// `close` の後に送ったメッセージは捨てる。
+#[derive(Clone)]
+pub struct Logger {
+    tx: mpsc::Sender<Envelope>,
+    events: broadcast::Sender<ProgressEvent>,
+    print_json: Arc<AtomicBool>,
+}
+
+impl Logger {
+    /// `draw_target` へ描画するタスクを現在の tokio ランタイムで起動する。
+    pub fn spawn(draw_target: ProgressDrawTarget) -> Self {
+        Self::with_capacity(draw_target, CHANNEL_CAPACITY)
+    }
+
+    fn with_capacity(draw_target: ProgressDrawTarget, capacity: usize) -> Self {
+        let (tx, mut rx) = mpsc::channel(capacity);
+        // FetchDoneIdle（300ms遅延）はタイマーのタスクから届くので別の channel にする。
+        // メイン channel と同じ sender を manager に持たせると、全 Logger を drop しても
+        // rx が閉じない。
+        let (idle_tx, mut idle_rx) = mpsc::channel::<Message>(capacity);
+        let (events, _) = broadcast::channel(capacity);
+        let print_json = Arc::new(AtomicBool::new(false));
+        tokio::spawn({
+            let events = events.clone();
+            let print_json = Arc::clone(&print_json);
+            async move {
+                let mut manager = ProgressManager::build(draw_target, Some(idle_tx));
+                let mut converter = ProgressEvents::default();
+                let done = loop {
+                    tokio::select! {
+                        envelope = rx.recv() => match envelope {
+                            Some(Envelope::Message(msg)) => {
+                                if let Some(event) = converter.event(&msg) {
+                                    if print_json.load(Ordering::Relaxed) {
+                                        println!("{}", event.to_json());
+                                    }
+                                    // 購読者がいなければ捨てる。
+                                    let _ = events.send(event);
+                                }
+                                manager.process(msg);
+                            }
+                            Some(Envelope::Flush(done)) => {
+                                let _ = done.send(());
+                            }
+                            Some(Envelope::Close(done)) => break Some(done),
+                            // Logger がすべて drop された。
+                            None => break None,
+                        },
+                        // idle channel は manager が idle_tx を保持するため自力では閉じない。
+                        Some(msg) = idle_rx.recv() => manager.process(msg),
+                    }
+                };
+                if let Some(done) = done {
+                    let _ = done.send(());
+                }
+            }
+        });
+        Self {
+            tx,
+            events,
+            print_json,
+        }
+    }
+
+    /// 以後の [`ProgressEvent`] を受け取る。遅れた購読者は古いイベントを取りこぼす。
+    pub fn subscribe_progress(&self) -> broadcast::Receiver<ProgressEvent> {
+        self.events.subscribe()
+    }
+
+    /// `--progress-json`: 以後の [`ProgressEvent`] を JSON 行として標準出力へ書く。
+    pub fn print_progress_json(&self) {
+        self.print_json.store(true, Ordering::Relaxed);
+    }
+
+    /// メッセージを送る。channel が満杯のときの扱いは [`Logger`] を参照。
+    pub fn send(&self, message: Message) {
+        match self.tx.try_send(Envelope::Message(message)) {
+            Ok(()) | Err(TrySendError::Closed(_)) => {}
+            Err(TrySendError::Full(Envelope::Message(message))) if message.is_progress() => {}
+            Err(TrySendError::Full(envelope)) => self.send_waiting(envelope),
+        }
+    }
+
+    fn send_waiting(&self, envelope: Envelope) {
+        match tokio::runtime::Handle::try_current().map(|handle| handle.runtime_flavor()) {
+            Ok(RuntimeFlavor::MultiThread) => tokio::task::block_in_place(|| {
+                let _ = self.tx.blocking_send(envelope);
+            }),
+            Ok(_) => {}
+            Err(_) => {
+                let _ = self.tx.blocking_send(envelope);
+            }
+        }
+    }
+
+    /// それまでに送られたメッセージを描画し終えるまで待つ。
+    pub async fn flush(&self) {
+        let (done_tx, done_rx) = oneshot::channel();
+        if self.tx.send(Envelope::Flush(done_tx)).await.is_ok() {
+            let _ = done_rx.await;
+        }
+    }
+
+    /// それまでに送られたメッセージを描画し終えるまで待ち、描画タスクを止める。
+    pub async fn close(&self) {
+        let (done_tx, done_rx) = oneshot::channel();
+        if self.tx.send(Envelope::Close(done_tx)).await.is_ok() {
+            let _ = done_rx.await;
+        }
+    }
+}
+
+static LOGGER: Lazy<Logger> = Lazy::new(|| Logger::spawn(ProgressDrawTarget::stderr()));
 
 const GRAPHQL_PROGRESS_ID: &str = "graphql_resolve";
 const CACHE_FETCH_PROGRESS_ID: &str = "cache_fetch_progress";
@@ -330,7 +628,10 @@ struct ProgressManager {
     // State
     progress_bars: HashMap<String, BarState>,
     installskipped_count: usize,
+    installdeferred_count: usize,
     yankfile_count: usize,
+    /// `Copied` の要約に添える配置の時間と方式（[`placement_summary`]）。
+    placement: Option<String>,
     not_installed: Vec<Arc<str>>,
     /// `-u` で実際に rev が変わった（更新された）プラグインの表示名。
     updated_plugins: Vec<Arc<str>>,
@@ -338,6 +639,7 @@ struct ProgressManager {
     installed_plugins: Vec<Arc<str>>,
     /// `dotgit=true` なのに `.git` がなく、pack へ copy できないプラグインの表示名。
     dotgit_missing: Vec<Arc<str>>,
+    dirty_discarded: Vec<Arc<str>>,
     cachefetching_oids: HashMap<String, (usize, usize)>,
     cache_updating_fetching: HashMap<String, ()>,
     cache_updating_current: Option<String>,
@@ -364,9 +666,9 @@ struct ProgressManager {
     loading_running: Option<Arc<AtomicUsize>>,
     loading_running_count: usize,
     /// fetched 行の300ms空欄化タイマー用の sender。
-    /// 本番は `init` が注入、テストは None（タイマー不起動、`FetchDoneIdle` を直接
+    /// 本番は `Logger::spawn` が注入、テストは None（タイマー不起動、`FetchDoneIdle` を直接
     /// `process` に送って検証）。グローバル LOGGER に依存しないことで単体テストを可能にする。
-    idle_tx: Option<mpsc::UnboundedSender<Message>>,
+    idle_tx: Option<mpsc::Sender<Message>>,
 }
 
 struct BarState {
@@ -384,6 +686,10 @@ impl BarState {
 
     fn set_message_if_changed(&mut self, message: impl Into<String>) {
         let message = message.into();
+        let message = match redact(&message) {
+            Cow::Borrowed(_) => message,
+            Cow::Owned(masked) => masked,
+        };
         if self.last_message.as_deref() == Some(message.as_str()) {
             return;
         }
@@ -508,17 +814,21 @@ impl ProgressManager {
         Self::build(ProgressDrawTarget::stderr(), None)
     }
 
-    /// 描画先を注入可能にしたコンストラクタ（検証用）。本番は `init` が
+    /// 描画先を注入可能にしたコンストラクタ（検証用）。本番は `Logger::spawn` が
     /// `build(stderr, Some(tx))` を呼ぶ。テストはタイマー sender 無し。
     #[cfg(test)]
     fn with_draw_target(draw_target: ProgressDrawTarget) -> Self {
         Self::build(draw_target, None)
     }
 
-    fn build(
-        draw_target: ProgressDrawTarget,
-        idle_tx: Option<mpsc::UnboundedSender<Message>>,
-    ) -> Self {
+    fn build(draw_target: ProgressDrawTarget, idle_tx: Option<mpsc::Sender<Message>>) -> Self {
+        let multipb = MultiProgress::new();
+        multipb.set_draw_target(draw_target);
+        Self::with_multi_progress(multipb, idle_tx)
+    }
+
+    /// 描画先を設定済みの `multipb` で、1 回の同期分の状態を作る。
+    fn with_multi_progress(multipb: MultiProgress, idle_tx: Option<mpsc::Sender<Message>>) -> Self {
         // ツリー罫線で階層を表現する:
         //   Loading は親（レベル0）、Fetching/Building/Updating は子（レベル1）、
         //   フェッチオブジェクト進捗は孫（レベル2）。
@@ -567,8 +877,6 @@ impl ProgressManager {
         // prefix を自前で色付けする（✓/✗ 付きの持続サマリー行用）。テキストはそのまま通る。
         let pb_style_summary = ProgressStyle::with_template("{prefix} {wide_msg}").unwrap();
 
-        let multipb = MultiProgress::new();
-        multipb.set_draw_target(draw_target);
         let mut barstate = BarState::new(
             multipb.add(
                 ProgressBar::no_length()
@@ -591,11 +899,14 @@ impl ProgressManager {
             pb_style_summary,
             progress_bars: HashMap::from([("config_files".to_string(), barstate)]),
             installskipped_count: 0,
+            installdeferred_count: 0,
             yankfile_count: 0,
+            placement: None,
             not_installed: Vec::new(),
             updated_plugins: Vec::new(),
             installed_plugins: Vec::new(),
             dotgit_missing: Vec::new(),
+            dirty_discarded: Vec::new(),
             cachefetching_oids: HashMap::new(),
             cache_updating_fetching: HashMap::new(),
             cache_updating_current: None,
@@ -842,7 +1153,7 @@ impl ProgressManager {
         if let Some(tx) = self.idle_tx.clone() {
             tokio::spawn(async move {
                 tokio::time::sleep(Duration::from_millis(300)).await;
-                let _ = tx.send(Message::FetchDoneIdle { idle_gen });
+                let _ = tx.send(Message::FetchDoneIdle { idle_gen }).await;
             });
         }
 
@@ -885,6 +1196,11 @@ impl ProgressManager {
         self.fetch_done_idle_gen = self.fetch_done_idle_gen.wrapping_add(1);
     }
 
+    /// 認証情報を伏せて（[`redact`]）バーの上に 1 行（または複数行のブロック）を印字する。
+    fn println(&self, line: impl AsRef<str>) {
+        self.multipb.println(redact(line.as_ref())).unwrap();
+    }
+
     /// ヘッダー行と名前一覧（`ellipsis_names`）から2行ブロックを組み立てて印字する。
     /// `warn_not_installed` / `print_plugin_list_block` の共通の描画本体。
     fn print_name_block(&self, header: String, names: &[Arc<str>]) {
@@ -894,7 +1210,7 @@ impl ProgressManager {
         } else {
             format!("{header}\n    {}", style(body).dim())
         };
-        self.multipb.println(block).unwrap();
+        self.println(block);
     }
 
     /// フラグなし実行でキャッシュが無くロードできなかったプラグインの警告を印字。
@@ -919,6 +1235,16 @@ impl ProgressManager {
         self.print_name_block(header, &self.dotgit_missing);
     }
 
+    /// ローカル変更を置き去りにして更新したプラグインの警告（旧 snapshot に変更は残る）。
+    fn warn_dirty_discarded(&self) {
+        let header = format!(
+            "{} {} plugins updated past local modifications",
+            style("⚠").yellow().bold(),
+            self.dirty_discarded.len()
+        );
+        self.print_name_block(header, &self.dirty_discarded);
+    }
+
     /// 更新/新規インストールされたプラグインのサマリーブロックを印字。
     /// `warn_not_installed` と同じ体裁（個別20字 truncate・先頭3件・超過は ` …`）。
     fn print_plugin_list_block(&self, label: &str, names: &[Arc<str>]) {
@@ -951,7 +1277,7 @@ impl ProgressManager {
                 // MultiProgress::println は orphan 行として、後続バーの描画領域より上に
                 // 永続出力する。各行を独立して渡し、行末カーソル位置も確実に同期する。
                 for line in display.render_lines() {
-                    self.multipb.println(style(line).dim().to_string()).unwrap();
+                    self.println(style(line).dim().to_string());
                 }
             }
             Message::MergeFinished { total, merged } => {
@@ -978,15 +1304,16 @@ impl ProgressManager {
                 if !self.not_installed.is_empty() {
                     self.warn_not_installed();
                 }
+                if !self.dirty_discarded.is_empty() {
+                    self.warn_dirty_discarded();
+                }
                 if !self.updated_plugins.is_empty() {
                     self.print_plugin_list_block("Updated", &self.updated_plugins);
                 }
                 if !self.installed_plugins.is_empty() {
                     self.print_plugin_list_block("Installed", &self.installed_plugins);
                 }
-                self.multipb
-                    .println(format!("{} {message}", summary_prefix("Loaded", true)))
-                    .unwrap();
+                self.println(format!("{} {message}", summary_prefix("Loaded", true)));
             }
             Message::Cache(r#type, url) => {
                 if r#type == "Fetching" || r#type == "Initializing" {
@@ -1236,23 +1563,227 @@ impl ProgressManager {
             Message::PluginDotgitMissing(id) => {
                 self.dotgit_missing.push(id);
             }
+            Message::PluginDirtyDiscarded(id) => {
+                self.dirty_discarded.push(id);
+            }
             Message::DetectLockFile(path) => {
-                self.multipb
-                    .println(format!(
-                        "{} {}",
-                        summary_prefix("Lockfile", true),
-                        style(path.to_string_lossy()).dim()
-                    ))
-                    .unwrap();
+                self.println(format!(
+                    "{} {}",
+                    summary_prefix("Lockfile", true),
+                    style(path.to_string_lossy()).dim()
+                ));
             }
             Message::GraphQLBatchFailed { reason } => {
-                self.multipb
-                    .println(format!(
-                        "{} batch failed ({}); falling back to per-repo resolution",
-                        summary_prefix("GraphQL", false),
+                self.println(format!(
+                    "{} batch failed ({}); falling back to per-repo resolution",
+                    summary_prefix("GraphQL", false),
+                    reason
+                ));
+            }
+            Message::PluginIncompatible { id, reason } => {
+                self.println(format!(
+                    "{} {} {}",
+                    summary_prefix("Skipped", false),
+                    id,
+                    style(reason).dim()
+                ));
+            }
+            Message::LazyTriggersIgnored {
+                plugin,
+                dependent,
+                triggers,
+            } => {
+                self.println(format!(
+                    "{} {} loads at startup because {} depends on it; ignored: {}",
+                    summary_prefix("Startup", false),
+                    plugin,
+                    dependent,
+                    triggers.join(", ")
+                ));
+            }
+            Message::NvimVersionUnknown { reason } => {
+                self.println(format!(
+                        "{} version unknown ({}); `compat` is not checked and `when.nvim` entries are left out",
+                        summary_prefix("Neovim", false),
                         reason
-                    ))
-                    .unwrap();
+                    ));
+            }
+            Message::TaskPanicked { context, payload } => {
+                self.println(format!(
+                    "{} {} panicked: {}",
+                    summary_prefix("Task", false),
+                    context,
+                    style(payload).dim()
+                ));
+            }
+            Message::GitHubRateLimited => {
+                self.println(format!(
+                    "{} API rate limit reached; resolving the remaining repositories over git",
+                    summary_prefix("GitHub", false),
+                ));
+            }
+            Message::InstallRejectedPath { id, path } => {
+                self.println(format!(
+                    "{} {} escapes package {}; not installed",
+                    summary_prefix("Rejected", false),
+                    path.display(),
+                    style(id).dim()
+                ));
+            }
+            Message::ModuleCollision { module, plugins } => {
+                self.println(format!(
+                    "{} {} is provided with different content by {}; require loads only one",
+                    summary_prefix("Module", false),
+                    module,
+                    plugins.join(" · ")
+                ));
+            }
+            Message::DuplicateTrigger { trigger, plugins } => {
+                self.println(format!(
+                    "{} {} is registered by {}; they overwrite each other when loaded",
+                    summary_prefix("Trigger", false),
+                    trigger,
+                    plugins.join(" · ")
+                ));
+            }
+            Message::PluginUnloadable { plugin } => {
+                self.println(format!(
+                    "{} {} has no trigger that can load it; add one or set `start = true`",
+                    summary_prefix("Unloadable", false),
+                    plugin
+                ));
+            }
+            Message::RunRecorded { path } => {
+                self.println(format!(
+                    "{} network results to {}",
+                    summary_prefix("Recorded", true),
+                    path.display()
+                ));
+            }
+            Message::LuaEmitted { dir, files } => {
+                self.println(format!(
+                    "{} {} generated files to {}",
+                    summary_prefix("Emitted", true),
+                    files,
+                    dir.display()
+                ));
+            }
+            Message::LoaderDiffed { files: 0 } => {
+                self.println(format!(
+                    "{} matches the installed pack",
+                    summary_prefix("Loader", true)
+                ));
+            }
+            Message::LoaderDiffed { files } => {
+                self.println(format!(
+                    "{} {} files differ from the installed pack",
+                    summary_prefix("Loader", false),
+                    files
+                ));
+            }
+            Message::RunPlanned {
+                snapshots,
+                packages,
+                removed,
+            } => {
+                self.println(format!(
+                    "{} {snapshots} snapshots to create, {packages} packages to place, and {removed} to remove; nothing was changed",
+                    summary_prefix("Planned", true)
+                ));
+            }
+            Message::CacheCleaned {
+                repositories: 0, ..
+            } => {
+                self.println(format!(
+                    "{} no cached repositories are unused",
+                    summary_prefix("Cleaned", true)
+                ));
+            }
+            Message::CacheCleaned {
+                repositories,
+                dry_run: true,
+            } => {
+                self.println(format!(
+                    "{} {repositories} unused cached repositories would be removed",
+                    summary_prefix("Clean", true)
+                ));
+            }
+            Message::CacheCleaned {
+                repositories,
+                dry_run: false,
+            } => {
+                self.println(format!(
+                    "{} {repositories} unused cached repositories",
+                    summary_prefix("Removed", true)
+                ));
+            }
+            Message::PurgeSkipped { repository } => {
+                self.println(format!(
+                    "{} {} is still used by the config files or the lockfile",
+                    summary_prefix("Kept", false),
+                    repository.display()
+                ));
+            }
+            Message::PackagesPruned { packages: 0, .. } => {}
+            Message::PackagesPruned {
+                packages,
+                dry_run: true,
+            } => {
+                self.println(format!(
+                    "{} {packages} unused packages would be removed",
+                    summary_prefix("Clean", true)
+                ));
+            }
+            Message::PackagesPruned {
+                packages,
+                dry_run: false,
+            } => {
+                self.println(format!(
+                    "{} {packages} unused packages",
+                    summary_prefix("Removed", true)
+                ));
+            }
+            Message::PackSkipped => {
+                self.println(format!(
+                    "{} pack generation and install (--fetch-only)",
+                    summary_prefix("Skipped", true)
+                ));
+            }
+            Message::NvimReloaded {
+                server,
+                error: None,
+            } => {
+                self.println(format!(
+                    "{} the loader in {}",
+                    summary_prefix("Reloaded", true),
+                    server
+                ));
+            }
+            Message::NvimReloaded {
+                server,
+                error: Some(error),
+            } => {
+                self.println(format!(
+                    "{} could not reload {}: {}",
+                    summary_prefix("Reloaded", false),
+                    server,
+                    error
+                ));
+            }
+            Message::TemplateOverridden(path) => {
+                self.println(format!(
+                    "{} using {}",
+                    summary_prefix("Template", true),
+                    path.display()
+                ));
+            }
+            Message::TemplateOverrideIgnored { path, reason } => {
+                self.println(format!(
+                    "{} {} ignored: {}",
+                    summary_prefix("Template", false),
+                    path.display(),
+                    reason
+                ));
             }
             Message::GraphQLResolveProgress { resolved, total } => {
                 if total == 0 {
@@ -1296,6 +1827,21 @@ impl ProgressManager {
                     });
                 pb.set_message_if_changed(format!("{}", style(id).italic().dim()));
             }
+            Message::InstallDeferred(id) => {
+                self.installdeferred_count += 1;
+                let pb = self
+                    .progress_bars
+                    .entry("install_deferred".to_string())
+                    .or_insert_with(|| {
+                        let bar = self.multipb.add(
+                            ProgressBar::no_length()
+                                .with_style(self.pb_style.clone())
+                                .with_prefix("Deferred"),
+                        );
+                        BarState::new(bar)
+                    });
+                pb.set_message_if_changed(format!("{}", style(id).italic().dim()));
+            }
             Message::InstallYank { id, which: file } => {
                 self.yankfile_count += 1;
                 self.osc94
@@ -1332,8 +1878,22 @@ impl ProgressManager {
                     });
                 pb.set_message_if_changed(help_dir.to_string_lossy().into_owned());
             }
+            Message::InstallPlaced {
+                elapsed,
+                cloned_bytes,
+                cloned_dirs,
+                copied_bytes,
+            } => {
+                self.placement = Some(placement_summary(
+                    elapsed,
+                    cloned_bytes,
+                    cloned_dirs,
+                    copied_bytes,
+                ));
+            }
             Message::InstallDone => {
                 drop(self.osc94.take());
+                let placement = self.placement.take();
                 if let Some(pb) = self.progress_bars.remove("install_skipped") {
                     pb.bar.set_style(self.pb_style_summary.clone());
                     if self.installskipped_count != 0 {
@@ -1344,12 +1904,25 @@ impl ProgressManager {
                         pb.bar.finish_and_clear();
                     }
                 }
+                if let Some(pb) = self.progress_bars.remove("install_deferred") {
+                    pb.bar.set_style(self.pb_style_summary.clone());
+                    pb.bar.set_prefix(summary_prefix("Deferred", true));
+                    pb.bar.finish_with_message(format!(
+                        "{} packages (placed on first load)",
+                        self.installdeferred_count
+                    ));
+                }
                 if let Some(pb) = self.progress_bars.remove("install_yank") {
                     pb.bar.set_style(self.pb_style_summary.clone());
                     if self.yankfile_count != 0 {
                         pb.bar.set_prefix(summary_prefix("Copied", true));
-                        pb.bar
-                            .finish_with_message(format!("{} files", self.yankfile_count));
+                        let placement = placement
+                            .map(|placement| format!(" {}", style(placement).dim()))
+                            .unwrap_or_default();
+                        pb.bar.finish_with_message(format!(
+                            "{} files{placement}",
+                            self.yankfile_count
+                        ));
                     } else {
                         pb.bar.finish_and_clear();
                     }
@@ -1358,68 +1931,79 @@ impl ProgressManager {
                     self.warn_dotgit_missing();
                 }
             }
+            Message::InstallPackage { id, names } => {
+                let names = if names.is_empty() {
+                    style("(unnamed)").dim().to_string()
+                } else {
+                    names.join(", ")
+                };
+                self.println(format!(
+                    "{} {} {} {}",
+                    summary_prefix("Package", true),
+                    names,
+                    style("→").dim(),
+                    style(format!("opt/{id}")).dim()
+                ));
+            }
+            Message::Interrupted => {
+                for (_, pb) in std::mem::take(&mut self.progress_bars) {
+                    pb.bar.finish_and_clear();
+                }
+                if let Some(pb) = self.updating_bar.take() {
+                    pb.bar.finish_and_clear();
+                }
+                let _ = self.multipb.clear();
+                eprintln!(
+                    "{} interrupted; unfinished checkouts and staged packages were discarded",
+                    style("error:").red().bold()
+                );
+            }
+            Message::SyncBegin => {
+                for (_, pb) in std::mem::take(&mut self.progress_bars) {
+                    pb.bar.finish_and_clear();
+                }
+                if let Some(pb) = self.updating_bar.take() {
+                    pb.bar.finish_and_clear();
+                }
+                // 世代は引き継ぐ。前回の同期の FetchDoneIdle が新しい fetched 行を消さないように。
+                let idle_gen = self.fetch_done_idle_gen;
+                *self = Self::with_multi_progress(self.multipb.clone(), self.idle_tx.take());
+                self.fetch_done_idle_gen = idle_gen;
+            }
             Message::Error(e) => {
                 // To prevent flicker with other progress bars, suspend drawing.
                 self.multipb.suspend(|| {
-                    eprintln!("{} {e}", style("error:").red().bold());
+                    eprintln!(
+                        "{} {}",
+                        style("error:").red().bold(),
+                        redact(&e.to_string())
+                    );
                 });
             }
         }
     }
 }
 
-fn init() -> Logger {
-    let (tx, mut rx) = mpsc::unbounded_channel();
-    let (tx_end, rx_end) = mpsc::unbounded_channel::<()>();
-    // FetchDoneIdle（300ms遅延）用にメイン channel とは別の channel を持つ。
-    // メインと同じ sender を manager に持たせると、close() が sender を drop しても
-    // manager 内の clone が生きて rx が閉じず、受信ループが抜けず終了しなくなる。
-    let (idle_tx, mut idle_rx) = mpsc::unbounded_channel::<Message>();
-    tokio::spawn(async move {
-        let mut manager = ProgressManager::build(ProgressDrawTarget::stderr(), Some(idle_tx));
-        loop {
-            // メイン channel が閉じたら（close 呼出）即座に抜ける。
-            // idle channel は manager が idle_tx を保持するため自力では閉じない。
-            tokio::select! {
-                msg = rx.recv() => match msg {
-                    Some(msg) => manager.process(msg),
-                    None => break,
-                },
-                msg = idle_rx.recv() => {
-                    if let Some(msg) = msg {
-                        manager.process(msg);
-                    }
-                }
-            }
-        }
-        let _ = tx_end.send(());
-    });
-    (Some(tx).into(), rx_end.into())
+/// プロセス全体の logger。`msg` と `close` はこれを使う。
+pub fn logger() -> &'static Logger {
+    &LOGGER
 }
 
 /// Output log messages
 pub fn msg(message: Message) {
-    let _ = LOGGER
-        .0
-        .read()
-        .unwrap()
-        .as_ref() // 下記closeなどで破棄された際はLOGGERが取得できなくなるので、その時はmessageを揉み消す
-        .and_then(|lgr| lgr.send(message).ok());
+    LOGGER.send(message);
+}
+
+/// Flush out the rest of the log
+pub async fn flush() {
+    LOGGER.close().await;
+    let _ = std::io::stdout().flush();
 }
 
 /// Flush out the rest of the log and exit
 pub async fn close(code: i32) -> ! {
-    loop {
-        if let Ok(mut sender) = LOGGER.0.try_write() {
-            let sender: &mut Option<_> = &mut sender;
-            drop(std::mem::take(sender));
-        } else {
-            continue;
-        }
-        LOGGER.1.lock().await.recv().await;
-        std::io::stdout().flush().unwrap();
-        std::process::exit(code);
-    }
+    flush().await;
+    std::process::exit(code);
 }
 
 #[cfg(test)]
@@ -1592,6 +2176,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn placement_summary_names_only_the_ways_used() {
+        let elapsed = Duration::from_millis(412);
+        assert_eq!(placement_summary(elapsed, 0, 0, 0), "in 0.41s");
+        assert_eq!(
+            placement_summary(elapsed, 24_641_536, 0, 1_258_291),
+            "in 0.41s · 23.5 MiB cloned, 1.2 MiB copied"
+        );
+        assert_eq!(
+            placement_summary(elapsed, 0, 3, 0),
+            "in 0.41s · 3 directories cloned"
+        );
+    }
+
+    /// daemon の 2 回目以降の同期: SyncBegin で Config 行と集計が作り直される。
+    #[test]
+    fn sync_begin_resets_state_for_the_next_sync() {
+        let mut m = ProgressManager::new();
+        m.process(Message::ConfigFound(PathBuf::from("/tmp/rsplug/a.toml")));
+        m.process(Message::ConfigWalkFinish);
+        m.process(Message::LoadBegin { total: 1 });
+        m.process(Message::PluginNotInstalled("a".into()));
+
+        m.process(Message::SyncBegin);
+        assert!(m.progress_bars.contains_key("config_files"));
+        assert!(!m.progress_bars.contains_key("loading"));
+        assert!(m.not_installed.is_empty());
+
+        m.process(Message::ConfigFound(PathBuf::from("/tmp/rsplug/a.toml")));
+        m.process(Message::ConfigWalkFinish);
+        assert!(!m.progress_bars.contains_key("config_files"));
+    }
+
     /// `-u` 相当のメッセージ列を流した最終画面に "Loading" が残留しないか検証する。
     /// LoadDone→MergeFinished の間（lockfile 書き出し+merge に相当）に steady_tick が
     /// 回り続けると finish_and_clear と競合して初期フレームが残るため、責務分離で
@@ -1956,6 +2573,56 @@ mod tests {
             .join("\n")
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn close_draws_every_message_sent_before_it() {
+        let (term, screen) = ScreenTermLike::new(120);
+        // 容量 1 なので、ほとんどの send は描画タスクが channel を空けるのを待つ。
+        let logger = Logger::with_capacity(ProgressDrawTarget::term_like(Box::new(term)), 1);
+        for i in 0..20 {
+            logger.send(Message::PluginIncompatible {
+                id: format!("plugin{i}"),
+                reason: "reason".to_string(),
+            });
+        }
+        logger.close().await;
+        let rendered = screen_rendered(&screen);
+        for i in 0..20 {
+            assert!(
+                rendered.contains(&format!("plugin{i} reason")),
+                "plugin{i} should be drawn before close returns; got:\n{rendered}"
+            );
+        }
+        // close 後の送信は待たずに捨てられる。
+        logger.send(Message::InstallDone);
+        logger.close().await;
+    }
+
+    #[test]
+    fn progress_is_dropped_when_the_channel_is_full() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let logger = Logger {
+            tx,
+            events: broadcast::channel(1).0,
+            print_json: Default::default(),
+        };
+        logger.send(Message::GraphQLResolveProgress {
+            resolved: 1,
+            total: 3,
+        });
+        logger.send(Message::GraphQLResolveProgress {
+            resolved: 2,
+            total: 3,
+        });
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(Envelope::Message(Message::GraphQLResolveProgress {
+                resolved: 1,
+                ..
+            }))
+        ));
+        assert!(rx.try_recv().is_err());
+    }
+
     /// child_order を表示順（category_rank）で整列したビュー。検証は整列後で行う
     /// （追加順はバーの再生成時に物理 insert 順とずれるため）。
     fn ordered_children(m: &ProgressManager) -> Vec<ChildKey> {
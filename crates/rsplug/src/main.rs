@@ -1,14 +1,34 @@
+mod changelog;
+mod clean;
+mod context;
+mod daemon;
+mod disk_usage;
+mod glob;
+mod info;
 mod log;
 mod osc94;
+mod plan;
+mod progress;
+mod record;
+mod redact;
+mod reload;
+mod resolve;
 mod rsplug;
+mod sbom;
 mod scheduler;
+mod share;
+mod spec_edit;
+mod status;
+mod validate;
+mod watch_remote;
 
 use clap::Parser;
 use console::style;
+use context::AppContext;
 use log::{Message, close, msg};
-use once_cell::sync::Lazy;
 use rsplug::config_walker::ConfigWalker;
-use scheduler::{LoadCtx, LoadRev, RunMode, run_load_early, run_load_late};
+use rsplug::util::task;
+use scheduler::{LoadCtx, LoadRev, PluginSelection, RunMode, run_load_early, run_load_late};
 use std::{
     collections::{BTreeMap, BinaryHeap, HashMap, HashSet},
     path::PathBuf,
@@ -18,21 +38,93 @@ use std::{
 #[derive(clap::Parser, Debug)]
 #[command(about, version)]
 struct Args {
-    /// Install plugins which are not installed yet
-    #[arg(short, long)]
-    install: bool,
-    /// Access remote and update repositories
-    #[arg(conflicts_with = "locked", short, long)]
-    update: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Update even if cached repositories have local modifications
+    #[arg(long)]
+    force: bool,
     /// Fix the repo version with rev in the lockfile
     #[arg(long)]
     locked: bool,
+    /// Rebuild the pack from the cache and the lockfile only, never touching the network
+    #[arg(long)]
+    offline: bool,
     /// Specify the lockfile path
     #[arg(long)]
     lockfile: Option<PathBuf>,
-    /// Glob-patterns of the config files. Split by ':' to specify multiple patterns
+    /// Write the remote resolutions and fetch locations of this run to DIR/recording.json
+    #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+    /// Rerun from DIR/recording.json without the network, pinning the recorded commits
+    #[arg(long, value_name = "DIR", conflicts_with = "lockfile")]
+    replay: Option<PathBuf>,
+    /// Root directory of local checkouts used by `dev = true` plugins
+    #[arg(long, env = "RSPLUG_DEV_PATH")]
+    dev_path: Option<PathBuf>,
+    /// Merge policy for plugins that do not set `merge` themselves
+    #[arg(long, value_enum, env = "RSPLUG_MERGE", default_value_t)]
+    merge: rsplug::MergePolicy,
+    /// Never merge plugins, ignoring every `merge` setting (debugging)
+    #[arg(long)]
+    no_merge: bool,
+    /// Describe each package in `by-name/<name>/`, print the package of each plugin, and imply
+    /// `--stable-names`
+    #[arg(long)]
+    verbose_install: bool,
+    /// Install unmerged plugins as `opt/<owner>__<repo>` instead of under a content hash
+    #[arg(long)]
+    stable_names: bool,
+    /// Fail instead of warning when plugins register the same lazy command or mapping
+    #[arg(long)]
+    strict: bool,
+    /// Generate a loader with assertions and logging to `_rsplug.log` that does not swallow errors
+    #[arg(long)]
+    debug_loader: bool,
+    /// Experimental: install lazy plugins as stubs and place their files on the first load
+    #[arg(long)]
+    sparse: bool,
+    /// Steps to run on new packages before they are published [default: helptags]
     #[arg(
-        required = true,
+        long,
+        value_name = "STEP",
+        value_enum,
+        env = "RSPLUG_POST_PROCESS",
+        value_delimiter = ','
+    )]
+    post_process: Option<Vec<rsplug::PostStep>>,
+    /// Packpath to install into, as `TARGET=DIR` with TARGET `user` or `system` (repeatable)
+    #[arg(long = "packpath", value_name = "TARGET=DIR")]
+    packpaths: Vec<PackpathSpec>,
+    /// After installing, reload the loader in the Neovim listening on this address (e.g. $NVIM)
+    #[arg(long, value_name = "SERVER")]
+    reload: Option<String>,
+    /// Also write structured progress events to stdout as JSON lines, for UIs
+    #[arg(long)]
+    progress_json: bool,
+    /// Maximum number of config parses, plugin loads, and builds running at once [default: unlimited]
+    #[arg(short, long, env = "RSPLUG_JOBS", value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: Option<u16>,
+    /// Maximum number of build hooks running at once [default: half the CPUs]
+    #[arg(long, env = "RSPLUG_BUILD_JOBS", value_parser = clap::value_parser!(u16).range(1..))]
+    build_jobs: Option<u16>,
+    /// Number of async worker threads [default: the number of CPUs]
+    #[arg(long, env = "RSPLUG_THREADS", value_parser = clap::value_parser!(u16).range(1..))]
+    threads: Option<u16>,
+    /// Maximum number of threads for blocking work such as Git and file copies [default: 512]
+    #[arg(long, env = "RSPLUG_BLOCKING_THREADS", value_parser = clap::value_parser!(u16).range(1..))]
+    blocking_threads: Option<u16>,
+    /// Neovim executable used for `lua_build`, helptags, and version checks
+    #[arg(long, env = "RSPLUG_NVIM")]
+    nvim: Option<PathBuf>,
+    /// Mirror to fetch a host's repositories from, as `HOST=URL` (repeatable; the fastest responder wins)
+    #[arg(long = "mirror", env = "RSPLUG_MIRRORS", value_delimiter = ',')]
+    mirrors: Vec<rsplug::util::mirror::MirrorSpec>,
+    /// Glob-patterns of the config files (repeatable). Split by ':' to specify multiple patterns
+    #[arg(
+        short,
+        long = "config",
+        value_name = "CONFIG_FILES",
+        global = true,
         env = "RSPLUG_CONFIG_FILES",
         value_delimiter = ':',
         hide_env_values = true
@@ -40,6 +132,193 @@ struct Args {
     config_files: Vec<String>,
 }
 
+/// 引数の組み合わせの誤りを clap と同じ形式で報告して終了する。
+fn conflict(message: &str) -> ! {
+    <Args as clap::CommandFactory>::command()
+        .error(clap::error::ErrorKind::ArgumentConflict, message)
+        .exit()
+}
+
+/// 設定ファイルを読むコマンドか。`add`・`remove`・`daemon` は設定ファイルが無いときの
+/// 誤りを自分で報告する。
+fn reads_config_files(command: &Option<Command>) -> bool {
+    !matches!(
+        command,
+        Some(
+            Command::Owners(_)
+                | Command::Materialize(_)
+                | Command::Import(_)
+                | Command::Du(_)
+                | Command::Glob(_)
+                | Command::Sbom(_)
+                | Command::Add(_)
+                | Command::Remove(_)
+                | Command::Daemon(_)
+        )
+    )
+}
+
+/// `--packpath` の `TARGET=DIR`。
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct PackpathSpec {
+    target: rsplug::PackTarget,
+    dir: PathBuf,
+}
+
+impl std::str::FromStr for PackpathSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((target, dir)) = s.split_once('=') else {
+            return Err(format!("expected TARGET=DIR, got `{s}`"));
+        };
+        let target = <rsplug::PackTarget as clap::ValueEnum>::from_str(target.trim(), true)
+            .map_err(|_| format!("unknown packpath target `{target}` (user, system)"))?;
+        if dir.is_empty() {
+            return Err(format!("missing directory for `{s}`"));
+        }
+        Ok(Self {
+            target,
+            dir: PathBuf::from(dir),
+        })
+    }
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Install the repositories not present in the cache, then generate and install the pack
+    Install(RunArgs),
+    /// Fetch newer revisions of the installed repositories, then generate and install the pack
+    Update(RunArgs),
+    /// Install and update in one run, then remove the cache of plugins no longer configured
+    Sync(RunArgs),
+    /// Remove cached repositories that neither the config files nor the lockfile use
+    Clean(clean::CleanArgs),
+    /// Append a plugin entry to a config file, then install it
+    Add(spec_edit::AddArgs),
+    /// Remove a plugin entry from the config files, then resynchronize the pack
+    Remove(spec_edit::RemoveArgs),
+    /// Show which repository and merged package an installed file comes from
+    Owners(OwnersArgs),
+    /// Report disk space used by cached repositories, installed packages, and generated files
+    Du(disk_usage::DuArgs),
+    /// Parse the config files and warn about patterns that can never take effect
+    Validate,
+    /// Print the paths matching glob patterns, using the matcher that finds config files
+    Glob(glob::GlobArgs),
+    /// Generate the loader as usual but only write the generated Lua tree to a directory
+    EmitLua(EmitLuaArgs),
+    /// Print a unified diff between the loader a run would generate and the installed one
+    DiffLoader(DiffLoaderArgs),
+    /// Print what a run with the same options would fetch, build, merge, and place, changing nothing
+    Plan(plan::PlanArgs),
+    /// Print a CycloneDX or SPDX bill of materials of the installed plugins
+    Sbom(sbom::SbomArgs),
+    /// Show a configured plugin's repository, load triggers, and GitHub metadata
+    Info(info::InfoArgs),
+    /// List installed revisions and how far forks are ahead of or behind their `upstream`
+    Status(status::StatusArgs),
+    /// Print the commits and release notes between the locked and the latest revisions
+    Changelog(changelog::ChangelogArgs),
+    /// Check the remotes for revisions newer than the lockfile and report them
+    WatchRemote(watch_remote::WatchRemoteArgs),
+    /// Keep running and serve install/update/status requests on a local socket
+    Daemon(daemon::DaemonArgs),
+    /// Resolve the revisions of all repositories and cache them for the next install, update, or sync
+    Resolve(resolve::ResolveArgs),
+    /// Write the configured plugins, pinned to the locked commits, as one shareable config file
+    Export(share::ExportArgs),
+    /// Write a config file exported by `rsplug export` (file or URL), then install it
+    Import(share::ImportArgs),
+    /// Place the files of a package installed with --sparse (run by the loader on the first load)
+    Materialize(MaterializeArgs),
+}
+
+/// `install`・`update`・`sync` のオプション。
+#[derive(clap::Args, Debug)]
+struct RunArgs {
+    /// Fetch and build the repositories (installing missing ones) without generating or installing the pack
+    #[arg(long)]
+    fetch_only: bool,
+    /// Print what the run would fetch, build, merge, install, and remove, changing nothing
+    #[arg(long, conflicts_with = "fetch_only")]
+    dry_run: bool,
+    /// Only install or update the plugins matching these names or `owner/repo` globs; the others are loaded from the cache as they are
+    #[arg(value_name = "PLUGIN")]
+    plugins: Vec<String>,
+}
+
+/// `plan`・`emit-lua`・`diff-loader` が見積もる実行。どちらも無ければ cache の revision を使う。
+#[derive(clap::Args, Clone, Debug, Default)]
+struct AsRunArgs {
+    /// Resolve as `rsplug install` would, including repositories not present in the cache
+    #[arg(short, long)]
+    install: bool,
+    /// Resolve as `rsplug update` would, asking the remotes for newer revisions
+    #[arg(short, long)]
+    update: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct EmitLuaArgs {
+    /// Directory to write the generated Lua tree to (replaced when it holds a previous output)
+    #[arg(long)]
+    out: PathBuf,
+    #[command(flatten)]
+    run: AsRunArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct DiffLoaderArgs {
+    #[command(flatten)]
+    run: AsRunArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct MaterializeArgs {
+    /// Package directory, `<packpath>/pack/_gen/opt/<id>`
+    dir: PathBuf,
+}
+
+#[derive(clap::Args, Debug)]
+struct OwnersArgs {
+    /// Installed path: absolute, or relative to the packpath (e.g. `opt/<id>/lua/foo.lua`)
+    path: PathBuf,
+}
+
+/// pack 生成（マージ・install）の挙動を決める CLI オプション。
+#[derive(Clone)]
+struct PackOptions {
+    merge: rsplug::MergePolicy,
+    no_merge: bool,
+    verbose_install: bool,
+    /// `--stable-names`: 併合されないパッケージを `<owner>__<repo>` の名前で置く。
+    stable_names: bool,
+    /// `--strict`: 複数のプラグインが同じ遅延コマンド・マッピングを登録していたら中断する。
+    strict: bool,
+    debug_loader: bool,
+    /// `--sparse`: 遅延パッケージをスタブだけで公開し、初回の読み込みで配置する。
+    sparse: bool,
+    /// `--post-process`: 公開前のパッケージに施す後処理。`None` なら既定。
+    post_process: Option<Vec<rsplug::PostStep>>,
+    /// `emit-lua --out`: pack を install せず、生成した Lua だけをここへ書き出す。
+    emit_lua: Option<PathBuf>,
+    /// `diff-loader`: pack を install せず、生成した Lua と公開中のローダの差を表示する。
+    diff_loader: bool,
+    /// `--fetch-only`: repo の取得とビルドで止め、pack の生成・install・lock 更新を行わない。
+    fetch_only: bool,
+    /// `--packpath system=<DIR>`: `target = "system"` のパッケージの置き場所。
+    system_packpath: Option<PathBuf>,
+    /// `--reload`: install 後、このアドレスで待ち受ける Neovim にローダを読み込み直させる。
+    reload: Option<String>,
+    /// `--record <DIR>`: リモートでの解決・取得の結果を DIR に書き出す。
+    record: Option<PathBuf>,
+    /// `plan`: 取得・build・install・lock 更新をせず、run の予定を表示する。
+    plan: Option<plan::PlanArgs>,
+    /// `install`・`update`・`sync` の位置引数: 取得・更新するプラグイン。他はキャッシュのまま読む。
+    select: Option<Arc<PluginSelection>>,
+}
+
 /// EARLY 相の進行状態。EARLY 完了結果（`EarlyOutcome`）を保持する。
 #[allow(clippy::large_enum_variant)]
 enum EarlySlot {
@@ -112,23 +391,453 @@ fn should_resolve_graphql(install: bool, update: bool, installed: bool) -> bool
     (update && installed) || (install && !installed)
 }
 
-async fn app() -> Result<(), Error> {
+async fn app(ctx: &AppContext, args: Args) -> Result<(), Error> {
     let Args {
-        install,
-        update,
+        command,
+        force,
         lockfile,
+        record,
+        replay,
+        dev_path,
+        merge,
+        no_merge,
+        verbose_install,
+        stable_names,
+        strict,
+        debug_loader,
+        sparse,
+        post_process,
+        packpaths,
+        reload,
+        progress_json,
+        jobs,
+        build_jobs,
+        // ランタイムの構築時に使用済み。
+        threads: _,
+        blocking_threads: _,
+        nvim,
+        mirrors,
         locked,
+        offline,
         config_files,
-    } = Args::parse();
-    let mode = RunMode::from_flags(install, update, locked);
-    let lockfile = lockfile.unwrap_or_else(|| DEFAULT_APP_DIR.join("rsplug.lock.json"));
+    } = args;
+    if reads_config_files(&command) && config_files.is_empty() {
+        <Args as clap::CommandFactory>::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "no config files given; pass --config <CONFIG_FILES> or set RSPLUG_CONFIG_FILES",
+            )
+            .exit()
+    }
+    if progress_json {
+        ctx.logger.print_progress_json();
+    }
+    if let Some(jobs) = jobs {
+        rsplug::util::resources::set_jobs(usize::from(jobs));
+    }
+    if let Some(jobs) = build_jobs {
+        rsplug::util::resources::set_build_jobs(usize::from(jobs));
+    }
+    rsplug::util::mirror::configure(mirrors);
+    if let Some(nvim) = nvim {
+        rsplug::util::nvim::set_program(nvim);
+    }
+    let (fetch_only, dry_run, select) = match &command {
+        Some(Command::Install(run) | Command::Update(run) | Command::Sync(run)) => (
+            run.fetch_only,
+            run.dry_run,
+            PluginSelection::new(&run.plugins),
+        ),
+        _ => (false, false, None),
+    };
+    if fetch_only && reload.is_some() {
+        conflict("--reload cannot be used with --fetch-only");
+    }
+    // update はリモートの新しい revision を取りに行くので、revision を固定する指定とは両立しない。
+    let updating = match &command {
+        Some(Command::Update(_)) => Some("`rsplug update`"),
+        Some(Command::Sync(_)) => Some("`rsplug sync`"),
+        Some(Command::EmitLua(EmitLuaArgs { run, .. }))
+        | Some(Command::DiffLoader(DiffLoaderArgs { run }))
+        | Some(Command::Plan(plan::PlanArgs { run, .. }))
+            if run.update =>
+        {
+            Some("--update")
+        }
+        _ => None,
+    };
+    if let Some(updating) = updating {
+        for (pinned, flag) in [
+            (locked, "--locked"),
+            (offline, "--offline"),
+            (replay.is_some(), "--replay"),
+        ] {
+            if pinned {
+                conflict(&format!("{flag} cannot be used with {updating}"));
+            }
+        }
+    }
+    let mut pack = PackOptions {
+        merge,
+        no_merge,
+        verbose_install,
+        stable_names,
+        strict,
+        debug_loader,
+        sparse,
+        post_process,
+        emit_lua: None,
+        diff_loader: false,
+        fetch_only,
+        system_packpath: None,
+        reload,
+        record,
+        // `--dry-run` は `rsplug plan` と同じ予定をテキストで表示する。
+        plan: dry_run.then(plan::PlanArgs::default),
+        select: select.map(Arc::new),
+    };
+    // 同じ TARGET が複数回あれば最後の指定を使う。
+    let mut user_packpath = None;
+    for PackpathSpec { target, dir } in packpaths {
+        match target {
+            rsplug::PackTarget::User => user_packpath = Some(dir),
+            rsplug::PackTarget::System => pack.system_packpath = Some(dir),
+        }
+    }
+    let user_ctx;
+    let ctx = match user_packpath {
+        Some(dir) => {
+            user_ctx = ctx.clone().with_packpath(dir);
+            &user_ctx
+        }
+        None => ctx,
+    };
+    // `--replay` は記録の commit を固定した lock で `--offline` と同じように実行する。
+    let (lockfile, offline) = match &replay {
+        Some(dir) => (record::replay_lockfile(dir).await?, true),
+        None => (lockfile.unwrap_or_else(|| ctx.default_lockfile()), offline),
+    };
+    match command {
+        // 引数が無ければリモートに触れず、cache の revision で pack を作り直す。
+        None => {
+            let mode = RunMode::from_flags(false, false, locked, offline);
+            sync(
+                ctx,
+                mode,
+                force,
+                lockfile,
+                dev_path,
+                pack,
+                config_files,
+                &[],
+            )
+            .await
+        }
+        Some(Command::Install(_)) => {
+            let mode = RunMode::from_flags(true, false, locked, offline);
+            sync(
+                ctx,
+                mode,
+                force,
+                lockfile,
+                dev_path,
+                pack,
+                config_files,
+                &[],
+            )
+            .await
+        }
+        // `--fetch-only` は取得が目的なので、`update` でも未インストール分を取りに行く。
+        Some(Command::Update(run)) => {
+            let mode = RunMode::from_flags(run.fetch_only, true, locked, offline);
+            sync(
+                ctx,
+                mode,
+                force,
+                lockfile,
+                dev_path,
+                pack,
+                config_files,
+                &[],
+            )
+            .await
+        }
+        // `sync` は install と update を 1 回で行い、書き直した lock を見て使われない cache を消す。
+        Some(Command::Sync(_)) => {
+            let mode = RunMode::from_flags(true, true, locked, offline);
+            sync(
+                ctx,
+                mode,
+                force,
+                lockfile.clone(),
+                dev_path,
+                pack,
+                config_files.clone(),
+                &[],
+            )
+            .await?;
+            let args = clean::CleanArgs { dry_run };
+            let removed = clean::clean(&ctx.repo_cache_dir, &lockfile, config_files, &args).await?;
+            // `--dry-run` では予定の続きとして、消す cache も plan と同じ形で並べる。
+            if dry_run {
+                for repository in &removed {
+                    println!("{:<11} {}", "clean", repository.display());
+                }
+            }
+            if !removed.is_empty() {
+                ctx.logger.send(Message::CacheCleaned {
+                    repositories: removed.len(),
+                    dry_run,
+                });
+            }
+            Ok(())
+        }
+        Some(Command::Clean(args)) => {
+            let removed = clean::clean(&ctx.repo_cache_dir, &lockfile, config_files, &args).await?;
+            for repository in &removed {
+                println!("{}", repository.display());
+            }
+            let stagings =
+                clean::clean_stagings(&ctx.repo_cache_dir, &args, clean::STALE_STAGING).await?;
+            for staging in &stagings {
+                println!("{}", staging.display());
+            }
+            let packpaths: Vec<&std::path::Path> = std::iter::once(ctx.packpath())
+                .chain(pack.system_packpath.as_deref())
+                .collect();
+            let pruned = clean::prune_packpaths(&packpaths, &args).await?;
+            for package in &pruned {
+                println!("{}", package.display());
+            }
+            ctx.logger.send(Message::CacheCleaned {
+                repositories: removed.len(),
+                dry_run: args.dry_run,
+            });
+            ctx.logger.send(Message::PackagesPruned {
+                packages: pruned.len(),
+                dry_run: args.dry_run,
+            });
+            Ok(())
+        }
+        // `add` は設定ファイルへ追記してから、追加分を含めて通常の install 実行を行う。
+        Some(Command::Add(add)) => {
+            let config_files =
+                spec_edit::synced_config_files(config_files, add.file.as_deref(), &lockfile)
+                    .await?;
+            let target = match &add.file {
+                Some(file) => file.clone(),
+                None => spec_edit::first_config_file(config_files.clone()).await?,
+            };
+            spec_edit::append_entry(&target, &add).await?;
+            let mode = RunMode::from_flags(true, false, locked, offline);
+            sync(
+                ctx,
+                mode,
+                force,
+                lockfile,
+                dev_path,
+                pack,
+                config_files,
+                &[],
+            )
+            .await
+        }
+        // `remove` はエントリ削除 → pack 再生成 → lock からの除去を 1 つの実行で行う。
+        // 途中で失敗したら設定ファイル・lock・公開中の世代を元に戻し、食い違いを残さない。
+        Some(Command::Remove(remove)) => {
+            let config_files =
+                spec_edit::synced_config_files(config_files, remove.file.as_deref(), &lockfile)
+                    .await?;
+            let removed = spec_edit::remove_entry(&remove, config_files.clone()).await?;
+            let checkpoint = spec_edit::Checkpoint::capture(ctx.packpath(), &lockfile).await?;
+            let mode = RunMode::from_flags(false, false, locked, offline);
+            let forget = [removed.repo.canonical()];
+            if let Err(e) = sync(
+                ctx,
+                mode,
+                force,
+                lockfile.clone(),
+                dev_path,
+                pack,
+                config_files.clone(),
+                &forget,
+            )
+            .await
+            {
+                // 片方の復元が失敗しても、もう片方は必ず試す。
+                let restores = [
+                    (removed.path.display().to_string(), removed.rollback().await),
+                    ("lockfile and pack".to_string(), checkpoint.restore().await),
+                ];
+                return Err(with_rollback_failures(e, restores));
+            }
+            if remove.purge {
+                // 別のエントリ（他の設定の `repo`・`upstream`、lock）が同じ cache を使うなら残す。
+                let cachedir = removed.repo.default_cachedir();
+                if clean::kept_cachedirs(&lockfile, config_files)
+                    .await?
+                    .contains(&cachedir)
+                {
+                    ctx.logger.send(Message::PurgeSkipped {
+                        repository: cachedir,
+                    });
+                } else {
+                    let root = ctx.repo_cache_dir.join(cachedir);
+                    match tokio::fs::remove_dir_all(&root).await {
+                        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                            return Err(e.into());
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Ok(())
+        }
+        // `owners` は最後の install が残した provenance index を引くだけで、同期は行わない。
+        Some(Command::Owners(OwnersArgs { path })) => {
+            let packpath = ctx.packpath();
+            let owners = match rsplug::pack_plan::find_owners(packpath, &path).await {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    return Err(Error::NoProvenanceIndex);
+                }
+                owners => owners?,
+            };
+            if owners.is_empty() {
+                return Err(Error::NotInstalledPath { path });
+            }
+            for owner in owners {
+                println!("{owner}");
+            }
+            Ok(())
+        }
+        // `materialize` は疎に公開したパッケージを配置するだけで、設定は読まない。
+        Some(Command::Materialize(MaterializeArgs { dir })) => {
+            rsplug::pack_plan::materialize(&dir).await?;
+            Ok(())
+        }
+        Some(Command::Resolve(resolve)) => {
+            resolve::resolve(&ctx.app_dir, config_files, &resolve).await
+        }
+        Some(Command::Export(export)) => share::export(&lockfile, config_files, &export).await,
+        // `import` は取り込んだ設定ファイルだけで install する（他の設定ファイルは読まない）。
+        Some(Command::Import(import)) => {
+            share::import(&import).await?;
+            let mode = RunMode::from_flags(true, false, false, offline);
+            sync(
+                ctx,
+                mode,
+                force,
+                lockfile,
+                dev_path,
+                pack,
+                vec![import.to.to_string_lossy().into_owned()],
+                &[],
+            )
+            .await
+        }
+        Some(Command::Du(du)) => disk_usage::print_disk_usage(&ctx.app_dir, &du).await,
+        Some(Command::Validate) => validate::validate(ctx, config_files).await,
+        Some(Command::Glob(args)) => glob::print_glob(&args).await,
+        Some(Command::Sbom(sbom)) => sbom::print_sbom(ctx, &sbom).await,
+        Some(Command::Info(info)) => info::print_info(ctx, config_files, &info).await,
+        Some(Command::Status(status)) => {
+            status::print_status(&ctx.repo_cache_dir, &lockfile, config_files, &status).await
+        }
+        Some(Command::Changelog(changelog)) => {
+            changelog::print_changelog(&ctx.repo_cache_dir, &lockfile, config_files, &changelog)
+                .await
+        }
+        Some(Command::WatchRemote(watch)) => {
+            watch_remote::watch_remote(&lockfile, config_files, &watch).await
+        }
+        // `daemon` は起動時のオプションを覚えておき、要求ごとに同じ同期処理を行う。
+        Some(Command::Daemon(daemon)) => {
+            let options = daemon::SyncOptions {
+                force,
+                locked,
+                offline,
+                lockfile,
+                dev_path,
+                pack,
+                config_files,
+            };
+            daemon::run(ctx, &daemon, options).await
+        }
+        // `emit-lua` は通常と同じ読み込みを行い、pack と lock には触れずに Lua だけを書き出す。
+        Some(Command::EmitLua(EmitLuaArgs { out, run })) => {
+            pack.emit_lua = Some(out);
+            let mode = RunMode::from_flags(run.install, run.update, locked, offline);
+            sync(
+                ctx,
+                mode,
+                force,
+                lockfile,
+                dev_path,
+                pack,
+                config_files,
+                &[],
+            )
+            .await
+        }
+        // `diff-loader` も `emit-lua` と同じく pack と lock には触れない。
+        Some(Command::DiffLoader(DiffLoaderArgs { run })) => {
+            pack.diff_loader = true;
+            let mode = RunMode::from_flags(run.install, run.update, locked, offline);
+            sync(
+                ctx,
+                mode,
+                force,
+                lockfile,
+                dev_path,
+                pack,
+                config_files,
+                &[],
+            )
+            .await
+        }
+        // `plan` は予定を表示するだけで、何も取得・変更しない。
+        Some(Command::Plan(args)) => {
+            let mode = RunMode::from_flags(args.run.install, args.run.update, locked, offline);
+            pack.plan = Some(args);
+            sync(
+                ctx,
+                mode,
+                force,
+                lockfile,
+                dev_path,
+                pack,
+                config_files,
+                &[],
+            )
+            .await
+        }
+    }
+}
+
+/// 設定を読み込み、plugin を load して pack を publish し、lock を書き出す 1 回分の同期処理。
+/// `forget` の canonical は設定に無くても lock から取り除く（`remove` 用）。
+#[allow(clippy::too_many_arguments)]
+async fn sync(
+    ctx: &AppContext,
+    mode: RunMode,
+    force: bool,
+    lockfile: PathBuf,
+    dev_path: Option<PathBuf>,
+    pack: PackOptions,
+    config_files: Vec<String>,
+    forget: &[String],
+) -> Result<(), Error> {
+    // `--offline` は何かを書き換える前に、lock とキャッシュだけで足りるかを確かめる。
+    if mode.offline() {
+        offline_preflight(&ctx.repo_cache_dir, &config_files, &lockfile).await?;
+    }
 
     // Ensure the app cache dir exists up front. `Plugin::load` creates it as a
-    // side effect of `--install`/`--update` (via `init_source`), but a flagless
+    // side effect of `install`/`update` (via `init_source`), but a plain
     // run that skips every plugin (fresh cache, nothing to reuse) would never
     // touch the dir and then fail with ENOENT when writing the lockfile or the
     // packpath below.
-    tokio::fs::create_dir_all(DEFAULT_APP_DIR.as_path()).await?;
+    tokio::fs::create_dir_all(&ctx.app_dir).await?;
 
     // パース生産者: walker → sort → 並列パース（spawn_blocking）→ SchedEvent 送信。
     // 完了順に関わらず index を添えて送り、最後に ParsePhaseDone{total} で確定通知する。
@@ -136,6 +845,7 @@ async fn app() -> Result<(), Error> {
     let (parse_tx, parse_rx) = tokio::sync::mpsc::unbounded_channel::<SchedEvent>();
     let parse_prod = tokio::spawn({
         let parse_tx = parse_tx.clone();
+        let logger = ctx.logger.clone();
         async move {
             let mut config_paths = Vec::new();
             let mut walker = match ConfigWalker::new(config_files).await {
@@ -148,7 +858,7 @@ async fn app() -> Result<(), Error> {
             while let Some(item) = walker.recv().await {
                 match item {
                     Ok(path) => {
-                        msg(Message::ConfigFound(path.clone()));
+                        logger.send(Message::ConfigFound(path.clone()));
                         config_paths.push(path);
                     }
                     Err(e) => {
@@ -158,12 +868,15 @@ async fn app() -> Result<(), Error> {
                 }
             }
             config_paths.sort();
-            msg(Message::ConfigWalkFinish);
+            logger.send(Message::ConfigWalkFinish);
             let total = config_paths.len();
+            let nvim = Arc::new(NvimVersionCell::new());
             let mut parse_tasks = tokio::task::JoinSet::new();
             for (index, path) in config_paths.into_iter().enumerate() {
                 let parse_tx = parse_tx.clone();
+                let nvim = Arc::clone(&nvim);
                 parse_tasks.spawn(async move {
+                    let _job = rsplug::util::resources::job().await?;
                     let input = tokio::fs::read_to_string(&path).await.map_err(|source| {
                         Error::ConfigRead {
                             path: path.clone(),
@@ -171,22 +884,11 @@ async fn app() -> Result<(), Error> {
                         }
                     })?;
                     // Error::Parse が大きいので Box に詰める（clippy::result_large_err 回避）。
-                    let parsed = tokio::task::spawn_blocking(move || {
-                        toml::from_str::<rsplug::Config>(&input).map_err(|source| {
-                            Box::new(Error::Parse {
-                                source,
-                                path,
-                                input,
-                            })
-                        })
-                    })
-                    .await
-                    .map_err(|e| {
-                        Error::Io(std::io::Error::other(format!(
-                            "config parse task failed: {e}"
-                        )))
-                    })?
-                    .map_err(|boxed| *boxed)?;
+                    let mut parsed = tokio::task::spawn_blocking(move || parse_config(path, input))
+                        .await
+                        .map_err(task::panicked("config parse"))?
+                        .map_err(|boxed| *boxed)?;
+                    select_for_host(&mut parsed, &nvim, true).await;
                     let _ = parse_tx.send(SchedEvent::Parsed {
                         index,
                         config: parsed,
@@ -202,9 +904,9 @@ async fn app() -> Result<(), Error> {
                         return;
                     }
                     Err(e) => {
-                        let _ = parse_tx.send(SchedEvent::ParseError(Error::Io(
-                            std::io::Error::other(format!("config parse task panicked: {e}")),
-                        )));
+                        let _ = parse_tx.send(SchedEvent::ParseError(
+                            task::panicked("config parse")(e).into(),
+                        ));
                         return;
                     }
                 }
@@ -224,8 +926,8 @@ async fn app() -> Result<(), Error> {
             let normalized = lock.clone().normalize_keys()?;
             let lockfile_is_current = lock.version == "2" && lock.locked == normalized.locked;
             let rsplug::LockFile { locked: map, .. } = normalized;
-            if locked {
-                msg(Message::DetectLockFile(lockfile.clone()));
+            if mode.locked() {
+                ctx.logger.send(Message::DetectLockFile(lockfile.clone()));
             }
             (map, lockfile_is_current)
         }
@@ -246,57 +948,156 @@ async fn app() -> Result<(), Error> {
         std::time::Duration::from_millis(64),
     );
 
-    // プロセス全体で共有する HTTP クライアント（接続プール・HTTP/2 再利用）。
-    let http_client = reqwest::Client::builder()
-        .user_agent(concat!("rsplug/", env!("CARGO_PKG_VERSION")))
-        .pool_idle_timeout(std::time::Duration::from_secs(90))
-        .pool_max_idle_per_host(64)
-        .tcp_keepalive(std::time::Duration::from_secs(60))
-        .connect_timeout(std::time::Duration::from_secs(10))
-        .build()
-        .map_err(|e| {
-            Error::Io(std::io::Error::other(format!(
-                "failed to build HTTP client: {e}"
-            )))
-        })?;
+    let http_client = http_client()?;
+    // ミラーの計測は取得が起こり得る実行でだけ、プロセスにつき 1 度行う。
+    if !mode.offline() {
+        rsplug::util::mirror::select_once(&http_client, &ctx.app_dir.join("mirrors.json")).await;
+    }
 
     // Keep the conservative default for API/Git hosts. codeload is a separate
     // CDN download workload, so it gets its own 64-request ceiling.
     let network = adaptive_semaphore::NetworkLimits::new(fetch_semaphore, 16)
         .with_host_cap("codeload.github.com", 64);
-    let ctx = LoadCtx {
+    // `rsplug resolve` の結果が有効なうちは、リモート解決をそれで置き換える。
+    let mut catalogs = if mode.allows_remote() {
+        rsplug::RepoJobRegistry::with_resolutions(resolve::load_fresh(&ctx.app_dir).await)
+    } else {
+        rsplug::RepoJobRegistry::new()
+    };
+    if pack.plan.is_some() {
+        catalogs = catalogs.with_planning();
+    }
+    let load_ctx = LoadCtx {
         mode,
+        force,
         locked_map: Arc::clone(&locked_map),
         network,
         breaker: Arc::new(rsplug::util::github::CircuitBreaker::new()),
         http_client: http_client.clone(),
-        cache_dir: DEFAULT_REPOCACHE_DIR.clone(),
-        catalogs: Arc::new(rsplug::RepoJobRegistry::new()),
+        cache_dir: ctx.repo_cache_dir.clone(),
+        dev_path: dev_path.unwrap_or_else(|| ctx.dev_dir.clone()),
+        catalogs: Arc::new(catalogs),
+        select: pack.select.clone(),
     };
 
     let token = rsplug::util::github::token();
     let do_graphql = mode.allows_remote() && token.is_some();
 
+    // `--record` 用に、スケジューラに渡す前に解決結果の置き場を手元に残す。
+    let catalogs = Arc::clone(&load_ctx.catalogs);
     // スケジューラがパースイベントを消費しつつ load fan-out を統括する。
-    // ctx を消費して返るので、ここ以降 locked_map の Arc はスケジューラ内でのみ保持される。
-    let (plugins, lock_infos, remove_canons) =
-        run_load_scheduler(parse_rx, ctx, token.map(Arc::<str>::from), do_graphql).await?;
+    // load_ctx を消費して返るので、ここ以降 locked_map の Arc はスケジューラ内でのみ保持される。
+    let loaded =
+        run_load_scheduler(parse_rx, load_ctx, token.map(Arc::<str>::from), do_graphql).await;
+    // 記録は不具合の再現に使うので、読み込みに失敗した run でも書き出す。
+    if let Some(dir) = &pack.record {
+        let commits = match &loaded {
+            Ok((_, lock_infos, _)) => lock_infos.as_slice(),
+            Err(_) => &[],
+        };
+        let path = record::write(dir, &catalogs, commits).await?;
+        ctx.logger.send(Message::RunRecorded { path });
+    }
+    let (plugins, lock_infos, remove_canons) = loaded?;
+    // 打ち間違えた名前で何も更新されないまま成功しないよう、一致しなかったパターンは誤りにする。
+    if let Some(select) = &pack.select {
+        let patterns = select.unmatched();
+        if !patterns.is_empty() {
+            return Err(Error::NoPluginSelected { patterns });
+        }
+    }
     // パース生産者タスクは ParsePhaseDone 送信後に終了しているはず。join して panic を拾う。
     let _ = parse_prod.await;
     let total_count = plugins.len();
 
+    // `--fetch-only`: repo キャッシュとビルドを温めるだけで、pack と lock には触れない。
+    if pack.fetch_only {
+        ctx.logger.send(Message::MergeFinished {
+            total: total_count,
+            merged: total_count,
+        });
+        ctx.logger.send(Message::PackSkipped);
+        return Ok(());
+    }
+
     // Create PackPlan and load packages into it.
     // doc 盗みはマージ前に行う（doc が source 間マージの対象にならないよう）。
-    let mut state = rsplug::PackPlan::new();
+    let templates = rsplug::TemplateOverrides::load(&ctx.template_dir).await?;
+    let mut state = rsplug::PackPlan::new()
+        .with_merge_policy(pack.merge)
+        .with_no_merge(pack.no_merge)
+        .with_verbose_install(pack.verbose_install)
+        // 隔離デバッグでは併合されないパッケージを読める名前で置く。
+        .with_stable_names(pack.stable_names || pack.verbose_install)
+        .with_debug_loader(pack.debug_loader)
+        .with_sparse(pack.sparse)
+        .with_post_process(pack.post_process.clone())
+        .with_system_packpath(pack.system_packpath.clone())
+        .with_template_overrides(Arc::new(templates));
     state.load(plugins);
-    msg(Message::MergeFinished {
+    ctx.logger.send(Message::MergeFinished {
         total: total_count,
         merged: state.len(),
     });
+    let duplicates = state.duplicate_triggers();
+    let duplicate_count = duplicates.len();
+    for duplicate in duplicates {
+        ctx.logger.send(Message::DuplicateTrigger {
+            trigger: duplicate.trigger,
+            plugins: duplicate.plugins,
+        });
+    }
+    for plugin in state.unloadable_packages() {
+        ctx.logger.send(Message::PluginUnloadable { plugin });
+    }
+    if pack.strict && duplicate_count > 0 {
+        return Err(Error::DuplicateTriggers {
+            count: duplicate_count,
+        });
+    }
 
+    if let Some(args) = &pack.plan {
+        let (snapshots, packages, removed) = plan::print_plan(
+            args,
+            ctx.packpath(),
+            &catalogs,
+            &state,
+            &lock_infos,
+            &remove_canons,
+            &initial_locked_map,
+        )
+        .await?;
+        ctx.logger.send(Message::RunPlanned {
+            snapshots,
+            packages,
+            removed,
+        });
+        return Ok(());
+    }
+    if let Some(dir) = pack.emit_lua {
+        let files = state.emit_lua(&dir).await.map_err(rsplug::Error::Io)?;
+        ctx.logger.send(Message::LuaEmitted { dir, files });
+        return Ok(());
+    }
+    if pack.diff_loader {
+        let diff = state
+            .diff_loader(ctx.packpath())
+            .await
+            .map_err(rsplug::Error::Io)?;
+        {
+            use std::io::Write;
+            std::io::stdout().write_all(diff.patch.as_bytes())?;
+        }
+        ctx.logger.send(Message::LoaderDiffed { files: diff.files });
+        return Ok(());
+    }
+
+    if state.has_system_packages() && pack.system_packpath.is_none() {
+        return Err(Error::NoSystemPackpath);
+    }
     // Install the packages into the packpath.
     state
-        .install(DEFAULT_APP_DIR.as_path())
+        .install(ctx.packpath())
         .await
         .map_err(rsplug::Error::Io)?;
 
@@ -308,7 +1109,7 @@ async fn app() -> Result<(), Error> {
             Arc::try_unwrap(locked_map).expect("No other references to locked_map");
         // Remove entries for plugins that are in the config but not installed
         // (cache missing). This keeps the lock file in sync with the cache.
-        for canon in &remove_canons {
+        for canon in remove_canons.iter().chain(forget) {
             merged_locked.remove(canon);
         }
         for (url, resolved_rev) in lock_infos {
@@ -328,9 +1129,179 @@ async fn app() -> Result<(), Error> {
             desired_lock.write(lockfile.as_path()).await?;
         }
     }
+
+    // 読み込み直しに失敗しても pack と lock は更新済みなので、同期自体は成功とする。
+    if let Some(server) = pack.reload {
+        let error = reload::reload(&server, &ctx.packpath().join("init.lua"))
+            .await
+            .err();
+        ctx.logger.send(Message::NvimReloaded { server, error });
+    }
     Ok(())
 }
 
+/// `rsplug daemon` が有効にする、パース済み設定のキャッシュ（パス → 内容とパース結果）。
+/// 内容が前回と同じファイルはパースし直さない。通常の 1 回きりの実行では使わない。
+static PARSED_CONFIGS: once_cell::sync::OnceCell<
+    std::sync::Mutex<HashMap<PathBuf, (String, rsplug::Config)>>,
+> = once_cell::sync::OnceCell::new();
+
+/// 設定ファイル 1 つ分の内容をパースする。[`PARSED_CONFIGS`] が有効ならそれを引く。
+fn parse_config(path: PathBuf, input: String) -> Result<rsplug::Config, Box<Error>> {
+    let Some(cache) = PARSED_CONFIGS.get() else {
+        return from_toml(&path, &input).map_err(|source| {
+            Box::new(Error::Parse {
+                source: Box::new(source),
+                path,
+                input,
+            })
+        });
+    };
+    if let Some((cached, config)) = cache.lock().unwrap().get(&path)
+        && *cached == input
+    {
+        return Ok(config.clone());
+    }
+    match from_toml(&path, &input) {
+        Ok(config) => {
+            cache.lock().unwrap().insert(path, (input, config.clone()));
+            Ok(config)
+        }
+        Err(source) => {
+            cache.lock().unwrap().remove(&path);
+            Err(Box::new(Error::Parse {
+                source: Box::new(source),
+                path,
+                input,
+            }))
+        }
+    }
+}
+
+/// 1 回の同期で使う Neovim のバージョン。判別できなかった警告を同期ごとに 1 度だけ出すために
+/// 持つ（バージョン自体は [`rsplug::util::nvim::version`] がプロセス内でキャッシュする）。
+type NvimVersionCell = tokio::sync::OnceCell<Option<rsplug::compat::NvimVersion>>;
+
+/// `when` と `compat` に従って `config` のエントリを選ぶ。`report` なら `compat` で外したものと、
+/// バージョンを判別できなかったことを表示する。
+async fn select_for_host(config: &mut rsplug::Config, nvim: &NvimVersionCell, report: bool) {
+    if config
+        .plugins
+        .iter()
+        .all(|plugin| plugin.compat.nvim.is_none() && plugin.when.nvim.is_none())
+    {
+        return;
+    }
+    let version = nvim
+        .get_or_init(|| async {
+            rsplug::util::nvim::version()
+                .await
+                .inspect_err(|reason| {
+                    if report {
+                        msg(Message::NvimVersionUnknown {
+                            reason: reason.clone(),
+                        });
+                    }
+                })
+                .ok()
+        })
+        .await;
+    for (id, reason) in config.retain_for_host(*version) {
+        if report {
+            msg(Message::PluginIncompatible { id, reason });
+        }
+    }
+}
+
+/// 設定ファイル `path` の内容をパースし、相対パス（`patches`）を `path` のディレクトリ基準に解決する。
+fn from_toml(path: &std::path::Path, input: &str) -> Result<rsplug::Config, toml::de::Error> {
+    let config = rsplug::Config::from_toml(input)?;
+    Ok(match path.parent() {
+        Some(dir) => config.resolve_relative_paths(dir),
+        None => config,
+    })
+}
+
+/// プロセス全体で共有する HTTP クライアント（接続プール・HTTP/2 再利用）。
+/// `rsplug daemon` では同期をまたいで接続が温まったまま残る。
+fn http_client() -> Result<reqwest::Client, Error> {
+    static CLIENT: once_cell::sync::OnceCell<reqwest::Client> = once_cell::sync::OnceCell::new();
+    CLIENT
+        .get_or_try_init(|| {
+            reqwest::Client::builder()
+                .user_agent(concat!("rsplug/", env!("CARGO_PKG_VERSION")))
+                .pool_idle_timeout(std::time::Duration::from_secs(90))
+                .pool_max_idle_per_host(64)
+                .tcp_keepalive(std::time::Duration::from_secs(60))
+                .connect_timeout(std::time::Duration::from_secs(10))
+                .build()
+        })
+        .cloned()
+        .map_err(|e| {
+            Error::Io(std::io::Error::other(format!(
+                "failed to build HTTP client: {e}"
+            )))
+        })
+}
+
+/// 設定ファイルを展開し、パス順に読み込んでパースする。
+async fn read_configs(config_files: Vec<String>) -> Result<Vec<(PathBuf, rsplug::Config)>, Error> {
+    let mut walker = ConfigWalker::new(config_files).await?;
+    let mut config_paths = Vec::new();
+    while let Some(path) = walker.recv().await {
+        config_paths.push(path?);
+    }
+    config_paths.sort();
+
+    let mut configs = Vec::with_capacity(config_paths.len());
+    for path in config_paths {
+        let input = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|source| Error::ConfigRead {
+                path: path.clone(),
+                source,
+            })?;
+        match from_toml(&path, &input) {
+            Ok(config) => configs.push((path, config)),
+            Err(source) => {
+                return Err(Error::Parse {
+                    source: Box::new(source),
+                    path,
+                    input,
+                });
+            }
+        }
+    }
+    Ok(configs)
+}
+
+/// `--offline` の事前検査。設定中の全 repo について lock の rev がキャッシュにあるかを調べ、
+/// 足りないものをまとめて [`Error::OfflineCacheIncomplete`] で返す。
+async fn offline_preflight(
+    repo_cache_dir: &std::path::Path,
+    config_files: &[String],
+    lockfile: &std::path::Path,
+) -> Result<(), Error> {
+    let nvim = NvimVersionCell::new();
+    let mut configs = Vec::new();
+    for (_, mut config) in read_configs(config_files.to_vec()).await? {
+        select_for_host(&mut config, &nvim, false).await;
+        configs.push(config);
+    }
+    let config: rsplug::Config = configs.into();
+    let locked = match rsplug::LockFile::read(lockfile).await {
+        Ok(lock) => lock.normalize_keys()?.locked,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let missing = rsplug::plugin::offline_preflight(&config, repo_cache_dir, &locked).await?;
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::OfflineCacheIncomplete { missing })
+    }
+}
+
 /// 1 プラグインの LATE 相成功ペイロード（`run_load_late` の成功値）。
 struct LoadPayload {
     loaded: Option<(rsplug::LoadedPlugin, Option<(String, String)>)>,
@@ -505,8 +1476,12 @@ fn flush_graphql_chunks(
                                 .await;
                             attempt += 1;
                         }
+                        rsplug::util::github::ApiFallbackPolicy::Wait(wait) => {
+                            tokio::time::sleep(wait).await;
+                            attempt += 1;
+                        }
                         rsplug::util::github::ApiFallbackPolicy::RateLimited => {
-                            breaker.trip();
+                            breaker.trip_rate_limited();
                             break Err(err);
                         }
                         rsplug::util::github::ApiFallbackPolicy::GiveUp => break Err(err),
@@ -795,7 +1770,13 @@ impl LoadScheduler {
                                 Some(repo) => {
                                     let canonical = repo.canonical();
                                     let repo_rev = repo.rev();
-                                    if ctx.mode.locked() || !do_graphql {
+                                    // `rsplug resolve` で解決済みなら GraphQL に載せず、EARLY で引く。
+                                    if ctx.mode.locked()
+                                        || !do_graphql
+                                        || pc.cache.dev
+                                        || !ctx.selects(&dummy)
+                                        || ctx.catalogs.is_preresolved(&canonical, repo_rev.as_deref())
+                                    {
                                         Some(LoadRev::Auto)
                                     } else if repo_rev
                                         .as_deref()
@@ -997,9 +1978,7 @@ impl LoadScheduler {
                         key,
                         plugin,
                         outcome,
-                    } = jr.map_err(|e| {
-                        Error::Io(std::io::Error::other(format!("early load task panicked: {e}")))
-                    })?;
+                    } = jr.map_err(task::panicked("early load"))?;
                     match key {
                         EarlyKey::Node(idx) => {
                             // 既存ロジック。plugin（resolved）を nodes[idx] に戻す。
@@ -1076,9 +2055,7 @@ impl LoadScheduler {
                     }
                 }
                 Some(jr) = load_tasks.join_next(), if !load_tasks.is_empty() => {
-                    let LoadDone { idx, outcome } = jr.map_err(|e| {
-                        Error::Io(std::io::Error::other(format!("load task panicked: {e}")))
-                    })?;
+                    let LoadDone { idx, outcome } = jr.map_err(task::panicked("load"))?;
                     // LATE 完了（成功/エラー問わず）を格納し、依存元の pending_deps を進める。
                     finished.push(outcome.map(|p| (p.loaded, p.canon_to_remove)));
                     let dependents = std::mem::take(&mut nodes[idx].dependents);
@@ -1093,18 +2070,20 @@ impl LoadScheduler {
                     let (result, chunk_canonicals) = match jr {
                         Ok(v) => v,
                         Err(e) => {
-                            eprintln!(
-                                "[rsplug] GraphQL chunk join error: {e}; affected plugins fall back to per-repo"
-                            );
                             // A task panic must not strand the staged keys forever.  The
                             // normal task cannot panic, but route this defensively as a
-                            // transient shared failure if JoinSet reports one.
+                            // transient shared failure if JoinSet reports one.  The panic
+                            // itself is reported by the supervision helper.
+                            task::panicked("GraphQL chunk")(e);
                             (Err(rsplug::util::github::ApiError::Transient), Vec::new())
                         }
                     };
                     let err_reason = match &result {
                         Ok(_) => None,
-                        Err(rsplug::util::github::ApiError::RateLimited) => {
+                        Err(
+                            rsplug::util::github::ApiError::RateLimited
+                            | rsplug::util::github::ApiError::RetryAfter(_),
+                        ) => {
                             Some("rate-limited".to_string())
                         }
                         Err(err) => Some(format!("{err:?}")),
@@ -1184,9 +2163,7 @@ impl LoadScheduler {
                     }
                 }
                 Some(jr) = catalog_tasks.join_next(), if !catalog_tasks.is_empty() => {
-                    let done = jr.map_err(|e| {
-                        Error::Io(std::io::Error::other(format!("catalog task panicked: {e}")))
-                    })?;
+                    let done = jr.map_err(task::panicked("catalog"))?;
                     if should_resolve_graphql(done.install, done.update, done.installed) {
                         graphql_batch.push(rsplug::util::github::GithubRev {
                             owner: done.owner,
@@ -1274,19 +2251,11 @@ impl LoadScheduler {
     }
 }
 
-static DEFAULT_APP_DIR: Lazy<PathBuf> = Lazy::new(|| {
-    let homedir = std::env::home_dir().expect("Failed to get home directory");
-    let cachedir = homedir.join(".cache");
-    cachedir.join("rsplug")
-});
-
-static DEFAULT_REPOCACHE_DIR: Lazy<PathBuf> = Lazy::new(|| DEFAULT_APP_DIR.join("repos"));
-
 #[derive(Debug, thiserror::Error)]
 enum Error {
     #[error("{}", format_toml_parse_error(path, input, source))]
     Parse {
-        source: toml::de::Error,
+        source: Box<toml::de::Error>,
         path: PathBuf,
         input: String,
     },
@@ -1307,6 +2276,100 @@ enum Error {
         rev_a: Option<Arc<str>>,
         rev_b: Option<Arc<str>>,
     },
+    #[error("no config file found to edit (pass --file)")]
+    NoConfigFile,
+    #[error("invalid repository {repo:?}: {reason}")]
+    InvalidRepo { repo: String, reason: &'static str },
+    #[error("failed to parse config {} for editing: {source}", path.display())]
+    ConfigEdit {
+        path: PathBuf,
+        source: toml_edit::TomlError,
+    },
+    #[error("`plugins` in {} is not an array of tables", path.display())]
+    ConfigShape { path: PathBuf },
+    #[error("{canonical} is already configured in {}", path.display())]
+    AlreadyConfigured { canonical: String, path: PathBuf },
+    #[error("{canonical} is not configured in any config file")]
+    NotConfigured { canonical: String },
+    #[error(
+        "{canonical} is configured more than once; remove it by hand or pass --file:\n{}",
+        paths.iter().map(|p| format!("  {}", p.display())).collect::<Vec<_>>().join("\n")
+    )]
+    AmbiguousEntry {
+        canonical: String,
+        paths: Vec<PathBuf>,
+    },
+    #[error("could not determine the home directory (set HOME or XDG_CACHE_HOME)")]
+    NoHomeDirectory,
+    #[error("no provenance index found; run rsplug once to install the pack")]
+    NoProvenanceIndex,
+    #[error("{} is not a file of an installed package", path.display())]
+    NotInstalledPath { path: PathBuf },
+    #[error("rsplug clean needs config file patterns (pass --config or set RSPLUG_CONFIG_FILES)")]
+    CleanNoConfigFiles,
+    #[error("rsplug daemon needs config file patterns (pass --config or set RSPLUG_CONFIG_FILES)")]
+    DaemonNoConfigFiles,
+    #[error(
+        "rsplug add and remove rebuild the pack from every config file; pass them with --config or set RSPLUG_CONFIG_FILES"
+    )]
+    EditNoConfigFiles,
+    #[error("no configured plugin matches {}", patterns.join(", "))]
+    NoPluginSelected { patterns: Vec<String> },
+    #[error("rsplug daemon cannot read config files from standard input (`-`)")]
+    DaemonStdinConfig,
+    #[error("another rsplug daemon is already listening on {}", path.display())]
+    DaemonRunning { path: PathBuf },
+    #[error("no rsplug daemon is listening on {}", path.display())]
+    DaemonNotRunning { path: PathBuf },
+    #[error("rsplug daemon: {message}")]
+    DaemonRequest { message: String },
+    #[error("{count} lazy command(s) or mapping(s) are registered by several plugins (--strict)")]
+    DuplicateTriggers { count: usize },
+    #[error("plugins with `target = \"system\"` need `--packpath system=<DIR>`")]
+    NoSystemPackpath,
+    #[error("{} already exists (pass --replace to overwrite it)", path.display())]
+    ImportTargetExists { path: PathBuf },
+    #[error("failed to fetch {url}: {source}")]
+    Fetch { url: String, source: reqwest::Error },
+    #[error("validation found {count} problem(s) in the config files")]
+    Validation { count: usize },
+    #[error("no recording at {} (create one with --record)", path.display())]
+    NoRecording { path: PathBuf },
+    #[error(
+        "--offline cannot rebuild the pack from the cache; nothing was changed. Missing:\n{}",
+        missing.iter().map(|m| format!("  {m}")).collect::<Vec<_>>().join("\n")
+    )]
+    OfflineCacheIncomplete {
+        missing: Vec<rsplug::plugin::OfflineMissing>,
+    },
+    /// `remove` の同期が失敗し、変更を元に戻すのにも失敗した。
+    #[error(
+        "{source}\nrolling back the removal also failed:\n{}",
+        failures.iter().map(|f| format!("  {f}")).collect::<Vec<_>>().join("\n")
+    )]
+    RollbackFailed {
+        source: Box<Error>,
+        failures: Vec<String>,
+    },
+}
+
+/// 失敗した `e` の後始末の結果（対象, 結果）を添える。復元がすべて成功したら `e` のまま返す。
+fn with_rollback_failures(
+    e: Error,
+    restores: impl IntoIterator<Item = (String, std::io::Result<()>)>,
+) -> Error {
+    let failures: Vec<String> = restores
+        .into_iter()
+        .filter_map(|(target, result)| result.err().map(|err| format!("{target}: {err}")))
+        .collect();
+    if failures.is_empty() {
+        e
+    } else {
+        Error::RollbackFailed {
+            source: Box::new(e),
+            failures,
+        }
+    }
 }
 
 fn format_toml_parse_error(
@@ -1340,7 +2403,7 @@ fn format_toml_parse_error(
         style("^".repeat(caret_len)).red().bold(),
         source.message()
     );
-    format!(
+    let mut rendered = format!(
         "failed to parse config\n {} {}:{}:{}\n   {}\n{} {} {}\n   {} {}{}",
         style("-->").blue(),
         path.display(),
@@ -1353,7 +2416,15 @@ fn format_toml_parse_error(
         gutter,
         " ".repeat(span_start_col.saturating_sub(1)),
         caret_msg,
-    )
+    );
+    if let Some(key_path) = toml_key_path(input, &span) {
+        rendered.push_str(&format!(
+            "\n   {} in {}",
+            style("=").blue(),
+            style(key_path).bold()
+        ));
+    }
+    rendered
 }
 
 /// The `toml` crate reports the enclosing table-header span (e.g. `[[plugins]]`)
@@ -1363,7 +2434,9 @@ fn format_toml_parse_error(
 ///
 /// `repo` is the only required field of a plugin entry. We test it alone first;
 /// if it parses, we probe every other value-typed field against a known-valid
-/// repo. Works for both serde type errors and custom `RepoSource` errors.
+/// repo. Works for both serde type errors and custom `RepoSource` errors. A
+/// failing array or inline table is narrowed further with
+/// [`narrow_parse_error_span`].
 fn refine_parse_error_span(
     input: &str,
     header_span: std::ops::Range<usize>,
@@ -1383,24 +2456,186 @@ fn refine_parse_error_span(
         })
         .collect();
 
-    let (_, repo_text, repo_span) = fields.iter().find(|(k, _, _)| *k == "repo")?;
+    let (repo_key, repo_text, repo_span) = fields
+        .iter()
+        .find(|(k, _, _)| *k == "repo" || *k == "source")?;
     let base = format!("[[plugins]]\nrepo = {}\n", repo_text);
 
     if toml::from_str::<rsplug::Config>(&base).is_err() {
         return Some(repo_span.clone());
     }
     for (key, text, span) in &fields {
-        if *key == "repo" {
+        if key == repo_key {
             continue;
         }
         let probe = format!("{}{} = {}\n", base, key, text);
         if toml::from_str::<rsplug::Config>(&probe).is_err() {
-            return Some(span.clone());
+            let value = entry.get(key)?.as_value()?;
+            let fails = |value: &toml_edit::Value| {
+                let mut value = value.clone();
+                value.decor_mut().clear();
+                toml::from_str::<rsplug::Config>(&format!("{base}{key} = {value}\n")).is_err()
+            };
+            return Some(narrow_parse_error_span(value, &fails).unwrap_or(span.clone()));
         }
     }
     None
 }
 
+/// 配列・インラインテーブルの要素への道順。
+enum ValueStep {
+    Index(usize),
+    Key(String),
+}
+
+fn value_at<'a>(value: &'a toml_edit::Value, path: &[ValueStep]) -> Option<&'a toml_edit::Value> {
+    path.iter()
+        .try_fold(value, |value, step| match (value, step) {
+            (toml_edit::Value::Array(array), ValueStep::Index(index)) => array.get(*index),
+            (toml_edit::Value::InlineTable(table), ValueStep::Key(key)) => table.get(key),
+            _ => None,
+        })
+}
+
+fn value_at_mut<'a>(
+    value: &'a mut toml_edit::Value,
+    path: &[ValueStep],
+) -> Option<&'a mut toml_edit::Value> {
+    path.iter()
+        .try_fold(value, |value, step| match (value, step) {
+            (toml_edit::Value::Array(array), ValueStep::Index(index)) => array.get_mut(*index),
+            (toml_edit::Value::InlineTable(table), ValueStep::Key(key)) => table.get_mut(key),
+            _ => None,
+        })
+}
+
+/// `fails` が `root` を拒むとき、取り除けば受け付けられるようになる要素を内側へ辿り、
+/// 最も内側のものの span を返す。どの要素も単独の原因でなければ（必須キーの欠落など）`None`。
+fn narrow_parse_error_span(
+    root: &toml_edit::Value,
+    fails: &dyn Fn(&toml_edit::Value) -> bool,
+) -> Option<std::ops::Range<usize>> {
+    let mut path = Vec::new();
+    loop {
+        let steps: Vec<ValueStep> = match value_at(root, &path)? {
+            toml_edit::Value::Array(array) => (0..array.len()).map(ValueStep::Index).collect(),
+            toml_edit::Value::InlineTable(table) => table
+                .iter()
+                .map(|(key, _)| ValueStep::Key(key.to_string()))
+                .collect(),
+            _ => break,
+        };
+        let culprit = steps.into_iter().find(|step| {
+            let mut probe = root.clone();
+            match (value_at_mut(&mut probe, &path), step) {
+                (Some(toml_edit::Value::Array(array)), ValueStep::Index(index)) => {
+                    array.remove(*index);
+                }
+                (Some(toml_edit::Value::InlineTable(table)), ValueStep::Key(key)) => {
+                    table.remove(key);
+                }
+                _ => return false,
+            }
+            !fails(&probe)
+        });
+        match culprit {
+            Some(step) => path.push(step),
+            None => break,
+        }
+    }
+    if path.is_empty() {
+        return None;
+    }
+    value_at(root, &path)?.span()
+}
+
+/// `span` の値（またはテーブル見出し）までのキーパス（例: `plugins[3].on_map.n`）。
+/// 配列の添字は 0 始まり。文書を読めない（構文エラー）なら `None`。
+fn toml_key_path(input: &str, span: &std::ops::Range<usize>) -> Option<String> {
+    let doc = toml_edit::Document::parse(input).ok()?;
+    let mut path = String::new();
+    table_key_path(doc.as_table(), span, &mut path).then_some(path)
+}
+
+fn push_toml_key(path: &mut String, key: &str) {
+    if !path.is_empty() {
+        path.push('.');
+    }
+    path.push_str(&toml_edit::Key::new(key).to_string());
+}
+
+fn table_key_path(
+    table: &toml_edit::Table,
+    span: &std::ops::Range<usize>,
+    path: &mut String,
+) -> bool {
+    for (key, item) in table.iter() {
+        let len = path.len();
+        push_toml_key(path, key);
+        let found = match item {
+            toml_edit::Item::Value(value) => value_key_path(value, span, path),
+            toml_edit::Item::Table(table) => {
+                table.span().as_ref() == Some(span) || table_key_path(table, span, path)
+            }
+            toml_edit::Item::ArrayOfTables(tables) => {
+                tables.iter().enumerate().any(|(index, table)| {
+                    let len = path.len();
+                    path.push_str(&format!("[{index}]"));
+                    let found =
+                        table.span().as_ref() == Some(span) || table_key_path(table, span, path);
+                    if !found {
+                        path.truncate(len);
+                    }
+                    found
+                })
+            }
+            toml_edit::Item::None => false,
+        };
+        if found {
+            return true;
+        }
+        path.truncate(len);
+    }
+    false
+}
+
+fn value_key_path(
+    value: &toml_edit::Value,
+    span: &std::ops::Range<usize>,
+    path: &mut String,
+) -> bool {
+    let Some(own) = value.span() else {
+        return false;
+    };
+    if span.start < own.start || own.end < span.end {
+        return false;
+    }
+    match value {
+        toml_edit::Value::Array(array) => {
+            for (index, element) in array.iter().enumerate() {
+                let len = path.len();
+                path.push_str(&format!("[{index}]"));
+                if value_key_path(element, span, path) {
+                    return true;
+                }
+                path.truncate(len);
+            }
+        }
+        toml_edit::Value::InlineTable(table) => {
+            for (key, element) in table.iter() {
+                let len = path.len();
+                push_toml_key(path, key);
+                if value_key_path(element, span, path) {
+                    return true;
+                }
+                path.truncate(len);
+            }
+        }
+        _ => {}
+    }
+    true
+}
+
 fn line_info(input: &str, offset: usize) -> (usize, usize, usize, usize) {
     let mut line_no = 1;
     let mut line_start = 0;
@@ -1435,19 +2670,170 @@ fn highlight_toml_line(line: &str, span_start_col: usize, span_end_col: usize) -
     rendered
 }
 
-#[tokio::main]
-async fn main() {
-    if let Err(e) = app().await {
-        msg(Message::Error(e.into()));
-        close(1).await;
+/// Ctrl-C で中断したときの終了コード（128 + SIGINT）。
+const EXIT_INTERRUPTED: i32 = 130;
+/// 中断後、実行中の git 操作と blocking task の片付けを待つ上限。
+const INTERRUPT_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// `--threads`・`--blocking-threads` を反映した tokio ランタイム。未指定の値は tokio の既定のまま。
+fn build_runtime(
+    threads: Option<u16>,
+    blocking_threads: Option<u16>,
+) -> std::io::Result<tokio::runtime::Runtime> {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all();
+    if let Some(threads) = threads {
+        builder.worker_threads(usize::from(threads));
     }
-    close(0).await;
+    if let Some(blocking_threads) = blocking_threads {
+        builder.max_blocking_threads(usize::from(blocking_threads));
+    }
+    builder.build()
+}
+
+fn main() {
+    // スレッド数は引数で決まるので、ランタイムより先に引数を読む。
+    let args = Args::parse();
+    // SAFETY: ランタイムを起動する前で、git2 を使うスレッドはまだ無い。
+    unsafe { rsplug::util::git::configure_ssl_certs() };
+    let runtime = match build_runtime(args.threads, args.blocking_threads) {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("rsplug: failed to start the async runtime: {e}");
+            std::process::exit(1);
+        }
+    };
+    runtime.block_on(async {
+        // Ctrl-C は git 操作へ中断を合図し、git のスレッドが書きかけの checkout を終えてから
+        // `app` の future ごと破棄する。JoinSet は drop で配下の task を abort し、build の子プロセスは
+        // kill_on_drop で殺され、staging（checkout・tarball・pack の世代）は各 guard の drop で消える。
+        // 公開は rename と init.lua の差し替えで行うため、どの時点で止まっても公開済みの pack と
+        // lock は壊れない。
+        let mut run = Box::pin(async {
+            let ctx = AppContext::from_home(log::logger().clone())?;
+            app(&ctx, args).await
+        });
+        let result = tokio::select! {
+            result = &mut run => result,
+            _ = tokio::signal::ctrl_c() => {
+                rsplug::util::git::interrupt();
+                msg(Message::Interrupted);
+                rsplug::util::git::wait_idle(INTERRUPT_GRACE).await;
+                drop(run);
+                log::flush().await;
+                return;
+            }
+        };
+        if let Err(e) = result {
+            msg(Message::Error(e.into()));
+            close(1).await;
+        }
+        close(0).await;
+    });
+    // 中断したときだけここへ来る。blocking task（展開・コピー）の終わりを待ってから終了する。
+    // 待ちきれずに残った staging は `rsplug clean` が消す。
+    runtime.shutdown_timeout(INTERRUPT_GRACE);
+    std::process::exit(EXIT_INTERRUPTED);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn config_files_are_an_option_of_every_subcommand() {
+        let args = Args::try_parse_from(["rsplug", "validate", "-c", "a.toml"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Validate)));
+        assert_eq!(args.config_files, ["a.toml"]);
+
+        let args =
+            Args::try_parse_from(["rsplug", "--config", "a.toml:b/*.toml", "clean"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Clean(_))));
+        assert_eq!(args.config_files, ["a.toml", "b/*.toml"]);
+
+        let args = Args::try_parse_from(["rsplug", "status", "-c", "a.toml", "--config", "b.toml"])
+            .unwrap();
+        assert!(matches!(args.command, Some(Command::Status(_))));
+        assert_eq!(args.config_files, ["a.toml", "b.toml"]);
+    }
+
+    #[test]
+    fn config_files_are_not_positional() {
+        // 以前はサブコマンドの前の位置引数が設定ファイルで、`validate` もそれに数えられていた。
+        assert!(Args::try_parse_from(["rsplug", "a.toml", "validate"]).is_err());
+        assert!(Args::try_parse_from(["rsplug", "validate", "a.toml"]).is_err());
+        assert!(reads_config_files(&Some(Command::Validate)));
+        let du = Args::try_parse_from(["rsplug", "du"]).unwrap();
+        assert!(!reads_config_files(&du.command));
+    }
+
+    #[test]
+    fn plugin_names_follow_the_run_subcommands() {
+        let args = Args::try_parse_from(["rsplug", "update", "telescope*", "nvim-cmp"]).unwrap();
+        let Some(Command::Update(run)) = args.command else {
+            panic!("expected update");
+        };
+        assert_eq!(run.plugins, ["telescope*", "nvim-cmp"]);
+
+        let args = Args::try_parse_from(["rsplug", "install", "-c", "a.toml", "nvim-cmp"]).unwrap();
+        let Some(Command::Install(run)) = args.command else {
+            panic!("expected install");
+        };
+        assert_eq!(run.plugins, ["nvim-cmp"]);
+        assert_eq!(args.config_files, ["a.toml"]);
+    }
+
+    #[test]
+    fn runtime_honours_the_thread_limits() {
+        let runtime = build_runtime(Some(2), Some(1)).unwrap();
+        assert_eq!(runtime.metrics().num_workers(), 2);
+        let answer = runtime.block_on(async { tokio::task::spawn_blocking(|| 42).await.unwrap() });
+        assert_eq!(answer, 42);
+    }
+
+    #[test]
+    fn config_globs_expand_with_one_blocking_thread() {
+        let tmp = tempfile::tempdir().unwrap();
+        for dir in ["a/x", "a/y", "b", "c/x/y"] {
+            std::fs::create_dir_all(tmp.path().join(dir)).unwrap();
+            std::fs::write(tmp.path().join(dir).join("plugins.toml"), "").unwrap();
+        }
+        let pattern = format!("{}/**/*.toml", tmp.path().display());
+        let runtime = build_runtime(Some(1), Some(1)).unwrap();
+        let found = runtime.block_on(async {
+            let mut walker = ConfigWalker::new(vec![pattern]).await.unwrap();
+            let mut found = 0;
+            while let Some(path) =
+                tokio::time::timeout(std::time::Duration::from_secs(10), walker.recv())
+                    .await
+                    .expect("the walk should not stall")
+            {
+                path.unwrap();
+                found += 1;
+            }
+            found
+        });
+        assert_eq!(found, 4);
+    }
+
+    #[test]
+    fn packpath_arguments_name_a_target_and_a_directory() {
+        assert_eq!(
+            "system=/opt/nvim/site".parse::<PackpathSpec>(),
+            Ok(PackpathSpec {
+                target: rsplug::PackTarget::System,
+                dir: PathBuf::from("/opt/nvim/site"),
+            })
+        );
+        assert_eq!(
+            "user=a=b".parse::<PackpathSpec>().map(|spec| spec.dir),
+            Ok(PathBuf::from("a=b"))
+        );
+        assert!("/opt/nvim/site".parse::<PackpathSpec>().is_err());
+        assert!("site=/opt/nvim".parse::<PackpathSpec>().is_err());
+        assert!("system=".parse::<PackpathSpec>().is_err());
+    }
+
     #[test]
     fn documented_example_toml_parses() {
         let config = toml::from_str::<rsplug::Config>(include_str!("../../../example.toml"))
@@ -1496,6 +2882,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_error_narrows_into_arrays_and_names_the_key_path() {
+        let input = "[[plugins]]\nrepo = \"owner/a\"\n\n[[plugins]]\nrepo = \"owner/b\"\non_cmd = [\"Good\", 5]\n";
+        let err = match toml::from_str::<rsplug::Config>(input) {
+            Ok(_) => panic!("expected parse error"),
+            Err(err) => err,
+        };
+        let span =
+            refine_parse_error_span(input, err.span().unwrap()).expect("span should be refined");
+        assert_eq!(&input[span.clone()], "5");
+        assert_eq!(
+            toml_key_path(input, &span).as_deref(),
+            Some("plugins[1].on_cmd[1]")
+        );
+        let rendered = format_toml_parse_error(std::path::Path::new("x"), input, &err);
+        let rendered = console::strip_ansi_codes(&rendered);
+        assert!(rendered.contains("= in plugins[1].on_cmd[1]"), "{rendered}");
+    }
+
     #[test]
     fn toml_parse_error_format_includes_source_snippet() {
         let input = "[[plugins]]\nrepo = \"owner/plugin\"\nstart = tru\n";
@@ -1532,6 +2937,39 @@ mod tests {
         assert!(should_resolve_graphql(true, true, true));
     }
 
+    #[test]
+    fn offline_mode_is_locked_and_never_remote() {
+        for (install, locked) in [(false, false), (true, false), (true, true)] {
+            let mode = RunMode::from_flags(install, false, locked, true);
+            assert!(mode.offline());
+            assert!(mode.locked());
+            assert!(!mode.allows_remote());
+            assert_eq!(mode.install(), install);
+        }
+        assert!(!RunMode::from_flags(true, true, false, false).offline());
+    }
+
+    #[test]
+    fn failed_rollback_keeps_the_sync_error() {
+        let sync_error = || Error::NotConfigured {
+            canonical: "github.com/owner/repo".into(),
+        };
+        let kept = with_rollback_failures(sync_error(), [("a.toml".into(), Ok(()))]);
+        assert!(matches!(kept, Error::NotConfigured { .. }));
+
+        let failed = with_rollback_failures(
+            sync_error(),
+            [
+                ("a.toml".into(), Err(std::io::Error::other("disk full"))),
+                ("lockfile and pack".into(), Ok(())),
+            ],
+        );
+        let message = failed.to_string();
+        assert!(message.starts_with("github.com/owner/repo is not configured"));
+        assert!(message.contains("  a.toml: disk full"));
+        assert!(!message.contains("lockfile and pack"));
+    }
+
     /// Step 4: `run_load_scheduler` が Parsed 到着順で EARLY を kick し、ParsePhaseDone
     /// 後に LATE を実行して、正しい LoadedPlugin を生成することを検証する。
     /// script-only プラグイン（repo なし）でネットワーク・キャッシュ不要。
@@ -1540,6 +2978,7 @@ mod tests {
         let tmp = tempfile::tempdir().unwrap();
         let ctx = LoadCtx {
             mode: RunMode::Refresh,
+            force: false,
             locked_map: Arc::new(BTreeMap::new()),
             network: adaptive_semaphore::NetworkLimits::new(
                 adaptive_semaphore::AdaptiveSemaphore::new(),
@@ -1548,7 +2987,9 @@ mod tests {
             breaker: Arc::new(rsplug::util::github::CircuitBreaker::new()),
             http_client: reqwest::Client::new(),
             cache_dir: tmp.path().to_path_buf(),
+            dev_path: tmp.path().join("dev"),
             catalogs: Arc::new(rsplug::RepoJobRegistry::new()),
+            select: None,
         };
         let (parse_tx, parse_rx) = tokio::sync::mpsc::unbounded_channel::<SchedEvent>();
         let c1: rsplug::Config =
@@ -1592,6 +3033,7 @@ mod tests {
         let tmp = tempfile::tempdir().unwrap();
         let ctx = LoadCtx {
             mode: RunMode::Refresh,
+            force: false,
             locked_map: Arc::new(BTreeMap::new()),
             network: adaptive_semaphore::NetworkLimits::new(
                 adaptive_semaphore::AdaptiveSemaphore::new(),
@@ -1600,7 +3042,9 @@ mod tests {
             breaker: Arc::new(rsplug::util::github::CircuitBreaker::new()),
             http_client: reqwest::Client::new(),
             cache_dir: tmp.path().to_path_buf(),
+            dev_path: tmp.path().join("dev"),
             catalogs: Arc::new(rsplug::RepoJobRegistry::new()),
+            select: None,
         };
         let (parse_tx, parse_rx) = tokio::sync::mpsc::unbounded_channel::<SchedEvent>();
         // 到着順は依存逆順（child → base）。Plugin::resolve が topo 順に並べ直す。
@@ -1643,6 +3087,7 @@ mod tests {
         let tmp = tempfile::tempdir().unwrap();
         let ctx = LoadCtx {
             mode: RunMode::Refresh,
+            force: false,
             locked_map: Arc::new(BTreeMap::new()),
             network: adaptive_semaphore::NetworkLimits::new(
                 adaptive_semaphore::AdaptiveSemaphore::new(),
@@ -1651,7 +3096,9 @@ mod tests {
             breaker: Arc::new(rsplug::util::github::CircuitBreaker::new()),
             http_client: reqwest::Client::new(),
             cache_dir: tmp.path().to_path_buf(),
+            dev_path: tmp.path().join("dev"),
             catalogs: Arc::new(rsplug::RepoJobRegistry::new()),
+            select: None,
         };
         let (parse_tx, parse_rx) = tokio::sync::mpsc::unbounded_channel::<SchedEvent>();
         let config: rsplug::Config = toml::from_str(
@@ -1690,4 +3137,58 @@ mod tests {
             ]
         );
     }
+
+    /// 位置引数で選ばなかったプラグインは未インストールでも lock から外さない。
+    #[tokio::test]
+    async fn unselected_plugins_keep_their_lock_entries() {
+        assert_eq!(RunMode::InstallAndUpdate.untouched(), RunMode::Refresh);
+        assert_eq!(RunMode::LockedInstall.untouched(), RunMode::Locked);
+        assert_eq!(RunMode::OfflineInstall.untouched(), RunMode::Offline);
+
+        let tmp = tempfile::tempdir().unwrap();
+        let select = PluginSelection::new(&["owner/*-one".to_string(), "nope".to_string()])
+            .map(Arc::new)
+            .unwrap();
+        let ctx = LoadCtx {
+            mode: RunMode::Refresh,
+            force: false,
+            locked_map: Arc::new(BTreeMap::new()),
+            network: adaptive_semaphore::NetworkLimits::new(
+                adaptive_semaphore::AdaptiveSemaphore::new(),
+                16,
+            ),
+            breaker: Arc::new(rsplug::util::github::CircuitBreaker::new()),
+            http_client: reqwest::Client::new(),
+            cache_dir: tmp.path().to_path_buf(),
+            dev_path: tmp.path().join("dev"),
+            catalogs: Arc::new(rsplug::RepoJobRegistry::new()),
+            select: Some(Arc::clone(&select)),
+        };
+        let (parse_tx, parse_rx) = tokio::sync::mpsc::unbounded_channel::<SchedEvent>();
+        let config: rsplug::Config = toml::from_str(
+            r#"
+            [[plugins]]
+            repo = "owner/missing-one"
+
+            [[plugins]]
+            repo = "owner/missing-two"
+            "#,
+        )
+        .unwrap();
+        parse_tx
+            .send(SchedEvent::Parsed { index: 0, config })
+            .unwrap();
+        parse_tx
+            .send(SchedEvent::ParsePhaseDone { total: 1 })
+            .unwrap();
+        drop(parse_tx);
+
+        let (plugins, _locks, remove_canons) = run_load_scheduler(parse_rx, ctx, None, false)
+            .await
+            .unwrap();
+
+        assert!(plugins.is_empty());
+        assert_eq!(remove_canons, vec!["github.com/owner/missing-one"]);
+        assert_eq!(select.unmatched(), ["nope"]);
+    }
 }
@@ -0,0 +1,336 @@
+//! Planning a run without executing it (`rsplug plan`).
+//!
+//! `rsplug plan` runs the usual pipeline with the same flags (`--install` and
+//! `--update` standing in for `rsplug install` and `rsplug update`, `--locked`,
+//! `--offline`, merge options) up to the point where
+//! something would change. Revisions are resolved as the run would resolve
+//! them, which may ask the remotes, but nothing is fetched, built, installed,
+//! or written to the lockfile. A repository whose snapshot for the resolved
+//! commit is missing is reported with the fetch and build steps that would
+//! create it; its files are not known yet, so it is left out of the packages.
+//! Packages are listed with the configured plugins merged into them and every
+//! entry they would place, followed by the installed packages the run would
+//! drop from the pack. `rsplug install`, `update`, and `sync` print the same
+//! plan with `--dry-run`.
+//!
+//! With `--json` the plan is one JSON object on standard output, with a
+//! `repositories`, a `packages`, and a `removed` array.
+//!
+//! `action` is `reuse` (the snapshot is cached), `fetch` (downloaded, then
+//! built), `materialize` (checked out from the cached `source.git` without the
+//! network, then built), or `skip` (not installed and the run would not install
+//! it). `resolved_via` is `api`, `git`, or `preresolved` for commits asked from
+//! the remote in this run, `lockfile` for locked commits, and `installed` for
+//! the snapshot already in use.
+
+use std::{fmt::Write as _, path::Path};
+
+use rsplug::pack_plan::PlannedPackage;
+use serde::Serialize;
+
+use super::*;
+
+#[derive(clap::Args, Clone, Debug, Default)]
+pub(crate) struct PlanArgs {
+    /// Print the plan as one JSON object
+    #[arg(long)]
+    pub(crate) json: bool,
+    #[command(flatten)]
+    pub(crate) run: AsRunArgs,
+}
+
+#[derive(Serialize)]
+struct Plan {
+    version: u8,
+    repositories: Vec<PlannedRepository>,
+    packages: Vec<PlannedPackage>,
+    removed: Vec<RemovedPackage>,
+}
+
+/// 今の generation にあり、run の後の pack には無いパッケージ。
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct RemovedPackage {
+    id: String,
+    names: Vec<String>,
+}
+
+/// 1 repo（と commit）について run が行うこと。
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct PlannedRepository {
+    canonical: String,
+    action: &'static str,
+    commit: Option<String>,
+    resolved_via: Option<&'static str>,
+    fetch: Option<&'static str>,
+    build: Vec<String>,
+    lua_build: Option<String>,
+    patches: usize,
+}
+
+impl PlannedRepository {
+    fn new(canonical: String, action: &'static str, commit: Option<String>) -> Self {
+        Self {
+            canonical,
+            action,
+            commit,
+            resolved_via: None,
+            fetch: None,
+            build: Vec::new(),
+            lua_build: None,
+            patches: 0,
+        }
+    }
+}
+
+impl Plan {
+    const VERSION: u8 = 1;
+}
+
+/// 読み込みの結果から repo ごとの予定を組み立てる。`loaded` は読み込んだ repo の
+/// `(canonical, commit)`、`skipped` は読み込まなかった repo の canonical。
+fn repositories(
+    planned: Vec<rsplug::plugin::PlannedFetch>,
+    loaded: &[(String, String)],
+    skipped: &[String],
+    resolved: &[rsplug::plugin::ResolvedRemote],
+    locked: &BTreeMap<String, rsplug::LockedResource>,
+) -> Vec<PlannedRepository> {
+    let resolved_via = |canonical: &str, commit: &str| {
+        resolved
+            .iter()
+            .find(|remote| remote.canonical == canonical && remote.oid == commit)
+            .map(|remote| remote.via)
+            .or_else(|| {
+                locked
+                    .get(canonical)
+                    .is_some_and(|entry| entry.rev == commit)
+                    .then_some("lockfile")
+            })
+            .unwrap_or("installed")
+    };
+    let mut repositories = BTreeMap::new();
+    for (canonical, commit) in loaded {
+        let mut repository =
+            PlannedRepository::new(canonical.clone(), "reuse", Some(commit.clone()));
+        repository.resolved_via = Some(resolved_via(canonical, commit));
+        repositories.insert((canonical.clone(), Some(commit.clone())), repository);
+    }
+    for fetch in planned {
+        let mut repository = PlannedRepository::new(
+            fetch.canonical.clone(),
+            if fetch.fetch.is_some() {
+                "fetch"
+            } else {
+                "materialize"
+            },
+            Some(fetch.oid.clone()),
+        );
+        repository.resolved_via = Some(resolved_via(&fetch.canonical, &fetch.oid));
+        repository.fetch = fetch.fetch;
+        repository.build = fetch.build;
+        repository.lua_build = fetch.lua_build;
+        repository.patches = fetch.patches;
+        repositories.insert((fetch.canonical, Some(fetch.oid)), repository);
+    }
+    for canonical in skipped {
+        if repositories.keys().any(|(planned, _)| planned == canonical) {
+            continue;
+        }
+        repositories.insert(
+            (canonical.clone(), None),
+            PlannedRepository::new(canonical.clone(), "skip", None),
+        );
+    }
+    repositories.into_values().collect()
+}
+
+/// インストール済みのユーザパッケージのうち、予定に無いもの。
+fn removed(
+    installed: BTreeMap<String, Vec<String>>,
+    packages: &[PlannedPackage],
+) -> Vec<RemovedPackage> {
+    installed
+        .into_iter()
+        .filter(|(id, _)| !packages.iter().any(|package| package.id == *id))
+        .map(|(id, names)| RemovedPackage { id, names })
+        .collect()
+}
+
+fn render_text(plan: &Plan) -> String {
+    let mut out = String::new();
+    for repository in &plan.repositories {
+        let _ = write!(out, "{:<11} {}", repository.action, repository.canonical);
+        if let Some(commit) = &repository.commit {
+            let _ = write!(out, " @ {}", &commit[..commit.len().min(12)]);
+        }
+        let mut steps = Vec::new();
+        if let Some(fetch) = repository.fetch {
+            steps.push(format!("via {fetch}"));
+        }
+        if repository.patches > 0 {
+            steps.push(format!("{} patch(es)", repository.patches));
+        }
+        for build in &repository.build {
+            steps.push(format!("build: {build}"));
+        }
+        if repository.lua_build.is_some() {
+            steps.push("lua_build".to_string());
+        }
+        if !steps.is_empty() {
+            let _ = write!(out, " ({})", steps.join(", "));
+        }
+        out.push('\n');
+    }
+    for package in &plan.packages {
+        let _ = write!(
+            out,
+            "{:<11} {} <- {}",
+            if package.names.len() > 1 {
+                "merge"
+            } else {
+                "place"
+            },
+            package.id,
+            package.names.join(", ")
+        );
+        if package.system {
+            out.push_str(" [system]");
+        }
+        let _ = writeln!(out, " ({} entries)", package.entries.len());
+        for entry in package.entries.keys() {
+            let _ = writeln!(out, "              {}", entry.display());
+        }
+    }
+    for package in &plan.removed {
+        let _ = writeln!(
+            out,
+            "{:<11} {} ({})",
+            "remove",
+            package.id,
+            package.names.join(", ")
+        );
+    }
+    out
+}
+
+/// `rsplug plan`・`--dry-run`: 読み込みまで済んだ run の予定を標準出力へ書き、
+/// 作る snapshot・置く package・外す package の数を返す。
+pub(crate) async fn print_plan(
+    args: &PlanArgs,
+    packpath: &Path,
+    catalogs: &rsplug::RepoJobRegistry,
+    state: &rsplug::PackPlan,
+    loaded: &[(String, String)],
+    skipped: &[String],
+    locked: &BTreeMap<String, rsplug::LockedResource>,
+) -> Result<(usize, usize, usize), Error> {
+    let planned = catalogs.planned_fetches();
+    let snapshots = planned.len();
+    let installed = match rsplug::pack_plan::user_package_names(packpath).await {
+        Ok(installed) => installed,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let packages = state.planned_packages();
+    let plan = Plan {
+        version: Plan::VERSION,
+        repositories: repositories(
+            planned,
+            loaded,
+            skipped,
+            &catalogs.resolved_remotes().await,
+            locked,
+        ),
+        removed: removed(installed, &packages),
+        packages,
+    };
+    let packages = plan.packages.len();
+    let removed = plan.removed.len();
+    let rendered = if args.json {
+        let mut json = serde_json::to_string(&plan).map_err(std::io::Error::other)?;
+        json.push('\n');
+        json
+    } else {
+        render_text(&plan)
+    };
+    {
+        use std::io::Write;
+        std::io::stdout().write_all(rendered.as_bytes())?;
+    }
+    Ok((snapshots, packages, removed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repositories_combine_loaded_planned_and_skipped() {
+        let commit = "0123456789abcdef0123456789abcdef01234567";
+        let newer = "89abcdef0123456789abcdef0123456789abcdef";
+        let planned = vec![rsplug::plugin::PlannedFetch {
+            canonical: "github.com/o/b".into(),
+            oid: newer.into(),
+            fetch: Some("tarball"),
+            build: vec!["make".into()],
+            lua_build: None,
+            patches: 0,
+        }];
+        let loaded = [("github.com/o/a".to_string(), commit.to_string())];
+        let skipped = ["github.com/o/b".to_string(), "github.com/o/c".to_string()];
+        let resolved = [rsplug::plugin::ResolvedRemote {
+            canonical: "github.com/o/b".into(),
+            rev: None,
+            oid: newer.into(),
+            via: "api",
+        }];
+        let locked = BTreeMap::from([(
+            "github.com/o/a".to_string(),
+            rsplug::LockedResource {
+                kind: rsplug::LockedResourceType::Git,
+                rev: commit.into(),
+            },
+        )]);
+
+        let repositories = repositories(planned, &loaded, &skipped, &resolved, &locked);
+        let summary: Vec<_> = repositories
+            .iter()
+            .map(|r| (r.canonical.as_str(), r.action, r.resolved_via, r.fetch))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("github.com/o/a", "reuse", Some("lockfile"), None),
+                ("github.com/o/b", "fetch", Some("api"), Some("tarball")),
+                ("github.com/o/c", "skip", None, None),
+            ]
+        );
+        assert_eq!(repositories[1].build, ["make"]);
+    }
+
+    #[test]
+    fn removed_lists_installed_packages_missing_from_the_plan() {
+        let installed = BTreeMap::from([
+            ("kept".to_string(), vec!["a.nvim".to_string()]),
+            (
+                "dropped".to_string(),
+                vec!["b.nvim".to_string(), "c.nvim".to_string()],
+            ),
+        ]);
+        let packages = [PlannedPackage {
+            id: "kept".into(),
+            names: vec!["a.nvim".into()],
+            merge_policy: None,
+            system: false,
+            entries: BTreeMap::new(),
+        }];
+
+        assert_eq!(
+            removed(installed, &packages),
+            [RemovedPackage {
+                id: "dropped".into(),
+                names: vec!["b.nvim".into(), "c.nvim".into()],
+            }]
+        );
+    }
+}
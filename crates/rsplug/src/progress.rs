@@ -0,0 +1,210 @@
+//! Structured progress events for external UIs.
+//!
+//! Every event is one JSON object with a fixed set of keys, so a UI inside
+//! Neovim can render live state without parsing the human-readable log:
+//!
+//! ```json
+//! {"event":"progress","phase":"fetch","plugin":"https://github.com/a/b","percent":40,"message":null}
+//! ```
+//!
+//! `phase` is one of `config`, `resolve`, `fetch`, `build`, `load`, `merge`,
+//! `install`, and `error`. `plugin`, `percent` (0–100), and `message` are
+//! `null` when they do not apply. Keys are only ever added, never renamed.
+
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::log::Message;
+use crate::redact::redact;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    /// 設定ファイルの探索と読み込み。
+    Config,
+    /// リモートの rev の解決。
+    Resolve,
+    /// repo の取得。
+    Fetch,
+    /// build フックの実行。
+    Build,
+    /// プラグインの読み込み（取得と build を含む全体の進み具合）。
+    Load,
+    /// パッケージの併合。
+    Merge,
+    /// パッケージの配置。
+    Install,
+    Error,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProgressEvent {
+    pub phase: Phase,
+    pub plugin: Option<String>,
+    pub percent: Option<u8>,
+    pub message: Option<String>,
+}
+
+impl ProgressEvent {
+    fn new(phase: Phase) -> Self {
+        Self {
+            phase,
+            plugin: None,
+            percent: None,
+            message: None,
+        }
+    }
+
+    fn plugin(mut self, plugin: impl Into<String>) -> Self {
+        self.plugin = Some(plugin.into());
+        self
+    }
+
+    fn percent(mut self, done: usize, total: usize) -> Self {
+        self.percent = Some(
+            (done.min(total) * 100)
+                .checked_div(total)
+                .map_or(100, |percent| percent as u8),
+        );
+        self
+    }
+
+    fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    /// `"event":"progress"` を添えた 1 行分の JSON。daemon の応答と区別できるようにする。
+    pub fn to_json(&self) -> Value {
+        let mut value = serde_json::to_value(self).expect("progress events always serialize");
+        value["event"] = json!("progress");
+        value
+    }
+}
+
+/// `Message` を `ProgressEvent` に変換する。読み込みの総数を覚えて割合を出す。
+#[derive(Default)]
+pub struct ProgressEvents {
+    load_total: usize,
+    loaded: usize,
+}
+
+impl ProgressEvents {
+    pub fn event(&mut self, message: &Message) -> Option<ProgressEvent> {
+        let event = match message {
+            Message::ConfigFound(path) => {
+                ProgressEvent::new(Phase::Config).message(path.display().to_string())
+            }
+            Message::ConfigWalkFinish => ProgressEvent::new(Phase::Config).percent(1, 1),
+            Message::GraphQLResolveProgress { resolved, total } => {
+                ProgressEvent::new(Phase::Resolve).percent(*resolved, *total)
+            }
+            Message::Cache(stage, url) => {
+                let (stage, done) = match stage.strip_suffix(":done") {
+                    Some(stage) => (stage, true),
+                    None => (*stage, false),
+                };
+                let phase = if stage == "Updating" {
+                    Phase::Resolve
+                } else {
+                    Phase::Fetch
+                };
+                let event = ProgressEvent::new(phase).plugin(url.as_ref());
+                let event = if done { event.percent(1, 1) } else { event };
+                event.message(stage.to_lowercase())
+            }
+            Message::CacheFetchObjectsProgress {
+                id,
+                total_objs_count,
+                received_objs_count,
+            } => ProgressEvent::new(Phase::Fetch)
+                .plugin(id.as_str())
+                .percent(*received_objs_count, *total_objs_count),
+            Message::CacheBuildProgress { id, line, .. } => ProgressEvent::new(Phase::Build)
+                .plugin(id.as_str())
+                .message(redact(line)),
+            Message::CacheBuildFinished { id, success } => ProgressEvent::new(Phase::Build)
+                .plugin(id.as_str())
+                .percent(1, 1)
+                .message(if *success { "succeeded" } else { "failed" }),
+            Message::LoadBegin { total } => {
+                self.load_total = *total;
+                self.loaded = 0;
+                ProgressEvent::new(Phase::Load).percent(0, *total)
+            }
+            Message::LoadPluginDone => {
+                self.loaded += 1;
+                ProgressEvent::new(Phase::Load).percent(self.loaded, self.load_total)
+            }
+            Message::LoadDone => ProgressEvent::new(Phase::Load).percent(1, 1),
+            Message::PluginInstalled(name) => ProgressEvent::new(Phase::Load)
+                .plugin(name.as_ref())
+                .message("installed"),
+            Message::PluginUpdated(name) => ProgressEvent::new(Phase::Load)
+                .plugin(name.as_ref())
+                .message("updated"),
+            Message::MergeFinished { total, merged } => ProgressEvent::new(Phase::Merge)
+                .percent(1, 1)
+                .message(format!("merged {merged} of {total} plugins")),
+            Message::InstallPlaced { elapsed, .. } => ProgressEvent::new(Phase::Install)
+                .message(format!("placed in {:.2}s", elapsed.as_secs_f64())),
+            Message::InstallDone => ProgressEvent::new(Phase::Install).percent(1, 1),
+            Message::Error(e) => ProgressEvent::new(Phase::Error).message(redact(&e.to_string())),
+            Message::SyncBegin => {
+                *self = Self::default();
+                return None;
+            }
+            _ => return None,
+        };
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn load_progress_counts_finished_plugins() {
+        let mut events = ProgressEvents::default();
+        let begin = events.event(&Message::LoadBegin { total: 4 }).unwrap();
+        assert_eq!((begin.phase, begin.percent), (Phase::Load, Some(0)));
+        events.event(&Message::LoadPluginDone);
+        let half = events.event(&Message::LoadPluginDone).unwrap();
+        assert_eq!(half.percent, Some(50));
+        assert!(events.event(&Message::SyncBegin).is_none());
+        let empty = events.event(&Message::LoadPluginDone).unwrap();
+        assert_eq!(empty.percent, Some(100));
+    }
+
+    #[test]
+    fn events_have_a_fixed_set_of_keys() {
+        let mut events = ProgressEvents::default();
+        let url: Arc<str> = Arc::from("https://github.com/a/b");
+        let fetched = events
+            .event(&Message::Cache("Fetching:done", url))
+            .unwrap()
+            .to_json();
+        assert_eq!(
+            fetched,
+            json!({
+                "event": "progress",
+                "phase": "fetch",
+                "plugin": "https://github.com/a/b",
+                "percent": 100,
+                "message": "fetching",
+            })
+        );
+        let merged = events
+            .event(&Message::MergeFinished {
+                total: 3,
+                merged: 2,
+            })
+            .unwrap()
+            .to_json();
+        assert_eq!(merged["plugin"], Value::Null);
+        assert_eq!(merged["message"], "merged 2 of 3 plugins");
+        assert!(events.event(&Message::PackSkipped).is_none());
+    }
+}
@@ -0,0 +1,198 @@
+//! Recording a run's network results and replaying them (`--record`, `--replay`).
+//!
+//! `--record <DIR>` writes `recording.json` into DIR once the repositories have
+//! been resolved and fetched, also when the run fails afterwards. It lists the
+//! commit every remote reported for each repository and `rev`, and how it was
+//! asked (GitHub API or the git ref advertisement). It also lists where fetched
+//! data was stored (the repository's `source.git`, or the snapshot a tarball
+//! was unpacked into) and the commit each repository ended up at.
+//!
+//! `--replay <DIR>` reruns the pipeline from such a recording without touching
+//! the network. The recorded commits are pinned the way `--offline` pins the
+//! lock file, so the objects must already be in the repository cache. The
+//! lock file of the replay is kept in DIR and the user's lock file is left alone.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+/// 記録ファイル名（`--record` の DIR 直下）。
+const RECORDING_FILE: &str = "recording.json";
+
+/// `--replay` が使う lock ファイル名（記録と同じ DIR に置く）。
+const REPLAY_LOCKFILE: &str = "replay.lock.json";
+
+/// `recording.json` の内容。
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Recording {
+    version: u8,
+    /// リモートに問い合わせた `(repo, rev)` と、返ってきた commit。
+    resolutions: Vec<RecordedResolution>,
+    /// ネットワークから取得したデータの置き場所。取得順。
+    fetches: Vec<RecordedFetch>,
+    /// 各 repo が最終的に使った commit。run が読み込みまで進んだときだけ。
+    commits: BTreeMap<String, String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct RecordedResolution {
+    canonical: String,
+    rev: Option<String>,
+    oid: String,
+    via: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct RecordedFetch {
+    canonical: String,
+    oid: String,
+    location: PathBuf,
+    tarball: bool,
+}
+
+impl Recording {
+    const VERSION: u8 = 1;
+
+    /// 記録から replay で固定する commit。最終的な commit を優先し、無い repo は
+    /// 最初に記録された解決結果を使う。
+    fn pinned(&self) -> BTreeMap<String, String> {
+        let mut pinned = self.commits.clone();
+        for resolution in &self.resolutions {
+            pinned
+                .entry(resolution.canonical.clone())
+                .or_insert_with(|| resolution.oid.clone());
+        }
+        pinned
+    }
+}
+
+/// `--record`: `catalogs` がこの run で解決・取得したものと、`commits`（canonical → commit）を
+/// `dir/recording.json` に書き出す。
+pub(crate) async fn write(
+    dir: &Path,
+    catalogs: &rsplug::RepoJobRegistry,
+    commits: &[(String, String)],
+) -> Result<PathBuf, Error> {
+    let recording = Recording {
+        version: Recording::VERSION,
+        resolutions: catalogs
+            .resolved_remotes()
+            .await
+            .into_iter()
+            .map(|resolved| RecordedResolution {
+                canonical: resolved.canonical,
+                rev: resolved.rev,
+                oid: resolved.oid,
+                via: resolved.via.to_string(),
+            })
+            .collect(),
+        fetches: catalogs
+            .fetched_data()
+            .into_iter()
+            .map(|fetched| RecordedFetch {
+                canonical: fetched.canonical,
+                oid: fetched.oid,
+                location: fetched.location,
+                tarball: fetched.tarball,
+            })
+            .collect(),
+        commits: commits.iter().cloned().collect(),
+    };
+    tokio::fs::create_dir_all(dir).await?;
+    let path = dir.join(RECORDING_FILE);
+    tokio::fs::write(
+        &path,
+        serde_json::to_vec_pretty(&recording).map_err(std::io::Error::other)?,
+    )
+    .await?;
+    Ok(path)
+}
+
+/// `--replay`: `dir` の記録から固定する commit を lock ファイルに書き、そのパスを返す。
+pub(crate) async fn replay_lockfile(dir: &Path) -> Result<PathBuf, Error> {
+    let path = dir.join(RECORDING_FILE);
+    let content = match tokio::fs::read(&path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(Error::NoRecording { path });
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let recording: Recording = serde_json::from_slice(&content)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    let lock = rsplug::LockFile {
+        version: "2".into(),
+        locked: recording
+            .pinned()
+            .into_iter()
+            .map(|(canonical, rev)| {
+                (
+                    canonical,
+                    rsplug::LockedResource {
+                        kind: rsplug::LockedResourceType::Git,
+                        rev,
+                    },
+                )
+            })
+            .collect(),
+    };
+    let lockfile = dir.join(REPLAY_LOCKFILE);
+    lock.write(&lockfile).await?;
+    Ok(lockfile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolution(canonical: &str, rev: Option<&str>, oid: &str) -> RecordedResolution {
+        RecordedResolution {
+            canonical: canonical.to_string(),
+            rev: rev.map(str::to_string),
+            oid: oid.to_string(),
+            via: "git".to_string(),
+        }
+    }
+
+    #[test]
+    fn final_commits_win_over_resolutions() {
+        let recording = Recording {
+            version: Recording::VERSION,
+            resolutions: vec![
+                resolution("github.com/o/a", None, "aaaa"),
+                resolution("github.com/o/b", Some("v1"), "bbbb"),
+                resolution("github.com/o/b", Some("v2"), "cccc"),
+            ],
+            fetches: Vec::new(),
+            commits: BTreeMap::from([("github.com/o/a".to_string(), "dddd".to_string())]),
+        };
+        assert_eq!(
+            recording.pinned(),
+            BTreeMap::from([
+                ("github.com/o/a".to_string(), "dddd".to_string()),
+                ("github.com/o/b".to_string(), "bbbb".to_string()),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn replay_pins_the_recorded_commits() {
+        let tmp = tempfile::tempdir().unwrap();
+        let registry = rsplug::RepoJobRegistry::new();
+        let commits = [(
+            "github.com/o/a".to_string(),
+            "0123456789abcdef0123456789abcdef01234567".to_string(),
+        )];
+        write(tmp.path(), &registry, &commits).await.unwrap();
+
+        let lockfile = replay_lockfile(tmp.path()).await.unwrap();
+        let lock = rsplug::LockFile::read(&lockfile).await.unwrap();
+        assert_eq!(lock.locked.len(), 1);
+        assert_eq!(lock.locked["github.com/o/a"].rev, commits[0].1);
+
+        let missing = replay_lockfile(&tmp.path().join("none")).await.unwrap_err();
+        assert!(matches!(missing, Error::NoRecording { .. }));
+    }
+}
@@ -0,0 +1,78 @@
+//! Hot reload of the loader inside a running Neovim (`--reload`).
+//!
+//! After a successful install, rsplug connects to the Neovim listening on the
+//! given server address (`$NVIM` inside a `:terminal`) with
+//! `nvim --server <addr> --remote-expr`. The expression calls
+//! `require('_rsplug').reset()`, which deletes the rsplug augroups, stubs, and
+//! the old runtime, and then sources the new `init.lua`, which prepends the
+//! packpath again and sets up the lazy triggers of the new generation.
+//! Start plugins added by the sync are only loaded by the next startup.
+
+use std::{path::Path, time::Duration};
+
+use super::*;
+
+/// 読み込み直しが最後まで進んだときに式が返す値。
+const RELOADED: &str = "rsplug-reloaded";
+
+/// 応答しない Neovim を待つ上限。
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 旧ランタイムを `reset()` で片付け、`init_lua` を読み込み直す式。
+/// Lua 側は二重引用符だけを使い、Vim の単一引用符の文字列に収める。
+fn reload_expr(init_lua: &Path) -> String {
+    let lua = format!(
+        "local rsplug = package.loaded[\"_rsplug\"] \
+         if type(rsplug) == \"table\" and rsplug.reset then rsplug.reset() \
+         else vim.g.rsplug_loaded = nil end \
+         vim.cmd.source(vim.fn.fnameescape(_A)) \
+         return \"{RELOADED}\""
+    );
+    let path = init_lua.to_string_lossy().replace('\'', "''");
+    format!("luaeval('{lua}', '{path}')")
+}
+
+/// `server` の Neovim に `init_lua` のローダを読み込み直させる。失敗の理由を文字列で返す。
+pub(crate) async fn reload(server: &str, init_lua: &Path) -> Result<(), String> {
+    let output = tokio::process::Command::new(rsplug::util::nvim::program())
+        .arg("--server")
+        .arg(server)
+        .arg("--remote-expr")
+        .arg(reload_expr(init_lua))
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = match tokio::time::timeout(TIMEOUT, output).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return Err(e.to_string()),
+        Err(_) => return Err(format!("no reply within {}s", TIMEOUT.as_secs())),
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if output.status.success() && stdout.trim() == RELOADED {
+        return Ok(());
+    }
+    // 接続できない・式がエラーになったときの理由は stderr か stdout に出る。
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let reason = [stderr.trim(), stdout.trim()]
+        .into_iter()
+        .find(|text| !text.is_empty())
+        .unwrap_or("Neovim did not confirm the reload");
+    Err(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_expression_quotes_the_path_for_vim() {
+        let expr = reload_expr(Path::new("/home/o'neil/.cache/rsplug/init.lua"));
+        assert!(expr.starts_with("luaeval('local rsplug = package.loaded[\"_rsplug\"] "));
+        assert!(expr.ends_with("', '/home/o''neil/.cache/rsplug/init.lua')"));
+        // 引数の前では単一引用符が閉じる箇所の他に現れない。
+        let lua = &expr["luaeval('".len()..expr.find("', '").unwrap()];
+        assert!(!lua.contains('\''));
+        assert!(lua.contains("rsplug.reset()"));
+        assert!(lua.contains(&format!("return \"{RELOADED}\"")));
+    }
+}
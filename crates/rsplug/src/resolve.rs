@@ -0,0 +1,228 @@
+//! Revision resolution ahead of an install (`rsplug resolve`).
+//!
+//! Resolving which commit every repository's `rev` points to is the slow,
+//! network-bound part of an install. `rsplug resolve` does only that: it asks
+//! each remote with ls-remote (authenticated for GitHub when a token is set)
+//! and writes the commits to `resolved.json` in the rsplug cache directory,
+//! together with the time the result stops being used. An `install`, `update`,
+//! or `sync` run within that time takes the commits from the file for every
+//! repository it would otherwise resolve remotely, and goes straight to
+//! fetching and placing files. Runs that do not contact remotes (`--locked`,
+//! `--offline`, plain runs) never read it.
+
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use git2::Oid;
+use rsplug::plugin::RepoSource;
+use rsplug::util::{git, github};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinSet;
+
+use super::*;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct ResolveArgs {
+    /// Seconds for which later install, update, and sync runs reuse the result
+    #[arg(long, default_value_t = 600)]
+    pub(crate) ttl: u64,
+}
+
+/// 解決結果を置くファイル名（rsplug のキャッシュディレクトリ直下）。
+const CACHE_FILE: &str = "resolved.json";
+
+/// 同時に問い合わせるリモートの数。
+const CONCURRENCY: usize = 16;
+
+/// `resolved.json` の内容。
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct ResolutionCache {
+    /// これより後（UNIX 時刻の秒）の実行では使わない。
+    expires_at: u64,
+    revs: Vec<ResolvedRev>,
+}
+
+/// 1 repo の `rev`（未指定なら既定ブランチ）が指していた commit。
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct ResolvedRev {
+    canonical: String,
+    rev: Option<String>,
+    oid: String,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+}
+
+impl ResolutionCache {
+    /// `now` の時点で有効なら `(canonical, rev)` → commit を返す。期限切れなら空。
+    fn fresh(self, now: u64) -> Vec<((String, Option<String>), Oid)> {
+        if now >= self.expires_at {
+            return Vec::new();
+        }
+        self.revs
+            .into_iter()
+            .filter_map(
+                |ResolvedRev {
+                     canonical,
+                     rev,
+                     oid,
+                 }| { Some(((canonical, rev), Oid::from_str(&oid).ok()?)) },
+            )
+            .collect()
+    }
+}
+
+/// `app_dir` の解決結果のうち、まだ有効なもの。読めなければ空。
+pub(crate) async fn load_fresh(app_dir: &Path) -> Vec<((String, Option<String>), Oid)> {
+    let Ok(content) = tokio::fs::read(app_dir.join(CACHE_FILE)).await else {
+        return Vec::new();
+    };
+    serde_json::from_slice::<ResolutionCache>(&content)
+        .map(|cache| cache.fresh(now()))
+        .unwrap_or_default()
+}
+
+/// `rsplug resolve`: 全 repo プラグインの `rev` をリモートで解決し、`resolved.json` に書き出す。
+pub(crate) async fn resolve(
+    app_dir: &Path,
+    config_files: Vec<String>,
+    args: &ResolveArgs,
+) -> Result<(), Error> {
+    let heads = ls_remote_heads(config_files, |_, repo| {
+        // wildcard は install 時も問い合わせ方が違う（タグ一覧から選ぶ）。
+        !repo.rev().is_some_and(|rev| rev.contains('*'))
+    })
+    .await?;
+    let mut revs = Vec::new();
+    let mut failed = 0;
+    for (canonical, rev, oid) in heads {
+        match oid {
+            Ok(oid) => revs.push(ResolvedRev {
+                canonical,
+                rev: rev.as_deref().map(ToString::to_string),
+                oid: oid.to_string(),
+            }),
+            Err(e) => {
+                failed += 1;
+                eprintln!("{} {canonical}: {e}", style("warning:").yellow().bold());
+            }
+        }
+    }
+    revs.sort_by(|a, b| (&a.canonical, &a.rev).cmp(&(&b.canonical, &b.rev)));
+    let count = revs.len();
+    let cache = ResolutionCache {
+        expires_at: now().saturating_add(args.ttl),
+        revs,
+    };
+    let path = app_dir.join(CACHE_FILE);
+    tokio::fs::create_dir_all(app_dir).await?;
+    tokio::fs::write(
+        &path,
+        serde_json::to_vec_pretty(&cache).map_err(std::io::Error::other)?,
+    )
+    .await?;
+    println!(
+        "Resolved {count} repositories ({failed} failed), reused for {}s: {}",
+        args.ttl,
+        path.display()
+    );
+    Ok(())
+}
+
+/// ls-remote で解決した head（canonical, 問い合わせた rev, commit）。
+pub(crate) type RemoteHead = (String, Option<Arc<str>>, Result<Oid, rsplug::Error>);
+
+/// 設定中の repo プラグインの `rev` を ls-remote で並行して解決する（GitHub は token 付き）。
+/// dev checkout・commit 固定の rev と、`select` が false を返すものは問い合わせない。
+/// 同じ (canonical, rev) は 1 度だけ問い合わせる。
+pub(crate) async fn ls_remote_heads(
+    config_files: Vec<String>,
+    mut select: impl FnMut(&rsplug::PluginConfig, &RepoSource) -> bool,
+) -> Result<Vec<RemoteHead>, Error> {
+    let mut seen = HashSet::new();
+    let mut tasks = JoinSet::new();
+    let permits = Arc::new(tokio::sync::Semaphore::new(CONCURRENCY));
+    for (_, config) in read_configs(config_files).await? {
+        for plugin in &config.plugins {
+            let Some(repo) = plugin.cache.repo.clone() else {
+                continue;
+            };
+            let rev = repo.rev();
+            if plugin.cache.dev
+                || rev.as_deref().is_some_and(github::is_full_hex_hash)
+                || !select(plugin, &repo)
+            {
+                continue;
+            }
+            let canonical = repo.canonical();
+            if !seen.insert((canonical.clone(), rev.clone())) {
+                continue;
+            }
+            let url = Arc::<str>::from(repo.url());
+            let token = repo
+                .is_github_https()
+                .then(github::token)
+                .flatten()
+                .map(Arc::<str>::from);
+            let permits = Arc::clone(&permits);
+            tasks.spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                let oid = git::ls_remote(url, rev.clone(), token).await;
+                (canonical, rev, oid)
+            });
+        }
+    }
+    let mut heads = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        heads.push(joined.map_err(task::panicked("ls-remote"))?);
+    }
+    Ok(heads)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_unexpired_results_are_reused() {
+        let oid = "0123456789abcdef0123456789abcdef01234567";
+        let cache = || ResolutionCache {
+            expires_at: 1_000,
+            revs: vec![
+                ResolvedRev {
+                    canonical: "github.com/owner/a".into(),
+                    rev: None,
+                    oid: oid.into(),
+                },
+                ResolvedRev {
+                    canonical: "github.com/owner/b".into(),
+                    rev: Some("v1".into()),
+                    oid: "not a commit".into(),
+                },
+            ],
+        };
+        assert_eq!(
+            cache().fresh(999),
+            [(
+                ("github.com/owner/a".to_string(), None),
+                Oid::from_str(oid).unwrap()
+            )]
+        );
+        assert!(cache().fresh(1_000).is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_missing_or_broken_file_resolves_nothing() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(load_fresh(tmp.path()).await.is_empty());
+        std::fs::write(tmp.path().join(CACHE_FILE), "{").unwrap();
+        assert!(load_fresh(tmp.path()).await.is_empty());
+    }
+}
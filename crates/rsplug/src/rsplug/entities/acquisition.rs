@@ -11,15 +11,9 @@ pub(super) async fn ensure_source_git(ctx: &FetchCtx<'_>) -> Result<bool, Error>
         .acquisition_cell(ctx.source_git, ctx.oid, ctx.dotgit)
         .await;
     let result = cell
-        .get_or_init(|| async {
-            ensure_source_git_inner(ctx)
-                .await
-                .map_err(|error| Arc::from(error.to_string()))
-        })
+        .get_or_init(|| async { ensure_source_git_inner(ctx).await.map_err(Arc::new) })
         .await;
-    result
-        .clone()
-        .map_err(|message| Error::Io(std::io::Error::other(message.as_ref())))
+    result.clone().map_err(|error| error.unshare())
 }
 
 async fn ensure_source_git_inner(ctx: &FetchCtx<'_>) -> Result<bool, Error> {
@@ -30,6 +24,8 @@ async fn ensure_source_git_inner(ctx: &FetchCtx<'_>) -> Result<bool, Error> {
     let _source_guard = source_lock.lock().await;
     let mut repo = match git::open_source(ctx.source_git).await {
         Ok(r) => r,
+        // 事前検査を通っていれば来ないが、ネットワークに触れずに失敗させる。
+        Err(_) if ctx.offline => return Err(offline_missing(ctx)),
         Err(_) if ctx.install || ctx.update => {
             let _git = super::util::resources::git().await?;
             msg(Message::Cache("Initializing", ctx.url.clone()));
@@ -52,17 +48,71 @@ async fn ensure_source_git_inner(ctx: &FetchCtx<'_>) -> Result<bool, Error> {
     if repo.contains_oid(ctx.oid).await? {
         return Ok(true);
     }
+    if ctx.offline {
+        return Err(offline_missing(ctx));
+    }
     let _git = super::util::resources::git().await?;
     msg(Message::Cache("Fetching", ctx.url.clone()));
     crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::GitFetch);
     let host = util::repo::host_of(ctx.url);
+    let cancel = git::Cancel::default();
+    // 時間は接続枠を得てから測る（他の取得の順番待ちで切らない）。
     ctx.network
-        .run(&host, repo.fetch_oid(ctx.oid, ctx.token.clone()))
+        .run(
+            &host,
+            within(
+                ctx,
+                "fetch",
+                ctx.fetch_timeout,
+                &cancel,
+                repo.fetch_oid(ctx.url.clone(), ctx.oid, ctx.token.clone(), cancel.clone()),
+            ),
+        )
         .await?;
     msg(Message::Cache("Fetching:done", ctx.url.clone()));
+    ctx.jobs.record_fetch(FetchedData {
+        canonical: ctx.canonical.to_owned(),
+        oid: ctx.oid.to_string(),
+        location: ctx.source_git.to_path_buf(),
+        tarball: false,
+    });
     Ok(true)
 }
 
+/// `limit` を過ぎたら `cancel` で裏の git 操作に中断を伝え、[`Error::Timeout`] で返る。
+async fn within<T>(
+    ctx: &FetchCtx<'_>,
+    phase: &'static str,
+    limit: Option<std::time::Duration>,
+    cancel: &super::util::git::Cancel,
+    op: impl Future<Output = Result<T, Error>>,
+) -> Result<T, Error> {
+    let Some(limit) = limit else {
+        return op.await;
+    };
+    match tokio::time::timeout(limit, op).await {
+        Ok(result) => result,
+        Err(_) => {
+            cancel.cancel();
+            Err(Error::Timeout {
+                phase,
+                plugin: display_name(ctx.source_name, ctx.logid),
+                after: limit,
+            })
+        }
+    }
+}
+
+fn offline_missing(ctx: &FetchCtx<'_>) -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!(
+            "{} is not in the cache for {} and --offline forbids fetching it",
+            ctx.oid, ctx.url
+        ),
+    ))
+}
+
 pub(super) async fn materialize(
     ctx: &FetchCtx<'_>,
     dest: &Path,
@@ -85,12 +135,10 @@ pub(super) async fn materialize(
         .get_or_init(|| async {
             materialize_inner(ctx, dest, use_tarball)
                 .await
-                .map_err(|error| Arc::<str>::from(error.to_string()))
+                .map_err(Arc::new)
         })
         .await;
-    let snapshot = result
-        .clone()
-        .map_err(|message| Error::Io(std::io::Error::other(message.as_ref())))?;
+    let snapshot = result.clone().map_err(|error| error.unshare())?;
     let Some(snapshot) = snapshot else {
         return Ok(None);
     };
@@ -127,21 +175,33 @@ async fn materialize_inner(
             msg(Message::Cache("Fetching", ctx.url.clone()));
             let head_rev = ctx.oid.to_string();
             crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::TarballFetch);
+            // download は async なので、時間切れの drop だけで止まる。
             let download = ctx
                 .network
                 .run(
                     "codeload.github.com",
-                    TarballFetch.download(
-                        ctx.http_client,
-                        ctx.url.as_ref(),
-                        &head_rev,
-                        dest,
-                        ctx.token.as_deref(),
+                    within(
+                        ctx,
+                        "fetch",
+                        ctx.fetch_timeout,
+                        &git::Cancel::default(),
+                        TarballFetch.download(
+                            ctx.http_client,
+                            ctx.url.as_ref(),
+                            &head_rev,
+                            dest,
+                            ctx.token.as_deref(),
+                        ),
                     ),
                 )
                 .await;
             let ok = match download {
                 Ok(archive) => archive.extract_to_snapshot(dest).await.is_ok(),
+                // 時間を使い切ったので、Git 経路で同じだけ待ち直さない。
+                Err(error @ Error::Timeout { .. }) => {
+                    crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::PermitError);
+                    return Err(error);
+                }
                 Err(_) => false,
             };
             if ok {
@@ -155,6 +215,12 @@ async fn materialize_inner(
             ok
         };
         if tarball_ok {
+            ctx.jobs.record_fetch(FetchedData {
+                canonical: ctx.canonical.to_owned(),
+                oid: ctx.oid.to_string(),
+                location: dest.to_path_buf(),
+                tarball: true,
+            });
             return Ok(Some(MaterializedSnapshot {
                 root: Arc::from(dest.to_path_buf()),
                 plain: true,
@@ -167,7 +233,30 @@ async fn materialize_inner(
 
     let _git = super::util::resources::git().await?;
     crate::rsplug::perf::failpoint("materialize_after")?;
-    git::init_snapshot(dest.to_path_buf(), ctx.source_git, ctx.oid).await?;
+    // checkout は隠し staging に作ってから rename で公開する。中断（Ctrl-C・エラー）で
+    // 途中の checkout が完成した snapshot として再利用されないようにする。staging は
+    // TempDir の drop で消える（tarball 経路の `.rsplug-tarball-*` と同じ扱い）。
+    let parent = dest.parent().ok_or_else(|| {
+        Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "snapshot root has no parent",
+        ))
+    })?;
+    tokio::fs::create_dir_all(parent).await?;
+    let staging = tempfile::Builder::new()
+        .prefix(".rsplug-checkout-")
+        .tempdir_in(parent)?;
+    let checkout = staging.path().join("tree");
+    let cancel = git::Cancel::default();
+    within(
+        ctx,
+        "checkout",
+        ctx.checkout_timeout,
+        &cancel,
+        git::init_snapshot(checkout.clone(), ctx.source_git, ctx.oid, cancel.clone()),
+    )
+    .await?;
+    tokio::fs::rename(&checkout, dest).await?;
     Ok(Some(MaterializedSnapshot {
         root: Arc::from(dest.to_path_buf()),
         plain: false,
@@ -12,12 +12,13 @@ pub(super) async fn assemble_loaded_plugin(
     identity: &RepoSnapshotIdentity,
     catalogs: &SnapshotCatalogCache,
     dotgit: bool,
+    symlink: bool,
     merge: &MergeConfig,
     lazy_type: LazyType,
     source_name: Option<String>,
     script: SetupScript,
     order: usize,
-    merge_enabled: bool,
+    merge_policy: Option<MergePolicy>,
     was_updated: bool,
     was_installed: bool,
     logid: &str,
@@ -27,12 +28,13 @@ pub(super) async fn assemble_loaded_plugin(
         identity,
         catalogs,
         dotgit,
+        symlink,
         merge,
         lazy_type,
         source_name,
         script,
         order,
-        merge_enabled,
+        merge_policy,
         was_updated,
         was_installed,
         logid,
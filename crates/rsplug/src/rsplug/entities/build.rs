@@ -131,3 +131,115 @@ pub(super) async fn run_repo_build(
     }
     Ok(())
 }
+
+/// Patch files configured by `patches`, read once in the EARLY phase so the
+/// content can be folded into the snapshot key before materialization.
+#[derive(Clone, Default)]
+pub(super) struct Patches(Arc<[(PathBuf, Arc<[u8]>)]>);
+
+impl Patches {
+    /// Reads every patch in configuration order. A missing or unreadable file
+    /// is reported with the configured path rather than a bare IO error.
+    pub(super) async fn read(paths: &[PathBuf], repo: &Arc<str>) -> Result<Self, Error> {
+        let mut patches = Vec::with_capacity(paths.len());
+        for path in paths {
+            let content = tokio::fs::read(path)
+                .await
+                .map_err(|source| Error::PatchRead {
+                    repo: repo.clone(),
+                    path: path.clone(),
+                    source,
+                })?;
+            patches.push((path.clone(), Arc::from(content)));
+        }
+        Ok(Self(patches.into()))
+    }
+
+    /// Digest of the patch contents in order. File paths are excluded so that
+    /// moving a patch does not invalidate the snapshot; `None` without patches
+    /// keeps existing snapshot keys and plugin IDs unchanged.
+    pub(super) fn digest(&self) -> Option<[u8; 16]> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let contents: Vec<&[u8]> = self.0.iter().map(|(_, content)| content.as_ref()).collect();
+        Some(crate::rsplug::util::hash::digest_hash(&contents))
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Applies the patches to a freshly materialized worktree, in order. A
+    /// patch that does not apply leaves the worktree untouched for that patch
+    /// and fails with the files whose hunks were rejected.
+    pub(super) async fn apply(
+        &self,
+        repository: &MaterializedRepo,
+        repo_name: &Arc<str>,
+        rev: &str,
+    ) -> Result<(), Error> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        // Patched plugins are forced onto GitFetch, so this is always a Git worktree.
+        let MaterializedRepo::Git(repository) = repository else {
+            return Err(Error::Io(std::io::Error::other(format!(
+                "patches require a git checkout of {repo_name}"
+            ))));
+        };
+        for (path, content) in self.0.iter() {
+            let files = repository.apply_patch(content.clone()).await?;
+            if !files.is_empty() {
+                return Err(Error::PatchConflict {
+                    repo: repo_name.clone(),
+                    patch: path.clone(),
+                    rev: rev.to_string(),
+                    files,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn builds_run_within_the_build_jobs_limit() {
+        let tmp = tempfile::tempdir().unwrap();
+        let log = tmp.path().join("log");
+        let workdir: Arc<Path> = Arc::from(tmp.path());
+        let repo: Arc<str> = Arc::from("owner/repo");
+        let limit = super::super::util::resources::build_jobs();
+        let script = format!("echo s >> '{0}'; sleep 0.2; echo e >> '{0}'", log.display());
+        let build = vec!["sh".to_string(), "-c".to_string(), script];
+        let mut builds = tokio::task::JoinSet::new();
+        for i in 0..limit + 2 {
+            let (build, workdir, repo) = (build.clone(), workdir.clone(), repo.clone());
+            builds.spawn(async move {
+                run_repo_build(&build, None, workdir, Vec::new(), &format!("b{i}"), &repo).await
+            });
+        }
+        while let Some(result) = builds.join_next().await {
+            result.unwrap().unwrap();
+        }
+
+        // 始まった build と終わった build の差が、同時に走っていた数。
+        let mut running = 0usize;
+        let mut peak = 0usize;
+        for line in std::fs::read_to_string(&log).unwrap().lines() {
+            if line == "s" {
+                running += 1;
+                peak = peak.max(running);
+            } else {
+                running -= 1;
+            }
+        }
+        assert_eq!(running, 0);
+        assert!((1..=limit).contains(&peak), "{peak} builds ran at once");
+    }
+}
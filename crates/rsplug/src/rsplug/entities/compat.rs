@@ -0,0 +1,159 @@
+use std::{fmt, str::FromStr};
+
+use serde::Deserialize;
+use serde_with::DeserializeFromStr;
+
+pub use crate::rsplug::util::nvim::NvimVersion;
+
+/// プラグインごとの互換性の宣言（TOML の `[plugins.compat]`）。
+/// 満たさなければ警告してプラグインを外す。
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct CompatConfig {
+    /// 対応する Neovim のバージョン範囲（例: `">=0.10"`, `">=0.9, <0.11"`）。
+    #[serde(default)]
+    pub nvim: Option<VersionReq>,
+}
+
+impl CompatConfig {
+    /// `nvim` が要件を満たさなければ、満たされなかった要件を返す。
+    pub fn unmet(&self, nvim: NvimVersion) -> Option<&VersionReq> {
+        self.nvim.as_ref().filter(|req| !req.matches(nvim))
+    }
+}
+
+/// エントリを使う条件（TOML の `[plugins.when]`）。成り立たなければ黙って外す。
+/// 同じプラグインの別エントリを条件で切り替えるのに使う。
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Condition {
+    /// 実行環境の Neovim のバージョン範囲。
+    #[serde(default)]
+    pub nvim: Option<VersionReq>,
+}
+
+impl Condition {
+    /// 条件が成り立つか。バージョンが分からない（`None`）ときの `nvim` 条件は成り立たない。
+    pub fn holds(&self, nvim: Option<NvimVersion>) -> bool {
+        self.nvim
+            .as_ref()
+            .is_none_or(|req| nvim.is_some_and(|version| req.matches(version)))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Ge,
+    Gt,
+    Le,
+    Lt,
+    Eq,
+}
+
+impl Op {
+    fn as_str(self) -> &'static str {
+        match self {
+            Op::Ge => ">=",
+            Op::Gt => ">",
+            Op::Le => "<=",
+            Op::Lt => "<",
+            Op::Eq => "=",
+        }
+    }
+}
+
+/// カンマ区切りの比較（`>=0.9, <0.11`）の積。演算子の無い `0.10` は `>=0.10` とみなす。
+#[derive(DeserializeFromStr, Clone, Debug, PartialEq, Eq)]
+pub struct VersionReq(Vec<(Op, NvimVersion)>);
+
+impl VersionReq {
+    pub fn matches(&self, version: NvimVersion) -> bool {
+        self.0.iter().all(|(op, bound)| match op {
+            Op::Ge => version >= *bound,
+            Op::Gt => version > *bound,
+            Op::Le => version <= *bound,
+            Op::Lt => version < *bound,
+            Op::Eq => version == *bound,
+        })
+    }
+}
+
+impl FromStr for VersionReq {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let comparators = s
+            .split(',')
+            .map(|comparator| {
+                let comparator = comparator.trim();
+                // 2 文字の演算子を先に試す。
+                let (op, rest) = [
+                    (">=", Op::Ge),
+                    ("<=", Op::Le),
+                    (">", Op::Gt),
+                    ("<", Op::Lt),
+                    ("=", Op::Eq),
+                ]
+                .into_iter()
+                .find_map(|(prefix, op)| comparator.strip_prefix(prefix).map(|rest| (op, rest)))
+                .unwrap_or((Op::Ge, comparator));
+                Ok((op, rest.parse::<NvimVersion>()?))
+            })
+            .collect::<Result<Vec<_>, String>>()
+            .map_err(|e| format!("invalid Neovim version requirement {s:?}: {e}"))?;
+        Ok(VersionReq(comparators))
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (op, version)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}{version}", op.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(s: &str) -> NvimVersion {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn requirements_are_conjunctions_of_comparators() {
+        let req: VersionReq = ">=0.9, <0.11".parse().unwrap();
+        assert!(!req.matches(v("0.8.3")));
+        assert!(req.matches(v("0.9.0")));
+        assert!(req.matches(v("0.10.4")));
+        assert!(!req.matches(v("0.11.0")));
+        assert_eq!(req.to_string(), ">=0.9.0, <0.11.0");
+
+        let bare: VersionReq = "0.10".parse().unwrap();
+        assert_eq!(bare.to_string(), ">=0.10.0");
+        assert!(
+            "=0.10.1"
+                .parse::<VersionReq>()
+                .unwrap()
+                .matches(v("0.10.1"))
+        );
+        assert!(">=zero".parse::<VersionReq>().is_err());
+        assert!("".parse::<VersionReq>().is_err());
+    }
+
+    #[test]
+    fn conditions_on_an_unknown_version_do_not_hold() {
+        let when = Condition {
+            nvim: Some("<0.11".parse().unwrap()),
+        };
+        assert!(when.holds(Some(v("0.10.4"))));
+        assert!(!when.holds(Some(v("0.11.0"))));
+        assert!(!when.holds(None));
+        assert!(Condition::default().holds(None));
+    }
+}
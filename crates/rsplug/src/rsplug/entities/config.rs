@@ -3,6 +3,7 @@ use std::{
     hash::Hash,
     iter::{Sum, once},
     ops::AddAssign,
+    path::{Path, PathBuf},
     sync::Arc,
 };
 
@@ -10,8 +11,8 @@ use dag::DagNode;
 use file_specifier::FileSpecifier;
 use hashbrown::HashMap;
 use sailfish::runtime::Render;
-use serde::{Deserialize, Deserializer};
-use serde_with::{FromInto, OneOrMany, serde_as};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_with::{DurationSeconds, FromInto, OneOrMany, TryFromInto, serde_as};
 
 use super::*;
 
@@ -22,13 +23,248 @@ impl<T: IntoIterator<Item = Config>> From<T> for Config {
 }
 
 /// 設定ファイルの構造体
-#[serde_as]
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
+#[serde(from = "ConfigFile")]
 pub struct Config {
-    #[serde(default)]
     pub(crate) plugins: Vec<PluginConfig>,
 }
 
+/// 設定ファイル 1 つ分。ファイル全体の既定値は、合算する前に各エントリへ配る。
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    plugins: Vec<PluginConfig>,
+    /// `auto_setup` を書かないエントリの既定値。
+    #[serde(default)]
+    auto_setup: bool,
+}
+
+impl From<ConfigFile> for Config {
+    fn from(value: ConfigFile) -> Self {
+        let ConfigFile {
+            mut plugins,
+            auto_setup,
+        } = value;
+        if auto_setup {
+            for plugin in &mut plugins {
+                plugin.script.auto_setup.get_or_insert(AutoSetup::Detect);
+            }
+        }
+        Config { plugins }
+    }
+}
+
+impl Config {
+    /// 実行環境の Neovim（`nvim`、不明なら `None`）に合わせてエントリを選ぶ。
+    /// `when` が成り立たないエントリは黙って外し、`compat.nvim` を満たさないエントリと、
+    /// 同じ設定ファイル内で外れた名前に（推移的に）依存するエントリは外して (名前, 理由) を返す。
+    /// `when` で切り替えた別エントリが同じ名前を提供していれば依存元は残す。
+    /// 合算後ではなく設定ファイルごとに呼ぶ（EARLY を kick する前に外すため）。
+    pub fn retain_for_host(&mut self, nvim: Option<NvimVersion>) -> Vec<(String, String)> {
+        let mut skipped: Vec<(String, String)> = Vec::new();
+        let mut removed = BTreeSet::new();
+        loop {
+            let present: BTreeSet<String> = self
+                .plugins
+                .iter()
+                .map(PluginConfig::compute_internal_id)
+                .collect();
+            let before = self.plugins.len();
+            self.plugins.retain(|plugin| {
+                let reason = if !plugin.when.holds(nvim) {
+                    None
+                } else if let Some((version, req)) =
+                    nvim.and_then(|version| Some((version, plugin.compat.unmet(version)?)))
+                {
+                    Some(format!("requires Neovim {req}, found {version}"))
+                } else if let Some(dep) = plugin
+                    .depends
+                    .iter()
+                    .find(|dep| removed.contains(*dep) && !present.contains(*dep))
+                {
+                    Some(format!("depends on skipped {dep}"))
+                } else {
+                    return true;
+                };
+                let id = plugin.compute_internal_id();
+                if let Some(reason) = reason {
+                    skipped.push((id.clone(), reason));
+                }
+                removed.insert(id);
+                false
+            });
+            if self.plugins.len() == before {
+                return skipped;
+            }
+        }
+    }
+
+    /// 設定ファイル `base` ディレクトリ基準の相対パス（`patches`）を絶対化する。
+    /// 複数の設定ファイルを合算する前に、ファイルごとに呼ぶ。
+    pub fn resolve_relative_paths(mut self, base: &Path) -> Self {
+        for plugin in &mut self.plugins {
+            for patch in &mut plugin.cache.patches {
+                if patch.is_relative() {
+                    *patch = base.join(&*patch);
+                }
+            }
+        }
+        self
+    }
+
+    /// 設定ファイルの内容をパースする。`extends` の継承と `template = true` のエントリの除外は、
+    /// 型付きのデシリアライズ（と DAG の構築）より前にエントリの表の上で行う。
+    pub fn from_toml(input: &str) -> Result<Self, toml::de::Error> {
+        let mut table: toml::Table = toml::from_str(input)?;
+        let inherits = match table.get("plugins") {
+            Some(toml::Value::Array(plugins)) => {
+                plugins
+                    .iter()
+                    .any(|p| p.get("extends").is_some() || p.get("template").is_some())
+                    && plugins.iter().all(toml::Value::is_table)
+            }
+            _ => false,
+        };
+        // 継承が無ければ（または表でない要素があれば）位置付きのエラーを保つため文字列から読む。
+        if !inherits {
+            return toml::from_str(input);
+        }
+        let plugins = resolve_entries(&table)?;
+        table.insert(
+            "plugins".to_string(),
+            toml::Value::Array(plugins.into_iter().map(toml::Value::Table).collect()),
+        );
+        toml::Value::Table(table).try_into()
+    }
+
+    /// `extends` を解決し、`template` のエントリを除いた表のままのエントリ（`rsplug export` 用）。
+    /// 設定として読めない入力はエラーにする。
+    pub fn plugin_tables(input: &str) -> Result<Vec<toml::Table>, toml::de::Error> {
+        Self::from_toml(input)?;
+        resolve_entries(&toml::from_str(input)?)
+    }
+}
+
+/// 設定ファイルの `plugins` の `extends` を解決し、`template = true` のエントリを除く。
+fn resolve_entries(table: &toml::Table) -> Result<Vec<toml::Table>, toml::de::Error> {
+    use serde::de::Error as _;
+
+    let Some(plugins) = table.get("plugins") else {
+        return Ok(Vec::new());
+    };
+    let mut entries = plugins
+        .as_array()
+        .ok_or_else(|| toml::de::Error::custom("`plugins` must be an array"))?
+        .iter()
+        .map(|p| p.as_table().cloned())
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| toml::de::Error::custom("every entry of `plugins` must be a table"))?;
+    resolve_extends(&mut entries).map_err(toml::de::Error::custom)?;
+    let mut plugins = Vec::with_capacity(entries.len());
+    for entry in entries {
+        match entry.get("template") {
+            None | Some(toml::Value::Boolean(false)) => {}
+            Some(toml::Value::Boolean(true)) => continue,
+            Some(_) => {
+                return Err(toml::de::Error::custom("`template` must be a boolean"));
+            }
+        }
+        plugins.push(entry);
+    }
+    Ok(plugins)
+}
+
+/// `extends` で継承しないキー。取得元に結びつくものと、エントリ自身の名前・継承の指定。
+const NOT_INHERITED: &[&str] = &[
+    "repo", "source", "branch", "upstream", "dev", "patches", "name", "extends", "template",
+];
+
+/// 表のままのエントリの名前（`name` ?? repo basename）。`extends` の参照先を探すのに使う。
+fn entry_name(entry: &toml::Table) -> Option<String> {
+    if let Some(name) = entry.get("name").and_then(toml::Value::as_str) {
+        return Some(name.to_string());
+    }
+    let repo = entry
+        .get("repo")
+        .or_else(|| entry.get("source"))?
+        .as_str()?;
+    let repo: RepoSource = repo.parse().ok()?;
+    Some(repo.basename().to_string())
+}
+
+/// 同じ設定ファイル内の `extends` を解決する。継承元のキーのうち、継承先が書いていないものを
+/// （表ごと）写す。継承元が更に `extends` していれば先にそちらを解決し、循環はエラーにする。
+fn resolve_extends(entries: &mut [toml::Table]) -> Result<(), String> {
+    let names: Vec<Option<String>> = entries.iter().map(entry_name).collect();
+    let mut done = vec![false; entries.len()];
+    for index in 0..entries.len() {
+        resolve_extends_of(index, entries, &names, &mut done, &mut Vec::new())?;
+    }
+    Ok(())
+}
+
+/// `entries[index]` の継承を解決する。`path` は解決中の継承の連なり（循環の検出用）。
+fn resolve_extends_of(
+    index: usize,
+    entries: &mut [toml::Table],
+    names: &[Option<String>],
+    done: &mut [bool],
+    path: &mut Vec<usize>,
+) -> Result<(), String> {
+    if done[index] {
+        return Ok(());
+    }
+    let label = |i: usize| names[i].clone().unwrap_or_else(|| format!("plugins[{i}]"));
+    if let Some(start) = path.iter().position(|&i| i == index) {
+        let cycle: Vec<String> = path[start..]
+            .iter()
+            .chain(once(&index))
+            .map(|&i| label(i))
+            .collect();
+        return Err(format!("`extends` cycle: {}", cycle.join(" -> ")));
+    }
+    let Some(extends) = entries[index].get("extends") else {
+        done[index] = true;
+        return Ok(());
+    };
+    let Some(base) = extends.as_str() else {
+        return Err(format!("`extends` of {} must be a string", label(index)));
+    };
+    // fork は継承元と同じ basename になりうるので自身は候補から外す。
+    let mut candidates =
+        (0..entries.len()).filter(|&i| i != index && names[i].as_deref() == Some(base));
+    let base_index = match (candidates.next(), candidates.next()) {
+        (Some(i), None) => i,
+        (None, _) => {
+            return Err(format!(
+                "{} extends {base:?}, which is not an entry of this file",
+                label(index)
+            ));
+        }
+        (Some(_), Some(_)) => {
+            return Err(format!(
+                "{} extends {base:?}, which names several entries",
+                label(index)
+            ));
+        }
+    };
+    path.push(index);
+    resolve_extends_of(base_index, entries, names, done, path)?;
+    path.pop();
+    let inherited: Vec<(String, toml::Value)> = entries[base_index]
+        .iter()
+        .filter(|(key, _)| !NOT_INHERITED.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    let entry = &mut entries[index];
+    entry.remove("extends");
+    for (key, value) in inherited {
+        entry.entry(key).or_insert(value);
+    }
+    done[index] = true;
+    Ok(())
+}
+
 impl AddAssign for Config {
     fn add_assign(&mut self, rhs: Self) {
         self.plugins.extend(rhs.plugins);
@@ -47,19 +283,196 @@ impl Sum for Config {
     }
 }
 
+#[serde_as]
 #[derive(Deserialize, Clone)]
 pub struct CacheConfig {
-    #[serde(default, rename = "repo")]
+    /// 取得元。fork を使う場合は `source` と書いてもよい（同じ意味）。
+    /// `branch` を指定すると rev は `refs/heads/<branch>` になる。
+    #[serde(flatten)]
+    #[serde_as(as = "TryFromInto<RepoDeserializer>")]
     pub repo: Option<RepoSource>,
+    /// fork 元のリポジトリ。`rsplug status` が取得元との差（ahead/behind）を表示する。
+    /// 取得・更新には使わない。
+    #[serde(default)]
+    pub upstream: Option<RepoSource>,
     /// pack に `.git` を複製する（git 利用プラグイン用）。`true` だと TarballFetch を無効化し GitFetch に強制する。
     #[serde(default)]
     pub dotgit: bool,
-    #[serde(default)]
+    /// 実行するビルドコマンドの argv。`build`・`shell`・`build.unix`/`build.windows` を
+    /// 現在のプラットフォーム向けに解決したもので、空ならビルドしない。
+    #[serde(flatten)]
+    #[serde_as(as = "TryFromInto<BuildDeserializer>")]
     pub build: Vec<String>,
     #[serde(default)]
     pub lua_build: Option<String>,
     #[serde(default)]
     pub lua_post_update: Option<String>,
+    /// update で snapshot のローカル変更を置き去りにしてよいか。未指定なら `--force` に従う。
+    #[serde(default)]
+    pub allow_dirty: Option<bool>,
+    /// 開発用ローカル checkout（`<dev_path>/<repo 名>`）を symlink で配置する。
+    /// fetch・update・lock の対象外になる。
+    #[serde(default)]
+    pub dev: bool,
+    /// checkout 後に順に適用するパッチファイル（unified diff）。相対パスは設定ファイルの
+    /// ディレクトリ基準（[`Config::resolve_relative_paths`]）。内容は snapshot key に含まれる。
+    #[serde(default)]
+    pub patches: Vec<PathBuf>,
+    /// 1 回の取得（git fetch・tarball の download）を待つ秒数。過ぎたら中断して
+    /// `Error::Timeout` で失敗する。未指定なら待ち続ける。
+    #[serde(default)]
+    #[serde_as(as = "Option<DurationSeconds<u64>>")]
+    pub fetch_timeout: Option<std::time::Duration>,
+    /// source.git から snapshot を checkout するのを待つ秒数。扱いは `fetch_timeout` と同じ。
+    #[serde(default)]
+    #[serde_as(as = "Option<DurationSeconds<u64>>")]
+    pub checkout_timeout: Option<std::time::Duration>,
+}
+
+/// `repo`（`source`）と `branch`。
+#[derive(Deserialize)]
+struct RepoDeserializer {
+    #[serde(default, rename = "repo", alias = "source")]
+    repo: Option<RepoSource>,
+    #[serde(default)]
+    branch: Option<String>,
+}
+
+impl TryFrom<RepoDeserializer> for Option<RepoSource> {
+    type Error = String;
+
+    /// `branch` を ls-remote の候補を絞る `refs/heads/<branch>` に変換する。
+    /// `@rev` と両立しない組み合わせ（別の branch・tag・ワイルドカード）はここで弾く。
+    fn try_from(value: RepoDeserializer) -> Result<Self, Self::Error> {
+        let RepoDeserializer { repo, branch } = value;
+        let Some(branch) = branch else {
+            return Ok(repo);
+        };
+        let Some(repo) = repo else {
+            return Err("`branch` requires `repo`".to_string());
+        };
+        if branch.is_empty() || branch.contains('*') || branch.starts_with("refs/") {
+            return Err(format!(
+                "`branch = \"{branch}\"` must be a plain branch name (no wildcard or `refs/` prefix)"
+            ));
+        }
+        let head = format!("refs/heads/{branch}");
+        match repo.rev() {
+            None => Ok(Some(repo.with_rev(head))),
+            Some(rev) if *rev == *branch || *rev == *head => Ok(Some(repo.with_rev(head))),
+            // commit 固定は branch 上の commit として扱い、そのまま使う。
+            Some(rev) if util::github::is_full_hex_hash(&rev) => Ok(Some(repo)),
+            Some(rev) => Err(format!(
+                "`branch = \"{branch}\"` contradicts the revision `@{rev}`; \
+                 drop one of them or pin a full commit hash"
+            )),
+        }
+    }
+}
+
+/// 文字列の `build` を実行するシェル。
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum BuildShell {
+    Sh,
+    Bash,
+    Pwsh,
+    Cmd,
+}
+
+impl BuildShell {
+    /// `shell` 未指定時のシェル。Windows は `cmd`、それ以外は `sh`。
+    fn platform_default() -> Self {
+        if cfg!(windows) {
+            BuildShell::Cmd
+        } else {
+            BuildShell::Sh
+        }
+    }
+
+    /// `script` をこのシェルで実行する argv。
+    fn argv(self, script: String) -> Vec<String> {
+        let prefix: &[&str] = match self {
+            BuildShell::Sh => &["sh", "-c"],
+            BuildShell::Bash => &["bash", "-c"],
+            BuildShell::Pwsh => &["pwsh", "-NoProfile", "-NonInteractive", "-Command"],
+            BuildShell::Cmd => &["cmd", "/C"],
+        };
+        prefix
+            .iter()
+            .map(|arg| arg.to_string())
+            .chain(once(script))
+            .collect()
+    }
+}
+
+/// ビルドコマンド 1 つ。argv 配列はそのまま、文字列は `shell` で実行する。
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BuildCommand {
+    Argv(Vec<String>),
+    Script(String),
+}
+
+/// `build.unix`・`build.windows` によるプラットフォーム別のビルドコマンド。
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlatformBuild {
+    #[serde(default)]
+    unix: Option<BuildCommand>,
+    #[serde(default)]
+    windows: Option<BuildCommand>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BuildSpec {
+    Command(BuildCommand),
+    Platform(PlatformBuild),
+}
+
+#[derive(Deserialize)]
+struct BuildDeserializer {
+    #[serde(default)]
+    build: Option<BuildSpec>,
+    #[serde(default)]
+    shell: Option<BuildShell>,
+}
+
+impl TryFrom<BuildDeserializer> for Vec<String> {
+    type Error = String;
+
+    fn try_from(value: BuildDeserializer) -> Result<Self, Self::Error> {
+        let BuildDeserializer { build, shell } = value;
+        let (current, other) = match build {
+            None => (None, None),
+            Some(BuildSpec::Command(command)) => (Some(command), None),
+            Some(BuildSpec::Platform(PlatformBuild { unix, windows })) => {
+                if cfg!(windows) {
+                    (windows, unix)
+                } else {
+                    (unix, windows)
+                }
+            }
+        };
+        // `shell` の誤用はどのプラットフォームでも同じように弾く。
+        let has_script = [&current, &other]
+            .into_iter()
+            .any(|command| matches!(command, Some(BuildCommand::Script(_))));
+        if shell.is_some() && !has_script {
+            return Err(
+                "`shell` only applies to a `build` string; an argument array runs without a shell"
+                    .to_string(),
+            );
+        }
+        Ok(match current {
+            None => Vec::new(),
+            Some(BuildCommand::Argv(argv)) => argv,
+            Some(BuildCommand::Script(script)) => shell
+                .unwrap_or_else(BuildShell::platform_default)
+                .argv(script),
+        })
+    }
 }
 
 #[serde_as]
@@ -190,6 +603,12 @@ pub(crate) struct PluginConfig {
     #[serde(flatten)]
     #[serde(default)]
     pub merge: MergeConfig,
+    /// 対応する Neovim のバージョン（`[plugins.compat]`）。
+    #[serde(default)]
+    pub compat: CompatConfig,
+    /// このエントリを使う条件（`[plugins.when]`）。
+    #[serde(default)]
+    pub when: Condition,
 }
 
 impl PluginConfig {
@@ -199,11 +618,7 @@ impl PluginConfig {
         if let Some(name) = &self.custom_name {
             return Some(name.as_str());
         }
-        match &self.cache.repo {
-            Some(RepoSource::GitHub { repo, .. }) => Some(repo.as_ref()),
-            Some(RepoSource::Git { url, .. }) => Some(repo_basename(url.as_ref())),
-            None => None,
-        }
+        self.cache.repo.as_ref().map(RepoSource::basename)
     }
 
     /// 内部的同一性 id（Phase 3A）。`dep_name()` ?? script 内容ハッシュ。
@@ -221,7 +636,7 @@ impl PluginConfig {
 /// Git URL のクローン時に作成されるディレクトリ名（最後のパスセグメント）を返す。
 /// `git clone` と同じく末尾の `.git` と末尾スラッシュを取り除いてから最後の `/` 以降を取る。
 /// 例: `https://gitlab.com/foo/bar.nvim.git` → `bar.nvim`
-fn repo_basename(url: &str) -> &str {
+pub(super) fn repo_basename(url: &str) -> &str {
     // 末尾の `.git` と `/` を（どちらの順序で現れても）取り除く。
     let mut s = url;
     while let Some(stripped) = s.strip_suffix(".git").or_else(|| s.strip_suffix('/')) {
@@ -249,10 +664,44 @@ struct SetupScriptOne {
     lua_after: Option<String>,
     /// プラグイン読み込み直前に実行される Lua スクリプト
     lua_before: Option<String>,
+    /// 各スクリプトから `opts` として参照できるテーブル
+    opts: Option<toml::Table>,
+    /// 読み込み直後に `require(<module>).setup(opts)` を呼ぶか。文字列なら module 名。
+    #[serde(default, deserialize_with = "deserialize_auto_setup")]
+    auto_setup: Option<AutoSetup>,
+}
+
+/// `auto_setup` の指定。
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize)]
+pub enum AutoSetup {
+    /// `auto_setup = false`。ファイル全体の既定値を打ち消す。
+    Off,
+    /// `auto_setup = true`。repo の `lua/` から主な module を選ぶ。
+    Detect,
+    /// `auto_setup = "<module>"`。
+    Module(String),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AutoSetupSetting {
+    Enabled(bool),
+    Module(String),
+}
+
+fn deserialize_auto_setup<'de, D>(deserializer: D) -> Result<Option<AutoSetup>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Some(match AutoSetupSetting::deserialize(deserializer)? {
+        AutoSetupSetting::Enabled(true) => AutoSetup::Detect,
+        AutoSetupSetting::Enabled(false) => AutoSetup::Off,
+        AutoSetupSetting::Module(module) => AutoSetup::Module(module),
+    }))
 }
 
 /// プラグインのセットアップに用いるスクリプト群
-#[derive(Clone, Default, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Default, Debug, Hash, PartialEq, Eq, Serialize)]
 pub struct SetupScript {
     /// Neovim 起動時に実行される Lua スクリプト
     pub lua_start: BTreeSet<String>,
@@ -260,6 +709,11 @@ pub struct SetupScript {
     pub lua_after: BTreeSet<String>,
     /// プラグイン読み込み直前に実行される Lua スクリプト
     pub lua_before: BTreeSet<String>,
+    /// `opts` の Lua テーブル構築子。`auto_setup` の引数になる。
+    pub opts: Option<String>,
+    /// 未解決の `auto_setup`。読み込み時に [`SetupScript::resolve_auto_setup`] が
+    /// `opts` とともに `lua_after` へ変えるので、併合（`+=`）の時点では常に `None`。
+    pub auto_setup: Option<AutoSetup>,
 }
 
 impl From<SetupScriptOne> for SetupScript {
@@ -268,20 +722,130 @@ impl From<SetupScriptOne> for SetupScript {
             lua_start,
             lua_after,
             lua_before,
+            opts,
+            auto_setup,
         } = value;
+        let opts = opts.map(|opts| lua_table(&opts));
+        // 各スクリプトはそれぞれ関数に包まれて実行されるので、`opts` はその先頭に
+        // ローカル変数として置く。併合されたパッケージでもエントリごとの値が保たれる。
+        let with_opts = |script: String| match &opts {
+            Some(opts) => format!("local opts = {opts}\n{script}"),
+            None => script,
+        };
         SetupScript {
-            lua_start: lua_start.into_iter().collect(),
-            lua_after: lua_after.into_iter().collect(),
-            lua_before: lua_before.into_iter().collect(),
+            lua_start: lua_start.into_iter().map(with_opts).collect(),
+            lua_after: lua_after.into_iter().map(with_opts).collect(),
+            lua_before: lua_before.into_iter().map(with_opts).collect(),
+            opts,
+            auto_setup,
+        }
+    }
+}
+
+impl SetupScript {
+    /// `auto_setup` を `require(<module>).setup(opts)` の `lua_after` に変える。
+    /// `lua_modules` は repo の `lua/` 直下の module、`name` は設定上の名前（`name` ?? repo 名）。
+    /// module を 1 つに決められなければ候補を返す。
+    pub(crate) fn resolve_auto_setup(
+        &mut self,
+        lua_modules: &[String],
+        name: Option<&str>,
+    ) -> Result<(), Vec<String>> {
+        let opts = self.opts.take();
+        let module = match self.auto_setup.take() {
+            None | Some(AutoSetup::Off) => return Ok(()),
+            Some(AutoSetup::Module(module)) => module,
+            Some(AutoSetup::Detect) => match main_lua_module(lua_modules, name) {
+                Some(module) => module.to_string(),
+                None => return Err(lua_modules.to_vec()),
+            },
+        };
+        let opts = opts.as_deref().unwrap_or("{}");
+        self.lua_after
+            .insert(format!("require({}).setup({opts})", lua_string(module)));
+        Ok(())
+    }
+}
+
+/// 主な Lua module。1 つしか無ければそれ、複数なら名前から `.nvim` などの飾りを
+/// 除いたもの（`telescope.nvim` → `telescope`、`nvim-cmp` → `cmp`）。
+fn main_lua_module<'a>(lua_modules: &'a [String], name: Option<&str>) -> Option<&'a str> {
+    if let [module] = lua_modules {
+        return Some(module);
+    }
+    let name = name?;
+    let stem = [".nvim", ".lua", ".vim", "-nvim", "-lua", "_nvim"]
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix))
+        .unwrap_or(name);
+    let candidates = [stem, stem.strip_prefix("nvim-").unwrap_or(stem)];
+    candidates.iter().find_map(|candidate| {
+        let candidate = candidate.to_lowercase();
+        lua_modules.iter().map(String::as_str).find(|module| {
+            let module = module.to_lowercase();
+            module == candidate || module == candidate.replace('-', "_")
+        })
+    })
+}
+
+/// TOML のテーブルを Lua のテーブル構築子として書き出す。日時は文字列にする。
+fn lua_table(table: &toml::Table) -> String {
+    let mut out = String::from("{");
+    for (i, (key, value)) in table.iter().enumerate() {
+        out.push_str(if i == 0 { " " } else { ", " });
+        if is_lua_name(key) {
+            out.push_str(key);
+        } else {
+            out.push_str(&format!("[{}]", lua_string(key)));
+        }
+        out.push_str(" = ");
+        out.push_str(&lua_value(value));
+    }
+    out.push_str(if table.is_empty() { "}" } else { " }" });
+    out
+}
+
+fn lua_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => lua_string(s).to_string(),
+        toml::Value::Integer(i) => i.to_string(),
+        toml::Value::Float(f) if f.is_nan() => "0/0".to_string(),
+        toml::Value::Float(f) if f.is_infinite() => {
+            if *f > 0.0 { "math.huge" } else { "-math.huge" }.to_string()
+        }
+        // `{:?}` は整数値でも `1.0` のように小数点を残す。
+        toml::Value::Float(f) => format!("{f:?}"),
+        toml::Value::Boolean(b) => b.to_string(),
+        toml::Value::Datetime(d) => lua_string(d).to_string(),
+        toml::Value::Array(items) if items.is_empty() => "{}".to_string(),
+        toml::Value::Array(items) => {
+            let items: Vec<_> = items.iter().map(lua_value).collect();
+            format!("{{ {} }}", items.join(", "))
         }
+        toml::Value::Table(table) => lua_table(table),
     }
 }
 
+/// `key = value` の形で書ける Lua の名前か（予約語は除く）。
+fn is_lua_name(key: &str) -> bool {
+    const KEYWORDS: &[&str] = &[
+        "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if",
+        "in", "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+    ];
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS.contains(&key)
+}
+
 impl AddAssign for SetupScript {
     fn add_assign(&mut self, rhs: Self) {
         self.lua_start.extend(rhs.lua_start);
         self.lua_after.extend(rhs.lua_after);
         self.lua_before.extend(rhs.lua_before);
+        // `opts` と `auto_setup` は読み込み時に解決して空になっているので併合しない。
     }
 }
 
@@ -291,24 +855,104 @@ pub struct MergeConfig {
     #[serde(deserialize_with = "deserialize_file_specifier")]
     #[serde(default = "default_ignore")]
     pub ignore: FileSpecifier,
-    #[serde(default = "default_merge_true")]
-    pub merge: bool,
+    /// 併合ポリシー。`None` は未指定（`merge = true` を含む）で、CLI の `--merge` に従う。
+    #[serde(default, deserialize_with = "deserialize_merge_policy")]
+    pub merge: Option<MergePolicy>,
+    /// 配置するファイル数の上限。超える checkout は install を中止する。
+    #[serde(default = "default_max_files")]
+    pub max_files: u64,
+    /// 配置するファイルの合計バイト数の上限（整数または `"512MiB"` 形式）。
+    #[serde(
+        default = "default_max_total_size",
+        deserialize_with = "deserialize_byte_size"
+    )]
+    pub max_total_size: u64,
+    /// 置き場所の packpath（`target = "system"`）。既定はユーザの packpath。
+    #[serde(default)]
+    pub target: PackTarget,
 }
 
 impl Default for MergeConfig {
-    /// `merge` を設定ファイルで言及しない場合のデフォルト。**merge = true**（未指定なら
-    /// マージする）。`#[serde(flatten)]` で MergeConfig 全体が未指定のときもこの Default が
-    /// 使われるよう、PluginConfig 側に `#[serde(default)]` を併用する。
+    /// `merge` を設定ファイルで言及しない場合のデフォルト。**ポリシー未指定**（グローバル
+    /// 設定に従い、既定ではマージする）。`#[serde(flatten)]` で MergeConfig 全体が未指定の
+    /// ときもこの Default が使われるよう、PluginConfig 側に `#[serde(default)]` を併用する。
     fn default() -> Self {
         Self {
             ignore: default_ignore(),
-            merge: true,
+            merge: None,
+            max_files: default_max_files(),
+            max_total_size: default_max_total_size(),
+            target: PackTarget::default(),
         }
     }
 }
 
-fn default_merge_true() -> bool {
-    true
+/// `merge` は真偽値（後方互換）とポリシー名の両方を受け付ける。
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MergeSetting {
+    Enabled(bool),
+    Policy(MergePolicy),
+}
+
+/// `merge = true` はグローバル設定に委ね、`merge = false` は `never` と同じ。
+fn deserialize_merge_policy<'de, D>(deserializer: D) -> Result<Option<MergePolicy>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match MergeSetting::deserialize(deserializer)? {
+        MergeSetting::Enabled(true) => None,
+        MergeSetting::Enabled(false) => Some(MergePolicy::Never),
+        MergeSetting::Policy(policy) => Some(policy),
+    })
+}
+
+/// `node_modules` 等の誤コミットを捕まえつつ、通常のプラグインや build 成果物は通す値。
+fn default_max_files() -> u64 {
+    20_000
+}
+
+fn default_max_total_size() -> u64 {
+    1 << 30
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ByteSize {
+    Bytes(u64),
+    Text(String),
+}
+
+/// `max_total_size` はバイト数、または `K`/`M`/`G`（`KiB`・`KB` 等も可、いずれも 1024 倍）付きの文字列。
+fn deserialize_byte_size<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match ByteSize::deserialize(deserializer)? {
+        ByteSize::Bytes(bytes) => Ok(bytes),
+        ByteSize::Text(text) => parse_byte_size(&text).ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "invalid size {text:?} (expected bytes or e.g. \"512MiB\", \"2G\")"
+            ))
+        }),
+    }
+}
+
+fn parse_byte_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    let shift = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 0,
+        "k" | "kb" | "kib" => 10,
+        "m" | "mb" | "mib" => 20,
+        "g" | "gb" | "gib" => 30,
+        _ => return None,
+    };
+    number.checked_mul(1 << shift)
 }
 
 fn default_ignore() -> FileSpecifier {
@@ -340,9 +984,9 @@ mod tests {
             "#,
         )
         .unwrap();
-        assert!(
-            config.plugins[0].merge.merge,
-            "merge must default to true when absent"
+        assert_eq!(
+            config.plugins[0].merge.merge, None,
+            "merge must default to the global policy when absent"
         );
     }
 
@@ -358,9 +1002,100 @@ mod tests {
             "#,
         )
         .unwrap();
+        assert_eq!(
+            config.plugins[0].merge.merge, None,
+            "merge must default to the global policy even when only ignore is set"
+        );
+    }
+
+    #[test]
+    fn merge_accepts_booleans_and_policy_names() {
+        let config: Config = toml::from_str(
+            r#"
+            [[plugins]]
+            repo = "owner/a"
+            merge = true
+
+            [[plugins]]
+            repo = "owner/b"
+            merge = false
+
+            [[plugins]]
+            repo = "owner/c"
+            merge = "aggressive"
+
+            [[plugins]]
+            repo = "owner/d"
+            merge = "same-lazy-type"
+            "#,
+        )
+        .unwrap();
+        let policies: Vec<_> = config.plugins.iter().map(|p| p.merge.merge).collect();
+        assert_eq!(
+            policies,
+            vec![
+                None,
+                Some(MergePolicy::Never),
+                Some(MergePolicy::Aggressive),
+                Some(MergePolicy::SameLazyType),
+            ]
+        );
         assert!(
-            config.plugins[0].merge.merge,
-            "merge must default to true even when only ignore is set"
+            toml::from_str::<Config>("[[plugins]]\nrepo = \"owner/e\"\nmerge = \"always\"")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn placement_limits_default_and_accept_size_suffixes() {
+        let config: Config = toml::from_str(
+            r#"
+            [[plugins]]
+            repo = "owner/default"
+
+            [[plugins]]
+            repo = "owner/bytes"
+            max_files = 100
+            max_total_size = 4096
+
+            [[plugins]]
+            repo = "owner/suffix"
+            max_total_size = "512MiB"
+            "#,
+        )
+        .unwrap();
+        let limits: Vec<_> = config
+            .plugins
+            .iter()
+            .map(|p| (p.merge.max_files, p.merge.max_total_size))
+            .collect();
+        assert_eq!(
+            limits,
+            vec![(20_000, 1 << 30), (100, 4096), (20_000, 512 << 20)]
+        );
+        assert_eq!(parse_byte_size("2 G"), Some(2 << 30));
+        assert_eq!(parse_byte_size("10kb"), Some(10 << 10));
+        assert_eq!(parse_byte_size("1.5G"), None);
+        assert_eq!(parse_byte_size("12 parsecs"), None);
+    }
+
+    #[test]
+    fn target_defaults_to_the_user_packpath() {
+        let config: Config = toml::from_str(
+            r#"
+            [[plugins]]
+            repo = "owner/user"
+
+            [[plugins]]
+            repo = "owner/shared"
+            target = "system"
+            "#,
+        )
+        .unwrap();
+        let targets: Vec<_> = config.plugins.iter().map(|p| p.merge.target).collect();
+        assert_eq!(targets, vec![PackTarget::User, PackTarget::System]);
+        assert!(
+            toml::from_str::<Config>("[[plugins]]\nrepo = \"owner/x\"\ntarget = \"site\"").is_err()
         );
     }
 
@@ -383,6 +1118,87 @@ mod tests {
         assert!(script.lua_after.contains("vim.g.rsplug_after = true"));
     }
 
+    #[test]
+    fn opts_table_is_a_local_of_every_script() {
+        let config: Config = toml::from_str(
+            r#"
+            [[plugins]]
+            repo = "owner/plugin"
+            lua_after = "require 'plugin'.setup(opts)"
+
+            [plugins.opts]
+            width = 0.5
+            ignore = ["*.lock", 'say "hi"']
+            "end" = true
+            nested = { depth = 2, empty = {} }
+            "#,
+        )
+        .unwrap();
+
+        let script = &config.plugins[0].script;
+        assert!(script.lua_before.is_empty());
+        assert_eq!(
+            script.lua_after.iter().collect::<Vec<_>>(),
+            [concat!(
+                "local opts = { [\"end\"] = true, ignore = { \"*.lock\", \"say \\\"hi\\\"\" }, ",
+                "nested = { depth = 2, empty = {} }, width = 0.5 }\n",
+                "require 'plugin'.setup(opts)"
+            )]
+        );
+        assert!(toml::from_str::<Config>("[[plugins]]\nrepo = \"owner/x\"\nopts = 1").is_err());
+    }
+
+    #[test]
+    fn auto_setup_calls_setup_of_the_main_module() {
+        let config: Config = toml::from_str(
+            r#"
+            auto_setup = true
+
+            [[plugins]]
+            repo = "nvim-telescope/telescope.nvim"
+            opts = { defaults = { layout_strategy = "vertical" } }
+
+            [[plugins]]
+            repo = "hrsh7th/nvim-cmp"
+
+            [[plugins]]
+            repo = "owner/plenary.nvim"
+            auto_setup = false
+
+            [[plugins]]
+            repo = "owner/custom"
+            auto_setup = "custom.core"
+            "#,
+        )
+        .unwrap();
+        let modules = |names: &[&str]| names.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let resolve = |index: usize, lua_modules: &[String]| {
+            let plugin = &config.plugins[index];
+            let mut script = plugin.script.clone();
+            script
+                .resolve_auto_setup(lua_modules, plugin.dep_name())
+                .map(|()| {
+                    assert_eq!((&script.opts, &script.auto_setup), (&None, &None));
+                    script.lua_after.into_iter().collect::<Vec<_>>()
+                })
+        };
+
+        assert_eq!(
+            resolve(0, &modules(&["telescope", "telescope._extensions"])).unwrap(),
+            [r#"require("telescope").setup({ defaults = { layout_strategy = "vertical" } })"#]
+        );
+        assert_eq!(
+            resolve(1, &modules(&["cmp", "cmp_extra"])).unwrap(),
+            [r#"require("cmp").setup({})"#]
+        );
+        assert_eq!(resolve(1, &modules(&["a", "b"])).unwrap_err(), ["a", "b"]);
+        assert!(resolve(2, &modules(&["plenary"])).unwrap().is_empty());
+        assert_eq!(
+            resolve(3, &[]).unwrap(),
+            [r#"require("custom.core").setup({})"#]
+        );
+    }
+
     #[test]
     fn plugin_config_deserializes_on_func() {
         let config: Config = toml::from_str(
@@ -440,6 +1256,259 @@ mod tests {
             Some("vim.g.updated = true")
         );
     }
+
+    #[test]
+    fn plugin_config_deserializes_allow_dirty() {
+        let config: Config = toml::from_str(
+            r#"
+            [[plugins]]
+            repo = "owner/plugin"
+            allow_dirty = true
+
+            [[plugins]]
+            repo = "owner/other"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.plugins[0].cache.allow_dirty, Some(true));
+        assert_eq!(config.plugins[1].cache.allow_dirty, None);
+    }
+
+    #[test]
+    fn plugin_config_deserializes_timeouts() {
+        let config: Config = toml::from_str(
+            r#"
+            [[plugins]]
+            repo = "owner/plugin"
+            fetch_timeout = 120
+            checkout_timeout = 30
+
+            [[plugins]]
+            repo = "owner/other"
+            "#,
+        )
+        .unwrap();
+
+        let cache = &config.plugins[0].cache;
+        assert_eq!(
+            cache.fetch_timeout,
+            Some(std::time::Duration::from_secs(120))
+        );
+        assert_eq!(
+            cache.checkout_timeout,
+            Some(std::time::Duration::from_secs(30))
+        );
+        assert_eq!(config.plugins[1].cache.fetch_timeout, None);
+    }
+
+    #[test]
+    fn plugin_config_deserializes_dev() {
+        let config: Config = toml::from_str(
+            r#"
+            [[plugins]]
+            repo = "owner/plugin.nvim"
+            dev = true
+
+            [[plugins]]
+            repo = "owner/other"
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.plugins[0].cache.dev);
+        assert!(!config.plugins[1].cache.dev);
+        assert_eq!(
+            config.plugins[0]
+                .cache
+                .repo
+                .as_ref()
+                .map(RepoSource::basename),
+            Some("plugin.nvim")
+        );
+    }
+    #[test]
+    fn source_is_an_alias_of_repo_and_upstream_is_separate() {
+        let config: Config = toml::from_str(
+            r#"
+            [[plugins]]
+            source = "me/plugin.nvim"
+            upstream = "orig/plugin.nvim@main"
+            "#,
+        )
+        .unwrap();
+
+        let cache = &config.plugins[0].cache;
+        assert_eq!(
+            cache.repo.as_ref().map(RepoSource::canonical).as_deref(),
+            Some("github.com/me/plugin.nvim")
+        );
+        let upstream = cache.upstream.as_ref().unwrap();
+        assert_eq!(upstream.canonical(), "github.com/orig/plugin.nvim");
+        assert_eq!(upstream.rev().as_deref(), Some("main"));
+        assert_eq!(config.plugins[0].dep_name(), Some("plugin.nvim"));
+    }
+
+    #[test]
+    fn branch_restricts_the_rev_to_that_head() {
+        let parse = |body: &str| {
+            toml::from_str::<Config>(&format!("[[plugins]]\n{body}"))
+                .map(|config| config.plugins[0].cache.repo.as_ref().unwrap().rev())
+        };
+        let heads = Some(Arc::from("refs/heads/develop"));
+        assert_eq!(
+            parse("repo = \"o/r\"\nbranch = \"develop\"").unwrap(),
+            heads
+        );
+        assert_eq!(
+            parse("source = \"o/r@develop\"\nbranch = \"develop\"").unwrap(),
+            heads
+        );
+        let hash = "0123456789abcdef0123456789abcdef01234567";
+        assert_eq!(
+            parse(&format!("repo = \"o/r@{hash}\"\nbranch = \"develop\"")).unwrap(),
+            Some(Arc::from(hash))
+        );
+        for contradictory in [
+            "repo = \"o/r@v1.0.0\"\nbranch = \"develop\"",
+            "repo = \"o/r@v1.*\"\nbranch = \"develop\"",
+            "repo = \"o/r\"\nbranch = \"dev*\"",
+            "branch = \"develop\"",
+        ] {
+            assert!(parse(contradictory).is_err(), "{contradictory}");
+        }
+    }
+
+    #[test]
+    fn incompatible_plugins_and_their_dependents_are_skipped() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [[plugins]]
+            repo = "owner/new.nvim"
+            compat = { nvim = ">=0.11" }
+
+            [[plugins]]
+            repo = "owner/ext.nvim"
+            depends = "new.nvim"
+
+            [[plugins]]
+            repo = "owner/old.nvim"
+            compat = { nvim = ">=0.9, <0.12" }
+            "#,
+        )
+        .unwrap();
+
+        let mut same = config.clone();
+        assert!(
+            same.retain_for_host(Some("0.11.2".parse().unwrap()))
+                .is_empty()
+        );
+        assert_eq!(same.plugins.len(), 3);
+        // バージョン不明なら `compat` は検査しない。
+        let mut unknown = config.clone();
+        assert!(unknown.retain_for_host(None).is_empty());
+        assert_eq!(unknown.plugins.len(), 3);
+
+        let skipped = config.retain_for_host(Some("0.10.4".parse().unwrap()));
+        assert_eq!(
+            skipped,
+            [
+                (
+                    "new.nvim".to_string(),
+                    "requires Neovim >=0.11.0, found 0.10.4".to_string()
+                ),
+                (
+                    "ext.nvim".to_string(),
+                    "depends on skipped new.nvim".to_string()
+                ),
+            ]
+        );
+        assert_eq!(config.plugins.len(), 1);
+        assert_eq!(config.plugins[0].dep_name(), Some("old.nvim"));
+
+        assert!(
+            toml::from_str::<Config>("[[plugins]]\nrepo = 'o/r'\ncompat = { nvim = '>=x' }")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn when_selects_between_alternative_entries() {
+        let config: Config = toml::from_str(
+            r#"
+            [[plugins]]
+            repo = "owner/ts.nvim@main"
+            when.nvim = ">=0.11"
+
+            [[plugins]]
+            repo = "owner/ts.nvim@master"
+            when.nvim = "<0.11"
+
+            [[plugins]]
+            repo = "owner/ext.nvim"
+            depends = "ts.nvim"
+            "#,
+        )
+        .unwrap();
+        let select = |nvim: Option<&str>| {
+            let mut config = config.clone();
+            let skipped = config.retain_for_host(nvim.map(|v| v.parse().unwrap()));
+            let revs = config
+                .plugins
+                .iter()
+                .map(|plugin| {
+                    let repo = plugin.cache.repo.as_ref().unwrap();
+                    format!(
+                        "{}@{}",
+                        repo.basename(),
+                        repo.rev().as_deref().unwrap_or("")
+                    )
+                })
+                .collect::<Vec<_>>();
+            (revs, skipped)
+        };
+        assert_eq!(
+            select(Some("0.11.0")),
+            (vec!["ts.nvim@main".into(), "ext.nvim@".into()], vec![])
+        );
+        assert_eq!(
+            select(Some("0.10.4")),
+            (vec!["ts.nvim@master".into(), "ext.nvim@".into()], vec![])
+        );
+        // どちらの条件も判定できなければ両方とも黙って外れ、依存元は理由付きで外れる。
+        assert_eq!(
+            select(None),
+            (
+                vec![],
+                vec![(
+                    "ext.nvim".to_string(),
+                    "depends on skipped ts.nvim".to_string()
+                )]
+            )
+        );
+    }
+
+    #[test]
+    fn patches_resolve_against_the_config_directory() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [[plugins]]
+            repo = "owner/plugin"
+            patches = ["./patches/fix.diff", "/abs/other.diff"]
+            "#,
+        )
+        .unwrap()
+        .resolve_relative_paths(Path::new("/conf"));
+
+        assert_eq!(
+            config.plugins[0].cache.patches,
+            [
+                PathBuf::from("/conf/./patches/fix.diff"),
+                PathBuf::from("/abs/other.diff"),
+            ]
+        );
+    }
+
     #[test]
     fn plugin_config_deserializes_on_source() {
         let config: Config = toml::from_str(
@@ -554,6 +1623,210 @@ mod tests {
             "start=true must win and ignore lazy triggers"
         );
     }
+
+    #[test]
+    fn on_event_rejects_misspelled_events_with_a_suggestion() {
+        let parse = |event: &str| {
+            toml::from_str::<Config>(&format!(
+                "[[plugins]]\nrepo = 'o/p'\non_event = '{event}'\n"
+            ))
+            .map(|mut config| config.plugins.remove(0).lazy_type.describe())
+        };
+        assert_eq!(parse("BufReadPre").unwrap(), ["on_event:BufReadPre"]);
+        // Neovim に無い名前は User イベントとして受け付ける。
+        assert_eq!(parse("VeryLazy").unwrap(), ["on_event:VeryLazy"]);
+
+        let err = parse("BufReadpre").unwrap_err().to_string();
+        assert!(err.contains("did you mean `BufReadPre`?"), "{err}");
+        let err = parse("InsertEntr").unwrap_err().to_string();
+        assert!(err.contains("did you mean `InsertEnter`?"), "{err}");
+
+        // `User:` で似た名前の独自イベントを明示できる。
+        assert_eq!(parse("User:LspAttached").unwrap(), ["on_event:LspAttached"]);
+        assert!(parse("LspAttached").is_err());
+        assert!(parse("User:VimEnter").is_err());
+        assert!(parse("User:").is_err());
+    }
+
+    #[test]
+    fn on_cmd_table_form_validates_completion() {
+        let parse = |on_cmd: &str| {
+            toml::from_str::<Config>(&format!("[[plugins]]\nrepo = 'o/p'\non_cmd = {on_cmd}\n"))
+                .map(|mut config| config.plugins.remove(0).lazy_type.describe())
+        };
+        assert_eq!(
+            parse("[{ name = 'Foo', complete = 'file' }, 'Bar']").unwrap(),
+            ["on_cmd:Bar", "on_cmd:Foo"]
+        );
+        assert!(parse("{ name = 'Foo', complete = 'customlist,v:lua.foo' }").is_ok());
+        assert!(parse("{ name = 'Foo', complete = 'custom,' }").is_err());
+        assert!(parse("{ name = 'Foo', complete = 'file,x' }").is_err());
+        assert!(parse("{ name = 'Foo', complete = '' }").is_err());
+        assert!(parse("{ name = 'foo' }").is_err());
+        assert!(parse("{ name = 'Foo', nargs = 1 }").is_err());
+    }
+
+    fn build_of(toml: &str) -> Result<Vec<String>, toml::de::Error> {
+        toml::from_str::<Config>(toml).map(|mut config| config.plugins.remove(0).cache.build)
+    }
+
+    #[test]
+    fn build_accepts_argv_script_and_platform_blocks() {
+        // argv 配列はシェルを通さずそのまま（既存設定の snapshot キーを変えない）。
+        assert_eq!(
+            build_of("[[plugins]]\nrepo = 'o/p'\nbuild = ['make', 'all']\n").unwrap(),
+            ["make", "all"]
+        );
+        assert!(build_of("[[plugins]]\nrepo = 'o/p'\n").unwrap().is_empty());
+
+        let script = build_of("[[plugins]]\nrepo = 'o/p'\nbuild = 'make && make install'\n");
+        let expected: &[&str] = if cfg!(windows) {
+            &["cmd", "/C", "make && make install"]
+        } else {
+            &["sh", "-c", "make && make install"]
+        };
+        assert_eq!(script.unwrap(), expected);
+
+        let pwsh = build_of("[[plugins]]\nrepo = 'o/p'\nbuild = './build.ps1'\nshell = 'pwsh'\n");
+        assert_eq!(
+            pwsh.unwrap(),
+            [
+                "pwsh",
+                "-NoProfile",
+                "-NonInteractive",
+                "-Command",
+                "./build.ps1"
+            ]
+        );
+
+        let platform = build_of(
+            "[[plugins]]\nrepo = 'o/p'\nbuild.unix = ['make']\nbuild.windows = 'build.cmd'\n",
+        )
+        .unwrap();
+        if cfg!(windows) {
+            assert_eq!(platform, ["cmd", "/C", "build.cmd"]);
+        } else {
+            assert_eq!(platform, ["make"]);
+        }
+        let windows_only =
+            build_of("[[plugins]]\nrepo = 'o/p'\nbuild.windows = ['build.cmd']\n").unwrap();
+        assert_eq!(windows_only.is_empty(), !cfg!(windows));
+    }
+
+    #[test]
+    fn build_rejects_shell_without_script() {
+        let err =
+            build_of("[[plugins]]\nrepo = 'o/p'\nbuild = ['make']\nshell = 'bash'\n").unwrap_err();
+        assert!(err.to_string().contains("`shell` only applies"), "{err}");
+        assert!(build_of("[[plugins]]\nrepo = 'o/p'\nshell = 'bash'\n").is_err());
+        assert!(build_of("[[plugins]]\nrepo = 'o/p'\nbuild = 'make'\nshell = 'zsh'\n").is_err());
+        assert!(build_of("[[plugins]]\nrepo = 'o/p'\nbuild.linux = ['make']\n").is_err());
+    }
+
+    #[test]
+    fn extends_inherits_unset_keys_and_drops_templates() {
+        let config = Config::from_toml(
+            r#"
+            [[plugins]]
+            name = "lsp-template"
+            template = true
+            on_ft = ["python"]
+            build = ["make"]
+            lua_after = "vim.g.lsp = true"
+
+            [[plugins]]
+            repo = "owner/python.nvim"
+            extends = "lsp-template"
+
+            [[plugins]]
+            repo = "owner/rust.nvim"
+            extends = "python.nvim"
+            on_ft = ["rust"]
+            "#,
+        )
+        .unwrap();
+        let ids: Vec<String> = config
+            .plugins
+            .iter()
+            .map(PluginConfig::compute_internal_id)
+            .collect();
+        assert_eq!(ids, ["python.nvim", "rust.nvim"]);
+        let [python, rust] = &config.plugins[..] else {
+            unreachable!()
+        };
+        assert_eq!(python.lazy_type.describe(), ["on_ft:python"]);
+        assert_eq!(rust.lazy_type.describe(), ["on_ft:rust"]);
+        for plugin in [python, rust] {
+            assert_eq!(plugin.cache.build, ["make"]);
+            assert!(plugin.script.lua_after.contains("vim.g.lsp = true"));
+        }
+    }
+
+    #[test]
+    fn extends_keeps_the_source_of_a_fork() {
+        let config = Config::from_toml(
+            r#"
+            [[plugins]]
+            repo = "upstream/plugin.nvim@v1"
+            dev = true
+            on_cmd = "Plugin"
+
+            [[plugins]]
+            repo = "me/plugin.nvim"
+            name = "plugin-fork"
+            extends = "plugin.nvim"
+            "#,
+        )
+        .unwrap();
+        let fork = &config.plugins[1];
+        assert_eq!(
+            fork.cache.repo.as_ref().unwrap().canonical(),
+            "github.com/me/plugin.nvim"
+        );
+        assert!(!fork.cache.dev);
+        assert_eq!(fork.lazy_type.describe(), ["on_cmd:Plugin"]);
+    }
+
+    #[test]
+    fn extends_reports_cycles_and_unknown_bases() {
+        let err = Config::from_toml(
+            "[[plugins]]\nrepo = 'o/a'\nextends = 'b'\n\
+             [[plugins]]\nrepo = 'o/b'\nextends = 'c'\n\
+             [[plugins]]\nrepo = 'o/c'\nextends = 'b'\n",
+        )
+        .err()
+        .unwrap()
+        .to_string();
+        assert!(err.contains("`extends` cycle: b -> c -> b"), "{err}");
+
+        let err = Config::from_toml("[[plugins]]\nrepo = 'o/a'\nextends = 'missing'\n")
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("a extends \"missing\""), "{err}");
+    }
+
+    #[test]
+    fn plugin_tables_resolve_extends_and_drop_templates() {
+        let tables = Config::plugin_tables(
+            r#"
+            [[plugins]]
+            name = "base"
+            template = true
+            on_ft = ["python"]
+
+            [[plugins]]
+            repo = "owner/python.nvim"
+            extends = "base"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0]["repo"].as_str(), Some("owner/python.nvim"));
+        assert_eq!(tables[0]["on_ft"].as_array().unwrap().len(), 1);
+        assert!(!tables[0].contains_key("extends"));
+        assert!(Config::plugin_tables("[[plugins]]\nrepo = 1\n").is_err());
+    }
 }
 
 /// キーパターン
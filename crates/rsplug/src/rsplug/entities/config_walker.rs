@@ -3,7 +3,7 @@ use tokio::io::AsyncReadExt;
 use tokio::{sync::mpsc, task::JoinHandle};
 use walker::{
     compiled_glob::CompiledGlob,
-    walker::{EntryKind, WalkError, Walker, WalkerOptions},
+    walker::{EntryKind, SuppressErrors, WalkError, Walker, WalkerOptions},
 };
 
 pub struct ConfigWalker {
@@ -31,14 +31,6 @@ fn materialize_stdin(bytes: &[u8]) -> io::Result<tempfile::NamedTempFile> {
     Ok(temp)
 }
 
-fn is_ignorable_walk_error(e: &io::Error) -> bool {
-    use std::io::ErrorKind;
-    matches!(
-        e.kind(),
-        ErrorKind::NotFound | ErrorKind::NotADirectory | ErrorKind::PermissionDenied
-    )
-}
-
 impl ConfigWalker {
     pub fn recv(&mut self) -> impl Future<Output = Option<Result<PathBuf, io::Error>>> {
         self.rx.recv()
@@ -82,6 +74,11 @@ impl ConfigWalker {
         let _cwd = current_dir()?;
         let options = WalkerOptions {
             files_only: true,
+            suppress_errors: SuppressErrors::new([
+                io::ErrorKind::NotFound,
+                io::ErrorKind::NotADirectory,
+                io::ErrorKind::PermissionDenied,
+            ]),
             ..WalkerOptions::default()
         };
         let handle = tokio::spawn(async move {
@@ -104,9 +101,6 @@ impl ConfigWalker {
                         }
                     }
                     Err(WalkError::Io { source, .. }) => {
-                        if is_ignorable_walk_error(&source) {
-                            continue;
-                        }
                         if tx.send(Err(source)).await.is_err() {
                             return;
                         }
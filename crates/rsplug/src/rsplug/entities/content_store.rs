@@ -0,0 +1,110 @@
+//! Install-scoped content-addressed store for small package files.
+//!
+//! Many small plugins ship byte-identical LICENSE/README/boilerplate files.
+//! While one generation is staged, each distinct (content, mode) pair is
+//! written once under `<staging>/.store/` and every package that needs it gets
+//! a hard link. The store lives inside the private staging root, so it is
+//! discarded with it; the published links keep the shared inode alive.
+//!
+//! Links are only made between files of the same staged generation, never to
+//! a repository snapshot, so editing a snapshot still cannot change the
+//! published pack.
+
+use std::sync::{Mutex, atomic::AtomicBool};
+
+use tokio::sync::OnceCell;
+use xxhash_rust::xxh3::xxh3_128;
+
+use super::*;
+
+/// これより大きいファイルは hash のための全読み込みが割に合わないので通常 copy に任せる。
+const MAX_DEDUP_BYTES: u64 = 64 * 1024;
+
+#[derive(Hash, PartialEq, Eq, Clone, Copy)]
+struct ContentKey {
+    len: u64,
+    /// 実行ビット等が異なる同一内容は別エントリにする（リンク先は mode を共有するため）。
+    mode: u32,
+    digest: u128,
+}
+
+pub(super) struct ContentStore {
+    root: PathBuf,
+    entries: Mutex<HashMap<ContentKey, Arc<OnceCell<PathBuf>>>>,
+}
+
+impl ContentStore {
+    pub(super) fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 通常ファイル `src` を store 経由のハードリンクとして `dst` に配置する。
+    /// 対象外（大きい・通常ファイルでない）なら `Ok(false)` を返し、呼出元が通常 copy する。
+    pub(super) async fn place(
+        &self,
+        src: &Path,
+        meta: &std::fs::Metadata,
+        dst: &Path,
+    ) -> io::Result<bool> {
+        if !meta.is_file() || meta.len() > MAX_DEDUP_BYTES {
+            return Ok(false);
+        }
+        let data = tokio::fs::read(src).await?;
+        crate::rsplug::perf::incr_content_bytes(data.len() as u64);
+        let key = ContentKey {
+            len: data.len() as u64,
+            mode: mode_of(meta),
+            digest: xxh3_128(&data),
+        };
+        let cell = Arc::clone(self.entries.lock().unwrap().entry(key).or_default());
+        let first = AtomicBool::new(false);
+        let stored = cell
+            .get_or_try_init(|| async {
+                first.store(true, AtomicOrdering::Relaxed);
+                let path = self
+                    .root
+                    .join(format!("{:032x}-{:o}", key.digest, key.mode));
+                tokio::fs::create_dir_all(&self.root).await?;
+                tokio::fs::write(&path, &data).await?;
+                tokio::fs::set_permissions(&path, meta.permissions()).await?;
+                Ok::<_, io::Error>(path)
+            })
+            .await?;
+        if let Some(parent) = dst.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // マージで同名パスが既に配置済みなら、内容を書き換えず（共有 inode を壊さないよう）外して張り直す。
+        let linked = match tokio::fs::hard_link(stored, dst).await {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                tokio::fs::remove_file(dst).await?;
+                tokio::fs::hard_link(stored, dst).await
+            }
+            result => result,
+        };
+        if linked.is_err() {
+            // ハードリンク非対応・リンク数上限等。内容は手元にあるので単独ファイルとして書く。
+            let _ = tokio::fs::remove_file(dst).await;
+            tokio::fs::write(dst, &data).await?;
+            tokio::fs::set_permissions(dst, meta.permissions()).await?;
+            return Ok(true);
+        }
+        if !first.load(AtomicOrdering::Relaxed) {
+            crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::DedupLink);
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(unix)]
+fn mode_of(meta: &std::fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode()
+}
+
+#[cfg(not(unix))]
+fn mode_of(meta: &std::fs::Metadata) -> u32 {
+    meta.permissions().readonly() as u32
+}
@@ -1,4 +1,6 @@
-use std::{io, sync::Arc};
+use std::{io, path::PathBuf, sync::Arc, time::Duration};
+
+use crate::rsplug::util::format_bytes;
 
 /// System-derived errors which cannot be handled by the application.
 #[derive(thiserror::Error, Debug)]
@@ -27,7 +29,97 @@ pub enum Error {
     },
     #[error("Build Lua script failed with exit code {code} in repo {repo:?}")]
     BuildLuaScriptFailed { code: i32, repo: Arc<str> },
+    /// update が snapshot のローカル変更を置き去りにするため中断した。
+    #[error(
+        "Local modifications in {repo} would be discarded by update (rerun with --force or set `allow_dirty = true`):\n{}",
+        paths.iter().map(|p| format!("  {p}")).collect::<Vec<_>>().join("\n")
+    )]
+    DirtySnapshot { repo: Arc<str>, paths: Vec<String> },
+    /// `dev = true` のプラグインのローカル checkout が見つからない。
+    #[error("Dev checkout for {repo} not found at {path:?} (clone it there or set --dev-path)")]
+    DevCheckoutMissing { repo: Arc<str>, path: PathBuf },
+    /// `patches` のパッチファイルを読めない。
+    #[error("Failed to read patch {path:?} for {repo}: {source}")]
+    PatchRead {
+        repo: Arc<str>,
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// `patches` のパッチが checkout に当たらない（update で upstream 側が変わった等）。
+    #[error(
+        "Patch {patch:?} does not apply to {repo} at {rev}; rejected hunks in:\n{}\nrefresh the patch against this revision or pin `rev`",
+        files.iter().map(|f| format!("  {f}")).collect::<Vec<_>>().join("\n")
+    )]
+    PatchConflict {
+        repo: Arc<str>,
+        patch: PathBuf,
+        rev: String,
+        files: Vec<String>,
+    },
+    /// checkout が配置上限（`max_files`・`max_total_size`）を超えた（`node_modules` の誤コミット等）。
+    #[error(
+        "{plugin} is too large to install: {files} files, {} (limits: max_files = {max_files}, max_total_size = {})\nlargest entries:\n{}\nexclude them with `ignore` or raise `max_files` / `max_total_size` for this plugin",
+        format_bytes(*bytes),
+        format_bytes(*max_total_size),
+        largest.iter().map(|(path, files, bytes)| format!("  {}  {files} files, {}", path.display(), format_bytes(*bytes))).collect::<Vec<_>>().join("\n")
+    )]
+    PluginTooLarge {
+        plugin: Arc<str>,
+        files: u64,
+        bytes: u64,
+        max_files: u64,
+        max_total_size: u64,
+        /// 大きい順の最上位エントリ（パス・ファイル数・バイト数）。
+        largest: Vec<(PathBuf, u64, u64)>,
+    },
+    /// `auto_setup = true` の対象の Lua module を 1 つに決められない。
+    #[error(
+        "{plugin} sets `auto_setup`, but its main Lua module is unclear (found: {}); name it with `auto_setup = \"<module>\"`",
+        if modules.is_empty() { "none".to_string() } else { modules.join(", ") }
+    )]
+    AutoSetupModule {
+        plugin: Arc<str>,
+        modules: Vec<String>,
+    },
+    /// `on_source` に自身の依存元を指定した。依存先は依存元より先に読まれるので成り立たない。
+    #[error(
+        "{plugin} sets `on_source = \"{dependent}\"`, but {dependent} depends on {plugin} and always loads it first; drop one of them"
+    )]
+    OnSourceOfDependent { plugin: String, dependent: String },
     /// Dependency-graph 構築エラー（重複 id・未知の依存・閉路）。
     #[error(transparent)]
     Dag(#[from] dag::DagError),
+    /// spawn したタスクが panic（または cancel）した。panic の内容はログに出す。
+    #[error("{context} task panicked")]
+    TaskPanicked { context: &'static str },
+    /// fetch・checkout が `fetch_timeout`・`checkout_timeout` を過ぎた（応答しないリモート等）。
+    /// 裏で動いている git 操作には中断を伝えてある。
+    #[error(
+        "{phase} of {plugin} timed out after {}s; raise `{phase}_timeout` for this plugin if the remote is just slow",
+        after.as_secs()
+    )]
+    Timeout {
+        phase: &'static str,
+        plugin: Arc<str>,
+        after: Duration,
+    },
+}
+
+impl Error {
+    /// job の cell で共有した失敗を、待っていた呼び出し側ごとの `Error` に写す。
+    /// 種類を保つのは時間切れだけで、他は表示文字列の I/O エラーになる。
+    pub(crate) fn unshare(&self) -> Error {
+        match self {
+            Error::Timeout {
+                phase,
+                plugin,
+                after,
+            } => Error::Timeout {
+                phase,
+                plugin: plugin.clone(),
+                after: *after,
+            },
+            error => Error::Io(io::Error::other(error.to_string())),
+        }
+    }
 }
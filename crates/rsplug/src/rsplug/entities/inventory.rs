@@ -4,8 +4,48 @@
 //! fallback helpers. Repository acquisition and plugin identity calculation
 //! consume these functions but do not own traversal policy.
 
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    str::FromStr,
+};
+
+use file_specifier::FileSpecifier;
+
 use super::*;
 
+/// repo 直下（作者が同梱）と repo キャッシュ root（利用者の上書き）に置く除外指定のファイル名。
+pub(super) const IGNORE_FILE: &str = ".rsplugignore";
+
+/// `merge.ignore` の後ろに repo の `.rsplugignore`、さらに cache 側の上書きを連結した除外指定。
+/// gitignore 形式なので後のルールが勝ち、`!` で前の除外を取り消せる。どちらも無ければ借用のまま。
+/// manifest があればその列挙で `.rsplugignore` の有無を判定し、無いファイルは読みに行かない。
+pub(super) async fn effective_ignore<'a>(
+    ignore: &'a FileSpecifier,
+    snapshot_root: &Path,
+    manifest: Option<&SnapshotManifest>,
+    overlay: Option<&Path>,
+) -> Cow<'a, FileSpecifier> {
+    let mut extra = String::new();
+    if manifest.is_none_or(|manifest| manifest.kind_of(Path::new(IGNORE_FILE)).is_some())
+        && let Ok(content) = tokio::fs::read_to_string(snapshot_root.join(IGNORE_FILE)).await
+    {
+        extra.push_str(&content);
+        extra.push('\n');
+    }
+    if let Some(overlay) = overlay
+        && let Ok(content) = tokio::fs::read_to_string(overlay).await
+    {
+        extra.push_str(&content);
+        extra.push('\n');
+    }
+    if extra.is_empty() {
+        return Cow::Borrowed(ignore);
+    }
+    let combined = format!("{}\n{extra}", ignore.as_str());
+    Cow::Owned(FileSpecifier::from_str(&combined).unwrap_or_else(|never| match never {}))
+}
+
 pub(super) async fn load_snapshot_manifest(snapshot_root: &Path) -> Option<SnapshotManifest> {
     crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::InventoryParse);
     let bytes = tokio::fs::read(snapshot_root.join(MANIFEST_FILE))
@@ -112,6 +152,109 @@ pub(super) async fn doc_file_entries(
     out
 }
 
+/// 1 つの最上位エントリが pack に持ち込むファイル数・バイト数。
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub(super) struct PlacementUsage {
+    pub(super) files: u64,
+    pub(super) bytes: u64,
+}
+
+/// `tops`（snapshot 直下の配置対象名）ごとに、配下の leaf を数える。manifest があれば
+/// その記録から、なければ filesystem を walk して求める。symlink は辿らず 0 バイトの
+/// 1 ファイルとして数える（pack にも symlink として置かれる）。
+pub(super) async fn placement_usage(
+    snapshot_root: &Path,
+    manifest: Option<&SnapshotManifest>,
+    tops: &BTreeSet<PathBuf>,
+) -> std::io::Result<BTreeMap<PathBuf, PlacementUsage>> {
+    let mut usage: BTreeMap<PathBuf, PlacementUsage> = tops
+        .iter()
+        .map(|top| (top.clone(), PlacementUsage::default()))
+        .collect();
+    if let Some(manifest) = manifest {
+        for entry in &manifest.entries {
+            if entry.kind == ManifestKind::Dir {
+                continue;
+            }
+            let Some(top) = entry.path.iter().next() else {
+                continue;
+            };
+            let Some(top_usage) = usage.get_mut(Path::new(top)) else {
+                continue;
+            };
+            top_usage.files += 1;
+            top_usage.bytes += match (entry.kind, entry.size) {
+                (_, Some(size)) => size,
+                // サイズを記録する前に書かれた manifest。該当ファイルだけ stat する。
+                (ManifestKind::File, None) => {
+                    tokio::fs::symlink_metadata(snapshot_root.join(&entry.path))
+                        .await?
+                        .len()
+                }
+                _ => 0,
+            };
+        }
+        return Ok(usage);
+    }
+    for (top, top_usage) in &mut usage {
+        let mut stack = vec![snapshot_root.join(top)];
+        while let Some(path) = stack.pop() {
+            let meta = match tokio::fs::symlink_metadata(&path).await {
+                Ok(meta) => meta,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            if meta.is_dir() {
+                let mut rd = tokio::fs::read_dir(&path).await?;
+                while let Some(entry) = rd.next_entry().await? {
+                    stack.push(entry.path());
+                }
+            } else {
+                top_usage.files += 1;
+                if meta.is_file() {
+                    top_usage.bytes += meta.len();
+                }
+            }
+        }
+    }
+    Ok(usage)
+}
+
+/// 配置対象が `max_files`・`max_total_size` を超えていれば、大きい最上位エントリを添えて中止する。
+pub(super) async fn check_placement_limits(
+    snapshot_root: &Path,
+    manifest: Option<&SnapshotManifest>,
+    entries: &[(PathBuf, FileItem)],
+    merge: &MergeConfig,
+    plugin: Arc<str>,
+) -> Result<(), Error> {
+    let tops = entries
+        .iter()
+        .filter_map(|(path, _)| path.iter().next())
+        .map(PathBuf::from)
+        .collect::<BTreeSet<_>>();
+    let usage = placement_usage(snapshot_root, manifest, &tops).await?;
+    let files = usage.values().map(|usage| usage.files).sum::<u64>();
+    let bytes = usage.values().map(|usage| usage.bytes).sum::<u64>();
+    if files <= merge.max_files && bytes <= merge.max_total_size {
+        return Ok(());
+    }
+    let mut largest: Vec<_> = usage.into_iter().collect();
+    largest.sort_by_key(|(_, usage)| std::cmp::Reverse((usage.bytes, usage.files)));
+    largest.truncate(3);
+    Err(Error::PluginTooLarge {
+        plugin,
+        files,
+        bytes,
+        max_files: merge.max_files,
+        max_total_size: merge.max_total_size,
+        largest: largest
+            .into_iter()
+            .map(|(path, usage)| (path, usage.files, usage.bytes))
+            .collect(),
+    })
+}
+
 pub(super) async fn extract_unique_lua_modules_from_snapshot(snapshot_root: &Path) -> Vec<String> {
     let mut rd = match tokio::fs::read_dir(snapshot_root.join("lua")).await {
         Ok(rd) => rd,
@@ -139,3 +282,30 @@ pub(super) async fn extract_unique_lua_modules_from_snapshot(snapshot_root: &Pat
     }
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rsplugignore_and_cache_overlay_extend_merge_ignore() {
+        let tmp = tempfile::tempdir().unwrap();
+        let snapshot = tmp.path().join("worktrees/abc");
+        std::fs::create_dir_all(&snapshot).unwrap();
+        let base = FileSpecifier::from_str("*.md").unwrap();
+
+        // どちらも無ければ merge.ignore をそのまま使う。
+        let ignore = effective_ignore(&base, &snapshot, None, None).await;
+        assert!(matches!(ignore, Cow::Borrowed(_)));
+
+        std::fs::write(snapshot.join(IGNORE_FILE), "scripts\nassets\n").unwrap();
+        let overlay = tmp.path().join(IGNORE_FILE);
+        std::fs::write(&overlay, "!assets\n").unwrap();
+        let ignore = effective_ignore(&base, &snapshot, None, Some(&overlay)).await;
+        assert!(ignore.matched("README.md"));
+        assert!(ignore.matched("scripts"));
+        // 利用者の上書きは作者の除外より後なので、`!` で取り消せる。
+        assert!(!ignore.matched("assets"));
+        assert!(!ignore.matched("lua"));
+    }
+}
@@ -49,6 +49,26 @@ pub(super) fn lua_string(value: impl std::fmt::Display) -> LuaStringLiteral {
     LuaStringLiteral(out)
 }
 
+/// trigger → [id] 表を `{ [key]={id,...}, ... }` の Lua テーブルとして描く。
+/// 組み込みの `.stpl` と同じ形で、差し替えの `.stpl` に渡す値に使う。
+fn lua_table<K, V>(table: impl IntoIterator<Item = (K, V)>) -> String
+where
+    K: std::fmt::Display,
+    V: IntoIterator,
+    V::Item: std::fmt::Display,
+{
+    let mut out = String::from("{ ");
+    for (key, values) in table {
+        out.push_str(&format!("[{}]={{", lua_string(key)));
+        for value in values {
+            out.push_str(&format!("{},", lua_string(value)));
+        }
+        out.push_str("},");
+    }
+    out.push_str(" }");
+    out
+}
+
 /// docファイルを束ねたヘルプ専用プラグインの source_name。
 /// `pack/_gen/start/` への配置判定に使われる。
 pub(super) const DOC_PLUGIN_NAME: &str = "_rsplug:doc";
@@ -60,18 +80,31 @@ struct PkgId2ScriptsItem {
     start: bool, // もし読み込みプラグイン元が LazyType::Start なら、他のスクリプトと別の仕組みでスクリプトを呼び出す必要があるため
 }
 
+/// 2 つ以上のパッケージが遅延読み込みのトリガーにした同じコマンド・マッピング。
+#[derive(Debug, PartialEq, Eq)]
+pub struct DuplicateTrigger {
+    /// 設定に書く形のトリガー（例: `on_cmd:Foo`・`on_map:n:<leader>f`）。
+    pub trigger: String,
+    /// 登録したパッケージごとの設定上の名前（併合されたものは `, ` で結合）。宣言順。
+    pub plugins: Vec<String>,
+}
+
 /// プラグインの読み込み制御・ロード後の設定 (after_lua等)を行う構造体
 #[derive(Default)]
 pub struct LazyRegistration {
     pkgid2scripts: Vec<PkgId2ScriptsItem>,
     event2pkgid: BTreeMap<Autocmd, Vec<PluginIDStr>>,
     cmd2pkgid: BTreeMap<UserCmd, Vec<PluginIDStr>>,
+    /// 読み込み前のスタブに付ける補完。キーは補完指定を除いたコマンド。
+    cmd_complete: BTreeMap<UserCmd, Arc<String>>,
     ft2pkgid: BTreeMap<FileType, Vec<PluginIDStr>>,
     func2pkgid: BTreeMap<VimFunc, Vec<PluginIDStr>>,
     luam2pkgid: BTreeMap<LuaModule, Vec<PluginIDStr>>,
     source_name2pkgid: BTreeMap<String, Vec<PluginIDStr>>,
     source_target2pkgid: BTreeMap<String, PluginIDStr>,
     keypattern2pkgid: BTreeMap<ModeChar, BTreeMap<Arc<String>, Vec<PluginIDStr>>>,
+    /// lazy パッケージの宣言順（`depends` の DAG 順）。trigger の id 配列をこの順に保つ。
+    pkgid2order: BTreeMap<PluginIDStr, usize>,
 }
 
 /// 生成ファイル（`FileSource::File`）の `(install_path, FileItem)` を作る。
@@ -101,359 +134,645 @@ fn instant_startup_pkg(path: &str, data: impl Into<Cow<'static, [u8]>>) -> Loade
         files: HowToPlaceFiles::CopyEachFile(files),
         script: Default::default(),
         order: usize::MAX,
-        merge_enabled: true,
+        merge_policy: None,
+        target: PackTarget::User,
         is_lazy_registration: true,
         dotgit: false,
     }
 }
 
-impl From<LazyRegistration> for Vec<LoadedPlugin> {
-    fn from(value: LazyRegistration) -> Vec<LoadedPlugin> {
-        if value.is_empty() {
-            return Vec::with_capacity(0);
+/// 描画ジョブ 1 件。互いに独立なので任意のスレッドで並列に実行できる。
+type RenderJob = Box<dyn FnOnce() -> Vec<LoadedPlugin> + Send>;
+
+/// hook module 描画の入力 1 件: `(pkgid, lua_before, lua_after)`。
+type HookInput = (PluginIDStr, Vec<String>, Vec<String>);
+
+/// 1 ジョブあたりの hook module / ftplugin の描画件数（spawn_blocking の起動コストとの釣り合い）。
+const RENDER_CHUNK: usize = 64;
+
+/// `LazyRegistration` を描画単位に分解したもの。`init.lua` は hook module の id
+/// （内容ハッシュ）を参照するので、hook の描画が揃ってから描く。それ以外は互いに独立。
+struct RenderPlan {
+    /// 描画を要しない固定パッケージ（`doc/rsplug.txt`）。
+    fixed: Vec<LoadedPlugin>,
+    hooks: Vec<Vec<HookInput>>,
+    init: InitInput,
+    sections: Vec<RenderJob>,
+}
+
+struct InitInput {
+    debug_loader: bool,
+    startup_plugins: Vec<PluginIDStr>,
+    startup_scripts: Vec<String>,
+    source2pkgid: Vec<(PluginIDStr, Vec<PluginIDStr>)>,
+    pkgid2names: Vec<(PluginIDStr, Vec<String>)>,
+}
+
+impl LazyRegistration {
+    /// `From` と同じ出力を、描画ジョブを `spawn_blocking` で並列に実行して得る。
+    /// 数百の lazy プラグインを持つ設定で async runtime のスレッドを塞がないようにする。
+    /// `debug_loader` なら検証・ログ付きの loader を描く（`--debug-loader`）。
+    /// 差し替え可能なランタイムは `templates` から取る。
+    pub(super) async fn render(
+        self,
+        debug_loader: bool,
+        templates: Arc<TemplateOverrides>,
+    ) -> std::io::Result<Vec<LoadedPlugin>> {
+        if self.is_empty() {
+            return Ok(Vec::new());
+        }
+        let RenderPlan {
+            fixed: mut plugs,
+            hooks,
+            init,
+            sections,
+        } = self.into_render_plan(debug_loader, templates);
+        let hook_tasks = hooks
+            .into_iter()
+            .map(|chunk| tokio::task::spawn_blocking(move || render_hook_modules(chunk)))
+            .collect::<Vec<_>>();
+        let section_tasks = sections
+            .into_iter()
+            .map(tokio::task::spawn_blocking)
+            .collect::<Vec<_>>();
+        let mut modules = Vec::new();
+        for task in hook_tasks {
+            let (ids, hook_plugs) = task.await.map_err(render_join_error)?;
+            modules.extend(ids);
+            plugs.extend(hook_plugs);
         }
+        let init = tokio::task::spawn_blocking(move || render_init(init, modules));
+        plugs.push(init.await.map_err(render_join_error)?);
+        for task in section_tasks {
+            plugs.extend(task.await.map_err(render_join_error)?);
+        }
+        Ok(plugs)
+    }
+
+    fn into_render_plan(self, debug_loader: bool, templates: Arc<TemplateOverrides>) -> RenderPlan {
         let LazyRegistration {
             pkgid2scripts,
             event2pkgid,
             cmd2pkgid,
+            cmd_complete,
             ft2pkgid,
             func2pkgid,
             luam2pkgid,
             source_name2pkgid,
             source_target2pkgid,
             keypattern2pkgid,
-        } = value;
+            pkgid2order: _,
+        } = self;
 
-        let mut plugs = vec![instant_startup_pkg(
+        let fixed = vec![instant_startup_pkg(
             "./doc/rsplug.txt",
             include_bytes!("../../../templates/doc/rsplug.txt"),
         )];
 
+        // Scripts that do the initial setup of the plugin
+        let mut hooks = Vec::with_capacity(pkgid2scripts.len());
+        let mut startup_plugins = Vec::new();
+        let mut startup_scripts = Vec::new();
+        for PkgId2ScriptsItem {
+            pkgid,
+            script,
+            order,
+            start,
+        } in pkgid2scripts
         {
-            // Add packages to place scripts that does the initial setup of the plugin
-            let (pkgid2scripts, startup_plugins, startup_scripts) = pkgid2scripts.into_iter().fold(
-                (Vec::new(), Vec::new(), Vec::new()),
-                |(mut scripts_lazy, mut scripts_start, mut scripts_startup),
-                 PkgId2ScriptsItem {
-                     pkgid,
-                     script,
-                     order,
-                     start,
-                 }| {
-                    let SetupScript {
-                        lua_start,
-                        lua_after,
-                        lua_before,
-                    } = script;
-                    let before_scripts: Vec<String> = lua_before.into_iter().collect();
-                    let after_scripts: Vec<String> = lua_after.into_iter().collect();
-                    let hook_module = if before_scripts.is_empty() && after_scripts.is_empty() {
-                        None
-                    } else {
-                        let data = HookModuleTemplate {
-                            before: &before_scripts,
-                            after: &after_scripts,
-                        }
-                        .render_once()
-                        .unwrap()
-                        .into_bytes();
-                        let module_id =
-                            format!("_rsplug/hooks_{}", hash::digest_hash_hex_string(&data));
-                        plugs.push(instant_startup_pkg(&format!("lua/{module_id}.lua"), data));
-                        Some(module_id)
-                    };
-                    for content in lua_start {
-                        scripts_startup.push((order, pkgid.clone(), content));
-                    }
-
-                    if start {
-                        scripts_start.push((order, pkgid.clone()));
-                    }
-                    if let Some(hook_module) = hook_module {
-                        scripts_lazy.push((pkgid, hook_module));
-                    }
-                    (scripts_lazy, scripts_start, scripts_startup)
-                },
-            );
-            let mut startup_plugins = startup_plugins;
-            startup_plugins.sort_by(|(l_order, l_pkgid), (r_order, r_pkgid)| {
-                l_order.cmp(r_order).then_with(|| l_pkgid.cmp(r_pkgid))
-            });
-            let startup_plugins = startup_plugins
+            let SetupScript {
+                lua_start,
+                lua_after,
+                lua_before,
+                ..
+            } = script;
+            for content in lua_start {
+                startup_scripts.push((order, pkgid.clone(), content));
+            }
+            if start {
+                startup_plugins.push((order, pkgid.clone()));
+            }
+            if !(lua_before.is_empty() && lua_after.is_empty()) {
+                hooks.push((
+                    pkgid,
+                    lua_before.into_iter().collect(),
+                    lua_after.into_iter().collect(),
+                ));
+            }
+        }
+        startup_plugins.sort_by(|(l_order, l_pkgid), (r_order, r_pkgid)| {
+            l_order.cmp(r_order).then_with(|| l_pkgid.cmp(r_pkgid))
+        });
+        startup_scripts.sort_by(
+            |(l_order, l_pkgid, l_module), (r_order, r_pkgid, r_module)| {
+                l_order
+                    .cmp(r_order)
+                    .then_with(|| l_pkgid.cmp(r_pkgid))
+                    .then_with(|| l_module.cmp(r_module))
+            },
+        );
+        let init = InitInput {
+            debug_loader,
+            startup_plugins: startup_plugins
                 .into_iter()
                 .map(|(_, pkgid)| pkgid)
-                .collect();
-            let mut startup_scripts = startup_scripts;
-            startup_scripts.sort_by(
-                |(l_order, l_pkgid, l_module), (r_order, r_pkgid, r_module)| {
-                    l_order
-                        .cmp(r_order)
-                        .then_with(|| l_pkgid.cmp(r_pkgid))
-                        .then_with(|| l_module.cmp(r_module))
-                },
-            );
-            let startup_scripts: Vec<String> = startup_scripts
+                .collect(),
+            startup_scripts: startup_scripts
                 .into_iter()
                 .map(|(_, _, content)| content)
-                .collect();
-            let init_data: Cow<'static, [u8]> = CustomPackaddTemplate {
-                pkgid2scripts,
-                startup_plugins,
-                source2pkgid: build_source2pkgid(source_name2pkgid, source_target2pkgid),
-            }
-            .render_once()
-            .unwrap()
-            .into_bytes()
-            .into();
-            let mut files = BTreeMap::from([generated_file_item(
-                PathBuf::from("lua/_rsplug/init.lua"),
-                init_data,
-            )]);
-            if !startup_scripts.is_empty() {
-                let data: Cow<'static, [u8]> = LuaStartPluginTemplate {
-                    startup_scripts: &startup_scripts,
-                }
-                .render_once()
-                .unwrap()
-                .into_bytes()
-                .into();
-                let (ls_path, ls_item) =
-                    generated_file_item(PathBuf::from("plugin/lua_start.lua"), data);
-                files.insert(ls_path, ls_item);
-            }
-            plugs.push(LoadedPlugin {
-                source_names: BTreeSet::from(["_rsplug:init".to_string()]),
-                lazy_type: LazyType::Start,
-                files: HowToPlaceFiles::CopyEachFile(files),
-                script: Default::default(),
-                order: usize::MAX,
-                merge_enabled: true,
-                is_lazy_registration: true,
-                dotgit: false,
-            });
-        }
-
-        if !ft2pkgid.is_empty() {
-            // on_ft setup
-            plugs.push(instant_startup_pkg(
-                "lua/_rsplug/on_ft.lua",
-                include_bytes!("../../../templates/lua/_rsplug/on_ft.lua"),
-            ));
-            for (ft, pkgids) in ft2pkgid {
-                let mut path = format!("ftplugin/{ft}/");
-                let data = FtpluginTemplate { pkgids, ft }
-                    .render_once()
-                    .unwrap()
-                    .into_bytes();
-                path.push_str(&hash::digest_hash_hex_string(&data));
-                path.push_str(".lua");
+                .collect(),
+            pkgid2names: build_pkgid2names(&source_target2pkgid),
+            source2pkgid: build_source2pkgid(source_name2pkgid, source_target2pkgid),
+        };
 
-                plugs.push(instant_startup_pkg(&path, data));
-            }
+        let mut sections: Vec<RenderJob> = Vec::new();
+        let (ft_patterns, ft2pkgid): (BTreeMap<_, _>, BTreeMap<_, _>) =
+            ft2pkgid.into_iter().partition(|(ft, _)| ft.is_pattern());
+        // 共通の `on_ft.lua` は最初に描く on_ft ジョブが運ぶ。
+        let mut ft_runtime = Some(debug_loader);
+        for chunk in into_chunks(ft2pkgid.into_iter().collect()) {
+            let runtime = ft_runtime.take();
+            let templates = Arc::clone(&templates);
+            sections.push(Box::new(move || render_on_ft(chunk, runtime, &templates)));
+        }
+        if !ft_patterns.is_empty() {
+            let templates = Arc::clone(&templates);
+            sections.push(Box::new(move || {
+                render_on_ft_pattern(ft_patterns, ft_runtime, &templates)
+            }));
         }
-
         if !event2pkgid.is_empty() {
-            // on_event setup
-            let events = event2pkgid.keys();
-            let on_event_setup: Cow<'static, [u8]> = OnEventSetupTemplate { events }
-                .render_once()
-                .unwrap()
-                .into_bytes()
-                .into();
-            let on_event: Cow<'static, [u8]> = OnEventTemplate {
-                event2pkgid: &event2pkgid,
-            }
-            .render_once()
-            .unwrap()
-            .into_bytes()
-            .into();
-            let on_event_setup_path = PathBuf::from(format!(
-                "plugin/{}.lua",
-                hash::digest_hash_hex_string(&on_event_setup)
-            ));
-            let files = BTreeMap::from([
-                generated_file_item(PathBuf::from("lua/_rsplug/on_event.lua"), on_event),
-                generated_file_item(on_event_setup_path, on_event_setup),
-            ]);
-            plugs.push({
-                LoadedPlugin {
-                    source_names: BTreeSet::from(["_rsplug:on_event".to_string()]),
-                    lazy_type: LazyType::Start,
-                    files: HowToPlaceFiles::CopyEachFile(files),
-                    script: Default::default(),
-                    order: usize::MAX,
-                    merge_enabled: true,
-                    is_lazy_registration: true,
-                    dotgit: false,
-                }
-            });
+            let templates = Arc::clone(&templates);
+            sections.push(Box::new(move || {
+                vec![render_on_event(event2pkgid, debug_loader, &templates)]
+            }));
         }
         if !func2pkgid.is_empty() {
-            let funcs = func2pkgid.keys();
-            let on_func_setup: Cow<'static, [u8]> = OnFuncSetupTemplate { funcs }
-                .render_once()
-                .unwrap()
-                .into_bytes()
-                .into();
-            let on_func: Cow<'static, [u8]> = OnFuncTemplate {
-                func2pkgid: &func2pkgid,
-            }
-            .render_once()
-            .unwrap()
-            .into_bytes()
-            .into();
-            let on_func_setup_path = PathBuf::from(format!(
-                "plugin/{}.lua",
-                hash::digest_hash_hex_string(&on_func_setup)
-            ));
-            let files = BTreeMap::from([
-                generated_file_item(PathBuf::from("lua/_rsplug/on_func.lua"), on_func),
-                generated_file_item(on_func_setup_path, on_func_setup),
-            ]);
-            plugs.push(LoadedPlugin {
-                source_names: BTreeSet::from(["_rsplug:on_func".to_string()]),
-                lazy_type: LazyType::Start,
-                files: HowToPlaceFiles::CopyEachFile(files),
-                script: Default::default(),
-                order: usize::MAX,
-                merge_enabled: true,
-                is_lazy_registration: true,
-                dotgit: false,
-            });
+            let templates = Arc::clone(&templates);
+            sections.push(Box::new(move || {
+                vec![render_on_func(func2pkgid, &templates)]
+            }));
         }
         if !cmd2pkgid.is_empty() {
-            // on_cmd setup
-            plugs.push({
-                let cmds = cmd2pkgid.keys();
-                let on_cmd_setup: Cow<'static, [u8]> = OnCmdSetupTemplate { cmds }
-                    .render_once()
-                    .unwrap()
-                    .into_bytes()
-                    .into();
-                let on_cmd: Cow<'static, [u8]> = OnCmdTemplate {
-                    cmd2pkgid: &cmd2pkgid,
-                }
-                .render_once()
-                .unwrap()
-                .into_bytes()
-                .into();
-                let on_cmd_setup_path = PathBuf::from(format!(
-                    "plugin/{}.lua",
-                    hash::digest_hash_hex_string(&on_cmd_setup)
-                ));
-                let files = BTreeMap::from([
-                    generated_file_item(PathBuf::from("lua/_rsplug/on_cmd.lua"), on_cmd),
-                    generated_file_item(on_cmd_setup_path, on_cmd_setup),
-                ]);
-                LoadedPlugin {
-                    source_names: BTreeSet::from(["_rsplug:on_cmd".to_string()]),
-                    lazy_type: LazyType::Start,
-                    files: HowToPlaceFiles::CopyEachFile(files),
-                    script: Default::default(),
-                    order: usize::MAX,
-                    merge_enabled: true,
-                    is_lazy_registration: true,
-                    dotgit: false,
-                }
-            });
+            let templates = Arc::clone(&templates);
+            sections.push(Box::new(move || {
+                vec![render_on_cmd(cmd2pkgid, cmd_complete, &templates)]
+            }));
         }
         if !luam2pkgid.is_empty() {
-            let plugin_on_lua = include_bytes!("../../../templates/plugin/on_lua.lua");
-            // R4: luam2pkgid から pkgid2luam (id -> [root]) を決定的に導出する。
-            let mut pkgid2luam_map: BTreeMap<PluginIDStr, BTreeSet<String>> = BTreeMap::new();
-            for (luam, ids) in &luam2pkgid {
-                for id in ids {
-                    pkgid2luam_map
-                        .entry(id.clone())
-                        .or_default()
-                        .insert(luam.to_string());
-                }
-            }
-            let pkgid2luam: Vec<(PluginIDStr, Vec<String>)> = pkgid2luam_map
-                .into_iter()
-                .map(|(id, roots)| (id, roots.into_iter().collect()))
-                .collect();
-            let on_lua: Cow<'static, [u8]> = OnLuaTemplate {
-                luam2pkgid: &luam2pkgid,
-                pkgid2luam,
-            }
-            .render_once()
-            .unwrap()
-            .into_bytes()
-            .into();
-            let plugin_on_lua_path = PathBuf::from(format!(
-                "plugin/{}.lua",
-                hash::digest_hash_hex_string(plugin_on_lua)
-            ));
-            let files = BTreeMap::from([
-                generated_file_item(PathBuf::from("lua/_rsplug/on_lua.lua"), on_lua),
-                generated_file_item(plugin_on_lua_path, plugin_on_lua.into()),
-            ]);
-            plugs.push(LoadedPlugin {
-                source_names: BTreeSet::from(["_rsplug:on_lua".to_string()]),
-                lazy_type: LazyType::Start,
-                files: HowToPlaceFiles::CopyEachFile(files),
-                script: Default::default(),
-                order: usize::MAX,
-                merge_enabled: true,
-                is_lazy_registration: true,
-                dotgit: false,
-            });
+            let templates = Arc::clone(&templates);
+            sections.push(Box::new(move || {
+                vec![render_on_lua(luam2pkgid, &templates)]
+            }));
         }
         if !keypattern2pkgid.is_empty() {
-            // R5: on_map セットアップはテンプレート化。到達可能モードから pending_modes を構築し、
-            // 専有 augroup に ModeChanged / VimEnter(once) を登録する。
-            let on_map_setup: Cow<'static, [u8]> = OnMapSetupTemplate {
-                modes: keypattern2pkgid.keys(),
-            }
-            .render_once()
-            .unwrap()
-            .into_bytes()
-            .into();
-            plugs.push(instant_startup_pkg(
-                &format!("plugin/{}.lua", hash::digest_hash_hex_string(&on_map_setup)),
-                on_map_setup,
-            ));
-            plugs.push(instant_startup_pkg(
-                "lua/_rsplug/on_map/init.lua",
-                include_bytes!("../../../templates/lua/_rsplug/on_map/init.lua"),
-            ));
-            for mode in keypattern2pkgid.keys() {
-                let data = OnMapTemplate {
-                    mode,
-                    keypattern2pkgid: &keypattern2pkgid,
-                }
-                .render_once()
-                .unwrap()
-                .into_bytes();
-                plugs.push(instant_startup_pkg(
-                    &format!("lua/_rsplug/on_map/mode_{mode}.lua"),
-                    data,
-                ));
-            }
+            sections.push(Box::new(move || {
+                render_on_map(keypattern2pkgid, &templates)
+            }));
         }
 
         // NOTE: doc 盗みは `LoadedPlugin::split_doc`（`PackPlan::load`）で LoadedPlugin として
         // 扱い、ここ（control マージ）で rsplug-doc・lazy loader と統一マージされる。
         // LazyRegistration 自体は lazy 実行制御のみを担う。
 
+        RenderPlan {
+            fixed,
+            hooks: into_chunks(hooks),
+            init,
+            sections,
+        }
+    }
+}
+
+impl From<LazyRegistration> for Vec<LoadedPlugin> {
+    fn from(value: LazyRegistration) -> Vec<LoadedPlugin> {
+        if value.is_empty() {
+            return Vec::with_capacity(0);
+        }
+        let RenderPlan {
+            fixed: mut plugs,
+            hooks,
+            init,
+            sections,
+        } = value.into_render_plan(false, Default::default());
+        let mut modules = Vec::new();
+        for chunk in hooks {
+            let (ids, hook_plugs) = render_hook_modules(chunk);
+            modules.extend(ids);
+            plugs.extend(hook_plugs);
+        }
+        plugs.push(render_init(init, modules));
+        for job in sections {
+            plugs.extend(job());
+        }
         plugs
     }
 }
 
+fn render_join_error(e: tokio::task::JoinError) -> std::io::Error {
+    std::io::Error::other(util::task::panicked("render")(e))
+}
+
+fn into_chunks<T>(items: Vec<T>) -> Vec<Vec<T>> {
+    let mut chunks = Vec::with_capacity(items.len().div_ceil(RENDER_CHUNK));
+    let mut items = items.into_iter().peekable();
+    while items.peek().is_some() {
+        chunks.push(items.by_ref().take(RENDER_CHUNK).collect());
+    }
+    chunks
+}
+
+/// 出力規模の見積もり `capacity` で確保した buffer に描画する（大規模設定での再確保を避ける）。
+fn render_sized(template: impl TemplateSimple, capacity: usize) -> Vec<u8> {
+    let mut buffer = sailfish::runtime::Buffer::with_capacity(capacity);
+    template.render_once_to(&mut buffer).unwrap();
+    buffer.into_string().into_bytes()
+}
+
+/// trigger → pkgid 表を描くテンプレートの出力サイズ見積もり（id 1 件あたりの行を概算）。
+fn table_capacity<K, V>(table: &BTreeMap<K, Vec<V>>) -> usize {
+    1024 + 96 * table.values().map(Vec::len).sum::<usize>()
+}
+
+/// hook module（`lua/_rsplug/hooks_<hash>.lua`）を描き、`(pkgid, module_id)` と配置パッケージを返す。
+fn render_hook_modules(chunk: Vec<HookInput>) -> (Vec<(PluginIDStr, String)>, Vec<LoadedPlugin>) {
+    let mut modules = Vec::with_capacity(chunk.len());
+    let mut plugs = Vec::with_capacity(chunk.len());
+    for (pkgid, before, after) in chunk {
+        let capacity = 512 + before.iter().chain(&after).map(String::len).sum::<usize>();
+        let data = render_sized(
+            HookModuleTemplate {
+                before: &before,
+                after: &after,
+            },
+            capacity,
+        );
+        let module_id = format!("_rsplug/hooks_{}", hash::digest_hash_hex_string(&data));
+        plugs.push(instant_startup_pkg(&format!("lua/{module_id}.lua"), data));
+        modules.push((pkgid, module_id));
+    }
+    (modules, plugs)
+}
+
+fn render_init(init: InitInput, pkgid2scripts: Vec<(PluginIDStr, String)>) -> LoadedPlugin {
+    let InitInput {
+        debug_loader,
+        startup_plugins,
+        startup_scripts,
+        source2pkgid,
+        pkgid2names,
+    } = init;
+    let capacity = 4096
+        + 128 * (pkgid2scripts.len() + startup_plugins.len())
+        + 96 * source2pkgid
+            .iter()
+            .map(|(_, ids)| ids.len() + 1)
+            .sum::<usize>()
+        + 96 * pkgid2names
+            .iter()
+            .map(|(_, names)| names.len() + 1)
+            .sum::<usize>();
+    let init_data: Cow<'static, [u8]> = render_sized(
+        CustomPackaddTemplate {
+            debug_loader,
+            pkgid2scripts,
+            startup_plugins,
+            source2pkgid,
+            pkgid2names,
+        },
+        capacity,
+    )
+    .into();
+    let mut files = BTreeMap::from([generated_file_item(
+        PathBuf::from("lua/_rsplug/init.lua"),
+        init_data,
+    )]);
+    if !startup_scripts.is_empty() {
+        let capacity = 256 + startup_scripts.iter().map(|s| s.len() + 64).sum::<usize>();
+        let data: Cow<'static, [u8]> = render_sized(
+            LuaStartPluginTemplate {
+                startup_scripts: &startup_scripts,
+            },
+            capacity,
+        )
+        .into();
+        let (ls_path, ls_item) = generated_file_item(PathBuf::from("plugin/lua_start.lua"), data);
+        files.insert(ls_path, ls_item);
+    }
+    LoadedPlugin {
+        source_names: BTreeSet::from(["_rsplug:init".to_string()]),
+        lazy_type: LazyType::Start,
+        files: HowToPlaceFiles::CopyEachFile(files),
+        script: Default::default(),
+        order: usize::MAX,
+        merge_policy: None,
+        target: PackTarget::User,
+        is_lazy_registration: true,
+        dotgit: false,
+    }
+}
+
+/// 共通の `lua/_rsplug/on_ft.lua`。
+fn render_on_ft_runtime(debug_loader: bool, templates: &TemplateOverrides) -> LoadedPlugin {
+    let data = templates
+        .render(ON_FT_RUNTIME, |_| debug_loader.to_string())
+        .unwrap_or_else(|| {
+            OnFtRuntimeTemplate { debug_loader }
+                .render_once()
+                .unwrap()
+                .into_bytes()
+        });
+    instant_startup_pkg("lua/_rsplug/on_ft.lua", data)
+}
+
+/// on_ft setup。`runtime` が `Some(debug_loader)` の chunk だけが共通の `on_ft.lua` を運ぶ。
+fn render_on_ft(
+    chunk: Vec<(FileType, Vec<PluginIDStr>)>,
+    runtime: Option<bool>,
+    templates: &TemplateOverrides,
+) -> Vec<LoadedPlugin> {
+    let mut plugs = Vec::with_capacity(chunk.len() + 1);
+    if let Some(debug_loader) = runtime {
+        plugs.push(render_on_ft_runtime(debug_loader, templates));
+    }
+    for (ft, pkgids) in chunk {
+        let mut path = format!("ftplugin/{ft}/");
+        let capacity = 512 + 96 * pkgids.len();
+        let data = render_sized(FtpluginTemplate { pkgids, ft }, capacity);
+        path.push_str(&hash::digest_hash_hex_string(&data));
+        path.push_str(".lua");
+
+        plugs.push(instant_startup_pkg(&path, data));
+    }
+    plugs
+}
+
+/// パターン・複合 filetype の on_ft setup（`FileType` 自動コマンド）。
+fn render_on_ft_pattern(
+    ft2pkgid: BTreeMap<FileType, Vec<PluginIDStr>>,
+    runtime: Option<bool>,
+    templates: &TemplateOverrides,
+) -> Vec<LoadedPlugin> {
+    let mut plugs = Vec::with_capacity(2);
+    if let Some(debug_loader) = runtime {
+        plugs.push(render_on_ft_runtime(debug_loader, templates));
+    }
+    let data = render_sized(
+        OnFtPatternSetupTemplate {
+            ft2pkgid: &ft2pkgid,
+        },
+        table_capacity(&ft2pkgid),
+    );
+    let path = format!("plugin/{}.lua", hash::digest_hash_hex_string(&data));
+    plugs.push(instant_startup_pkg(&path, data));
+    plugs
+}
+
+fn render_on_event(
+    event2pkgid: BTreeMap<Autocmd, Vec<PluginIDStr>>,
+    debug_loader: bool,
+    templates: &TemplateOverrides,
+) -> LoadedPlugin {
+    let events = event2pkgid.keys();
+    let on_event_setup: Cow<'static, [u8]> = OnEventSetupTemplate { events }
+        .render_once()
+        .unwrap()
+        .into_bytes()
+        .into();
+    let on_event: Cow<'static, [u8]> = templates
+        .render(ON_EVENT_RUNTIME, |var| match var {
+            "event2pkgid" => lua_table(&event2pkgid),
+            _ => debug_loader.to_string(),
+        })
+        .unwrap_or_else(|| {
+            render_sized(
+                OnEventTemplate {
+                    event2pkgid: &event2pkgid,
+                    debug_loader,
+                },
+                table_capacity(&event2pkgid),
+            )
+        })
+        .into();
+    let on_event_setup_path = PathBuf::from(format!(
+        "plugin/{}.lua",
+        hash::digest_hash_hex_string(&on_event_setup)
+    ));
+    let files = BTreeMap::from([
+        generated_file_item(PathBuf::from("lua/_rsplug/on_event.lua"), on_event),
+        generated_file_item(on_event_setup_path, on_event_setup),
+    ]);
+    LoadedPlugin {
+        source_names: BTreeSet::from(["_rsplug:on_event".to_string()]),
+        lazy_type: LazyType::Start,
+        files: HowToPlaceFiles::CopyEachFile(files),
+        script: Default::default(),
+        order: usize::MAX,
+        merge_policy: None,
+        target: PackTarget::User,
+        is_lazy_registration: true,
+        dotgit: false,
+    }
+}
+
+fn render_on_func(
+    func2pkgid: BTreeMap<VimFunc, Vec<PluginIDStr>>,
+    templates: &TemplateOverrides,
+) -> LoadedPlugin {
+    let funcs = func2pkgid.keys();
+    let on_func_setup: Cow<'static, [u8]> = OnFuncSetupTemplate { funcs }
+        .render_once()
+        .unwrap()
+        .into_bytes()
+        .into();
+    let on_func: Cow<'static, [u8]> = templates
+        .render(ON_FUNC_RUNTIME, |_| lua_table(&func2pkgid))
+        .unwrap_or_else(|| {
+            render_sized(
+                OnFuncTemplate {
+                    func2pkgid: &func2pkgid,
+                },
+                table_capacity(&func2pkgid),
+            )
+        })
+        .into();
+    let on_func_setup_path = PathBuf::from(format!(
+        "plugin/{}.lua",
+        hash::digest_hash_hex_string(&on_func_setup)
+    ));
+    let files = BTreeMap::from([
+        generated_file_item(PathBuf::from("lua/_rsplug/on_func.lua"), on_func),
+        generated_file_item(on_func_setup_path, on_func_setup),
+    ]);
+    LoadedPlugin {
+        source_names: BTreeSet::from(["_rsplug:on_func".to_string()]),
+        lazy_type: LazyType::Start,
+        files: HowToPlaceFiles::CopyEachFile(files),
+        script: Default::default(),
+        order: usize::MAX,
+        merge_policy: None,
+        target: PackTarget::User,
+        is_lazy_registration: true,
+        dotgit: false,
+    }
+}
+
+fn render_on_cmd(
+    cmd2pkgid: BTreeMap<UserCmd, Vec<PluginIDStr>>,
+    cmd_complete: BTreeMap<UserCmd, Arc<String>>,
+    templates: &TemplateOverrides,
+) -> LoadedPlugin {
+    let cmds = cmd2pkgid
+        .keys()
+        .map(|cmd| (cmd, cmd_complete.get(cmd).map(|complete| complete.as_str())))
+        .collect();
+    let on_cmd_setup: Cow<'static, [u8]> = OnCmdSetupTemplate { cmds }
+        .render_once()
+        .unwrap()
+        .into_bytes()
+        .into();
+    let on_cmd: Cow<'static, [u8]> = templates
+        .render(ON_CMD_RUNTIME, |_| lua_table(&cmd2pkgid))
+        .unwrap_or_else(|| {
+            render_sized(
+                OnCmdTemplate {
+                    cmd2pkgid: &cmd2pkgid,
+                },
+                table_capacity(&cmd2pkgid),
+            )
+        })
+        .into();
+    let on_cmd_setup_path = PathBuf::from(format!(
+        "plugin/{}.lua",
+        hash::digest_hash_hex_string(&on_cmd_setup)
+    ));
+    let files = BTreeMap::from([
+        generated_file_item(PathBuf::from("lua/_rsplug/on_cmd.lua"), on_cmd),
+        generated_file_item(on_cmd_setup_path, on_cmd_setup),
+    ]);
+    LoadedPlugin {
+        source_names: BTreeSet::from(["_rsplug:on_cmd".to_string()]),
+        lazy_type: LazyType::Start,
+        files: HowToPlaceFiles::CopyEachFile(files),
+        script: Default::default(),
+        order: usize::MAX,
+        merge_policy: None,
+        target: PackTarget::User,
+        is_lazy_registration: true,
+        dotgit: false,
+    }
+}
+
+fn render_on_lua(
+    luam2pkgid: BTreeMap<LuaModule, Vec<PluginIDStr>>,
+    templates: &TemplateOverrides,
+) -> LoadedPlugin {
+    let plugin_on_lua = templates.get(ON_LUA_PLUGIN);
+    // R4: luam2pkgid から pkgid2luam (id -> [root]) を決定的に導出する。
+    let mut pkgid2luam_map: BTreeMap<PluginIDStr, BTreeSet<String>> = BTreeMap::new();
+    for (luam, ids) in &luam2pkgid {
+        for id in ids {
+            pkgid2luam_map
+                .entry(id.clone())
+                .or_default()
+                .insert(luam.to_string());
+        }
+    }
+    let pkgid2luam: Vec<(PluginIDStr, Vec<String>)> = pkgid2luam_map
+        .into_iter()
+        .map(|(id, roots)| (id, roots.into_iter().collect()))
+        .collect();
+    let capacity = 2 * table_capacity(&luam2pkgid);
+    let on_lua: Cow<'static, [u8]> = templates
+        .render(ON_LUA_RUNTIME, |var| match var {
+            "luam2pkgid" => lua_table(&luam2pkgid),
+            _ => lua_table(pkgid2luam.iter().map(|(id, roots)| (id, roots))),
+        })
+        .unwrap_or_else(|| {
+            render_sized(
+                OnLuaTemplate {
+                    luam2pkgid: &luam2pkgid,
+                    pkgid2luam,
+                },
+                capacity,
+            )
+        })
+        .into();
+    let plugin_on_lua_path = PathBuf::from(format!(
+        "plugin/{}.lua",
+        hash::digest_hash_hex_string(&plugin_on_lua)
+    ));
+    let files = BTreeMap::from([
+        generated_file_item(PathBuf::from("lua/_rsplug/on_lua.lua"), on_lua),
+        generated_file_item(plugin_on_lua_path, plugin_on_lua),
+    ]);
+    LoadedPlugin {
+        source_names: BTreeSet::from(["_rsplug:on_lua".to_string()]),
+        lazy_type: LazyType::Start,
+        files: HowToPlaceFiles::CopyEachFile(files),
+        script: Default::default(),
+        order: usize::MAX,
+        merge_policy: None,
+        target: PackTarget::User,
+        is_lazy_registration: true,
+        dotgit: false,
+    }
+}
+
+fn render_on_map(
+    keypattern2pkgid: BTreeMap<ModeChar, BTreeMap<Arc<String>, Vec<PluginIDStr>>>,
+    templates: &TemplateOverrides,
+) -> Vec<LoadedPlugin> {
+    let mut plugs = Vec::with_capacity(keypattern2pkgid.len() + 2);
+    // R5: on_map セットアップはテンプレート化。到達可能モードから pending_modes を構築し、
+    // 専有 augroup に ModeChanged / VimEnter(once) を登録する。
+    let on_map_setup: Cow<'static, [u8]> = OnMapSetupTemplate {
+        modes: keypattern2pkgid.keys(),
+    }
+    .render_once()
+    .unwrap()
+    .into_bytes()
+    .into();
+    plugs.push(instant_startup_pkg(
+        &format!("plugin/{}.lua", hash::digest_hash_hex_string(&on_map_setup)),
+        on_map_setup,
+    ));
+    plugs.push(instant_startup_pkg(
+        ON_MAP_RUNTIME,
+        templates.get(ON_MAP_RUNTIME),
+    ));
+    for (mode, patterns) in &keypattern2pkgid {
+        let data = render_sized(
+            OnMapTemplate {
+                mode,
+                keypattern2pkgid: &keypattern2pkgid,
+            },
+            table_capacity(patterns),
+        );
+        plugs.push(instant_startup_pkg(
+            &format!("lua/_rsplug/on_map/mode_{mode}.lua"),
+            data,
+        ));
+    }
+    plugs
+}
+
 impl AddAssign for LazyRegistration {
     fn add_assign(&mut self, other: Self) {
         let Self {
             pkgid2scripts,
             event2pkgid,
             cmd2pkgid,
+            cmd_complete,
             ft2pkgid,
             func2pkgid,
             luam2pkgid,
             source_name2pkgid,
             source_target2pkgid,
             keypattern2pkgid,
+            pkgid2order,
         } = other;
+        self.pkgid2order.extend(pkgid2order);
         for (event, ids) in event2pkgid {
             self.event2pkgid.entry(event).or_default().extend(ids);
         }
@@ -461,6 +780,10 @@ impl AddAssign for LazyRegistration {
         for (cmd, ids) in cmd2pkgid {
             self.cmd2pkgid.entry(cmd).or_default().extend(ids);
         }
+        // 同じコマンドに複数の補完指定があれば先に登録されたものを使う。
+        for (cmd, complete) in cmd_complete {
+            self.cmd_complete.entry(cmd).or_insert(complete);
+        }
         for (ft, ids) in ft2pkgid {
             self.ft2pkgid.entry(ft).or_default().extend(ids);
         }
@@ -484,27 +807,34 @@ impl AddAssign for LazyRegistration {
             }
         }
 
-        // Trigger records are rendered in insertion order. Deduplicate once while
-        // composing the registration so generated Lua need not linearly scan lists.
-        dedup_trigger_ids(&mut self.event2pkgid);
-        dedup_trigger_ids(&mut self.cmd2pkgid);
-        dedup_trigger_ids(&mut self.ft2pkgid);
-        dedup_trigger_ids(&mut self.func2pkgid);
-        dedup_trigger_ids(&mut self.luam2pkgid);
-        dedup_trigger_ids(&mut self.source_name2pkgid);
+        // Trigger records are rendered in order and packadd'ed in that order. Keep
+        // them in declaration order (not in the order registrations were added,
+        // which follows lazy_type first) and deduplicated while composing, so the
+        // generated Lua loads each id once and need not linearly scan lists.
+        let orders = &self.pkgid2order;
+        order_trigger_ids(&mut self.event2pkgid, orders);
+        order_trigger_ids(&mut self.cmd2pkgid, orders);
+        order_trigger_ids(&mut self.ft2pkgid, orders);
+        order_trigger_ids(&mut self.func2pkgid, orders);
+        order_trigger_ids(&mut self.luam2pkgid, orders);
+        order_trigger_ids(&mut self.source_name2pkgid, orders);
         for patterns in self.keypattern2pkgid.values_mut() {
-            dedup_trigger_ids(patterns);
+            order_trigger_ids(patterns, orders);
         }
     }
 }
 
-fn dedup_trigger_ids<K>(records: &mut BTreeMap<K, Vec<PluginIDStr>>)
-where
+/// 各 trigger の id 配列から重複を除き、宣言順（同順なら登録順）に安定ソートする。
+fn order_trigger_ids<K>(
+    records: &mut BTreeMap<K, Vec<PluginIDStr>>,
+    orders: &BTreeMap<PluginIDStr, usize>,
+) where
     K: Ord,
 {
     for ids in records.values_mut() {
         let mut seen = BTreeSet::new();
         ids.retain(|id| seen.insert(id.clone()));
+        ids.sort_by_key(|id| orders.get(id).copied().unwrap_or(usize::MAX));
     }
 }
 
@@ -529,12 +859,14 @@ impl LazyRegistration {
             pkgid2scripts: scripts,
             event2pkgid,
             cmd2pkgid,
+            cmd_complete: _,
             ft2pkgid,
             func2pkgid,
             luam2pkgid,
             source_name2pkgid,
             source_target2pkgid,
             keypattern2pkgid,
+            pkgid2order: _,
         } = self;
         event2pkgid.is_empty()
             && scripts.is_empty()
@@ -550,9 +882,11 @@ impl LazyRegistration {
     /// on_ft で登録された `(ft, id)` を文字列キーで取り出す（R1: ft インデックス構築用）。
     /// `PackPlan::install` が `self.ctl` を消費する前に呼ぶ。ft2pkgid 以外のマップは
     /// 外部に公開しない。戻り値は `ft -> [id]`（id は挿入順、重複なしを前提）。
+    /// パターン・複合 filetype は実際の filetype が実行時まで分からないので含めない。
     pub(super) fn ft_index_pairs(&self) -> BTreeMap<String, Vec<String>> {
         self.ft2pkgid
             .iter()
+            .filter(|(ft, _)| !ft.is_pattern())
             .map(|(ft, ids)| {
                 (
                     ft.to_string(),
@@ -562,6 +896,103 @@ impl LazyRegistration {
             .collect()
     }
 
+    /// 別々のパッケージが登録した同じユーザコマンド・同じモードとキーのマッピング。
+    /// stub はトリガーごとに 1 つなので読み込みは全パッケージに及ぶが、読み込まれた側は
+    /// 互いのコマンド・マッピングを上書きし合う。併合されて同じパッケージに入ったものは含めない。
+    pub(super) fn duplicate_triggers(&self) -> Vec<DuplicateTrigger> {
+        let mut names: BTreeMap<&PluginIDStr, Vec<&str>> = BTreeMap::new();
+        for (name, id) in &self.source_target2pkgid {
+            names.entry(id).or_default().push(name);
+        }
+        let mut duplicates = Vec::new();
+        let mut check = |trigger: String, ids: &[PluginIDStr]| {
+            let mut distinct: Vec<&PluginIDStr> = Vec::new();
+            for id in ids {
+                if !distinct.contains(&id) {
+                    distinct.push(id);
+                }
+            }
+            if distinct.len() < 2 {
+                return;
+            }
+            let plugins = distinct
+                .into_iter()
+                .map(|id| {
+                    names
+                        .get(id)
+                        .map_or_else(|| id.to_string(), |n| n.join(", "))
+                })
+                .collect();
+            duplicates.push(DuplicateTrigger { trigger, plugins });
+        };
+        for (cmd, ids) in &self.cmd2pkgid {
+            check(format!("on_cmd:{cmd}"), ids);
+        }
+        for (mode, lhs2pkgid) in &self.keypattern2pkgid {
+            for (lhs, ids) in lhs2pkgid {
+                check(format!("on_map:{mode}:{lhs}"), ids);
+            }
+        }
+        duplicates
+    }
+
+    /// どのトリガーからも読み込まれない lazy パッケージの設定上の名前（併合されたものは `, ` で結合）。
+    /// 宣言順。`on_source` は参照先が読み込まれうるときだけ数えるので、存在しない名前や
+    /// 読み込まれないパッケージを待つものも含む。
+    pub(super) fn unloadable_packages(&self) -> Vec<String> {
+        let mut loadable: BTreeSet<&PluginIDStr> = self
+            .pkgid2scripts
+            .iter()
+            .filter(|item| item.start)
+            .map(|item| &item.pkgid)
+            .collect();
+        loadable.extend(
+            self.event2pkgid
+                .values()
+                .chain(self.cmd2pkgid.values())
+                .chain(self.ft2pkgid.values())
+                .chain(self.func2pkgid.values())
+                .chain(self.luam2pkgid.values())
+                .chain(self.keypattern2pkgid.values().flat_map(BTreeMap::values))
+                .flatten(),
+        );
+        // `on_source` は連鎖するので、読み込まれうるものが増えなくなるまで繰り返す。
+        loop {
+            let known = loadable.len();
+            for (name, ids) in &self.source_name2pkgid {
+                if self
+                    .source_target2pkgid
+                    .get(name)
+                    .is_some_and(|target| loadable.contains(target))
+                {
+                    loadable.extend(ids);
+                }
+            }
+            if loadable.len() == known {
+                break;
+            }
+        }
+
+        let mut names: BTreeMap<&PluginIDStr, Vec<&str>> = BTreeMap::new();
+        for (name, id) in &self.source_target2pkgid {
+            names.entry(id).or_default().push(name);
+        }
+        let mut unloadable: Vec<_> = self
+            .pkgid2order
+            .iter()
+            .filter(|(id, _)| !loadable.contains(id))
+            .collect();
+        unloadable.sort_by_key(|(_, order)| **order);
+        unloadable
+            .into_iter()
+            .map(|(id, _)| {
+                names
+                    .get(id)
+                    .map_or_else(|| id.to_string(), |n| n.join(", "))
+            })
+            .collect()
+    }
+
     #[cfg(test)]
     pub(super) fn event_ids_for_test(&self, event: &Autocmd) -> Vec<String> {
         self.event2pkgid
@@ -575,15 +1006,15 @@ impl LazyRegistration {
     /// パッケージ情報を読み込み、 LazyRegistration を作成する。
     /// 読み込む情報が要らない場合は `None` を返す。
     /// NOTE: Package はインストールされる必要があるため、変更を抑制する意図で PackageID の所有権を奪う。
+    /// `id_str` は `pack/_gen/opt/` 以下のパッケージ名（内容ハッシュまたは安定名）。
     /// その他必要な情報のみ引数に取る。
     pub(super) fn create(
-        id: PluginID,
+        id_str: PluginIDStr,
         source_names: BTreeSet<String>,
         lazy_type: LazyType,
         script: SetupScript,
         order: usize,
     ) -> Self {
-        let id_str = id.as_str();
         // 全 source_name を自身の id に紐付ける。マージで集約された複数名すべてが
         // on_source 参照できるようにする（Phase 1: source_name を潰さない）。
         let source_target2pkgid = source_names
@@ -605,6 +1036,7 @@ impl LazyRegistration {
         };
         let mut event2pkgid: BTreeMap<Autocmd, Vec<_>> = BTreeMap::new();
         let mut cmd2pkgid: BTreeMap<UserCmd, Vec<_>> = BTreeMap::new();
+        let mut cmd_complete: BTreeMap<UserCmd, Arc<String>> = BTreeMap::new();
         let mut ft2pkgid: BTreeMap<FileType, Vec<_>> = BTreeMap::new();
         let mut func2pkgid: BTreeMap<VimFunc, Vec<_>> = BTreeMap::new();
         let mut luam2pkgid: BTreeMap<LuaModule, Vec<_>> = BTreeMap::new();
@@ -622,13 +1054,17 @@ impl LazyRegistration {
             use LoadEvent::*;
             match ev {
                 Autocmd(autocmd) => {
-                    event2pkgid.entry(autocmd).or_default().push(id.as_str());
+                    event2pkgid.entry(autocmd).or_default().push(id_str.clone());
                 }
                 UserCmd(cmd) => {
-                    cmd2pkgid.entry(cmd).or_default().push(id.as_str());
+                    let (cmd, complete) = cmd.split_complete();
+                    if let Some(complete) = complete {
+                        cmd_complete.entry(cmd.clone()).or_insert(complete);
+                    }
+                    cmd2pkgid.entry(cmd).or_default().push(id_str.clone());
                 }
                 FileType(ft) => {
-                    ft2pkgid.entry(ft).or_default().push(id.as_str());
+                    ft2pkgid.entry(ft).or_default().push(id_str.clone());
                 }
                 VimFunc(func) => {
                     func2pkgid.entry(func).or_default().push(id_str.clone());
@@ -644,7 +1080,6 @@ impl LazyRegistration {
                 }
                 OnMap(pattern) => {
                     let KeyPattern(pattern) = pattern;
-                    let id = id.as_str();
                     for (mode, pattern) in pattern {
                         for pattern in pattern {
                             keypattern2pkgid
@@ -652,7 +1087,7 @@ impl LazyRegistration {
                                 .or_default()
                                 .entry(pattern)
                                 .or_default()
-                                .push(id.clone());
+                                .push(id_str.clone());
                         }
                     }
                 }
@@ -662,12 +1097,14 @@ impl LazyRegistration {
             pkgid2scripts,
             event2pkgid,
             cmd2pkgid,
+            cmd_complete,
             ft2pkgid,
             func2pkgid,
             luam2pkgid,
             source_name2pkgid,
             source_target2pkgid,
             keypattern2pkgid,
+            pkgid2order: BTreeMap::from([(id_str, order)]),
         }
     }
 }
@@ -680,13 +1117,32 @@ struct FtpluginTemplate {
     ft: FileType,
 }
 
+#[derive(TemplateSimple)]
+#[template(path = "plugin/on_ft.stpl")]
+#[template(escape = false)]
+struct OnFtPatternSetupTemplate<'a> {
+    ft2pkgid: &'a BTreeMap<FileType, Vec<PluginIDStr>>,
+}
+
+#[derive(TemplateSimple)]
+#[template(path = "lua/_rsplug/on_ft.stpl")]
+#[template(escape = false)]
+struct OnFtRuntimeTemplate {
+    /// `--debug-loader`: 再生した autocmd の失敗を握りつぶさず、ログに残す。
+    debug_loader: bool,
+}
+
 #[derive(TemplateSimple)]
 #[template(path = "lua/_rsplug/init.stpl")]
 #[template(escape = false)]
 struct CustomPackaddTemplate {
+    /// `--debug-loader`: assert と `_rsplug.log` へのログを埋め込む。
+    debug_loader: bool,
     pkgid2scripts: Vec<(PluginIDStr, String)>,
     startup_plugins: Vec<PluginIDStr>,
     source2pkgid: Vec<(PluginIDStr, Vec<PluginIDStr>)>,
+    /// `stats()` で示すパッケージ名（id ごとの source name）。
+    pkgid2names: Vec<(PluginIDStr, Vec<String>)>,
 }
 
 #[derive(TemplateSimple)]
@@ -704,6 +1160,20 @@ struct LuaStartPluginTemplate<'a> {
     startup_scripts: &'a [String],
 }
 
+/// id → その id に集約された source name（昇順）。
+fn build_pkgid2names(
+    source_target2pkgid: &BTreeMap<String, PluginIDStr>,
+) -> Vec<(PluginIDStr, Vec<String>)> {
+    let mut pkgid2names: BTreeMap<PluginIDStr, Vec<String>> = BTreeMap::new();
+    for (source_name, pkgid) in source_target2pkgid {
+        pkgid2names
+            .entry(pkgid.clone())
+            .or_default()
+            .push(source_name.clone());
+    }
+    pkgid2names.into_iter().collect()
+}
+
 fn build_source2pkgid(
     source_name2pkgid: BTreeMap<String, Vec<PluginIDStr>>,
     source_target2pkgid: BTreeMap<String, PluginIDStr>,
@@ -730,13 +1200,16 @@ struct OnEventSetupTemplate<'a> {
 #[template(escape = false)]
 struct OnEventTemplate<'a> {
     event2pkgid: &'a BTreeMap<Autocmd, Vec<PluginIDStr>>,
+    /// `--debug-loader`: 再生した autocmd の失敗を握りつぶさず、ログに残す。
+    debug_loader: bool,
 }
 
 #[derive(TemplateSimple)]
 #[template(path = "plugin/on_cmd.stpl")]
 #[template(escape = false)]
 struct OnCmdSetupTemplate<'a> {
-    cmds: Keys<'a, UserCmd, Vec<PluginIDStr>>,
+    /// コマンドと、スタブに付ける補完の指定。
+    cmds: Vec<(&'a UserCmd, Option<&'a str>)>,
 }
 
 #[derive(TemplateSimple)]
@@ -788,6 +1261,89 @@ struct OnMapSetupTemplate<'a> {
 mod tests {
     use super::*;
 
+    /// 描画ベンチ・並列描画テスト用: on_cmd/on_event/on_ft と hook を持つ lazy プラグイン群。
+    fn lazy_registration_fixture(plugins: usize) -> LazyRegistration {
+        let mut registration = LazyRegistration::default();
+        for i in 0..plugins {
+            // ユーザコマンド名は英字のみなので、番号を英字に書き換える。
+            let suffix: String = i
+                .to_string()
+                .bytes()
+                .map(|digit| char::from(b'a' + digit - b'0'))
+                .collect();
+            let events = BTreeSet::from([
+                LoadEvent::UserCmd(format!("BenchCmd{suffix}").parse().unwrap()),
+                LoadEvent::Autocmd("BufReadPre".parse().unwrap()),
+                LoadEvent::FileType(format!("ft{}", i % 97).parse().unwrap()),
+            ]);
+            let script = SetupScript {
+                lua_after: BTreeSet::from([format!("vim.g.bench_{i} = true")]),
+                ..Default::default()
+            };
+            registration += LazyRegistration::create(
+                format!("bench-plugin-{i}").plugin_id().as_str(),
+                BTreeSet::from([format!("bench-plugin-{i}")]),
+                LazyType::Opt(events),
+                script,
+                i,
+            );
+        }
+        registration
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn parallel_render_matches_sequential_conversion() {
+        let sequential: Vec<LoadedPlugin> = lazy_registration_fixture(150).into();
+        let parallel = lazy_registration_fixture(150)
+            .render(false, Default::default())
+            .await
+            .unwrap();
+
+        assert_eq!(sequential, parallel);
+    }
+
+    /// Validation bench（非gating・ignored）: 数百の lazy プラグインを持つ LazyRegistration の
+    /// 描画を逐次（`From`、before）と並列（`render`）で計測し、
+    /// `target/lazy_registration_render_bench.json` へ書き出す。
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "non-gating benchmark: run with --ignored"]
+    async fn bench_lazy_registration_render() {
+        use crate::rsplug::perf::{median, write_bench_report};
+
+        const SAMPLES: usize = 7;
+        let mut cases = Vec::new();
+        for scale in [100usize, 300, 1000] {
+            let mut before = Vec::with_capacity(SAMPLES);
+            let mut after = Vec::with_capacity(SAMPLES);
+            for _ in 0..SAMPLES {
+                let registration = lazy_registration_fixture(scale);
+                let started = std::time::Instant::now();
+                let plugs: Vec<LoadedPlugin> = registration.into();
+                before.push(started.elapsed().as_nanos());
+                drop(plugs);
+
+                let registration = lazy_registration_fixture(scale);
+                let started = std::time::Instant::now();
+                let plugs = registration
+                    .render(false, Default::default())
+                    .await
+                    .unwrap();
+                after.push(started.elapsed().as_nanos());
+                drop(plugs);
+            }
+            cases.push(format!(
+                "    \"render_{scale}\": {{\"scale\":{scale}, \"samples\":{SAMPLES}, \"median_ns\":{}, \"before_median_ns\":{}}}",
+                median(after),
+                median(before),
+            ));
+        }
+        write_bench_report(
+            "lazy_registration_render",
+            "lazy_registration_render_bench.json",
+            &cases,
+        );
+    }
+
     #[test]
     fn lua_string_literal_handles_quotes_controls_and_utf8() {
         assert_eq!(
@@ -808,6 +1364,9 @@ mod tests {
         .unwrap();
 
         assert!(rendered.contains("FuncUndefined"));
+        // 読み直しで FuncUndefined が重複しないよう、専用の augroup に置く。
+        assert!(rendered.contains(".augroup('rsplug.runtime.on_func')"));
+        assert!(rendered.contains("{ group = group, pattern = \"foo#bar\""));
         assert!(rendered.contains("autoload_handler(\"foo#bar\")"));
         assert!(!rendered.contains("function! foo#bar"));
     }
@@ -845,6 +1404,242 @@ mod tests {
         );
     }
 
+    #[test]
+    fn lua_table_matches_the_builtin_trigger_table() {
+        let func = "foo#bar".parse::<VimFunc>().unwrap();
+        let func2pkgid = BTreeMap::from([(func, vec!["a".plugin_id().as_str()])]);
+        let rendered = OnFuncTemplate {
+            func2pkgid: &func2pkgid,
+        }
+        .render_once()
+        .unwrap();
+
+        let table = lua_table(&func2pkgid);
+        assert!(
+            rendered.contains(&format!("local func2pkgid = {table}\n")),
+            "{table} not in rendered on_func.lua:\n{rendered}"
+        );
+    }
+
+    #[test]
+    fn on_ft_patterns_and_compound_filetypes_use_filetype_autocmds() {
+        let ft = |ft: &str| LoadEvent::FileType(ft.parse().unwrap());
+        let registration = LazyRegistration::create(
+            "ft-plugin".plugin_id().as_str(),
+            BTreeSet::from(["ft-plugin".to_string()]),
+            LazyType::Opt(BTreeSet::from([
+                ft("lua"),
+                ft("typescript*"),
+                ft("markdown.mdx"),
+            ])),
+            SetupScript::default(),
+            0,
+        );
+        assert_eq!(
+            registration
+                .ft_index_pairs()
+                .into_keys()
+                .collect::<Vec<_>>(),
+            ["lua"]
+        );
+
+        let plugs: Vec<LoadedPlugin> = registration.into();
+        let paths: Vec<PathBuf> = plugs
+            .iter()
+            .flat_map(|plug| {
+                let HowToPlaceFiles::CopyEachFile(files) = &plug.files;
+                files.keys().cloned().collect::<Vec<_>>()
+            })
+            .collect();
+        let ftplugins: Vec<_> = paths
+            .iter()
+            .filter(|path| path.starts_with("ftplugin"))
+            .collect();
+        assert_eq!(ftplugins.len(), 1);
+        assert!(ftplugins[0].starts_with("ftplugin/lua"));
+        assert_eq!(
+            paths
+                .iter()
+                .filter(|path| path.ends_with("lua/_rsplug/on_ft.lua"))
+                .count(),
+            1
+        );
+
+        let id = b"ft-plugin".plugin_id().as_str();
+        let ft2pkgid = BTreeMap::from([
+            (
+                "markdown.mdx".parse::<FileType>().unwrap(),
+                vec![id.clone()],
+            ),
+            ("typescript*".parse::<FileType>().unwrap(), vec![id]),
+        ]);
+        let setup = OnFtPatternSetupTemplate {
+            ft2pkgid: &ft2pkgid,
+        }
+        .render_once()
+        .unwrap();
+        assert!(setup.contains("nvim_create_autocmd('FileType'"));
+        assert!(setup.contains(r#"["markdown.mdx"]="#));
+        assert!(setup.contains(r#"["typescript*"]="#));
+        assert!(setup.contains("on_ft.load_matched(ids, ev.match)"));
+    }
+
+    #[test]
+    fn shared_trigger_ids_follow_declaration_order_once() {
+        let event: Autocmd = "BufReadPre".parse().unwrap();
+        let register = |name: &str, events: Vec<LoadEvent>, order| {
+            LazyRegistration::create(
+                name.plugin_id().as_str(),
+                BTreeSet::from([name.to_string()]),
+                LazyType::Opt(events.into_iter().collect()),
+                SetupScript::default(),
+                order,
+            )
+        };
+        // 依存元は lazy_type が異なるため、依存先より先に登録されうる。
+        let mut registration = register(
+            "dependent",
+            vec![
+                LoadEvent::Autocmd(event.clone()),
+                LoadEvent::Autocmd("InsertEnter".parse().unwrap()),
+            ],
+            2,
+        );
+        registration += register("dependency", vec![LoadEvent::Autocmd(event.clone())], 0);
+        registration += register("sibling", vec![LoadEvent::Autocmd(event.clone())], 2);
+        registration += register("dependency", vec![LoadEvent::Autocmd(event.clone())], 0);
+
+        let id = |name: &str| name.plugin_id().as_str().to_string();
+        assert_eq!(
+            registration.event_ids_for_test(&event),
+            [id("dependency"), id("dependent"), id("sibling")]
+        );
+    }
+
+    #[test]
+    fn on_cmd_stub_advertises_configured_completion() {
+        let lazy_type = |toml: &str| {
+            toml::from_str::<Config>(toml)
+                .unwrap()
+                .plugins
+                .remove(0)
+                .lazy_type
+        };
+        let mut registration = LazyRegistration::create(
+            "telescope".plugin_id().as_str(),
+            BTreeSet::from(["telescope".to_string()]),
+            lazy_type(
+                r#"
+                [[plugins]]
+                repo = "o/telescope"
+                on_cmd = [{ name = "Telescope", complete = "custom,v:lua.telescope_complete" }, "Other"]
+                "#,
+            ),
+            SetupScript::default(),
+            0,
+        );
+        registration += LazyRegistration::create(
+            "extension".plugin_id().as_str(),
+            BTreeSet::from(["extension".to_string()]),
+            lazy_type("[[plugins]]\nrepo = 'o/extension'\non_cmd = 'Telescope'\n"),
+            SetupScript::default(),
+            1,
+        );
+        // 補完指定の有無にかかわらず、同じコマンドは 1 つのスタブにまとまる。
+        assert_eq!(registration.cmd2pkgid.len(), 2);
+        let telescope = "Telescope".parse::<UserCmd>().unwrap();
+        assert_eq!(registration.cmd2pkgid[&telescope].len(), 2);
+
+        let cmds = registration
+            .cmd2pkgid
+            .keys()
+            .map(|cmd| {
+                let complete = registration.cmd_complete.get(cmd);
+                (cmd, complete.map(|complete| complete.as_str()))
+            })
+            .collect();
+        let setup = OnCmdSetupTemplate { cmds }.render_once().unwrap();
+        assert!(setup.contains(r#"["Telescope"]="custom,v:lua.telescope_complete","#));
+        assert!(setup.contains(r#"["Other"]=false,"#));
+        assert!(setup.contains("complete = complete or function(...)"));
+    }
+
+    #[test]
+    fn duplicate_triggers_list_commands_and_mappings_of_several_packages() {
+        let register = |name: &str, ids: &[&str], toml: &str, order| {
+            let config = format!("[[plugins]]\nrepo = 'o/{name}'\n{toml}\n");
+            LazyRegistration::create(
+                name.plugin_id().as_str(),
+                ids.iter().map(ToString::to_string).collect(),
+                toml::from_str::<Config>(&config)
+                    .unwrap()
+                    .plugins
+                    .remove(0)
+                    .lazy_type,
+                SetupScript::default(),
+                order,
+            )
+        };
+        let mut registration = register(
+            "a",
+            &["a.nvim", "merged.nvim"],
+            "on_cmd = ['Foo', 'Bar']\non_map = { n = '<leader>f' }",
+            0,
+        );
+        registration += register("b", &["b.nvim"], "on_cmd = 'Foo'", 1);
+        registration += register("c", &["c.nvim"], "on_map = { n = '<leader>f' }", 2);
+        registration += register("d", &["d.nvim"], "on_map = { x = '<leader>f' }", 3);
+        // 同じパッケージ（併合後）の登録が重なっても重複ではない。
+        registration += register("a", &["a.nvim"], "on_cmd = 'Bar'", 0);
+
+        assert_eq!(
+            registration.duplicate_triggers(),
+            [
+                DuplicateTrigger {
+                    trigger: "on_cmd:Foo".to_string(),
+                    plugins: vec!["a.nvim, merged.nvim".to_string(), "b.nvim".to_string()],
+                },
+                DuplicateTrigger {
+                    trigger: "on_map:n:<leader>f".to_string(),
+                    plugins: vec!["a.nvim, merged.nvim".to_string(), "c.nvim".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn packages_without_reachable_triggers_are_unloadable() {
+        let register = |name: &str, toml: &str, order| {
+            let config = format!("[[plugins]]\nrepo = 'o/{name}'\n{toml}\n");
+            LazyRegistration::create(
+                name.plugin_id().as_str(),
+                BTreeSet::from([format!("{name}.nvim")]),
+                toml::from_str::<Config>(&config)
+                    .unwrap()
+                    .plugins
+                    .remove(0)
+                    .lazy_type,
+                SetupScript::default(),
+                order,
+            )
+        };
+        let mut registration = register("start", "start = true", 0);
+        registration += register("cmd", "on_cmd = 'Foo'", 1);
+        // 既定は lazy なので、トリガーが無ければ読み込まれない。
+        registration += register("empty", "", 2);
+        // 起動時・トリガー付きのパッケージを待つ on_source は連鎖して成り立つ。
+        registration += register("after-start", "on_source = 'start.nvim'", 3);
+        registration += register("after-after", "on_source = 'after-start.nvim'", 4);
+        // 存在しない名前や、読み込まれないパッケージを待つものは読み込まれない。
+        registration += register("missing", "on_source = 'nothing.nvim'", 5);
+        registration += register("after-empty", "on_source = 'empty.nvim'", 6);
+
+        assert_eq!(
+            registration.unloadable_packages(),
+            ["empty.nvim", "missing.nvim", "after-empty.nvim"]
+        );
+    }
+
     #[test]
     fn on_cmd_delegates_once_with_command_metadata_and_arguments() {
         let cmd = "MyCommand".parse::<UserCmd>().unwrap();
@@ -867,9 +1662,11 @@ mod tests {
     fn custom_packadd_template_packadds_startup_plugins() {
         let startup_plugin = b"startup-plugin".plugin_id().as_str();
         let rendered = CustomPackaddTemplate {
+            debug_loader: false,
             pkgid2scripts: Vec::new(),
             startup_plugins: vec![startup_plugin.clone()],
             source2pkgid: Vec::new(),
+            pkgid2names: Vec::new(),
         }
         .render_once()
         .unwrap();
@@ -882,6 +1679,78 @@ mod tests {
         assert!(!rendered.contains("vim.list_contains(result"));
     }
 
+    #[test]
+    fn init_records_loads_for_stats_with_names() {
+        let id = b"stats-plugin".plugin_id().as_str();
+        let pkgid2names = build_pkgid2names(&BTreeMap::from([
+            ("stats.nvim".to_string(), id.clone()),
+            ("stats-extra".to_string(), id.clone()),
+        ]));
+        assert_eq!(
+            pkgid2names,
+            [(
+                id.clone(),
+                vec!["stats-extra".to_string(), "stats.nvim".to_string()]
+            )]
+        );
+        let rendered = CustomPackaddTemplate {
+            debug_loader: false,
+            pkgid2scripts: Vec::new(),
+            startup_plugins: Vec::new(),
+            source2pkgid: Vec::new(),
+            pkgid2names,
+        }
+        .render_once()
+        .unwrap();
+
+        assert!(rendered.contains(&format!(
+            "local pkgid2names = {{[\"{id}\"]={{\"stats-extra\",\"stats.nvim\",}},}}"
+        )));
+        assert!(rendered.contains("packadd = function(id, startup, trigger)"));
+        assert!(rendered.contains("trigger = trigger or (startup and 'start' or 'manual'),"));
+        assert!(rendered.contains("stats = function()"));
+    }
+
+    #[test]
+    fn debug_loader_adds_assertions_and_logging_without_pcall() {
+        let id = b"event-plugin".plugin_id().as_str();
+        let event2pkgid = BTreeMap::from([("BufReadPre".parse::<Autocmd>().unwrap(), vec![id])]);
+        let render = |debug_loader: bool| {
+            let init = CustomPackaddTemplate {
+                debug_loader,
+                pkgid2scripts: Vec::new(),
+                startup_plugins: Vec::new(),
+                source2pkgid: Vec::new(),
+                pkgid2names: Vec::new(),
+            }
+            .render_once()
+            .unwrap();
+            let on_event = OnEventTemplate {
+                event2pkgid: &event2pkgid,
+                debug_loader,
+            }
+            .render_once()
+            .unwrap();
+            let on_ft = OnFtRuntimeTemplate { debug_loader }.render_once().unwrap();
+            (init, on_event, on_ft)
+        };
+
+        let (init, on_event, on_ft) = render(false);
+        assert!(!init.contains("_rsplug.log"));
+        assert!(!init.contains("assert("));
+        assert!(on_event.contains("pcall(doautocmd, { group = a.group })"));
+        assert!(on_ft.contains("pcall(vim.api.nvim_exec_autocmds"));
+
+        let (init, on_event, on_ft) = render(true);
+        assert!(init.contains("vim.fn.stdpath('log') .. '/_rsplug.log'"));
+        assert!(init.contains("vim.uv.fs_open(log_path, 'a', 420)"));
+        assert!(init.contains("assert(on_runtimepath(id)"));
+        assert!(init.contains("\tlog = log,\n"));
+        assert!(!on_event.contains("pcall(doautocmd"));
+        assert!(on_event.contains("doautocmd { group = a.group }"));
+        assert!(!on_ft.contains("pcall(vim.api.nvim_exec_autocmds"));
+    }
+
     #[test]
     fn lua_start_template_wraps_scripts_in_order() {
         let rendered = LuaStartPluginTemplate {
@@ -936,7 +1805,7 @@ mod runtime_hot_paths {
     }
 
     /// ダミープラグイン1件。`files` は snapshot root からの相対パスと内容。
-    /// 各プラグインは `merge_enabled = false` で独立パッケージになる。
+    /// 各プラグインは `merge = "never"` で独立パッケージになる。
     /// `lazy` には複数トリガを指定可能（on_event と on_ft の併用など）。
     pub(super) struct FakePlugin {
         pub tag: &'static str,
@@ -998,6 +1867,7 @@ mod runtime_hot_paths {
                 path: Arc::from(snap.clone()),
                 inventory: None,
                 handle: None,
+                symlink: false,
             });
             let mut files: BTreeMap<PathBuf, FileItem> = BTreeMap::new();
             for (rel, data) in &p.files {
@@ -1022,7 +1892,8 @@ mod runtime_hot_paths {
                 files: HowToPlaceFiles::CopyEachFile(files),
                 script: SetupScript::default(),
                 order,
-                merge_enabled: false,
+                merge_policy: Some(MergePolicy::Never),
+                target: PackTarget::User,
                 is_lazy_registration: false,
                 dotgit: false,
             };
@@ -1306,6 +2177,56 @@ mod runtime_hot_paths {
         run_scenario(&pack, "map_special_key_replay", &["sk_a", "sk_b"]);
     }
 
+    /// R5: 遅延マッピングの replay が `2"azL`・`"bd2zK`・`v3zV` のカウント・レジスタ・
+    /// オペレータ・Visual モードを本物のマッピングへ引き継ぎ、マクロに lhs を二重記録しない。
+    #[tokio::test]
+    async fn r5_map_replay_keeps_count_register_operator_and_visual() {
+        let pack = build_pack(vec![
+            FakePlugin {
+                tag: "pend_n",
+                files: vec![(
+                    "plugin/init.lua",
+                    b"vim.g.pend_n = true\n\
+                      vim.keymap.set('n', 'zL', function() vim.g.pending_n = { vim.v.count, vim.v.register } end)\n",
+                )],
+                lazy: vec![FakeLazy::Map {
+                    mode: Some('n'),
+                    pattern: "zL",
+                }],
+            },
+            FakePlugin {
+                tag: "pend_o",
+                files: vec![(
+                    "plugin/init.lua",
+                    b"vim.g.pend_o = true\nvim.keymap.set('o', 'zK', 'iw')\n",
+                )],
+                lazy: vec![FakeLazy::Map {
+                    mode: Some('o'),
+                    pattern: "zK",
+                }],
+            },
+            FakePlugin {
+                tag: "pend_x",
+                files: vec![(
+                    "plugin/init.lua",
+                    b"vim.g.pend_x = true\n\
+                      vim.keymap.set('x', 'zV', function() vim.g.pending_x = { vim.api.nvim_get_mode().mode, vim.v.count } end)\n",
+                )],
+                lazy: vec![FakeLazy::Map {
+                    mode: Some('x'),
+                    pattern: "zV",
+                }],
+            },
+        ])
+        .await
+        .expect("build_pack");
+        run_scenario(
+            &pack,
+            "map_replay_keeps_pending_state",
+            &["pend_n", "pend_o", "pend_x"],
+        );
+    }
+
     /// Validation gate: 10,000 件の無関係 require でも pending/進行中状態は成長しない。
     #[tokio::test]
     async fn val_lua_10k_unrelated_requires_no_state_growth() {
@@ -1727,6 +2648,30 @@ mod runtime_hot_paths {
         );
     }
 
+    /// `_rsplug.reset()` が augroup と stub を取り除き、init.lua を読み直すとトリガーが
+    /// 重複せずに張り直される。
+    #[tokio::test]
+    async fn reset_allows_the_loader_to_be_sourced_again() {
+        let pack = build_pack(vec![
+            FakePlugin {
+                tag: "reset_ev",
+                files: vec![("plugin/init.lua", b"vim.g.reset_ev = true")],
+                lazy: vec![FakeLazy::Event("RsResetEv")],
+            },
+            FakePlugin {
+                tag: "reset_cmd",
+                files: vec![(
+                    "plugin/init.lua",
+                    b"vim.api.nvim_create_user_command('RsResetCmd', function() vim.g.reset_cmd = true end, {})",
+                )],
+                lazy: vec![FakeLazy::Cmd("RsResetCmd")],
+            },
+        ])
+        .await
+        .expect("build_pack");
+        run_scenario(&pack, "reset_then_resource", &["reset_ev", "reset_cmd"]);
+    }
+
     /// on_map で同一パターンに2プラグイン。キー1回で両方読み込まれる。
     #[tokio::test]
     async fn r0_on_map_loads_duplicate_pattern_plugins() {
@@ -8,9 +8,11 @@ use std::{
     sync::Arc,
 };
 
+use dag::edit_distance;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use sailfish::runtime::Render;
+use serde::Deserialize;
 use serde_with::DeserializeFromStr;
 
 /// Startプラグインとするか、Optプラグインとするか
@@ -28,6 +30,14 @@ impl LazyType {
     pub fn is_start(&self) -> bool {
         matches!(self, LazyType::Start)
     }
+
+    /// 人間向けの trigger 一覧（`start` や `on_event:BufReadPre` など）。
+    pub fn describe(&self) -> Vec<String> {
+        match self {
+            LazyType::Start => vec!["start".to_string()],
+            LazyType::Opt(events) => events.iter().flat_map(LoadEvent::describe).collect(),
+        }
+    }
 }
 
 impl PartialOrd for LazyType {
@@ -70,6 +80,14 @@ impl From<LazyType> for Cow<'_, LazyType> {
     }
 }
 
+/// 依存先の `LazyType` に依存元のものを合わせる（`Plugin::new` の集約）。依存先が依存元より
+/// 後に読まれることがないよう、読み込みの早い側に寄せる:
+///
+/// - `Start & x = x & Start = Start`（起動時に読まれる依存元があれば依存先も起動時に読む。
+///   依存先の遅延トリガは効かなくなる）
+/// - `Opt(a) & Opt(b) = Opt(a ∪ b)`（どちらのトリガでも読む）
+///
+/// 可換・結合的・冪等なので、依存元を合わせる順序によらず結果は同じになる。
 impl<'a, Rhs: Into<Cow<'a, LazyType>>> BitAndAssign<Rhs> for LazyType {
     fn bitand_assign(&mut self, rhs: Rhs) {
         let rhs: Cow<'a, LazyType> = rhs.into();
@@ -118,19 +136,209 @@ pub enum LoadEvent {
     OnMap(super::KeyPattern),
 }
 
+impl LoadEvent {
+    /// 設定フィールド名を添えた表示。空の `on_map` は何も返さない。
+    fn describe(&self) -> Vec<String> {
+        match self {
+            LoadEvent::Autocmd(event) => vec![format!("on_event:{event}")],
+            LoadEvent::UserCmd(cmd) => vec![format!("on_cmd:{cmd}")],
+            LoadEvent::FileType(ft) => vec![format!("on_ft:{ft}")],
+            LoadEvent::VimFunc(func) => vec![format!("on_func:{func}")],
+            LoadEvent::OnSource(name) => vec![format!("on_source:{name}")],
+            LoadEvent::LuaModule(module) => vec![format!("require:{module}")],
+            LoadEvent::OnMap(pattern) => pattern
+                .0
+                .iter()
+                .flat_map(|(mode, lhs)| lhs.iter().map(move |lhs| format!("on_map:{mode}:{lhs}")))
+                .collect(),
+        }
+    }
+}
+
+/// Neovim の自動コマンドイベント（`:help autocmd-events`）。
+/// ここに無い名前は `User` イベントのパターンとして扱われる。
+const NVIM_EVENTS: &[&str] = &[
+    "BufAdd",
+    "BufDelete",
+    "BufEnter",
+    "BufFilePost",
+    "BufFilePre",
+    "BufHidden",
+    "BufLeave",
+    "BufModifiedSet",
+    "BufNew",
+    "BufNewFile",
+    "BufRead",
+    "BufReadCmd",
+    "BufReadPost",
+    "BufReadPre",
+    "BufUnload",
+    "BufWinEnter",
+    "BufWinLeave",
+    "BufWipeout",
+    "BufWrite",
+    "BufWriteCmd",
+    "BufWritePost",
+    "BufWritePre",
+    "ChanInfo",
+    "ChanOpen",
+    "CmdUndefined",
+    "CmdlineChanged",
+    "CmdlineEnter",
+    "CmdlineLeave",
+    "CmdwinEnter",
+    "CmdwinLeave",
+    "ColorScheme",
+    "ColorSchemePre",
+    "CompleteChanged",
+    "CompleteDone",
+    "CompleteDonePre",
+    "CursorHold",
+    "CursorHoldI",
+    "CursorMoved",
+    "CursorMovedC",
+    "CursorMovedI",
+    "DiagnosticChanged",
+    "DiffUpdated",
+    "DirChanged",
+    "DirChangedPre",
+    "ExitPre",
+    "FileAppendCmd",
+    "FileAppendPost",
+    "FileAppendPre",
+    "FileChangedRO",
+    "FileChangedShell",
+    "FileChangedShellPost",
+    "FileReadCmd",
+    "FileReadPost",
+    "FileReadPre",
+    "FileType",
+    "FileWriteCmd",
+    "FileWritePost",
+    "FileWritePre",
+    "FilterReadPost",
+    "FilterReadPre",
+    "FilterWritePost",
+    "FilterWritePre",
+    "FocusGained",
+    "FocusLost",
+    "FuncUndefined",
+    "InsertChange",
+    "InsertCharPre",
+    "InsertEnter",
+    "InsertLeave",
+    "InsertLeavePre",
+    "LspAttach",
+    "LspDetach",
+    "LspNotify",
+    "LspProgress",
+    "LspRequest",
+    "LspTokenUpdate",
+    "MenuPopup",
+    "ModeChanged",
+    "OptionSet",
+    "QuickFixCmdPost",
+    "QuickFixCmdPre",
+    "QuitPre",
+    "RecordingEnter",
+    "RecordingLeave",
+    "RemoteReply",
+    "SafeState",
+    "SearchWrapped",
+    "SessionLoadPost",
+    "SessionWritePost",
+    "ShellCmdPost",
+    "ShellFilterPost",
+    "Signal",
+    "SourceCmd",
+    "SourcePost",
+    "SourcePre",
+    "SpellFileMissing",
+    "StdinReadPost",
+    "StdinReadPre",
+    "SwapExists",
+    "Syntax",
+    "TabClosed",
+    "TabEnter",
+    "TabLeave",
+    "TabNew",
+    "TabNewEntered",
+    "TermClose",
+    "TermEnter",
+    "TermLeave",
+    "TermOpen",
+    "TermRequest",
+    "TermResponse",
+    "TextChanged",
+    "TextChangedI",
+    "TextChangedP",
+    "TextChangedT",
+    "TextYankPost",
+    "UIEnter",
+    "UILeave",
+    "User",
+    "VimEnter",
+    "VimLeave",
+    "VimLeavePre",
+    "VimResized",
+    "VimResume",
+    "VimSuspend",
+    "WinClosed",
+    "WinEnter",
+    "WinLeave",
+    "WinNew",
+    "WinResized",
+    "WinScrolled",
+];
+
+/// 明示的に `User` イベントとして扱う接頭辞（`User:MyEvent`）。
+/// Neovim のイベント名に似た独自イベントを typo と誤認させないための逃げ道。
+const USER_EVENT_PREFIX: &str = "User:";
+
+/// `name` が Neovim のイベント名の書き損じに見えるなら、その正しい綴りを返す。
+fn misspelled_event(name: &str) -> Option<&'static str> {
+    if NVIM_EVENTS.contains(&name) {
+        return None;
+    }
+    let tolerance = if name.chars().count() < 6 { 1 } else { 2 };
+    NVIM_EVENTS
+        .iter()
+        .map(|event| (edit_distance(name, event), *event))
+        .filter(|(distance, _)| *distance <= tolerance)
+        .min()
+        .map(|(_, event)| event)
+}
+
 /// Vimの自動コマンドの文字列を表す型。
 #[derive(Hash, Clone, PartialOrd, Ord, PartialEq, Eq, DeserializeFromStr, Debug)]
 pub struct Autocmd(Arc<String>);
 
 impl FromStr for Autocmd {
-    type Err = &'static str;
+    type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         static AUTOCMD_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[^\p{C}\p{Z}*]+$").unwrap());
-        if AUTOCMD_REGEX.is_match(s) {
-            Ok(Autocmd(Arc::new(s.to_string())))
-        } else {
-            Err("Autocmd must not contain control characters, spaces, or asterisks")
+        let (name, explicit_user) = match s.strip_prefix(USER_EVENT_PREFIX) {
+            Some(name) => (name, true),
+            None => (s, false),
+        };
+        if !AUTOCMD_REGEX.is_match(name) {
+            return Err(
+                "Autocmd must not contain control characters, spaces, or asterisks".to_string(),
+            );
+        }
+        if explicit_user {
+            if NVIM_EVENTS.contains(&name) {
+                return Err(format!(
+                    "`{s}`: {name} is a Neovim event; write `{name}` to use it"
+                ));
+            }
+        } else if let Some(event) = misspelled_event(name) {
+            return Err(format!(
+                "unknown autocmd event `{name}`; did you mean `{event}`? \
+                 (write `{USER_EVENT_PREFIX}{name}` for a custom User event)"
+            ));
         }
+        Ok(Autocmd(Arc::new(name.to_string())))
     }
 }
 
@@ -146,9 +354,79 @@ impl fmt::Display for Autocmd {
     }
 }
 
-/// Vimのユーザーコマンドの文字列を表す型。
-#[derive(Hash, Clone, PartialOrd, Ord, PartialEq, Eq, DeserializeFromStr, Debug)]
-pub struct UserCmd(Arc<String>);
+/// Vimのユーザーコマンドを表す型。
+/// `complete` は読み込み前のスタブに付ける補完（`:command-complete` の値）。
+#[derive(Hash, Clone, PartialOrd, Ord, PartialEq, Eq, Deserialize, Debug)]
+#[serde(try_from = "UserCmdDeserializer")]
+pub struct UserCmd {
+    name: Arc<String>,
+    complete: Option<Arc<String>>,
+}
+
+impl UserCmd {
+    /// コマンド名だけの `UserCmd` と、補完の指定に分ける。
+    pub fn split_complete(self) -> (UserCmd, Option<Arc<String>>) {
+        let UserCmd { name, complete } = self;
+        (
+            UserCmd {
+                name,
+                complete: None,
+            },
+            complete,
+        )
+    }
+}
+
+/// `"Name"` または `{ name = "Name", complete = "..." }`。
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum UserCmdDeserializer {
+    Name(String),
+    Spec(UserCmdSpec),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UserCmdSpec {
+    name: String,
+    #[serde(default)]
+    complete: Option<String>,
+}
+
+impl TryFrom<UserCmdDeserializer> for UserCmd {
+    type Error = &'static str;
+    fn try_from(value: UserCmdDeserializer) -> Result<Self, Self::Error> {
+        match value {
+            UserCmdDeserializer::Name(name) => name.parse(),
+            UserCmdDeserializer::Spec(UserCmdSpec { name, complete }) => {
+                let cmd: UserCmd = name.parse()?;
+                let Some(complete) = complete else {
+                    return Ok(cmd);
+                };
+                if complete.is_empty()
+                    || complete
+                        .chars()
+                        .any(|c| c.is_whitespace() || c.is_control())
+                {
+                    return Err(
+                        "`complete` must be a non-empty `:command-complete` value without spaces",
+                    );
+                }
+                if let Some((kind, func)) = complete.split_once(',')
+                    && (!matches!(kind, "custom" | "customlist") || func.is_empty())
+                {
+                    return Err(
+                        "only `custom,{func}` and `customlist,{func}` take a completion function",
+                    );
+                }
+                Ok(UserCmd {
+                    complete: Some(Arc::new(complete)),
+                    ..cmd
+                })
+            }
+        }
+    }
+}
 
 impl FromStr for UserCmd {
     type Err = &'static str;
@@ -162,7 +440,10 @@ impl FromStr for UserCmd {
         }
 
         if chars.all(|c| c.is_ascii_alphabetic()) {
-            Ok(UserCmd(Arc::new(s.to_string())))
+            Ok(UserCmd {
+                name: Arc::new(s.to_string()),
+                complete: None,
+            })
         } else {
             Err("UserCmd must consist of ascii alphabetic letters only")
         }
@@ -171,13 +452,13 @@ impl FromStr for UserCmd {
 
 impl Render for UserCmd {
     fn render(&self, b: &mut sailfish::runtime::Buffer) -> Result<(), sailfish::RenderError> {
-        self.0.render(b)
+        self.name.render(b)
     }
 }
 
 impl fmt::Display for UserCmd {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
+        self.name.fmt(f)
     }
 }
 
@@ -222,19 +503,28 @@ impl fmt::Display for VimFunc {
 }
 
 /// Vimのファイルタイプの文字列を表す型。
+/// 単一の filetype のほか、複合 filetype（`markdown.mdx`）やパターン（`typescript*`）も表す。
 #[derive(Hash, Clone, PartialOrd, Ord, PartialEq, Eq, DeserializeFromStr, Debug)]
 pub struct FileType(Arc<String>);
 
+impl FileType {
+    /// `ftplugin/<ft>/` では拾えず、`FileType` 自動コマンドのパターンで待つ必要があるか。
+    /// Vim は複合 filetype を `.` で分けて各部分の ftplugin を読むため、複合 filetype もこれに含む。
+    pub fn is_pattern(&self) -> bool {
+        self.0.contains(['.', '*', '?', '['])
+    }
+}
+
 impl FromStr for FileType {
     type Err = &'static str;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.chars()
-            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
-        {
+        if s.chars().all(|c| {
+            c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '*' | '?' | '[' | ']')
+        }) {
             Ok(FileType(Arc::new(s.to_string())))
         } else {
             Err(
-                "FileType must consist of ascii alphanumeric characters, underscores, hyphens, or dots",
+                "FileType must consist of ascii alphanumeric characters, underscores, hyphens, dots, or the wildcards `*`, `?`, and `[...]`",
             )
         }
     }
@@ -58,6 +58,9 @@ pub(super) struct SnapshotManifest {
     doc_files: Vec<PathBuf>,
     #[serde(skip)]
     ftplugin_files: Vec<PathBuf>,
+    /// snapshot 直下のライセンスファイル（`LICENSE`・`COPYING` 等）。
+    #[serde(skip)]
+    license_files: Vec<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -71,6 +74,9 @@ pub(super) struct ManifestEntry {
     /// merge-time stat while retaining the symlink itself as a leaf entry.
     #[serde(default)]
     pub(super) followed_kind: Option<ManifestKind>,
+    /// 通常ファイルのバイト数（`max_total_size` の判定用）。サイズ記録前の manifest では `None`。
+    #[serde(default)]
+    pub(super) size: Option<u64>,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -90,6 +96,7 @@ impl SnapshotManifest {
         self.lua_roots.clear();
         self.doc_files.clear();
         self.ftplugin_files.clear();
+        self.license_files.clear();
         let mut lua_seen = HashSet::new();
         let mut last_top_level: Option<PathBuf> = None;
         for entry in &self.entries {
@@ -114,6 +121,13 @@ impl SnapshotManifest {
             if entry.path.starts_with("ftplugin/") && entry.kind != ManifestKind::Dir {
                 self.ftplugin_files.push(entry.path.clone());
             }
+            if entry.path.parent() == Some(Path::new(""))
+                && (entry.kind == ManifestKind::File
+                    || entry.followed_kind == Some(ManifestKind::File))
+                && entry.path.to_str().is_some_and(is_license_file_name)
+            {
+                self.license_files.push(entry.path.clone());
+            }
             if let Ok(relative) = entry.path.strip_prefix("lua")
                 && let Some(first) = relative.components().next()
             {
@@ -141,6 +155,7 @@ impl SnapshotManifest {
         self.lua_roots.sort();
         self.doc_files.sort();
         self.ftplugin_files.sort();
+        self.license_files.sort();
 
         // Materialize the portion of an in-snapshot directory target visible
         // through each symlink. External targets remain opaque, which is the
@@ -259,6 +274,11 @@ impl SnapshotManifest {
                     })?
                     .to_path_buf();
                 let file_type = entry.file_type().await?;
+                let size = if file_type.is_file() {
+                    Some(entry.metadata().await?.len())
+                } else {
+                    None
+                };
                 let (kind, symlink_target, followed_kind) = if file_type.is_symlink() {
                     let target = tokio::fs::read_link(&path).await.ok();
                     let followed_kind = tokio::fs::metadata(&path).await.ok().map(|metadata| {
@@ -279,6 +299,7 @@ impl SnapshotManifest {
                     kind,
                     symlink_target,
                     followed_kind,
+                    size,
                 });
                 if kind == ManifestKind::Dir {
                     stack.push(path);
@@ -297,6 +318,7 @@ impl SnapshotManifest {
             lua_roots: Vec::new(),
             doc_files: Vec::new(),
             ftplugin_files: Vec::new(),
+            license_files: Vec::new(),
         };
         if include_content_digest {
             let mut hasher = Xxh3::new();
@@ -342,14 +364,16 @@ impl SnapshotManifest {
         Ok(())
     }
 
-    /// `root` を walk して manifest を構築し、`snapshot_root` に書き込む（best-effort cache）。
+    /// `snapshot_root` を walk して manifest を構築し、書き込んで返す。書き込みは
+    /// best-effort cache なので失敗しても構築した manifest を返す。
     pub(super) async fn build_and_write(
         snapshot_root: &Path,
         dotgit: bool,
         build_success_file: &str,
-    ) -> std::io::Result<()> {
+    ) -> std::io::Result<Self> {
         let manifest = Self::build(snapshot_root, dotgit, build_success_file).await?;
-        manifest.write(snapshot_root).await
+        let _ = manifest.write(snapshot_root).await;
+        Ok(manifest)
     }
 
     /// `rel` の種別。通常の entries に無ければ symlink overlay も検索する。
@@ -405,6 +429,20 @@ impl SnapshotManifest {
     pub(super) fn ftplugin_files(&self) -> &[PathBuf] {
         &self.ftplugin_files
     }
+
+    pub(super) fn license_files(&self) -> &[PathBuf] {
+        &self.license_files
+    }
+}
+
+/// ライセンス本文を置く慣習的なファイル名か（`LICENSE`・`LICENSE-MIT`・`COPYING.txt`・
+/// `UNLICENSE` 等。大文字小文字は区別しない）。
+pub(super) fn is_license_file_name(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    let stem = name
+        .split_once(['.', '-', '_'])
+        .map_or(name.as_str(), |(stem, _)| stem);
+    matches!(stem, "license" | "licence" | "copying" | "unlicense")
 }
 
 fn normalize_relative_path(parent: &Path, target: &Path) -> Option<PathBuf> {
@@ -561,12 +599,14 @@ mod tests {
                     kind: ManifestKind::Dir,
                     symlink_target: None,
                     followed_kind: None,
+                    size: None,
                 },
                 ManifestEntry {
                     path: PathBuf::from("lua/init.lua"),
                     kind: ManifestKind::File,
                     symlink_target: None,
                     followed_kind: None,
+                    size: None,
                 },
             ],
             content_digest: None,
@@ -577,6 +617,7 @@ mod tests {
             lua_roots: Vec::new(),
             doc_files: Vec::new(),
             ftplugin_files: Vec::new(),
+            license_files: Vec::new(),
         };
         manifest.reindex();
         assert_eq!(
@@ -602,18 +643,21 @@ mod tests {
                     kind: ManifestKind::Symlink,
                     symlink_target: Some(PathBuf::from("target")),
                     followed_kind: Some(ManifestKind::Dir),
+                    size: None,
                 },
                 ManifestEntry {
                     path: PathBuf::from("target"),
                     kind: ManifestKind::Dir,
                     symlink_target: None,
                     followed_kind: None,
+                    size: None,
                 },
                 ManifestEntry {
                     path: PathBuf::from("target/init.lua"),
                     kind: ManifestKind::File,
                     symlink_target: None,
                     followed_kind: None,
+                    size: None,
                 },
             ],
             content_digest: None,
@@ -624,6 +668,7 @@ mod tests {
             lua_roots: Vec::new(),
             doc_files: Vec::new(),
             ftplugin_files: Vec::new(),
+            license_files: Vec::new(),
         };
         manifest.reindex();
         assert_eq!(
@@ -645,6 +690,7 @@ mod tests {
                 kind: ManifestKind::File,
                 symlink_target: None,
                 followed_kind: None,
+                size: None,
             }],
             content_digest: None,
             children: HashMap::new(),
@@ -654,12 +700,43 @@ mod tests {
             lua_roots: Vec::new(),
             doc_files: Vec::new(),
             ftplugin_files: Vec::new(),
+            license_files: Vec::new(),
         };
         assert!(!manifest.validate());
         manifest.schema = MANIFEST_SCHEMA;
         assert!(!manifest.validate());
     }
 
+    #[tokio::test]
+    async fn reindex_derives_top_level_license_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        for file in ["LICENSE", "COPYING.txt", "license-mit.md", "README.md"] {
+            tokio::fs::write(root.join(file), b"text").await.unwrap();
+        }
+        tokio::fs::create_dir_all(root.join("doc/LICENSE"))
+            .await
+            .unwrap();
+        tokio::fs::write(root.join("doc/COPYING"), b"text")
+            .await
+            .unwrap();
+
+        let manifest = SnapshotManifest::build(root, false, ".rsplug_build_success")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            manifest.license_files(),
+            [
+                PathBuf::from("COPYING.txt"),
+                PathBuf::from("LICENSE"),
+                PathBuf::from("license-mit.md")
+            ]
+        );
+        assert!(is_license_file_name("UNLICENSE"));
+        assert!(!is_license_file_name("licenses.json"));
+    }
+
     #[tokio::test]
     async fn reindex_derives_lua_file_stems_as_module_roots() {
         let tmp = tempfile::tempdir().unwrap();
@@ -2,6 +2,12 @@
 //!
 //! Ordering, compatibility probing, and fixed-point group construction live
 //! here so publication/copy code only consumes a deterministic plan.
+//!
+//! Plugins are first partitioned by [`MergeClass`]: `LoadedPlugin + LoadedPlugin`
+//! always rejects a pair whose classes differ, and a merged group keeps its
+//! class. The first-fit fixed point therefore runs inside each class only,
+//! which yields exactly the plan of a single global pass while skipping every
+//! cross-class probe (`merge = "never"` plugins are never probed at all).
 
 use super::*;
 
@@ -9,15 +15,31 @@ pub(super) struct MergePlanner;
 
 impl MergePlanner {
     pub(super) fn plan(plugs: &mut BinaryHeap<LoadedPlugin>) {
-        let mut items = Vec::with_capacity(plugs.len());
+        let mut classes = BTreeMap::<MergeClass, Vec<MergeEntry>>::new();
+        let mut planned = Vec::with_capacity(plugs.len());
         while let Some(plug) = plugs.pop() {
-            items.push(MergeEntry {
+            let entry = MergeEntry {
                 key: MergeSortKey::new(&plug),
                 plugin: plug,
-            });
+            };
+            match MergeClass::of(&entry.plugin) {
+                Some(class) => classes.entry(class).or_default().push(entry),
+                None => planned.push(entry),
+            }
+        }
+        for (_, mut items) in classes {
+            items.sort_by(|left, right| left.key.cmp(&right.key));
+            planned.extend(Self::plan_class(items));
         }
-        items.sort_by(|left, right| left.key.cmp(&right.key));
+        planned.sort_by(|left, right| left.key.cmp(&right.key));
+        plugs.extend(planned.into_iter().map(|entry| entry.plugin));
+    }
 
+    /// 1 クラス内の first-fit fixed point。`items` はキー順に整列済み。
+    fn plan_class(items: Vec<MergeEntry>) -> Vec<MergeEntry> {
+        if items.len() < 2 {
+            return items;
+        }
         let mut groups: Vec<Option<MergeEntry>> = Vec::with_capacity(items.len());
         let mut ordered = BTreeSet::<(MergeSortKey, usize)>::new();
         for item in items {
@@ -68,12 +90,48 @@ impl MergePlanner {
             ordered.insert((pending.key.clone(), index));
             groups.push(Some(pending));
         }
-        plugs.extend(
-            ordered
-                .into_iter()
-                .filter_map(|(_, index)| groups[index].take())
-                .map(|entry| entry.plugin),
-        );
+        ordered
+            .into_iter()
+            .filter_map(|(_, index)| groups[index].take())
+            .collect()
+    }
+}
+
+/// 併合し得る plugin の組を表す分類キー（`LoadedPlugin::merge_compatible` の拒否条件そのもの）。
+#[derive(Eq, PartialEq, Ord, PartialOrd)]
+struct MergeClass {
+    /// `aggressive` では start/opt の別だけが効くので、代表値（`Start` / 空の `Opt`）に正規化する。
+    lazy_type: LazyType,
+    is_lazy_registration: bool,
+    /// LazyRegistration はポリシーを見ないので `None`。
+    policy: Option<MergePolicy>,
+    target: PackTarget,
+}
+
+impl MergeClass {
+    /// `never` のユーザプラグインは何とも併合されないので `None`。
+    fn of(plugin: &LoadedPlugin) -> Option<Self> {
+        if plugin.is_lazy_registration {
+            return Some(Self {
+                lazy_type: plugin.lazy_type.clone(),
+                is_lazy_registration: true,
+                policy: None,
+                target: plugin.target,
+            });
+        }
+        let policy = plugin.effective_merge_policy();
+        let lazy_type = match policy {
+            MergePolicy::Never => return None,
+            MergePolicy::SameLazyType => plugin.lazy_type.clone(),
+            MergePolicy::Aggressive if plugin.lazy_type.is_start() => LazyType::Start,
+            MergePolicy::Aggressive => LazyType::Opt(BTreeSet::new()),
+        };
+        Some(Self {
+            lazy_type,
+            is_lazy_registration: false,
+            policy: Some(policy),
+            target: plugin.target,
+        })
     }
 }
 
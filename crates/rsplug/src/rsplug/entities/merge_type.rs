@@ -7,3 +7,62 @@ pub enum MergeType {
     // /// プログラム的にマージする
     // Merge(Arc<dyn Fn(Vec<u8>, Vec<u8>) -> Result<Vec<u8>, MergeType>>),
 }
+
+/// ユーザプラグインをどこまで併合するか（TOML の `merge` / CLI の `--merge`）。
+#[derive(
+    Debug,
+    Default,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Clone,
+    Copy,
+    serde::Deserialize,
+    serde::Serialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergePolicy {
+    /// 併合しない。常に単独のパッケージとして配置する。
+    Never,
+    /// lazy_type が完全一致するものだけ併合する（従来の挙動）。
+    #[default]
+    SameLazyType,
+    /// start 同士・opt 同士なら trigger が異なっても併合し、trigger を和集合にする。
+    /// どれか 1 つの trigger で併合先の全プラグインが読み込まれる。
+    Aggressive,
+}
+
+/// パッケージを置く packpath（TOML の `target`）。`system` は `--packpath system=<DIR>` に置く。
+#[derive(
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Clone,
+    Copy,
+    serde::Deserialize,
+    serde::Serialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum PackTarget {
+    /// ユーザの packpath（既定では `~/.cache/rsplug`）。
+    #[default]
+    User,
+    /// 共有の system packpath。
+    System,
+}
+
+impl std::hash::Hash for PackTarget {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        // `user` は何も足さず、`target` を導入する前と同じ plugin_id を保つ。
+        if *self == PackTarget::System {
+            state.write_u8(1);
+        }
+    }
+}
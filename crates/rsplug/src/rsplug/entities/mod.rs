@@ -1,3 +1,4 @@
+pub mod compat;
 pub mod config;
 pub mod config_walker;
 pub mod error;
@@ -8,11 +9,13 @@ pub mod merge_type;
 pub mod pack_plan;
 pub mod plugin;
 pub mod plugin_id;
+pub mod template_override;
 
 mod lazy_registration;
 
 use super::util;
 
+use compat::*;
 use config::*;
 use error::*;
 use lazy_registration::*;
@@ -22,3 +25,4 @@ use merge_type::*;
 use pack_plan::*;
 use plugin::*;
 use plugin_id::*;
+use template_override::*;
@@ -0,0 +1,140 @@
+//! Post-install check that every `lua/` module resolves to one content.
+//!
+//! `require` loads the first `lua/<module>.lua` or `lua/<module>/init.lua` on
+//! 'runtimepath', so when two packages ship the same module with different
+//! content, one of them is shadowed without any error. After a generation is
+//! published, the `lua/` trees of its user packages are walked and each module
+//! provided by more than one package with differing content is reported.
+
+use super::*;
+
+/// 2 つ以上のパッケージが異なる内容で提供する Lua モジュール。
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct ModuleCollision {
+    /// `require` に渡す名前（例: `foo.bar`）。
+    pub(super) module: String,
+    /// 提供するパッケージごとの設定上の名前（併合されたものは `, ` で結合）。
+    pub(super) plugins: Vec<String>,
+}
+
+/// `lua/` からの相対パスをモジュール名にする。`.lua` 以外は `None`。
+fn module_name(relative: &Path) -> Option<String> {
+    let mut parts = relative
+        .iter()
+        .map(|part| part.to_str())
+        .collect::<Option<Vec<_>>>()?;
+    let file = parts.pop()?.strip_suffix(".lua")?;
+    // `lua/foo/init.lua` は `foo`。`lua/init.lua` だけは `init` のまま。
+    if file != "init" || parts.is_empty() {
+        parts.push(file);
+    }
+    Some(parts.join("."))
+}
+
+/// `packages`（パッケージ id → 設定上の名前）の `opt/<id>/lua/` を走査し、異なる内容で
+/// 重複するモジュールを名前順に返す。
+pub(super) async fn module_collisions<'a>(
+    opt: &Path,
+    packages: impl IntoIterator<Item = (&'a str, &'a BTreeSet<String>)>,
+) -> io::Result<Vec<ModuleCollision>> {
+    // モジュール名 → [(表示名, そのパッケージで require が読むファイル)]
+    let mut providers: BTreeMap<String, Vec<(String, PathBuf)>> = BTreeMap::new();
+    for (id, names) in packages {
+        let root = opt.join(id).join("lua");
+        let mut modules: BTreeMap<String, PathBuf> = BTreeMap::new();
+        for file in list_files(&root).await? {
+            let Some(module) = file.strip_prefix(&root).ok().and_then(module_name) else {
+                continue;
+            };
+            // 同じパッケージでは `foo.lua` が `foo/init.lua` より先に見つかる。
+            let is_init = file.file_stem().is_some_and(|stem| stem == "init");
+            match modules.entry(module) {
+                btree_map::Entry::Vacant(entry) => {
+                    entry.insert(file);
+                }
+                btree_map::Entry::Occupied(mut entry) if !is_init => {
+                    entry.insert(file);
+                }
+                btree_map::Entry::Occupied(_) => {}
+            }
+        }
+        let label = if names.is_empty() {
+            id.to_string()
+        } else {
+            names
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        for (module, file) in modules {
+            providers
+                .entry(module)
+                .or_default()
+                .push((label.clone(), file));
+        }
+    }
+    let mut collisions = Vec::new();
+    for (module, provided) in providers {
+        if provided.len() < 2 {
+            continue;
+        }
+        let mut digests = HashSet::new();
+        for (_, file) in &provided {
+            digests.insert(util::hash::digest_hash(&tokio::fs::read(file).await?));
+        }
+        if digests.len() > 1 {
+            collisions.push(ModuleCollision {
+                module,
+                plugins: provided.into_iter().map(|(label, _)| label).collect(),
+            });
+        }
+    }
+    Ok(collisions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_names_follow_require() {
+        assert_eq!(module_name(Path::new("foo.lua")).as_deref(), Some("foo"));
+        assert_eq!(
+            module_name(Path::new("foo/bar/init.lua")).as_deref(),
+            Some("foo.bar")
+        );
+        assert_eq!(module_name(Path::new("init.lua")).as_deref(), Some("init"));
+        assert_eq!(module_name(Path::new("foo/README.md")), None);
+    }
+
+    #[tokio::test]
+    async fn only_modules_with_different_content_collide() {
+        let tmp = tempfile::tempdir().unwrap();
+        let write = |path: &str, data: &str| {
+            let path = tmp.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, data).unwrap();
+        };
+        write("a/lua/foo.lua", "return 1");
+        write("a/lua/shared.lua", "return 'same'");
+        write("b/lua/foo/init.lua", "return 2");
+        write("b/lua/shared.lua", "return 'same'");
+        write("c/lua/bar.lua", "return 3");
+        let names = |names: &[&str]| names.iter().map(ToString::to_string).collect();
+        let a: BTreeSet<String> = names(&["a.nvim", "merged.nvim"]);
+        let b: BTreeSet<String> = names(&["b.nvim"]);
+        let c = BTreeSet::new();
+
+        let collisions = module_collisions(tmp.path(), [("a", &a), ("b", &b), ("c", &c)])
+            .await
+            .unwrap();
+        assert_eq!(
+            collisions,
+            [ModuleCollision {
+                module: "foo".to_string(),
+                plugins: vec!["a.nvim, merged.nvim".to_string(), "b.nvim".to_string()],
+            }]
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use std::{
     borrow::Cow,
     cmp::Ordering,
-    collections::{BTreeMap, BTreeSet, BinaryHeap},
+    collections::{BTreeMap, BTreeSet, BinaryHeap, btree_map},
     ffi::{OsStr, OsString},
     hash::{Hash, Hasher},
     io,
@@ -25,24 +25,71 @@ use super::*;
 #[path = "merge.rs"]
 mod merge;
 
+#[path = "content_store.rs"]
+mod content_store;
+
+use content_store::ContentStore;
+
+#[path = "provenance.rs"]
+mod provenance;
+
+use provenance::ProvenanceIndex;
+
+#[path = "module_collision.rs"]
+mod module_collision;
+
+#[path = "sparse.rs"]
+mod sparse;
+
+#[path = "post_process.rs"]
+mod post_process;
+
+pub use super::lazy_registration::DuplicateTrigger;
+use module_collision::module_collisions;
+pub use post_process::PostStep;
+use post_process::{PostPipeline, PostRecord, read_post_record};
+pub use provenance::{
+    InstalledRepository, PlannedPackage, find_owners, installed_repositories, installed_snapshots,
+    package_names, user_package_names,
+};
+pub use sparse::materialize;
+use sparse::{SPARSE_STUB_FILE, sparse_stub, stale_stub};
+
 /// Git リポジトリ snapshot の論理 identity。
 ///
 /// **絶対配置パス（cache root や `snapshot_root`）は含めない。** identity は
-/// `repo_cache_dir`(相対)・`head_rev`・`dirty_diff`・`build`・`lua_build` のみで決まり、
-/// `LoadedPlugin::plugin_id()` を通じて `_gen` id を決める。これらを構造体に集約することで、
-/// identity に影響する入力の追加・変更がコンパイラによって検出される。
-#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
+/// `repo_cache_dir`(相対)・`head_rev`・`dirty_diff`・`build`・`lua_build`・`patches` のみで
+/// 決まり、`LoadedPlugin::plugin_id()` を通じて `_gen` id を決める。これらを構造体に集約する
+/// ことで、identity に影響する入力の追加・変更がコンパイラによって検出される。
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub(super) struct RepoSnapshotIdentity {
     /// `repos/` からの相対 repo パス（例: `github.com/owner/repo`）。どの repo かを識別する。
     repo_cache_dir: PathBuf,
     /// HEAD コミットハッシュ
     head_rev: Box<[u8]>,
     /// 作業ツリーに未コミット変更がある場合の差分ハッシュ。クリーンなら None。
+    /// dev checkout では checkout 全体の内容ハッシュ。
     dirty_diff: Option<[u8; 16]>,
     /// TOML設定の build コマンド
     build: Arc<[String]>,
     /// TOML設定の lua_build スクリプト
     lua_build: Option<Arc<str>>,
+    /// TOML設定の patches の内容ハッシュ。パッチが無ければ None。
+    patches: Option<[u8; 16]>,
+}
+
+impl Hash for RepoSnapshotIdentity {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.repo_cache_dir.hash(state);
+        self.head_rev.hash(state);
+        self.dirty_diff.hash(state);
+        self.build.hash(state);
+        self.lua_build.hash(state);
+        // patches 導入前の plugin id を変えないよう、パッチがある時だけ hash に含める。
+        if let Some(patches) = &self.patches {
+            patches.hash(state);
+        }
+    }
 }
 
 impl RepoSnapshotIdentity {
@@ -59,20 +106,30 @@ impl RepoSnapshotIdentity {
             dirty_diff,
             build,
             lua_build,
+            patches: None,
         }
     }
 
+    /// `patches` の内容ハッシュを設定する。snapshot key と plugin id の両方に反映される。
+    pub(super) fn with_patches(mut self, patches: Option<[u8; 16]>) -> Self {
+        self.patches = patches;
+        self
+    }
+
     /// `worktrees/<snapshot_key>` の directory 名を生成する (PLANS §7)。
     ///
     /// **`dirty_diff` は含めない**: key は commit + build/lua_build 入力のみで決まり、
     /// build を実行する前に確定する。これにより「同じ入力の snapshot が既にあれば build を
     /// スキップして再利用」できる。build 成果物の差（dirty）は `RepoSnapshotIdentity`
     /// （ひいては `plugin_id`）に反映されるため、異なる成果物は別 `_gen` id になる。
-    /// `build`・`lua_build` が共に無ければ `<head_rev>` のみ、あれば `<head_rev>__v1_<hash>`。
+    /// `build`・`lua_build`・`patches` が全て無ければ `<head_rev>` のみ、あれば
+    /// `<head_rev>__v1_<hash>`。
     /// `repo_cache_dir` は key に含めない（`worktrees/` は repo ごとに分かれているため暗黙）。
     pub(super) fn snapshot_key(&self) -> String {
+        use crate::rsplug::util::hash::digest_hash_hex_string;
+
         let head_rev = String::from_utf8_lossy(&self.head_rev);
-        if self.build.is_empty() && self.lua_build.is_none() {
+        if self.build.is_empty() && self.lua_build.is_none() && self.patches.is_none() {
             head_rev.into_owned()
         } else {
             let input = SnapshotKeyInput {
@@ -81,11 +138,12 @@ impl RepoSnapshotIdentity {
                 build: &self.build,
                 lua_build: self.lua_build.as_deref(),
             };
-            format!(
-                "{head_rev}__v{}_{}",
-                SNAPSHOT_KEY_SCHEMA,
-                crate::rsplug::util::hash::digest_hash_hex_string(&input)
-            )
+            // patches が無い key は従来どおり（既存 snapshot を再利用できる）。
+            let hash = match &self.patches {
+                Some(patches) => digest_hash_hex_string(&(&input, patches)),
+                None => digest_hash_hex_string(&input),
+            };
+            format!("{head_rev}__v{SNAPSHOT_KEY_SCHEMA}_{hash}")
         }
     }
 }
@@ -119,6 +177,7 @@ impl std::fmt::Debug for RepoSnapshotIdentity {
             .field("repo_cache_dir", &self.repo_cache_dir)
             .field("head_rev", &String::from_utf8_lossy(&self.head_rev))
             .field("dirty_diff", &self.dirty_diff)
+            .field("patches", &self.patches)
             .finish_non_exhaustive()
     }
 }
@@ -185,8 +244,10 @@ pub struct LoadedPlugin {
     pub(super) script: SetupScript,
     /// 設定/DAG後の読み込み順。特に controlled startup の順序維持に使う。
     pub(super) order: usize,
-    /// マージを許可するか（TOMLの `merge` フィールド）
-    pub(super) merge_enabled: bool,
+    /// 併合ポリシー（TOMLの `merge` フィールド）。`None` は `PackPlan` のグローバル設定に従う。
+    pub(super) merge_policy: Option<MergePolicy>,
+    /// 置き場所の packpath（TOML の `target`）。別の packpath のパッケージとは併合しない。
+    pub(super) target: PackTarget,
     /// LazyRegistrationを元に作成されたかどうか
     pub(super) is_lazy_registration: bool,
     /// pack に `.git` を含めるか（git 利用プラグイン用）。`dotgit=true` なら `Plugin::load` が
@@ -215,6 +276,28 @@ impl LoadedPlugin {
             })
     }
 
+    /// 安定名モードでのパッケージ名（`<owner>__<repo>`）。1 つの repo だけから成るユーザプラグインに
+    /// 限り、複数 repo の併合・生成ファイルだけのもの・制御パッケージは `None`（内容ハッシュのまま）。
+    pub(super) fn stable_name(&self) -> Option<String> {
+        if self.is_lazy_registration {
+            return None;
+        }
+        let HowToPlaceFiles::CopyEachFile(files) = &self.files;
+        let mut repos = files.values().filter_map(|item| match &item.identity {
+            FileIdentity::RepoFile(file) => Some(&file.snapshot.repo_cache_dir),
+            FileIdentity::GeneratedFile { .. } => None,
+        });
+        let repo = repos.next()?;
+        if repos.any(|other| other != repo) {
+            return None;
+        }
+        // `repos/` 相対の `<host>/<owner>/<repo>` の末尾 2 つ。
+        let mut components = repo.iter().rev().map(|component| component.to_str());
+        let name = components.next()??;
+        let owner = components.next()??;
+        Some(format!("{owner}__{name}"))
+    }
+
     /// self を `(rest, doc)` に分割する。`rest` は `doc/**` を除外した元プラグイン。
     /// `doc` は抜き出した `doc/**` を持つ `_rsplug:doc` プラグイン（doc が無ければ `None`）。
     /// doc を「LoadedPlugin のまま」扱い、control マージで rsplug-doc・lazy loader と統一的に
@@ -226,7 +309,8 @@ impl LoadedPlugin {
             files,
             script,
             order,
-            merge_enabled,
+            merge_policy,
+            target,
             is_lazy_registration,
             dotgit,
         } = self;
@@ -249,7 +333,8 @@ impl LoadedPlugin {
             files: HowToPlaceFiles::CopyEachFile(map),
             script,
             order,
-            merge_enabled,
+            merge_policy,
+            target,
             is_lazy_registration,
             dotgit,
         };
@@ -264,7 +349,8 @@ impl LoadedPlugin {
                 files: HowToPlaceFiles::CopyEachFile(doc_map),
                 script: SetupScript::default(),
                 order: usize::MAX,
-                merge_enabled: true,
+                merge_policy: None,
+                target: PackTarget::User,
                 is_lazy_registration: true,
                 dotgit: false,
             })
@@ -347,6 +433,7 @@ impl FileItem {
                 path,
                 inventory,
                 handle,
+                ..
             } => {
                 let relative = self.identity.relative_path();
                 let manifest = handle
@@ -539,19 +626,40 @@ impl LoadedPlugin {
     }
 }
 
+impl LoadedPlugin {
+    /// 実際に適用される併合ポリシー。未指定なら [`MergePolicy::default`]。
+    pub(super) fn effective_merge_policy(&self) -> MergePolicy {
+        self.merge_policy.unwrap_or_default()
+    }
+
+    /// ファイル競合以外の併合条件。`merge::MergeClass` はこの判定と同じ分類でなければならない。
+    ///
+    /// 生成された LazyRegistration アーティファクトはユーザ設定によらず lazy_type 一致で
+    /// 内部集約できるが、ユーザプラグインと混ざってはならない。ユーザプラグイン同士は
+    /// 同じポリシーのときだけ併合し、`never` は start/opt を問わず併合を阻止する。
+    fn merge_compatible(&self, rhs: &Self) -> bool {
+        if self.is_lazy_registration != rhs.is_lazy_registration || self.target != rhs.target {
+            return false;
+        }
+        if self.is_lazy_registration {
+            return self.lazy_type == rhs.lazy_type;
+        }
+        let policy = self.effective_merge_policy();
+        if policy != rhs.effective_merge_policy() {
+            return false;
+        }
+        match policy {
+            MergePolicy::Never => false,
+            MergePolicy::SameLazyType => self.lazy_type == rhs.lazy_type,
+            MergePolicy::Aggressive => self.lazy_type.is_start() == rhs.lazy_type.is_start(),
+        }
+    }
+}
+
 impl Add for LoadedPlugin {
     type Output = (Self, Option<Self>);
     fn add(self, rhs: Self) -> Self::Output {
-        if self.lazy_type != rhs.lazy_type {
-            return (self, Some(rhs));
-        }
-        // `merge = false` は start/opt を問わずユーザプラグインのマージを阻止する。
-        // 生成された LazyRegistration アーティファクトはユーザ設定によらず内部集約できるが、
-        // ユーザプラグインと混ざってはならない。なお `merge` のデフォルトは true
-        // （MergeConfig: Default + `#[serde(default)]`）なので、未指定ならマージする。
-        if self.is_lazy_registration != rhs.is_lazy_registration
-            || !(self.is_lazy_registration || self.merge_enabled && rhs.merge_enabled)
-        {
+        if !self.merge_compatible(&rhs) {
             return (self, Some(rhs));
         }
         match (&self.files, &rhs.files) {
@@ -564,23 +672,27 @@ impl Add for LoadedPlugin {
                         files: HowToPlaceFiles::CopyEachFile(mut files),
                         mut script,
                         order,
-                        merge_enabled,
+                        merge_policy,
+                        target,
                         is_lazy_registration,
                         dotgit,
                     } = self;
                     let Self {
                         source_names: r_source_names,
-                        lazy_type: _,
+                        lazy_type: r_lazy_type,
                         files: HowToPlaceFiles::CopyEachFile(rfiles),
                         script: rscript,
                         order: r_order,
-                        merge_enabled: _,
+                        merge_policy: _,
+                        target: _,
                         is_lazy_registration: r_is_lazy_registration,
                         dotgit: r_dotgit,
                     } = rhs;
                     files = union_files(files, rfiles);
                     script += rscript;
                     let order = order.min(r_order);
+                    // `aggressive` では trigger が異なり得るので和集合を取る（一致なら不変）。
+                    let lazy_type = lazy_type & r_lazy_type;
                     // マージで source_name を潰さず、両側の on_source 参照名をすべて保持する。
                     source_names.extend(r_source_names);
 
@@ -591,7 +703,8 @@ impl Add for LoadedPlugin {
                             files: HowToPlaceFiles::CopyEachFile(files),
                             script,
                             order,
-                            merge_enabled,
+                            merge_policy,
+                            target,
                             is_lazy_registration: is_lazy_registration || r_is_lazy_registration,
                             dotgit: dotgit || r_dotgit,
                         },
@@ -901,6 +1014,9 @@ pub(super) enum FileSource {
         /// Production callers provide the complete immutable snapshot handle;
         /// `None` is retained only for legacy hand-built test fixtures.
         handle: Option<Arc<SnapshotHandle>>,
+        /// copy せず `path` 配下への symlink として配置する（dev プラグイン）。
+        /// 配置方法だけの違いなので同値・hash には含めない。
+        symlink: bool,
     },
     File {
         data: Cow<'static, [u8]>,
@@ -941,19 +1057,40 @@ impl FileSource {
         &self,
         whichfile: impl AsRef<Path>,
         install_dir: impl AsRef<Path>,
+        store: &Arc<ContentStore>,
+        stats: &Arc<PlacementStats>,
     ) -> io::Result<()> {
         crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::PackageCopy);
+        let whichfile = whichfile.as_ref();
+        if !is_contained_relative(whichfile) {
+            return Err(escaping_path_error(whichfile));
+        }
+        ensure_no_symlink_ancestor(install_dir.as_ref(), whichfile).await?;
         match self {
+            FileSource::Directory {
+                path,
+                symlink: true,
+                ..
+            } => {
+                let src = path.join(whichfile);
+                let dst = install_dir.as_ref().join(whichfile);
+                if let Some(parent) = dst.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                symlink_path(&src, &dst).await
+            }
             FileSource::Directory { path, .. } => {
-                let src = path.join(&whichfile);
-                let dst = install_dir.as_ref().join(&whichfile);
-                place_path(&src, &dst).await
+                let src = path.join(whichfile);
+                let dst = install_dir.as_ref().join(whichfile);
+                place_path(&src, &dst, Some(store), stats).await
             }
             FileSource::File { data } => {
                 let dst = install_dir.as_ref().join(whichfile);
                 if let Some(parent) = dst.parent() {
                     tokio::fs::create_dir_all(parent).await?;
                 }
+                // 既存の dst は content store と inode を共有しうるので、上書きせず外してから書く。
+                let _ = tokio::fs::remove_file(&dst).await;
                 tokio::fs::write(dst, data).await?;
                 Ok(())
             }
@@ -961,6 +1098,54 @@ impl FileSource {
     }
 }
 
+/// `path` がパッケージ内に留まる相対パスか。配置先は `install_dir.join(path)` なので、
+/// 絶対パス・空パス・`.`/`..` を含むパスは（細工された repo や inventory 由来なら）パッケージ外を指しうる。
+fn is_contained_relative(path: &Path) -> bool {
+    !path.as_os_str().is_empty()
+        && path
+            .components()
+            .all(|component| matches!(component, std::path::Component::Normal(_)))
+}
+
+fn escaping_path_error(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "refusing to place {} outside of the package directory",
+            path.display()
+        ),
+    )
+}
+
+/// `install_dir` から `whichfile` の親までの既存ディレクトリに symlink が無いことを確かめる。
+/// 先に配置された別エントリ（repo 内の symlink）を辿ってパッケージ外へ書き出すのを防ぐ。
+async fn ensure_no_symlink_ancestor(install_dir: &Path, whichfile: &Path) -> io::Result<()> {
+    let Some(parent) = whichfile.parent() else {
+        return Ok(());
+    };
+    let mut dir = install_dir.to_path_buf();
+    for component in parent.components() {
+        dir.push(component);
+        match tokio::fs::symlink_metadata(&dir).await {
+            Ok(meta) if meta.is_symlink() => return Err(escaping_path_error(whichfile)),
+            Ok(_) => {}
+            // 以降の祖先もまだ無い（これから実ディレクトリとして作られる）。
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// `dir` を実ディレクトリとして用意する。既に symlink が置かれていれば、辿らずにエラーにする。
+async fn create_real_dir(dir: &Path) -> io::Result<()> {
+    match tokio::fs::symlink_metadata(dir).await {
+        Ok(meta) if meta.is_symlink() => Err(escaping_path_error(dir)),
+        Ok(meta) if meta.is_dir() => Ok(()),
+        _ => tokio::fs::create_dir_all(dir).await,
+    }
+}
+
 struct Files {
     is_lazy_registration: bool,
     /// 配置エントリ（ファイル・sealed-dir 不分別）。install で各 `source.yank` に任せる。
@@ -998,6 +1183,21 @@ async fn symlink_file(original: impl AsRef<Path>, link: impl AsRef<Path>) -> io:
     tokio::fs::symlink_file(original, link).await
 }
 
+/// ファイル・ディレクトリを問わず `original` への symlink を作る。
+#[cfg(unix)]
+async fn symlink_path(original: &Path, link: &Path) -> io::Result<()> {
+    tokio::fs::symlink(original, link).await
+}
+
+#[cfg(windows)]
+async fn symlink_path(original: &Path, link: &Path) -> io::Result<()> {
+    if tokio::fs::metadata(original).await?.is_dir() {
+        tokio::fs::symlink_dir(original, link).await
+    } else {
+        tokio::fs::symlink_file(original, link).await
+    }
+}
+
 /// staging ディレクトリ名の衝突を避ける単調カウンタ（PID と組み合わせる）。
 static STAGING_NONCE: AtomicU64 = AtomicU64::new(0);
 
@@ -1142,6 +1342,9 @@ struct GenerationManifest {
     plan: Option<GenerationPlan>,
     #[serde(default)]
     runtime: RuntimeManifest,
+    /// パッケージ（`opt/<id>`）ごとの後処理と、その生成・書き換えたファイル。
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    post_process: BTreeMap<String, PostRecord>,
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -1177,6 +1380,246 @@ async fn write_generation_registry(gen_root: &Path, current: &str) -> io::Result
     tokio::fs::rename(tmp, path).await
 }
 
+/// 公開中の世代を指すもの（`init.lua`・世代の registry・provenance index）の写し。
+/// 同期が公開の後で失敗したとき、[`restore`](Self::restore) で公開を元の世代へ戻す。
+/// 直前の世代のパッケージは公開後の掃除でも残るので、指すものを戻せば元の pack になる。
+pub struct Publication {
+    packpath: PathBuf,
+    init: Option<InitPointer>,
+    registry: Option<Vec<u8>>,
+    provenance: Option<Vec<u8>>,
+}
+
+/// `init.lua` の中身。世代のローダへの symlink か、制御パッケージが無いときの実ファイル。
+enum InitPointer {
+    Link(PathBuf),
+    File(Vec<u8>),
+}
+
+/// `path` の中身。無ければ `None`。
+async fn read_optional(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match tokio::fs::read(path).await {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// `content` があれば temp 経由の rename で `path` へ書き、無ければ `path` を消す。
+async fn restore_optional(path: &Path, content: Option<&[u8]>) -> io::Result<()> {
+    match content {
+        Some(content) => {
+            let tmp = path.with_extension("restore");
+            tokio::fs::write(&tmp, content).await?;
+            tokio::fs::rename(&tmp, path).await
+        }
+        None => match tokio::fs::remove_file(path).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+    }
+}
+
+impl Publication {
+    /// `packpath` で公開中の世代を写し取る。未公開なら何も無い状態を写す。
+    pub async fn capture(packpath: &Path) -> io::Result<Self> {
+        let gen_root = packpath.join("pack").join("_gen");
+        let init_path = packpath.join("init.lua");
+        let init = match tokio::fs::symlink_metadata(&init_path).await {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                Some(InitPointer::Link(tokio::fs::read_link(&init_path).await?))
+            }
+            Ok(_) => Some(InitPointer::File(tokio::fs::read(&init_path).await?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            packpath: packpath.to_path_buf(),
+            init,
+            registry: read_optional(&gen_root.join("generations").join(GENERATION_REGISTRY_FILE))
+                .await?,
+            provenance: read_optional(&gen_root.join(provenance::PROVENANCE_FILE)).await?,
+        })
+    }
+
+    /// 写し取った世代を公開し直す。
+    pub async fn restore(self) -> io::Result<()> {
+        let gen_root = self.packpath.join("pack").join("_gen");
+        #[cfg(unix)]
+        let _install_lock = acquire_install_lock(&gen_root).await?;
+        restore_optional(
+            &gen_root.join("generations").join(GENERATION_REGISTRY_FILE),
+            self.registry.as_deref(),
+        )
+        .await?;
+        restore_optional(
+            &gen_root.join(provenance::PROVENANCE_FILE),
+            self.provenance.as_deref(),
+        )
+        .await?;
+        let init_path = self.packpath.join("init.lua");
+        match self.init {
+            Some(InitPointer::Link(target)) => {
+                let tmp = self.packpath.join(".init.lua.swap");
+                let _ = tokio::fs::remove_file(&tmp).await;
+                symlink_file(&target, &tmp).await?;
+                if tokio::fs::rename(&tmp, &init_path).await.is_err() {
+                    let _ = tokio::fs::remove_file(&init_path).await;
+                    tokio::fs::rename(&tmp, &init_path).await?;
+                }
+                Ok(())
+            }
+            Some(InitPointer::File(content)) => restore_optional(&init_path, Some(&content)).await,
+            None => restore_optional(&init_path, None).await,
+        }
+    }
+}
+
+/// ローダが置くディレクトリ（制御パッケージ直下）。`diff_loader` はここだけを比べる。
+const LOADER_DIRS: [&str; 3] = ["lua", "plugin", "ftplugin"];
+
+/// [`PackPlan::diff_loader`] の結果。
+#[derive(Debug, Default)]
+pub struct LoaderDiff {
+    /// 追加・削除を含め、内容が変わるファイルの数。
+    pub files: usize,
+    /// 公開中のローダから生成したローダへの unified diff。差が無ければ空。
+    pub patch: String,
+}
+
+/// 公開中の世代の制御パッケージのファイル（パッケージ相対パス → 内容）。未公開なら空。
+async fn installed_loader(packpath: &Path) -> io::Result<BTreeMap<PathBuf, Vec<u8>>> {
+    let gen_root = packpath.join("pack").join("_gen");
+    let mut tree = BTreeMap::new();
+    // registry の先頭が公開中の世代。
+    let Some(current) = read_generation_registry(&gen_root).await.into_iter().next() else {
+        return Ok(tree);
+    };
+    let manifest_path = gen_root
+        .join("generations")
+        .join(&current)
+        .with_extension("json");
+    let manifest: GenerationManifest =
+        serde_json::from_slice(&tokio::fs::read(&manifest_path).await?)
+            .map_err(io::Error::other)?;
+    // plan の無い旧 manifest では、世代名が制御パッケージの id。
+    let control_ids = manifest
+        .plan
+        .map_or_else(|| vec![current], |plan| plan.control_ids);
+    for id in control_ids {
+        let root = gen_root.join("opt").join(id);
+        for file in list_files(&root).await? {
+            let Ok(relative) = file.strip_prefix(&root) else {
+                continue;
+            };
+            tree.insert(relative.to_path_buf(), tokio::fs::read(&file).await?);
+        }
+    }
+    Ok(tree)
+}
+
+/// 公開中の世代から辿れない `pack/_gen` のパッケージを消し（`dry_run` なら挙げるだけ）、
+/// packpath 相対のパスを返す（`rsplug clean`）。
+///
+/// install 後の掃除は直近 [`RETAIN_GENERATIONS`] 世代のパッケージを残し、件数と時間にも
+/// 上限があるが、こちらは公開中の世代だけを残して全部を調べる。古い世代の manifest と
+/// ローダも消し、registry を公開中の世代だけにする。未公開（registry が無い）なら何もしない。
+pub async fn prune_packages(packpath: &Path, dry_run: bool) -> io::Result<Vec<PathBuf>> {
+    let gen_root = packpath.join("pack").join("_gen");
+    let Some(current) = read_generation_registry(&gen_root).await.into_iter().next() else {
+        return Ok(Vec::new());
+    };
+    #[cfg(unix)]
+    let _install_lock = acquire_install_lock(&gen_root).await?;
+    let generations = gen_root.join("generations");
+    let manifest: GenerationManifest = serde_json::from_slice(
+        &tokio::fs::read(generations.join(&current).with_extension("json")).await?,
+    )
+    .map_err(io::Error::other)?;
+    let entries = manifest_entries(&manifest);
+    let mut unused = Vec::new();
+    for root_name in ["start", "opt"] {
+        let Ok(mut read_dir) = tokio::fs::read_dir(gen_root.join(root_name)).await else {
+            continue;
+        };
+        while let Some(entry) = read_dir.next_entry().await? {
+            let Some(name) = entry.file_name().to_str().map(str::to_owned) else {
+                continue;
+            };
+            let prefix = format!("{root_name}/{name}");
+            let reachable = entries.iter().any(|entry| {
+                entry.as_ref() == prefix.as_bytes()
+                    || entry.starts_with(format!("{prefix}/").as_bytes())
+            });
+            if !reachable && entry.file_type().await?.is_dir() {
+                unused.push(PathBuf::from_iter(["pack", "_gen", root_name, &name]));
+            }
+        }
+    }
+    unused.sort();
+    if dry_run {
+        return Ok(unused);
+    }
+    for package in &unused {
+        tokio::fs::remove_dir_all(packpath.join(package)).await?;
+    }
+    // 古い世代は消したパッケージを参照しうるので、manifest とローダも残さない。
+    for (dir, extension) in [
+        (generations.clone(), "json"),
+        (packpath.join("generations"), "lua"),
+    ] {
+        let Ok(mut read_dir) = tokio::fs::read_dir(&dir).await else {
+            continue;
+        };
+        while let Some(entry) = read_dir.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some(extension)
+                && path.file_stem().and_then(|stem| stem.to_str()) != Some(current.as_str())
+                && path.file_name().and_then(|name| name.to_str()) != Some(GENERATION_REGISTRY_FILE)
+            {
+                tokio::fs::remove_file(&path).await?;
+            }
+        }
+    }
+    let registry = GenerationRegistry {
+        generations: vec![current],
+    };
+    let path = generations.join(GENERATION_REGISTRY_FILE);
+    let tmp = path.with_extension(format!(
+        "json.tmp-{}",
+        STAGING_NONCE.fetch_add(1, AtomicOrdering::Relaxed)
+    ));
+    tokio::fs::write(
+        &tmp,
+        serde_json::to_vec_pretty(&registry).map_err(io::Error::other)?,
+    )
+    .await?;
+    tokio::fs::rename(tmp, path).await?;
+    Ok(unused)
+}
+
+/// `--stable-names` で名前を付けたパッケージ直下に置く、中身の内容ハッシュ id。
+const CONTENT_ID_FILE: &str = ".rsplug-id";
+
+/// 公開済みパッケージ `dir` の中身が内容ハッシュ `content_id` のものか。
+/// 内容ハッシュの名前のパッケージ（`None`）は名前が中身を表すので常に真。
+async fn holds_content(dir: &Path, content_id: Option<&str>) -> bool {
+    match content_id {
+        None => true,
+        Some(content_id) => tokio::fs::read(dir.join(CONTENT_ID_FILE))
+            .await
+            .is_ok_and(|recorded| recorded == content_id.as_bytes()),
+    }
+}
+
+/// 公開済みパッケージ `dir` を再利用できるか。中身が `content_id` のもので、後処理が
+/// `post_steps` と同じ組み合わせで施されていること。
+async fn reusable(dir: &Path, content_id: Option<&str>, post_steps: &[String]) -> bool {
+    holds_content(dir, content_id).await
+        && read_post_record(dir).await.steps == post_steps
+        && !stale_stub(dir).await
+}
+
 /// v2 manifest のランタイム側インデックス。現在は ftplugin のみ。
 /// `ftplugin`: `ft -> id -> [opt/<id>/ftplugin/...]`（generation root 相対パス）。
 #[derive(Clone, Debug, Hash, Serialize, Deserialize, Default, PartialEq, Eq)]
@@ -1187,26 +1630,50 @@ struct RuntimeManifest {
 
 /// Pure, deterministic input to generation publication. Filesystem paths are
 /// generation-root-relative and all ordered collections use stable ordering.
-#[derive(Clone, Debug, Hash, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 struct GenerationPlan {
     schema: u8,
     merge_abi: u8,
     entries: Vec<String>,
     control_ids: Vec<String>,
     runtime: RuntimeManifest,
+    /// `--stable-names` で名前を付けたパッケージ → 内容ハッシュの id。名前が同じでも中身が
+    /// 変われば別の generation になる。
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    named: BTreeMap<String, String>,
+}
+
+impl Hash for GenerationPlan {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.schema.hash(state);
+        self.merge_abi.hash(state);
+        self.entries.hash(state);
+        self.control_ids.hash(state);
+        self.runtime.hash(state);
+        // 安定名の導入前の generation id を変えないよう、名前付きパッケージがある時だけ含める。
+        if !self.named.is_empty() {
+            self.named.hash(state);
+        }
+    }
 }
 
 impl GenerationPlan {
     const SCHEMA: u8 = 1;
     const MERGE_ABI: u8 = 1;
 
-    fn new(entries: Vec<String>, control_ids: &[PluginIDStr], runtime: RuntimeManifest) -> Self {
+    fn new(
+        entries: Vec<String>,
+        control_ids: &[PluginIDStr],
+        runtime: RuntimeManifest,
+        named: BTreeMap<String, String>,
+    ) -> Self {
         Self {
             schema: Self::SCHEMA,
             merge_abi: Self::MERGE_ABI,
             entries,
             control_ids: control_ids.iter().map(ToString::to_string).collect(),
             runtime,
+            named,
         }
     }
 
@@ -1220,13 +1687,18 @@ impl GenerationPlan {
 #[template(escape = false)]
 struct InitTemplate<'a> {
     control_ids: &'a [PluginIDStr],
+    /// `target = "system"` のパッケージを置いた packpath。'packpath' の末尾に足す。
+    system_packpath: Option<&'a Path>,
 }
 
-fn render_init(control_ids: &[PluginIDStr]) -> Vec<u8> {
-    InitTemplate { control_ids }
-        .render_once()
-        .map(String::into_bytes)
-        .unwrap_or_else(|_| Vec::new())
+fn render_init(control_ids: &[PluginIDStr], system_packpath: Option<&Path>) -> Vec<u8> {
+    InitTemplate {
+        control_ids,
+        system_packpath,
+    }
+    .render_once()
+    .map(String::into_bytes)
+    .unwrap_or_else(|_| Vec::new())
 }
 
 /// `true` は生成物を公開し、`false` は既存の完全な generation を再利用した。
@@ -1236,13 +1708,16 @@ pub type GenerationPublished = bool;
 ///
 /// S1 前のため ft index の読み取り自体は必要だが、コピー・metadata/loader/init の書き込みと
 /// retention/GC を避けられる。欠損や破損はすべて `false` に倒し通常 publish で修復する。
+#[allow(clippy::too_many_arguments)]
 async fn generation_is_current(
     gen_root: &Path,
     control_ids: &[PluginIDStr],
     generation_entries: &[String],
+    named: &BTreeMap<String, String>,
     init_content: &[u8],
     packpath: &Path,
     desired_plan: Option<&GenerationPlan>,
+    post_steps: &[String],
 ) -> bool {
     for entry in generation_entries {
         let Ok(metadata) = tokio::fs::symlink_metadata(gen_root.join(entry)).await else {
@@ -1251,6 +1726,20 @@ async fn generation_is_current(
         if !metadata.is_dir() || metadata.file_type().is_symlink() {
             return false;
         }
+        // 後処理の組み合わせを変えたら、同じパッケージでも配置し直す。
+        if read_post_record(&gen_root.join(entry)).await.steps != post_steps {
+            return false;
+        }
+        // 疎なパッケージのスタブが消えた snapshot や helper を指すなら、スタブを書き直す。
+        if stale_stub(&gen_root.join(entry)).await {
+            return false;
+        }
+    }
+    // 名前付きパッケージは entries が同じでも中身が古いことがある。
+    for (name, content_id) in named {
+        if !holds_content(&gen_root.join("opt").join(name), Some(content_id.as_str())).await {
+            return false;
+        }
     }
     // A script-only/empty plan has no control package or generation manifest;
     // its durable pointer is the plain root init.lua written by the publisher.
@@ -1319,6 +1808,24 @@ const STRATEGY_REFLINK: u8 = 0;
 const STRATEGY_HARDLINK: u8 = 1;
 const STRATEGY_COPY: u8 = 2;
 
+/// 1 回の配置で reflink（`clonefile` / `FICLONE`）したバイト数・ディレクトリ数と、内容複製した
+/// バイト数。install ごとに持って Copied の要約に出すので、daemon で同時に走る install の分は
+/// 混ざらない。
+#[derive(Default)]
+struct PlacementStats {
+    cloned_bytes: AtomicU64,
+    cloned_dirs: AtomicU64,
+    copied_bytes: AtomicU64,
+}
+
+impl PlacementStats {
+    /// `[clone したバイト数, clone したディレクトリ数, 複製したバイト数]`。
+    fn totals(&self) -> [u64; 3] {
+        [&self.cloned_bytes, &self.cloned_dirs, &self.copied_bytes]
+            .map(|n| n.load(AtomicOrdering::Relaxed))
+    }
+}
+
 /// 別 filesystem を跨ぐ errno（reflink も hardlink も不可）。
 const EXDEV: i32 = libc::EXDEV;
 
@@ -1378,22 +1885,25 @@ async fn reflink_file(_src: &Path, _dst: &Path) -> io::Result<()> {
     Err(io::Error::from_raw_os_error(38)) // ENOSYS
 }
 
-/// 1ファイルを現在の戦略で配置。未対応/`EXDev` エラーで戦略を昇格して再試行する。
-async fn copy_file_with_strategy(src: &Path, dst: &Path) -> io::Result<()> {
+/// 1ファイル（`len` バイト）を現在の戦略で配置。未対応/`EXDev` エラーで戦略を昇格して再試行する。
+async fn copy_file_with_strategy(
+    src: &Path,
+    dst: &Path,
+    len: u64,
+    stats: &PlacementStats,
+) -> io::Result<()> {
     loop {
         match copy_strategy() {
             s if s == STRATEGY_REFLINK => match reflink_file(src, dst).await {
                 Ok(()) => {
                     crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::ReflinkCopy);
+                    stats.cloned_bytes.fetch_add(len, AtomicOrdering::Relaxed);
                     return Ok(());
                 }
                 Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
                     // dst 既存在（マージで同名ファイルが複数 plugin 由来等）。copy で上書き。
                     // 戦略は変更しない（AlreadyExists は環境起因ではない）。
-                    let bytes = tokio::fs::copy(src, dst).await?;
-                    crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::FileCopied);
-                    crate::rsplug::perf::incr_bytes(bytes);
-                    return Ok(());
+                    return replace_with_copy(src, dst, stats).await;
                 }
                 Err(e) if reflink_should_fallback(&e) => {
                     crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::FallbackFanout);
@@ -1413,10 +1923,7 @@ async fn copy_file_with_strategy(src: &Path, dst: &Path) -> io::Result<()> {
                     continue;
                 }
                 Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
-                    let bytes = tokio::fs::copy(src, dst).await?;
-                    crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::FileCopied);
-                    crate::rsplug::perf::incr_bytes(bytes);
-                    return Ok(());
+                    return replace_with_copy(src, dst, stats).await;
                 }
                 Err(e) => return Err(e),
             },
@@ -1425,24 +1932,47 @@ async fn copy_file_with_strategy(src: &Path, dst: &Path) -> io::Result<()> {
                 let bytes = tokio::fs::copy(src, dst).await?;
                 crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::FileCopied);
                 crate::rsplug::perf::incr_bytes(bytes);
+                stats.copied_bytes.fetch_add(bytes, AtomicOrdering::Relaxed);
                 return Ok(());
             }
         }
     }
 }
 
+/// 既存の `dst` を外してから `src` を内容複製する。`dst` が content store 由来の
+/// ハードリンクでも、共有 inode を書き換えて他パッケージを壊さないようにする。
+async fn replace_with_copy(src: &Path, dst: &Path, stats: &PlacementStats) -> io::Result<()> {
+    let _ = tokio::fs::remove_file(dst).await;
+    let bytes = tokio::fs::copy(src, dst).await?;
+    crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::FileCopied);
+    crate::rsplug::perf::incr_bytes(bytes);
+    stats.copied_bytes.fetch_add(bytes, AtomicOrdering::Relaxed);
+    Ok(())
+}
+
 /// `src`（file/dir/symlink）を `dst` に配置する。ディレクトリは `copy_tree`、それ以外は `copy_leaf`。
-async fn place_path(src: &Path, dst: &Path) -> io::Result<()> {
+/// `store` があれば小さい通常ファイルは内容で重複排除してハードリンクする。配置量は `stats` に足す。
+async fn place_path(
+    src: &Path,
+    dst: &Path,
+    store: Option<&Arc<ContentStore>>,
+    stats: &Arc<PlacementStats>,
+) -> io::Result<()> {
     let meta = tokio::fs::symlink_metadata(src).await?;
     if meta.is_dir() {
-        copy_tree(src, dst).await
+        copy_tree(src, dst, store, stats).await
     } else {
-        copy_leaf(src, dst).await
+        copy_leaf(src, dst, store.map(Arc::as_ref), stats).await
     }
 }
 
 /// leaf（ファイル/symlink）を `dst` に配置する。ディレクトリは扱わない（呼出元が mkdir 済み）。
-async fn copy_leaf(src: &Path, dst: &Path) -> io::Result<()> {
+async fn copy_leaf(
+    src: &Path,
+    dst: &Path,
+    store: Option<&ContentStore>,
+    stats: &PlacementStats,
+) -> io::Result<()> {
     let meta = tokio::fs::symlink_metadata(src).await?;
     if meta.is_symlink() {
         let target = tokio::fs::read_link(src).await?;
@@ -1468,10 +1998,15 @@ async fn copy_leaf(src: &Path, dst: &Path) -> io::Result<()> {
         }
         Ok(())
     } else {
+        if let Some(store) = store
+            && store.place(src, &meta, dst).await?
+        {
+            return Ok(());
+        }
         if let Some(parent) = dst.parent() {
             tokio::fs::create_dir_all(parent).await?;
         }
-        copy_file_with_strategy(src, dst).await
+        copy_file_with_strategy(src, dst, meta.len(), stats).await
     }
 }
 
@@ -1480,7 +2015,12 @@ async fn copy_leaf(src: &Path, dst: &Path) -> io::Result<()> {
 /// （CoW かつ独立 inode なので元 snapshot を編集しても pack に影響しない）。
 /// フォールバック時はスタックでディレクトリを walk して leaf のみ `JoinSet` で並列 copy する
 /// （`copy_leaf` は非再帰なので、再帰的 future 型による Send 推論の破綻を避ける）。
-async fn copy_tree(src: &Path, dst: &Path) -> io::Result<()> {
+async fn copy_tree(
+    src: &Path,
+    dst: &Path,
+    store: Option<&Arc<ContentStore>>,
+    stats: &Arc<PlacementStats>,
+) -> io::Result<()> {
     #[cfg(target_os = "macos")]
     if copy_strategy() == STRATEGY_REFLINK {
         // clonefile は dst を新規作成するので親だけ作る。
@@ -1488,7 +2028,10 @@ async fn copy_tree(src: &Path, dst: &Path) -> io::Result<()> {
             tokio::fs::create_dir_all(parent).await?;
         }
         match clonefile(src, dst).await {
-            Ok(()) => return Ok(()),
+            Ok(()) => {
+                stats.cloned_dirs.fetch_add(1, AtomicOrdering::Relaxed);
+                return Ok(());
+            }
             Err(e) if reflink_should_fallback(&e) => {
                 advance_strategy(&e);
                 // フォールバック: dst は未作成のまま walk へ。
@@ -1500,7 +2043,7 @@ async fn copy_tree(src: &Path, dst: &Path) -> io::Result<()> {
             Err(e) => return Err(e),
         }
     }
-    tokio::fs::create_dir_all(dst).await?;
+    create_real_dir(dst).await?;
     // Keep traversal memory bounded.  A semaphore alone limits active copies,
     // but spawning one task per leaf still retains the whole snapshot in the
     // executor queue.  The channel is deliberately small so walking and
@@ -1513,6 +2056,8 @@ async fn copy_tree(src: &Path, dst: &Path) -> io::Result<()> {
     for _ in 0..COPY_WORKERS {
         crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::SpawnedWorker);
         let worker_rx = shared_rx.clone();
+        let store = store.cloned();
+        let stats = Arc::clone(stats);
         workers.spawn(async move {
             loop {
                 let item = {
@@ -1524,7 +2069,7 @@ async fn copy_tree(src: &Path, dst: &Path) -> io::Result<()> {
                     .acquire()
                     .await
                     .map_err(|e| io::Error::other(format!("copy leaf semaphore closed: {e}")))?;
-                let result = copy_leaf(&src, &dst).await;
+                let result = copy_leaf(&src, &dst, store.as_deref(), &stats).await;
                 drop(permit);
                 result?;
             }
@@ -1535,7 +2080,7 @@ async fn copy_tree(src: &Path, dst: &Path) -> io::Result<()> {
     while let Some((s, d)) = stack.pop() {
         let meta = tokio::fs::symlink_metadata(&s).await?;
         if meta.is_dir() {
-            tokio::fs::create_dir_all(&d).await?;
+            create_real_dir(&d).await?;
             let mut entries = tokio::fs::read_dir(&s).await?;
             while let Some(entry) = entries.next_entry().await? {
                 let name = entry.file_name();
@@ -1550,60 +2095,49 @@ async fn copy_tree(src: &Path, dst: &Path) -> io::Result<()> {
     }
     drop(tx);
     while let Some(res) = workers.join_next().await {
-        res.map_err(|e| io::Error::other(format!("copy join failed: {e}")))??;
+        res.map_err(|e| io::Error::other(util::task::panicked("copy")(e)))??;
     }
     Ok(())
 }
 
-/// Generate all staged helptags with one Neovim process. Package copying is
-/// already complete, so the command can issue one `helptags` invocation per
-/// doc directory while keeping process creation bounded to one per generation.
-async fn run_staged_helptags(staging: &Path) -> io::Result<()> {
-    let mut help_dirs = Vec::new();
-    let opt = staging.join("opt");
-    let Ok(mut packages) = tokio::fs::read_dir(opt).await else {
-        return Ok(());
-    };
-    while let Some(package) = packages.next_entry().await? {
-        let help_dir = package.path().join("doc");
-        if tokio::fs::metadata(&help_dir)
-            .await
-            .is_ok_and(|metadata| metadata.is_dir())
-        {
-            help_dirs.push(help_dir);
+/// staging に置いたパッケージに後処理を施し、パッケージ id ごとの記録を返す。
+/// `deferred` のパッケージ（dev プラグインとスパースのスタブ）には処理を施さず、処理すべき
+/// 手順だけを記録する。
+async fn run_staged_post_process(
+    staging: &Path,
+    pipeline: &PostPipeline,
+    deferred: &BTreeSet<String>,
+) -> io::Result<BTreeMap<String, PostRecord>> {
+    let mut packages = Vec::new();
+    let mut skipped = Vec::new();
+    if let Ok(mut entries) = tokio::fs::read_dir(staging.join("opt")).await {
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                let deferred = entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|id| deferred.contains(id));
+                if deferred {
+                    skipped.push(entry.path());
+                } else {
+                    packages.push(entry.path());
+                }
+            }
         }
     }
-    help_dirs.sort();
-    if help_dirs.is_empty() {
-        return Ok(());
-    }
-
-    let mut nvim = tokio::process::Command::new("nvim");
-    nvim.arg("--headless")
-        .arg("-u")
-        .arg("NONE")
-        .arg("-i")
-        .arg("NONE")
-        .arg("-n");
-    for help_dir in &help_dirs {
-        msg(Message::InstallHelp {
-            help_dir: help_dir.clone(),
-        });
-        let escaped = help_dir
-            .to_string_lossy()
-            .replace('\\', "\\\\")
-            .replace(' ', "\\ ")
-            .replace('|', "\\|");
-        nvim.arg("-c").arg(format!("helptags {escaped}"));
-    }
-    crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::HelptagsProcess);
-    nvim.arg("-c").arg("q").status().await.and_then(|code| {
-        if code.success() {
-            Ok(())
-        } else {
-            Err(io::Error::other("Failed to generate staged helptags"))
-        }
-    })
+    packages.sort();
+    skipped.sort();
+    let records = pipeline.run(packages.clone()).await?;
+    let skipped_records = pipeline.record(skipped.clone()).await?;
+    Ok(packages
+        .iter()
+        .zip(records)
+        .chain(skipped.iter().zip(skipped_records))
+        .filter_map(|(package, record)| {
+            let id = package.file_name()?.to_str()?;
+            Some((format!("opt/{id}"), record))
+        })
+        .collect())
 }
 
 // ---- プラットフォーム固有 reflink 実装 ----
@@ -2017,6 +2551,46 @@ fn push_unique(paths: &mut Vec<String>, p: &str) {
     }
 }
 
+/// `--verbose-install` の後処理。`<packpath>/by-name/<name>/` を作り直し、各名前のディレクトリへ
+/// `_rsplug_manifest.json` とパッケージへの `package` symlink を置いて、名前とパッケージの対応を表示する。
+///
+/// `opt/<id>` は内容ハッシュで世代間に共有されるので、公開済みのパッケージには書き込まない。
+/// `.git` 欠損で配置されなかったパッケージは飛ばす。
+async fn write_package_origins(
+    packpath: &Path,
+    origins: BTreeMap<PluginIDStr, PackageOrigin>,
+) -> io::Result<()> {
+    let opt = packpath.join("pack").join("_gen").join("opt");
+    let by_name = packpath.join(BY_NAME_DIR);
+    match tokio::fs::remove_dir_all(&by_name).await {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    tokio::fs::create_dir_all(&by_name).await?;
+    for (id, origin) in origins {
+        let dir = opt.join(&*id);
+        if !tokio::fs::try_exists(&dir).await? {
+            continue;
+        }
+        let content = serde_json::to_vec_pretty(&origin).map_err(io::Error::other)?;
+        for name in &origin.names {
+            let named = by_name.join(name.replace(['/', '\\'], "_"));
+            // 同じ名前が別のパッケージにもあれば、先に書いた方を残す。
+            match tokio::fs::create_dir(&named).await {
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                result => result?,
+            }
+            tokio::fs::write(named.join(PACKAGE_ORIGIN_FILE), &content).await?;
+            symlink_path(&dir, &named.join(PACKAGE_LINK)).await?;
+        }
+        msg(Message::InstallPackage {
+            id: id.into(),
+            names: origin.names.into_iter().collect(),
+        });
+    }
+    Ok(())
+}
+
 /// PackPath の象徴となる状態。この構造体に PluginLoaded をインサートしていき、最後に実際のパスを指定して install を行う。
 #[derive(Default)]
 pub struct PackPlan {
@@ -2026,6 +2600,75 @@ pub struct PackPlan {
     /// `split_doc` で分割された doc プラグイン群（LoadedPlugin のまま）。install の control
     /// マージで rsplug-doc・lazy loader と統一マージされ、1つの `_rsplug:doc` に集約される（Phase 8）。
     doc_plugins: Vec<LoadedPlugin>,
+    /// `merge` 未指定のユーザプラグインに適用する併合ポリシー（CLI の `--merge`）。
+    merge_policy: MergePolicy,
+    /// `--no-merge`: プラグインごとの `merge` も無視して一切併合しない。
+    no_merge: bool,
+    /// `--debug-loader`: 生成する loader に assert とログを埋め込む。
+    debug_loader: bool,
+    /// テンプレートディレクトリから読み込んだランタイムの差し替え。
+    templates: Arc<TemplateOverrides>,
+    /// `--verbose-install`: ユーザパッケージごとの由来情報。`None` なら収集しない。
+    origins: Option<BTreeMap<PluginIDStr, PackageOrigin>>,
+    /// `rsplug owners` 用の配置エントリ → 由来の index（publish 後に永続化する）。
+    provenance: ProvenanceIndex,
+    /// `--stable-names`: 併合されないプラグインを `<owner>__<repo>` の名前で配置する。
+    stable_names: bool,
+    /// 付けた安定名 → 内容ハッシュの id。同じ名前を別の中身が求めたらハッシュの名前に戻す。
+    named: BTreeMap<String, PluginIDStr>,
+    /// `--packpath system=<DIR>`: `target = "system"` のパッケージの置き場所。
+    system_packpath: Option<PathBuf>,
+    /// `target = "system"` のパッケージの id。install でユーザの packpath から外す。
+    system_ids: HashSet<PluginIDStr>,
+    /// `--sparse`: 遅延パッケージの中身を置かず、初回の読み込みで配置する（実験的）。
+    sparse: bool,
+    /// start プラグインを含むパッケージの id。疎な install でも常に配置する。
+    start_ids: HashSet<PluginIDStr>,
+    /// `--post-process`: 公開前のパッケージに施す後処理。`None` なら [`PostStep::DEFAULT`]。
+    post_process: Option<Vec<PostStep>>,
+}
+
+/// `rsplug emit-lua` の出力ディレクトリの目印。これがあれば次回の出力で置き換えてよい。
+pub const EMIT_LUA_MARKER: &str = ".rsplug-emit-lua";
+/// `--verbose-install` で `by-name/<name>/` へ書き出す由来情報のファイル名。
+const PACKAGE_ORIGIN_FILE: &str = "_rsplug_manifest.json";
+/// `--verbose-install` で名前ごとのディレクトリを並べるディレクトリ（packpath 直下）。
+const BY_NAME_DIR: &str = "by-name";
+/// `by-name/<name>/` からパッケージを指す symlink の名前。
+const PACKAGE_LINK: &str = "package";
+
+/// 1 パッケージがどの設定エントリ・どの snapshot から作られたか（`_rsplug_manifest.json`）。
+#[derive(Serialize)]
+struct PackageOrigin {
+    id: String,
+    names: BTreeSet<String>,
+    /// 由来 repo（`repos/` からの相対パス）→ HEAD rev。
+    sources: BTreeMap<String, String>,
+    triggers: Vec<String>,
+    scripts: SetupScript,
+}
+
+impl PackageOrigin {
+    fn new(id: &PluginIDStr, plugin: &LoadedPlugin) -> Self {
+        let HowToPlaceFiles::CopyEachFile(files) = &plugin.files;
+        let sources = files
+            .values()
+            .filter_map(|item| match &item.identity {
+                FileIdentity::RepoFile(file) => Some((
+                    file.snapshot.repo_cache_dir.to_string_lossy().into_owned(),
+                    String::from_utf8_lossy(&file.snapshot.head_rev).into_owned(),
+                )),
+                FileIdentity::GeneratedFile { .. } => None,
+            })
+            .collect();
+        Self {
+            id: id.to_string(),
+            names: plugin.source_names.clone(),
+            sources,
+            triggers: plugin.lazy_type.describe(),
+            scripts: plugin.script.clone(),
+        }
+    }
 }
 
 impl PackPlan {
@@ -2036,6 +2679,58 @@ impl PackPlan {
     pub fn new() -> Self {
         Default::default()
     }
+    /// `merge` 未指定のプラグインに適用する併合ポリシーを設定する。
+    pub fn with_merge_policy(mut self, merge_policy: MergePolicy) -> Self {
+        self.merge_policy = merge_policy;
+        self
+    }
+    /// プラグインごとの設定によらず、ユーザプラグインを一切併合しない（隔離デバッグ用）。
+    pub fn with_no_merge(mut self, no_merge: bool) -> Self {
+        self.no_merge = no_merge;
+        self
+    }
+    /// 生成する Lua loader を検証・ログ付きで描く。読み込みの問題を調べるためのもので、
+    /// 既定（`false`）の loader には何も足さない。
+    pub fn with_debug_loader(mut self, debug_loader: bool) -> Self {
+        self.debug_loader = debug_loader;
+        self
+    }
+    /// 組み込みランタイムの代わりに使う差し替えテンプレートを設定する。
+    pub fn with_template_overrides(mut self, templates: Arc<TemplateOverrides>) -> Self {
+        self.templates = templates;
+        self
+    }
+    /// 併合されないプラグインのパッケージを、内容ハッシュではなく `<owner>__<repo>` の名前で置く。
+    /// 名前は読みやすいが内容を表さないので、中身が変われば公開中のディレクトリを置き換える。
+    pub fn with_stable_names(mut self, stable_names: bool) -> Self {
+        self.stable_names = stable_names;
+        self
+    }
+    /// `target = "system"` のパッケージを置く packpath。生成するローダはこれを 'packpath' に足す。
+    pub fn with_system_packpath(mut self, system_packpath: Option<PathBuf>) -> Self {
+        self.system_packpath = system_packpath;
+        self
+    }
+    /// `target = "system"` のプラグインを含むか。
+    pub fn has_system_packages(&self) -> bool {
+        !self.system_ids.is_empty()
+    }
+    /// 遅延パッケージを中身なしで公開し、初回の `packadd` でローダが `rsplug materialize` を
+    /// 呼んで配置するようにする（実験的）。
+    pub fn with_sparse(mut self, sparse: bool) -> Self {
+        self.sparse = sparse;
+        self
+    }
+    /// 公開前のパッケージに施す後処理。`None` なら既定（help tags の生成だけ）。
+    pub fn with_post_process(mut self, steps: Option<Vec<PostStep>>) -> Self {
+        self.post_process = steps;
+        self
+    }
+    /// install 時に各ユーザパッケージへ由来情報を書き出し、名前との対応を表示する。
+    pub fn with_verbose_install(mut self, verbose_install: bool) -> Self {
+        self.origins = verbose_install.then(BTreeMap::new);
+        self
+    }
     /// source プラグイン群を受け取る。**マージ前に各プラグインを `split_doc` で (rest, doc) に分割**し、
     /// doc 無しの rest 群をマージして登録する。doc 部は LoadedPlugin のまま `doc_plugins` に集め、
     /// install の control マージで rsplug-doc・lazy loader と統一的に1つの `_rsplug:doc` に集約する
@@ -2043,7 +2738,12 @@ impl PackPlan {
     pub fn load(&mut self, mut plugins: BinaryHeap<LoadedPlugin>) {
         let drained: Vec<LoadedPlugin> = plugins.drain().collect();
         for p in drained {
-            let (rest, doc) = p.split_doc();
+            let (mut rest, doc) = p.split_doc();
+            if self.no_merge {
+                rest.merge_policy = Some(MergePolicy::Never);
+            } else {
+                rest.merge_policy.get_or_insert(self.merge_policy);
+            }
             if let Some(doc) = doc {
                 self.doc_plugins.push(doc);
             }
@@ -2059,14 +2759,51 @@ impl PackPlan {
             self.insert(plugin);
         }
     }
+    /// `load` したプラグインのうち、別々のパッケージが同じ `on_cmd` のコマンドや同じモード・キーの
+    /// `on_map` を登録したもの。トリガー順（コマンド、次にモード・キー順）。
+    pub fn duplicate_triggers(&self) -> Vec<DuplicateTrigger> {
+        self.ctl.duplicate_triggers()
+    }
+    /// `load` した lazy パッケージのうち、どのトリガーからも読み込まれないもの。宣言順。
+    pub fn unloadable_packages(&self) -> Vec<String> {
+        self.ctl.unloadable_packages()
+    }
+    /// `load` したユーザパッケージと、それぞれに配置するエントリ。id 順。制御パッケージ
+    /// （ローダ・help）は install で生成するので含まない。
+    pub fn planned_packages(&self) -> Vec<PlannedPackage> {
+        self.provenance
+            .planned_packages(|id| self.system_ids.iter().any(|system| **system == *id))
+    }
     /// PluginLoaded をインサートする。その PluginLoaded の実行制御や設定に必要な LazyRegistration を返す。
-    pub fn insert(&mut self, loaded_plugin: LoadedPlugin) {
-        let id = loaded_plugin.plugin_id();
-        let id_str = id.as_str();
+    pub fn insert(&mut self, mut loaded_plugin: LoadedPlugin) {
+        // パッケージ外を指すエントリは id の計算・配置・記録の前に落とす（yank 側でも拒否する
+        // 二重の防御）。落とした後の中身で id を決めるので、同じ中身のプラグインと id が揃う。
+        let HowToPlaceFiles::CopyEachFile(files) = &mut loaded_plugin.files;
+        let mut rejected = Vec::new();
+        files.retain(|path, _| {
+            let contained = is_contained_relative(path);
+            if !contained {
+                rejected.push(path.clone());
+            }
+            contained
+        });
+        let id_str = self.package_id(&loaded_plugin);
+        for path in rejected {
+            msg(Message::InstallRejectedPath {
+                id: id_str.clone().into(),
+                path,
+            });
+        }
         let already_installed = !self.installing.insert(id_str.clone().into());
         if already_installed {
             return;
         }
+        if let Some(origins) = &mut self.origins
+            && !loaded_plugin.is_lazy_registration
+        {
+            origins.insert(id_str.clone(), PackageOrigin::new(&id_str, &loaded_plugin));
+        }
+        self.provenance.record(&id_str, &loaded_plugin);
 
         let LoadedPlugin {
             source_names,
@@ -2074,15 +2811,24 @@ impl PackPlan {
             files,
             script,
             order,
-            merge_enabled: _,
+            merge_policy: _,
+            target,
             is_lazy_registration,
             dotgit,
         } = loaded_plugin;
 
+        if target == PackTarget::System {
+            self.system_ids.insert(id_str.clone());
+        }
+
+        if !is_lazy_registration && lazy_type.is_start() {
+            self.start_ids.insert(id_str.clone());
+        }
         if !is_lazy_registration {
             // doc 盗みはマージ前に `PackPlan::load` → `LoadedPlugin::steal_doc` で済ませているため、
             // ここでは lazy 実行制御（LazyRegistration）の生成のみ。files は変更しない。
-            self.ctl += LazyRegistration::create(id, source_names, lazy_type, script, order);
+            self.ctl +=
+                LazyRegistration::create(id_str.clone(), source_names, lazy_type, script, order);
         }
         match files {
             HowToPlaceFiles::CopyEachFile(files) => {
@@ -2105,29 +2851,235 @@ impl PackPlan {
         }
     }
 
-    /// PackPlan を指定されたパスにインストールする。パスは Vim の 'packpath' に基づく。
-    /// NOTE: インストール後のディレクトリ構成は以下のようになる。
-    /// {packpath}/pack/_gen/opt/{id}/
-    pub async fn install(mut self, packpath: &Path) -> io::Result<GenerationPublished> {
-        // R1: control マージが self.ctl を消費する前に、on_ft の (ft,id) を取り出す。
-        // 公開後に gen_root/opt/<id>/ を走査して ftplugin インデックスを構築する。
-        let ft_pairs = self.ctl.ft_index_pairs();
-        {
-            // LazyRegistration（lazy 実行制御）と分割された doc プラグイン群を control マージで統一する。
-            // rsplug-doc・lazy loader・doc 分割群が1つの `_rsplug:doc`（+ 制御パック）に集約される。
-            let plugins = {
-                let plugins: Vec<LoadedPlugin> = std::mem::take(&mut self.ctl).into();
-                let mut heap: BinaryHeap<_> = plugins.into();
-                for doc in std::mem::take(&mut self.doc_plugins) {
-                    heap.push(doc);
-                }
-                MergePlanner::plan(&mut heap);
+    /// `pack/_gen/opt/` 以下のパッケージ名。安定名が使えなければ内容ハッシュ。
+    fn package_id(&mut self, plugin: &LoadedPlugin) -> PluginIDStr {
+        let hashed = plugin.plugin_id().as_str();
+        let Some(name) = self.stable_names.then(|| plugin.stable_name()).flatten() else {
+            return hashed;
+        };
+        match self.named.entry(name) {
+            btree_map::Entry::Vacant(entry) => {
+                let id = PluginIDStr::named(entry.key().as_str());
+                entry.insert(hashed);
+                id
+            }
+            btree_map::Entry::Occupied(entry) if *entry.get() == hashed => {
+                PluginIDStr::named(entry.key().as_str())
+            }
+            // 同じ repo の別エントリ（`when` や build の違い）は名前を譲らない。
+            btree_map::Entry::Occupied(_) => hashed,
+        }
+    }
+
+    /// 生成される制御パッケージ（`_rsplug` の Lua ローダ一式）のファイル（パッケージ相対パス →
+    /// 内容）。`emit_lua`・`diff_loader` が使う。
+    async fn loader_tree(&mut self) -> io::Result<BTreeMap<PathBuf, Cow<'static, [u8]>>> {
+        let plugins = std::mem::take(&mut self.ctl)
+            .render(self.debug_loader, Arc::clone(&self.templates))
+            .await?;
+        let mut tree: BTreeMap<PathBuf, Cow<'static, [u8]>> = BTreeMap::new();
+        for plugin in &plugins {
+            let HowToPlaceFiles::CopyEachFile(files) = &plugin.files;
+            for (path, item) in files {
+                let FileSource::File { data } = item.source.as_ref() else {
+                    continue;
+                };
+                match tree.get(path) {
+                    Some(existing) if existing != data => {
+                        return Err(io::Error::other(format!(
+                            "generated file {} has conflicting contents",
+                            path.display()
+                        )));
+                    }
+                    _ => {
+                        tree.insert(path.clone(), data.clone());
+                    }
+                }
+            }
+        }
+        Ok(tree)
+    }
+
+    /// 生成される制御パッケージ（`_rsplug` の Lua ローダ一式）だけを `out` へ書き出し、
+    /// 書いたファイル数を返す。ユーザプラグインのファイルは配置しない。
+    ///
+    /// 全体を `out` の隣の staging に書いてから置き換えるので、前回の出力に残る古い
+    /// `plugin/<hash>.lua` が混ざらない。`out` が前回の出力（[`EMIT_LUA_MARKER`] を含む）でも
+    /// 空でもないディレクトリなら、消さずにエラーを返す。
+    pub async fn emit_lua(mut self, out: &Path) -> io::Result<usize> {
+        let tree = self.loader_tree().await?;
+
+        match tokio::fs::read_dir(out).await {
+            Ok(mut entries) => {
+                if entries.next_entry().await?.is_some()
+                    && !tokio::fs::try_exists(out.join(EMIT_LUA_MARKER)).await?
+                {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!(
+                            "{} is not empty and was not written by `rsplug emit-lua`",
+                            out.display()
+                        ),
+                    ));
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        let name = out
+            .file_name()
+            .ok_or_else(|| io::Error::other(format!("invalid output path {}", out.display())))?;
+        let staging = out.with_file_name(format!(
+            ".{}.staging-{}-{}",
+            name.to_string_lossy(),
+            std::process::id(),
+            STAGING_NONCE.fetch_add(1, AtomicOrdering::Relaxed)
+        ));
+        let _staging_guard = StagingGuard(staging.clone());
+        tokio::fs::create_dir_all(&staging).await?;
+        for (path, data) in &tree {
+            let target = staging.join(path);
+            if let Some(parent) = target.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&target, data).await?;
+        }
+        tokio::fs::write(staging.join(EMIT_LUA_MARKER), b"").await?;
+        match tokio::fs::remove_dir_all(out).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        tokio::fs::rename(&staging, out).await?;
+        Ok(tree.len())
+    }
+
+    /// 生成する Lua ローダを `packpath` に公開中のものと比べ、unified diff を返す。
+    /// pack と lock には触れない。公開中の制御パッケージにだけあるファイルは、ローダの
+    /// ディレクトリ（[`LOADER_DIRS`]）のものを削除として数え、同居する help などは比べない。
+    pub async fn diff_loader(mut self, packpath: &Path) -> io::Result<LoaderDiff> {
+        // `doc/rsplug.txt` は doc パッケージ側に公開されるので、生成側もローダの dir だけ比べる。
+        let is_loader = |path: &Path| {
+            path.components()
+                .find(|component| !matches!(component, std::path::Component::CurDir))
+                .is_some_and(|first| LOADER_DIRS.iter().any(|dir| first.as_os_str() == *dir))
+        };
+        let mut generated = self.loader_tree().await?;
+        generated.retain(|path, _| is_loader(path));
+        let mut installed = installed_loader(packpath).await?;
+        installed.retain(|path, _| generated.contains_key(path) || is_loader(path));
+        let paths: BTreeSet<&PathBuf> = generated.keys().chain(installed.keys()).collect();
+        let mut diff = LoaderDiff::default();
+        for path in paths {
+            let old = installed.get(path).map(Vec::as_slice);
+            let new = generated.get(path).map(|data| &data[..]);
+            if old == new {
+                continue;
+            }
+            let (old, new) = (old.unwrap_or_default(), new.unwrap_or_default());
+            diff.files += 1;
+            diff.patch
+                .push_str(&util::git::unified_diff(path, old, new).map_err(io::Error::other)?);
+        }
+        Ok(diff)
+    }
+
+    /// PackPlan を指定されたパスにインストールする。パスは Vim の 'packpath' に基づく。
+    /// NOTE: インストール後のディレクトリ構成は以下のようになる。
+    /// {packpath}/pack/_gen/opt/{id}/
+    pub async fn install(mut self, packpath: &Path) -> io::Result<GenerationPublished> {
+        // R1: control マージが self.ctl を消費する前に、on_ft の (ft,id) を取り出す。
+        // 公開後に gen_root/opt/<id>/ を走査して ftplugin インデックスを構築する。
+        let mut ft_pairs = self.ctl.ft_index_pairs();
+        {
+            // LazyRegistration（lazy 実行制御）と分割された doc プラグイン群を control マージで統一する。
+            // rsplug-doc・lazy loader・doc 分割群が1つの `_rsplug:doc`（+ 制御パック）に集約される。
+            let plugins = {
+                let plugins = std::mem::take(&mut self.ctl)
+                    .render(self.debug_loader, Arc::clone(&self.templates))
+                    .await?;
+                let mut heap: BinaryHeap<_> = plugins.into();
+                for doc in std::mem::take(&mut self.doc_plugins) {
+                    heap.push(doc);
+                }
+                MergePlanner::plan(&mut heap);
                 heap
             };
             for plugin in plugins {
                 self.insert(plugin);
             }
         }
+        // `target = "system"` のパッケージは先に system packpath へ公開し、ユーザ側の
+        // generation（entries・ftplugin インデックス）からは外す。ローダは 'packpath' の
+        // 末尾に足した system packpath から `packadd` する。
+        let system_ids = self.publish_system_packages().await?;
+        for ids in ft_pairs.values_mut() {
+            ids.retain(|id| !system_ids.contains(id));
+        }
+        ft_pairs.retain(|_, ids| !ids.is_empty());
+        let origins = self.origins.take();
+        let provenance = std::mem::take(&mut self.provenance);
+        let published = self.publish(packpath, ft_pairs).await?;
+        if published {
+            // 公開した generation の `lua/` モジュールが一意に解決されるかを確かめる。
+            // 検査は best-effort で、読めなくても install は失敗させない。
+            let opt = packpath.join("pack").join("_gen").join("opt");
+            let user_packages = provenance
+                .user_packages()
+                .filter(|(id, _)| !system_ids.contains(*id));
+            for collision in module_collisions(&opt, user_packages)
+                .await
+                .unwrap_or_default()
+            {
+                msg(Message::ModuleCollision {
+                    module: collision.module,
+                    plugins: collision.plugins,
+                });
+            }
+        }
+        if let Some(origins) = origins {
+            write_package_origins(packpath, origins).await?;
+        }
+        // publish 成功後（no-op 含む）に、現在の公開ツリーに対応する index へ置き換える。
+        provenance
+            .write(&packpath.join("pack").join("_gen"))
+            .await?;
+        Ok(published)
+    }
+
+    /// `target = "system"` のパッケージを `self.files` から取り出して system packpath に公開し、
+    /// その id を返す。system packpath の generation はこれらのパッケージだけを持つ。
+    async fn publish_system_packages(&mut self) -> io::Result<HashSet<String>> {
+        let files: HashMap<PluginIDStr, Files> = std::mem::take(&mut self.system_ids)
+            .into_iter()
+            .filter_map(|id| self.files.remove_entry(&id))
+            .collect();
+        let ids = files.keys().map(ToString::to_string).collect();
+        match &self.system_packpath {
+            Some(root) => {
+                let system = PackPlan {
+                    files,
+                    named: self.named.clone(),
+                    post_process: self.post_process.clone(),
+                    ..Default::default()
+                };
+                system.publish(root, BTreeMap::new()).await?;
+            }
+            None if files.is_empty() => {}
+            None => {
+                return Err(io::Error::other(
+                    "plugins with `target = \"system\"` need `--packpath system=<DIR>`",
+                ));
+            }
+        }
+        Ok(ids)
+    }
+
+    async fn publish(
+        self,
+        packpath: &Path,
+        ft_pairs: BTreeMap<String, Vec<String>>,
+    ) -> io::Result<GenerationPublished> {
         let gen_root = packpath.join("pack").join("_gen");
         tokio::fs::create_dir_all(&gen_root).await?;
         // Staging is private to this run. The global publication lock is acquired
@@ -2137,7 +3089,21 @@ impl PackPlan {
             files,
             ctl: _,
             doc_plugins: _,
+            merge_policy: _,
+            no_merge: _,
+            debug_loader: _,
+            templates: _,
+            origins: _,
+            provenance: _,
+            stable_names: _,
+            named,
+            system_packpath,
+            system_ids: _,
+            sparse,
+            start_ids,
+            post_process,
         } = self;
+        let pipeline = PostPipeline::new(post_process.as_deref().unwrap_or(PostStep::DEFAULT))?;
         let mut generation_entries: Vec<String> = files
             .iter()
             .map(|(id, _)| {
@@ -2148,6 +3114,15 @@ impl PackPlan {
             })
             .collect();
         generation_entries.sort();
+        let named: BTreeMap<String, String> = files
+            .keys()
+            .filter(|id| id.is_named())
+            .filter_map(|id| {
+                named
+                    .get(&**id)
+                    .map(|hashed| (id.to_string(), hashed.to_string()))
+            })
+            .collect();
         // R1: manifest の生成は publish 後（ftplugin インデックス構築の後）へ移動した。
         let mut control_ids: Vec<PluginIDStr> = files
             .iter()
@@ -2155,7 +3130,7 @@ impl PackPlan {
             .map(|(id, _)| id.clone())
             .collect();
         control_ids.sort();
-        let init_content = render_init(&control_ids);
+        let init_content = render_init(&control_ids, system_packpath.as_deref());
         let inventory_ftplugin_index = build_ft_index_from_inventories(&files, &ft_pairs);
         let desired_plan = inventory_ftplugin_index.as_ref().map(|ftplugin| {
             GenerationPlan::new(
@@ -2164,6 +3139,7 @@ impl PackPlan {
                 RuntimeManifest {
                     ftplugin: ftplugin.clone(),
                 },
+                named.clone(),
             )
         });
         // 全パッケージが既にある場合だけ、既存 generation と比較する。足りない package が
@@ -2173,9 +3149,11 @@ impl PackPlan {
                 &gen_root,
                 &control_ids,
                 &generation_entries,
+                &named,
                 &init_content,
                 packpath,
                 desired_plan.as_ref(),
+                pipeline.steps(),
             )
             .await
         {
@@ -2213,7 +3191,15 @@ impl PackPlan {
             id: Arc<str>,
             entries: Vec<(PathBuf, Arc<FileSource>)>,
             dir: Arc<Path>,
+            /// 安定名のパッケージなら、[`CONTENT_ID_FILE`] に記録する内容ハッシュの id。
+            content_id: Option<String>,
+            /// 疎な install で entries の代わりに置くスタブ（[`SPARSE_STUB_FILE`]）。
+            stub: Option<Vec<u8>>,
         }
+        // 同一内容の小ファイルを generation 内で 1 inode に集約する store（staging と共に破棄）。
+        let store = Arc::new(ContentStore::new(staging.join(".store")));
+        let copy_started = std::time::Instant::now();
+        let stats = Arc::new(PlacementStats::default());
         let (package_tx, package_rx) =
             tokio::sync::mpsc::channel::<PackageCopyJob>(copy_budget * 2);
         let shared_package_rx = Arc::new(tokio::sync::Mutex::new(package_rx));
@@ -2222,18 +3208,32 @@ impl PackPlan {
             crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::SpawnedWorker);
             let worker_rx = Arc::clone(&shared_package_rx);
             let yank_semaphore = yank_semaphore.clone();
+            let store = Arc::clone(&store);
+            let stats = Arc::clone(&stats);
             package_workers.spawn(async move {
                 loop {
                     let job = {
                         let mut rx = worker_rx.lock().await;
                         rx.recv().await
                     };
-                    let Some(PackageCopyJob { id, entries, dir }) = job else {
+                    let Some(PackageCopyJob {
+                        id,
+                        entries,
+                        dir,
+                        content_id,
+                        stub,
+                    }) = job
+                    else {
                         break;
                     };
+                    if let Some(stub) = stub {
+                        tokio::fs::create_dir_all(dir.as_ref()).await?;
+                        tokio::fs::write(dir.join(SPARSE_STUB_FILE), stub).await?;
+                        msg(Message::InstallDeferred(id.clone()));
+                    }
                     for (which, source) in entries {
                         let permit = yank_semaphore.acquire().await;
-                        let result = source.yank(&which, dir.as_ref()).await;
+                        let result = source.yank(&which, dir.as_ref(), &store, &stats).await;
                         let is_error = result.is_err();
                         permit.finish(is_error);
                         result?;
@@ -2242,28 +3242,57 @@ impl PackPlan {
                             which,
                         });
                     }
+                    if let Some(content_id) = content_id {
+                        tokio::fs::create_dir_all(dir.as_ref()).await?;
+                        tokio::fs::write(dir.join(CONTENT_ID_FILE), content_id).await?;
+                    }
                 }
                 Ok::<(), io::Error>(())
             });
         }
 
+        // `--sparse`: 遅延パッケージにはスタブだけを置き、中身は初回の読み込みで配置する。
+        // ftplugin インデックスを inventory から作れないときは公開ツリーを走査するので疎にしない。
+        let mut post_deferred = BTreeSet::new();
+        let sparse_helper = (sparse && inventory_ftplugin_index.is_some())
+            .then(std::env::current_exe)
+            .and_then(Result::ok);
         for (
             id,
             Files {
-                is_lazy_registration: _,
-                entries,
+                is_lazy_registration,
+                mut entries,
                 dotgit,
             },
         ) in files
         {
+            let content_id = named.get(&*id).cloned();
+            let stub = sparse_helper
+                .as_deref()
+                .filter(|_| !is_lazy_registration && !dotgit && !start_ids.contains(&id))
+                .and_then(|helper| sparse_stub(helper, &entries));
+            // dev プラグインは利用者の checkout への symlink として置くので、後処理がその先に
+            // 書き込まないよう対象から外す。スタブの後処理は materialize で行う。
+            if stub.is_some()
+                || entries.iter().any(|(_, source)| {
+                    matches!(source.as_ref(), FileSource::Directory { symlink: true, .. })
+                })
+            {
+                post_deferred.insert(String::from(&*id));
+            }
+            if stub.is_some() {
+                entries.clear();
+            }
             let id: Arc<str> = id.into();
             let published = gen_root.join("opt").join(id.as_ref());
             // 既存パッケージは内容ハッシュで識別（同じ id ≡ 同じ内容）なので再利用し copy を skip。
+            // 安定名のパッケージは記録した内容ハッシュが一致するときだけ再利用する。
             // 公開 opt/ には触らず、新規パッケージを staging に構築することで、copy 失敗が
             // 公開ツリーを壊さないようにする。
             if tokio::fs::symlink_metadata(&published)
                 .await
                 .is_ok_and(|metadata| metadata.is_dir() && !metadata.file_type().is_symlink())
+                && reusable(&published, content_id.as_deref(), pipeline.steps()).await
             {
                 msg(Message::InstallSkipped(id));
                 continue;
@@ -2283,15 +3312,28 @@ impl PackPlan {
             let dir: Arc<Path> = Arc::from(dir);
             crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::QueuedJob);
             package_tx
-                .send(PackageCopyJob { id, entries, dir })
+                .send(PackageCopyJob {
+                    id,
+                    entries,
+                    dir,
+                    content_id,
+                    stub,
+                })
                 .await
                 .map_err(|_| io::Error::other("package copy workers stopped"))?;
         }
         drop(package_tx);
         while let Some(result) = package_workers.join_next().await {
-            result.map_err(|e| io::Error::other(format!("package worker join failed: {e}")))??;
+            result.map_err(|e| io::Error::other(util::task::panicked("package worker")(e)))??;
         }
-        run_staged_helptags(&staging).await?;
+        let [cloned_bytes, cloned_dirs, copied_bytes] = stats.totals();
+        msg(Message::InstallPlaced {
+            elapsed: copy_started.elapsed(),
+            cloned_bytes,
+            cloned_dirs,
+            copied_bytes,
+        });
+        let mut post_records = run_staged_post_process(&staging, &pipeline, &post_deferred).await?;
         // The compatibility filesystem scan is also private planning work and
         // must not extend the publication lock window.
         let ftplugin_index = match inventory_ftplugin_index {
@@ -2314,9 +3356,11 @@ impl PackPlan {
             &gen_root,
             &control_ids,
             &generation_entries,
+            &named,
             &init_content,
             packpath,
             desired_plan.as_ref(),
+            pipeline.steps(),
         )
         .await
         {
@@ -2328,6 +3372,7 @@ impl PackPlan {
         // === Publish: staging で構築した新規パッケージを opt/ へ原子 rename で公開する。 ===
         // パッケージ id は内容ハッシュなので、staging にあるものは全て「新規」（既存は再利用され
         // staging に無い）で、opt/ との衝突はない。各 rename は POSIX 原子。
+        // 例外は中身の変わった安定名のパッケージで、旧版を退避してから置き換える。
         tokio::fs::create_dir_all(gen_root.join("opt")).await?;
         if let Ok(mut rd) = tokio::fs::read_dir(staging.join("opt")).await {
             while let Some(entry) = rd.next_entry().await? {
@@ -2342,8 +3387,21 @@ impl PackPlan {
                             destination.display()
                         )));
                     }
-                    tokio::fs::remove_dir_all(entry.path()).await?;
-                    continue;
+                    let content_id = name.to_str().and_then(|name| named.get(name));
+                    if reusable(
+                        &destination,
+                        content_id.map(String::as_str),
+                        pipeline.steps(),
+                    )
+                    .await
+                    {
+                        tokio::fs::remove_dir_all(entry.path()).await?;
+                        continue;
+                    }
+                    // 旧版は staging へ移し、staging と一緒に消す。置き換えの間だけパッケージが欠ける。
+                    let replaced = staging.join("replaced");
+                    tokio::fs::create_dir_all(&replaced).await?;
+                    tokio::fs::rename(&destination, replaced.join(&name)).await?;
                 }
                 crate::rsplug::perf::failpoint("package_rename_before")?;
                 tokio::fs::rename(entry.path(), destination).await?;
@@ -2357,8 +3415,17 @@ impl PackPlan {
             RuntimeManifest {
                 ftplugin: ftplugin_index.clone(),
             },
+            named,
         );
         let plan_id = plan.id();
+        // 再利用したパッケージの後処理は、パッケージに残した記録から引く。
+        for entry in &generation_entries {
+            if !post_records.contains_key(entry) {
+                let record = read_post_record(&gen_root.join(entry)).await;
+                post_records.insert(entry.clone(), record);
+            }
+        }
+        post_records.retain(|entry, _| generation_entries.contains(entry));
         let manifest = GenerationManifest {
             version: 2,
             entries: generation_entries,
@@ -2367,6 +3434,7 @@ impl PackPlan {
             runtime: RuntimeManifest {
                 ftplugin: ftplugin_index,
             },
+            post_process: post_records,
         };
         let manifest_content = serde_json::to_vec_pretty(&manifest).map_err(io::Error::other)?;
         let publication_name = desired_plan
@@ -2591,7 +3659,8 @@ mod tests {
     #[test]
     fn init_template_packadds_control_packages() {
         let control_id = b"control-package".plugin_id().as_str();
-        let script = String::from_utf8(render_init(std::slice::from_ref(&control_id))).unwrap();
+        let script =
+            String::from_utf8(render_init(std::slice::from_ref(&control_id), None)).unwrap();
 
         // The control id is emitted into the ids table and looped over with vim.cmd.packadd(id).
         assert!(
@@ -2606,7 +3675,7 @@ mod tests {
     fn init_template_emits_exact_packadd_block() {
         let a = b"aaaa".plugin_id().as_str();
         let b = b"bbbb".plugin_id().as_str();
-        let script = String::from_utf8(render_init(&[a.clone(), b.clone()])).unwrap();
+        let script = String::from_utf8(render_init(&[a.clone(), b.clone()], None)).unwrap();
         // locks in the exact ids-table shape; break whitespace here if the template changes.
         let actual = script
             .split("vim.opt.packpath:prepend(root)\n\n")
@@ -2624,7 +3693,7 @@ mod tests {
     #[test]
     fn init_template_resolves_symlink_and_goes_up_two_levels() {
         let id = b"gen".plugin_id().as_str();
-        let script = String::from_utf8(render_init(std::slice::from_ref(&id))).unwrap();
+        let script = String::from_utf8(render_init(std::slice::from_ref(&id), None)).unwrap();
         // init.lua is a symlink into generations/; resolve + :h:h recovers ~/.cache/rsplug
         // whether loaded through the symlink or directly as a generation file.
         assert!(
@@ -2642,7 +3711,7 @@ mod tests {
         // control_ids が空（プラグイン0件）のとき _rsplug ランタイムモジュールは
         // 生成されない。init.lua が無条件で require('_rsplug') すると nvim 起動が
         // クラッシュするため、require/startup ブロックを出力しない。
-        let script = String::from_utf8(render_init(&[])).unwrap();
+        let script = String::from_utf8(render_init(&[], None)).unwrap();
         assert!(
             !script.contains("require, '_rsplug'"),
             "empty control_ids must not require _rsplug: {script:?}"
@@ -2660,7 +3729,7 @@ mod tests {
     #[test]
     fn init_template_supports_rsplug_generation_override() {
         let id = b"gen".plugin_id().as_str();
-        let script = String::from_utf8(render_init(std::slice::from_ref(&id))).unwrap();
+        let script = String::from_utf8(render_init(std::slice::from_ref(&id), None)).unwrap();
         // Reads RSPLUG_GENERATION and prefers it over the default ids when valid.
         assert!(script.contains("vim.env.RSPLUG_GENERATION"));
         // Guards the override id: hex-only and exactly 32 chars (no path traversal).
@@ -2711,6 +3780,7 @@ mod tests {
                     )]),
                 )]),
             },
+            post_process: BTreeMap::new(),
         };
         let bytes = serde_json::to_vec(&manifest).unwrap();
         let back: GenerationManifest = serde_json::from_slice(&bytes).unwrap();
@@ -2749,11 +3819,13 @@ mod tests {
             vec![format!("opt/{id}")],
             std::slice::from_ref(&id),
             runtime.clone(),
+            BTreeMap::new(),
         );
         let second = GenerationPlan::new(
             vec![format!("opt/{id}")],
             std::slice::from_ref(&id),
-            runtime,
+            runtime.clone(),
+            BTreeMap::new(),
         );
         assert_eq!(first, second);
         assert_eq!(first.id(), second.id());
@@ -2761,8 +3833,20 @@ mod tests {
             vec![format!("opt/{id}")],
             std::slice::from_ref(&id),
             RuntimeManifest::default(),
+            BTreeMap::new(),
         );
         assert_ne!(first.id(), changed.id());
+        // 名前付きパッケージの中身が変われば、entries が同じでも別の generation。
+        let named = |content_id: &str| {
+            GenerationPlan::new(
+                vec![format!("opt/{id}"), "opt/owner__repo".to_string()],
+                std::slice::from_ref(&id),
+                runtime.clone(),
+                BTreeMap::from([("owner__repo".to_string(), content_id.to_string())]),
+            )
+        };
+        assert_ne!(named("a").id(), named("b").id());
+        assert!(!serde_json::to_string(&first).unwrap().contains("\"named\""));
     }
 
     const FT_ID: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
@@ -2859,6 +3943,7 @@ mod tests {
                         path: Arc::from(tmp.path().to_path_buf()),
                         inventory: Some(inventory),
                         handle: None,
+                        symlink: false,
                     }),
                 )],
                 dotgit: false,
@@ -2950,6 +4035,7 @@ mod tests {
                     path: Arc::from(PathBuf::from(dir)),
                     inventory: None,
                     handle: None,
+                    symlink: false,
                 }),
                 FileIdentity::RepoFile(RepoFileIdentity::new(snapshot, PathBuf::from(rel))),
                 MergeType::Conflict,
@@ -2964,7 +4050,8 @@ mod tests {
             files,
             script: SetupScript::default(),
             order: 0,
-            merge_enabled: true,
+            merge_policy: None,
+            target: PackTarget::User,
             is_lazy_registration: false,
             dotgit: false,
         }
@@ -2993,7 +4080,8 @@ mod tests {
             )])),
             script: SetupScript::default(),
             order,
-            merge_enabled: false,
+            merge_policy: Some(MergePolicy::Never),
+            target: PackTarget::User,
             is_lazy_registration: false,
             dotgit: false,
         };
@@ -3086,7 +4174,7 @@ mod tests {
                     path, item,
                 )])));
                 plugin.order = index % 4;
-                plugin.merge_enabled = index % 7 != 0;
+                plugin.merge_policy = (index % 7 == 0).then_some(MergePolicy::Never);
                 plugin
             })
             .collect()
@@ -3119,6 +4207,123 @@ mod tests {
         }
     }
 
+    /// 合成設定: lazy 種別の混在・`merge = false`・`merge = "aggressive"`・同一パスの衝突ファイルを含む。
+    fn synthetic_merge_config(plugins: usize) -> Vec<LoadedPlugin> {
+        const EVENTS: [&str; 3] = ["BufReadPre", "InsertEnter", "CmdlineEnter"];
+        (0..plugins)
+            .map(|index| {
+                let path = if index % 5 == 0 {
+                    PathBuf::from("plugin/shared.lua")
+                } else {
+                    PathBuf::from(format!("plugin/p{index}.lua"))
+                };
+                let data = format!("-- {index}\n").into_bytes();
+                let item = FileItem::new(
+                    Arc::new(FileSource::File {
+                        data: Cow::Owned(data.clone()),
+                    }),
+                    FileIdentity::GeneratedFile {
+                        path: path.clone(),
+                        data_hash: crate::rsplug::util::hash::digest_hash(&data),
+                    },
+                    MergeType::Conflict,
+                );
+                let mut plugin = synth(HowToPlaceFiles::CopyEachFile(BTreeMap::from([(
+                    path, item,
+                )])));
+                plugin.order = index % 4;
+                plugin.merge_policy = if index % 11 == 0 {
+                    Some(MergePolicy::Never)
+                } else if index % 13 == 0 {
+                    Some(MergePolicy::Aggressive)
+                } else {
+                    None
+                };
+                if index % 2 == 1 {
+                    let event = EVENTS[index % EVENTS.len()].parse().unwrap();
+                    plugin.lazy_type = LazyType::Opt(BTreeSet::from([LoadEvent::Autocmd(event)]));
+                }
+                plugin
+            })
+            .collect()
+    }
+
+    fn sorted_ids(heap: BinaryHeap<LoadedPlugin>) -> Vec<String> {
+        let mut ids = heap
+            .into_iter()
+            .map(|plugin| plugin.plugin_id().as_str().to_string())
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn merge_planner_matches_reference_with_fewer_attempts() {
+        use crate::rsplug::perf::{PerfGuard, PerfOp};
+
+        let _perf = PerfGuard::install();
+        let mut reference = synthetic_merge_config(300)
+            .into_iter()
+            .collect::<BinaryHeap<_>>();
+        LoadedPlugin::merge(&mut reference);
+        let reference_attempts = PerfGuard::count(PerfOp::MergeAttempt);
+
+        let mut planned = synthetic_merge_config(300)
+            .into_iter()
+            .collect::<BinaryHeap<_>>();
+        MergePlanner::plan(&mut planned);
+        let planned_attempts = PerfGuard::count(PerfOp::MergeAttempt) - reference_attempts;
+
+        assert_eq!(sorted_ids(planned), sorted_ids(reference));
+        assert!(
+            planned_attempts < reference_attempts,
+            "class partitioning must skip cross-class probes ({planned_attempts} >= {reference_attempts})"
+        );
+    }
+
+    /// Validation bench（非gating・ignored）: 合成設定のマージ計画を、単一 pass の参照実装
+    /// （before）とクラス分割版で比較し `target/merge_planner_bench.json` へ書き出す。
+    #[test]
+    #[ignore = "non-gating benchmark: run with --ignored"]
+    fn bench_merge_planner() {
+        use crate::rsplug::perf::{PerfGuard, PerfOp, median, write_bench_report};
+
+        const SAMPLES: usize = 5;
+        let mut cases = Vec::new();
+        for scale in [100usize, 300, 1000] {
+            let _perf = PerfGuard::install();
+            let mut before = Vec::with_capacity(SAMPLES);
+            let mut after = Vec::with_capacity(SAMPLES);
+            let mut before_attempts = 0;
+            let mut after_attempts = 0;
+            for _ in 0..SAMPLES {
+                let mut heap = synthetic_merge_config(scale)
+                    .into_iter()
+                    .collect::<BinaryHeap<_>>();
+                let attempts = PerfGuard::count(PerfOp::MergeAttempt);
+                let started = std::time::Instant::now();
+                LoadedPlugin::merge(&mut heap);
+                before.push(started.elapsed().as_nanos());
+                before_attempts = PerfGuard::count(PerfOp::MergeAttempt) - attempts;
+
+                let mut heap = synthetic_merge_config(scale)
+                    .into_iter()
+                    .collect::<BinaryHeap<_>>();
+                let attempts = PerfGuard::count(PerfOp::MergeAttempt);
+                let started = std::time::Instant::now();
+                MergePlanner::plan(&mut heap);
+                after.push(started.elapsed().as_nanos());
+                after_attempts = PerfGuard::count(PerfOp::MergeAttempt) - attempts;
+            }
+            cases.push(format!(
+                "    \"merge_{scale}\": {{\"scale\":{scale}, \"samples\":{SAMPLES}, \"median_ns\":{}, \"before_median_ns\":{}, \"merge_attempts\":{after_attempts}, \"before_merge_attempts\":{before_attempts}}}",
+                median(after),
+                median(before),
+            ));
+        }
+        write_bench_report("merge_planner", "merge_planner_bench.json", &cases);
+    }
+
     #[test]
     fn merge_disabled_start_user_plugins_are_not_merged() {
         let snapshot_a = snap("github.com/owner/a", b"rev-a");
@@ -3128,7 +4333,7 @@ mod tests {
             "plugin/a.lua",
             "/cache/a",
         )])));
-        disabled.merge_enabled = false;
+        disabled.merge_policy = Some(MergePolicy::Never);
         let enabled = synth(HowToPlaceFiles::CopyEachFile(BTreeMap::from([repo_file(
             snapshot_b,
             "plugin/b.lua",
@@ -3152,7 +4357,7 @@ mod tests {
             "/cache/a",
         )])));
         disabled.lazy_type = LazyType::Opt(Default::default());
-        disabled.merge_enabled = false;
+        disabled.merge_policy = Some(MergePolicy::Never);
         let mut enabled = synth(HowToPlaceFiles::CopyEachFile(BTreeMap::from([repo_file(
             snapshot_b,
             "plugin/b.lua",
@@ -3167,6 +4372,56 @@ mod tests {
         );
     }
 
+    #[test]
+    fn aggressive_opt_plugins_merge_across_triggers() {
+        let event = |name: &str| LoadEvent::Autocmd(name.parse().unwrap());
+        let mut a = synth(HowToPlaceFiles::CopyEachFile(BTreeMap::from([repo_file(
+            snap("github.com/owner/a", b"rev-a"),
+            "plugin/a.lua",
+            "/cache/a",
+        )])));
+        a.lazy_type = LazyType::Opt(BTreeSet::from([event("BufReadPre")]));
+        a.merge_policy = Some(MergePolicy::Aggressive);
+        let mut b = synth(HowToPlaceFiles::CopyEachFile(BTreeMap::from([repo_file(
+            snap("github.com/owner/b", b"rev-b"),
+            "plugin/b.lua",
+            "/cache/b",
+        )])));
+        b.lazy_type = LazyType::Opt(BTreeSet::from([event("InsertEnter")]));
+        b.merge_policy = Some(MergePolicy::Aggressive);
+
+        let (merged, rest) = a + b;
+        assert!(rest.is_none(), "aggressive opt plugins should merge");
+        assert_eq!(
+            merged.lazy_type,
+            LazyType::Opt(BTreeSet::from([event("BufReadPre"), event("InsertEnter")])),
+            "merged triggers must be the union of both sides"
+        );
+    }
+
+    #[test]
+    fn aggressive_never_mixes_start_opt_or_policies() {
+        let plugin = |name: &str, policy, lazy_type| {
+            let mut plugin = synth(HowToPlaceFiles::CopyEachFile(BTreeMap::from([repo_file(
+                snap(&format!("github.com/owner/{name}"), name.as_bytes()),
+                &format!("plugin/{name}.lua"),
+                &format!("/cache/{name}"),
+            )])));
+            plugin.merge_policy = policy;
+            plugin.lazy_type = lazy_type;
+            plugin
+        };
+        let opt = || LazyType::Opt(Default::default());
+
+        let (_, rest) = plugin("a", Some(MergePolicy::Aggressive), LazyType::Start)
+            + plugin("b", Some(MergePolicy::Aggressive), opt());
+        assert!(rest.is_some(), "aggressive must keep start and opt apart");
+
+        let (_, rest) =
+            plugin("a", Some(MergePolicy::Aggressive), opt()) + plugin("b", None, opt());
+        assert!(rest.is_some(), "different policies must not merge");
+    }
+
     #[test]
     fn merge_preserves_all_source_names() {
         // マージで片側の on_source 参照名 (source_name) を潰さないこと（Phase 1）。
@@ -3472,6 +4727,7 @@ mod tests {
                     path: Arc::from(snapshot_root.clone()),
                     inventory: None,
                     handle: None,
+                    symlink: false,
                 }),
                 FileIdentity::RepoFile(RepoFileIdentity::new(
                     snapshot,
@@ -3486,7 +4742,8 @@ mod tests {
             files: HowToPlaceFiles::CopyEachFile(files),
             script: SetupScript::default(),
             order: 0,
-            merge_enabled: true,
+            merge_policy: None,
+            target: PackTarget::User,
             is_lazy_registration: false,
             dotgit: true,
         };
@@ -3507,6 +4764,35 @@ mod tests {
         let _ = std::fs::remove_dir_all(&dir);
     }
 
+    #[tokio::test]
+    async fn placement_stats_count_only_their_own_copies() {
+        let tmp = tempfile::tempdir().unwrap();
+        let tree = |name: &str, len: usize| {
+            let src = tmp.path().join(name);
+            std::fs::create_dir_all(src.join("sub")).unwrap();
+            std::fs::write(src.join("sub/data"), vec![b'x'; len]).unwrap();
+            (src, tmp.path().join(format!("{name}-dst")))
+        };
+        let (src_a, dst_a) = tree("a", 3000);
+        let (src_b, dst_b) = tree("b", 5000);
+        let (a, b) = (Arc::<PlacementStats>::default(), Arc::default());
+        let (placed_a, placed_b) = tokio::join!(
+            copy_tree(&src_a, &dst_a, None, &a),
+            copy_tree(&src_b, &dst_b, None, &b),
+        );
+        placed_a.unwrap();
+        placed_b.unwrap();
+        for (stats, len) in [(a, 3000), (b, 5000)] {
+            let [cloned_bytes, cloned_dirs, copied_bytes] = stats.totals();
+            // macOS の reflink 戦略ではディレクトリごと clonefile する。
+            assert!(
+                cloned_dirs == 1 || cloned_bytes + copied_bytes == len,
+                "{:?}",
+                stats.totals()
+            );
+        }
+    }
+
     #[tokio::test]
     async fn copy_tree_preserves_files_dirs_and_symlinks() {
         let root = std::env::temp_dir().join(format!("rsplug-copytree-{}", std::process::id()));
@@ -3519,7 +4805,7 @@ mod tests {
         #[cfg(unix)]
         std::os::unix::fs::symlink("a.txt", src.join("link.txt")).unwrap();
 
-        copy_tree(&src, &dst).await.unwrap();
+        copy_tree(&src, &dst, None, &Arc::default()).await.unwrap();
 
         assert_eq!(std::fs::read(dst.join("a.txt")).unwrap(), b"hello");
         assert_eq!(std::fs::read(dst.join("sub/b.txt")).unwrap(), b"world");
@@ -3563,7 +4849,7 @@ mod tests {
         std::fs::write(src.join("gin/util.vim"), b"gin").unwrap();
         std::fs::write(src.join("README.md"), b"gin-readme").unwrap();
 
-        copy_tree(&src, &dst).await.unwrap();
+        copy_tree(&src, &dst, None, &Arc::default()).await.unwrap();
 
         // dst は元のファイルと src のファイルの両方（union）を持つ。
         assert_eq!(std::fs::read(dst.join("edisch.vim")).unwrap(), b"edisch");
@@ -3603,6 +4889,7 @@ mod tests {
             path: Arc::from(snapshot_root.clone()),
             inventory: None,
             handle: None,
+            symlink: false,
         });
         let files = BTreeMap::from([
             (
@@ -3634,7 +4921,8 @@ mod tests {
             files: HowToPlaceFiles::CopyEachFile(files),
             script: SetupScript::default(),
             order: 0,
-            merge_enabled: true,
+            merge_policy: None,
+            target: PackTarget::User,
             is_lazy_registration: false,
             dotgit: true,
         };
@@ -3737,8 +5025,8 @@ mod tests {
         let _ = std::fs::remove_dir_all(&dir);
     }
 
-    /// Phase 1 回帰防止: opt プラグイン（merge_enabled=true, デフォルト）はマージすること。
-    /// main では opt の merge_enabled ガードをスキップしていたが、Phase 1 で両方に適用した
+    /// Phase 1 回帰防止: opt プラグイン（merge ポリシー未指定, デフォルト）はマージすること。
+    /// main では opt の merge ガードをスキップしていたが、Phase 1 で両方に適用した
     /// ことで merge=true の opt マージまで壊れていないか検証する。
     #[test]
     fn opt_plugins_with_default_merge_still_merge() {
@@ -3750,6 +5038,287 @@ mod tests {
         let _ = std::fs::remove_dir_all(&dir);
     }
 
+    #[tokio::test]
+    async fn verbose_install_writes_package_origin_and_name_link() {
+        let tmp = tempfile::tempdir().unwrap();
+        let packpath = tmp.path().join("packpath");
+        let data: &'static [u8] = b"-- a\n";
+        let mut loaded = synth(HowToPlaceFiles::CopyEachFile(BTreeMap::from([(
+            PathBuf::from("plugin/a.lua"),
+            FileItem::new(
+                Arc::new(FileSource::File {
+                    data: Cow::Borrowed(data),
+                }),
+                FileIdentity::GeneratedFile {
+                    path: PathBuf::from("plugin/a.lua"),
+                    data_hash: crate::rsplug::util::hash::digest_hash(data),
+                },
+                MergeType::Conflict,
+            ),
+        )])));
+        loaded.source_names = BTreeSet::from(["a.nvim".to_string()]);
+        loaded.script.lua_after.insert("vim.g.a = 1".to_string());
+        let plugin_id = loaded.plugin_id();
+
+        let mut state = PackPlan::new().with_verbose_install(true);
+        state.insert(loaded);
+        state.install(&packpath).await.unwrap();
+
+        let pkg = packpath.join("pack/_gen/opt").join(plugin_id.as_str());
+        let named = packpath.join(BY_NAME_DIR).join("a.nvim");
+        let origin: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(named.join(PACKAGE_ORIGIN_FILE)).unwrap())
+                .unwrap();
+        assert_eq!(origin["names"], serde_json::json!(["a.nvim"]));
+        assert_eq!(origin["triggers"], serde_json::json!(["start"]));
+        assert_eq!(
+            origin["scripts"]["lua_after"],
+            serde_json::json!(["vim.g.a = 1"])
+        );
+        assert_eq!(
+            std::fs::canonicalize(named.join(PACKAGE_LINK)).unwrap(),
+            std::fs::canonicalize(&pkg).unwrap()
+        );
+        // 内容ハッシュで共有されるパッケージ自体には書き込まない。
+        assert!(!pkg.join(PACKAGE_ORIGIN_FILE).exists());
+    }
+
+    #[tokio::test]
+    async fn install_persists_provenance_for_owners() {
+        let tmp = tempfile::tempdir().unwrap();
+        let packpath = tmp.path().join("packpath");
+        let data: &'static [u8] = b"-- a\n";
+        let mut loaded = synth(HowToPlaceFiles::CopyEachFile(BTreeMap::from([(
+            PathBuf::from("plugin/a.lua"),
+            FileItem::new(
+                Arc::new(FileSource::File {
+                    data: Cow::Borrowed(data),
+                }),
+                FileIdentity::GeneratedFile {
+                    path: PathBuf::from("plugin/a.lua"),
+                    data_hash: crate::rsplug::util::hash::digest_hash(data),
+                },
+                MergeType::Conflict,
+            ),
+        )])));
+        loaded.source_names = BTreeSet::from(["a.nvim".to_string()]);
+        let plugin_id = loaded.plugin_id();
+
+        let mut state = PackPlan::new();
+        state.insert(loaded);
+        state.install(&packpath).await.unwrap();
+
+        let installed = packpath
+            .join("pack/_gen/opt")
+            .join(plugin_id.as_str())
+            .join("plugin/a.lua");
+        let owners = find_owners(&packpath, &installed).await.unwrap();
+        assert_eq!(owners.len(), 1);
+        assert_eq!(owners[0].package, plugin_id.as_str().to_string());
+        assert_eq!(owners[0].entry, PathBuf::from("plugin/a.lua"));
+        assert_eq!(owners[0].source, provenance::EntrySource::Generated);
+        assert_eq!(owners[0].names, vec!["a.nvim".to_string()]);
+        assert!(!owners[0].control);
+    }
+
+    #[tokio::test]
+    async fn emit_lua_writes_only_the_generated_loader() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("loader");
+        let plan = || {
+            let data: &'static [u8] = b"-- a\n";
+            let mut plan = PackPlan::new();
+            plan.insert(synth(HowToPlaceFiles::CopyEachFile(BTreeMap::from([(
+                PathBuf::from("plugin/a.lua"),
+                FileItem::new(
+                    Arc::new(FileSource::File {
+                        data: Cow::Borrowed(data),
+                    }),
+                    FileIdentity::GeneratedFile {
+                        path: PathBuf::from("plugin/a.lua"),
+                        data_hash: crate::rsplug::util::hash::digest_hash(data),
+                    },
+                    MergeType::Conflict,
+                ),
+            )]))));
+            plan
+        };
+
+        let written = plan().emit_lua(&out).await.unwrap();
+        assert!(written > 0);
+        assert!(out.join("lua/_rsplug/init.lua").is_file());
+        assert!(out.join(EMIT_LUA_MARKER).is_file());
+        assert!(!out.join("plugin/a.lua").exists());
+        assert!(!tmp.path().join("pack").exists());
+
+        // 前回の出力は置き換え、古いファイルを残さない。
+        std::fs::create_dir_all(out.join("plugin")).unwrap();
+        std::fs::write(out.join("plugin/stale.lua"), "").unwrap();
+        assert_eq!(plan().emit_lua(&out).await.unwrap(), written);
+        assert!(!out.join("plugin/stale.lua").exists());
+
+        // rsplug が書いたのではないディレクトリは消さない。
+        let foreign = tmp.path().join("dotfiles");
+        std::fs::create_dir_all(&foreign).unwrap();
+        std::fs::write(foreign.join("init.lua"), "").unwrap();
+        let err = plan().emit_lua(&foreign).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(foreign.join("init.lua").is_file());
+        assert_eq!(std::fs::read_dir(tmp.path()).unwrap().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn diff_loader_compares_against_the_published_control_package() {
+        let tmp = tempfile::tempdir().unwrap();
+        let packpath = tmp.path().join("app");
+        let plan = || {
+            let data: &'static [u8] = b"-- a\n";
+            let mut plan = PackPlan::new();
+            plan.insert(synth(HowToPlaceFiles::CopyEachFile(BTreeMap::from([(
+                PathBuf::from("plugin/a.lua"),
+                FileItem::new(
+                    Arc::new(FileSource::File {
+                        data: Cow::Borrowed(data),
+                    }),
+                    FileIdentity::GeneratedFile {
+                        path: PathBuf::from("plugin/a.lua"),
+                        data_hash: crate::rsplug::util::hash::digest_hash(data),
+                    },
+                    MergeType::Conflict,
+                ),
+            )]))));
+            plan
+        };
+
+        // 未公開なら生成物はすべて追加になる。
+        let fresh = plan().diff_loader(&packpath).await.unwrap();
+        assert!(fresh.files > 0);
+        assert!(fresh.patch.contains("+++ b/lua/_rsplug/init.lua"));
+        assert!(!packpath.exists());
+
+        plan().install(&packpath).await.unwrap();
+        let same = plan().diff_loader(&packpath).await.unwrap();
+        assert_eq!((same.files, same.patch.as_str()), (0, ""));
+
+        // 公開中のローダを書き換えると、その差だけが出る。
+        let installed = std::fs::read_dir(packpath.join("pack/_gen/opt"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path().join("lua/_rsplug/init.lua"))
+            .find(|path| path.is_file())
+            .unwrap();
+        let mut content = std::fs::read_to_string(&installed).unwrap();
+        content.push_str("\n-- stale\n");
+        // content store と hardlink を共有し得るので、書き換えずに置き換える。
+        std::fs::remove_file(&installed).unwrap();
+        std::fs::write(&installed, content).unwrap();
+        let changed = plan().diff_loader(&packpath).await.unwrap();
+        assert_eq!(changed.files, 1);
+        assert!(changed.patch.contains("\n--- stale\n"), "{}", changed.patch);
+    }
+
+    #[tokio::test]
+    async fn yank_rejects_paths_escaping_the_package() {
+        let tmp = tempfile::tempdir().unwrap();
+        let install_dir = tmp.path().join("pkg");
+        let store = Arc::new(ContentStore::new(tmp.path().join(".store")));
+        let source = FileSource::File {
+            data: Cow::Borrowed(b"-- escape\n"),
+        };
+        for path in [
+            "../escape.lua",
+            "lua/../../escape.lua",
+            "/tmp/rsplug-escape.lua",
+            "./plugin.lua",
+            "",
+        ] {
+            let err = source
+                .yank(path, &install_dir, &store, &Arc::default())
+                .await
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{path:?}");
+        }
+        assert!(!tmp.path().join("escape.lua").exists());
+        assert!(!install_dir.exists());
+    }
+
+    /// 先に配置された symlink（repo 内の `lua -> /outside` 等）を辿って書き出さない。
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn yank_refuses_to_write_through_a_placed_symlink() {
+        let tmp = tempfile::tempdir().unwrap();
+        let outside = tmp.path().join("outside");
+        let install_dir = tmp.path().join("pkg");
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::create_dir_all(&install_dir).unwrap();
+        std::os::unix::fs::symlink(&outside, install_dir.join("lua")).unwrap();
+        let store = Arc::new(ContentStore::new(tmp.path().join(".store")));
+
+        let file = FileSource::File {
+            data: Cow::Borrowed(b"-- evil\n"),
+        };
+        let err = file
+            .yank("lua/evil.lua", &install_dir, &store, &Arc::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let snapshot = tmp.path().join("snapshot");
+        std::fs::create_dir_all(snapshot.join("lua/nested")).unwrap();
+        std::fs::write(snapshot.join("lua/nested/evil.lua"), "-- evil\n").unwrap();
+        let dir = FileSource::Directory {
+            path: Arc::from(snapshot.as_path()),
+            inventory: None,
+            handle: None,
+            symlink: false,
+        };
+        let err = dir
+            .yank("lua", &install_dir, &store, &Arc::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(std::fs::read_dir(&outside).unwrap().next().is_none());
+    }
+
+    #[tokio::test]
+    async fn insert_drops_entries_escaping_the_package() {
+        let tmp = tempfile::tempdir().unwrap();
+        let packpath = tmp.path().join("packpath");
+        let file = |path: &str, data: &'static [u8]| {
+            (
+                PathBuf::from(path),
+                FileItem::new(
+                    Arc::new(FileSource::File {
+                        data: Cow::Borrowed(data),
+                    }),
+                    FileIdentity::GeneratedFile {
+                        path: PathBuf::from(path),
+                        data_hash: crate::rsplug::util::hash::digest_hash(data),
+                    },
+                    MergeType::Conflict,
+                ),
+            )
+        };
+        let loaded = synth(HowToPlaceFiles::CopyEachFile(BTreeMap::from([
+            file("../escape.lua", b"-- escape\n"),
+            file("plugin/a.lua", b"-- a\n"),
+        ])));
+        // id は落とした後の中身から決まる。
+        let plugin_id = synth(HowToPlaceFiles::CopyEachFile(BTreeMap::from([file(
+            "plugin/a.lua",
+            b"-- a\n",
+        )])))
+        .plugin_id();
+        assert!(loaded.plugin_id() != plugin_id);
+
+        let mut state = PackPlan::new();
+        state.insert(loaded);
+        state.install(&packpath).await.unwrap();
+
+        let opt = packpath.join("pack/_gen/opt");
+        assert!(opt.join(plugin_id.as_str()).join("plugin/a.lua").is_file());
+        assert!(!opt.join("escape.lua").exists());
+    }
+
     /// Phase 6c データロス回帰テスト: 同 path の sealed-dir（`lua`）を子 disjoint で merge し、
     /// install 後に**両方の** repo の子が pack に届くことを検証する。
     /// 旧 `files.extend` は片側を上書きして消していた。
@@ -4016,6 +5585,7 @@ mod tests {
                     path: Arc::from(src_root.to_path_buf()),
                     inventory: None,
                     handle: None,
+                    symlink: false,
                 }),
                 FileIdentity::RepoFile(RepoFileIdentity::new(snapshot, PathBuf::from(rel))),
                 MergeType::Conflict,
@@ -4078,6 +5648,207 @@ mod tests {
         assert!(no_staging_dirs(&genpath), "no staging dirs must remain");
     }
 
+    /// `target = "system"` のパッケージは system packpath に置き、ユーザ側のローダがそれを
+    /// 'packpath' に足す。ユーザのパッケージとは併合しない。
+    #[tokio::test]
+    async fn system_packages_are_published_to_the_system_packpath() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = dir.path().join("user");
+        let system = dir.path().join("system");
+        let snap_root = dir.path().join("snap");
+        std::fs::create_dir_all(snap_root.join("plugin")).unwrap();
+        std::fs::write(snap_root.join("plugin/a.lua"), b"-- a\n").unwrap();
+        std::fs::write(snap_root.join("plugin/b.lua"), b"-- b\n").unwrap();
+        let plugins = || {
+            let user_plugin =
+                one_file_plugin("github.com/owner/a", b"rev-a", "plugin/a.lua", &snap_root);
+            let mut system_plugin =
+                one_file_plugin("github.com/owner/b", b"rev-b", "plugin/b.lua", &snap_root);
+            system_plugin.target = PackTarget::System;
+            (user_plugin, system_plugin)
+        };
+
+        let (user_plugin, system_plugin) = plugins();
+        assert!((user_plugin + system_plugin).1.is_some());
+
+        let (user_plugin, system_plugin) = plugins();
+        let mut state = PackPlan::new();
+        state.insert(user_plugin);
+        state.insert(system_plugin);
+        assert!(state.has_system_packages());
+        assert!(state.install(&user).await.is_err());
+        assert!(!user.join("init.lua").exists());
+
+        let (user_plugin, system_plugin) = plugins();
+        let user_id = user_plugin.plugin_id().as_str().to_string();
+        let system_id = system_plugin.plugin_id().as_str().to_string();
+        let mut state = PackPlan::new().with_system_packpath(Some(system.clone()));
+        state.insert(user_plugin);
+        state.insert(system_plugin);
+        assert!(state.install(&user).await.unwrap());
+
+        let user_opt = user.join("pack/_gen/opt");
+        let system_opt = system.join("pack/_gen/opt");
+        assert!(user_opt.join(&user_id).join("plugin/a.lua").is_file());
+        assert!(!user_opt.join(&system_id).exists());
+        assert!(system_opt.join(&system_id).join("plugin/b.lua").is_file());
+        assert!(!system_opt.join(&user_id).exists());
+        let init = std::fs::read_to_string(user.join("init.lua")).unwrap();
+        assert!(init.contains(&format!(
+            "vim.opt.packpath:append({})",
+            lua_string(system.display())
+        )));
+    }
+
+    /// `--stable-names` では単独 repo のパッケージを `opt/<owner>__<repo>` に置き、rev が
+    /// 変われば同じ名前のまま中身を置き換える。
+    #[tokio::test]
+    async fn stable_names_replace_the_package_when_its_content_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let packpath = dir.path().to_path_buf();
+        let genpath = packpath.join("pack/_gen");
+        let snap_root = dir.path().join("snap");
+        std::fs::create_dir_all(snap_root.join("plugin")).unwrap();
+        std::fs::write(snap_root.join("plugin/a.lua"), b"-- a\n").unwrap();
+        let published = genpath.join("opt/owner__a");
+
+        let plugin = one_file_plugin("github.com/owner/a", b"rev-a", "plugin/a.lua", &snap_root);
+        assert_eq!(plugin.stable_name().as_deref(), Some("owner__a"));
+        let first_id = plugin.plugin_id().as_str().to_string();
+        let mut state = PackPlan::new().with_stable_names(true);
+        state.insert(plugin);
+        assert!(state.install(&packpath).await.unwrap());
+        assert_eq!(
+            std::fs::read(published.join("plugin/a.lua")).unwrap(),
+            b"-- a\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(published.join(CONTENT_ID_FILE)).unwrap(),
+            first_id
+        );
+        assert!(!genpath.join("opt").join(&first_id).exists());
+
+        // 同じ内容なら再公開しない。
+        let mut same = PackPlan::new().with_stable_names(true);
+        same.insert(one_file_plugin(
+            "github.com/owner/a",
+            b"rev-a",
+            "plugin/a.lua",
+            &snap_root,
+        ));
+        assert!(!same.install(&packpath).await.unwrap());
+
+        std::fs::write(snap_root.join("plugin/a.lua"), b"-- b\n").unwrap();
+        let plugin = one_file_plugin("github.com/owner/a", b"rev-b", "plugin/a.lua", &snap_root);
+        let second_id = plugin.plugin_id().as_str().to_string();
+        let mut state = PackPlan::new().with_stable_names(true);
+        state.insert(plugin);
+        assert!(state.install(&packpath).await.unwrap());
+        assert_eq!(
+            std::fs::read(published.join("plugin/a.lua")).unwrap(),
+            b"-- b\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(published.join(CONTENT_ID_FILE)).unwrap(),
+            second_id
+        );
+        assert!(no_staging_dirs(&genpath), "no staging dirs must remain");
+    }
+
+    #[test]
+    fn stable_names_fall_back_to_hashes_for_merged_and_colliding_plugins() {
+        let a = || {
+            synth(HowToPlaceFiles::CopyEachFile(BTreeMap::from([repo_file(
+                snap("github.com/owner/a", b"1"),
+                "plugin/a.lua",
+                "/a",
+            )])))
+        };
+        let b = synth(HowToPlaceFiles::CopyEachFile(BTreeMap::from([repo_file(
+            snap("github.com/owner/b", b"1"),
+            "plugin/b.lua",
+            "/b",
+        )])));
+        let mut merged = a();
+        let HowToPlaceFiles::CopyEachFile(files) = &mut merged.files;
+        let HowToPlaceFiles::CopyEachFile(other) = b.files;
+        files.extend(other);
+        assert_eq!(merged.stable_name(), None);
+
+        let mut plan = PackPlan::new().with_stable_names(true);
+        assert_eq!(&*plan.package_id(&a()), "owner__a");
+        // 同じ中身は同じ名前、同じ repo の別の中身はハッシュに戻る。
+        assert_eq!(&*plan.package_id(&a()), "owner__a");
+        let other_rev = synth(HowToPlaceFiles::CopyEachFile(BTreeMap::from([repo_file(
+            snap("github.com/owner/a", b"2"),
+            "plugin/a.lua",
+            "/a",
+        )])));
+        let hashed = other_rev.plugin_id().as_str();
+        assert_eq!(&*plan.package_id(&other_rev), &*hashed);
+        assert!(!PackPlan::new().package_id(&a()).is_named());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn install_hard_links_identical_files_across_plugins() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let packpath = dir.path().join("packpath");
+        let snap_a = dir.path().join("snap-a");
+        let snap_b = dir.path().join("snap-b");
+        for (snap, name) in [(&snap_a, "a"), (&snap_b, "b")] {
+            std::fs::create_dir_all(snap.join("plugin")).unwrap();
+            std::fs::write(snap.join(format!("plugin/{name}.lua")), b"-- MIT License\n").unwrap();
+        }
+
+        let mut state = PackPlan::new();
+        state.insert(one_file_plugin(
+            "github.com/owner/a",
+            b"rev-a",
+            "plugin/a.lua",
+            &snap_a,
+        ));
+        state.insert(one_file_plugin(
+            "github.com/owner/b",
+            b"rev-b",
+            "plugin/b.lua",
+            &snap_b,
+        ));
+        state.install(&packpath).await.unwrap();
+
+        let mut placed = Vec::new();
+        for package in std::fs::read_dir(packpath.join("pack/_gen/opt")).unwrap() {
+            for name in ["a", "b"] {
+                let path = package
+                    .as_ref()
+                    .unwrap()
+                    .path()
+                    .join(format!("plugin/{name}.lua"));
+                if let Ok(meta) = std::fs::metadata(&path) {
+                    placed.push(meta);
+                }
+            }
+        }
+        assert_eq!(placed.len(), 2);
+        assert_eq!(
+            placed[0].ino(),
+            placed[1].ino(),
+            "identical content must share one inode"
+        );
+        assert!(no_staging_dirs(&packpath.join("pack/_gen")));
+
+        // snapshot 側の編集は公開 pack に波及しない（リンクは generation 内に閉じる）。
+        std::fs::write(snap_a.join("plugin/a.lua"), b"-- CHANGED\n").unwrap();
+        assert_ne!(
+            std::fs::metadata(snap_a.join("plugin/a.lua"))
+                .unwrap()
+                .ino(),
+            placed[0].ino()
+        );
+    }
+
     #[tokio::test]
     async fn concurrent_identical_publications_have_one_winner() {
         let _perf = crate::rsplug::perf::PerfGuard::install();
@@ -4177,6 +5948,64 @@ mod tests {
         assert!(no_staging_dirs(&packpath.join("pack/_gen")));
     }
 
+    #[tokio::test]
+    async fn prune_packages_keeps_only_the_published_generation() {
+        let dir = tempfile::tempdir().unwrap();
+        let packpath = dir.path().join("packpath");
+        assert!(prune_packages(&packpath, false).await.unwrap().is_empty());
+        let plan = |name: &str| {
+            let snapshot = dir.path().join(name);
+            std::fs::create_dir_all(snapshot.join("plugin")).unwrap();
+            std::fs::write(snapshot.join(format!("plugin/{name}.lua")), name).unwrap();
+            let mut plan = PackPlan::new();
+            plan.insert(one_file_plugin(
+                &format!("github.com/example/{name}"),
+                name.as_bytes(),
+                &format!("plugin/{name}.lua"),
+                &snapshot,
+            ));
+            plan
+        };
+        plan("first").install(&packpath).await.unwrap();
+        plan("second").install(&packpath).await.unwrap();
+        let opt = packpath.join("pack/_gen/opt");
+        let packages = || std::fs::read_dir(&opt).unwrap().count();
+        let before = packages();
+
+        let planned = prune_packages(&packpath, true).await.unwrap();
+        assert!(!planned.is_empty());
+        assert!(
+            planned
+                .iter()
+                .all(|package| package.starts_with("pack/_gen/opt"))
+        );
+        assert_eq!(packages(), before);
+        let removed = prune_packages(&packpath, false).await.unwrap();
+        assert_eq!(removed, planned);
+        assert_eq!(packages(), before - removed.len());
+        assert!(
+            std::fs::read_dir(&opt).unwrap().any(|entry| entry
+                .unwrap()
+                .path()
+                .join("plugin/second.lua")
+                .is_file())
+        );
+        assert_eq!(
+            read_generation_registry(&packpath.join("pack/_gen"))
+                .await
+                .len(),
+            1
+        );
+        assert_eq!(
+            std::fs::read_dir(packpath.join("generations"))
+                .unwrap()
+                .count(),
+            1
+        );
+        assert!(packpath.join("init.lua").exists());
+        assert!(prune_packages(&packpath, false).await.unwrap().is_empty());
+    }
+
     #[tokio::test]
     async fn install_skips_publication_when_generation_is_unchanged() {
         let dir = tempfile::tempdir().unwrap();
@@ -1,6 +1,6 @@
 use std::{
     borrow::Cow,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     ffi::OsStr,
     path::{Path, PathBuf},
     str::FromStr,
@@ -28,6 +28,10 @@ mod assembly;
 mod build;
 #[path = "inventory.rs"]
 mod inventory;
+#[path = "source_backend.rs"]
+mod source_backend;
+
+use source_backend::*;
 
 /// 設定を構成する基本単位
 pub struct Plugin {
@@ -43,8 +47,8 @@ pub struct Plugin {
     pub merge: MergeConfig,
     /// `depends` で指定された依存プラグインのキャッシュ相対パス
     pub dependency_cachedirs: Vec<PathBuf>,
-    /// マージを許可するかどうか（TOML の `merge` フィールドから設定）
-    pub merge_enabled: bool,
+    /// 併合ポリシー（TOML の `merge` フィールドから設定）。`None` はグローバル設定に従う。
+    pub merge_policy: Option<MergePolicy>,
     /// DAGトポロジカル順。controlled startup の順序維持に使う。
     pub order: usize,
     /// 内部 id（BFS 依存スケジューリング用）。`plugin_id`（`LoadedPlugin` の Hash）には
@@ -118,6 +122,15 @@ impl RepoSource {
         }
     }
 
+    /// rev を差し替えた取得元。
+    pub(crate) fn with_rev(self, rev: impl Into<Arc<str>>) -> Self {
+        let rev = Some(rev.into());
+        match self {
+            RepoSource::GitHub { owner, repo, .. } => RepoSource::GitHub { owner, repo, rev },
+            RepoSource::Git { url, .. } => RepoSource::Git { url, rev },
+        }
+    }
+
     /// Canonical repository identity（PLANS「Model and repository identity」）。
     /// `host[:port]/path` 形式で、host は小文字化、デフォルトポート・userinfo・scheme・
     /// 末尾 `.git` は削除される。GitHub shorthand は `github.com/owner/repo`。
@@ -141,6 +154,15 @@ impl RepoSource {
         path
     }
 
+    /// clone 時のディレクトリ名（GitHub は repo 名、URL は末尾 segment）。
+    /// `name` 未指定時の表示名と dev checkout の探索名に使う。
+    pub(crate) fn basename(&self) -> &str {
+        match self {
+            RepoSource::GitHub { repo, .. } => repo.as_ref(),
+            RepoSource::Git { url, .. } => repo_basename(url.as_ref()),
+        }
+    }
+
     /// token 認証の対象となる GitHub HTTPS URL かどうか。
     /// `GitHub` バリアントは常に true（`github::url()` が HTTPS を生成）。
     /// `Git` バリアントは `https://github.com/` で始まる場合 true。
@@ -180,6 +202,92 @@ pub(super) fn snapshot_root(repo_root: &Path, snapshot_key: &str) -> PathBuf {
 pub(super) const LATEST_SNAPSHOT_FILE: &str = "latest-snapshot";
 static LATEST_INDEX_TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
 
+// === --offline preflight ===================================================
+// `--offline` は lockfile とキャッシュだけで pack を組み立てる。load を始める前に全 repo を
+// 検査し、足りないものを一度に列挙して、pack・cache・lock のどれにも触れずに中断する。
+
+/// `--offline` の事前検査で見つかった、キャッシュから復元できない repo。
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OfflineMissing {
+    /// lockfile に完全なハッシュの rev が無い。
+    LockEntry { canonical: String },
+    /// lock の rev の snapshot も、source.git 内の object も無い。
+    Object { canonical: String, rev: String },
+}
+
+impl std::fmt::Display for OfflineMissing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OfflineMissing::LockEntry { canonical } => {
+                write!(f, "{canonical}: no locked revision in the lockfile")
+            }
+            OfflineMissing::Object { canonical, rev } => {
+                write!(
+                    f,
+                    "{canonical}: {rev} is neither a cached snapshot nor in source.git"
+                )
+            }
+        }
+    }
+}
+
+/// 設定中の全 repo について、lock の rev が exact snapshot か source.git の object として
+/// `cache_dir` にあるかを調べ、無いものを返す。`dev` と script-only は対象外。読むだけで
+/// catalog の index 修復以外は何も書き換えない。
+pub async fn offline_preflight(
+    config: &Config,
+    cache_dir: &Path,
+    locked: &BTreeMap<String, super::lockfile::LockedResource>,
+) -> Result<Vec<OfflineMissing>, Error> {
+    let catalogs = SnapshotCatalogCache::new();
+    let mut missing = BTreeSet::new();
+    for plugin in &config.plugins {
+        let Some(repo) = plugin.cache.repo.as_ref() else {
+            continue;
+        };
+        if plugin.cache.dev {
+            continue;
+        }
+        let canonical = repo.canonical();
+        let Some(rev) = locked
+            .get(&canonical)
+            .map(|entry| entry.rev.as_str())
+            .filter(|rev| util::github::is_full_hex_hash(rev))
+        else {
+            missing.insert(OfflineMissing::LockEntry { canonical });
+            continue;
+        };
+        // load_early と同じ exact key（build/lua_build/patches 込み）。あれば source.git は読まない。
+        let patches = build::Patches::read(&plugin.cache.patches, &Arc::from(repo.url())).await?;
+        let key = RepoSnapshotIdentity::new(
+            repo.default_cachedir(),
+            rev.as_bytes().to_vec(),
+            None,
+            Arc::from(plugin.cache.build.as_slice()),
+            plugin.cache.lua_build.as_deref().map(Into::into),
+        )
+        .with_patches(patches.digest())
+        .snapshot_key();
+        let r_root = repo_root(cache_dir, repo);
+        let catalog = catalogs.get(r_root.clone(), canonical.clone()).await;
+        if catalog.contains_exact_key(&key).await {
+            continue;
+        }
+        let oid = Oid::from_str(rev)?;
+        let cached = match util::git::open_source(source_git_dir(&r_root)).await {
+            Ok(source) => source.contains_oid(oid).await?,
+            Err(_) => false,
+        };
+        if !cached {
+            missing.insert(OfflineMissing::Object {
+                canonical,
+                rev: rev.to_string(),
+            });
+        }
+    }
+    Ok(missing.into_iter().collect())
+}
+
 // === SnapshotCatalog (PLANS U1) ============================================
 // 1 canonical repository の snapshot 一覧を O(1) fast path で引く catalog。
 // `<repo>/latest-snapshot` index が有効なら worktrees/ を scan せずに最新 snapshot を特定
@@ -294,6 +402,16 @@ impl SnapshotCatalog {
             .map(|l| snapshot_root(&self.repo_root, &l.key))
     }
 
+    /// 最新 snapshot が build 生成物を含まない素の checkout ならその絶対パス。
+    /// build variant（`<oid>__...`）は生成物が untracked として現れるため dirty 検査の対象外。
+    async fn latest_unbuilt_dir(&self) -> Option<PathBuf> {
+        let res = self.ensure().await;
+        res.latest
+            .as_ref()
+            .filter(|l| !l.key.contains("__"))
+            .map(|l| snapshot_root(&self.repo_root, &l.key))
+    }
+
     /// exact な snapshot key が存在するか (U1 step 7)。広域 OID prefix ではなく build-variant
     /// 込みの exact key で判定する。valid fast path で最新以外の key は1回の targeted stat で
     /// 確定する（read_dir scan ではない）。
@@ -333,11 +451,11 @@ impl SnapshotCatalog {
 type InventoryCell = Arc<tokio::sync::OnceCell<Option<Arc<SnapshotManifest>>>>;
 type ResolutionResult = Result<(Oid, ResolutionBackend), Arc<str>>;
 type ResolutionCell = Arc<tokio::sync::OnceCell<ResolutionResult>>;
-type ResolutionKey = (String, Option<String>);
-type AcquisitionResult = Result<bool, Arc<str>>;
+pub(crate) type ResolutionKey = (String, Option<String>);
+type AcquisitionResult = Result<bool, Arc<Error>>;
 type AcquisitionCell = Arc<tokio::sync::OnceCell<AcquisitionResult>>;
 type AcquisitionKey = (PathBuf, String, bool);
-type MaterializationResult = Result<Option<MaterializedSnapshot>, Arc<str>>;
+type MaterializationResult = Result<Option<MaterializedSnapshot>, Arc<Error>>;
 type MaterializationCell = Arc<tokio::sync::OnceCell<MaterializationResult>>;
 type BuildResult = Result<(), Arc<str>>;
 type BuildCell = Arc<tokio::sync::OnceCell<BuildResult>>;
@@ -414,6 +532,51 @@ pub struct SnapshotCatalogCache {
     source_git_locks: std::sync::Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>,
     materialize_locks: std::sync::Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>,
     build_locks: std::sync::Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>,
+    /// `rsplug resolve` で解決済みの `(canonical, rev)`。リモートに問い合わせない。
+    preresolved: HashSet<ResolutionKey>,
+    /// この run でネットワークから取得したデータ（`--record` 用）。取得順。
+    fetched: std::sync::Mutex<Vec<FetchedData>>,
+    /// `rsplug plan`: 取得・materialize・build をせずに記録した予定。`None` なら通常の run。
+    planned: Option<std::sync::Mutex<Vec<PlannedFetch>>>,
+    /// 取得元の種類ごとの backend。
+    backends: SourceBackends,
+}
+
+/// この run で解決した `(canonical, rev)` の commit と、その解決手段（`--record` 用）。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedRemote {
+    pub canonical: String,
+    pub rev: Option<String>,
+    pub oid: String,
+    /// `api`（GitHub REST API）・`git`（ref 広告・ls-remote）・`preresolved`（`rsplug resolve`
+    /// や GraphQL の一括解決、commit 固定の rev）のいずれか。
+    pub via: &'static str,
+}
+
+/// この run でネットワークから取得した commit と、その置き場所（`--record` 用）。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FetchedData {
+    pub canonical: String,
+    pub oid: String,
+    /// git fetch なら source.git、tarball なら展開先の snapshot root。
+    pub location: PathBuf,
+    /// GitHub の tarball を展開したか。
+    pub tarball: bool,
+}
+
+/// `rsplug plan` で、実行すれば snapshot を用意するはずだった repo。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlannedFetch {
+    pub canonical: String,
+    pub oid: String,
+    /// `tarball`（GitHub の tarball）・`git`（source.git への fetch）。source.git に commit が
+    /// あり、ネットワークに触れずに materialize できるなら `None`。
+    pub fetch: Option<&'static str>,
+    /// 新しい snapshot で実行する `build` コマンド。
+    pub build: Vec<String>,
+    pub lua_build: Option<String>,
+    /// 当てる patch の数。
+    pub patches: usize,
 }
 
 /// Scheduler-owned repository job registry. The historical cache name remains
@@ -427,6 +590,90 @@ impl SnapshotCatalogCache {
         Self::default()
     }
 
+    /// `(canonical, rev)` → commit の解決結果を持たせて作る。リモート解決が必要になった
+    /// repo はここから commit を引き、ls-remote・API へ問い合わせない。
+    pub(crate) fn with_resolutions(
+        resolutions: impl IntoIterator<Item = (ResolutionKey, Oid)>,
+    ) -> Self {
+        let mut preresolved = HashSet::new();
+        let mut cells = HashMap::new();
+        for (key, oid) in resolutions {
+            preresolved.insert(key.clone());
+            let cell = tokio::sync::OnceCell::new_with(Some(Ok((oid, ResolutionBackend::Locked))));
+            cells.insert(key, Arc::new(cell));
+        }
+        Self {
+            resolutions: tokio::sync::Mutex::new(cells),
+            preresolved,
+            ..Self::default()
+        }
+    }
+
+    /// `rsplug plan` 用にする。snapshot が無い repo は取得・materialize・build せず、
+    /// 予定を [`Self::planned_fetches`] に記録して読み込みを飛ばす。
+    pub(crate) fn with_planning(mut self) -> Self {
+        self.planned = Some(Default::default());
+        self
+    }
+
+    /// 取得元の backend を追加する。既存の backend と同じ取得元を受け持つなら、こちらが優先される。
+    #[allow(dead_code)]
+    pub(crate) fn with_backend(mut self, backend: Arc<dyn SourceBackend>) -> Self {
+        self.backends.register(backend);
+        self
+    }
+
+    /// [`Self::with_planning`] で記録した予定。canonical 順。
+    pub fn planned_fetches(&self) -> Vec<PlannedFetch> {
+        let mut planned = self
+            .planned
+            .as_ref()
+            .map(|planned| planned.lock().unwrap().clone())
+            .unwrap_or_default();
+        planned.sort_by(|a, b| (&a.canonical, &a.oid).cmp(&(&b.canonical, &b.oid)));
+        planned.dedup();
+        planned
+    }
+
+    /// この run で解決済みの rev。`(canonical, rev)` 順。
+    pub async fn resolved_remotes(&self) -> Vec<ResolvedRemote> {
+        let resolutions = self.resolutions.lock().await;
+        let mut resolved: Vec<_> = resolutions
+            .iter()
+            .filter_map(|((canonical, rev), cell)| {
+                let (oid, backend) = cell.get()?.as_ref().ok()?;
+                let via = match backend {
+                    ResolutionBackend::Api => "api",
+                    ResolutionBackend::Git => "git",
+                    ResolutionBackend::Locked | ResolutionBackend::Cached => "preresolved",
+                };
+                Some(ResolvedRemote {
+                    canonical: canonical.clone(),
+                    rev: rev.clone(),
+                    oid: oid.to_string(),
+                    via,
+                })
+            })
+            .collect();
+        resolved.sort_by(|a, b| (&a.canonical, &a.rev).cmp(&(&b.canonical, &b.rev)));
+        resolved
+    }
+
+    /// この run でネットワークから取得したデータ。取得順。
+    pub fn fetched_data(&self) -> Vec<FetchedData> {
+        self.fetched.lock().unwrap().clone()
+    }
+
+    fn record_fetch(&self, data: FetchedData) {
+        self.fetched.lock().unwrap().push(data);
+    }
+
+    /// `canonical` の `rev` が [`Self::with_resolutions`] で解決済みか。
+    pub(crate) fn is_preresolved(&self, canonical: &str, rev: Option<&str>) -> bool {
+        self.preresolved
+            .contains(&(canonical.to_string(), rev.map(str::to_owned)))
+    }
+
     /// canonical で catalog を取得（無ければ作成）。解決は各 catalog が lazy (OnceCell) に行う。
     /// Mutex は map 操作のみで保持し（FS I/O 中は保持しない）。
     pub(crate) async fn get(&self, repo_root: PathBuf, canonical: String) -> Arc<SnapshotCatalog> {
@@ -461,6 +708,13 @@ impl SnapshotCatalogCache {
         .clone()
     }
 
+    /// `root` の inventory をこの run 内で `manifest` に差し替える。直前に構築・書き込んだ manifest を
+    /// 組み立てでそのまま使い、ディスクからの再読込（や読めなかった結果の記憶）を避ける。
+    async fn replace_inventory(&self, root: PathBuf, manifest: SnapshotManifest) {
+        let cell = tokio::sync::OnceCell::new_with(Some(Some(Arc::new(manifest))));
+        self.inventories.lock().await.insert(root, Arc::new(cell));
+    }
+
     async fn resolution_cell(&self, canonical: &str, rev: &Option<Arc<str>>) -> ResolutionCell {
         let mut resolutions = self.resolutions.lock().await;
         resolutions
@@ -704,6 +958,18 @@ impl FromStr for RepoSource {
 struct ResolvedGraph {
     /// トポロジカル順 + (depth, original_index) tiebreak で並んだ解決済みノード。
     nodes: Vec<ResolvedNode>,
+    /// 起動時に読まれる依存元のために格上げされ、遅延トリガが無効になったプラグイン。
+    upgrades: Vec<LazyUpgrade>,
+}
+
+/// 遅延トリガを持つプラグインが、起動時に読まれる依存元のために起動時読み込みになった。
+struct LazyUpgrade {
+    /// 格上げされたプラグインの内部 id。
+    plugin: String,
+    /// 格上げの原因になった（最初の）依存元の内部 id。
+    dependent: String,
+    /// 無効になった自身のトリガ（`on_cmd:Foo` など）。
+    triggers: Vec<String>,
 }
 
 /// 1つの解決済みプラグインノード。DAG 解決由来のフィールド（`order`,
@@ -723,7 +989,7 @@ struct ResolvedNode {
     cache: CacheConfig,
     script: SetupScript,
     merge: MergeConfig,
-    merge_enabled: bool,
+    merge_policy: Option<MergePolicy>,
     /// 内部 id（BFS 用）。Plugin への移行のみ。
     id: String,
     /// 依存先 id リスト（BFS 用）。
@@ -741,7 +1007,7 @@ impl From<ResolvedNode> for Plugin {
             script: n.script,
             merge: n.merge,
             dependency_cachedirs: n.dependency_cachedirs,
-            merge_enabled: n.merge_enabled,
+            merge_policy: n.merge_policy,
             order: n.order,
             id: n.id,
             depends: n.depends,
@@ -752,15 +1018,30 @@ impl From<ResolvedNode> for Plugin {
 impl Plugin {
     /// 設定ファイルから Plugin のコレクションを構築する。
     /// `Config → ResolvedGraph`（DAG 解決）→ `Plugin`（フィールド移動）の2段階。
+    /// 依存元のために起動時読み込みへ格上げされ、自身の遅延トリガが無効になったものは知らせる。
     pub fn new(config: Config) -> Result<impl Iterator<Item = Plugin>, Error> {
+        use crate::log::{Message, msg};
+
         let resolved = Self::resolve(config)?;
+        for LazyUpgrade {
+            plugin,
+            dependent,
+            triggers,
+        } in resolved.upgrades
+        {
+            msg(Message::LazyTriggersIgnored {
+                plugin,
+                dependent,
+                triggers,
+            });
+        }
         Ok(resolved.nodes.into_iter().map(Plugin::from))
     }
 
     /// Pre-resolve Plugin for EARLY-only execution（Step 4 到着順ストリーミング）。
     ///
     /// FACT 1 により `load_early` は `order`/`lazy_type`/`dependency_cachedirs`/
-    /// `merge_enabled`/`depends`/`id` を一切読まないので、これらは dummy でよい。
+    /// `merge_policy`/`depends`/`id` を一切読まないので、これらは dummy でよい。
     /// LATE（`load_late`）は `Plugin::resolve` で確定した resolved Plugin で実行され、
     /// 最終的な `order`/`lazy_type` が `plugin_id` に焼き込まれる。
     ///
@@ -779,7 +1060,7 @@ impl Plugin {
             script: pc.script,
             merge: pc.merge,
             dependency_cachedirs: Vec::new(), // dummy。LATE は resolved Plugin の値を使う。
-            merge_enabled: false,             // dummy。load_early は読まない。
+            merge_policy: None,               // dummy。load_early は読まない。
             order: 0,                         // dummy。load_early は読まない。
             id,
             depends: pc.depends,
@@ -816,6 +1097,8 @@ impl Plugin {
             .map(|plug| plug.cache.repo.as_ref().map(RepoSource::default_cachedir))
             .collect::<Vec<_>>();
 
+        let mut upgrades = Vec::new();
+        let upgrades_of_nodes = &mut upgrades;
         let nodes = plugins
             .try_dag()?
             .into_map_iter(
@@ -837,10 +1120,36 @@ impl Plugin {
                         merge,
                         ..
                     } = inner;
-                    // 依存元の lazy_type を集約
-                    let lazy_type = dependents_iter
-                        .flatten()
-                        .fold(lazy_type, |dep, plug| dep & &plug.lazy_type);
+                    // 依存元（推移的）の lazy_type を集約する（`LazyType` の `&` を参照）。
+                    let own = lazy_type.clone();
+                    let mut upgraded_by = None;
+                    let mut lazy_type = lazy_type;
+                    for plug in dependents_iter.flatten() {
+                        let dependent = plug.id.clone().unwrap_or_default();
+                        // 依存先は依存元より先に読まれるので、依存元の `on_source` は成り立たない。
+                        if let (LazyType::Opt(events), Some(name)) = (&own, plug.dep_name())
+                            && events.contains(&LoadEvent::OnSource(name.to_string()))
+                        {
+                            return Err(Error::OnSourceOfDependent {
+                                plugin: id,
+                                dependent,
+                            });
+                        }
+                        if !lazy_type.is_start() && plug.lazy_type.is_start() {
+                            upgraded_by = Some(dependent);
+                        }
+                        lazy_type &= &plug.lazy_type;
+                    }
+                    let triggers = own.describe();
+                    if let Some(dependent) = upgraded_by
+                        && !triggers.is_empty()
+                    {
+                        upgrades_of_nodes.push(LazyUpgrade {
+                            plugin: id.clone(),
+                            dependent,
+                            triggers,
+                        });
+                    }
                     // 依存先が script-only（リポジトリなし）の場合はキャッシュディレクトリが
                     // 存在しないため除外する（runtimepath に追加すべきパスがない）。
                     let dependency_cachedirs = depends
@@ -851,8 +1160,8 @@ impl Plugin {
                                 .and_then(|&dep_index| cachedirs[dep_index].clone())
                         })
                         .collect();
-                    let merge_enabled = merge.merge;
-                    ResolvedNode {
+                    let merge_policy = merge.merge;
+                    Ok(ResolvedNode {
                         order,
                         dependency_cachedirs,
                         lazy_type,
@@ -860,15 +1169,15 @@ impl Plugin {
                         cache,
                         script,
                         merge,
-                        merge_enabled,
+                        merge_policy,
                         id,
                         depends,
-                    }
+                    })
                 },
             )
-            .collect();
+            .collect::<Result<_, Error>>()?;
 
-        Ok(ResolvedGraph { nodes })
+        Ok(ResolvedGraph { nodes, upgrades })
     }
 
     /// キャッシュに既存 snapshot があるか（= インストール済み）。
@@ -911,6 +1220,9 @@ impl Plugin {
             .load_early(
                 install,
                 update,
+                false,
+                false,
+                &cache_dir,
                 &cache_dir,
                 locked_rev.as_deref(),
                 &network,
@@ -931,7 +1243,10 @@ impl Plugin {
         &self,
         install: bool,
         update: bool,
+        offline: bool,
+        force: bool,
         cache_dir: &Path,
+        dev_path: &Path,
         locked_rev: Option<&str>,
         network: &adaptive_semaphore::NetworkLimits,
         breaker: &util::github::CircuitBreaker,
@@ -943,16 +1258,27 @@ impl Plugin {
         use unicode_width::UnicodeWidthStr;
 
         // script-only（repo 無し）は EARLY では何もしない。LATE で LoadedPlugin を構築。
-        let Some(repo) = self.cache.repo.as_ref() else {
+        let (Some(repo), Some(backend)) = (
+            self.cache.repo.as_ref(),
+            catalogs.backends.for_config(&self.cache),
+        ) else {
             return Ok(EarlyOutcome::ScriptOnly);
         };
+        // ローカル checkout（dev）は rev 解決・fetch・update の対象外。
+        if let Some(path) = backend.checkout(repo, dev_path).await? {
+            return Ok(EarlyOutcome::Dev {
+                path: Arc::from(path),
+            });
+        }
 
         // `repo` は借りるので、論理 identity に使う相対 cachedir を先に捕捉する。
-        let cachedir = repo.default_cachedir();
-        let r_root = repo_root(cache_dir, repo);
+        let cachedir = backend.identity(repo);
+        let r_root = cache_dir.join(&cachedir);
         let source_git = source_git_dir(&r_root);
         let worktrees = worktrees_dir(&r_root);
-        let url: Arc<str> = Arc::from(repo.url());
+        // `--mirror` で速いミラーが選ばれていれば、fetch・ls-remote はそちらへ向ける。
+        let mirrored = util::mirror::rewrite(&repo.url());
+        let url: Arc<str> = Arc::from(mirrored.clone().unwrap_or_else(|| repo.url()));
         // lock key 用の canonical identity（fetch/tarball/error 表示用の実 URL とは別）。
         let canonical = repo.canonical();
 
@@ -960,8 +1286,8 @@ impl Plugin {
         // 同じ Arc<SnapshotCatalog> を共有し、1 run で高々1回の fallback scan に制限される。
         let catalog = catalogs.get(r_root.clone(), canonical.clone()).await;
 
-        // GitHub HTTPS URL かつ環境変数に token があれば認証フェッチする。
-        let token = if repo.is_github_https() {
+        // GitHub HTTPS URL かつ環境変数に token があれば認証フェッチする。ミラーへは送らない。
+        let token = if repo.is_github_https() && mirrored.is_none() {
             util::github::token().map(Arc::<str>::from)
         } else {
             None
@@ -994,31 +1320,43 @@ impl Plugin {
         let build = &self.cache.build;
         let lua_build = self.cache.lua_build.as_deref();
         let dotgit = self.cache.dotgit;
+        // patches は内容を snapshot key に含めるため、materialize より前に読んでおく。
+        let patches = build::Patches::read(&self.cache.patches, &url).await?;
 
         // --- ステージ1: target commit 解決（install/update/locked の分岐とリモート解決） ---
         // ResolvedRevision は oid/旧 OID/変更状態/バックエンドを一度で運ぶ (PLANS U2 step 7)。
         // EARLY で使うのは oid と was_updated/was_installed のみ（LATE/main が残りを参照可能）。
-        let revision @ ResolvedRevision { oid, .. } = match resolve_target_commit(
-            &catalog,
-            &url,
-            &rev,
-            &token,
+        let resolve_ctx = ResolveCtx {
+            catalog: &catalog,
+            url: &url,
+            rev: &rev,
+            token: &token,
             network,
             breaker,
             http_client,
             install,
             update,
             locked_rev,
-            &self.source_name,
-            &logid,
-            canonical.clone(),
-            catalogs,
-        )
-        .await?
-        {
+            source_name: &self.source_name,
+            logid: &logid,
+            canonical: &canonical,
+            jobs: catalogs,
+        };
+        let revision @ ResolvedRevision { oid, .. } = match backend.resolve(&resolve_ctx).await? {
             Some(o) => o,
             None => return Ok(EarlyOutcome::Skipped),
         };
+        // 更新で latest が切り替わると、旧 snapshot に加えたローカル変更は読み込まれなくなる。
+        // `allow_dirty`（未指定なら --force）が無ければ失われる変更を列挙して中断する。
+        if revision.was_updated {
+            check_dirty_snapshot(
+                &catalog,
+                &url,
+                self.cache.allow_dirty.unwrap_or(force),
+                display_name(&self.source_name, &logid),
+            )
+            .await?;
+        }
         let head_rev_str = oid.to_string();
         let guard = RunningGuard::new();
 
@@ -1026,7 +1364,13 @@ impl Plugin {
         // token があって GitHub HTTPS URL なら TarballFetch（source.git 不要）。
         // それ以外は従来の GitFetch（source.git）パス。TarballFetch 失敗時は GitFetch にフォールバック。
         // dotgit=true は .git 複製が必要なため TarballFetch（.git を作れない）を無効化し GitFetch に強制する。
-        let use_tarball = !dotgit && token.is_some() && util::github::supports_tarball(&url);
+        // patches も git apply で当てるため GitFetch に強制する。
+        // --offline では tarball を落とさず、source.git の object だけから materialize する。
+        let use_tarball = !offline
+            && !dotgit
+            && patches.is_empty()
+            && token.is_some()
+            && util::github::supports_tarball(&url);
         let locked = locked_rev.is_some();
 
         // フェッチヘルパーへ渡すコンテキスト。GitFetch/TarballFetch で共有し、引数過多を避ける。
@@ -1042,31 +1386,52 @@ impl Plugin {
             install,
             update,
             locked,
+            offline,
             dotgit,
+            use_tarball,
             logid: &logid,
             jobs: catalogs,
+            fetch_timeout: self.cache.fetch_timeout,
+            checkout_timeout: self.cache.checkout_timeout,
         };
 
         // exact snapshot key（build/lua_build 入力込み）を先に確定し、source.git 要否を exact
         // build-variant で判定する (U1 step 7)。`snapshot_key` は dirty_diff を含まないので build
         // 前に確定でき、広域 OID prefix ではなく exact key で再利用する。別 build variant の存在が
         // 誤って取得を省くことはない（欲しい variant が無ければ source.git を取得する）。
-        let has_build = !build.is_empty() || lua_build.is_some();
+        // patches も build と同様に building worktree で当ててから rename する。
+        let has_build = !build.is_empty() || lua_build.is_some() || !patches.is_empty();
         let pre_identity = RepoSnapshotIdentity::new(
             cachedir.clone(),
             head_rev_str.as_bytes().to_vec(),
             None,
             Arc::from(build.as_slice()),
             lua_build.map(Into::into),
-        );
+        )
+        .with_patches(patches.digest());
         let final_key = pre_identity.snapshot_key();
         let final_root: Arc<Path> = Arc::from(snapshot_root(&r_root, &final_key));
 
-        // GitFetch（非 tarball）の場合だけ source.git を確保する。exact snapshot が無ければ取得。
-        if !use_tarball
-            && !catalog.contains_exact_key(&final_key).await
-            && !acquisition::ensure_source_git(&ctx).await?
+        // `rsplug plan`: exact snapshot が無ければ、取得と build の予定だけ残して読み込まない。
+        if let Some(planned) = &catalogs.planned
+            && !tokio::fs::symlink_metadata(final_root.as_ref())
+                .await
+                .is_ok_and(|metadata| metadata.is_dir() && !metadata.file_type().is_symlink())
         {
+            let fetch = backend.planned_fetch(&ctx).await?;
+            planned.lock().unwrap().push(PlannedFetch {
+                canonical,
+                oid: head_rev_str,
+                fetch,
+                build: build.clone(),
+                lua_build: lua_build.map(str::to_string),
+                patches: self.cache.patches.len(),
+            });
+            return Ok(EarlyOutcome::Skipped);
+        }
+
+        // exact snapshot が無ければ取得する（GitFetch なら source.git を確保）。
+        if !catalog.contains_exact_key(&final_key).await && !backend.fetch(&ctx).await? {
             return Ok(EarlyOutcome::Skipped);
         }
 
@@ -1100,7 +1465,7 @@ impl Plugin {
             // dirty_diff を snapshot_key に含めるため、build 後でないと最終 key が確定しない。
             let building = building_worktree_dir(&worktrees, &final_key);
             let building: Arc<Path> = Arc::from(building);
-            let repo = match acquisition::materialize(&ctx, building.as_ref(), use_tarball).await? {
+            let repo = match backend.materialize(&ctx, building.as_ref()).await? {
                 Some(r) => r,
                 None => return Ok(EarlyOutcome::Skipped),
             };
@@ -1109,11 +1474,10 @@ impl Plugin {
             (building, repo, is_plain, true)
         } else {
             // build 無し: key は確定（dirty=None）。final に materialize。
-            let repo =
-                match acquisition::materialize(&ctx, final_root.as_ref(), use_tarball).await? {
-                    Some(r) => r,
-                    None => return Ok(EarlyOutcome::Skipped),
-                };
+            let repo = match backend.materialize(&ctx, final_root.as_ref()).await? {
+                Some(r) => r,
+                None => return Ok(EarlyOutcome::Skipped),
+            };
             (final_root.clone(), repo, false, false)
         };
 
@@ -1132,6 +1496,7 @@ impl Plugin {
                 logid,
                 repo_name,
                 canonical,
+                patches,
             },
         })
     }
@@ -1159,24 +1524,81 @@ impl Plugin {
                 let Plugin {
                     source_name,
                     lazy_type,
-                    script,
-                    merge_enabled,
+                    mut script,
+                    merge,
+                    merge_policy,
                     order,
+                    id,
                     ..
                 } = self;
+                // repo が無いので module を探せない。`auto_setup = "<module>"` だけが通る。
+                script
+                    .resolve_auto_setup(&[], source_name.as_deref())
+                    .map_err(|modules| Error::AutoSetupModule {
+                        plugin: display_name(&source_name, &id),
+                        modules,
+                    })?;
                 let loaded = LoadedPlugin {
                     source_names: source_name.into_iter().collect(),
                     files: HowToPlaceFiles::CopyEachFile(Default::default()),
                     lazy_type,
                     script,
                     order,
-                    merge_enabled,
+                    merge_policy,
+                    target: merge.target,
                     is_lazy_registration: false,
                     dotgit: false,
                 };
                 Ok(Some((loaded, None)))
             }
             EarlyOutcome::Skipped => Ok(None),
+            EarlyOutcome::Dev { path } => {
+                let Plugin {
+                    source_name,
+                    cache,
+                    lazy_type,
+                    script,
+                    merge,
+                    order,
+                    ..
+                } = self;
+                let repo = cache
+                    .repo
+                    .as_ref()
+                    .expect("dev outcome is only produced for repo plugins");
+                // symlink 配置なので checkout の編集は再実行なしで反映される。identity は配置元パスに
+                // checkout の内容 hash を加えて決め、内容が変わった後の実行でだけ package id が変わる
+                // （helptags の再生成や provenance の `tree` が変更を検知できる）。読めないファイルが
+                // あれば hash は諦め、配置元パスだけで決める。
+                let tree = util::dirty_diff_from_content(&path).await.ok();
+                let identity = RepoSnapshotIdentity::new(
+                    repo.default_cachedir(),
+                    path.as_os_str().as_encoded_bytes().to_vec(),
+                    tree,
+                    Arc::from([]),
+                    None,
+                );
+                // 他プラグインとマージすると symlink が展開されるため、dev は常に単独配置。
+                let loaded = assembly::assemble_loaded_plugin(
+                    &path,
+                    &identity,
+                    catalogs,
+                    false,
+                    true,
+                    &merge,
+                    lazy_type,
+                    source_name,
+                    script,
+                    order,
+                    Some(MergePolicy::Never),
+                    false,
+                    false,
+                    repo.basename(),
+                )
+                .await?;
+                // lock には記録しない（dev checkout の HEAD は rsplug の管理外）。
+                Ok(Some((loaded, None)))
+            }
             EarlyOutcome::Materialized { guard, outcome } => {
                 // RunningGuard は LATE 相の終了まで保持（従来 Plugin::load スコープと同一ライフサイクル）。
                 let _guard = guard;
@@ -1193,6 +1615,7 @@ impl Plugin {
                     logid,
                     repo_name,
                     canonical,
+                    patches,
                 } = outcome;
                 let ResolvedRevision {
                     canonical: revision_canonical,
@@ -1220,7 +1643,7 @@ impl Plugin {
                     script,
                     merge,
                     dependency_cachedirs,
-                    merge_enabled,
+                    merge_policy,
                     order,
                     ..
                 } = self;
@@ -1230,6 +1653,12 @@ impl Plugin {
                     build,
                     lua_build,
                     lua_post_update,
+                    allow_dirty: _,
+                    dev: _,
+                    patches: _,
+                    upstream: _,
+                    fetch_timeout: _,
+                    checkout_timeout: _,
                 } = cache;
                 // A newly materialized build worktree is unpublished. Remove
                 // it on every error path until the final atomic rename.
@@ -1296,6 +1725,10 @@ impl Plugin {
                         };
                         final_root.clone()
                     } else {
+                        // patches は fresh checkout に当て、build hook はパッチ後の内容で動かす。
+                        patches
+                            .apply(&repository, &repo_name, &head_rev_str)
+                            .await?;
                         // Both hooks consume the same dependency-completion-ordered
                         // runtimepath. Construct it once for this snapshot job.
                         let rtp = build_runtimepaths(
@@ -1418,7 +1851,8 @@ impl Plugin {
                     lua_build.as_deref(),
                     plain_content_digest,
                 )
-                .await?;
+                .await?
+                .with_patches(patches.digest());
                 if has_build {
                     tokio::fs::write(
                         snapshot_root_path.join(RSPLUG_BUILD_SUCCESS_FILE),
@@ -1428,19 +1862,25 @@ impl Plugin {
                 }
 
                 // Phase 2: snapshot が ready になったので manifest を記録する（best-effort cache）。
-                // 以降の merge/copy 計画は manifest からパス集合を引ける（Part B）。欠損時は filesystem
-                // fallback。既存 snapshot の再利用時は書き込みを省き、重複 walk を避ける。
+                // 以降の merge/copy 計画は manifest からパス集合を引ける（Part B）。snapshot は
+                // HEAD・dirty 差分・build 入力で key 付けされるので、これらが変わらない再利用時は
+                // 有効な manifest を読むだけで、ルート列挙・lua module 抽出・doc walk を行わない。
+                // 欠損・破損・旧 schema の manifest はここで作り直し、次回以降の filesystem
+                // fallback を止める。構築した manifest は run 内の inventory に直接渡す。
                 crate::rsplug::perf::failpoint("inventory_write_before")?;
-                let manifest_path = snapshot_root_path.join(MANIFEST_FILE);
+                let root = snapshot_root_path.to_path_buf();
                 if let Some(manifest) = prepared_manifest {
                     let _ = manifest.write(snapshot_root_path.as_ref()).await;
-                } else if !tokio::fs::try_exists(&manifest_path).await.unwrap_or(false) {
-                    let _ = SnapshotManifest::build_and_write(
+                    catalogs.replace_inventory(root, manifest).await;
+                } else if catalogs.inventory(root.clone()).await.is_none()
+                    && let Ok(manifest) = SnapshotManifest::build_and_write(
                         snapshot_root_path.as_ref(),
                         dotgit,
                         RSPLUG_BUILD_SUCCESS_FILE,
                     )
-                    .await;
+                    .await
+                {
+                    catalogs.replace_inventory(root, manifest).await;
                 }
                 crate::rsplug::perf::failpoint("inventory_write_after")?;
                 // per-repo latest-snapshot index: `<repo>/latest-snapshot` に snapshot_key を記録する。
@@ -1461,12 +1901,13 @@ impl Plugin {
                     &identity,
                     catalogs,
                     dotgit,
+                    false,
                     &merge,
                     lazy_type,
                     source_name,
                     script,
                     order,
-                    merge_enabled,
+                    merge_policy,
                     was_updated,
                     was_installed,
                     &logid,
@@ -1506,7 +1947,7 @@ enum ChangeStatus {
 /// target commit 解決の結果（ステージ1, PLANS U2 step 7）。canonical ID・OID・解決バックエンド・
 /// 旧 OID・変更状態を運び、LATE/main がインストール状態を再計算せずに変更選別できるようにする。
 /// `resolve_target_commit` の `Ok(None)` が `Plugin::load` のスキップ（`Ok(None)`）を表す。
-struct ResolvedRevision {
+pub(crate) struct ResolvedRevision {
     canonical: String,
     oid: Oid,
     backend: ResolutionBackend,
@@ -1531,7 +1972,7 @@ pub(crate) struct MaterializeOutcome {
     worktree_path: Arc<Path>,
     /// 実体化された snapshot バックエンド。BUILD 後に reopen される。
     repository: MaterializedRepo,
-    /// build/lua_build があるか（build 成功 marker 書き込みの判定）。
+    /// build/lua_build/patches があるか（build 成功 marker 書き込みの判定）。
     has_build: bool,
     /// LATE 相で実際に BUILD（lua_post_update/run_repo_build/rename）を実行するか。
     /// 新規 has-build materialize のみ true（reuse は既存 snapshot を使い build をスキップ）。
@@ -1549,6 +1990,8 @@ pub(crate) struct MaterializeOutcome {
     repo_name: Arc<str>,
     /// lock key 用 canonical identity。
     canonical: String,
+    /// EARLY で読んだ `patches`。新規 materialize 時に BUILD より前に当てる。
+    patches: build::Patches,
 }
 
 /// EARLY 相の結果。LATE 相への引き継ぎを3ケースで表現する。
@@ -1567,6 +2010,8 @@ pub(crate) enum EarlyOutcome {
         guard: RunningGuard,
         outcome: MaterializeOutcome,
     },
+    /// `dev = true`。fetch せずローカル checkout をそのまま LATE で symlink 配置する。
+    Dev { path: Arc<Path> },
 }
 
 /// ステージ1: target commit 解決。install/update/locked の分岐とリモート解決。
@@ -1609,6 +2054,41 @@ async fn resolve_shared_remote_oid(
     }
 }
 
+/// update で置き去りになる最新 snapshot のローカル変更を検査する。
+/// 変更があり `allow` でなければ [`Error::DirtySnapshot`] で中断し、許可時は警告だけ出す。
+/// `.git` を持たない snapshot（tarball 由来）は変更を検出できないため対象外。
+async fn check_dirty_snapshot(
+    catalog: &SnapshotCatalog,
+    url: &Arc<str>,
+    allow: bool,
+    name: Arc<str>,
+) -> Result<(), Error> {
+    use super::util::git;
+    use crate::log::{Message, msg};
+
+    let Some(dir) = catalog.latest_unbuilt_dir().await else {
+        return Ok(());
+    };
+    if !tokio::fs::try_exists(dir.join(".git"))
+        .await
+        .unwrap_or(false)
+    {
+        return Ok(());
+    }
+    let paths = git::open(dir).await?.dirty_paths().await?;
+    if paths.is_empty() {
+        return Ok(());
+    }
+    if !allow {
+        return Err(Error::DirtySnapshot {
+            repo: url.clone(),
+            paths,
+        });
+    }
+    msg(Message::PluginDirtyDiscarded(name));
+    Ok(())
+}
+
 // Plugin::load からの抽出。引数過多は LoadCtx 構造体化（巨大化）より局所的と判断し allow する。
 #[allow(clippy::too_many_arguments)]
 async fn resolve_target_commit(
@@ -1781,12 +2261,13 @@ async fn assemble_loaded_plugin(
     identity: &RepoSnapshotIdentity,
     catalogs: &SnapshotCatalogCache,
     dotgit: bool,
+    symlink: bool,
     merge: &MergeConfig,
     mut lazy_type: LazyType,
     source_name: Option<String>,
-    script: SetupScript,
+    mut script: SetupScript,
     order: usize,
-    merge_enabled: bool,
+    merge_policy: Option<MergePolicy>,
     was_updated: bool,
     was_installed: bool,
     logid: &str,
@@ -1804,9 +2285,14 @@ async fn assemble_loaded_plugin(
     // A valid inventory is the authoritative immutable snapshot view.  It avoids
     // re-reading the root, lua/, and doc/ trees on every warm refresh; an absent or
     // invalid cache deliberately falls back to the historical filesystem path.
-    let inventory = catalogs
-        .inventory(snapshot_root_path.as_ref().to_path_buf())
-        .await;
+    // dev checkout は可変なので inventory を持たず、常に filesystem を読む。
+    let inventory = if symlink {
+        None
+    } else {
+        catalogs
+            .inventory(snapshot_root_path.as_ref().to_path_buf())
+            .await
+    };
     let filesource = Arc::new(FileSource::Directory {
         path: snapshot_root_path.clone(),
         inventory: inventory.clone(),
@@ -1817,6 +2303,7 @@ async fn assemble_loaded_plugin(
                 inventory,
             })
         }),
+        symlink,
     });
     let (entries, mut manifest_doc_entries, lua_modules) = if let Some(manifest) = &inventory {
         let mut top = manifest
@@ -1842,6 +2329,12 @@ async fn assemble_loaded_plugin(
             inventory::extract_unique_lua_modules_from_snapshot(snapshot_root_path.as_ref()).await,
         )
     };
+    script
+        .resolve_auto_setup(&lua_modules, source_name.as_deref())
+        .map_err(|modules| Error::AutoSetupModule {
+            plugin: display_name(&source_name, logid),
+            modules,
+        })?;
     for luam in lua_modules {
         lazy_type &= LoadEvent::LuaModule(LuaModule(luam.into()));
     }
@@ -1849,15 +2342,29 @@ async fn assemble_loaded_plugin(
     // 「盗んで」`_rsplug:doc` start プラグインへ集約するため、sealed-dir 1エントリではなく
     // 個別ファイル（`doc/<rel>`）に展開する（`LazyRegistration::create` の `starts_with("doc/")` 盗みを効かせる）。
     // それ以外は sealed-dir のまま（install で copy_tree が clonefile/per-file copy で配置）。
+    // `merge.ignore` に repo の `.rsplugignore` と cache 側の上書き（`<repo>/.rsplugignore`）を
+    // 連結して列挙に使う。dev checkout は利用者の手元なので上書きを持たない。
+    let overlay = (!symlink)
+        .then(|| snapshot_root_path.parent().and_then(Path::parent))
+        .flatten()
+        .map(|repo_root| repo_root.join(inventory::IGNORE_FILE));
+    let ignore = inventory::effective_ignore(
+        &merge.ignore,
+        snapshot_root_path.as_ref(),
+        inventory.as_deref(),
+        overlay.as_deref(),
+    )
+    .await;
     let mut file_entries: Vec<(PathBuf, FileItem)> = Vec::with_capacity(entries.len());
     for name in &entries {
         // Phase 2 の manifest は cache 内部ファイル。pack に含めず、全プラグインで
         // 同 path が衝突してマージを阻害する原因にもしないため、列挙から除外する。
-        if name == Path::new(MANIFEST_FILE) {
+        // `.rsplugignore` も列挙の設定であって runtime の内容ではないので同様に除く。
+        if name == Path::new(MANIFEST_FILE) || name == Path::new(inventory::IGNORE_FILE) {
             continue;
         }
         // dotgit=true なら `.git` を ignore から救出して通常エントリに含める。
-        if !(dotgit && name == Path::new(".git") || !merge.ignore.matched(name)) {
+        if !(dotgit && name == Path::new(".git") || !ignore.matched(name)) {
             continue;
         }
         if name == Path::new("doc") {
@@ -1880,6 +2387,17 @@ async fn assemble_loaded_plugin(
             ));
         }
     }
+    // dev checkout は symlink で置くだけなので、コピー量の上限は copy 配置にだけ課す。
+    if !symlink {
+        inventory::check_placement_limits(
+            snapshot_root_path.as_ref(),
+            inventory.as_deref(),
+            &file_entries,
+            merge,
+            display_name(&source_name, logid),
+        )
+        .await?;
+    }
     let files: HowToPlaceFiles = HowToPlaceFiles::CopyEachFile(file_entries.into_iter().collect());
 
     // ロード成功が確定したので、実際に更新/新規インストールされたプラグインを
@@ -1896,7 +2414,8 @@ async fn assemble_loaded_plugin(
         lazy_type,
         script,
         order,
-        merge_enabled,
+        merge_policy,
+        target: merge.target,
         is_lazy_registration: false,
         dotgit,
     })
@@ -1904,7 +2423,7 @@ async fn assemble_loaded_plugin(
 
 /// フェッチに必要なコンテキスト（GitFetch / TarballFetch 共通）。
 /// `Plugin::load` で組み立ててヘルパーに渡すことで引数過多を避ける。
-struct FetchCtx<'a> {
+pub(crate) struct FetchCtx<'a> {
     url: &'a Arc<str>,
     oid: Oid,
     source_git: &'a Path,
@@ -1916,9 +2435,16 @@ struct FetchCtx<'a> {
     install: bool,
     update: bool,
     locked: bool,
+    /// `--offline`: source.git に object が無くても fetch しない。
+    offline: bool,
     dotgit: bool,
+    /// GitHub の tarball で取得するか（source.git を作らない）。
+    use_tarball: bool,
     logid: &'a str,
     jobs: &'a SnapshotCatalogCache,
+    /// `fetch_timeout`・`checkout_timeout`。`None` なら待ち続ける。
+    fetch_timeout: Option<std::time::Duration>,
+    checkout_timeout: Option<std::time::Duration>,
 }
 
 /// リモートの最新コミットハッシュを解決する (PLANS U2 step 5)。
@@ -1927,7 +2453,8 @@ struct FetchCtx<'a> {
 /// を試行する。一時エラー（5xx・ネットワーク）は少額の決定的再試行予算内で再試行し、
 /// レートリミットはブレーカーを開いて Git フォールバックへ、認証/404/無効 ref は即フォールバック
 /// する（盲目的再試行しない）。ワイルドカード ref・ブレーカー開放時は Git に直行する。
-/// いずれの経路も最終的に git protocol (`ls_remote`) で解決する。戻り値は OID と使用バックエンド。
+/// いずれの経路も最終的に git protocol (`ls_remote`) で解決する。http(s) の remote は ref 広告を
+/// 共有の HTTP クライアントで取得し、ホストごとの接続を使い回す。戻り値は OID と使用バックエンド。
 ///
 /// 各ネットワーク操作（API 解決・ls-remote）は [`NetworkLimits::run`] を通過し、
 /// global 上限 + per-host 上限の許可を 1 つずつ消費して適応サンプルを正確に 1 つ寄与する。
@@ -1974,9 +2501,13 @@ async fn resolve_remote_oid(
                                 tokio::time::sleep(github::transient_backoff(attempt)).await;
                                 attempt += 1;
                             }
+                            github::ApiFallbackPolicy::Wait(wait) => {
+                                tokio::time::sleep(wait).await;
+                                attempt += 1;
+                            }
                             github::ApiFallbackPolicy::RateLimited => {
                                 // レートリミット → ブレーカーを開き、以降は Git フォールバックへ直行。
-                                breaker.trip();
+                                breaker.trip_rate_limited();
                                 break;
                             }
                             github::ApiFallbackPolicy::GiveUp => {
@@ -1992,10 +2523,15 @@ async fn resolve_remote_oid(
 
     crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::LsRemoteRequest);
     // フォールバック: git smart HTTP protocol (ls_remote)。ホスト別上限の下で実行。
+    // http(s) の remote は共有クライアントで ref 広告を取り、同じホストへの問い合わせで
+    // 接続を使い回す。取得できなければ libgit2 で接続し直す。
     let host = util::repo::host_of(url);
     let oid = network
         .run(&host, async {
-            git::ls_remote(url.clone(), rev.clone(), token.clone()).await
+            match git::ref_advertisement(http_client, url, token.as_deref()).await {
+                Ok(refs) => git::select_rev(url, &refs, rev.as_deref()),
+                Err(_) => git::ls_remote(url.clone(), rev.clone(), token.clone()).await,
+            }
         })
         .await?;
     Ok((oid, ResolutionBackend::Git))
@@ -2011,15 +2547,9 @@ async fn ensure_source_git(ctx: &FetchCtx<'_>) -> Result<bool, Error> {
         .acquisition_cell(ctx.source_git, ctx.oid, ctx.dotgit)
         .await;
     let result = cell
-        .get_or_init(|| async {
-            ensure_source_git_inner(ctx)
-                .await
-                .map_err(|error| Arc::from(error.to_string()))
-        })
+        .get_or_init(|| async { ensure_source_git_inner(ctx).await.map_err(Arc::new) })
         .await;
-    result
-        .clone()
-        .map_err(|message| Error::Io(std::io::Error::other(message.as_ref())))
+    result.clone().map_err(|error| error.unshare())
 }
 
 #[cfg(test)]
@@ -2061,7 +2591,15 @@ async fn ensure_source_git_inner(ctx: &FetchCtx<'_>) -> Result<bool, Error> {
         crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::GitFetch);
         let host = util::repo::host_of(ctx.url);
         ctx.network
-            .run(&host, repo.fetch_oid(ctx.oid, ctx.token.clone()))
+            .run(
+                &host,
+                repo.fetch_oid(
+                    ctx.url.clone(),
+                    ctx.oid,
+                    ctx.token.clone(),
+                    Default::default(),
+                ),
+            )
             .await?;
         msg(Message::Cache("Fetching:done", ctx.url.clone()));
     }
@@ -2071,7 +2609,7 @@ async fn ensure_source_git_inner(ctx: &FetchCtx<'_>) -> Result<bool, Error> {
 /// 実体化された snapshot のバックエンド。
 /// `Git` = source.git 経由の worktree（`.git` 有り）。`Plain` = tarball 展開のみ（`.git` 無し、Phase 7）。
 /// identity/dirty の計算が異なる: `Git` は git diff、`Plain` はファイル内容ハッシュ。
-enum MaterializedRepo {
+pub(crate) enum MaterializedRepo {
     Git(util::git::Repository),
     Plain,
 }
@@ -2111,12 +2649,10 @@ async fn materialize(
         .get_or_init(|| async {
             materialize_inner(ctx, dest, use_tarball)
                 .await
-                .map_err(|error| Arc::<str>::from(error.to_string()))
+                .map_err(Arc::new)
         })
         .await;
-    let snapshot = result
-        .clone()
-        .map_err(|message| Error::Io(std::io::Error::other(message.as_ref())))?;
+    let snapshot = result.clone().map_err(|error| error.unshare())?;
     let Some(snapshot) = snapshot else {
         return Ok(None);
     };
@@ -2203,7 +2739,13 @@ async fn materialize_inner(
 
     let _git = super::util::resources::git().await?;
     crate::rsplug::perf::failpoint("materialize_after")?;
-    git::init_snapshot(dest.to_path_buf(), ctx.source_git, ctx.oid).await?;
+    git::init_snapshot(
+        dest.to_path_buf(),
+        ctx.source_git,
+        ctx.oid,
+        Default::default(),
+    )
+    .await?;
     Ok(Some(MaterializedSnapshot {
         root: Arc::from(dest.to_path_buf()),
         plain: false,
@@ -2409,7 +2951,7 @@ async fn build_repo_snapshot_identity(
 
 fn lua_build_nvim_command(lua_script_path: &OsStr) -> [Cow<'_, OsStr>; 8] {
     [
-        Cow::Borrowed(OsStr::new("nvim")),
+        Cow::Borrowed(util::nvim::program()),
         Cow::Borrowed(OsStr::new("--headless")),
         Cow::Borrowed(OsStr::new("-u")),
         Cow::Borrowed(OsStr::new("NONE")),
@@ -2514,6 +3056,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn unusual_repo_strings_are_rejected_or_parsed_without_panicking() {
+        for input in [
+            "",
+            "/",
+            "@",
+            "://",
+            "https://",
+            "git://@",
+            "ssh://user@:@/",
+            "https://h:/a",
+            "https://h/.git/",
+            "owner/repo@",
+            "owner/repo@v1 x",
+            "ö/ä",
+            "owner/répo",
+            "https://ホスト.example/所有者/リポ.git@枝",
+        ] {
+            let Ok(repo) = RepoSource::from_str(input) else {
+                continue;
+            };
+            let _ = (
+                repo.url(),
+                repo.canonical(),
+                repo.default_cachedir(),
+                repo.basename(),
+            );
+        }
+        // shorthand の owner/repo は ascii に限る。URL はそのまま受け付ける。
+        assert!(RepoSource::from_str("ö/ä").is_err());
+        assert!(RepoSource::from_str("owner/répo").is_err());
+        let repo = RepoSource::from_str("https://ホスト.example/所有者/リポ.git@枝").unwrap();
+        assert_eq!(repo.basename(), "リポ");
+        assert_eq!(repo.rev().as_deref(), Some("枝"));
+    }
+
     #[test]
     fn default_cachedir_matches_canonical_components() {
         // canonical() の `/` 区切り == default_cachedir() のコンポーネント。
@@ -2695,6 +3273,84 @@ mod tests {
         assert!(!first.contains_exact_key(&key).await);
     }
 
+    #[tokio::test]
+    async fn reload_rebuilds_an_unreadable_snapshot_manifest() {
+        use std::process::Command;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let remote = tmp.path().join("remote");
+        let cache = tmp.path().join("cache");
+        std::fs::create_dir_all(remote.join("lua/foo")).unwrap();
+        std::fs::write(remote.join("lua/foo/init.lua"), "return {}").unwrap();
+        for args in [
+            &["init", "-q"][..],
+            &["add", "-A"],
+            &[
+                "-c",
+                "user.email=t@t",
+                "-c",
+                "user.name=t",
+                "-c",
+                "commit.gpgsign=false",
+                "commit",
+                "-q",
+                "-m",
+                "init",
+            ],
+        ] {
+            let status = Command::new("git")
+                .current_dir(&remote)
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success(), "git {args:?} failed");
+        }
+        let url = format!("file://{}", remote.display());
+        let load = || async {
+            let config: Config =
+                toml::from_str(&format!("[[plugins]]\nrepo = \"{url}\"\n")).unwrap();
+            Plugin::new(config)
+                .unwrap()
+                .next()
+                .unwrap()
+                .load(
+                    true,
+                    false,
+                    &cache,
+                    None,
+                    adaptive_semaphore::AdaptiveSemaphore::new(),
+                    reqwest::Client::new(),
+                )
+                .await
+                .unwrap()
+                .unwrap();
+        };
+        fn find_manifest(dir: &Path) -> Option<PathBuf> {
+            for entry in std::fs::read_dir(dir).ok()?.flatten() {
+                let path = entry.path();
+                if entry.file_name() == MANIFEST_FILE {
+                    return Some(path);
+                }
+                if entry.file_type().is_ok_and(|t| t.is_dir())
+                    && let Some(found) = find_manifest(&path)
+                {
+                    return Some(found);
+                }
+            }
+            None
+        }
+
+        load().await;
+        let manifest = find_manifest(&cache).expect("install writes a snapshot manifest");
+        std::fs::write(&manifest, "{ not json").unwrap();
+
+        // snapshot を再利用する 2 回目の load で、読めない manifest を作り直す。
+        load().await;
+        let root = manifest.parent().unwrap().to_path_buf();
+        let rebuilt = SnapshotCatalogCache::new().inventory(root).await.unwrap();
+        assert_eq!(rebuilt.lua_roots(), ["foo"]);
+    }
+
     #[tokio::test]
     async fn materialization_jobs_share_typed_once_cell_results() {
         let cache = SnapshotCatalogCache::new();
@@ -2845,6 +3501,199 @@ mod tests {
         assert_ne!(first.plugin_id(), different_script.plugin_id());
     }
 
+    #[tokio::test]
+    async fn dev_plugin_loads_local_checkout_as_symlinks_without_lock() {
+        // dev=true は fetch せず `<dev_path>/<repo 名>` を symlink 配置し、lock を返さない。
+        // テスト経路の `load` は dev_path = cache_dir。
+        let tmp = tempfile::tempdir().unwrap();
+        let checkout = tmp.path().join("devplug.nvim");
+        std::fs::create_dir_all(checkout.join("lua")).unwrap();
+        std::fs::write(checkout.join("lua/devplug.lua"), "return {}\n").unwrap();
+        let config: Config = toml::from_str(
+            r#"
+            [[plugins]]
+            repo = "owner/devplug.nvim"
+            dev = true
+            "#,
+        )
+        .unwrap();
+        let plugin = Plugin::new(config).unwrap().next().unwrap();
+
+        let (loaded, lock_info) = plugin
+            .load(
+                true,
+                true,
+                tmp.path(),
+                None,
+                adaptive_semaphore::AdaptiveSemaphore::new(),
+                reqwest::Client::new(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(lock_info.is_none());
+        assert_eq!(loaded.merge_policy, Some(MergePolicy::Never));
+        let HowToPlaceFiles::CopyEachFile(files) = &loaded.files;
+        let item = files.get(Path::new("lua")).unwrap();
+        assert!(matches!(
+            item.source.as_ref(),
+            FileSource::Directory { symlink: true, path, .. } if path.as_ref() == checkout
+        ));
+    }
+
+    #[tokio::test]
+    async fn dev_plugin_id_follows_checkout_content() {
+        let tmp = tempfile::tempdir().unwrap();
+        let checkout = tmp.path().join("devplug.nvim");
+        std::fs::create_dir_all(checkout.join("doc")).unwrap();
+        std::fs::write(checkout.join("doc/devplug.txt"), "*devplug*\n").unwrap();
+        let load = || async {
+            let config: Config = toml::from_str(
+                r#"
+                [[plugins]]
+                repo = "owner/devplug.nvim"
+                dev = true
+                "#,
+            )
+            .unwrap();
+            let plugin = Plugin::new(config).unwrap().next().unwrap();
+            let (loaded, _) = plugin
+                .load(
+                    false,
+                    false,
+                    tmp.path(),
+                    None,
+                    adaptive_semaphore::AdaptiveSemaphore::new(),
+                    reqwest::Client::new(),
+                )
+                .await
+                .unwrap()
+                .unwrap();
+            loaded.plugin_id()
+        };
+
+        let first = load().await;
+        assert_eq!(load().await, first);
+        // 既存 help の編集はエントリ構成を変えないが、内容 hash で id が変わる。
+        std::fs::write(
+            checkout.join("doc/devplug.txt"),
+            "*devplug* *devplug-new*\n",
+        )
+        .unwrap();
+        assert_ne!(load().await, first);
+    }
+
+    #[tokio::test]
+    async fn dev_plugin_without_checkout_is_an_error() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config: Config = toml::from_str(
+            r#"
+            [[plugins]]
+            repo = "owner/missing.nvim"
+            dev = true
+            "#,
+        )
+        .unwrap();
+        let plugin = Plugin::new(config).unwrap().next().unwrap();
+
+        let result = plugin
+            .load(
+                false,
+                false,
+                tmp.path(),
+                None,
+                adaptive_semaphore::AdaptiveSemaphore::new(),
+                reqwest::Client::new(),
+            )
+            .await;
+
+        assert!(matches!(result, Err(Error::DevCheckoutMissing { .. })));
+    }
+
+    #[tokio::test]
+    async fn patches_apply_after_checkout_and_report_conflicts() {
+        use std::process::Command;
+        let tmp = tempfile::tempdir().unwrap();
+        let remote = tmp.path().join("remote");
+        let cache = tmp.path().join("cache");
+        std::fs::create_dir_all(remote.join("plugin")).unwrap();
+        std::fs::write(remote.join("plugin/init.vim"), "\"x\n").unwrap();
+        let git = |args: &[&str]| {
+            let s = Command::new("git")
+                .current_dir(&remote)
+                .args(["-c", "user.email=t@t", "-c", "user.name=t"])
+                .args(["-c", "commit.gpgsign=false"])
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(s.success(), "git {:?} failed", args);
+        };
+        git(&["init", "-q"]);
+        git(&["add", "-A"]);
+        git(&["commit", "-q", "-m", "init"]);
+        let url = format!("file://{}", remote.display());
+        let patch = |name: &str, from: &str, to: &str| {
+            let path = tmp.path().join(name);
+            std::fs::write(
+                &path,
+                format!(
+                    "diff --git a/plugin/init.vim b/plugin/init.vim\n\
+                     --- a/plugin/init.vim\n\
+                     +++ b/plugin/init.vim\n\
+                     @@ -1 +1 @@\n\
+                     -\"{from}\n\
+                     +\"{to}\n"
+                ),
+            )
+            .unwrap();
+            path
+        };
+        let fix = patch("fix.diff", "x", "y");
+        let stale = patch("stale.diff", "z", "w");
+        let load = |patches: Vec<PathBuf>| {
+            let mut config: Config =
+                toml::from_str(&format!("[[plugins]]\nrepo = \"{url}\"\n")).unwrap();
+            config.plugins[0].cache.patches = patches;
+            let plugin = Plugin::new(config).unwrap().next().unwrap();
+            plugin.load(
+                true,
+                false,
+                &cache,
+                None,
+                adaptive_semaphore::AdaptiveSemaphore::new(),
+                reqwest::Client::new(),
+            )
+        };
+
+        let (plain, _) = load(vec![]).await.unwrap().unwrap();
+        let (patched, _) = load(vec![fix.clone()]).await.unwrap().unwrap();
+        let root = patched.snapshot_root().unwrap();
+        assert_ne!(Some(root.clone()), plain.snapshot_root());
+        assert_eq!(
+            std::fs::read_to_string(root.join("plugin/init.vim")).unwrap(),
+            "\"y\n"
+        );
+        assert_ne!(patched.plugin_id(), plain.plugin_id());
+        // パッチ内容が変われば別 snapshot として当て直す。
+        std::fs::write(
+            &fix,
+            std::fs::read_to_string(&fix)
+                .unwrap()
+                .replace("+\"y", "+\"v"),
+        )
+        .unwrap();
+        let (edited, _) = load(vec![fix]).await.unwrap().unwrap();
+        assert_ne!(edited.snapshot_root(), Some(root));
+
+        match load(vec![stale]).await {
+            Err(Error::PatchConflict { files, .. }) => {
+                assert_eq!(files, ["plugin/init.vim"]);
+            }
+            other => panic!("expected a patch conflict, got {:?}", other.map(|_| ())),
+        }
+    }
+
     #[tokio::test]
     async fn load_creates_snapshot_worktree_and_reuses_it() {
         // 実 git で install → source.git + worktrees/<key> を作り、RepoSnapshotLink target が
@@ -3295,6 +4144,97 @@ mod tests {
         let _ = std::fs::remove_dir_all(&dir);
     }
 
+    #[tokio::test]
+    async fn offline_preflight_lists_what_the_cache_cannot_provide() {
+        use super::super::lockfile::{LockedResource, LockedResourceType};
+        use std::process::Command;
+        let dir = tempfile::tempdir().unwrap();
+        let remote = dir.path().join("remote");
+        let cache = dir.path().join("cache");
+        std::fs::create_dir_all(remote.join("plugin")).unwrap();
+        std::fs::write(remote.join("plugin/init.vim"), "\"x\n").unwrap();
+        let git = |args: &[&str]| {
+            let out = Command::new("git")
+                .current_dir(&remote)
+                .args([
+                    "-c",
+                    "user.email=t@t",
+                    "-c",
+                    "user.name=t",
+                    "-c",
+                    "commit.gpgsign=false",
+                ])
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(out.status.success(), "git {:?} failed", args);
+            String::from_utf8(out.stdout).unwrap().trim().to_string()
+        };
+        git(&["init", "-q"]);
+        git(&["add", "-A"]);
+        git(&["commit", "-q", "-m", "init"]);
+        let head = git(&["rev-parse", "HEAD"]);
+        let url = format!("file://{}", remote.display());
+        let config =
+            || toml::from_str::<Config>(&format!("[[plugins]]\nrepo = \"{url}\"\n")).unwrap();
+        let canonical = config().plugins[0].cache.repo.as_ref().unwrap().canonical();
+        let locked = BTreeMap::from([(
+            canonical.clone(),
+            LockedResource {
+                kind: LockedResourceType::Git,
+                rev: head.clone(),
+            },
+        )]);
+
+        assert_eq!(
+            offline_preflight(&config(), &cache, &BTreeMap::new())
+                .await
+                .unwrap(),
+            vec![OfflineMissing::LockEntry {
+                canonical: canonical.clone()
+            }]
+        );
+        assert_eq!(
+            offline_preflight(&config(), &cache, &locked).await.unwrap(),
+            vec![OfflineMissing::Object {
+                canonical: canonical.clone(),
+                rev: head.clone()
+            }]
+        );
+
+        Plugin::new(config())
+            .unwrap()
+            .next()
+            .unwrap()
+            .load(
+                true,
+                false,
+                &cache,
+                Some(Arc::from(head.as_str())),
+                adaptive_semaphore::AdaptiveSemaphore::new(),
+                reqwest::Client::new(),
+            )
+            .await
+            .unwrap();
+        assert!(
+            offline_preflight(&config(), &cache, &locked)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // snapshot が消えても source.git に object があれば復元できる。
+        let r_root = repo_root(&cache, config().plugins[0].cache.repo.as_ref().unwrap());
+        std::fs::remove_dir_all(worktrees_dir(&r_root)).unwrap();
+        let _ = std::fs::remove_file(r_root.join(LATEST_SNAPSHOT_FILE));
+        assert!(
+            offline_preflight(&config(), &cache, &locked)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
     #[tokio::test]
     async fn locked_rev_path_skips_installs_or_errors_for_uninstalled() {
         // locked_rev=Some（GraphQL preresolved / --lock 由来）+ 未インストールの分岐。
@@ -3648,6 +4588,88 @@ mod tests {
         );
     }
 
+    #[test]
+    fn lazy_types_of_dependents_are_combined_and_upgrades_reported() {
+        let config: Config = toml::from_str(
+            r#"
+            [[plugins]]
+            repo = "owner/lib.nvim"
+            on_cmd = "Lib"
+
+            [[plugins]]
+            repo = "owner/lazy.nvim"
+            depends = ["lib.nvim"]
+            on_ft = "lua"
+
+            [[plugins]]
+            repo = "owner/start.nvim"
+            depends = ["lazy.nvim"]
+            start = true
+
+            [[plugins]]
+            repo = "owner/quiet.nvim"
+
+            [[plugins]]
+            repo = "owner/other.nvim"
+            depends = ["quiet.nvim"]
+            on_cmd = "Other"
+            "#,
+        )
+        .unwrap();
+        let resolved = Plugin::resolve(config).unwrap();
+        let lazy_type = |id: &str| {
+            &resolved
+                .nodes
+                .iter()
+                .find(|node| node.id == id)
+                .unwrap()
+                .lazy_type
+        };
+        // `start.nvim` は推移的に `lib.nvim` まで起動時読み込みにする。
+        assert!(lazy_type("lib.nvim").is_start());
+        assert!(lazy_type("lazy.nvim").is_start());
+        // トリガの無い依存先は依存元のトリガで読まれる（格上げではない）。
+        assert_eq!(lazy_type("quiet.nvim").describe(), ["on_cmd:Other"]);
+        let mut upgrades: Vec<_> = resolved
+            .upgrades
+            .iter()
+            .map(|u| (u.plugin.as_str(), u.dependent.as_str(), u.triggers.clone()))
+            .collect();
+        upgrades.sort();
+        assert_eq!(
+            upgrades,
+            [
+                ("lazy.nvim", "start.nvim", vec!["on_ft:lua".to_string()]),
+                ("lib.nvim", "start.nvim", vec!["on_cmd:Lib".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn on_source_of_a_dependent_is_rejected() {
+        let config: Config = toml::from_str(
+            r#"
+            [[plugins]]
+            repo = "owner/lib.nvim"
+            on_source = "host.nvim"
+
+            [[plugins]]
+            repo = "owner/host.nvim"
+            depends = ["lib.nvim"]
+            "#,
+        )
+        .unwrap();
+        let err = Plugin::new(config).err().unwrap();
+        assert!(
+            matches!(
+                &err,
+                Error::OnSourceOfDependent { plugin, dependent }
+                    if plugin == "lib.nvim" && dependent == "host.nvim"
+            ),
+            "{err}"
+        );
+    }
+
     #[tokio::test]
     async fn doc_file_entries_walks_doc_into_individual_keys() {
         // doc を sealed-dir ではなく個別ファイル（doc/<rel>）に展開する（doc 盗みの前提）。
@@ -3672,6 +4694,7 @@ mod tests {
             path: Arc::from(root.to_path_buf()),
             inventory: None,
             handle: None,
+            symlink: false,
         });
         let identity = RepoSnapshotIdentity::new(
             PathBuf::from("github.com/o/r"),
@@ -3702,6 +4725,107 @@ mod tests {
         );
     }
 
+    /// 配置上限は manifest・filesystem のどちらから数えても同じ結果になり、超過時は
+    /// 大きい最上位エントリを名指しする。ignore で外したエントリは数えない。
+    #[tokio::test]
+    async fn placement_limits_count_selected_entries_only() {
+        use std::collections::{BTreeMap, BTreeSet};
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("lua/foo")).unwrap();
+        std::fs::write(root.join("lua/foo/init.lua"), vec![b'-'; 100]).unwrap();
+        std::fs::create_dir_all(root.join("node_modules/pkg")).unwrap();
+        for i in 0..5 {
+            std::fs::write(
+                root.join(format!("node_modules/pkg/{i}.js")),
+                vec![b'x'; 1000],
+            )
+            .unwrap();
+        }
+        std::fs::create_dir_all(root.join("tests")).unwrap();
+        std::fs::write(root.join("tests/huge.bin"), vec![0; 10_000]).unwrap();
+
+        let filesource = Arc::new(FileSource::Directory {
+            path: Arc::from(root.to_path_buf()),
+            inventory: None,
+            handle: None,
+            symlink: false,
+        });
+        let identity = RepoSnapshotIdentity::new(
+            PathBuf::from("github.com/o/r"),
+            b"deadbeef".to_vec(),
+            None,
+            Arc::<[String]>::from(Vec::<String>::new()),
+            None,
+        );
+        let entries: Vec<(PathBuf, FileItem)> = ["lua", "node_modules"]
+            .into_iter()
+            .map(|name| {
+                (
+                    PathBuf::from(name),
+                    FileItem::new(
+                        filesource.clone(),
+                        FileIdentity::RepoFile(RepoFileIdentity::new(
+                            identity.clone(),
+                            PathBuf::from(name),
+                        )),
+                        MergeType::Conflict,
+                    ),
+                )
+            })
+            .collect();
+        let manifest = SnapshotManifest::build(root, false, ".rsplug_build_success")
+            .await
+            .unwrap();
+        let tops = BTreeSet::from([PathBuf::from("lua"), PathBuf::from("node_modules")]);
+        let expected = BTreeMap::from([
+            (
+                PathBuf::from("lua"),
+                inventory::PlacementUsage {
+                    files: 1,
+                    bytes: 100,
+                },
+            ),
+            (
+                PathBuf::from("node_modules"),
+                inventory::PlacementUsage {
+                    files: 5,
+                    bytes: 5000,
+                },
+            ),
+        ]);
+        for manifest in [None, Some(&manifest)] {
+            assert_eq!(
+                inventory::placement_usage(root, manifest, &tops)
+                    .await
+                    .unwrap(),
+                expected
+            );
+        }
+
+        let mut merge = MergeConfig::default();
+        inventory::check_placement_limits(root, Some(&manifest), &entries, &merge, "r".into())
+            .await
+            .unwrap();
+        merge.max_files = 4;
+        let err = inventory::check_placement_limits(root, None, &entries, &merge, "r".into())
+            .await
+            .unwrap_err();
+        let Error::PluginTooLarge {
+            files,
+            bytes,
+            largest,
+            ..
+        } = &err
+        else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!((*files, *bytes), (6, 5100));
+        assert_eq!(largest[0].0, PathBuf::from("node_modules"));
+        assert!(err.to_string().contains("node_modules  5 files, 4.9 KiB"));
+    }
+
     /// Step 4: `Plugin::from_config`（EARLY 用 dummy Plugin）の id が、`Plugin::new` 経由の
     /// resolved Plugin の id と一致すること。EARLY（dummy）↔ LATE（resolved）を id で
     /// 橋渡しする根拠。`compute_internal_id` は単独 PluginConfig から計算可能で、resolve
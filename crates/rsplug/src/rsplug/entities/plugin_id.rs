@@ -3,10 +3,57 @@ use std::{cmp::Ordering, ops::Deref, path::Path, sync::Arc};
 use crate::rsplug::util::hash;
 use sailfish::runtime::Render;
 
-/// 固定されたプラグインのID(表示や書き込み用)。
+/// 固定されたプラグインのID(表示や書き込み用)。`pack/_gen/opt/` 以下のパッケージ名になる。
 /// インストールが済んだ後に使用するのが望ましい。未インストールの PluginID は変更される可能性があるため。
-#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
-pub struct PluginIDStr([u8; 32]);
+///
+/// 通常は [`PluginID`] の 16 進表現だが、安定名モードでは併合されないプラグインに
+/// `<owner>__<repo>` の名前が付く（[`PluginIDStr::named`]）。比較・ハッシュは文字列として行う。
+#[derive(Clone, Debug)]
+pub struct PluginIDStr(Repr);
+
+#[derive(Clone, Debug)]
+enum Repr {
+    Hash([u8; 32]),
+    Name(Arc<str>),
+}
+
+impl PluginIDStr {
+    /// 内容ハッシュではない名前のパッケージ id。`name` は 32 桁の 16 進数であってはならない。
+    pub(super) fn named(name: impl Into<Arc<str>>) -> Self {
+        Self(Repr::Name(name.into()))
+    }
+
+    /// 安定名モードで付けた名前か。
+    pub(super) fn is_named(&self) -> bool {
+        matches!(self.0, Repr::Name(_))
+    }
+}
+
+impl PartialEq for PluginIDStr {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for PluginIDStr {}
+
+impl PartialOrd for PluginIDStr {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PluginIDStr {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl std::hash::Hash for PluginIDStr {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
 
 impl Render for PluginIDStr {
     fn render(&self, b: &mut sailfish::runtime::Buffer) -> Result<(), sailfish::RenderError> {
@@ -17,7 +64,7 @@ impl Render for PluginIDStr {
 
 impl From<PluginIDStr> for Box<[u8]> {
     fn from(val: PluginIDStr) -> Self {
-        val.0.into()
+        val.as_bytes().into()
     }
 }
 
@@ -38,13 +85,19 @@ impl Deref for PluginIDStr {
 
     #[inline]
     fn deref(&self) -> &Self::Target {
-        unsafe { str::from_utf8_unchecked(&self.0) }
+        match &self.0 {
+            Repr::Hash(hex) => unsafe { str::from_utf8_unchecked(hex) },
+            Repr::Name(name) => name,
+        }
     }
 }
 
 impl From<PluginIDStr> for Arc<str> {
     fn from(val: PluginIDStr) -> Self {
-        Arc::from(unsafe { str::from_utf8_unchecked(&val.0) })
+        match val.0 {
+            Repr::Hash(hex) => Arc::from(unsafe { str::from_utf8_unchecked(&hex) }),
+            Repr::Name(name) => name,
+        }
     }
 }
 
@@ -80,7 +133,7 @@ impl std::fmt::Debug for PluginID {
 impl PluginID {
     /// 文字列表現を取得する。
     pub fn as_str(&self) -> PluginIDStr {
-        PluginIDStr(hash::to_hex_bytes(self.0))
+        PluginIDStr(Repr::Hash(hash::to_hex_bytes(self.0)))
     }
 }
 
@@ -125,4 +178,15 @@ mod tests {
         assert_ne!(baseline, different_path);
         assert_ne!(baseline, different_data);
     }
+
+    #[test]
+    fn named_ids_compare_as_strings() {
+        let hashed = b"plugin".plugin_id().as_str();
+        let named = PluginIDStr::named("owner__repo");
+        assert!(named.is_named() && !hashed.is_named());
+        assert_eq!(&*named, "owner__repo");
+        assert_eq!(named, PluginIDStr::named(String::from("owner__repo")));
+        assert_eq!(hashed.cmp(&named), (*hashed).cmp("owner__repo"));
+        assert_eq!(Arc::<str>::from(named).as_ref(), "owner__repo");
+    }
 }
@@ -0,0 +1,564 @@
+//! 公開前のパッケージに施す後処理（`--post-process`）。
+//!
+//! 後処理は [`PostProcessor`] として並べ、`after` の依存順に段を組んで実行する。同じ段の
+//! 処理は並行に走り、各処理はパッケージごとに並行に働く。パッケージごとに行った処理と
+//! 生成・書き換えたファイルを [`POST_PROCESS_FILE`] に記録し、処理の組み合わせが変わった
+//! パッケージは再利用せずに配置し直す。
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinSet;
+
+use crate::log::{Message, msg};
+
+/// パッケージ直下に置く、後処理の記録のファイル名。
+pub(super) const POST_PROCESS_FILE: &str = ".rsplug-post.json";
+
+/// 組み込みの後処理。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, clap::ValueEnum)]
+pub enum PostStep {
+    /// `doc/` の help tags を生成する。
+    Helptags,
+    /// `lua/` 以下の Lua を LuaJIT のバイトコードに置き換える。
+    ByteCompile,
+    /// tree-sitter の文法（`src/parser.c`）を `parser/<lang>.so` にビルドする。
+    Treesitter,
+}
+
+impl PostStep {
+    /// 指定が無いときの後処理。
+    pub const DEFAULT: &[PostStep] = &[PostStep::Helptags];
+
+    /// 記録に残した名前（[`PostProcessor::name`]）の処理。
+    pub(super) fn named(name: &str) -> Option<PostStep> {
+        <PostStep as clap::ValueEnum>::value_variants()
+            .iter()
+            .copied()
+            .find(|step| step.processor().name() == name)
+    }
+
+    fn processor(self) -> Arc<dyn PostProcessor> {
+        match self {
+            PostStep::Helptags => Arc::new(Helptags),
+            PostStep::ByteCompile => Arc::new(ByteCompile),
+            PostStep::Treesitter => Arc::new(Treesitter),
+        }
+    }
+}
+
+pub(super) type PostFuture<T> = Pin<Box<dyn Future<Output = io::Result<T>> + Send + 'static>>;
+
+/// 公開前のパッケージ群に施す処理。
+pub(super) trait PostProcessor: Send + Sync {
+    /// 記録に使う名前。
+    fn name(&self) -> &'static str;
+
+    /// 先に終えておく処理の名前。有効でない処理は無視する。
+    fn after(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// `packages` を処理し、パッケージごとに生成・書き換えたファイル（パッケージ相対）を
+    /// 同じ順で返す。
+    fn run(&self, packages: Arc<[PathBuf]>) -> PostFuture<Vec<Vec<PathBuf>>>;
+}
+
+/// パッケージごとの後処理の記録（[`POST_PROCESS_FILE`]・generation manifest）。
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub(super) struct PostRecord {
+    /// 行った処理の名前（整列済み）。
+    pub(super) steps: Vec<String>,
+    /// 処理ごとの生成・書き換えたファイル。
+    #[serde(default)]
+    pub(super) outputs: BTreeMap<String, Vec<PathBuf>>,
+}
+
+/// 有効な後処理を依存順の段に並べたもの。
+pub(super) struct PostPipeline {
+    stages: Vec<Vec<Arc<dyn PostProcessor>>>,
+    steps: Vec<String>,
+}
+
+impl PostPipeline {
+    pub(super) fn new(steps: &[PostStep]) -> io::Result<Self> {
+        let mut steps = steps.to_vec();
+        steps.sort();
+        steps.dedup();
+        Self::from_processors(steps.into_iter().map(PostStep::processor).collect())
+    }
+
+    fn from_processors(mut pending: Vec<Arc<dyn PostProcessor>>) -> io::Result<Self> {
+        let mut steps: Vec<String> = pending.iter().map(|p| p.name().to_string()).collect();
+        steps.sort();
+        let mut stages = Vec::new();
+        while !pending.is_empty() {
+            let waiting = |name: &str| pending.iter().any(|p| p.name() == name);
+            let (ready, rest): (Vec<_>, Vec<_>) = pending
+                .iter()
+                .cloned()
+                .partition(|p| !p.after().iter().any(|name| waiting(name)));
+            if ready.is_empty() {
+                let names: Vec<_> = rest.iter().map(|p| p.name()).collect();
+                return Err(io::Error::other(format!(
+                    "post-processing steps depend on each other: {}",
+                    names.join(", ")
+                )));
+            }
+            stages.push(ready);
+            pending = rest;
+        }
+        Ok(Self { stages, steps })
+    }
+
+    /// 行う処理の名前（整列済み）。
+    pub(super) fn steps(&self) -> &[String] {
+        &self.steps
+    }
+
+    /// `packages` に後処理を施し、パッケージごとの記録を [`POST_PROCESS_FILE`] に書く。
+    pub(super) async fn run(&self, packages: Vec<PathBuf>) -> io::Result<Vec<PostRecord>> {
+        let packages: Arc<[PathBuf]> = packages.into();
+        let mut records = vec![
+            PostRecord {
+                steps: self.steps.clone(),
+                outputs: BTreeMap::new(),
+            };
+            packages.len()
+        ];
+        for stage in &self.stages {
+            let mut running = JoinSet::new();
+            for processor in stage {
+                let name = processor.name();
+                let run = processor.run(Arc::clone(&packages));
+                running.spawn(async move { run.await.map(|outputs| (name, outputs)) });
+            }
+            while let Some(result) = running.join_next().await {
+                let (name, outputs) = result.map_err(|e| {
+                    io::Error::other(crate::rsplug::util::task::panicked("post-process")(e))
+                })??;
+                for (record, outputs) in records.iter_mut().zip(outputs) {
+                    if !outputs.is_empty() {
+                        record.outputs.insert(name.to_string(), outputs);
+                    }
+                }
+            }
+        }
+        write_records(&packages, &records).await?;
+        Ok(records)
+    }
+
+    /// 後処理を施さずに、行うべき処理だけを `packages` に記録する。
+    pub(super) async fn record(&self, packages: Vec<PathBuf>) -> io::Result<Vec<PostRecord>> {
+        let records = vec![
+            PostRecord {
+                steps: self.steps.clone(),
+                outputs: BTreeMap::new(),
+            };
+            packages.len()
+        ];
+        write_records(&packages, &records).await?;
+        Ok(records)
+    }
+}
+
+async fn write_records(packages: &[PathBuf], records: &[PostRecord]) -> io::Result<()> {
+    for (package, record) in packages.iter().zip(records) {
+        let content = serde_json::to_vec(record).map_err(io::Error::other)?;
+        tokio::fs::write(package.join(POST_PROCESS_FILE), content).await?;
+    }
+    Ok(())
+}
+
+/// 公開済みパッケージ `dir` の記録。記録の無いパッケージは後処理の導入前に helptags だけを
+/// 施したもの。
+pub(super) async fn read_post_record(dir: &Path) -> PostRecord {
+    match tokio::fs::read(dir.join(POST_PROCESS_FILE)).await {
+        Ok(content) => serde_json::from_slice(&content).unwrap_or_default(),
+        Err(_) => PostRecord {
+            steps: vec![Helptags.name().to_string()],
+            outputs: BTreeMap::new(),
+        },
+    }
+}
+
+/// `path` が symlink でない実ディレクトリか。dev プラグインは checkout への symlink として
+/// 置かれるので、後処理はその先（利用者の作業ツリー）に書き込まないようこれで確かめる。
+async fn is_real_dir(path: &Path) -> bool {
+    tokio::fs::symlink_metadata(path)
+        .await
+        .is_ok_and(|metadata| metadata.is_dir())
+}
+
+/// パッケージごとに `f` を build の並列数の範囲で並行に実行する。
+fn per_package<F, Fut>(packages: Arc<[PathBuf]>, f: F) -> PostFuture<Vec<Vec<PathBuf>>>
+where
+    F: Fn(PathBuf) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = io::Result<Vec<PathBuf>>> + Send + 'static,
+{
+    Box::pin(async move {
+        let mut running = JoinSet::new();
+        for (i, package) in packages.iter().enumerate() {
+            let job = f(package.clone());
+            running.spawn(async move {
+                let _build = crate::rsplug::util::resources::BUILD_SEMAPHORE
+                    .acquire()
+                    .await
+                    .map_err(io::Error::other)?;
+                job.await.map(|outputs| (i, outputs))
+            });
+        }
+        let mut outputs = vec![Vec::new(); packages.len()];
+        while let Some(result) = running.join_next().await {
+            let (i, files) = result.map_err(|e| {
+                io::Error::other(crate::rsplug::util::task::panicked("post-process")(e))
+            })??;
+            outputs[i] = files;
+        }
+        Ok(outputs)
+    })
+}
+
+/// 1 つの Neovim で全パッケージの `doc/` の help tags を生成する。パッケージごとに
+/// プロセスを起こさないよう、この処理だけはパッケージ単位に分けない。
+struct Helptags;
+
+impl PostProcessor for Helptags {
+    fn name(&self) -> &'static str {
+        "helptags"
+    }
+
+    fn run(&self, packages: Arc<[PathBuf]>) -> PostFuture<Vec<Vec<PathBuf>>> {
+        Box::pin(async move {
+            let mut help_dirs = Vec::new();
+            for package in packages.iter() {
+                let help_dir = package.join("doc");
+                if is_real_dir(&help_dir).await {
+                    help_dirs.push(help_dir);
+                }
+            }
+            help_dirs.sort();
+            if !help_dirs.is_empty() {
+                run_helptags(&help_dirs).await?;
+            }
+            let mut outputs = Vec::with_capacity(packages.len());
+            for package in packages.iter() {
+                let mut tags = Vec::new();
+                let help_dir = package.join("doc");
+                if help_dirs.contains(&help_dir)
+                    && let Ok(mut entries) = tokio::fs::read_dir(&help_dir).await
+                {
+                    while let Some(entry) = entries.next_entry().await? {
+                        let name = entry.file_name();
+                        if name.to_str().is_some_and(|name| name.starts_with("tags")) {
+                            tags.push(Path::new("doc").join(name));
+                        }
+                    }
+                }
+                tags.sort();
+                outputs.push(tags);
+            }
+            Ok(outputs)
+        })
+    }
+}
+
+async fn run_helptags(help_dirs: &[PathBuf]) -> io::Result<()> {
+    let mut nvim = headless_nvim();
+    for help_dir in help_dirs {
+        msg(Message::InstallHelp {
+            help_dir: help_dir.clone(),
+        });
+        let escaped = help_dir
+            .to_string_lossy()
+            .replace('\\', "\\\\")
+            .replace(' ', "\\ ")
+            .replace('|', "\\|");
+        nvim.arg("-c").arg(format!("helptags {escaped}"));
+    }
+    crate::rsplug::perf::incr(crate::rsplug::perf::PerfOp::HelptagsProcess);
+    nvim.arg("-c").arg("q").status().await.and_then(|code| {
+        if code.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other("Failed to generate staged helptags"))
+        }
+    })
+}
+
+fn headless_nvim() -> tokio::process::Command {
+    let mut nvim = tokio::process::Command::new(crate::rsplug::util::nvim::program());
+    nvim.kill_on_drop(true)
+        .arg("--headless")
+        .arg("-u")
+        .arg("NONE")
+        .arg("-i")
+        .arg("NONE")
+        .arg("-n");
+    nvim
+}
+
+/// `lua/` 以下の `.lua` をバイトコードに置き換える。LuaJIT の `loadfile` はバイトコードも
+/// そのまま読むので、`require` からは変わらず読める。配置したファイルは他のパッケージと
+/// inode を共有していることがあるので、書き換えずに別ファイルから rename で置き換える。
+struct ByteCompile;
+
+const BYTE_COMPILE_SCRIPT: &str = r#"
+for _, path in ipairs(arg) do
+  local chunk = assert(loadfile(path))
+  local tmp = path .. '.rsplug-tmp'
+  local file = assert(io.open(tmp, 'wb'))
+  assert(file:write(string.dump(chunk, true)))
+  assert(file:close())
+  assert(os.rename(tmp, path))
+end
+"#;
+
+impl PostProcessor for ByteCompile {
+    fn name(&self) -> &'static str {
+        "byte-compile"
+    }
+
+    fn run(&self, packages: Arc<[PathBuf]>) -> PostFuture<Vec<Vec<PathBuf>>> {
+        per_package(packages, |package| async move {
+            let mut sources = Vec::new();
+            // `read_dir` の `file_type` は symlink を辿らないので、入口の `lua/` だけ確かめる。
+            let lua = package.join("lua");
+            let mut dirs = Vec::new();
+            if is_real_dir(&lua).await {
+                dirs.push(lua);
+            }
+            while let Some(dir) = dirs.pop() {
+                let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+                    continue;
+                };
+                while let Some(entry) = entries.next_entry().await? {
+                    let file_type = entry.file_type().await?;
+                    let path = entry.path();
+                    if file_type.is_dir() {
+                        dirs.push(path);
+                    } else if file_type.is_file()
+                        && path.extension().is_some_and(|ext| ext == "lua")
+                    {
+                        sources.push(path);
+                    }
+                }
+            }
+            if sources.is_empty() {
+                return Ok(Vec::new());
+            }
+            sources.sort();
+            let script = package.join(".rsplug-byte-compile.lua");
+            tokio::fs::write(&script, BYTE_COMPILE_SCRIPT).await?;
+            let status = headless_nvim()
+                .arg("-l")
+                .arg(&script)
+                .args(&sources)
+                .status()
+                .await;
+            let _ = tokio::fs::remove_file(&script).await;
+            if !status?.success() {
+                return Err(io::Error::other(format!(
+                    "Failed to byte-compile the Lua files in {}",
+                    package.display()
+                )));
+            }
+            Ok(sources
+                .into_iter()
+                .filter_map(|path| path.strip_prefix(&package).ok().map(Path::to_path_buf))
+                .collect())
+        })
+    }
+}
+
+/// `src/parser.c`（と `src/scanner.c`）を C コンパイラ（`$CC`、無ければ `cc`）で
+/// `parser/<lang>.so` にビルドする。`<lang>` は `src/grammar.json` の `name`。
+struct Treesitter;
+
+#[derive(Deserialize)]
+struct Grammar {
+    name: String,
+}
+
+impl PostProcessor for Treesitter {
+    fn name(&self) -> &'static str {
+        "treesitter"
+    }
+
+    fn run(&self, packages: Arc<[PathBuf]>) -> PostFuture<Vec<Vec<PathBuf>>> {
+        per_package(packages, |package| async move {
+            let src = package.join("src");
+            let parser = package.join("parser");
+            if !is_real_dir(&src).await
+                || (tokio::fs::symlink_metadata(&parser).await.is_ok()
+                    && !is_real_dir(&parser).await)
+            {
+                return Ok(Vec::new());
+            }
+            let Ok(grammar) = tokio::fs::read(src.join("grammar.json")).await else {
+                return Ok(Vec::new());
+            };
+            if tokio::fs::metadata(src.join("parser.c")).await.is_err() {
+                return Ok(Vec::new());
+            }
+            let Grammar { name } = serde_json::from_slice(&grammar).map_err(io::Error::other)?;
+            let output = Path::new("parser").join(name).with_extension("so");
+            tokio::fs::create_dir_all(package.join("parser")).await?;
+            let mut cc =
+                tokio::process::Command::new(std::env::var_os("CC").unwrap_or_else(|| "cc".into()));
+            cc.kill_on_drop(true)
+                .current_dir(&package)
+                .args(["-o"])
+                .arg(&output)
+                .args(["-shared", "-fPIC", "-Os", "-I", "src", "src/parser.c"]);
+            if tokio::fs::metadata(src.join("scanner.c")).await.is_ok() {
+                cc.arg("src/scanner.c");
+            }
+            if !cc.status().await?.success() {
+                return Err(io::Error::other(format!(
+                    "Failed to build the tree-sitter parser in {}",
+                    package.display()
+                )));
+            }
+            Ok(vec![output])
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Step(&'static str, &'static [&'static str]);
+
+    impl PostProcessor for Step {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn after(&self) -> &'static [&'static str] {
+            self.1
+        }
+
+        fn run(&self, packages: Arc<[PathBuf]>) -> PostFuture<Vec<Vec<PathBuf>>> {
+            let name = self.0;
+            Box::pin(
+                async move { Ok(packages.iter().map(|_| vec![PathBuf::from(name)]).collect()) },
+            )
+        }
+    }
+
+    fn stage_names(pipeline: &PostPipeline) -> Vec<Vec<&'static str>> {
+        pipeline
+            .stages
+            .iter()
+            .map(|stage| stage.iter().map(|p| p.name()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn steps_run_after_the_steps_they_depend_on() {
+        let pipeline = PostPipeline::from_processors(vec![
+            Arc::new(Step("compile", &["build", "missing"])),
+            Arc::new(Step("build", &[])),
+            Arc::new(Step("tags", &[])),
+        ])
+        .unwrap();
+        assert_eq!(
+            stage_names(&pipeline),
+            vec![vec!["build", "tags"], vec!["compile"]]
+        );
+        assert_eq!(pipeline.steps(), ["build", "compile", "tags"]);
+
+        let cycle = PostPipeline::from_processors(vec![
+            Arc::new(Step("a", &["b"])),
+            Arc::new(Step("b", &["a"])),
+        ]);
+        assert!(cycle.is_err());
+    }
+
+    #[tokio::test]
+    async fn records_are_written_into_each_package() {
+        let root = tempfile::tempdir().unwrap();
+        let packages: Vec<PathBuf> = ["a", "b"]
+            .iter()
+            .map(|name| root.path().join(name))
+            .collect();
+        for package in &packages {
+            std::fs::create_dir(package).unwrap();
+        }
+        let pipeline = PostPipeline::from_processors(vec![Arc::new(Step("build", &[]))]).unwrap();
+        let records = pipeline.run(packages.clone()).await.unwrap();
+        assert_eq!(records.len(), 2);
+        let record = read_post_record(&packages[1]).await;
+        assert_eq!(record, records[1]);
+        assert_eq!(record.outputs["build"], [PathBuf::from("build")]);
+
+        let legacy = read_post_record(root.path()).await;
+        assert_eq!(legacy.steps, ["helptags"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlinked_dev_checkouts_are_left_untouched() {
+        fn listing(dir: &Path) -> Vec<PathBuf> {
+            let mut paths = Vec::new();
+            let mut dirs = vec![dir.to_path_buf()];
+            while let Some(dir) = dirs.pop() {
+                for entry in std::fs::read_dir(dir).unwrap() {
+                    let path = entry.unwrap().path();
+                    if path.is_dir() {
+                        dirs.push(path.clone());
+                    }
+                    paths.push(path);
+                }
+            }
+            paths.sort();
+            paths
+        }
+
+        let root = tempfile::tempdir().unwrap();
+        let checkout = root.path().join("checkout");
+        for (rel, content) in [
+            ("doc/dev.txt", "*dev.txt*\n"),
+            ("lua/dev/init.lua", "return {}\n"),
+            ("src/grammar.json", r#"{"name":"dev"}"#),
+            ("src/parser.c", "int tree_sitter_dev(void) { return 0; }\n"),
+        ] {
+            let path = checkout.join(rel);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        // dev プラグインは各エントリを checkout への symlink として置く。
+        let package = root.path().join("package");
+        std::fs::create_dir(&package).unwrap();
+        for entry in ["doc", "lua", "src"] {
+            std::os::unix::fs::symlink(checkout.join(entry), package.join(entry)).unwrap();
+        }
+        let before = listing(&checkout);
+
+        let steps = [
+            PostStep::Helptags,
+            PostStep::ByteCompile,
+            PostStep::Treesitter,
+        ];
+        let records = PostPipeline::new(&steps)
+            .unwrap()
+            .run(vec![package.clone()])
+            .await
+            .unwrap();
+        assert!(records[0].outputs.is_empty());
+        assert_eq!(listing(&checkout), before);
+        assert_eq!(
+            std::fs::read(checkout.join("lua/dev/init.lua")).unwrap(),
+            b"return {}\n"
+        );
+    }
+}
@@ -0,0 +1,649 @@
+//! File-provenance index for installed packages.
+//!
+//! `PackPlan::insert` records, for every package, which repository snapshot
+//! each placed entry comes from and which configured plugins were merged into
+//! it. Publication persists the index as `pack/_gen/provenance.json`, and
+//! `rsplug owners` resolves an installed path back through it.
+//!
+//! Entries are the merge-planning keys (sealed directories or files), so a
+//! path inside a sealed directory is attributed to the closest entry above it.
+
+use super::*;
+
+/// `pack/_gen/` 直下の provenance index のファイル名。
+pub(super) const PROVENANCE_FILE: &str = "provenance.json";
+
+#[derive(Default, Serialize, Deserialize)]
+pub(super) struct ProvenanceIndex {
+    version: u8,
+    /// パッケージ id → 由来。
+    packages: BTreeMap<String, PackageProvenance>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PackageProvenance {
+    /// このパッケージに併合された設定上の名前。
+    names: BTreeSet<String>,
+    /// rsplug が生成した制御パッケージ（lazy loader・help）か。
+    control: bool,
+    merge_policy: Option<MergePolicy>,
+    /// 配置エントリ（パッケージ相対）→ 由来。
+    entries: BTreeMap<PathBuf, EntrySource>,
+}
+
+/// 配置エントリ 1 つの由来。
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EntrySource {
+    /// repo snapshot のファイル・ディレクトリ。
+    Repo {
+        /// `repos/` からの相対 repo パス（例: `github.com/owner/repo`）。
+        repo: String,
+        rev: String,
+        /// 配置元の snapshot root。
+        snapshot: Option<PathBuf>,
+        /// dev checkout（symlink 配置）の内容 hash。前回の index と比べて変更を検知できる。
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tree: Option<String>,
+    },
+    /// rsplug が生成したファイル。
+    Generated,
+}
+
+impl EntrySource {
+    fn of(item: &FileItem) -> Self {
+        match &item.identity {
+            FileIdentity::RepoFile(file) => EntrySource::Repo {
+                repo: file.snapshot.repo_cache_dir.to_string_lossy().into_owned(),
+                rev: String::from_utf8_lossy(&file.snapshot.head_rev).into_owned(),
+                snapshot: snapshot_root_of(&item.source).map(Path::to_path_buf),
+                tree: match item.source.as_ref() {
+                    FileSource::Directory { symlink: true, .. } => file
+                        .snapshot
+                        .dirty_diff
+                        .map(crate::rsplug::util::hash::to_hex_bytes)
+                        .map(|hex| String::from_utf8_lossy(&hex).into_owned()),
+                    _ => None,
+                },
+            },
+            FileIdentity::GeneratedFile { .. } => EntrySource::Generated,
+        }
+    }
+}
+
+impl ProvenanceIndex {
+    const VERSION: u8 = 1;
+
+    pub(super) fn record(&mut self, id: &PluginIDStr, plugin: &LoadedPlugin) {
+        let HowToPlaceFiles::CopyEachFile(files) = &plugin.files;
+        let entries = files
+            .iter()
+            .map(|(path, item)| (path.clone(), EntrySource::of(item)))
+            .collect();
+        self.packages.insert(
+            id.to_string(),
+            PackageProvenance {
+                names: plugin.source_names.clone(),
+                control: plugin.is_lazy_registration,
+                merge_policy: plugin.merge_policy,
+                entries,
+            },
+        );
+    }
+
+    /// 制御パッケージ以外のパッケージ id と、併合された設定上の名前。
+    pub(super) fn user_packages(&self) -> impl Iterator<Item = (&str, &BTreeSet<String>)> {
+        self.packages
+            .iter()
+            .filter(|(_, package)| !package.control)
+            .map(|(id, package)| (id.as_str(), &package.names))
+    }
+
+    /// 制御パッケージ以外のパッケージと、その配置エントリ（`rsplug plan` 用）。
+    pub(super) fn planned_packages(&self, system: impl Fn(&str) -> bool) -> Vec<PlannedPackage> {
+        self.packages
+            .iter()
+            .filter(|(_, package)| !package.control)
+            .map(|(id, package)| PlannedPackage {
+                id: id.clone(),
+                names: package.names.iter().cloned().collect(),
+                merge_policy: package.merge_policy,
+                system: system(id),
+                entries: package.entries.clone(),
+            })
+            .collect()
+    }
+
+    /// `gen_root/provenance.json` を原子的に置き換える。内容が同じなら書かない。
+    pub(super) async fn write(mut self, gen_root: &Path) -> io::Result<()> {
+        self.version = Self::VERSION;
+        let content = serde_json::to_vec_pretty(&self).map_err(io::Error::other)?;
+        let path = gen_root.join(PROVENANCE_FILE);
+        if tokio::fs::read(&path)
+            .await
+            .is_ok_and(|current| current == content)
+        {
+            return Ok(());
+        }
+        let tmp = gen_root.join(format!(
+            ".{PROVENANCE_FILE}.tmp-{}",
+            STAGING_NONCE.fetch_add(1, AtomicOrdering::Relaxed)
+        ));
+        tokio::fs::write(&tmp, content).await?;
+        tokio::fs::rename(&tmp, &path).await
+    }
+
+    async fn read(gen_root: &Path) -> io::Result<Self> {
+        let content = tokio::fs::read(gen_root.join(PROVENANCE_FILE)).await?;
+        serde_json::from_slice(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// 配置する予定のパッケージ（`rsplug plan` の 1 パッケージ分）。
+#[derive(Debug, Serialize)]
+pub struct PlannedPackage {
+    /// パッケージ id（`pack/_gen/opt/<id>`）。
+    pub id: String,
+    /// 併合される設定上の名前。2 つ以上なら併合。
+    pub names: Vec<String>,
+    pub merge_policy: Option<MergePolicy>,
+    /// `--packpath system=<DIR>` に置くか。
+    pub system: bool,
+    /// 配置エントリ（パッケージ相対）→ 由来。
+    pub entries: BTreeMap<PathBuf, EntrySource>,
+}
+
+/// インストール済みパスの所有者（`rsplug owners` の 1 行分）。
+#[derive(Debug)]
+pub struct FileOwner {
+    /// パッケージ id（`pack/_gen/opt/<id>`）。
+    pub package: String,
+    /// 一致した配置エントリ（パッケージ相対）。
+    pub entry: PathBuf,
+    pub source: EntrySource,
+    /// パッケージに併合された設定上の名前。
+    pub names: Vec<String>,
+    /// パッケージに寄与した repo 数。
+    pub repositories: usize,
+    pub control: bool,
+    pub merge_policy: Option<MergePolicy>,
+}
+
+impl std::fmt::Display for FileOwner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let entry = Path::new("opt").join(&self.package).join(&self.entry);
+        match &self.source {
+            EntrySource::Repo {
+                repo,
+                rev,
+                snapshot,
+                ..
+            } => {
+                writeln!(f, "{} <- {repo} @ {rev}", entry.display())?;
+                if let Some(snapshot) = snapshot {
+                    writeln!(f, "    snapshot: {}", snapshot.display())?;
+                }
+            }
+            EntrySource::Generated => writeln!(f, "{} <- generated by rsplug", entry.display())?,
+        }
+        let kind = if self.control { "control" } else { "plugins" };
+        let policy = match self.merge_policy {
+            Some(MergePolicy::Never) => "never",
+            Some(MergePolicy::SameLazyType) | None => "same-lazy-type",
+            Some(MergePolicy::Aggressive) => "aggressive",
+        };
+        write!(
+            f,
+            "    {kind}: {} ({} repositories merged, merge = {policy})",
+            self.names.join(", "),
+            self.repositories,
+        )
+    }
+}
+
+/// `path`（絶対パス、packpath 相対、または `opt/<id>/...`）の所有者を provenance index から引く。
+///
+/// ファイルなら最も近い祖先エントリ 1 つ、ディレクトリならその配下のエントリすべてを返す。
+/// index が無ければ `NotFound`、パッケージ外のパスなら空を返す。
+pub async fn find_owners(packpath: &Path, path: &Path) -> io::Result<Vec<FileOwner>> {
+    let gen_root = packpath.join("pack").join("_gen");
+    let index = ProvenanceIndex::read(&gen_root).await?;
+    let Some((package, relative)) = package_relative(&gen_root.join("opt"), packpath, path).await
+    else {
+        return Ok(Vec::new());
+    };
+    let Some(provenance) = index.packages.get(&package) else {
+        return Ok(Vec::new());
+    };
+    let matched: Vec<(&PathBuf, &EntrySource)> = match provenance
+        .entries
+        .iter()
+        .filter(|(entry, _)| relative.starts_with(entry))
+        .max_by_key(|(entry, _)| entry.components().count())
+    {
+        Some(ancestor) => vec![ancestor],
+        None => provenance
+            .entries
+            .iter()
+            .filter(|(entry, _)| entry.starts_with(&relative))
+            .collect(),
+    };
+    let repositories = provenance
+        .entries
+        .values()
+        .filter_map(|source| match source {
+            EntrySource::Repo { repo, .. } => Some(repo),
+            EntrySource::Generated => None,
+        })
+        .collect::<BTreeSet<_>>()
+        .len();
+    Ok(matched
+        .into_iter()
+        .map(|(entry, source)| FileOwner {
+            package: package.clone(),
+            entry: entry.clone(),
+            source: source.clone(),
+            names: provenance.names.iter().cloned().collect(),
+            repositories,
+            control: provenance.control,
+            merge_policy: provenance.merge_policy,
+        })
+        .collect())
+}
+
+/// provenance index からパッケージ id → 併合された設定上の名前の対応を引く。
+pub async fn package_names(packpath: &Path) -> io::Result<BTreeMap<String, Vec<String>>> {
+    let index = ProvenanceIndex::read(&packpath.join("pack").join("_gen")).await?;
+    Ok(index
+        .packages
+        .into_iter()
+        .map(|(id, package)| (id, package.names.into_iter().collect()))
+        .collect())
+}
+
+/// provenance index から、制御パッケージ以外のパッケージ id → 併合された設定上の名前の対応を引く。
+pub async fn user_package_names(packpath: &Path) -> io::Result<BTreeMap<String, Vec<String>>> {
+    let index = ProvenanceIndex::read(&packpath.join("pack").join("_gen")).await?;
+    Ok(index
+        .user_packages()
+        .map(|(id, names)| (id.to_string(), names.iter().cloned().collect()))
+        .collect())
+}
+
+/// インストール済みパッケージに配置された repo 1 つ（`rsplug sbom` の 1 要素）。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledRepository {
+    /// `repos/` からの相対 repo パス（例: `github.com/owner/repo`）。
+    pub repo: String,
+    pub rev: String,
+    /// この repo だけから成るパッケージに併合された設定上の名前。
+    pub names: Vec<String>,
+    /// snapshot 直下のライセンスファイル（snapshot root からの相対パス）。
+    pub license_files: Vec<PathBuf>,
+    /// ライセンスファイルの本文から判定した SPDX license expression。
+    pub license: Option<String>,
+}
+
+/// 本文の判定に読むライセンスファイルの先頭バイト数。
+const LICENSE_READ_LIMIT: u64 = 64 * 1024;
+
+/// provenance index から、インストール済みの repo とそのライセンスを列挙する。
+///
+/// ライセンスファイルは snapshot manifest の列挙結果から取り（manifest が無ければ
+/// snapshot 直下を読む）、本文を [`detect_license`] で SPDX id に対応付ける。
+/// 判定できないものは `license` が `None` になる。index が無ければ `NotFound`。
+pub async fn installed_repositories(packpath: &Path) -> io::Result<Vec<InstalledRepository>> {
+    let index = ProvenanceIndex::read(&packpath.join("pack").join("_gen")).await?;
+    let mut repositories: BTreeMap<(String, String), (Option<PathBuf>, BTreeSet<String>)> =
+        BTreeMap::new();
+    for package in index.packages.values().filter(|package| !package.control) {
+        let mut sources = BTreeMap::new();
+        for source in package.entries.values() {
+            if let EntrySource::Repo {
+                repo,
+                rev,
+                snapshot,
+                ..
+            } = source
+            {
+                sources
+                    .entry((repo.clone(), rev.clone()))
+                    .or_insert_with(|| snapshot.clone());
+            }
+        }
+        let single = sources.len() == 1;
+        for (key, snapshot) in sources {
+            let (known, names) = repositories.entry(key).or_default();
+            if known.is_none() {
+                *known = snapshot;
+            }
+            if single {
+                names.extend(package.names.iter().cloned());
+            }
+        }
+    }
+
+    let mut installed = Vec::with_capacity(repositories.len());
+    for ((repo, rev), (snapshot, names)) in repositories {
+        let mut license_files = Vec::new();
+        let mut licenses = BTreeSet::new();
+        if let Some(snapshot) = &snapshot {
+            license_files = snapshot_license_files(snapshot).await;
+            for file in &license_files {
+                if let Some(license) = read_license(&snapshot.join(file)).await {
+                    licenses.insert(license);
+                }
+            }
+        }
+        let license =
+            (!licenses.is_empty()).then(|| licenses.into_iter().collect::<Vec<_>>().join(" AND "));
+        installed.push(InstalledRepository {
+            repo,
+            rev,
+            names: names.into_iter().collect(),
+            license_files,
+            license,
+        });
+    }
+    Ok(installed)
+}
+
+/// provenance index から、インストール済みの repo（`repos/` からの相対パス）と配置元の snapshot root。
+/// 同じ repo の snapshot が複数あれば最初に見つかったものを返す。index が無ければ `NotFound`。
+pub async fn installed_snapshots(packpath: &Path) -> io::Result<BTreeMap<String, PathBuf>> {
+    let index = ProvenanceIndex::read(&packpath.join("pack").join("_gen")).await?;
+    let mut snapshots = BTreeMap::new();
+    for package in index.packages.values().filter(|package| !package.control) {
+        for source in package.entries.values() {
+            if let EntrySource::Repo {
+                repo,
+                snapshot: Some(snapshot),
+                ..
+            } = source
+            {
+                snapshots
+                    .entry(repo.clone())
+                    .or_insert_with(|| snapshot.clone());
+            }
+        }
+    }
+    Ok(snapshots)
+}
+
+/// snapshot 直下のライセンスファイル。manifest があればその列挙結果を使う。
+async fn snapshot_license_files(snapshot: &Path) -> Vec<PathBuf> {
+    if let Ok(bytes) = tokio::fs::read(snapshot.join(MANIFEST_FILE)).await
+        && let Ok(mut manifest) = serde_json::from_slice::<SnapshotManifest>(&bytes)
+        && manifest.validate()
+    {
+        manifest.reindex();
+        return manifest.license_files().to_vec();
+    }
+    let mut files = Vec::new();
+    let Ok(mut entries) = tokio::fs::read_dir(snapshot).await else {
+        return files;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if let Some(name) = entry.file_name().to_str()
+            && is_license_file_name(name)
+            && tokio::fs::metadata(entry.path())
+                .await
+                .is_ok_and(|metadata| metadata.is_file())
+        {
+            files.push(PathBuf::from(name));
+        }
+    }
+    files.sort();
+    files
+}
+
+async fn read_license(path: &Path) -> Option<&'static str> {
+    use tokio::io::AsyncReadExt;
+    let file = tokio::fs::File::open(path).await.ok()?;
+    let mut text = Vec::new();
+    file.take(LICENSE_READ_LIMIT)
+        .read_to_end(&mut text)
+        .await
+        .ok()?;
+    detect_license(&String::from_utf8_lossy(&text))
+}
+
+/// ライセンス本文を代表的な文言で SPDX license id に対応付ける。
+///
+/// 全文の照合ではなく、各ライセンスに固有の一文で判定する。GPL 系は本文から
+/// "or later" の許諾を読み取れないため `-only` を返す。
+fn detect_license(text: &str) -> Option<&'static str> {
+    let text = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    let has = |phrase: &str| text.contains(phrase);
+    let version = |v: &str| has(&format!("version {v}"));
+    // GNU 系の本文は互いの名前に言及するので、最初に現れる名前（表題）で決める。
+    let gnu = [
+        "gnu affero general public license",
+        "gnu lesser general public license",
+        "gnu library general public license",
+        "gnu general public license",
+    ]
+    .into_iter()
+    .filter_map(|title| Some((text.find(title)?, title)))
+    .min();
+    let id = if has("apache license") && version("2.0") {
+        "Apache-2.0"
+    } else if has("mozilla public license") && version("2.0") {
+        "MPL-2.0"
+    } else if let Some((_, title)) = gnu {
+        match (title, version("3")) {
+            ("gnu affero general public license", _) => "AGPL-3.0-only",
+            ("gnu general public license", true) => "GPL-3.0-only",
+            ("gnu general public license", false) => "GPL-2.0-only",
+            (_, true) => "LGPL-3.0-only",
+            (_, false) => "LGPL-2.1-only",
+        }
+    } else if has("this is free and unencumbered software released into the public domain") {
+        "Unlicense"
+    } else if has("permission is hereby granted, free of charge") {
+        "MIT"
+    } else if has(
+        "permission to use, copy, modify, and/or distribute this software for any purpose",
+    ) {
+        "ISC"
+    } else if has("redistribution and use in source and binary forms") {
+        if has("neither the name") || has("names of its contributors") {
+            "BSD-3-Clause"
+        } else {
+            "BSD-2-Clause"
+        }
+    } else if has("do what the fuck you want to") {
+        "WTFPL"
+    } else if has("cc0 1.0 universal") {
+        "CC0-1.0"
+    } else if has("vim license") || has("see \":help license\"") {
+        "Vim"
+    } else {
+        return None;
+    };
+    Some(id)
+}
+
+/// `path` を `(package id, パッケージ相対パス)` に分解する。実在するパスは symlink
+/// （`by-name/` 等）を解決してから `opt/` 配下か判定する。
+async fn package_relative(opt: &Path, packpath: &Path, path: &Path) -> Option<(String, PathBuf)> {
+    let resolved = match tokio::fs::canonicalize(path).await {
+        Ok(resolved) => {
+            let opt = tokio::fs::canonicalize(opt)
+                .await
+                .unwrap_or_else(|_| opt.to_path_buf());
+            resolved.strip_prefix(&opt).ok().map(Path::to_path_buf)
+        }
+        Err(_) => None,
+    };
+    let inside = resolved.or_else(|| {
+        [opt, &packpath.join("pack").join("_gen").join("opt")]
+            .into_iter()
+            .find_map(|prefix| path.strip_prefix(prefix).ok())
+            .or_else(|| path.strip_prefix("pack/_gen/opt").ok())
+            .or_else(|| path.strip_prefix("opt").ok())
+            .map(Path::to_path_buf)
+    })?;
+    let mut components = inside.components();
+    let package = components.next()?.as_os_str().to_str()?.to_string();
+    Some((package, components.as_path().to_path_buf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index_with(entries: &[(&str, EntrySource)]) -> ProvenanceIndex {
+        let mut index = ProvenanceIndex::default();
+        index.packages.insert(
+            "p".repeat(32),
+            PackageProvenance {
+                names: BTreeSet::from(["a.nvim".to_string(), "b.nvim".to_string()]),
+                control: false,
+                merge_policy: Some(MergePolicy::SameLazyType),
+                entries: entries
+                    .iter()
+                    .map(|(path, source)| (PathBuf::from(path), source.clone()))
+                    .collect(),
+            },
+        );
+        index
+    }
+
+    fn repo(name: &str) -> EntrySource {
+        EntrySource::Repo {
+            repo: format!("github.com/owner/{name}"),
+            rev: "0123abcd".to_string(),
+            snapshot: None,
+            tree: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn owners_resolve_files_to_closest_entry_and_dirs_to_children() {
+        let tmp = tempfile::tempdir().unwrap();
+        let gen_root = tmp.path().join("pack/_gen");
+        tokio::fs::create_dir_all(&gen_root).await.unwrap();
+        index_with(&[
+            ("lua/a", repo("a.nvim")),
+            ("lua/b", repo("b.nvim")),
+            ("plugin/b.lua", repo("b.nvim")),
+        ])
+        .write(&gen_root)
+        .await
+        .unwrap();
+        let package = "p".repeat(32);
+
+        let owners = find_owners(
+            tmp.path(),
+            &PathBuf::from(format!("opt/{package}/lua/a/init.lua")),
+        )
+        .await
+        .unwrap();
+        assert_eq!(owners.len(), 1);
+        assert_eq!(owners[0].entry, PathBuf::from("lua/a"));
+        assert_eq!(owners[0].source, repo("a.nvim"));
+        assert_eq!(owners[0].repositories, 2);
+        assert_eq!(owners[0].names, vec!["a.nvim", "b.nvim"]);
+
+        let owners = find_owners(tmp.path(), &gen_root.join("opt").join(&package).join("lua"))
+            .await
+            .unwrap();
+        let entries: Vec<_> = owners.iter().map(|owner| owner.entry.clone()).collect();
+        assert_eq!(
+            entries,
+            vec![PathBuf::from("lua/a"), PathBuf::from("lua/b")]
+        );
+
+        assert!(
+            find_owners(tmp.path(), Path::new("/elsewhere/file.lua"))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn detect_license_recognizes_common_license_texts() {
+        for (text, id) in [
+            (
+                "MIT License\n\nPermission is hereby granted, free of charge, to any person",
+                Some("MIT"),
+            ),
+            (
+                "                                 Apache License\n                           Version 2.0, January 2004",
+                Some("Apache-2.0"),
+            ),
+            (
+                "GNU GENERAL PUBLIC LICENSE\n Version 3, 29 June 2007\n...\n13. Use with the GNU Affero General Public License.",
+                Some("GPL-3.0-only"),
+            ),
+            (
+                "Redistribution and use in source and binary forms, with or without\nmodification, are permitted. Neither the name of the copyright holder",
+                Some("BSD-3-Clause"),
+            ),
+            ("All rights reserved.", None),
+        ] {
+            assert_eq!(detect_license(text), id, "{text}");
+        }
+    }
+
+    #[tokio::test]
+    async fn installed_repositories_report_licenses_of_snapshots() {
+        let tmp = tempfile::tempdir().unwrap();
+        let gen_root = tmp.path().join("pack/_gen");
+        let snapshot = tmp.path().join("repos/github.com/owner/a.nvim/snapshot");
+        tokio::fs::create_dir_all(&gen_root).await.unwrap();
+        tokio::fs::create_dir_all(&snapshot).await.unwrap();
+        tokio::fs::write(
+            snapshot.join("LICENSE"),
+            "Permission is hereby granted, free of charge, to any person",
+        )
+        .await
+        .unwrap();
+        let a = EntrySource::Repo {
+            repo: "github.com/owner/a.nvim".to_string(),
+            rev: "0123abcd".to_string(),
+            snapshot: Some(snapshot),
+            tree: None,
+        };
+        let mut index = index_with(&[("lua/a", a.clone())]);
+        index.packages.insert(
+            "q".repeat(32),
+            PackageProvenance {
+                names: BTreeSet::from(["merged".to_string()]),
+                control: false,
+                merge_policy: None,
+                entries: BTreeMap::from([
+                    (PathBuf::from("plugin/a.lua"), a),
+                    (PathBuf::from("plugin/b.lua"), repo("b.nvim")),
+                ]),
+            },
+        );
+        index.write(&gen_root).await.unwrap();
+
+        let installed = installed_repositories(tmp.path()).await.unwrap();
+
+        assert_eq!(installed.len(), 2);
+        assert_eq!(installed[0].repo, "github.com/owner/a.nvim");
+        assert_eq!(installed[0].names, vec!["a.nvim", "b.nvim"]);
+        assert_eq!(installed[0].license_files, vec![PathBuf::from("LICENSE")]);
+        assert_eq!(installed[0].license.as_deref(), Some("MIT"));
+        assert_eq!(installed[1].repo, "github.com/owner/b.nvim");
+        assert!(installed[1].names.is_empty());
+        assert_eq!(installed[1].license, None);
+    }
+
+    #[tokio::test]
+    async fn owners_without_index_is_not_found() {
+        let tmp = tempfile::tempdir().unwrap();
+        let err = find_owners(tmp.path(), Path::new("opt/x/y"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,325 @@
+//! Source backends: how a configured source is resolved, fetched, and placed.
+//!
+//! `Plugin::load_early` does not know how a kind of source is obtained. It
+//! asks the [`SourceBackends`] registry of the run for the backend that handles
+//! the entry and drives it through the same steps for every kind: a local
+//! checkout is used as is, otherwise the wanted commit is resolved, the
+//! objects are fetched, and the files are materialized into the snapshot
+//! directory chosen by the caller. Snapshot keys, planning, builds, and
+//! assembly stay in `Plugin::load_early`/`load_late` and are shared by all
+//! backends.
+//!
+//! The built-in backends are [`GitBackend`] (GitHub shorthand and Git URLs,
+//! with the GitHub tarball as a fetch strategy) and [`LocalBackend`]
+//! (`dev = true` checkouts). A backend registered later takes precedence, so
+//! a new kind of source is added with [`SnapshotCatalogCache::with_backend`]
+//! without touching the load pipeline.
+
+use std::{future::Future, pin::Pin};
+
+use super::*;
+
+/// backend の非同期処理。`dyn SourceBackend` で呼べるよう Box にする。
+pub(crate) type SourceFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, Error>> + Send + 'a>>;
+
+/// 欲しい commit を決めるのに要るもの（[`SourceBackend::resolve`] の引数）。
+pub(crate) struct ResolveCtx<'a> {
+    pub(super) catalog: &'a SnapshotCatalog,
+    pub(super) url: &'a Arc<str>,
+    pub(super) rev: &'a Option<Arc<str>>,
+    pub(super) token: &'a Option<Arc<str>>,
+    pub(super) network: &'a adaptive_semaphore::NetworkLimits,
+    pub(super) breaker: &'a util::github::CircuitBreaker,
+    pub(super) http_client: &'a reqwest::Client,
+    pub(super) install: bool,
+    pub(super) update: bool,
+    pub(super) locked_rev: Option<&'a str>,
+    pub(super) source_name: &'a Option<String>,
+    pub(super) logid: &'a str,
+    pub(super) canonical: &'a str,
+    pub(super) jobs: &'a SnapshotCatalogCache,
+}
+
+/// 取得元の種類ごとの解決・取得・配置。
+pub(crate) trait SourceBackend: Send + Sync {
+    /// 登録した backend を見分けるための名前。
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn name(&self) -> &'static str;
+
+    /// この backend が `cache` の取得元を受け持つか。
+    fn handles(&self, cache: &CacheConfig) -> bool;
+
+    /// 取得元の identity。`repos/` 相対の cache ディレクトリで、snapshot key・lock key の元になる。
+    fn identity(&self, repo: &RepoSource) -> PathBuf {
+        repo.default_cachedir()
+    }
+
+    /// 取得せずにそのまま置くローカル checkout。`Some` なら resolve 以降は呼ばない。
+    fn checkout<'a>(
+        &'a self,
+        _repo: &'a RepoSource,
+        _dev_path: &'a Path,
+    ) -> SourceFuture<'a, Option<PathBuf>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    /// 欲しい commit を決める。`Ok(None)` なら読み込まない（未インストールで install でない等）。
+    fn resolve<'a>(&'a self, ctx: &'a ResolveCtx<'a>)
+    -> SourceFuture<'a, Option<ResolvedRevision>>;
+
+    /// `rsplug plan`: snapshot を作るのに取得が要るなら、その手段（`tarball`・`git` など）。
+    fn planned_fetch<'a>(&'a self, ctx: &'a FetchCtx<'a>)
+    -> SourceFuture<'a, Option<&'static str>>;
+
+    /// `ctx.oid` の object を手元に用意する。`Ok(false)` なら読み込まない。
+    fn fetch<'a>(&'a self, ctx: &'a FetchCtx<'a>) -> SourceFuture<'a, bool>;
+
+    /// `ctx.oid` のファイルを `dest` に置く。`Ok(None)` なら読み込まない。
+    fn materialize<'a>(
+        &'a self,
+        ctx: &'a FetchCtx<'a>,
+        dest: &'a Path,
+    ) -> SourceFuture<'a, Option<MaterializedRepo>>;
+}
+
+/// GitHub 省略記法と Git URL。GitHub の tarball は取得手段の 1 つとして扱う。
+pub(crate) struct GitBackend;
+
+impl SourceBackend for GitBackend {
+    fn name(&self) -> &'static str {
+        "git"
+    }
+
+    fn handles(&self, cache: &CacheConfig) -> bool {
+        cache.repo.is_some()
+    }
+
+    fn resolve<'a>(
+        &'a self,
+        ctx: &'a ResolveCtx<'a>,
+    ) -> SourceFuture<'a, Option<ResolvedRevision>> {
+        Box::pin(resolve_target_commit(
+            ctx.catalog,
+            ctx.url,
+            ctx.rev,
+            ctx.token,
+            ctx.network,
+            ctx.breaker,
+            ctx.http_client,
+            ctx.install,
+            ctx.update,
+            ctx.locked_rev,
+            ctx.source_name,
+            ctx.logid,
+            ctx.canonical.to_string(),
+            ctx.jobs,
+        ))
+    }
+
+    fn planned_fetch<'a>(
+        &'a self,
+        ctx: &'a FetchCtx<'a>,
+    ) -> SourceFuture<'a, Option<&'static str>> {
+        Box::pin(async move {
+            if ctx.use_tarball {
+                return Ok(Some("tarball"));
+            }
+            let cached = match util::git::open_source(ctx.source_git).await {
+                Ok(source) => source.contains_oid(ctx.oid).await?,
+                Err(_) => false,
+            };
+            Ok((!cached).then_some("git"))
+        })
+    }
+
+    fn fetch<'a>(&'a self, ctx: &'a FetchCtx<'a>) -> SourceFuture<'a, bool> {
+        Box::pin(async move {
+            // tarball は materialize で落とすので source.git は要らない。
+            if ctx.use_tarball {
+                return Ok(true);
+            }
+            acquisition::ensure_source_git(ctx).await
+        })
+    }
+
+    fn materialize<'a>(
+        &'a self,
+        ctx: &'a FetchCtx<'a>,
+        dest: &'a Path,
+    ) -> SourceFuture<'a, Option<MaterializedRepo>> {
+        Box::pin(acquisition::materialize(ctx, dest, ctx.use_tarball))
+    }
+}
+
+/// `dev = true`: `--dev-path` 配下の checkout をそのまま使い、取得も更新もしない。
+pub(crate) struct LocalBackend;
+
+impl SourceBackend for LocalBackend {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn handles(&self, cache: &CacheConfig) -> bool {
+        cache.dev && cache.repo.is_some()
+    }
+
+    fn checkout<'a>(
+        &'a self,
+        repo: &'a RepoSource,
+        dev_path: &'a Path,
+    ) -> SourceFuture<'a, Option<PathBuf>> {
+        Box::pin(async move {
+            let path = dev_path.join(repo.basename());
+            if !tokio::fs::metadata(&path)
+                .await
+                .is_ok_and(|metadata| metadata.is_dir())
+            {
+                return Err(Error::DevCheckoutMissing {
+                    repo: Arc::from(repo.url()),
+                    path,
+                });
+            }
+            Ok(Some(path))
+        })
+    }
+
+    // checkout が常に `Some` か Err を返すので、以下は呼ばれない。
+    fn resolve<'a>(
+        &'a self,
+        _ctx: &'a ResolveCtx<'a>,
+    ) -> SourceFuture<'a, Option<ResolvedRevision>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    fn planned_fetch<'a>(
+        &'a self,
+        _ctx: &'a FetchCtx<'a>,
+    ) -> SourceFuture<'a, Option<&'static str>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    fn fetch<'a>(&'a self, _ctx: &'a FetchCtx<'a>) -> SourceFuture<'a, bool> {
+        Box::pin(async { Ok(false) })
+    }
+
+    fn materialize<'a>(
+        &'a self,
+        _ctx: &'a FetchCtx<'a>,
+        _dest: &'a Path,
+    ) -> SourceFuture<'a, Option<MaterializedRepo>> {
+        Box::pin(async { Ok(None) })
+    }
+}
+
+/// run で使う backend の一覧。後から登録したものを先に試す。
+#[derive(Clone)]
+pub(crate) struct SourceBackends {
+    backends: Vec<Arc<dyn SourceBackend>>,
+}
+
+impl Default for SourceBackends {
+    fn default() -> Self {
+        Self {
+            backends: vec![Arc::new(GitBackend), Arc::new(LocalBackend)],
+        }
+    }
+}
+
+impl SourceBackends {
+    /// `backend` を登録する。既存の backend と同じ取得元を受け持つなら、こちらが優先される。
+    pub(crate) fn register(&mut self, backend: Arc<dyn SourceBackend>) {
+        self.backends.push(backend);
+    }
+
+    /// `cache` を受け持つ backend。無ければ `None`（`repo` の無い script-only など）。
+    pub(crate) fn for_config(&self, cache: &CacheConfig) -> Option<&dyn SourceBackend> {
+        self.backends
+            .iter()
+            .rev()
+            .find(|backend| backend.handles(cache))
+            .map(Arc::as_ref)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Vendored;
+
+    impl SourceBackend for Vendored {
+        fn name(&self) -> &'static str {
+            "vendored"
+        }
+
+        fn handles(&self, cache: &CacheConfig) -> bool {
+            cache
+                .repo
+                .as_ref()
+                .is_some_and(|repo| repo.url().starts_with("https://vendor.example/"))
+        }
+
+        fn resolve<'a>(
+            &'a self,
+            _ctx: &'a ResolveCtx<'a>,
+        ) -> SourceFuture<'a, Option<ResolvedRevision>> {
+            Box::pin(async { Ok(None) })
+        }
+
+        fn planned_fetch<'a>(
+            &'a self,
+            _ctx: &'a FetchCtx<'a>,
+        ) -> SourceFuture<'a, Option<&'static str>> {
+            Box::pin(async { Ok(None) })
+        }
+
+        fn fetch<'a>(&'a self, _ctx: &'a FetchCtx<'a>) -> SourceFuture<'a, bool> {
+            Box::pin(async { Ok(false) })
+        }
+
+        fn materialize<'a>(
+            &'a self,
+            _ctx: &'a FetchCtx<'a>,
+            _dest: &'a Path,
+        ) -> SourceFuture<'a, Option<MaterializedRepo>> {
+            Box::pin(async { Ok(None) })
+        }
+    }
+
+    fn cache(toml: &str) -> CacheConfig {
+        let config: Config = toml::from_str(toml).unwrap();
+        config.plugins.into_iter().next().unwrap().cache
+    }
+
+    #[test]
+    fn registry_picks_the_latest_backend_that_handles_the_source() {
+        let mut backends = SourceBackends::default();
+        let github = cache("[[plugins]]\nrepo = \"o/r\"\n");
+        let dev = cache("[[plugins]]\nrepo = \"o/r\"\ndev = true\n");
+        let vendored = cache("[[plugins]]\nrepo = \"https://vendor.example/o/r\"\n");
+        let script = cache("[[plugins]]\nlua_source = \"vim.g.x = 1\"\n");
+
+        let name = |backends: &SourceBackends, cache| backends.for_config(cache).map(|b| b.name());
+        assert_eq!(name(&backends, &github), Some("git"));
+        assert_eq!(name(&backends, &dev), Some("local"));
+        assert_eq!(name(&backends, &vendored), Some("git"));
+        assert_eq!(name(&backends, &script), None);
+
+        backends.register(Arc::new(Vendored));
+        assert_eq!(name(&backends, &vendored), Some("vendored"));
+        assert_eq!(name(&backends, &github), Some("git"));
+    }
+
+    #[tokio::test]
+    async fn local_backend_requires_the_checkout() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo: RepoSource = "o/plugin".parse().unwrap();
+
+        let missing = LocalBackend.checkout(&repo, tmp.path()).await;
+        assert!(matches!(missing, Err(Error::DevCheckoutMissing { .. })));
+
+        std::fs::create_dir(tmp.path().join("plugin")).unwrap();
+        let found = LocalBackend.checkout(&repo, tmp.path()).await.unwrap();
+        assert_eq!(found, Some(tmp.path().join("plugin")));
+    }
+}
@@ -0,0 +1,274 @@
+//! Sparse install (`--sparse`, experimental).
+//!
+//! Lazy packages are published as a directory that holds only a stub,
+//! `_rsplug_sparse.json`, listing each entry and the snapshot it is copied
+//! from. The first `packadd` through the generated loader runs
+//! `rsplug materialize <dir>`, which places the files from the repository
+//! cache and removes the stub. Start packages, the loader itself, generated
+//! files, and the symlinks of `dev` plugins are always placed at install time.
+//! A stub whose snapshot or helper has gone away (a clean, a moved binary)
+//! fails to load with a message to run `rsplug sync`, which republishes it.
+
+use super::*;
+
+/// 疎に公開したパッケージに置く、配置を後回しにしたエントリの一覧のファイル名。
+pub(super) const SPARSE_STUB_FILE: &str = "_rsplug_sparse.json";
+
+#[derive(Serialize, Deserialize)]
+struct SparseStub {
+    /// 配置を行うヘルパー（install した rsplug の実行ファイル）。ローダはこれを呼ぶ。
+    helper: PathBuf,
+    /// パッケージ内の相対パスと、その取得元の snapshot root。
+    entries: Vec<(PathBuf, PathBuf)>,
+}
+
+/// `entries` を後から配置するためのスタブの内容。snapshot から copy するエントリだけで
+/// できたパッケージでなければ `None`（install 時に配置する）。
+pub(super) fn sparse_stub(
+    helper: &Path,
+    entries: &[(PathBuf, Arc<FileSource>)],
+) -> Option<Vec<u8>> {
+    let entries = entries
+        .iter()
+        .map(|(which, source)| match source.as_ref() {
+            FileSource::Directory {
+                path,
+                symlink: false,
+                ..
+            } => Some((which.clone(), path.to_path_buf())),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if entries.is_empty() {
+        return None;
+    }
+    serde_json::to_vec(&SparseStub {
+        helper: helper.to_path_buf(),
+        entries,
+    })
+    .ok()
+}
+
+/// `dir` のスタブが、もう存在しない snapshot か helper を指しているか。スタブがなければ
+/// `false`。古いスタブのパッケージは再利用せず、次の install で公開し直す。
+pub(super) async fn stale_stub(dir: &Path) -> bool {
+    let Ok(stub) = tokio::fs::read(dir.join(SPARSE_STUB_FILE)).await else {
+        return false;
+    };
+    let Ok(SparseStub { helper, entries }) = serde_json::from_slice(&stub) else {
+        return true;
+    };
+    for path in std::iter::once(&helper).chain(entries.iter().map(|(_, root)| root)) {
+        if !tokio::fs::try_exists(path).await.unwrap_or(false) {
+            return true;
+        }
+    }
+    false
+}
+
+/// `--sparse` で公開したパッケージ `dir`（`<packpath>/pack/_gen/opt/<id>`）にファイルを
+/// 配置し、スタブを取り除く。配置済みなら何もせず `false` を返す。
+pub async fn materialize(dir: &Path) -> io::Result<bool> {
+    // install や別の Neovim からの配置と重ならないよう、generation の公開ロックを取る。
+    #[cfg(unix)]
+    let _install_lock = match dir.parent().and_then(Path::parent) {
+        Some(gen_root) => Some(acquire_install_lock(gen_root).await?),
+        None => None,
+    };
+    let stub = match tokio::fs::read(dir.join(SPARSE_STUB_FILE)).await {
+        Ok(stub) => stub,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    let SparseStub { entries, .. } = serde_json::from_slice(&stub).map_err(io::Error::other)?;
+    // snapshot は install 後の clean で消えうる。何か置く前に確かめ、配置を途中で止めない。
+    for (_, root) in &entries {
+        if !tokio::fs::try_exists(root).await? {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "snapshot {} for {} no longer exists; run `rsplug sync` to install it again",
+                    root.display(),
+                    dir.display()
+                ),
+            ));
+        }
+    }
+    let stats = Arc::new(PlacementStats::default());
+    for (which, root) in entries {
+        if !is_contained_relative(&which) {
+            return Err(escaping_path_error(&which));
+        }
+        ensure_no_symlink_ancestor(dir, &which).await?;
+        place_path(&root.join(&which), &dir.join(&which), None, &stats).await?;
+    }
+    // install 時に記録だけした後処理を、配置した中身に施す。
+    let steps: Vec<_> = read_post_record(dir)
+        .await
+        .steps
+        .iter()
+        .filter_map(|name| PostStep::named(name))
+        .collect();
+    PostPipeline::new(&steps)?
+        .run(vec![dir.to_path_buf()])
+        .await?;
+    // スタブは最後に消す。途中で失敗しても次の読み込みでやり直せる。
+    tokio::fs::remove_file(dir.join(SPARSE_STUB_FILE)).await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `files` だけの snapshot から、`roots` を置く遅延プラグインを `packpath` に疎に install する。
+    async fn install_lazy(
+        packpath: &Path,
+        snapshot: &Path,
+        files: &[(&str, &str)],
+        roots: &[&str],
+        post_process: Option<Vec<PostStep>>,
+    ) -> PathBuf {
+        for (rel, content) in files {
+            let path = snapshot.join(rel);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        let identity = RepoSnapshotIdentity::new(
+            PathBuf::from("github.com/owner/lazy"),
+            b"cccccccccccccccccccccccccccccccccccccccc".to_vec(),
+            None,
+            Arc::<[String]>::from([]),
+            None,
+        );
+        let entry = |rel: &str| {
+            (
+                PathBuf::from(rel),
+                FileItem::new(
+                    Arc::new(FileSource::Directory {
+                        path: Arc::from(snapshot),
+                        inventory: None,
+                        handle: None,
+                        symlink: false,
+                    }),
+                    FileIdentity::RepoFile(RepoFileIdentity::new(
+                        identity.clone(),
+                        PathBuf::from(rel),
+                    )),
+                    MergeType::Conflict,
+                ),
+            )
+        };
+        let event: Autocmd = "InsertEnter".parse().unwrap();
+        let plugin = LoadedPlugin {
+            source_names: BTreeSet::from(["lazy.nvim".to_string()]),
+            lazy_type: LazyType::Opt(BTreeSet::from([LoadEvent::Autocmd(event)])),
+            files: HowToPlaceFiles::CopyEachFile(roots.iter().map(|rel| entry(rel)).collect()),
+            script: SetupScript::default(),
+            order: 0,
+            merge_policy: None,
+            target: PackTarget::User,
+            is_lazy_registration: false,
+            dotgit: false,
+        };
+        let plugin_id = plugin.plugin_id();
+
+        let mut state = PackPlan::new()
+            .with_sparse(true)
+            .with_post_process(post_process);
+        state.insert(plugin);
+        state.install(packpath).await.unwrap();
+        packpath.join("pack/_gen/opt").join(plugin_id.as_str())
+    }
+
+    #[tokio::test]
+    async fn lazy_packages_are_placed_on_materialize() {
+        let tmp = tempfile::tempdir().unwrap();
+        let package = install_lazy(
+            &tmp.path().join("packpath"),
+            &tmp.path().join("snapshot"),
+            &[
+                ("plugin/lazy.lua", "-- plugin\n"),
+                ("lua/lazy/init.lua", "return {}\n"),
+            ],
+            &["plugin", "lua"],
+            None,
+        )
+        .await;
+        assert!(package.join(SPARSE_STUB_FILE).is_file());
+        assert!(!package.join("plugin").exists());
+
+        assert!(materialize(&package).await.unwrap());
+        assert!(!package.join(SPARSE_STUB_FILE).exists());
+        assert_eq!(
+            std::fs::read(package.join("plugin/lazy.lua")).unwrap(),
+            b"-- plugin\n"
+        );
+        assert!(package.join("lua/lazy/init.lua").is_file());
+        // 二度目は何もしない。
+        assert!(!materialize(&package).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn missing_snapshot_asks_for_a_sync() {
+        let tmp = tempfile::tempdir().unwrap();
+        let packpath = tmp.path().join("packpath");
+        let files = [("plugin/lazy.lua", "-- plugin\n")];
+        let package = install_lazy(
+            &packpath,
+            &tmp.path().join("snapshot"),
+            &files,
+            &["plugin"],
+            None,
+        )
+        .await;
+        std::fs::remove_dir_all(tmp.path().join("snapshot")).unwrap();
+        assert!(stale_stub(&package).await);
+
+        let err = materialize(&package).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("run `rsplug sync`"), "{err}");
+        // 何も置かず、スタブも残す。
+        assert!(package.join(SPARSE_STUB_FILE).is_file());
+        assert!(!package.join("plugin").exists());
+
+        // snapshot が別の場所に取り直されたら、同じパッケージでもスタブを書き直す。
+        let package = install_lazy(
+            &packpath,
+            &tmp.path().join("refetched"),
+            &files,
+            &["plugin"],
+            None,
+        )
+        .await;
+        assert!(!stale_stub(&package).await);
+        assert!(materialize(&package).await.unwrap());
+        assert!(package.join("plugin/lazy.lua").is_file());
+    }
+
+    #[tokio::test]
+    async fn post_process_runs_on_materialize() {
+        let tmp = tempfile::tempdir().unwrap();
+        let package = install_lazy(
+            &tmp.path().join("packpath"),
+            &tmp.path().join("snapshot"),
+            &[
+                ("src/grammar.json", r#"{"name":"lazy"}"#),
+                ("src/parser.c", "int tree_sitter_lazy(void) { return 0; }\n"),
+            ],
+            &["src"],
+            Some(vec![PostStep::Treesitter]),
+        )
+        .await;
+        // スタブには何も施さず、行うべき処理だけを記録する。
+        assert!(!package.join("parser").exists());
+        assert!(read_post_record(&package).await.outputs.is_empty());
+
+        assert!(materialize(&package).await.unwrap());
+        assert!(package.join("parser/lazy.so").is_file());
+        assert_eq!(
+            read_post_record(&package).await.outputs["treesitter"],
+            [PathBuf::from("parser/lazy.so")]
+        );
+    }
+}
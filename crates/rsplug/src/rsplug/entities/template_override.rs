@@ -0,0 +1,378 @@
+//! ユーザーによる組み込みテンプレートの差し替え（`~/.config/rsplug/templates/`）。
+//!
+//! 差し替えられるのは静的な Lua ランタイムと、trigger 表を埋め込む `lua/_rsplug/` の `.stpl`。
+//! 組み込みの `.stpl` は sailfish がビルド時にバイナリへ組み込むため、差し替えの `.stpl` は
+//! 実行時に描く。その書式は `<%= 名前 %>` で生成値（Lua の値）を埋め込むだけに限り、
+//! 使える名前はテンプレートごとに [`RUNTIME_STPL`] が決める。
+//! 差し替えファイルは先頭付近に `-- rsplug-template: <版>` を持ち、版が [`TEMPLATE_VERSION`] と
+//! 一致するものだけが使われる。一致しないもの・未知のもの・書式の誤りは理由を表示して無視し、
+//! 組み込みの内容で生成する。
+
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::log::{Message, msg};
+
+/// 生成モジュール間の取り決めの版。組み込みランタイムが依存する `_rsplug` の API を
+/// 変えたら上げ、古い版向けの差し替えを読み込まないようにする。
+pub const TEMPLATE_VERSION: u32 = 2;
+
+/// 版を宣言する行の接頭辞。
+const VERSION_MARKER: &str = "-- rsplug-template:";
+
+/// 版の宣言を探す先頭からの行数。
+const VERSION_SCAN_LINES: usize = 5;
+
+/// `plugin/on_lua.lua`: on_lua の `package.loaders` フック。
+pub(super) const ON_LUA_PLUGIN: &str = "plugin/on_lua.lua";
+/// `lua/_rsplug/on_map/init.lua`: on_map の共通ランタイム。
+pub(super) const ON_MAP_RUNTIME: &str = "lua/_rsplug/on_map/init.lua";
+
+/// `lua/_rsplug/on_cmd.stpl`: on_cmd のランタイム。
+pub(super) const ON_CMD_RUNTIME: &str = "lua/_rsplug/on_cmd.stpl";
+/// `lua/_rsplug/on_event.stpl`: on_event のランタイム。
+pub(super) const ON_EVENT_RUNTIME: &str = "lua/_rsplug/on_event.stpl";
+/// `lua/_rsplug/on_func.stpl`: on_func のランタイム。
+pub(super) const ON_FUNC_RUNTIME: &str = "lua/_rsplug/on_func.stpl";
+/// `lua/_rsplug/on_lua.stpl`: on_lua の状態表。
+pub(super) const ON_LUA_RUNTIME: &str = "lua/_rsplug/on_lua.stpl";
+/// `lua/_rsplug/on_ft.stpl`: on_ft のランタイム。
+pub(super) const ON_FT_RUNTIME: &str = "lua/_rsplug/on_ft.stpl";
+
+/// 実行時に描く `.stpl` 差し替えと、`<%= 名前 %>` で参照できる変数。
+const RUNTIME_STPL: &[(&str, &[&str])] = &[
+    (ON_CMD_RUNTIME, &["cmd2pkgid"]),
+    (ON_EVENT_RUNTIME, &["event2pkgid", "debug_loader"]),
+    (ON_FUNC_RUNTIME, &["func2pkgid"]),
+    (ON_LUA_RUNTIME, &["luam2pkgid", "pkgid2luam"]),
+    (ON_FT_RUNTIME, &["debug_loader"]),
+];
+
+/// 差し替えられる組み込みテンプレート（`templates/` からの相対パスと内容）。
+const BUILTIN: &[(&str, &[u8])] = &[
+    (
+        ON_LUA_PLUGIN,
+        include_bytes!("../../../templates/plugin/on_lua.lua"),
+    ),
+    (
+        ON_MAP_RUNTIME,
+        include_bytes!("../../../templates/lua/_rsplug/on_map/init.lua"),
+    ),
+];
+
+/// 読み込んだ差し替えテンプレート。空なら組み込みだけで生成する。
+#[derive(Default, Debug)]
+pub struct TemplateOverrides {
+    files: BTreeMap<&'static str, Arc<[u8]>>,
+    stpl: BTreeMap<&'static str, Vec<Segment>>,
+}
+
+/// 差し替えの `.stpl` を区切った断片。
+#[derive(Debug, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    /// `<%= 名前 %>`。名前は [`RUNTIME_STPL`] で検査済み。
+    Var(&'static str),
+}
+
+/// 検査を通った差し替え。
+#[derive(Debug, PartialEq, Eq)]
+enum Checked {
+    File(&'static str),
+    Stpl(&'static str, Vec<Segment>),
+}
+
+impl TemplateOverrides {
+    /// `dir` 以下の差し替えを読み込む。`dir` が無ければ空を返す。
+    pub async fn load(dir: &Path) -> io::Result<TemplateOverrides> {
+        let mut overrides = TemplateOverrides::default();
+        for path in list_files(dir).await? {
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+            let name = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let data = tokio::fs::read(&path).await?;
+            match check(&name, &data) {
+                Ok(Checked::File(builtin)) => {
+                    msg(Message::TemplateOverridden(path));
+                    overrides.files.insert(builtin, Arc::from(data));
+                }
+                Ok(Checked::Stpl(builtin, segments)) => {
+                    msg(Message::TemplateOverridden(path));
+                    overrides.stpl.insert(builtin, segments);
+                }
+                Err(reason) => msg(Message::TemplateOverrideIgnored { path, reason }),
+            }
+        }
+        Ok(overrides)
+    }
+
+    /// `name` の内容。差し替えがあればそれを、なければ組み込みを返す。
+    pub(super) fn get(&self, name: &'static str) -> Cow<'static, [u8]> {
+        if let Some(data) = self.files.get(name) {
+            return Cow::Owned(data.to_vec());
+        }
+        BUILTIN
+            .iter()
+            .find(|(builtin, _)| *builtin == name)
+            .map(|(_, data)| Cow::Borrowed(*data))
+            .unwrap_or_else(|| panic!("{name} is not a built-in template"))
+    }
+
+    /// `.stpl` の差し替え `name` があれば描く。`value` は変数名に対する Lua の値を返す。
+    /// 差し替えが無ければ `None` を返し、呼び出し側は組み込みのテンプレートで描く。
+    pub(super) fn render(&self, name: &str, value: impl Fn(&str) -> String) -> Option<Vec<u8>> {
+        let segments = self.stpl.get(name)?;
+        let mut out = String::new();
+        for segment in segments {
+            match segment {
+                Segment::Text(text) => out.push_str(text),
+                Segment::Var(var) => out.push_str(&value(var)),
+            }
+        }
+        Some(out.into_bytes())
+    }
+}
+
+/// 差し替えファイルを検査し、対応する組み込みの名前（`.stpl` なら区切った断片も）を返す。
+/// 使えなければ理由を返す。
+fn check(name: &str, data: &[u8]) -> Result<Checked, String> {
+    let replaceable = || {
+        BUILTIN
+            .iter()
+            .map(|(builtin, _)| *builtin)
+            .chain(RUNTIME_STPL.iter().map(|(stpl, _)| *stpl))
+    };
+    let Some(builtin) = replaceable().find(|builtin| *builtin == name) else {
+        return Err(format!(
+            "not a replaceable template (expected one of: {})",
+            replaceable().collect::<Vec<_>>().join(", ")
+        ));
+    };
+    let version = String::from_utf8_lossy(data)
+        .lines()
+        .take(VERSION_SCAN_LINES)
+        .find_map(|line| line.trim().strip_prefix(VERSION_MARKER).map(str::trim))
+        .map(str::to_string);
+    match version {
+        None => Err(format!(
+            "missing `{VERSION_MARKER} {TEMPLATE_VERSION}` in the first {VERSION_SCAN_LINES} lines"
+        )),
+        Some(version) if version != TEMPLATE_VERSION.to_string() => Err(format!(
+            "written for template version {version}, but this rsplug uses {TEMPLATE_VERSION}"
+        )),
+        Some(_) => match RUNTIME_STPL.iter().find(|(stpl, _)| *stpl == builtin) {
+            Some((_, vars)) => {
+                let text = std::str::from_utf8(data).map_err(|e| format!("not UTF-8: {e}"))?;
+                Ok(Checked::Stpl(builtin, parse_stpl(text, vars)?))
+            }
+            None => Ok(Checked::File(builtin)),
+        },
+    }
+}
+
+/// 差し替えの `.stpl` を区切る。`<%= 名前 %>`（`<%- 名前 %>` も同じ）だけを受け付け、
+/// 名前は `vars` のいずれかでなければならない。`<%%` は `<%` そのものを表す。
+fn parse_stpl(text: &str, vars: &'static [&'static str]) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("<%") {
+        literal.push_str(&rest[..start]);
+        rest = &rest[start + 2..];
+        if let Some(after) = rest.strip_prefix('%') {
+            literal.push_str("<%");
+            rest = after;
+            continue;
+        }
+        let Some(end) = rest.find("%>") else {
+            return Err("unterminated `<%` tag".into());
+        };
+        let tag = &rest[..end];
+        rest = &rest[end + 2..];
+        let Some(expr) = tag.strip_prefix(['=', '-']) else {
+            return Err(format!(
+                "`<%{tag}%>`: only `<%= name %>` placeholders are supported in run-time templates"
+            ));
+        };
+        let expr = expr.trim();
+        let Some(var) = vars.iter().find(|var| **var == expr) else {
+            return Err(format!(
+                "unknown variable `{expr}` (available: {})",
+                vars.join(", ")
+            ));
+        };
+        if !literal.is_empty() {
+            segments.push(Segment::Text(std::mem::take(&mut literal)));
+        }
+        segments.push(Segment::Var(var));
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        segments.push(Segment::Text(literal));
+    }
+    Ok(segments)
+}
+
+/// `dir` 以下の通常ファイルを列挙する（順序は決定的）。
+pub(super) async fn list_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        let mut entries = match tokio::fs::read_dir(&current).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && current == dir => return Ok(files),
+            Err(e) => return Err(e),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                stack.push(entry.path());
+            } else {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_templates_declare_the_current_version() {
+        for (name, data) in BUILTIN {
+            assert_eq!(check(name, data), Ok(Checked::File(name)));
+        }
+    }
+
+    #[tokio::test]
+    async fn load_applies_matching_overrides_and_ignores_the_rest() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir_all(dir.join("plugin")).unwrap();
+        std::fs::create_dir_all(dir.join("lua/_rsplug/on_map")).unwrap();
+        let custom = format!("-- custom\n{VERSION_MARKER} {TEMPLATE_VERSION}\nreturn 1\n");
+        std::fs::write(dir.join(ON_LUA_PLUGIN), &custom).unwrap();
+        std::fs::write(
+            dir.join(ON_MAP_RUNTIME),
+            format!("{VERSION_MARKER} 0\nreturn {{}}\n"),
+        )
+        .unwrap();
+        std::fs::write(dir.join("lua/_rsplug/init.stpl"), "return {}\n").unwrap();
+        std::fs::write(dir.join("unknown.lua"), "return {}\n").unwrap();
+
+        let overrides = TemplateOverrides::load(dir).await.unwrap();
+
+        assert_eq!(overrides.get(ON_LUA_PLUGIN).as_ref(), custom.as_bytes());
+        assert_eq!(
+            overrides.get(ON_MAP_RUNTIME).as_ref(),
+            include_bytes!("../../../templates/lua/_rsplug/on_map/init.lua")
+        );
+        assert_eq!(overrides.files.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn load_without_directory_is_empty() {
+        let tmp = tempfile::tempdir().unwrap();
+        let overrides = TemplateOverrides::load(&tmp.path().join("missing"))
+            .await
+            .unwrap();
+        assert!(overrides.files.is_empty());
+    }
+
+    #[test]
+    fn check_reports_version_mismatch_and_missing_header() {
+        let stale = format!("{VERSION_MARKER} {}\n", TEMPLATE_VERSION + 1);
+        assert!(
+            check(ON_LUA_PLUGIN, stale.as_bytes())
+                .unwrap_err()
+                .contains("written for template version")
+        );
+        assert!(
+            check(ON_LUA_PLUGIN, b"return {}\n")
+                .unwrap_err()
+                .starts_with("missing")
+        );
+        assert!(
+            check("plugin/on_map.stpl", b"")
+                .unwrap_err()
+                .starts_with("not a replaceable template")
+        );
+    }
+
+    #[tokio::test]
+    async fn stpl_overrides_render_only_known_variables() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir_all(dir.join("lua/_rsplug")).unwrap();
+        std::fs::write(
+            dir.join(ON_EVENT_RUNTIME),
+            format!(
+                "{VERSION_MARKER} {TEMPLATE_VERSION}\n\
+                 local t = <%= event2pkgid %>\nlocal d = <%- debug_loader %> -- <%%\n"
+            ),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join(ON_CMD_RUNTIME),
+            format!("{VERSION_MARKER} {TEMPLATE_VERSION}\nlocal t = <%= func2pkgid %>\n"),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join(ON_FUNC_RUNTIME),
+            format!("{VERSION_MARKER} {TEMPLATE_VERSION}\n<% for x in y {{ %>\n"),
+        )
+        .unwrap();
+
+        let overrides = TemplateOverrides::load(dir).await.unwrap();
+
+        let rendered = overrides
+            .render(ON_EVENT_RUNTIME, |var| match var {
+                "event2pkgid" => "{}".into(),
+                _ => "true".into(),
+            })
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(rendered).unwrap(),
+            format!("{VERSION_MARKER} {TEMPLATE_VERSION}\nlocal t = {{}}\nlocal d = true -- <%\n")
+        );
+        assert!(
+            overrides
+                .render(ON_CMD_RUNTIME, |_| unreachable!())
+                .is_none()
+        );
+        assert!(
+            overrides
+                .render(ON_FUNC_RUNTIME, |_| unreachable!())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn parse_stpl_rejects_code_blocks_and_unknown_variables() {
+        let vars = &["cmd2pkgid"];
+        assert!(
+            parse_stpl("<% if x { %>", vars)
+                .unwrap_err()
+                .contains("only `<%= name %>` placeholders")
+        );
+        assert!(
+            parse_stpl("<%= ft2pkgid %>", vars)
+                .unwrap_err()
+                .starts_with("unknown variable `ft2pkgid`")
+        );
+        assert!(parse_stpl("<%= cmd2pkgid", vars).is_err());
+    }
+}
@@ -2,16 +2,21 @@ mod entities;
 pub(crate) mod perf;
 pub(crate) mod util;
 
+pub use entities::compat;
 pub use entities::config_walker;
 pub use entities::error;
 pub use entities::pack_plan;
 pub use entities::plugin;
 
 pub use entities::config::Config;
+pub(crate) use entities::config::PluginConfig;
 pub use entities::error::Error;
 pub use entities::lockfile::{LockFile, LockedResource, LockedResourceType};
+pub use entities::merge_type::{MergePolicy, PackTarget};
+pub use entities::template_override::TemplateOverrides;
 pub use pack_plan::LoadedPlugin;
 pub use pack_plan::PackPlan;
+pub use pack_plan::PostStep;
 pub(crate) use plugin::EarlyOutcome;
 pub use plugin::Plugin;
 pub use plugin::RepoJobRegistry;
@@ -110,6 +110,8 @@ pub(crate) enum PerfOp {
     ReflinkCopy,
     /// hardlink 成功数。
     HardlinkCopy,
+    /// content store の既存エントリへのハードリンク数（重複排除できたファイル）。
+    DedupLink,
     /// 通常 copy 成功数。
     PlainCopy,
 }
@@ -164,6 +166,7 @@ impl PerfOp {
             PerfOp::FallbackFanout => "fallback_fanout",
             PerfOp::ReflinkCopy => "reflink_copy",
             PerfOp::HardlinkCopy => "hardlink_copy",
+            PerfOp::DedupLink => "dedup_link",
             PerfOp::PlainCopy => "plain_copy",
         }
     }
@@ -304,6 +307,30 @@ pub(crate) fn disarm_failpoint(name: &'static str) {
     FAILPOINTS.with(|f| f.borrow_mut().remove(name));
 }
 
+/// ignored bench 用: 計測サンプル（ns）の中央値。
+#[cfg(test)]
+pub(crate) fn median(mut samples: Vec<u128>) -> u128 {
+    samples.sort_unstable();
+    samples[samples.len() / 2]
+}
+
+/// ignored bench 用: `"name": {...}` 形式の `cases` を schema 2 の JSON にまとめ、
+/// workspace の `target/<file>` へ書き出す。
+#[cfg(test)]
+pub(crate) fn write_bench_report(phase: &str, file: &str, cases: &[String]) {
+    let json = format!(
+        "{{\n  \"schema\": 2,\n  \"phase\": \"{phase}\",\n  \"benchmarks\": {{\n{}\n  }}\n}}\n",
+        cases.join(",\n")
+    );
+    let target = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../..")
+        .join("target")
+        .join(file);
+    std::fs::create_dir_all(target.parent().unwrap()).ok();
+    std::fs::write(&target, &json).expect("write bench json");
+    println!("wrote bench report to {}", target.display());
+}
+
 /// 構造 counter が期待値と一致するか検査し、不一致なら**シナリオ名と操作名を含む**
 /// 可読エラーメッセージを返す（PLANS「M0 is complete only when a failing structural
 /// counter produces a readable test failure naming the scenario and unexpected operation」）。
@@ -6,9 +6,11 @@ use super::error::Error;
 /// プロセス全体で共有する、リソース別の並列度予算（PLANS Phase 1）。
 /// 予算: network 初期64・上限96（main.rs の AdaptiveSemaphore、codeload は最大64）・
 /// tarball 展開=min(4, CPU)（`fetch::EXTRACTION_SEMAPHORE`）・Git 実体化=CPU・
-/// build=max(1, CPU/2)・copy=min(16, max(2, CPU*2))。fetch/展開以外はここで集中管理する。
+/// build=max(1, CPU/2)（`--build-jobs` で変更可）・copy=min(16, max(2, CPU*2))。fetch/展開以外はここで集中管理する。
+/// `--jobs` を指定すると、パース・EARLY・LATE のタスク全体の同時実行数もここで抑える。
+/// git2 の呼び出し自体は専用スレッド（CPU*2 を 8〜32 に収めた数、`git::pool`）で実行する。
 pub(crate) mod resources {
-    use once_cell::sync::Lazy;
+    use once_cell::sync::{Lazy, OnceCell};
     use tokio::sync::Semaphore;
 
     use crate::rsplug::error::Error;
@@ -25,10 +27,41 @@ pub(crate) mod resources {
     pub(crate) static GIT_SEMAPHORE: Lazy<Semaphore> =
         Lazy::new(|| Semaphore::new(available_cpus()));
 
+    /// `--jobs` で指定されたタスク全体の並列数。最初のタスクより前に設定する。
+    static JOBS: OnceCell<usize> = OnceCell::new();
+
+    /// タスク全体の並列数を設定する。0 は 1 とみなす。2 回目以降の設定は無視される。
+    pub(crate) fn set_jobs(jobs: usize) {
+        let _ = JOBS.set(jobs.max(1));
+    }
+
+    /// パース・EARLY・LATE の各タスク。[`set_jobs`] が無ければ実質無制限。
+    /// タスクは permit を持ったまま他のタスクの完了を待たないので、上限を小さくしても詰まらない。
+    pub(crate) static JOB_SEMAPHORE: Lazy<Semaphore> =
+        Lazy::new(|| Semaphore::new(JOBS.get().copied().unwrap_or(Semaphore::MAX_PERMITS)));
+
+    /// `--build-jobs` で指定された build の並列数。最初の build より前に設定する。
+    static BUILD_JOBS: OnceCell<usize> = OnceCell::new();
+
+    /// build の並列数を設定する。0 は 1 とみなす。2 回目以降の設定は無視される。
+    pub(crate) fn set_build_jobs(jobs: usize) {
+        let _ = BUILD_JOBS.set(jobs.max(1));
+    }
+
+    /// build の並列数。未設定なら CPU の半分（最低1）。`--jobs` を超えない。
+    pub(crate) fn build_jobs() -> usize {
+        let jobs = BUILD_JOBS
+            .get()
+            .copied()
+            .unwrap_or_else(|| (available_cpus() / 2).max(1));
+        JOBS.get().map_or(jobs, |&limit| jobs.min(limit))
+    }
+
     /// build プロセス（sh build・lua_build・lua_post_update）。CPU+IO が重いので
-    /// CPU の半分（最低1）に制限し、fetch/展開/copy を飢えさせない。
-    pub(crate) static BUILD_SEMAPHORE: Lazy<Semaphore> =
-        Lazy::new(|| Semaphore::new((available_cpus() / 2).max(1)));
+    /// [`build_jobs`]（既定は CPU の半分）に制限し、fetch/展開/copy を飢えさせない。
+    /// 依存関係の順序は main.rs の `try_schedule_late` が保証する（依存先の LATE 完了まで
+    /// 依存元の build を始めない）。
+    pub(crate) static BUILD_SEMAPHORE: Lazy<Semaphore> = Lazy::new(|| Semaphore::new(build_jobs()));
 
     /// pack copy の leaf コピー（reflink 非対応/fallback 時の per-file copy）。
     /// copy 予算 min(16, max(2, CPU*2)) で fan-out を抑える。
@@ -42,6 +75,13 @@ pub(crate) mod resources {
             .map_err(|_| Error::Io(std::io::Error::other("git semaphore closed")))
     }
 
+    pub(crate) async fn job() -> Result<tokio::sync::SemaphorePermit<'static>, Error> {
+        JOB_SEMAPHORE
+            .acquire()
+            .await
+            .map_err(|_| Error::Io(std::io::Error::other("job semaphore closed")))
+    }
+
     pub(crate) async fn build() -> Result<tokio::sync::SemaphorePermit<'static>, Error> {
         BUILD_SEMAPHORE
             .acquire()
@@ -139,9 +179,12 @@ pub mod git {
     use std::{
         cell::Cell,
         ops::Deref,
-        path::Path,
+        path::{Path, PathBuf},
         str::FromStr,
-        sync::{Arc, Mutex},
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicBool, Ordering},
+        },
         time::{Duration, Instant},
     };
 
@@ -150,60 +193,277 @@ pub mod git {
     };
     use once_cell::sync::Lazy;
     use regex::Regex;
-    use tokio::task::spawn_blocking;
     use xxhash_rust::xxh3::Xxh3;
 
     use crate::log::{self, Message};
 
     use super::*;
 
-    /// 初期化済みのローカルリポジトリ
-    pub struct Repository(Arc<Mutex<git2::Repository>>);
+    /// 初期化済みのローカルリポジトリ。
+    ///
+    /// git2 の `Repository` は `Sync` でないので、操作ごとに handle を 1 つ専有する。使い終えた
+    /// handle は戻して使い回し、全て使用中なら同じパスをもう 1 つ開く。長い fetch の間も同じ
+    /// リポジトリへの問い合わせは待たされない（source.git の更新の排他は呼び出し側が取る）。
+    pub struct Repository(Arc<Handles>);
+
+    struct Handles {
+        /// 開き直すときのパス（作業ツリー、bare なら git dir）。
+        path: PathBuf,
+        idle: Mutex<Vec<git2::Repository>>,
+    }
+
+    impl Handles {
+        fn take(&self) -> Result<git2::Repository, git2::Error> {
+            let idle = self.idle.lock().unwrap().pop();
+            match idle {
+                Some(repo) => Ok(repo),
+                None => git2::Repository::open(&self.path),
+            }
+        }
+    }
+
+    /// 待つのをやめた git 操作への中断の合図。git のスレッドで動く libgit2 は future の drop
+    /// では止まらないので、次のコールバック（fetch の受信・checkout の 1 ファイル）で見て止める。
+    #[derive(Clone, Default)]
+    pub struct Cancel(Arc<AtomicBool>);
+
+    /// プロセス全体の中断（Ctrl-C）。立つと全ての [`Cancel`] が中断済みになる。
+    static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+    impl Cancel {
+        pub fn cancel(&self) {
+            self.0.store(true, Ordering::Relaxed);
+        }
+
+        pub fn is_cancelled(&self) -> bool {
+            self.0.load(Ordering::Relaxed) || INTERRUPTED.load(Ordering::Relaxed)
+        }
+    }
+
+    /// 実行中・待ち中の git 操作を全て中断させる。以後の操作も始まってすぐ止まる。
+    pub fn interrupt() {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+    }
+
+    /// git のスレッドに残った job が終わるまで（最長 `limit`）待つ。中断した checkout が
+    /// staging へ書き終えてから終了するためのもの。
+    pub async fn wait_idle(limit: Duration) {
+        let deadline = Instant::now() + limit;
+        while pool::pending() > 0 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    pub(crate) mod pool {
+        //! git2 の同期呼び出しを実行する専用のスレッド群。
+        //!
+        //! tokio の blocking pool は上限が大きく（既定 512）、ファイル I/O 等とも共有している。
+        //! 応答の遅いリモートへの fetch が並ぶとそれを埋め、無関係の `spawn_blocking` を待たせる。
+        //! git の呼び出しはここで固定数（[`threads`]）のスレッドに並べ、超えた分は順番を待つ。
+        //! job の中で別の job を待つとスレッドが尽きて止まり得るので、job は他の git 操作を待たない。
+
+        use std::{
+            panic::{AssertUnwindSafe, catch_unwind},
+            sync::{
+                Arc, Mutex,
+                atomic::{AtomicUsize, Ordering},
+                mpsc,
+            },
+        };
+
+        use once_cell::sync::Lazy;
+
+        use super::super::{resources, task};
+        use crate::rsplug::error::Error;
+
+        type Job = Box<dyn FnOnce() + Send>;
+
+        /// スレッド数。CPU の 2 倍を 8〜32 に収める（fetch はネットワーク待ちが主なので CPU より多い）。
+        pub(crate) fn threads() -> usize {
+            (resources::available_cpus() * 2).clamp(8, 32)
+        }
+
+        /// 最初の git 操作でスレッドを起こす。スレッドはプロセス終了まで job を待ち続ける。
+        static JOBS: Lazy<mpsc::Sender<Job>> = Lazy::new(|| {
+            let (sender, receiver) = mpsc::channel::<Job>();
+            let receiver = Arc::new(Mutex::new(receiver));
+            for i in 0..threads() {
+                let receiver = receiver.clone();
+                std::thread::Builder::new()
+                    .name(format!("rsplug-git-{i}"))
+                    .spawn(move || {
+                        loop {
+                            let job = receiver.lock().unwrap().recv();
+                            match job {
+                                Ok(job) => job(),
+                                Err(_) => break,
+                            }
+                        }
+                    })
+                    .expect("failed to spawn a git thread");
+            }
+            sender
+        });
+
+        /// 送ってまだ終わっていない job の数。呼び出し側が待つのをやめた job も数える。
+        static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+        pub(crate) fn pending() -> usize {
+            PENDING.load(Ordering::Acquire)
+        }
+
+        /// `f` を git のスレッドで実行し、結果を待つ。panic は [`Error::TaskPanicked`] になる。
+        pub(crate) async fn run<T: Send + 'static>(
+            f: impl FnOnce() -> T + Send + 'static,
+        ) -> Result<T, Error> {
+            let (sender, receiver) = tokio::sync::oneshot::channel();
+            PENDING.fetch_add(1, Ordering::AcqRel);
+            JOBS.send(Box::new(move || {
+                let _ = sender.send(catch_unwind(AssertUnwindSafe(f)));
+                PENDING.fetch_sub(1, Ordering::AcqRel);
+            }))
+            .map_err(|_| {
+                PENDING.fetch_sub(1, Ordering::AcqRel);
+                Error::Io(std::io::Error::other("git threads stopped"))
+            })?;
+            match receiver.await {
+                Ok(Ok(value)) => Ok(value),
+                Ok(Err(panic)) => Err(task::caught("git", panic)),
+                Err(_) => Err(Error::TaskPanicked { context: "git" }),
+            }
+        }
+    }
 
     impl Repository {
         /// (INTERNAL) git2のRepositoryから生成
         fn from(value: git2::Repository) -> Self {
-            Repository(Arc::new(Mutex::new(value)))
+            let path = value.workdir().unwrap_or(value.path()).to_path_buf();
+            Repository(Arc::new(Handles {
+                path,
+                idle: Mutex::new(vec![value]),
+            }))
+        }
+
+        /// handle を 1 つ専有して `f` を git のスレッドで実行する。
+        async fn with<T: Send + 'static>(
+            &self,
+            f: impl FnOnce(&git2::Repository) -> Result<T, Error> + Send + 'static,
+        ) -> Result<T, Error> {
+            let handles = self.0.clone();
+            pool::run(move || {
+                let repo = handles.take()?;
+                let result = f(&repo);
+                handles.idle.lock().unwrap().push(repo);
+                result
+            })
+            .await?
         }
 
         /// Check for an already acquired object without fetching or changing
         /// the worktree. Callers coordinate this with repository mutation.
         pub async fn contains_oid(&self, oid: Oid) -> Result<bool, Error> {
-            let repo = self.0.clone();
-            spawn_blocking(move || Ok(repo.lock().unwrap().find_object(oid, None).is_ok()))
+            self.with(move |repo| Ok(repo.find_object(oid, None).is_ok()))
                 .await
-                .unwrap()
         }
 
-        /// source.git に指定 oid を fetch する（HEAD も作業ツリーも変えない）。
-        pub async fn fetch_oid(&mut self, oid: Oid, token: Option<Arc<str>>) -> Result<(), Error> {
-            let repo = self.0.clone();
-            spawn_blocking(move || {
-                let repo = repo.lock().unwrap();
+        /// source.git に指定 oid を `url` から fetch する（HEAD も作業ツリーも変えない）。
+        /// `url` は origin と異なってよい（ミラーから取得する場合）。
+        pub async fn fetch_oid(
+            &mut self,
+            url: Arc<str>,
+            oid: Oid,
+            token: Option<Arc<str>>,
+            cancel: Cancel,
+        ) -> Result<(), Error> {
+            self.with(move |repo| {
                 if repo.find_object(oid, None).is_ok() {
                     return Ok(());
                 }
-                let mut remote = repo.find_remote("origin")?;
+                let mut remote = repo.remote_anonymous(&url)?;
                 // local transport (file://, bare path) は shallow fetch 非対応なので full fetch する。
                 let shallow = remote.url().map(|u| !is_local_transport(u)).unwrap_or(true);
                 remote.fetch(
                     &[oid.to_string()],
-                    Some(&mut build_fetch_options(oid, shallow, token)),
+                    Some(&mut build_fetch_options(oid, shallow, token, cancel)),
                     None,
                 )?;
                 Ok(())
             })
             .await
-            .unwrap()
+        }
+
+        /// `local` と `upstream` の分岐を数える。fetch はしない。
+        /// shallow な repository では共通祖先が履歴の外にあり得るので、見つからなければ
+        /// [`AheadBehind::Shallow`] を返す（[`Repository::deepen`] で深くして再試行できる）。
+        pub async fn ahead_behind(&self, local: Oid, upstream: Oid) -> Result<AheadBehind, Error> {
+            self.with(move |repo| {
+                if repo.find_commit(local).is_err() || repo.find_commit(upstream).is_err() {
+                    return Ok(AheadBehind::Missing);
+                }
+                match repo.merge_base(local, upstream) {
+                    Ok(_) => {
+                        let (ahead, behind) = repo.graph_ahead_behind(local, upstream)?;
+                        Ok(AheadBehind::Counts { ahead, behind })
+                    }
+                    // shallow 境界の先の親は object が無いので、NotFound 以外の失敗もあり得る。
+                    Err(_) if repo.is_shallow() => Ok(AheadBehind::Shallow),
+                    Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(AheadBehind::Unrelated),
+                    Err(e) => Err(e.into()),
+                }
+            })
+            .await
+        }
+
+        /// `url` から `oid` を履歴 `depth` commit 分まで取得する（shallow な source.git を深くする）。
+        /// local transport は shallow fetch 非対応なので全履歴を取得する。
+        pub async fn deepen(
+            &self,
+            url: Arc<str>,
+            oid: Oid,
+            depth: u32,
+            token: Option<Arc<str>>,
+        ) -> Result<(), Error> {
+            self.with(move |repo| {
+                let mut remote = repo.remote_anonymous(&url)?;
+                let mut opts = build_fetch_options(oid, false, token, Cancel::default());
+                if !is_local_transport(&url) {
+                    opts.depth(i32::try_from(depth).unwrap_or(i32::MAX));
+                }
+                remote.fetch(&[oid.to_string()], Some(&mut opts), None)?;
+                Ok(())
+            })
+            .await
+        }
+
+        /// `to` から辿れて `from` からは辿れない commit の (id, 件名) を新しい順に最大 `limit` 件返す
+        /// （`git log from..to`）。shallow 境界に達したらそこまでの commit だけになる。
+        pub async fn log(
+            &self,
+            from: Oid,
+            to: Oid,
+            limit: usize,
+        ) -> Result<Vec<(Oid, String)>, Error> {
+            self.with(move |repo| {
+                let mut walk = repo.revwalk()?;
+                walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+                walk.push(to)?;
+                walk.hide(from)?;
+                walk.take(limit)
+                    .map(|id| -> Result<_, Error> {
+                        let id = id?;
+                        let summary = repo.find_commit(id)?.summary()?.unwrap_or("").to_string();
+                        Ok((id, summary))
+                    })
+                    .collect()
+            })
+            .await
         }
 
         /// Compute the dirty diff digest and dirty state in one Git query.
         /// Untracked files are included explicitly so callers do not need a
         /// status query followed by a second diff walk.
         pub async fn dirty_diff_hash(&self) -> Result<Option<[u8; 16]>, Error> {
-            let repo = self.0.clone();
-            spawn_blocking(move || {
-                let repo = repo.lock().unwrap();
+            self.with(move |repo| {
                 // rsplug-owned snapshot metadata is cache state, not plugin content.
                 // Ignoring it keeps the repository identity stable after the first
                 // inventory is written; otherwise a warm refresh would acquire a
@@ -229,10 +489,83 @@ pub mod git {
                 Ok(Some(hasher.digest128().to_ne_bytes()))
             })
             .await
-            .unwrap()
+        }
+
+        /// 作業ツリーに unified diff を適用する。当たらない場合は作業ツリーを変更せず、
+        /// hunk が当たらなかったファイルを返す（全て当たれば空）。
+        pub async fn apply_patch(&self, patch: Arc<[u8]>) -> Result<Vec<String>, Error> {
+            self.with(move |repo| {
+                let diff = git2::Diff::from_buffer(&patch)?;
+                let mut check = git2::ApplyOptions::new();
+                check.check(true);
+                if repo
+                    .apply(&diff, git2::ApplyLocation::WorkDir, Some(&mut check))
+                    .is_ok()
+                {
+                    repo.apply(&diff, git2::ApplyLocation::WorkDir, None)?;
+                    return Ok(Vec::new());
+                }
+                // 診断用に delta（ファイル）ごとに検査し、当たらないものを集める。
+                let mut rejected = Vec::new();
+                for delta in diff.deltas() {
+                    let Some(path) = delta.new_file().path().or(delta.old_file().path()) else {
+                        continue;
+                    };
+                    let mut only = git2::ApplyOptions::new();
+                    only.check(true).delta_callback(|d| {
+                        d.is_some_and(|d| d.new_file().path().or(d.old_file().path()) == Some(path))
+                    });
+                    if repo
+                        .apply(&diff, git2::ApplyLocation::WorkDir, Some(&mut only))
+                        .is_err()
+                    {
+                        rejected.push(path.to_string_lossy().into_owned());
+                    }
+                }
+                if rejected.is_empty() {
+                    // ファイル単位では当たるのに全体では当たらない: git2 のエラーをそのまま返す。
+                    repo.apply(&diff, git2::ApplyLocation::WorkDir, None)?;
+                }
+                Ok(rejected)
+            })
+            .await
+        }
+
+        /// 作業ツリーのローカル変更（変更・削除・untracked）のパス一覧を返す。
+        /// update で snapshot を切り替えると失われる変更を利用者へ報告するために使う。
+        pub async fn dirty_paths(&self) -> Result<Vec<String>, Error> {
+            self.with(move |repo| {
+                // dirty_diff_hash と同じく rsplug 自身の snapshot metadata は変更とみなさない。
+                repo.add_ignore_rule(RSPLUG_BUILD_SUCCESS_FILE).unwrap();
+                repo.add_ignore_rule(".rsplug-manifest-v1.json").unwrap();
+                let mut opts = git2::StatusOptions::new();
+                opts.include_untracked(true)
+                    .recurse_untracked_dirs(true)
+                    .include_ignored(false);
+                let statuses = repo.statuses(Some(&mut opts))?;
+                Ok(statuses
+                    .iter()
+                    .filter(|s| s.status() != git2::Status::CURRENT)
+                    .filter_map(|s| s.path().ok().map(str::to_string))
+                    .collect())
+            })
+            .await
         }
     }
 
+    /// [`Repository::ahead_behind`] の結果。
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum AheadBehind {
+        /// `local` だけにある commit 数と `upstream` だけにある commit 数。
+        Counts { ahead: usize, behind: usize },
+        /// どちらかの commit がこの repository に無い。
+        Missing,
+        /// 共通祖先が shallow 境界より先にあるかもしれず、数えられない。
+        Shallow,
+        /// 全履歴があるのに共通祖先が無い。
+        Unrelated,
+    }
+
     /// fetch 進捗をログ出力する FetchOptions を構築する。
     /// `file://` URL や scheme 無しの bare path は local transport（shallow fetch 非対応）。
     fn is_local_transport(url: &str) -> bool {
@@ -243,11 +576,19 @@ pub mod git {
         rev: Oid,
         shallow: bool,
         token: Option<Arc<str>>,
+        cancel: Cancel,
     ) -> FetchOptions<'static> {
         let mut cbs = RemoteCallbacks::new();
         let last_reported = Cell::new(0usize);
         let last_tick = Cell::new(Instant::now());
+        // false を返すと libgit2 は fetch を中断する。object の受信前（サーバ側の準備中）は
+        // sideband のメッセージだけが届く。
+        let sideband_cancel = cancel.clone();
+        cbs.sideband_progress(move |_| !sideband_cancel.is_cancelled());
         cbs.transfer_progress(move |progress| {
+            if cancel.is_cancelled() {
+                return false;
+            }
             let total_objs_count = progress.total_objects();
             let received_objs_count = progress.received_objects();
             if received_objs_count == 0 || received_objs_count == last_reported.get() {
@@ -284,11 +625,69 @@ pub mod git {
         ops
     }
 
+    /// CA 証明書の束を探すよく知られた場所（Debian・Fedora・openSUSE・Alpine・FreeBSD 等）。
+    #[cfg(all(unix, not(target_os = "macos")))]
+    const CERT_FILES: &[&str] = &[
+        "/etc/ssl/certs/ca-certificates.crt",
+        "/etc/pki/tls/certs/ca-bundle.crt",
+        "/etc/ssl/ca-bundle.pem",
+        "/etc/ssl/cert.pem",
+        "/etc/pki/tls/cacert.pem",
+        "/usr/local/share/certs/ca-root-nss.crt",
+    ];
+
+    /// ハッシュ名の証明書を並べたディレクトリのよく知られた場所。
+    #[cfg(all(unix, not(target_os = "macos")))]
+    const CERT_DIRS: &[&str] = &["/etc/ssl/certs", "/etc/pki/tls/certs"];
+
+    /// libgit2 の HTTPS（OpenSSL）が使う CA 証明書の場所を設定する。`SSL_CERT_FILE`・
+    /// `SSL_CERT_DIR` を優先し、無ければよく知られた場所を探す。同梱（vendored）の OpenSSL は
+    /// ビルド時の既定の場所を見に行くため、静的リンクのバイナリではこれが無いと検証に失敗する。
+    /// 見つけた場所はシステムの既定の場所に追加されるだけなので、動的リンクでも害は無い。
+    ///
+    /// # Safety
+    ///
+    /// libgit2 の大域設定を書き換える。他のスレッドが git2 を使い始める前
+    /// （ランタイムの起動前）に呼ぶこと。
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub unsafe fn configure_ssl_certs() {
+        let from_env = |var: &str| {
+            std::env::var_os(var)
+                .map(std::path::PathBuf::from)
+                .filter(|path| path.exists())
+        };
+        let probe = |candidates: &[&str], is_kind: fn(&Path) -> bool| {
+            candidates
+                .iter()
+                .map(Path::new)
+                .find(|path| is_kind(path))
+                .map(Path::to_path_buf)
+        };
+        let file = from_env("SSL_CERT_FILE").or_else(|| probe(CERT_FILES, Path::is_file));
+        let dir = from_env("SSL_CERT_DIR").or_else(|| probe(CERT_DIRS, Path::is_dir));
+        // 設定できない（OpenSSL 以外のバックエンド等）ときは libgit2 の既定に任せる。
+        // SAFETY: 呼び出し側が git2 を使うスレッドが他に無いことを保証する。
+        unsafe {
+            if let Some(file) = file {
+                let _ = git2::opts::set_ssl_cert_file(file);
+            }
+            if let Some(dir) = dir {
+                let _ = git2::opts::set_ssl_cert_dir(dir);
+            }
+        }
+    }
+
+    /// macOS と Windows の libgit2 は OS の証明書ストアを使うので何もしない。
+    ///
+    /// # Safety
+    ///
+    /// 他のプラットフォームと同じ条件で呼ぶ。
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    pub unsafe fn configure_ssl_certs() {}
+
     /// リポジトリを開く
     pub async fn open(dir: impl AsRef<Path> + Send + 'static) -> Result<Repository, Error> {
-        let repo = spawn_blocking(move || git2::Repository::open(dir))
-            .await
-            .unwrap()?;
+        let repo = pool::run(move || git2::Repository::open(dir)).await??;
         Ok(Repository::from(repo))
     }
 
@@ -300,23 +699,19 @@ pub mod git {
     ) -> Result<Repository, Error> {
         let dir = dir.as_ref().to_path_buf();
         let repo = repo.as_ref().to_string();
-        let r = spawn_blocking(move || git2::Repository::init_bare(&dir))
-            .await
-            .unwrap()?;
-        spawn_blocking(move || {
+        let r = pool::run(move || git2::Repository::init_bare(&dir)).await??;
+        pool::run(move || {
             r.remote("origin", repo.as_ref())?;
             Ok(Repository::from(r))
         })
-        .await
-        .unwrap()
+        .await?
     }
 
     /// 既存の `source.git` を開く。
     pub async fn open_source(dir: impl AsRef<Path> + Send) -> Result<Repository, Error> {
         let dir = dir.as_ref().to_path_buf();
-        spawn_blocking(move || git2::Repository::open_bare(&dir))
-            .await
-            .unwrap()
+        pool::run(move || git2::Repository::open_bare(&dir))
+            .await?
             .map(Repository::from)
             .map_err(Into::into)
     }
@@ -329,10 +724,11 @@ pub mod git {
         snapshot_root: impl AsRef<Path> + Send,
         source_git_dir: impl AsRef<Path> + Send,
         oid: Oid,
+        cancel: Cancel,
     ) -> Result<Repository, Error> {
         let snapshot_root = snapshot_root.as_ref().to_path_buf();
         let source_git_dir = source_git_dir.as_ref().to_path_buf();
-        spawn_blocking(move || {
+        pool::run(move || {
             let source_url = source_git_dir
                 .to_str()
                 .ok_or_else(|| git2::Error::from_str("source.git path is not UTF-8"))?;
@@ -341,20 +737,26 @@ pub mod git {
             repo.set_head_detached(oid)?;
             {
                 let obj = repo.find_object(oid, None)?;
+                let notify_cancel = cancel.clone();
                 repo.checkout_tree(
                     &obj,
                     Some(
                         CheckoutBuilder::new()
                             .force()
                             .use_theirs(true)
-                            .allow_conflicts(true),
+                            .allow_conflicts(true)
+                            .notify_on(git2::CheckoutNotificationType::UPDATED)
+                            .notify(move |_, _, _, _, _| !notify_cancel.is_cancelled()),
                     ),
                 )?;
             }
+            // notify で止めた checkout は git2 では成功として返るので、合図を見て失敗にする。
+            if cancel.is_cancelled() {
+                return Err(git2::Error::from_str("checkout cancelled").into());
+            }
             Ok(Repository::from(repo))
         })
-        .await
-        .unwrap()
+        .await?
     }
 
     /// GitRefを並び替え可能・最大値を取得可能にするための型
@@ -411,17 +813,24 @@ pub mod git {
         }
     }
 
-    impl<'a> From<&'a git2::RemoteHead<'a>> for GitRef<'a> {
-        fn from(value: &'a git2::RemoteHead<'a>) -> Self {
-            let (ref_type, name) = GitRefType::parse(value.name());
+    impl<'a> GitRef<'a> {
+        fn new(refname: &'a str, id: Oid) -> Self {
+            let (ref_type, name) = GitRefType::parse(refname);
             GitRef {
                 ref_type,
-                id: value.oid(),
+                id,
                 name,
+                refname,
             }
         }
     }
 
+    impl<'a> From<&'a git2::RemoteHead<'a>> for GitRef<'a> {
+        fn from(value: &'a git2::RemoteHead<'a>) -> Self {
+            GitRef::new(value.name(), value.oid())
+        }
+    }
+
     impl From<GitRef<'_>> for Oid {
         fn from(value: GitRef<'_>) -> Self {
             value.id
@@ -433,6 +842,167 @@ pub mod git {
         ref_type: GitRefType<'a>,
         id: Oid,
         name: Option<&'a str>,
+        /// 完全修飾の ref 名（`refs/heads/main` 等）。
+        refname: &'a str,
+    }
+
+    /// ref の中から rev（ワイルドカード可）に一致する最新のものを選ぶ。rev が無ければ HEAD。
+    /// `refs/` で始まる rev（`branch` 由来の `refs/heads/<branch>` 等）は完全修飾名と照合し、
+    /// 同名の tag などを候補に含めない。
+    fn select_ref<'a>(
+        mut references: impl Iterator<Item = GitRef<'a>>,
+        rev: Option<&str>,
+    ) -> Option<Oid> {
+        match rev {
+            Some(rev) => {
+                let qualified = rev.starts_with("refs/");
+                let rev = wildmatch::WildMatch::new(rev);
+                references
+                    .filter(|gitref| {
+                        if qualified {
+                            rev.matches(gitref.refname)
+                        } else {
+                            gitref.name.is_some_and(|name| rev.matches(name))
+                        }
+                    })
+                    .max()
+                    .map(Oid::from)
+            }
+            None => references
+                .find(|gitref| gitref.ref_type == GitRefType::Head)
+                .map(Oid::from),
+        }
+    }
+
+    /// [`ref_advertisement`] で得た ref から rev に対応するコミットハッシュを選ぶ。
+    pub fn select_rev(
+        url: &Arc<str>,
+        refs: &[(String, Oid)],
+        rev: Option<&str>,
+    ) -> Result<Oid, Error> {
+        select_ref(refs.iter().map(|(name, id)| GitRef::new(name, *id)), rev).ok_or_else(|| {
+            Error::GitRev {
+                url: url.clone(),
+                rev: rev.unwrap_or("HEAD").to_string(),
+            }
+        })
+    }
+
+    /// smart HTTP の ref 広告（`info/refs?service=git-upload-pack` の pkt-line）を読み、
+    /// (ref 名, OID) を広告順に返す。形式が崩れていれば `None`。
+    pub fn parse_ref_advertisement(body: &[u8]) -> Option<Vec<(String, Oid)>> {
+        let mut refs = Vec::new();
+        let mut rest = body;
+        while !rest.is_empty() {
+            let len = usize::from_str_radix(std::str::from_utf8(rest.get(..4)?).ok()?, 16).ok()?;
+            // flush-pkt はサービス行と ref 一覧の区切り。
+            if len == 0 {
+                rest = &rest[4..];
+                continue;
+            }
+            let line = rest.get(4..len)?;
+            rest = &rest[len..];
+            let line = line.strip_suffix(b"\n").unwrap_or(line);
+            if line.starts_with(b"# service=") {
+                continue;
+            }
+            // 最初の ref だけ NUL の後ろに capabilities が付く。
+            let line = line.split(|byte| *byte == 0).next().unwrap_or(line);
+            let (id, name) = std::str::from_utf8(line).ok()?.split_once(' ')?;
+            // 空のリポジトリは ref の代わりにこれだけを広告する。
+            if name == "capabilities^{}" {
+                continue;
+            }
+            refs.push((name.to_string(), Oid::from_str(id).ok()?));
+        }
+        Some(refs)
+    }
+
+    /// smart HTTP でリモートの ref 広告を取得する。共有の HTTP クライアントを使うので、
+    /// 同じホストへの問い合わせは接続を使い回し、HTTP/2 なら 1 本の接続に多重化される。
+    /// http(s) 以外の URL・想定外の応答は `Err` を返し、呼出元は [`ls_remote`] に切り替える。
+    pub async fn ref_advertisement(
+        client: &reqwest::Client,
+        url: &str,
+        token: Option<&str>,
+    ) -> Result<Vec<(String, Oid)>, Error> {
+        let unsupported = |reason: String| Error::Io(std::io::Error::other(reason));
+        if !(url.starts_with("https://") || url.starts_with("http://")) {
+            return Err(unsupported(format!("not an HTTP remote: {url}")));
+        }
+        let mut req = client.get(format!(
+            "{}/info/refs?service=git-upload-pack",
+            url.trim_end_matches('/')
+        ));
+        // token は GitHub にだけ送り、他のホストへ漏らさない。
+        if let Some(token) = token
+            && super::github::supports_tarball(url)
+        {
+            req = req.basic_auth("x-access-token", Some(token));
+        }
+        let resp = req
+            .send()
+            .await
+            .map_err(|e| unsupported(format!("{url}: {e}")))?;
+        if !resp.status().is_success() {
+            return Err(unsupported(format!("{url}: HTTP {}", resp.status())));
+        }
+        let body = resp
+            .bytes()
+            .await
+            .map_err(|e| unsupported(format!("{url}: {e}")))?;
+        parse_ref_advertisement(&body)
+            .ok_or_else(|| unsupported(format!("{url}: malformed ref advertisement")))
+    }
+
+    /// リモートのタグ名と、それが指す commit の一覧。annotated tag は peel 済みの commit を返す。
+    pub async fn ls_remote_tags(
+        url: Arc<str>,
+        token: Option<Arc<str>>,
+    ) -> Result<Vec<(String, Oid)>, Error> {
+        pool::run(move || {
+            let mut remote = git2::Remote::create_detached(url.to_string())?;
+            let cbs = token.map(|token| {
+                let mut cbs = git2::RemoteCallbacks::new();
+                cbs.credentials(move |_url, _username_from_url, _allowed_types| {
+                    git2::Cred::userpass_plaintext("x-access-token", &token)
+                });
+                cbs
+            });
+            let connection = remote.connect_auth(git2::Direction::Fetch, cbs, None)?;
+            Ok(peel_tags(
+                connection
+                    .list()?
+                    .iter()
+                    .map(|head| (head.name(), head.oid())),
+            ))
+        })
+        .await?
+    }
+
+    /// `old` から `new` への unified diff（`a/<path>`・`b/<path>` のヘッダ付き）。同じなら空。
+    pub fn unified_diff(path: &Path, old: &[u8], new: &[u8]) -> Result<String, Error> {
+        let mut patch = git2::Patch::from_buffers(old, Some(path), new, Some(path), None)?;
+        Ok(String::from_utf8_lossy(&patch.to_buf()?).into_owned())
+    }
+
+    /// ref 一覧からタグを取り出す。`refs/tags/<name>^{}`（peel 後の commit）があればそちらを採る。
+    pub(crate) fn peel_tags<'a>(refs: impl Iterator<Item = (&'a str, Oid)>) -> Vec<(String, Oid)> {
+        let mut tags = std::collections::BTreeMap::new();
+        for (refname, id) in refs {
+            let Some(name) = refname.strip_prefix("refs/tags/") else {
+                continue;
+            };
+            match name.strip_suffix("^{}") {
+                Some(name) => {
+                    tags.insert(name.to_string(), id);
+                }
+                None => {
+                    tags.entry(name.to_string()).or_insert(id);
+                }
+            }
+        }
+        tags.into_iter().collect()
     }
 
     /// リポジトリのリモートからrevに対応する最新のコミットハッシュを取得する
@@ -441,8 +1011,8 @@ pub mod git {
         rev: Option<impl Deref<Target = str> + Send + 'static>,
         token: Option<Arc<str>>,
     ) -> Result<Oid, Error> {
-        spawn_blocking(move || {
-            let mut remote = git2::Remote::create_detached(url.to_string()).unwrap();
+        pool::run(move || {
+            let mut remote = git2::Remote::create_detached(url.to_string())?;
 
             // token が利用可能な場合のみ credentials コールバックを設定。
             let cbs = if let Some(token) = token {
@@ -456,38 +1026,15 @@ pub mod git {
             };
 
             let connection = remote.connect_auth(git2::Direction::Fetch, cbs, None)?;
-            let references = connection.list().unwrap();
-            let latest = if let Some(rev) = rev.as_deref() {
-                let rev = wildmatch::WildMatch::new(rev);
-                references
-                    .iter()
-                    .filter_map(|val| {
-                        let gitref = GitRef::from(val);
-                        if let Some(true) = gitref.name.map(|name| rev.matches(name)) {
-                            Some(gitref)
-                        } else {
-                            None
-                        }
-                    })
-                    .max()
-            } else {
-                references
-                    .iter()
-                    .find(|r| r.name() == "HEAD")
-                    .map(GitRef::from)
-            };
-
-            if let Some(latest) = latest {
-                Ok(latest.into())
-            } else {
-                Err(Error::GitRev {
+            let references = connection.list()?;
+            select_ref(references.iter().map(GitRef::from), rev.as_deref()).ok_or_else(|| {
+                Error::GitRev {
                     url,
                     rev: rev.as_deref().unwrap_or("HEAD").to_string(),
-                })
-            }
+                }
+            })
         })
-        .await
-        .unwrap()
+        .await?
     }
 
     /// Constant representing files to be ignored by rsplug
@@ -649,6 +1196,482 @@ pub mod repo {
     }
 }
 
+pub mod nvim {
+    //! 実行環境の Neovim。実行ファイルは `--nvim`（`RSPLUG_NVIM`）で差し替えられ、
+    //! `lua_build`・helptags・`compat`/`when` の判定が同じものを使う。
+    //! バージョンはプロセス内で 1 度だけ `nvim --version` を実行して調べ、使い回す。
+
+    use std::{
+        ffi::OsStr,
+        fmt,
+        path::{Path, PathBuf},
+        str::FromStr,
+    };
+
+    use once_cell::sync::OnceCell;
+
+    static PROGRAM: OnceCell<PathBuf> = OnceCell::new();
+    static VERSION: tokio::sync::OnceCell<Result<NvimVersion, String>> =
+        tokio::sync::OnceCell::const_new();
+
+    /// 使う Neovim の実行ファイルを設定する。起動時に 1 度だけ呼ぶ（2 度目以降は無視）。
+    pub fn set_program(program: PathBuf) {
+        let _ = PROGRAM.set(program);
+    }
+
+    /// 使う Neovim の実行ファイル。未設定なら `PATH` 上の `nvim`。
+    pub fn program() -> &'static OsStr {
+        PROGRAM
+            .get()
+            .map_or(OsStr::new("nvim"), |program| program.as_os_str())
+    }
+
+    /// Neovim のバージョン。`0.10` のように省略された部分は 0 とみなす。
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    pub struct NvimVersion {
+        pub major: u32,
+        pub minor: u32,
+        pub patch: u32,
+    }
+
+    impl FromStr for NvimVersion {
+        type Err = String;
+
+        /// `v0.10.2`・`0.10`・`0.11.0-dev-123+g...` を受け付ける。pre-release 部分は無視する。
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let core = s.trim().trim_start_matches('v');
+            let core = core.split(['-', '+']).next().unwrap_or_default();
+            let mut parts = core.split('.');
+            let mut next = |required: bool| match parts.next() {
+                Some(part) => part
+                    .parse::<u32>()
+                    .map_err(|_| format!("invalid Neovim version {s:?}")),
+                None if required => Err(format!("invalid Neovim version {s:?}")),
+                None => Ok(0),
+            };
+            let version = NvimVersion {
+                major: next(true)?,
+                minor: next(true)?,
+                patch: next(false)?,
+            };
+            if parts.next().is_some() {
+                return Err(format!("invalid Neovim version {s:?}"));
+            }
+            Ok(version)
+        }
+    }
+
+    impl fmt::Display for NvimVersion {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+        }
+    }
+
+    /// `nvim --version` の 1 行目（`NVIM v0.10.2`）からバージョンを読む。
+    pub fn parse_version_output(output: &str) -> Option<NvimVersion> {
+        output.lines().next()?.strip_prefix("NVIM ")?.parse().ok()
+    }
+
+    /// `program` の `--version` を実行してバージョンを読む。
+    pub async fn detect(program: &Path) -> Result<NvimVersion, String> {
+        let output = tokio::process::Command::new(program)
+            .arg("--version")
+            .output()
+            .await
+            .map_err(|e| format!("could not run `{} --version`: {e}", program.display()))?;
+        parse_version_output(&String::from_utf8_lossy(&output.stdout))
+            .ok_or_else(|| format!("unrecognized `{} --version` output", program.display()))
+    }
+
+    /// 実行環境の Neovim のバージョン（プロセス内でキャッシュ）。`RSPLUG_NVIM_VERSION` が
+    /// あればそれを使い、無ければ [`program`] を実行する。判別できなければ理由を返す。
+    pub async fn version() -> Result<NvimVersion, String> {
+        VERSION
+            .get_or_init(|| async {
+                if let Ok(version) = std::env::var("RSPLUG_NVIM_VERSION") {
+                    return version
+                        .parse()
+                        .map_err(|e| format!("RSPLUG_NVIM_VERSION: {e}"));
+                }
+                detect(Path::new(program())).await
+            })
+            .await
+            .clone()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn v(s: &str) -> NvimVersion {
+            s.parse().unwrap()
+        }
+
+        #[test]
+        fn versions_parse_from_nvim_output_and_shorthand() {
+            assert_eq!(
+                parse_version_output("NVIM v0.10.2\nBuild type: Release\n"),
+                Some(v("0.10.2"))
+            );
+            assert_eq!(
+                parse_version_output("NVIM v0.11.0-dev-1234+gabcdef\n"),
+                Some(v("0.11.0"))
+            );
+            assert_eq!(parse_version_output("vim 9.1\n"), None);
+            assert_eq!(v("0.10"), v("0.10.0"));
+            assert!("0".parse::<NvimVersion>().is_err());
+            assert!("0.10.1.2".parse::<NvimVersion>().is_err());
+        }
+
+        #[tokio::test]
+        async fn detect_runs_the_given_program() {
+            let tmp = tempfile::tempdir().unwrap();
+            let missing = tmp.path().join("no-such-nvim");
+            let err = detect(&missing).await.unwrap_err();
+            assert!(err.contains("no-such-nvim"), "{err}");
+        }
+    }
+}
+
+pub mod task {
+    //! spawn したタスクの join 失敗（panic・cancel）を `Error::TaskPanicked` に変換する。
+    //! panic でプロセス全体を落とさず、呼び出し元のエラー経路に乗せる。panic の内容はログに出す。
+
+    use std::any::Any;
+
+    use tokio::task::JoinError;
+
+    use super::super::error::Error;
+    use crate::log::{Message, msg};
+
+    /// `JoinHandle`・`JoinSet` の join 結果の `map_err` に渡す。`context` は何のタスクか。
+    pub fn panicked(context: &'static str) -> impl FnOnce(JoinError) -> Error {
+        move |e| {
+            msg(Message::TaskPanicked {
+                context,
+                payload: payload(e),
+            });
+            Error::TaskPanicked { context }
+        }
+    }
+
+    /// tokio 外のスレッドで `catch_unwind` した panic を [`panicked`] と同じく変換する。
+    pub fn caught(context: &'static str, panic: Box<dyn Any + Send>) -> Error {
+        msg(Message::TaskPanicked {
+            context,
+            payload: describe(&*panic),
+        });
+        Error::TaskPanicked { context }
+    }
+
+    fn payload(e: JoinError) -> String {
+        match e.try_into_panic() {
+            Ok(panic) => describe(&*panic),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    /// panic の内容。`panic!` の引数は `&str` か `String` なのでそれ以外は中身を出さない。
+    fn describe(panic: &(dyn Any + Send)) -> String {
+        panic
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "non-string panic payload".to_string())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[tokio::test]
+        async fn panics_become_errors_with_the_payload_kept() {
+            let e = tokio::spawn(async { panic!("boom {}", 1) })
+                .await
+                .unwrap_err();
+            assert_eq!(payload(e), "boom 1");
+
+            let e = tokio::task::spawn_blocking(|| panic!("static"))
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                panicked("git")(e),
+                Error::TaskPanicked { context: "git" }
+            ));
+
+            let handle = tokio::spawn(std::future::pending::<()>());
+            handle.abort();
+            assert!(payload(handle.await.unwrap_err()).contains("cancelled"));
+        }
+    }
+}
+
+pub mod mirror {
+    //! ホストごとのミラー選択。`--mirror HOST=URL` で同じホストに複数のミラーが与えられたとき、
+    //! 1 回の実行につき 1 度だけ各候補（元のホストを含む）へ HEAD を送って応答時間を測り、
+    //! 最速のものから取得する。選択は TTL 付きでキャッシュし、次回以降の計測を省く。
+
+    use std::{
+        collections::{BTreeMap, HashMap},
+        path::Path,
+        str::FromStr,
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    };
+
+    use once_cell::sync::OnceCell;
+    use serde::{Deserialize, Serialize};
+
+    /// キャッシュした選択を使い回す期間。
+    pub const TTL: Duration = Duration::from_secs(24 * 60 * 60);
+    /// 1 候補あたりの計測上限。これを超えた候補は選ばない。
+    const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+    /// `HOST=URL` 形式のミラー指定。`https://HOST` を `URL` に置き換えて取得する。
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct MirrorSpec {
+        pub host: String,
+        pub url: String,
+    }
+
+    impl FromStr for MirrorSpec {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let Some((host, url)) = s.split_once('=') else {
+                return Err(format!("expected HOST=URL, got `{s}`"));
+            };
+            let host = host.trim().to_ascii_lowercase();
+            let url = url.trim().trim_end_matches('/');
+            if host.is_empty() || host.contains('/') {
+                return Err(format!("invalid mirror host `{host}`"));
+            }
+            if !url.starts_with("https://") && !url.starts_with("http://") {
+                return Err(format!("mirror URL must be http(s): `{url}`"));
+            }
+            Ok(Self {
+                host,
+                url: url.to_string(),
+            })
+        }
+    }
+
+    /// 設定されたミラー。最初の同期より前に [`configure`] で設定する。
+    static SPECS: OnceCell<Vec<MirrorSpec>> = OnceCell::new();
+    /// 実行中に選ばれたホスト → 取得元 prefix。元のホストが最速なら載らない。
+    static SELECTED: tokio::sync::OnceCell<HashMap<String, String>> =
+        tokio::sync::OnceCell::const_new();
+
+    /// ミラーを設定する。2 回目以降の設定は無視される。
+    pub fn configure(specs: Vec<MirrorSpec>) {
+        let _ = SPECS.set(specs);
+    }
+
+    /// キャッシュ上の 1 ホスト分の選択。候補の集合が変われば無効とする。
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct CachedChoice {
+        candidates: Vec<String>,
+        url: String,
+        probed_at: u64,
+    }
+
+    /// ホストごとの候補。先頭は元のホスト、以降は指定順のミラー（重複除去）。
+    fn candidates(specs: &[MirrorSpec]) -> BTreeMap<String, Vec<String>> {
+        let mut map: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for spec in specs {
+            let list = map
+                .entry(spec.host.clone())
+                .or_insert_with(|| vec![format!("https://{}", spec.host)]);
+            if !list.contains(&spec.url) {
+                list.push(spec.url.clone());
+            }
+        }
+        map
+    }
+
+    /// 全候補へ並行して HEAD を送り、最初に応答した候補を返す。全滅なら元のホスト。
+    /// HTTP ステータスは問わない（到達できれば取得元として使える）。
+    async fn probe(client: &reqwest::Client, candidates: &[String]) -> String {
+        let mut tasks = tokio::task::JoinSet::new();
+        for url in candidates {
+            let request = client.head(url).send();
+            let url = url.clone();
+            tasks.spawn(async move {
+                let start = Instant::now();
+                match tokio::time::timeout(PROBE_TIMEOUT, request).await {
+                    Ok(Ok(_)) => Some((start.elapsed(), url)),
+                    _ => None,
+                }
+            });
+        }
+        let mut fastest: Option<(Duration, String)> = None;
+        while let Some(result) = tasks.join_next().await {
+            if let Ok(Some((elapsed, url))) = result
+                && fastest.as_ref().is_none_or(|(best, _)| elapsed < *best)
+            {
+                fastest = Some((elapsed, url));
+            }
+        }
+        fastest.map_or_else(|| candidates[0].clone(), |(_, url)| url)
+    }
+
+    /// ホストごとの取得元を決める。`cache_path` の選択が新しく候補も同じなら計測しない。
+    /// 計測した結果はキャッシュへ書き戻す（書き込み失敗は次回の再計測で済むので無視）。
+    pub async fn select(
+        client: &reqwest::Client,
+        specs: &[MirrorSpec],
+        cache_path: &Path,
+        now: u64,
+    ) -> HashMap<String, String> {
+        let mut cache: BTreeMap<String, CachedChoice> = match tokio::fs::read(cache_path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_default(),
+            Err(_) => BTreeMap::new(),
+        };
+        let mut probed = false;
+        let mut selected = HashMap::new();
+        for (host, candidates) in candidates(specs) {
+            let fresh = cache.get(&host).filter(|choice| {
+                choice.candidates == candidates
+                    && now.saturating_sub(choice.probed_at) < TTL.as_secs()
+            });
+            let url = match fresh {
+                Some(choice) => choice.url.clone(),
+                None => {
+                    let url = probe(client, &candidates).await;
+                    cache.insert(
+                        host.clone(),
+                        CachedChoice {
+                            candidates: candidates.clone(),
+                            url: url.clone(),
+                            probed_at: now,
+                        },
+                    );
+                    probed = true;
+                    url
+                }
+            };
+            if url != candidates[0] {
+                selected.insert(host, url);
+            }
+        }
+        if probed && let Ok(content) = serde_json::to_vec_pretty(&cache) {
+            if let Some(parent) = cache_path.parent() {
+                let _ = tokio::fs::create_dir_all(parent).await;
+            }
+            let _ = tokio::fs::write(cache_path, content).await;
+        }
+        selected
+    }
+
+    /// 設定されたミラーから取得元を選ぶ。プロセスにつき 1 度だけ計測し、以降は同じ選択を使う。
+    pub async fn select_once(client: &reqwest::Client, cache_path: &Path) {
+        let Some(specs) = SPECS.get().filter(|specs| !specs.is_empty()) else {
+            return;
+        };
+        SELECTED
+            .get_or_init(|| async {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_secs());
+                select(client, specs, cache_path, now).await
+            })
+            .await;
+    }
+
+    /// `url` を選択済みの取得元へ書き換える。`https://HOST/` 以外やミラー未選択なら `None`。
+    pub fn rewrite(url: &str) -> Option<String> {
+        rewrite_with(SELECTED.get()?, url)
+    }
+
+    fn rewrite_with(selected: &HashMap<String, String>, url: &str) -> Option<String> {
+        let (host, path) = url.strip_prefix("https://")?.split_once('/')?;
+        let prefix = selected.get(&host.to_ascii_lowercase())?;
+        Some(format!("{prefix}/{path}"))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn mirror_spec_parses_host_and_url() {
+            assert_eq!(
+                "GitHub.com=https://mirror.example/github/".parse::<MirrorSpec>(),
+                Ok(MirrorSpec {
+                    host: "github.com".to_string(),
+                    url: "https://mirror.example/github".to_string(),
+                })
+            );
+            assert!("github.com".parse::<MirrorSpec>().is_err());
+            assert!("github.com=ssh://mirror".parse::<MirrorSpec>().is_err());
+            assert!("a/b=https://mirror".parse::<MirrorSpec>().is_err());
+        }
+
+        #[test]
+        fn rewrite_replaces_only_selected_hosts() {
+            let selected = HashMap::from([(
+                "github.com".to_string(),
+                "https://mirror.example/github".to_string(),
+            )]);
+            assert_eq!(
+                rewrite_with(&selected, "https://github.com/o/r").as_deref(),
+                Some("https://mirror.example/github/o/r")
+            );
+            assert_eq!(rewrite_with(&selected, "https://gitlab.com/o/r"), None);
+            assert_eq!(rewrite_with(&selected, "ssh://github.com/o/r"), None);
+        }
+
+        #[tokio::test]
+        async fn select_reuses_a_fresh_cached_choice_without_probing() {
+            let tmp = tempfile::tempdir().unwrap();
+            let path = tmp.path().join("mirrors.json");
+            let specs = vec![
+                "github.com=https://a.invalid"
+                    .parse::<MirrorSpec>()
+                    .unwrap(),
+                "github.com=https://b.invalid"
+                    .parse::<MirrorSpec>()
+                    .unwrap(),
+            ];
+            let cache = BTreeMap::from([(
+                "github.com".to_string(),
+                CachedChoice {
+                    candidates: candidates(&specs)["github.com"].clone(),
+                    url: "https://b.invalid".to_string(),
+                    probed_at: 1_000,
+                },
+            )]);
+            std::fs::write(&path, serde_json::to_vec(&cache).unwrap()).unwrap();
+
+            // TTL 内ならキャッシュの選択をそのまま使い、ファイルも書き換えない。
+            let client = reqwest::Client::new();
+            let selected = select(&client, &specs, &path, 1_000 + 60).await;
+            assert_eq!(
+                selected.get("github.com").map(String::as_str),
+                Some("https://b.invalid")
+            );
+            let stored: BTreeMap<String, CachedChoice> =
+                serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+            assert_eq!(stored, cache);
+        }
+
+        #[test]
+        fn candidates_start_with_the_origin_and_drop_duplicates() {
+            let specs = [
+                "github.com=https://a.example"
+                    .parse::<MirrorSpec>()
+                    .unwrap(),
+                "github.com=https://a.example/"
+                    .parse::<MirrorSpec>()
+                    .unwrap(),
+            ];
+            assert_eq!(
+                candidates(&specs)["github.com"],
+                ["https://github.com", "https://a.example"]
+            );
+        }
+    }
+}
+
 pub mod github {
     //! GitHub関連のユーティリティ
 
@@ -712,6 +1735,9 @@ pub mod github {
         /// API rate limit 残量が少ない（閾値以下）または 429。
         /// 実行スコープのサーキットブレーカーを開き、以降は Git フォールバックへ直行する。
         RateLimited,
+        /// `Retry-After` 付きのレートリミット（二次レートリミット）。待ち時間が短ければ待って再試行し、
+        /// 長ければ [`ApiError::RateLimited`] と同じく扱う。
+        RetryAfter(std::time::Duration),
         /// 認証エラー（401/403）。再試行せず Git フォールバック。
         Auth,
         /// リポジトリ/ref 未検出（404）。再試行せず Git フォールバック。
@@ -733,10 +1759,41 @@ pub mod github {
         }
     }
 
+    /// 応答ヘッダからレートリミットを判定する。GitHub は一次レートリミットを
+    /// `x-ratelimit-remaining` の枯渇（403/429）で、二次レートリミットを `retry-after` 付きの
+    /// 403/429 で知らせる。残量が `threshold` 未満なら使い切る前に諦める。
+    fn rate_limit(
+        status: reqwest::StatusCode,
+        headers: &reqwest::header::HeaderMap,
+        threshold: u64,
+    ) -> Option<ApiError> {
+        let number = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        };
+        let limited_status = matches!(status.as_u16(), 403 | 429);
+        if limited_status && let Some(secs) = number("retry-after") {
+            return Some(ApiError::RetryAfter(std::time::Duration::from_secs(secs)));
+        }
+        if number("x-ratelimit-remaining").is_some_and(|remaining| remaining < threshold)
+            || status.as_u16() == 429
+        {
+            return Some(ApiError::RateLimited);
+        }
+        None
+    }
+
+    /// `Retry-After` をこれ以下なら待って再試行する。長い待ちは Git フォールバックに任せる。
+    pub(crate) const MAX_RETRY_AFTER: std::time::Duration = std::time::Duration::from_secs(10);
+
     /// API エラー後の行動 (PLANS U2 step 5)。純粋関数（テスト可能）。
     pub(crate) enum ApiFallbackPolicy {
         /// 一時エラーで予算が残っている: 再試行する。
         Retry,
+        /// 短い `Retry-After`: 指定の時間だけ待って再試行する。
+        Wait(std::time::Duration),
         /// レートリミット: ブレーカーを開き Git フォールバックへ。
         RateLimited,
         /// 認証/未検出/その他、または予算切れ: Git フォールバックへ。
@@ -744,7 +1801,8 @@ pub mod github {
     }
 
     /// `err` と現在の試行回数から次の行動を決定する。
-    /// - RateLimited → ブレーカー始動 + Git フォールバック
+    /// - RetryAfter が [`MAX_RETRY_AFTER`] 以下かつ `attempt < max_retries` → 待って再試行
+    /// - RateLimited・長い RetryAfter → ブレーカー始動 + Git フォールバック
     /// - Transient かつ `attempt < max_retries` → 再試行
     /// - それ以外（Auth/NotFound/Other、または予算切れ）→ Git フォールバック
     pub(crate) fn api_error_policy(
@@ -753,7 +1811,10 @@ pub mod github {
         max_retries: usize,
     ) -> ApiFallbackPolicy {
         match err {
-            ApiError::RateLimited => ApiFallbackPolicy::RateLimited,
+            ApiError::RetryAfter(wait) if attempt < max_retries && *wait <= MAX_RETRY_AFTER => {
+                ApiFallbackPolicy::Wait(*wait)
+            }
+            ApiError::RateLimited | ApiError::RetryAfter(_) => ApiFallbackPolicy::RateLimited,
             ApiError::Transient if attempt < max_retries => ApiFallbackPolicy::Retry,
             _ => ApiFallbackPolicy::GiveUp,
         }
@@ -774,6 +1835,8 @@ pub mod github {
     #[derive(Debug)]
     pub struct CircuitBreaker {
         open: std::sync::atomic::AtomicBool,
+        /// レートリミットを報告済みか（実行ごとに 1 回だけ知らせる）。
+        rate_limit_reported: std::sync::atomic::AtomicBool,
     }
 
     impl Default for CircuitBreaker {
@@ -786,6 +1849,7 @@ pub mod github {
         pub fn new() -> Self {
             Self {
                 open: std::sync::atomic::AtomicBool::new(false),
+                rate_limit_reported: std::sync::atomic::AtomicBool::new(false),
             }
         }
 
@@ -798,6 +1862,17 @@ pub mod github {
         pub fn trip(&self) {
             self.open.store(true, std::sync::atomic::Ordering::Release);
         }
+
+        /// レートリミットでブレーカーを開く。最初の 1 回だけ利用者に知らせる。
+        pub fn trip_rate_limited(&self) {
+            self.trip();
+            if !self
+                .rate_limit_reported
+                .swap(true, std::sync::atomic::Ordering::AcqRel)
+            {
+                crate::log::msg(crate::log::Message::GitHubRateLimited);
+            }
+        }
     }
 
     /// GitHub REST API でコミットハッシュを解決する。
@@ -831,14 +1906,8 @@ pub mod github {
         let resp = req.send().await.map_err(|_| ApiError::Transient)?;
 
         // rate limit チェック
-        if let Some(remaining) = resp
-            .headers()
-            .get("x-ratelimit-remaining")
-            .and_then(|v| v.to_str().ok())
-            .and_then(|v| v.parse::<u64>().ok())
-            && remaining < RATE_LIMIT_THRESHOLD
-        {
-            return Err(ApiError::RateLimited);
+        if let Some(err) = rate_limit(resp.status(), resp.headers(), RATE_LIMIT_THRESHOLD) {
+            return Err(err);
         }
 
         if !resp.status().is_success() {
@@ -866,14 +1935,8 @@ pub mod github {
         let resp2 = req2.send().await.map_err(|_| ApiError::Transient)?;
 
         // 2回目のリクエストでも rate limit をチェック
-        if let Some(remaining) = resp2
-            .headers()
-            .get("x-ratelimit-remaining")
-            .and_then(|v| v.to_str().ok())
-            .and_then(|v| v.parse::<u64>().ok())
-            && remaining < RATE_LIMIT_THRESHOLD
-        {
-            return Err(ApiError::RateLimited);
+        if let Some(err) = rate_limit(resp2.status(), resp2.headers(), RATE_LIMIT_THRESHOLD) {
+            return Err(err);
         }
 
         if !resp2.status().is_success() {
@@ -930,6 +1993,7 @@ pub mod github {
     /// N リポジトリの rev 解決を1つの GraphQL クエリに組み立てる。
     /// - rev=None → `defaultBranchRef`
     /// - rev=Some(name) → heads/tags 2フィールド（非 null を採用、heads 優先 = REST/git と同順序）
+    /// - rev=Some(`refs/...`) → その完全修飾 ref だけの1フィールド（`branch` 指定）
     fn build_graphql_query(chunk: &[GithubRev]) -> (String, Vec<AliasMapping>) {
         let mut fields = String::new();
         let mut mapping = Vec::new();
@@ -949,6 +2013,19 @@ pub mod github {
                         kind: AliasKind::DefaultBranch,
                     });
                 }
+                Some(name) if name.starts_with("refs/") => {
+                    let name = escape_graphql_string(name);
+                    let alias = format!("r{i}h");
+                    fields.push_str(&format!(
+                        "  {alias}: repository(owner: \"{owner}\", name: \"{repo}\") {{ ref(qualifiedName: \"{name}\") {{ target {{ ... on Commit {{ oid }} }} }} }}\n"
+                    ));
+                    mapping.push(AliasMapping {
+                        alias,
+                        owner: g.owner.clone(),
+                        repo: g.repo.clone(),
+                        kind: AliasKind::Heads,
+                    });
+                }
                 Some(name) => {
                     let name = escape_graphql_string(name);
                     let alias_h = format!("r{i}h");
@@ -1062,14 +2139,8 @@ pub mod github {
             .send()
             .await
             .map_err(|_| ApiError::Transient)?;
-        if let Some(remaining) = resp
-            .headers()
-            .get("x-ratelimit-remaining")
-            .and_then(|v| v.to_str().ok())
-            .and_then(|v| v.parse::<u64>().ok())
-            && remaining < RATE_LIMIT_THRESHOLD
-        {
-            return Err(ApiError::RateLimited);
+        if let Some(err) = rate_limit(resp.status(), resp.headers(), RATE_LIMIT_THRESHOLD) {
+            return Err(err);
         }
         if !resp.status().is_success() {
             return Err(classify_http_status(resp.status()));
@@ -1078,10 +2149,202 @@ pub mod github {
         parse_graphql_response(&text, &mapping)
     }
 
+    /// `rsplug info` で表示するリポジトリのメタデータ（state dir にキャッシュする）。
+    #[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    pub struct RepositoryInfo {
+        pub description: Option<String>,
+        pub homepage: Option<String>,
+        pub stars: u64,
+        /// 最新 release のタグ。release が無ければ最初に返るタグ。
+        pub latest_tag: Option<String>,
+        /// 取得した UNIX 時刻（秒）。
+        pub fetched_at: u64,
+    }
+
+    /// REST API に GET し、成功時の本文を返す。
+    async fn api_get(
+        client: &reqwest::Client,
+        path: &str,
+        token: Option<&str>,
+    ) -> Result<String, ApiError> {
+        let mut req = client
+            .get(format!("https://api.github.com{path}"))
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", "rsplug");
+        if let Some(token) = token {
+            req = req
+                .header("Authorization", format!("Bearer {token}"))
+                .header("X-GitHub-Api-Version", "2022-11-28");
+        }
+        let resp = req.send().await.map_err(|_| ApiError::Transient)?;
+        if let Some(err) = rate_limit(resp.status(), resp.headers(), 1) {
+            return Err(err);
+        }
+        if !resp.status().is_success() {
+            return Err(classify_http_status(resp.status()));
+        }
+        resp.text().await.map_err(|_| ApiError::Transient)
+    }
+
+    /// `GET /repos/{o}/{r}` と release/tag 一覧の本文から [`RepositoryInfo`] を組み立てる。
+    pub(crate) fn parse_repository_info(
+        repository: &str,
+        latest_release: Option<&str>,
+        tags: Option<&str>,
+        fetched_at: u64,
+    ) -> Result<RepositoryInfo, ApiError> {
+        let parse = |body: &str| {
+            serde_json::from_str::<serde_json::Value>(body)
+                .map_err(|e| ApiError::Other(format!("parse GitHub API response: {e}")))
+        };
+        let non_empty = |value: &serde_json::Value| {
+            value.as_str().filter(|s| !s.is_empty()).map(str::to_string)
+        };
+        let repository = parse(repository)?;
+        let release_tag = latest_release
+            .map(parse)
+            .transpose()?
+            .and_then(|release| non_empty(&release["tag_name"]));
+        let latest_tag = match release_tag {
+            Some(tag) => Some(tag),
+            None => tags
+                .map(parse)
+                .transpose()?
+                .and_then(|tags| non_empty(&tags[0]["name"])),
+        };
+        Ok(RepositoryInfo {
+            description: non_empty(&repository["description"]),
+            homepage: non_empty(&repository["homepage"]),
+            stars: repository["stargazers_count"].as_u64().unwrap_or(0),
+            latest_tag,
+            fetched_at,
+        })
+    }
+
+    /// GitHub REST API でリポジトリの説明・homepage・star 数・最新タグを取得する。
+    /// release が無い（404）ときはタグ一覧の先頭を最新タグとする。
+    pub async fn repository_info(
+        client: &reqwest::Client,
+        owner: &str,
+        repo: &str,
+        token: Option<&str>,
+    ) -> Result<RepositoryInfo, ApiError> {
+        let base = format!("/repos/{owner}/{repo}");
+        let repository = api_get(client, &base, token).await?;
+        let release = match api_get(client, &format!("{base}/releases/latest"), token).await {
+            Ok(body) => Some(body),
+            Err(ApiError::NotFound) => None,
+            Err(e) => return Err(e),
+        };
+        let tags = match &release {
+            Some(_) => None,
+            None => Some(api_get(client, &format!("{base}/tags?per_page=1"), token).await?),
+        };
+        let fetched_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        parse_repository_info(&repository, release.as_deref(), tags.as_deref(), fetched_at)
+    }
+
+    /// GitHub release 1 件（`rsplug changelog` 用）。
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Release {
+        pub tag: String,
+        /// release のタイトル。空なら `None`。
+        pub name: Option<String>,
+        /// release notes の本文（Markdown）。空なら `None`。
+        pub body: Option<String>,
+    }
+
+    /// `GET /repos/{o}/{r}/releases` の本文から draft を除いた release を新しい順に返す。
+    pub(crate) fn parse_releases(body: &str) -> Result<Vec<Release>, ApiError> {
+        let releases: Vec<serde_json::Value> = serde_json::from_str(body)
+            .map_err(|e| ApiError::Other(format!("parse GitHub API response: {e}")))?;
+        let non_empty = |value: &serde_json::Value| {
+            value
+                .as_str()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+        Ok(releases
+            .iter()
+            .filter(|release| !release["draft"].as_bool().unwrap_or(false))
+            .filter_map(|release| {
+                Some(Release {
+                    tag: non_empty(&release["tag_name"])?,
+                    name: non_empty(&release["name"]),
+                    body: non_empty(&release["body"]),
+                })
+            })
+            .collect())
+    }
+
+    /// GitHub REST API で最近の release を最大 `per_page` 件取得する。
+    pub async fn releases(
+        client: &reqwest::Client,
+        owner: &str,
+        repo: &str,
+        per_page: usize,
+        token: Option<&str>,
+    ) -> Result<Vec<Release>, ApiError> {
+        let path = format!("/repos/{owner}/{repo}/releases?per_page={per_page}");
+        parse_releases(&api_get(client, &path, token).await?)
+    }
+
     #[cfg(test)]
     mod graphql_tests {
         use super::*;
 
+        #[test]
+        fn releases_skip_drafts_and_blank_fields() {
+            let body = r#"[
+                {"tag_name":"v2.0","name":"v2.0","body":"Breaking:\n- x","draft":false},
+                {"tag_name":"v2.1-rc","name":"","body":null,"draft":true},
+                {"tag_name":"v1.9","name":" ","body":"  ","draft":false}
+            ]"#;
+            assert_eq!(
+                parse_releases(body).unwrap(),
+                [
+                    Release {
+                        tag: "v2.0".into(),
+                        name: Some("v2.0".into()),
+                        body: Some("Breaking:\n- x".into()),
+                    },
+                    Release {
+                        tag: "v1.9".into(),
+                        name: None,
+                        body: None,
+                    },
+                ]
+            );
+            assert!(parse_releases("{}").is_err());
+        }
+
+        #[test]
+        fn repository_info_prefers_release_tag_and_falls_back_to_tags() {
+            let repository = r#"{"description":"A plugin","homepage":"","stargazers_count":42}"#;
+            let info = parse_repository_info(repository, Some(r#"{"tag_name":"v1.2.0"}"#), None, 7)
+                .unwrap();
+            assert_eq!(
+                info,
+                RepositoryInfo {
+                    description: Some("A plugin".into()),
+                    homepage: None,
+                    stars: 42,
+                    latest_tag: Some("v1.2.0".into()),
+                    fetched_at: 7,
+                }
+            );
+
+            let info =
+                parse_repository_info(repository, None, Some(r#"[{"name":"v0.9"}]"#), 7).unwrap();
+            assert_eq!(info.latest_tag.as_deref(), Some("v0.9"));
+            let info = parse_repository_info(repository, None, Some("[]"), 7).unwrap();
+            assert_eq!(info.latest_tag, None);
+            assert!(parse_repository_info("not json", None, None, 7).is_err());
+        }
+
         fn rev(owner: &str, repo: &str, rev: Option<&str>) -> GithubRev {
             GithubRev {
                 owner: owner.into(),
@@ -1107,6 +2370,14 @@ pub mod github {
             assert_eq!(m.len(), 2);
         }
 
+        #[test]
+        fn build_graphql_query_qualified_ref_emits_only_that_ref() {
+            let (q, m) = build_graphql_query(&[rev("o", "r", Some("refs/heads/develop"))]);
+            assert!(q.contains("qualifiedName: \"refs/heads/develop\""));
+            assert!(!q.contains("refs/tags/") && !q.contains("refs/heads/refs/"));
+            assert_eq!(m.len(), 1);
+        }
+
         #[test]
         fn build_graphql_query_escapes_special_chars() {
             let (q, _) = build_graphql_query(&[rev("o", "r", Some("a\"b"))]);
@@ -1233,6 +2504,70 @@ pub mod github {
             }
         }
 
+        #[test]
+        fn rate_limit_reads_remaining_and_retry_after_headers() {
+            use reqwest::{StatusCode, header::HeaderMap};
+            let headers = |pairs: &[(&'static str, &str)]| {
+                let mut map = HeaderMap::new();
+                for (name, value) in pairs {
+                    map.insert(*name, value.parse().unwrap());
+                }
+                map
+            };
+            let exhausted = headers(&[("x-ratelimit-remaining", "0")]);
+            assert_eq!(
+                rate_limit(StatusCode::FORBIDDEN, &exhausted, 1),
+                Some(ApiError::RateLimited)
+            );
+            assert_eq!(
+                rate_limit(
+                    StatusCode::OK,
+                    &headers(&[("x-ratelimit-remaining", "49")]),
+                    50
+                ),
+                Some(ApiError::RateLimited)
+            );
+            assert_eq!(
+                rate_limit(
+                    StatusCode::FORBIDDEN,
+                    &headers(&[("retry-after", "3"), ("x-ratelimit-remaining", "10")]),
+                    1
+                ),
+                Some(ApiError::RetryAfter(std::time::Duration::from_secs(3)))
+            );
+            assert_eq!(
+                rate_limit(StatusCode::TOO_MANY_REQUESTS, &HeaderMap::new(), 1),
+                Some(ApiError::RateLimited)
+            );
+            // 残量のある 403 は認証エラーとして classify_http_status に任せる。
+            assert_eq!(
+                rate_limit(
+                    StatusCode::FORBIDDEN,
+                    &headers(&[("x-ratelimit-remaining", "4000")]),
+                    50
+                ),
+                None
+            );
+        }
+
+        #[test]
+        fn api_error_policy_waits_for_short_retry_after_only() {
+            let short = ApiError::RetryAfter(std::time::Duration::from_secs(2));
+            assert!(matches!(
+                api_error_policy(&short, 0, 2),
+                ApiFallbackPolicy::Wait(wait) if wait == std::time::Duration::from_secs(2)
+            ));
+            assert!(matches!(
+                api_error_policy(&short, 2, 2),
+                ApiFallbackPolicy::RateLimited
+            ));
+            let long = ApiError::RetryAfter(MAX_RETRY_AFTER * 6);
+            assert!(matches!(
+                api_error_policy(&long, 0, 2),
+                ApiFallbackPolicy::RateLimited
+            ));
+        }
+
         #[test]
         fn api_error_policy_rate_limited_trips_breaker() {
             assert!(matches!(
@@ -1300,7 +2635,7 @@ pub mod fetch {
     });
 
     use super::super::error::Error;
-    use super::github;
+    use super::{github, task};
 
     /// ダウンロード済み tarball と、その所有する一時 staging directory。
     ///
@@ -1332,11 +2667,11 @@ pub mod fetch {
     /// dropped `JoinHandle` otherwise detaches the task and can leave a
     /// request or blocking extraction alive after the staging owner returned.
     #[allow(dead_code)]
-    struct AbortOnDrop<T>(Option<tokio::task::JoinHandle<T>>);
+    pub(super) struct AbortOnDrop<T>(Option<tokio::task::JoinHandle<T>>);
 
     #[allow(dead_code)]
     impl<T> AbortOnDrop<T> {
-        fn new(handle: tokio::task::JoinHandle<T>) -> Self {
+        pub(super) fn new(handle: tokio::task::JoinHandle<T>) -> Self {
             Self(Some(handle))
         }
 
@@ -1346,7 +2681,7 @@ pub mod fetch {
             }
         }
 
-        async fn join(mut self) -> Result<T, tokio::task::JoinError> {
+        pub(super) async fn join(mut self) -> Result<T, tokio::task::JoinError> {
             self.0
                 .take()
                 .expect("AbortOnDrop handle must exist before join")
@@ -1499,9 +2834,10 @@ pub mod fetch {
                     Ok(())
                 },
             ));
-            let extraction_result = extraction.join().await.map_err(|error| {
-                Error::Io(std::io::Error::other(format!("join error: {error}")))
-            })?;
+            let extraction_result = extraction
+                .join()
+                .await
+                .map_err(task::panicked("tarball extraction"))?;
             if extraction_result.is_err() {
                 producer.abort();
             }
@@ -1725,9 +3061,11 @@ pub async fn execute(
         let mut cmd = Command::new(cmd);
         cmd.current_dir(workdir);
         cmd.args(args);
+        // 待つのをやめたら（Ctrl-C など）task ごと止め、子プロセスも道連れにする。
+        cmd.kill_on_drop(true);
         cmd
     };
-    tokio::spawn(async move {
+    let task = fetch::AbortOnDrop::new(tokio::spawn(async move {
         let mut child = cmd
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
@@ -1785,8 +3123,24 @@ pub async fn execute(
         receiving_task.await.map_err(std::io::Error::other)??;
 
         Ok(status.code().unwrap_or(-1))
-    })
-    .await?
+    }));
+    task.join().await?
+}
+
+/// バイト数を `KiB`・`MiB`・`GiB` 単位で表示する。
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
 }
 
 pub fn truncate(val: &impl ToString, len: usize) -> String {
@@ -1831,6 +3185,29 @@ pub fn truncate(val: &impl ToString, len: usize) -> String {
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn git_threads_turn_panics_into_errors_and_keep_serving() {
+        let panicked = git::pool::run(|| -> u8 { panic!("boom") }).await;
+        assert!(matches!(
+            panicked,
+            Err(Error::TaskPanicked { context: "git" })
+        ));
+        // スレッド数を超える job は順番を待ち、いずれも実行される。
+        let mut jobs = tokio::task::JoinSet::new();
+        for i in 0..git::pool::threads() * 2 {
+            jobs.spawn(git::pool::run(move || {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                i
+            }));
+        }
+        let mut done = Vec::new();
+        while let Some(result) = jobs.join_next().await {
+            done.push(result.unwrap().unwrap());
+        }
+        done.sort();
+        assert_eq!(done, (0..git::pool::threads() * 2).collect::<Vec<_>>());
+    }
+
     #[tokio::test]
     async fn tarball_stream_bridge_propagates_producer_errors() {
         let (tx, rx) = tokio::sync::mpsc::channel(1);
@@ -1992,6 +3369,97 @@ mod tests {
         );
     }
 
+    /// pkt-line の行（4 桁の 16 進長 + 内容）。
+    fn pkt(line: &str) -> String {
+        format!("{:04x}{line}", line.len() + 4)
+    }
+
+    #[test]
+    fn ref_advertisement_resolves_head_and_wildcard_revs() {
+        let oid = |digit: char| digit.to_string().repeat(40);
+        let body = [
+            pkt("# service=git-upload-pack\n"),
+            "0000".to_string(),
+            pkt(&format!(
+                "{} HEAD\0multi_ack symref=HEAD:refs/heads/main\n",
+                oid('1')
+            )),
+            pkt(&format!("{} refs/heads/main\n", oid('1'))),
+            pkt(&format!("{} refs/tags/v1.2.0\n", oid('2'))),
+            pkt(&format!("{} refs/tags/v1.10.0\n", oid('3'))),
+            "0000".to_string(),
+        ]
+        .concat();
+        let refs = git::parse_ref_advertisement(body.as_bytes()).unwrap();
+        assert_eq!(refs.len(), 4);
+        assert_eq!(refs[0].0, "HEAD");
+
+        let url: std::sync::Arc<str> = "https://example.com/repo".into();
+        let resolve = |rev| git::select_rev(&url, &refs, rev).unwrap().to_string();
+        assert_eq!(resolve(None), oid('1'));
+        assert_eq!(resolve(Some("main")), oid('1'));
+        assert_eq!(resolve(Some("v1.*")), oid('3'));
+        assert!(git::select_rev(&url, &refs, Some("nope")).is_err());
+    }
+
+    #[test]
+    fn qualified_rev_only_matches_the_named_branch() {
+        let oid = |digit: char| digit.to_string().repeat(40);
+        let body = [
+            pkt("# service=git-upload-pack\n"),
+            "0000".to_string(),
+            pkt(&format!("{} HEAD\0symref=HEAD:refs/heads/main\n", oid('1'))),
+            pkt(&format!("{} refs/heads/main\n", oid('1'))),
+            pkt(&format!("{} refs/heads/develop\n", oid('2'))),
+            pkt(&format!("{} refs/tags/develop\n", oid('3'))),
+            "0000".to_string(),
+        ]
+        .concat();
+        let refs = git::parse_ref_advertisement(body.as_bytes()).unwrap();
+        let url: std::sync::Arc<str> = "https://example.com/repo".into();
+        let resolve = |rev| git::select_rev(&url, &refs, rev).unwrap().to_string();
+        // 短縮名では同名の tag が勝つが、`branch` 由来の完全修飾名は heads だけを見る。
+        assert_eq!(resolve(Some("develop")), oid('3'));
+        assert_eq!(resolve(Some("refs/heads/develop")), oid('2'));
+        assert!(git::select_rev(&url, &refs, Some("refs/heads/feature")).is_err());
+    }
+
+    #[test]
+    fn peel_tags_prefers_the_peeled_commit() {
+        let oid = |digit: char| git2::Oid::from_str(&digit.to_string().repeat(40)).unwrap();
+        let refs = [
+            ("refs/heads/main", oid('1')),
+            ("refs/tags/v1.0", oid('2')),
+            ("refs/tags/v1.0^{}", oid('3')),
+            ("refs/tags/light", oid('4')),
+        ];
+        assert_eq!(
+            git::peel_tags(refs.into_iter()),
+            [
+                ("light".to_string(), oid('4')),
+                ("v1.0".to_string(), oid('3'))
+            ]
+        );
+    }
+
+    #[test]
+    fn ref_advertisement_rejects_truncated_lines_and_accepts_empty_repos() {
+        let empty = format!(
+            "{}0000{}0000",
+            pkt("# service=git-upload-pack\n"),
+            pkt(&format!(
+                "{} capabilities^{{}}\0agent=git/2\n",
+                "0".repeat(40)
+            ))
+        );
+        assert_eq!(
+            git::parse_ref_advertisement(empty.as_bytes()),
+            Some(Vec::new())
+        );
+        assert_eq!(git::parse_ref_advertisement(b"00ffabc"), None);
+        assert_eq!(git::parse_ref_advertisement(b"zz"), None);
+    }
+
     #[test]
     fn supports_tarball_classifies_correctly() {
         assert!(github::supports_tarball("https://github.com/owner/repo"));
@@ -2072,7 +3540,7 @@ mod tests {
         let oid = Oid::from_str(oid_str).unwrap();
 
         // init_snapshot が成功し、worktree に commit 内容が checkout されている
-        let _ = super::git::init_snapshot(&snap, &origin, oid)
+        let snapshot = super::git::init_snapshot(&snap, &origin, oid, Default::default())
             .await
             .unwrap();
         let content = tokio::fs::read_to_string(snap.join("README.md"))
@@ -2091,6 +3559,24 @@ mod tests {
         .unwrap();
         assert_eq!(head.trim(), oid_str);
 
+        // checkout 直後は clean。編集・untracked は列挙し、rsplug の metadata は無視する。
+        assert!(snapshot.dirty_paths().await.unwrap().is_empty());
+        std::fs::write(snap.join("README.md"), "edited\n").unwrap();
+        std::fs::write(snap.join("scratch.lua"), "").unwrap();
+        std::fs::write(snap.join(super::git::RSPLUG_BUILD_SUCCESS_FILE), "").unwrap();
+        let mut paths = snapshot.dirty_paths().await.unwrap();
+        paths.sort();
+        assert_eq!(paths, ["README.md", "scratch.lua"]);
+
+        // 中断の合図を受けた checkout は、途中で止まったまま成功扱いにしない。
+        let cancel = super::git::Cancel::default();
+        cancel.cancel();
+        assert!(
+            super::git::init_snapshot(dir.join("cancelled"), &origin, oid, cancel)
+                .await
+                .is_err()
+        );
+
         let _ = std::fs::remove_dir_all(&dir);
     }
 }
@@ -0,0 +1,279 @@
+//! Software bill of materials for the installed plugin set (`rsplug sbom`).
+//!
+//! The provenance index written by the last install lists every repository
+//! snapshot placed into the pack. Each one becomes a component with its source
+//! URL, revision, and the license detected from the license files found while
+//! enumerating the snapshot. The document is written as CycloneDX 1.5 or
+//! SPDX 2.3 JSON.
+
+use rsplug::pack_plan::InstalledRepository;
+use serde_json::{Value, json};
+
+use super::*;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct SbomArgs {
+    /// Output format
+    #[arg(long, value_enum, default_value_t)]
+    pub(crate) format: SbomFormat,
+    /// Write the document to this file instead of standard output
+    #[arg(long, short)]
+    pub(crate) output: Option<PathBuf>,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum SbomFormat {
+    #[default]
+    Cyclonedx,
+    Spdx,
+}
+
+/// SPDX で値が分からないことを表す。
+const NOASSERTION: &str = "NOASSERTION";
+
+/// repo パス（`host/path`）の取得元 URL。
+fn source_url(repo: &str) -> String {
+    format!("https://{repo}")
+}
+
+/// GitHub の repo なら package URL を返す。
+fn purl(component: &InstalledRepository) -> Option<String> {
+    let path = component.repo.strip_prefix("github.com/")?;
+    Some(format!("pkg:github/{path}@{}", component.rev))
+}
+
+/// 表示名。この repo だけから成るパッケージの設定上の名前、無ければ repo 名。
+fn component_name(component: &InstalledRepository) -> String {
+    match component.names.as_slice() {
+        [name] => name.clone(),
+        _ => component
+            .repo
+            .rsplit('/')
+            .next()
+            .unwrap_or(&component.repo)
+            .to_string(),
+    }
+}
+
+/// UNIX 時刻（秒）を `YYYY-MM-DDThh:mm:ssZ` にする。
+fn rfc3339(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // civil-from-days（proleptic Gregorian）。
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+fn cyclonedx(components: &[InstalledRepository], timestamp: &str) -> Value {
+    let components: Vec<Value> = components
+        .iter()
+        .map(|component| {
+            let mut value = json!({
+                "type": "library",
+                "bom-ref": format!("{}@{}", component.repo, component.rev),
+                "name": component_name(component),
+                "version": component.rev,
+                "externalReferences": [
+                    { "type": "vcs", "url": source_url(&component.repo) }
+                ],
+            });
+            if let Some(purl) = purl(component) {
+                value["purl"] = json!(purl);
+            }
+            if let Some(license) = &component.license {
+                value["licenses"] = if license.contains(' ') {
+                    json!([{ "expression": license }])
+                } else {
+                    json!([{ "license": { "id": license } }])
+                };
+            }
+            if !component.names.is_empty() {
+                value["properties"] = json!([
+                    { "name": "rsplug:names", "value": component.names.join(", ") }
+                ]);
+            }
+            value
+        })
+        .collect();
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "version": 1,
+        "metadata": {
+            "timestamp": timestamp,
+            "tools": {
+                "components": [{
+                    "type": "application",
+                    "name": "rsplug",
+                    "version": env!("CARGO_PKG_VERSION"),
+                }]
+            },
+        },
+        "components": components,
+    })
+}
+
+fn spdx(components: &[InstalledRepository], timestamp: &str) -> Value {
+    let packages: Vec<Value> = components
+        .iter()
+        .enumerate()
+        .map(|(index, component)| {
+            let mut value = json!({
+                "name": component_name(component),
+                "SPDXID": format!("SPDXRef-Package-{}", index + 1),
+                "versionInfo": component.rev,
+                "downloadLocation": format!(
+                    "git+{}@{}",
+                    source_url(&component.repo),
+                    component.rev
+                ),
+                "filesAnalyzed": false,
+                "licenseConcluded": NOASSERTION,
+                "licenseDeclared": component.license.as_deref().unwrap_or(NOASSERTION),
+                "copyrightText": NOASSERTION,
+            });
+            if let Some(purl) = purl(component) {
+                value["externalRefs"] = json!([{
+                    "referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl",
+                    "referenceLocator": purl,
+                }]);
+            }
+            value
+        })
+        .collect();
+    let relationships: Vec<Value> = (1..=packages.len())
+        .map(|index| {
+            json!({
+                "spdxElementId": "SPDXRef-DOCUMENT",
+                "relationshipType": "DESCRIBES",
+                "relatedSpdxElement": format!("SPDXRef-Package-{index}"),
+            })
+        })
+        .collect();
+    // 同じ内容・同じ時刻の文書は同じ namespace になる。
+    let namespace = rsplug::util::hash::digest_hash_hex_string(&(
+        timestamp,
+        packages.iter().map(Value::to_string).collect::<Vec<_>>(),
+    ));
+    json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": "rsplug-installed-plugins",
+        "documentNamespace": format!("https://spdx.org/spdxdocs/rsplug-{namespace}"),
+        "creationInfo": {
+            "created": timestamp,
+            "creators": [format!("Tool: rsplug-{}", env!("CARGO_PKG_VERSION"))],
+        },
+        "packages": packages,
+        "relationships": relationships,
+    })
+}
+
+/// `rsplug sbom`: provenance index からインストール済み plugin の SBOM を書き出す。
+pub(crate) async fn print_sbom(ctx: &AppContext, args: &SbomArgs) -> Result<(), Error> {
+    let components = match rsplug::pack_plan::installed_repositories(ctx.packpath()).await {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(Error::NoProvenanceIndex);
+        }
+        components => components?,
+    };
+    let timestamp = rfc3339(ctx.clock.unix_secs());
+    let document = match args.format {
+        SbomFormat::Cyclonedx => cyclonedx(&components, &timestamp),
+        SbomFormat::Spdx => spdx(&components, &timestamp),
+    };
+    let mut content = serde_json::to_string_pretty(&document)
+        .map_err(std::io::Error::other)?
+        .into_bytes();
+    content.push(b'\n');
+    match &args.output {
+        Some(path) => tokio::fs::write(path, content).await?,
+        None => {
+            use std::io::Write;
+            std::io::stdout().write_all(&content)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn components() -> Vec<InstalledRepository> {
+        vec![
+            InstalledRepository {
+                repo: "github.com/owner/a.nvim".to_string(),
+                rev: "0123abcd".to_string(),
+                names: vec!["a".to_string()],
+                license_files: vec![PathBuf::from("LICENSE")],
+                license: Some("MIT".to_string()),
+            },
+            InstalledRepository {
+                repo: "gitlab.com/group/b.nvim".to_string(),
+                rev: "4567ef01".to_string(),
+                names: Vec::new(),
+                license_files: Vec::new(),
+                license: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn rfc3339_formats_utc_seconds() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(951_827_696), "2000-02-29T12:34:56Z");
+        assert_eq!(rfc3339(1_790_000_000), "2026-09-21T14:13:20Z");
+    }
+
+    #[test]
+    fn cyclonedx_lists_components_with_source_and_license() {
+        let bom = cyclonedx(&components(), "2026-01-01T00:00:00Z");
+        assert_eq!(bom["bomFormat"], "CycloneDX");
+        let a = &bom["components"][0];
+        assert_eq!(a["name"], "a");
+        assert_eq!(a["version"], "0123abcd");
+        assert_eq!(a["purl"], "pkg:github/owner/a.nvim@0123abcd");
+        assert_eq!(a["licenses"][0]["license"]["id"], "MIT");
+        assert_eq!(
+            a["externalReferences"][0]["url"],
+            "https://github.com/owner/a.nvim"
+        );
+        let b = &bom["components"][1];
+        assert_eq!(b["name"], "b.nvim");
+        assert!(b.get("purl").is_none());
+        assert!(b.get("licenses").is_none());
+    }
+
+    #[test]
+    fn spdx_describes_every_package() {
+        let doc = spdx(&components(), "2026-01-01T00:00:00Z");
+        assert_eq!(doc["spdxVersion"], "SPDX-2.3");
+        assert_eq!(doc["packages"][0]["licenseDeclared"], "MIT");
+        assert_eq!(doc["packages"][1]["licenseDeclared"], NOASSERTION);
+        assert_eq!(
+            doc["packages"][1]["downloadLocation"],
+            "git+https://gitlab.com/group/b.nvim@4567ef01"
+        );
+        assert_eq!(doc["relationships"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            doc["relationships"][1]["relatedSpdxElement"],
+            "SPDXRef-Package-2"
+        );
+    }
+}
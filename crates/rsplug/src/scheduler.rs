@@ -5,6 +5,8 @@
 //! together prevents CLI booleans and phase-specific inputs from leaking into
 //! the publication code.
 
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use super::*;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -15,10 +17,20 @@ pub(crate) enum RunMode {
     InstallAndUpdate,
     Locked,
     LockedInstall,
+    /// `--offline`: lock の rev をキャッシュだけから復元する（ネットワークに触れない）。
+    Offline,
+    OfflineInstall,
 }
 
 impl RunMode {
-    pub(crate) fn from_flags(install: bool, update: bool, locked: bool) -> Self {
+    pub(crate) fn from_flags(install: bool, update: bool, locked: bool, offline: bool) -> Self {
+        if offline {
+            return if install {
+                Self::OfflineInstall
+            } else {
+                Self::Offline
+            };
+        }
         if locked {
             return if install {
                 Self::LockedInstall
@@ -37,7 +49,7 @@ impl RunMode {
     pub(crate) fn install(self) -> bool {
         matches!(
             self,
-            Self::Install | Self::InstallAndUpdate | Self::LockedInstall
+            Self::Install | Self::InstallAndUpdate | Self::LockedInstall | Self::OfflineInstall
         )
     }
 
@@ -45,24 +57,107 @@ impl RunMode {
         matches!(self, Self::Update | Self::InstallAndUpdate)
     }
 
+    /// lock の rev に固定するか。`--offline` も lock から復元するので含む。
     pub(crate) fn locked(self) -> bool {
-        matches!(self, Self::Locked | Self::LockedInstall)
+        matches!(
+            self,
+            Self::Locked | Self::LockedInstall | Self::Offline | Self::OfflineInstall
+        )
+    }
+
+    pub(crate) fn offline(self) -> bool {
+        matches!(self, Self::Offline | Self::OfflineInstall)
     }
 
     pub(crate) fn allows_remote(self) -> bool {
         !self.locked() && (self.install() || self.update())
     }
+
+    /// 位置引数で選ばれなかったプラグインのモード。取得も更新もせず、lock への固定だけ引き継ぐ。
+    pub(crate) fn untouched(self) -> Self {
+        match self {
+            Self::Offline | Self::OfflineInstall => Self::Offline,
+            Self::Locked | Self::LockedInstall => Self::Locked,
+            _ => Self::Refresh,
+        }
+    }
+}
+
+/// `install`・`update`・`sync` の位置引数で選んだプラグイン。名前（`name` か repo 名）か
+/// リポジトリ（`owner/repo` や `github.com/owner/repo`）に対するグロブで選ぶ。
+pub(crate) struct PluginSelection {
+    patterns: Vec<(String, wildmatch::WildMatch, AtomicBool)>,
+}
+
+impl PluginSelection {
+    /// パターンが無ければ `None`（すべてのプラグインが対象）。
+    pub(crate) fn new(patterns: &[String]) -> Option<Self> {
+        (!patterns.is_empty()).then(|| Self {
+            patterns: patterns
+                .iter()
+                .map(|pattern| {
+                    let matcher = wildmatch::WildMatch::new(pattern);
+                    (pattern.clone(), matcher, AtomicBool::new(false))
+                })
+                .collect(),
+        })
+    }
+
+    pub(crate) fn matches(&self, plugin: &rsplug::Plugin) -> bool {
+        let canonical = plugin.cache.repo.as_ref().map(|repo| repo.canonical());
+        let names = [
+            plugin.source_name.as_deref(),
+            plugin.cache.repo.as_ref().map(|repo| repo.basename()),
+            canonical.as_deref(),
+            canonical
+                .as_deref()
+                .and_then(|canonical| canonical.split_once('/'))
+                .map(|(_, path)| path),
+        ];
+        let mut matched = false;
+        for (_, matcher, used) in &self.patterns {
+            if names.iter().flatten().any(|name| matcher.matches(name)) {
+                used.store(true, Ordering::Relaxed);
+                matched = true;
+            }
+        }
+        matched
+    }
+
+    /// どのプラグインにも一致しなかったパターン。
+    pub(crate) fn unmatched(&self) -> Vec<String> {
+        self.patterns
+            .iter()
+            .filter(|(_, _, used)| !used.load(Ordering::Relaxed))
+            .map(|(pattern, _, _)| pattern.clone())
+            .collect()
+    }
 }
 
 #[derive(Clone)]
 pub(crate) struct LoadCtx {
     pub(crate) mode: RunMode,
+    /// update でローカル変更のある snapshot を置き去りにすることを許可する（`--force`）。
+    pub(crate) force: bool,
     pub(crate) locked_map: Arc<BTreeMap<String, rsplug::LockedResource>>,
     pub(crate) network: adaptive_semaphore::NetworkLimits,
     pub(crate) breaker: Arc<rsplug::util::github::CircuitBreaker>,
     pub(crate) http_client: reqwest::Client,
     pub(crate) cache_dir: PathBuf,
+    /// `dev = true` プラグインのローカル checkout を探す root（`--dev-path`）。
+    pub(crate) dev_path: PathBuf,
     pub(crate) catalogs: Arc<rsplug::RepoJobRegistry>,
+    /// 位置引数で選んだプラグイン。`None` ならすべてを `mode` で読み込む。
+    pub(crate) select: Option<Arc<PluginSelection>>,
+}
+
+impl LoadCtx {
+    /// 位置引数で選ばれたか（選んでいなければ常に真）。
+    pub(crate) fn selects(&self, plugin: &rsplug::Plugin) -> bool {
+        self.select
+            .as_ref()
+            .is_none_or(|select| select.matches(plugin))
+    }
 }
 
 pub(crate) enum LoadRev {
@@ -77,6 +172,8 @@ fn expand_rev(
     rev: LoadRev,
 ) -> Result<(Option<Arc<str>>, Option<String>), Error> {
     Ok(match rev {
+        // dev は lock に載らないので locked でも lock entry を要求しない。
+        LoadRev::Auto if plugin.cache.dev => (None, None),
         LoadRev::Auto => {
             if let Some(repo) = plugin.cache.repo.as_ref() {
                 let canonical = repo.canonical();
@@ -115,11 +212,21 @@ pub(crate) async fn run_load_early(
     rev: LoadRev,
 ) -> Result<(rsplug::EarlyOutcome, Option<String>), Error> {
     let (locked_rev, repo_canon) = expand_rev(plugin, ctx, rev)?;
+    let _job = rsplug::util::resources::job().await?;
+    // 選ばれなかったプラグインは取得も更新もせず、未インストールでも lock の記録を残す。
+    let (mode, repo_canon) = if ctx.selects(plugin) {
+        (ctx.mode, repo_canon)
+    } else {
+        (ctx.mode.untouched(), None)
+    };
     let early = plugin
         .load_early(
-            ctx.mode.install(),
-            ctx.mode.update(),
+            mode.install(),
+            mode.update(),
+            mode.offline(),
+            ctx.force,
             &ctx.cache_dir,
+            &ctx.dev_path,
             locked_rev.as_deref(),
             &ctx.network,
             &ctx.breaker,
@@ -142,12 +249,20 @@ pub(crate) async fn run_load_late(
     Error,
 > {
     let repo_canon = plugin.cache.repo.as_ref().map(|r| r.canonical());
+    let _job = rsplug::util::resources::job().await?;
+    let selected = ctx.selects(&plugin);
     let result = plugin
-        .load_late(early, &ctx.cache_dir, ctx.mode.update(), &ctx.catalogs)
+        .load_late(
+            early,
+            &ctx.cache_dir,
+            selected && ctx.mode.update(),
+            &ctx.catalogs,
+        )
         .await;
     msg(Message::LoadPluginDone);
     let canon_to_remove =
-        if repo_canon.is_some() && result.is_ok() && result.as_ref().unwrap().is_none() {
+        if selected && repo_canon.is_some() && result.is_ok() && result.as_ref().unwrap().is_none()
+        {
             repo_canon
         } else {
             None
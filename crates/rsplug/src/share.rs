@@ -0,0 +1,322 @@
+//! Shareable plugin sets (`rsplug export` / `rsplug import`).
+//!
+//! `rsplug export` writes every entry of the config files as one TOML config
+//! document, with `extends` resolved, templates dropped, and each `repo`
+//! pinned to the commit recorded in the lockfile. The document is an ordinary
+//! config file, so it can be published anywhere (a gist, a dotfiles
+//! repository) and read back as is. `rsplug import` takes it from a file,
+//! standard input, or an http(s) URL, checks that it parses, writes it to the
+//! given config file, and installs exactly those commits.
+
+use std::{
+    io::Read as _,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use rsplug::plugin::RepoSource;
+
+use super::*;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct ExportArgs {
+    /// Write the document to this file instead of standard output
+    #[arg(long, short)]
+    pub(crate) output: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct ImportArgs {
+    /// Exported document: a file, an http(s) URL (gist pages are read raw), or `-` for standard input
+    pub(crate) source: String,
+    /// Config file to write the imported entries to
+    #[arg(long)]
+    pub(crate) to: PathBuf,
+    /// Replace the config file when it already exists
+    #[arg(long)]
+    pub(crate) replace: bool,
+}
+
+/// export した文書の先頭に置く説明。
+const HEADER: &str = "\
+# Plugin set exported by `rsplug export`. Every `repo` is pinned to a commit.
+# Reproduce it with: rsplug import <this file or its URL> --to <config file>
+";
+
+/// 表のままのエントリの `repo` を lock の commit に固定する。固定すれば `branch` は要らない
+/// ので落とす。固定できたら `true`。
+fn pin_entry(entry: &mut toml::Table, locked: &BTreeMap<String, rsplug::LockedResource>) -> bool {
+    let Some(repo) = entry
+        .get("repo")
+        .or_else(|| entry.get("source"))
+        .and_then(toml::Value::as_str)
+        .and_then(|repo| RepoSource::from_str(repo).ok())
+    else {
+        return false;
+    };
+    let Some(resource) = locked.get(&repo.canonical()) else {
+        return false;
+    };
+    let Ok(pinned) = toml::Value::try_from(repo.with_rev(resource.rev.as_str())) else {
+        return false;
+    };
+    entry.remove("source");
+    entry.remove("branch");
+    entry.insert("repo".to_string(), pinned);
+    true
+}
+
+/// 設定ファイルのエントリを、lock の commit に固定した 1 つの設定ファイルの内容にする。
+/// 共有できないエントリ（`dev`）とキー（`patches`）は警告を返して除く。
+fn export_document(
+    files: Vec<(PathBuf, Vec<toml::Table>)>,
+    locked: &BTreeMap<String, rsplug::LockedResource>,
+) -> Result<(String, Vec<String>), Error> {
+    let mut warnings = Vec::new();
+    let mut plugins = Vec::new();
+    for (path, entries) in files {
+        for mut entry in entries {
+            let label = entry
+                .get("name")
+                .or_else(|| entry.get("repo"))
+                .or_else(|| entry.get("source"))
+                .and_then(toml::Value::as_str)
+                .unwrap_or("(unnamed)")
+                .to_string();
+            if entry.get("dev").and_then(toml::Value::as_bool) == Some(true) {
+                warnings.push(format!(
+                    "{label} ({}): skipped, `dev` checkouts are local",
+                    path.display()
+                ));
+                continue;
+            }
+            if entry.remove("patches").is_some() {
+                warnings.push(format!(
+                    "{label}: `patches` refer to local files and are left out"
+                ));
+            }
+            let has_repo = entry.contains_key("repo") || entry.contains_key("source");
+            if has_repo && !pin_entry(&mut entry, locked) {
+                warnings.push(format!("{label}: not in the lockfile, exported unpinned"));
+            }
+            plugins.push(toml::Value::Table(entry));
+        }
+    }
+    let document = toml::Table::from_iter([("plugins".to_string(), toml::Value::Array(plugins))]);
+    let body = toml::to_string(&document).map_err(std::io::Error::other)?;
+    Ok((format!("{HEADER}\n{body}"), warnings))
+}
+
+/// `rsplug export`: 設定ファイルと lock から共有用の文書を書き出す。
+pub(crate) async fn export(
+    lockfile: &Path,
+    config_files: Vec<String>,
+    args: &ExportArgs,
+) -> Result<(), Error> {
+    let locked = match rsplug::LockFile::read(lockfile).await {
+        Ok(lock) => lock.normalize_keys()?.locked,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let mut walker = ConfigWalker::new(config_files).await?;
+    let mut paths = Vec::new();
+    while let Some(path) = walker.recv().await {
+        paths.push(path?);
+    }
+    paths.sort();
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
+        let input = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|source| Error::ConfigRead {
+                path: path.clone(),
+                source,
+            })?;
+        match rsplug::Config::plugin_tables(&input) {
+            Ok(entries) => files.push((path, entries)),
+            Err(source) => {
+                return Err(Error::Parse {
+                    source: Box::new(source),
+                    path,
+                    input,
+                });
+            }
+        }
+    }
+    let (document, warnings) = export_document(files, &locked)?;
+    for warning in warnings {
+        eprintln!("{} {warning}", style("warning:").yellow().bold());
+    }
+    match &args.output {
+        Some(path) => tokio::fs::write(path, document).await?,
+        None => print!("{document}"),
+    }
+    Ok(())
+}
+
+/// gist のページ URL は、最新版の内容を返す `/raw` に読み替える。
+fn raw_url(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    let is_gist_page = url
+        .strip_prefix("https://gist.github.com/")
+        .is_some_and(|path| path.split('/').count() <= 2);
+    if is_gist_page {
+        format!("{url}/raw")
+    } else {
+        url.to_string()
+    }
+}
+
+/// 取り込む文書を読む。
+async fn read_source(source: &str) -> Result<String, Error> {
+    if source == "-" {
+        return tokio::task::spawn_blocking(|| {
+            let mut input = String::new();
+            std::io::stdin().read_to_string(&mut input).map(|_| input)
+        })
+        .await
+        .map_err(task::panicked("import"))?
+        .map_err(Error::from);
+    }
+    if source.starts_with("https://") || source.starts_with("http://") {
+        let url = raw_url(source);
+        let response = http_client()?
+            .get(&url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        let text = match response {
+            Ok(response) => response.text().await,
+            Err(e) => Err(e),
+        };
+        return text.map_err(|source| Error::Fetch { url, source });
+    }
+    tokio::fs::read_to_string(source)
+        .await
+        .map_err(|e| Error::ConfigRead {
+            path: PathBuf::from(source),
+            source: e,
+        })
+}
+
+/// `rsplug import`: 文書を検査して `args.to` に書き出す。続く install は呼び出し側が行う。
+pub(crate) async fn import(args: &ImportArgs) -> Result<(), Error> {
+    let input = read_source(&args.source).await?;
+    let config = match rsplug::Config::from_toml(&input) {
+        Ok(config) => config,
+        Err(source) => {
+            return Err(Error::Parse {
+                source: Box::new(source),
+                path: PathBuf::from(&args.source),
+                input,
+            });
+        }
+    };
+    if !args.replace && tokio::fs::try_exists(&args.to).await? {
+        return Err(Error::ImportTargetExists {
+            path: args.to.clone(),
+        });
+    }
+    if let Some(dir) = args.to.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    tokio::fs::write(&args.to, &input).await?;
+    eprintln!(
+        "Imported {} plugins to {}",
+        config.plugins.len(),
+        args.to.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMIT: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    fn locked() -> BTreeMap<String, rsplug::LockedResource> {
+        let lock: rsplug::LockFile = serde_json::from_value(serde_json::json!({
+            "version": "1",
+            "locked": { "github.com/owner/a.nvim": { "type": "git", "rev": COMMIT } },
+        }))
+        .unwrap();
+        lock.locked
+    }
+
+    #[test]
+    fn exported_entries_are_pinned_to_the_lockfile() {
+        let entries = rsplug::Config::plugin_tables(
+            r#"
+            [[plugins]]
+            source = "owner/a.nvim"
+            branch = "main"
+            on_cmd = "A"
+
+            [[plugins]]
+            repo = "owner/b.nvim"
+
+            [[plugins]]
+            repo = "owner/c.nvim"
+            dev = true
+            "#,
+        )
+        .unwrap();
+        let (document, warnings) =
+            export_document(vec![(PathBuf::from("plugins.toml"), entries)], &locked()).unwrap();
+        let exported = rsplug::Config::plugin_tables(&document).unwrap();
+        assert_eq!(exported.len(), 2);
+        assert_eq!(
+            exported[0]["repo"].as_str(),
+            Some(format!("owner/a.nvim@{COMMIT}").as_str())
+        );
+        assert!(!exported[0].contains_key("branch"));
+        assert_eq!(exported[0]["on_cmd"].as_str(), Some("A"));
+        assert_eq!(exported[1]["repo"].as_str(), Some("owner/b.nvim"));
+        assert_eq!(warnings.len(), 2, "{warnings:?}");
+        assert!(document.starts_with(HEADER));
+    }
+
+    #[test]
+    fn gist_pages_are_read_raw() {
+        assert_eq!(
+            raw_url("https://gist.github.com/someone/abc123/"),
+            "https://gist.github.com/someone/abc123/raw"
+        );
+        assert_eq!(
+            raw_url("https://gist.githubusercontent.com/someone/abc123/raw/plugins.toml"),
+            "https://gist.githubusercontent.com/someone/abc123/raw/plugins.toml"
+        );
+        assert_eq!(
+            raw_url("https://gist.github.com/someone/abc123/raw"),
+            "https://gist.github.com/someone/abc123/raw"
+        );
+    }
+
+    #[tokio::test]
+    async fn import_does_not_replace_a_config_without_replace() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("shared.toml");
+        std::fs::write(&source, "[[plugins]]\nrepo = 'owner/a.nvim'\n").unwrap();
+        let to = tmp.path().join("nvim/imported.toml");
+        let mut args = ImportArgs {
+            source: source.to_string_lossy().into_owned(),
+            to: to.clone(),
+            replace: false,
+        };
+        import(&args).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&to).unwrap(),
+            "[[plugins]]\nrepo = 'owner/a.nvim'\n"
+        );
+        assert!(matches!(
+            import(&args).await,
+            Err(Error::ImportTargetExists { .. })
+        ));
+        args.replace = true;
+        import(&args).await.unwrap();
+
+        std::fs::write(&source, "[[plugins]]\nrepo = 1\n").unwrap();
+        assert!(matches!(import(&args).await, Err(Error::Parse { .. })));
+    }
+}
@@ -0,0 +1,430 @@
+//! Config file edits performed by CLI subcommands.
+//!
+//! Edits go through `toml_edit` so the user's comments, ordering, and
+//! formatting survive; only the targeted `[[plugins]]` entry is touched. The
+//! resulting file is re-validated by the ordinary parse phase of the run that
+//! follows the edit.
+
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use rsplug::plugin::RepoSource;
+use toml_edit::{Array, ArrayOfTables, DocumentMut, Item, Table, value};
+
+use super::*;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct AddArgs {
+    /// Repository to add: GitHub shorthand or a Git URL, optionally with `@rev`
+    pub(crate) repo: String,
+    /// Config file to append the entry to [default: the first config file]
+    #[arg(long)]
+    pub(crate) file: Option<PathBuf>,
+    /// Load lazily instead of at startup
+    #[arg(long)]
+    pub(crate) opt: bool,
+    /// Lazy-load on this user command (repeatable)
+    #[arg(long = "on-cmd", value_name = "CMD")]
+    pub(crate) on_cmd: Vec<String>,
+    /// Build command argument; repeat for each argument
+    #[arg(long, value_name = "ARG")]
+    pub(crate) build: Vec<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct RemoveArgs {
+    /// Repository to remove: GitHub shorthand or a Git URL
+    pub(crate) repo: String,
+    /// Config file to remove the entry from [default: search all config files]
+    #[arg(long)]
+    pub(crate) file: Option<PathBuf>,
+    /// Also delete the cached repository (sources and snapshots)
+    #[arg(long)]
+    pub(crate) purge: bool,
+}
+
+/// config globs を展開し、編集可能な実ファイルをソート順で返す（stdin の `-` は除く）。
+async fn config_paths(patterns: Vec<String>) -> Result<Vec<PathBuf>, Error> {
+    let patterns = patterns.into_iter().filter(|p| p != "-").collect();
+    let mut walker = ConfigWalker::new(patterns).await?;
+    let mut found = Vec::new();
+    while let Some(path) = walker.recv().await {
+        found.push(path?);
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// config globs から編集対象の既定ファイル（ソート順で最初の実ファイル）を決める。
+pub(crate) async fn first_config_file(patterns: Vec<String>) -> Result<PathBuf, Error> {
+    config_paths(patterns)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(Error::NoConfigFile)
+}
+
+/// 比較用に `path` を解決する。まだ無いファイル（`add --file` の新規作成）は絶対パスにするだけ。
+async fn resolved(path: &Path) -> std::io::Result<PathBuf> {
+    match tokio::fs::canonicalize(path).await {
+        Ok(path) => Ok(path),
+        Err(_) => std::path::absolute(path),
+    }
+}
+
+/// `add`・`remove` の後に同期する config globs を決める。pack は毎回すべての設定から作り直すので、
+/// 編集する `edited`（`--file`）が globs に含まれなければ足す。globs が何も指さないと他の設定の
+/// プラグインを pack から落としてしまうため、既に同期した（lock がある）なら編集前に拒否する。
+pub(crate) async fn synced_config_files(
+    mut patterns: Vec<String>,
+    edited: Option<&Path>,
+    lockfile: &Path,
+) -> Result<Vec<String>, Error> {
+    let paths = config_paths(patterns.clone()).await?;
+    if paths.is_empty() && tokio::fs::try_exists(lockfile).await? {
+        return Err(Error::EditNoConfigFiles);
+    }
+    if let Some(edited) = edited {
+        let edited = resolved(edited).await?;
+        let mut listed = false;
+        for path in &paths {
+            listed |= resolved(path).await? == edited;
+        }
+        if !listed {
+            patterns.push(edited.to_string_lossy().into_owned());
+        }
+    }
+    Ok(patterns)
+}
+
+/// `path` の末尾に `args` の `[[plugins]]` エントリを追記する。ファイルが無ければ作る。
+pub(crate) async fn append_entry(path: &Path, args: &AddArgs) -> Result<(), Error> {
+    let input = match tokio::fs::read_to_string(path).await {
+        Ok(input) => input,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(source) => {
+            return Err(Error::ConfigRead {
+                path: path.to_path_buf(),
+                source,
+            });
+        }
+    };
+    let output = append_to_document(&input, path, args)?;
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, output).await?;
+    Ok(())
+}
+
+fn parse_repo(repo: &str) -> Result<RepoSource, Error> {
+    RepoSource::from_str(repo).map_err(|reason| Error::InvalidRepo {
+        repo: repo.to_string(),
+        reason,
+    })
+}
+
+/// `[[plugins]]` エントリの `repo`（別名 `source`）が `canonical` と同一リポジトリを指すか。
+fn entry_matches(entry: &Table, canonical: &str) -> bool {
+    entry
+        .get("repo")
+        .or_else(|| entry.get("source"))
+        .and_then(Item::as_str)
+        .and_then(|r| RepoSource::from_str(r).ok())
+        .is_some_and(|r| r.canonical() == canonical)
+}
+
+fn append_to_document(input: &str, path: &Path, args: &AddArgs) -> Result<String, Error> {
+    let canonical = parse_repo(&args.repo)?.canonical();
+    let mut doc = DocumentMut::from_str(input).map_err(|source| Error::ConfigEdit {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let plugins = doc
+        .entry("plugins")
+        .or_insert(Item::ArrayOfTables(ArrayOfTables::new()))
+        .as_array_of_tables_mut()
+        .ok_or_else(|| Error::ConfigShape {
+            path: path.to_path_buf(),
+        })?;
+    if plugins.iter().any(|entry| entry_matches(entry, &canonical)) {
+        return Err(Error::AlreadyConfigured {
+            canonical,
+            path: path.to_path_buf(),
+        });
+    }
+    plugins.push(render_entry(args));
+    Ok(doc.to_string())
+}
+
+/// 最小のエントリ。trigger も `--opt` も無ければ試用しやすいよう `start = true` にする。
+fn render_entry(args: &AddArgs) -> Table {
+    let mut entry = Table::new();
+    entry["repo"] = value(args.repo.as_str());
+    if !args.opt && args.on_cmd.is_empty() {
+        entry["start"] = value(true);
+    }
+    match args.on_cmd.as_slice() {
+        [] => {}
+        [cmd] => entry["on_cmd"] = value(cmd.as_str()),
+        cmds => entry["on_cmd"] = value(cmds.iter().map(String::as_str).collect::<Array>()),
+    }
+    if !args.build.is_empty() {
+        entry["build"] = value(args.build.iter().map(String::as_str).collect::<Array>());
+    }
+    entry
+}
+
+/// `remove` で書き換えた設定ファイル。後続の同期が失敗したら [`rollback`](Self::rollback) で戻す。
+pub(crate) struct RemovedEntry {
+    pub(crate) path: PathBuf,
+    pub(crate) repo: RepoSource,
+    original: String,
+}
+
+impl RemovedEntry {
+    pub(crate) async fn rollback(&self) -> std::io::Result<()> {
+        tokio::fs::write(&self.path, &self.original).await
+    }
+}
+
+/// `remove` の同期前の lock と公開中の世代。同期が失敗したら設定と一緒に戻す。
+pub(crate) struct Checkpoint {
+    lockfile: PathBuf,
+    lock: Option<Vec<u8>>,
+    publication: rsplug::pack_plan::Publication,
+}
+
+impl Checkpoint {
+    pub(crate) async fn capture(packpath: &Path, lockfile: &Path) -> std::io::Result<Self> {
+        let lock = match tokio::fs::read(lockfile).await {
+            Ok(lock) => Some(lock),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            lockfile: lockfile.to_path_buf(),
+            lock,
+            publication: rsplug::pack_plan::Publication::capture(packpath).await?,
+        })
+    }
+
+    pub(crate) async fn restore(self) -> std::io::Result<()> {
+        match &self.lock {
+            Some(lock) => tokio::fs::write(&self.lockfile, lock).await?,
+            None => match tokio::fs::remove_file(&self.lockfile).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            },
+        }
+        self.publication.restore().await
+    }
+}
+
+/// `args.repo` のエントリを設定ファイルから 1 件だけ削除して書き戻す。
+/// 該当が複数（複数ファイル、または同一ファイル内の重複）なら編集せず、候補ファイルを報告する。
+pub(crate) async fn remove_entry(
+    args: &RemoveArgs,
+    patterns: Vec<String>,
+) -> Result<RemovedEntry, Error> {
+    let repo = parse_repo(&args.repo)?;
+    let canonical = repo.canonical();
+    let candidates = match &args.file {
+        Some(file) => vec![file.clone()],
+        None => config_paths(patterns).await?,
+    };
+    let mut found = Vec::new();
+    let mut ambiguous = false;
+    for path in candidates {
+        let input = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|source| Error::ConfigRead {
+                path: path.clone(),
+                source,
+            })?;
+        match remove_from_document(&input, &path, &canonical)? {
+            Removal::NotFound => {}
+            Removal::Removed(output) => found.push((path, input, output)),
+            Removal::Duplicated => {
+                ambiguous = true;
+                found.push((path, input, String::new()));
+            }
+        }
+    }
+    if ambiguous || found.len() > 1 {
+        return Err(Error::AmbiguousEntry {
+            canonical,
+            paths: found.into_iter().map(|(path, ..)| path).collect(),
+        });
+    }
+    let Some((path, original, output)) = found.pop() else {
+        return Err(Error::NotConfigured { canonical });
+    };
+    tokio::fs::write(&path, output).await?;
+    Ok(RemovedEntry {
+        path,
+        repo,
+        original,
+    })
+}
+
+enum Removal {
+    NotFound,
+    Removed(String),
+    Duplicated,
+}
+
+fn remove_from_document(input: &str, path: &Path, canonical: &str) -> Result<Removal, Error> {
+    let mut doc = DocumentMut::from_str(input).map_err(|source| Error::ConfigEdit {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let Some(plugins) = doc.get_mut("plugins") else {
+        return Ok(Removal::NotFound);
+    };
+    let plugins = plugins
+        .as_array_of_tables_mut()
+        .ok_or_else(|| Error::ConfigShape {
+            path: path.to_path_buf(),
+        })?;
+    let positions = (0..plugins.len())
+        .filter(|&i| plugins.get(i).is_some_and(|e| entry_matches(e, canonical)))
+        .collect::<Vec<_>>();
+    match positions.as_slice() {
+        [] => Ok(Removal::NotFound),
+        &[index] => {
+            plugins.remove(index);
+            Ok(Removal::Removed(doc.to_string()))
+        }
+        _ => Ok(Removal::Duplicated),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add_args(repo: &str) -> AddArgs {
+        AddArgs {
+            repo: repo.to_string(),
+            file: None,
+            opt: false,
+            on_cmd: Vec::new(),
+            build: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn append_keeps_existing_content_and_adds_start_entry() {
+        let input = "# my plugins\n[[plugins]]\nrepo = \"owner/first\" # pinned\n";
+        let output =
+            append_to_document(input, Path::new("a.toml"), &add_args("owner/second")).unwrap();
+
+        assert!(output.starts_with(input), "got:\n{output}");
+        let config: rsplug::Config = toml::from_str(&output).unwrap();
+        let plugins = rsplug::Plugin::new(config).unwrap().collect::<Vec<_>>();
+        assert_eq!(plugins.len(), 2);
+        assert!(output.contains("repo = \"owner/second\"\nstart = true"));
+    }
+
+    #[test]
+    fn append_renders_lazy_triggers_and_build() {
+        let args = AddArgs {
+            on_cmd: vec!["Foo".into(), "Bar".into()],
+            build: vec!["make".into(), "all".into()],
+            ..add_args("owner/plugin@v1.0.0")
+        };
+        let output = append_to_document("", Path::new("a.toml"), &args).unwrap();
+
+        assert!(!output.contains("start"), "got:\n{output}");
+        assert!(
+            output.contains("on_cmd = [\"Foo\", \"Bar\"]"),
+            "got:\n{output}"
+        );
+        assert!(
+            output.contains("build = [\"make\", \"all\"]"),
+            "got:\n{output}"
+        );
+        toml::from_str::<rsplug::Config>(&output).unwrap();
+    }
+
+    #[test]
+    fn append_rejects_duplicate_canonical_repository() {
+        let input = "[[plugins]]\nrepo = \"https://github.com/Owner/plugin.git\"\n";
+        let err = append_to_document(input, Path::new("a.toml"), &add_args("Owner/plugin@main"))
+            .unwrap_err();
+
+        assert!(matches!(err, Error::AlreadyConfigured { .. }), "got {err}");
+    }
+
+    #[test]
+    fn append_rejects_invalid_repository() {
+        let err = append_to_document("", Path::new("a.toml"), &add_args("not a repo")).unwrap_err();
+
+        assert!(matches!(err, Error::InvalidRepo { .. }), "got {err}");
+    }
+
+    #[test]
+    fn remove_drops_only_the_matching_entry() {
+        let input = "# mine\n[[plugins]]\nrepo = \"owner/keep\" # note\n\n[[plugins]]\nrepo = \"https://github.com/owner/gone.git\"\non_cmd = \"Gone\"\n";
+        let Removal::Removed(output) =
+            remove_from_document(input, Path::new("a.toml"), "github.com/owner/gone").unwrap()
+        else {
+            panic!("entry not removed");
+        };
+
+        assert_eq!(
+            output,
+            "# mine\n[[plugins]]\nrepo = \"owner/keep\" # note\n"
+        );
+    }
+
+    #[test]
+    fn remove_reports_duplicates_and_missing_entries() {
+        let input = "[[plugins]]\nrepo = \"owner/a\"\n[[plugins]]\nrepo = \"owner/a@v1\"\n";
+        let path = Path::new("a.toml");
+
+        assert!(matches!(
+            remove_from_document(input, path, "github.com/owner/a").unwrap(),
+            Removal::Duplicated
+        ));
+        assert!(matches!(
+            remove_from_document(input, path, "github.com/owner/b").unwrap(),
+            Removal::NotFound
+        ));
+    }
+
+    #[tokio::test]
+    async fn remove_entry_rejects_matches_in_several_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let a = tmp.path().join("a.toml");
+        let b = tmp.path().join("b.toml");
+        let input = "[[plugins]]\nrepo = \"owner/plugin\"\n";
+        std::fs::write(&a, input).unwrap();
+        std::fs::write(&b, input).unwrap();
+        let args = RemoveArgs {
+            repo: "owner/plugin".into(),
+            file: None,
+            purge: false,
+        };
+
+        let pattern = tmp.path().join("*.toml").to_string_lossy().into_owned();
+        let err = remove_entry(&args, vec![pattern]).await.err().unwrap();
+        assert!(
+            matches!(&err, Error::AmbiguousEntry { paths, .. } if paths == &[a.clone(), b]),
+            "got {err}"
+        );
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), input);
+
+        let args = RemoveArgs {
+            file: Some(a.clone()),
+            ..args
+        };
+        let removed = remove_entry(&args, Vec::new()).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "");
+        removed.rollback().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), input);
+    }
+}
@@ -0,0 +1,318 @@
+//! Installed revisions of the configured repositories (`rsplug status`).
+//!
+//! Every repository plugin is listed with the revision recorded in the
+//! lockfile. Plugins that set `upstream`, typically forks configured with
+//! `source`, also show the upstream head resolved with ls-remote and how far
+//! the installed revision is ahead of and behind it. The counts are computed
+//! in the fork's repository cache. Caches are depth-1 clones, so when the
+//! upstream commit or the common ancestor is missing the history is deepened
+//! on demand, up to `--max-depth` commits, before giving up with
+//! "unknown (shallow)".
+
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+
+use git2::Oid;
+use rsplug::plugin::RepoSource;
+use rsplug::util::{
+    git::{self, AheadBehind},
+    github,
+};
+use tokio::task::JoinSet;
+
+use super::*;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct StatusArgs {
+    /// Do not contact remotes; show installed revisions only
+    #[arg(long)]
+    pub(crate) offline: bool,
+    /// Deepen shallow caches up to this many commits to count ahead/behind (0 never fetches)
+    #[arg(long, default_value_t = 1024)]
+    pub(crate) max_depth: u32,
+}
+
+/// 最初に深くする履歴の深さ。足りなければ 4 倍ずつ `--max-depth` まで深くする。
+const INITIAL_DEPTH: u32 = 32;
+
+/// 設定中の repo プラグイン 1 件。
+struct Row {
+    name: String,
+    repo: RepoSource,
+    upstream: Option<RepoSource>,
+}
+
+/// fork と upstream の差。
+#[derive(Debug, PartialEq, Eq)]
+enum Divergence {
+    /// installed 側だけにある commit 数と upstream 側だけにある commit 数。
+    Counts { ahead: usize, behind: usize },
+    /// 差を数えられない理由。
+    Unknown(&'static str),
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Divergence::Counts {
+                ahead: 0,
+                behind: 0,
+            } => write!(f, "up to date"),
+            Divergence::Counts { ahead, behind } => write!(f, "{ahead} ahead, {behind} behind"),
+            Divergence::Unknown(reason) => write!(f, "unknown ({reason})"),
+        }
+    }
+}
+
+/// shallow な cache を深くするための取得元。`--offline` では使わない。
+struct Deepen<'a> {
+    fork: &'a RepoSource,
+    upstream: &'a RepoSource,
+    max_depth: u32,
+}
+
+/// fork の `source.git` で `installed` と `upstream` の差を数える。commit や共通祖先が
+/// 足りなければ `deepen` の範囲で履歴を深くして数え直し、それでも駄目なら `Unknown`。
+async fn divergence(
+    source_git: &Path,
+    installed: Oid,
+    upstream: Oid,
+    deepen: Option<Deepen<'_>>,
+) -> Divergence {
+    let Ok(repo) = git::open_source(source_git).await else {
+        return Divergence::Unknown("not cached");
+    };
+    let mut depth = 0;
+    loop {
+        let reason = match repo.ahead_behind(installed, upstream).await {
+            Ok(AheadBehind::Counts { ahead, behind }) => {
+                return Divergence::Counts { ahead, behind };
+            }
+            Ok(AheadBehind::Unrelated) => return Divergence::Unknown("no common history"),
+            Ok(AheadBehind::Missing) => "upstream commit not cached",
+            Ok(AheadBehind::Shallow) => "shallow",
+            Err(_) => return Divergence::Unknown("unreadable cache"),
+        };
+        let Some(deepen) = &deepen else {
+            return Divergence::Unknown(reason);
+        };
+        if depth >= deepen.max_depth {
+            return Divergence::Unknown(reason);
+        }
+        depth = depth
+            .saturating_mul(4)
+            .clamp(INITIAL_DEPTH.min(deepen.max_depth), deepen.max_depth);
+        // 両側を同じ深さで取り直す。upstream の commit はここで初めて fork の cache に入り得る。
+        let fetched = async {
+            repo.deepen(
+                Arc::from(deepen.fork.url()),
+                installed,
+                depth,
+                token(deepen.fork),
+            )
+            .await?;
+            repo.deepen(
+                Arc::from(deepen.upstream.url()),
+                upstream,
+                depth,
+                token(deepen.upstream),
+            )
+            .await
+        }
+        .await;
+        if fetched.is_err() {
+            return Divergence::Unknown(reason);
+        }
+    }
+}
+
+/// GitHub HTTPS のリモートにだけ送る token。
+fn token(repo: &RepoSource) -> Option<Arc<str>> {
+    repo.is_github_https()
+        .then(github::token)
+        .flatten()
+        .map(Arc::<str>::from)
+}
+
+/// upstream の rev（未指定なら HEAD）が指す commit を ls-remote で引く。
+async fn upstream_head(upstream: &RepoSource) -> Result<Oid, rsplug::Error> {
+    git::ls_remote(Arc::from(upstream.url()), upstream.rev(), token(upstream)).await
+}
+
+/// `rsplug status`: 各 repo の installed rev と、`upstream` があればその差を表示する。
+pub(crate) async fn print_status(
+    repo_cache_dir: &Path,
+    lockfile: &Path,
+    config_files: Vec<String>,
+    args: &StatusArgs,
+) -> Result<(), Error> {
+    let mut rows = Vec::new();
+    for (_, config) in read_configs(config_files).await? {
+        for plugin in &config.plugins {
+            let Some(repo) = plugin.cache.repo.clone() else {
+                continue;
+            };
+            rows.push(Row {
+                name: plugin.dep_name().unwrap_or(repo.basename()).to_string(),
+                repo,
+                upstream: plugin.cache.upstream.clone(),
+            });
+        }
+    }
+    let locked = match rsplug::LockFile::read(lockfile).await {
+        Ok(lock) => lock.normalize_keys()?.locked,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    // upstream の ls-remote は互いに独立なので並行に問い合わせる。
+    let mut heads = JoinSet::new();
+    if !args.offline {
+        for (index, row) in rows.iter().enumerate() {
+            if let Some(upstream) = row.upstream.clone() {
+                heads.spawn(async move { (index, upstream_head(&upstream).await) });
+            }
+        }
+    }
+    let mut upstream_heads = BTreeMap::new();
+    while let Some(joined) = heads.join_next().await {
+        let (index, head) = joined.map_err(task::panicked("ls-remote"))?;
+        upstream_heads.insert(index, head);
+    }
+
+    let width = rows.iter().map(|row| row.name.len()).max().unwrap_or(0);
+    for (index, row) in rows.iter().enumerate() {
+        let installed = locked
+            .get(&row.repo.canonical())
+            .and_then(|entry| Oid::from_str(&entry.rev).ok());
+        let rev = installed.map_or_else(|| "not installed".to_string(), short);
+        println!("{:width$}  {rev}", row.name);
+        let Some(upstream) = &row.upstream else {
+            continue;
+        };
+        let label = upstream.canonical();
+        let summary = match upstream_heads.remove(&index) {
+            None => "not checked (--offline)".to_string(),
+            Some(Err(e)) => format!("unreachable ({e})"),
+            Some(Ok(head)) => match installed {
+                None => short(head),
+                Some(installed) => {
+                    let source_git = repo_cache_dir
+                        .join(row.repo.default_cachedir())
+                        .join("source.git");
+                    let deepen = Deepen {
+                        fork: &row.repo,
+                        upstream,
+                        max_depth: args.max_depth,
+                    };
+                    let divergence = divergence(&source_git, installed, head, Some(deepen)).await;
+                    format!("{}: {divergence}", short(head))
+                }
+            },
+        };
+        println!("{:width$}    upstream {label} {summary}", "");
+    }
+    Ok(())
+}
+
+/// 表示用の短い commit hash。
+fn short(oid: Oid) -> String {
+    oid.to_string()[..7].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn counts_commits_on_each_side_of_the_fork() {
+        use std::process::Command;
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = tmp.path().join("source.git");
+        let git = |args: &[&str]| {
+            let out = Command::new("git")
+                .current_dir(&repo)
+                .args(["-c", "user.email=t@t", "-c", "user.name=t"])
+                .args(["-c", "commit.gpgsign=false"])
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(out.status.success(), "git {args:?} failed");
+            String::from_utf8(out.stdout).unwrap().trim().to_string()
+        };
+        std::fs::create_dir_all(&repo).unwrap();
+        git(&["init", "-q", "--bare"]);
+        // 空 tree は git が常に知っている object なので、bare repo でも commit を作れる。
+        let empty = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+        let base = git(&["commit-tree", empty, "-m", "base"]);
+        let fork = git(&["commit-tree", empty, "-p", &base, "-m", "fork"]);
+        let up1 = git(&["commit-tree", empty, "-p", &base, "-m", "up1"]);
+        let up2 = git(&["commit-tree", empty, "-p", &up1, "-m", "up2"]);
+        let oid = |s: &str| Oid::from_str(s).unwrap();
+
+        assert_eq!(
+            divergence(&repo, oid(&fork), oid(&up2), None).await,
+            Divergence::Counts {
+                ahead: 1,
+                behind: 2
+            }
+        );
+        assert_eq!(
+            divergence(&repo, oid(&up2), oid(&up2), None)
+                .await
+                .to_string(),
+            "up to date"
+        );
+        let missing = oid("0123456789012345678901234567890123456789");
+        assert_eq!(
+            divergence(&repo, oid(&fork), missing, None).await,
+            Divergence::Unknown("upstream commit not cached")
+        );
+    }
+
+    #[tokio::test]
+    async fn shallow_cache_without_a_common_ancestor_is_unknown() {
+        use std::process::Command;
+        let tmp = tempfile::tempdir().unwrap();
+        let remote = tmp.path().join("remote");
+        std::fs::create_dir_all(&remote).unwrap();
+        let git = |dir: &Path, args: &[&str]| {
+            let out = Command::new("git")
+                .current_dir(dir)
+                .args(["-c", "user.email=t@t", "-c", "user.name=t"])
+                .args(["-c", "commit.gpgsign=false"])
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(out.status.success(), "git {args:?} failed");
+            String::from_utf8(out.stdout).unwrap().trim().to_string()
+        };
+        git(&remote, &["init", "-q", "-b", "up"]);
+        git(&remote, &["commit", "-q", "--allow-empty", "-m", "base"]);
+        git(&remote, &["checkout", "-q", "-b", "fork"]);
+        git(&remote, &["commit", "-q", "--allow-empty", "-m", "fork"]);
+        git(&remote, &["checkout", "-q", "up"]);
+        git(&remote, &["commit", "-q", "--allow-empty", "-m", "up"]);
+        let fork = Oid::from_str(&git(&remote, &["rev-parse", "fork"])).unwrap();
+        let up = Oid::from_str(&git(&remote, &["rev-parse", "up"])).unwrap();
+        // source.git と同じ depth 1 の cache。両端はあるが共通祖先は無い。
+        let url = format!("file://{}", remote.display());
+        git(
+            tmp.path(),
+            &[
+                "clone",
+                "-q",
+                "--bare",
+                "--depth",
+                "1",
+                "--no-single-branch",
+                &url,
+                "source.git",
+            ],
+        );
+
+        assert_eq!(
+            divergence(&tmp.path().join("source.git"), fork, up, None).await,
+            Divergence::Unknown("shallow")
+        );
+    }
+}
@@ -0,0 +1,514 @@
+//! Static checks of the config files (`rsplug validate`).
+//!
+//! Config files are found and parsed the same way a sync does, then their
+//! patterns are linted without touching the network or the pack. Every
+//! `merge.ignore` and the config-file globs themselves are compiled into a
+//! `CompiledGlob`, whose rule analysis reports rules that are always overridden
+//! by a later rule and excludes that remove nothing. `merge.ignore` is matched
+//! against paths inside each repository, so rules naming a host path are
+//! reported as well. Patch files listed in `patches` must exist.
+//!
+//! When the pack is installed, `on_cmd` and `on_ft` triggers are also checked
+//! against the repository snapshot they load: a command must be defined with
+//! `:command` or `nvim_create_user_command`, and a filetype needs an
+//! `ftplugin`/`indent`/`syntax`/`queries` entry or a `FileType` handler.
+//! Plugins that are not installed yet are skipped.
+
+use std::path::{MAIN_SEPARATOR_STR, Path};
+
+use file_specifier::{FileSpecifier, FileSpecifierRule};
+use walker::{
+    compiled_glob::{CompiledGlob, RuleLint, RuleLintKind},
+    walker::{Walker, WalkerOptions},
+};
+
+use super::*;
+
+/// `merge.ignore` を glob エンジンで解析するときの仮のリポジトリ root。
+const REPO_ROOT: &str = if cfg!(windows) {
+    r"C:\rsplug-repo"
+} else {
+    "/rsplug-repo"
+};
+
+/// root 固定のルールがこれらで始まれば、ホストの絶対パスを書いたものとみなす。
+const HOST_ROOTS: &[&str] = &[
+    "home", "Users", "root", "tmp", "private", "usr", "var", "mnt",
+];
+
+/// 遅延トリガーの定義を探すソースファイルの拡張子。
+const SOURCE_EXTENSIONS: &[&str] = &["vim", "lua"];
+
+/// filetype ごとのファイルを置くランタイムディレクトリ。
+const FILETYPE_DIRS: &[&str] = &[
+    "ftplugin",
+    "indent",
+    "syntax",
+    "queries",
+    "after/ftplugin",
+    "after/indent",
+    "after/syntax",
+    "after/queries",
+];
+
+/// 1 件の指摘。`location` は設定ファイルやプラグインを指す。
+struct Warning {
+    location: String,
+    message: String,
+}
+
+/// リポジトリ内のパスには決してマッチしない、ホストのパスを書いたルールか。
+fn names_host_path(rule: &FileSpecifierRule) -> bool {
+    let mut chars = rule.body.chars();
+    if rule.body.starts_with('~') {
+        return true;
+    }
+    if let (Some(drive), Some(':')) = (chars.next(), chars.next())
+        && drive.is_ascii_alphabetic()
+    {
+        return true;
+    }
+    let mut components = rule.body.split('/');
+    rule.anchored
+        && components
+            .next()
+            .is_some_and(|first| HOST_ROOTS.contains(&first))
+        && components.next().is_some()
+}
+
+/// `FileSpecifier` の 1 ルールを、[`REPO_ROOT`] 配下で同じパスにマッチする glob に直す。
+fn ignore_rule_glob(rule: &FileSpecifierRule) -> String {
+    let body = rule.body.replace('/', MAIN_SEPARATOR_STR);
+    let sep = MAIN_SEPARATOR_STR;
+    let glob = if rule.anchored {
+        format!("{REPO_ROOT}{sep}{body}")
+    } else {
+        format!("{REPO_ROOT}{sep}**{sep}{body}")
+    };
+    // `/` を含まないルールはパスのどの要素にもマッチするので、配下のパスも対象になる。
+    let glob = if rule.directory_only || !rule.path_only {
+        format!("{glob}{sep}**")
+    } else {
+        glob
+    };
+    if rule.negated {
+        format!("!{glob}")
+    } else {
+        glob
+    }
+}
+
+fn describe(lint: &RuleLint, pattern: &str, patterns: &[&str]) -> String {
+    match &lint.kind {
+        RuleLintKind::ShadowedBy { index, .. } => format!(
+            "`{pattern}` never takes effect: the later `{}` decides every path it matches",
+            patterns[*index]
+        ),
+        RuleLintKind::ExcludesNothing => {
+            format!(
+                "`{pattern}` excludes nothing: no earlier pattern includes the paths it matches"
+            )
+        }
+    }
+}
+
+/// `merge.ignore` のルールを解析し、指摘を返す。
+fn lint_ignore(spec: &FileSpecifier) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut lines = Vec::new();
+    let mut globs = Vec::new();
+    for rule in spec.rules() {
+        if names_host_path(rule) {
+            messages.push(format!(
+                "`{}` looks like a host path, but merge.ignore matches paths inside the repository",
+                rule.line
+            ));
+        }
+        // glob として解釈できないルールは解析の対象外にする。
+        if let Ok(glob) = CompiledGlob::new(&ignore_rule_glob(rule)) {
+            lines.push(rule.line.as_str());
+            globs.push(glob);
+        }
+    }
+    if let Ok(merged) = CompiledGlob::merge_many(globs) {
+        for lint in merged.lint() {
+            messages.push(describe(&lint, lines[lint.index], &lines));
+        }
+    }
+    messages
+}
+
+/// 設定ファイルの glob（`--config`）を解析し、指摘を返す。
+/// 標準入力（`-`）と既存ファイルの直接指定は glob ではないので対象外。
+fn lint_config_globs(patterns: &[String]) -> Result<Vec<String>, Error> {
+    let mut sources = Vec::new();
+    let mut globs = Vec::new();
+    for pattern in patterns {
+        if pattern == "-" || Path::new(pattern).is_file() {
+            continue;
+        }
+        globs.push(CompiledGlob::new(pattern)?);
+        sources.push(pattern.as_str());
+    }
+    let Ok(merged) = CompiledGlob::merge_many(globs) else {
+        return Ok(Vec::new());
+    };
+    Ok(merged
+        .lint()
+        .iter()
+        .map(|lint| describe(lint, sources[lint.index], &sources))
+        .collect())
+}
+
+/// インストール済み snapshot のファイル一覧と、Vim script・Lua の本文。
+#[derive(Default)]
+struct RepoContents {
+    /// snapshot root からの相対パス（`/` 区切り）。
+    files: Vec<String>,
+    sources: Vec<String>,
+}
+
+impl RepoContents {
+    /// snapshot を走査する。読めないファイルは飛ばす。
+    async fn read(snapshot: &Path) -> Result<Self, Error> {
+        let mut contents = RepoContents::default();
+        let mut rx = Walker::spawn_with_options(
+            CompiledGlob::new("/**")?,
+            WalkerOptions {
+                files_only: true,
+                roots: vec![snapshot.to_path_buf()],
+                ..Default::default()
+            },
+        );
+        while let Some(event) = rx.recv().await {
+            let Ok(event) = event else {
+                continue;
+            };
+            let Ok(relative) = event.path.strip_prefix(snapshot) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            if relative.starts_with(".git/") {
+                continue;
+            }
+            if event
+                .path
+                .extension()
+                .is_some_and(|ext| SOURCE_EXTENSIONS.iter().any(|known| ext == *known))
+                && let Ok(bytes) = tokio::fs::read(&event.path).await
+            {
+                contents
+                    .sources
+                    .push(String::from_utf8_lossy(&bytes).into_owned());
+            }
+            contents.files.push(relative);
+        }
+        Ok(contents)
+    }
+
+    fn defines_command(&self, name: &str) -> bool {
+        self.sources
+            .iter()
+            .any(|source| defines_command(source, name))
+    }
+
+    fn handles_filetype(&self, filetype: &str) -> bool {
+        self.files.iter().any(|file| {
+            FILETYPE_DIRS.iter().any(|dir| {
+                let Some(rest) = file
+                    .strip_prefix(dir)
+                    .and_then(|rest| rest.strip_prefix('/'))
+                else {
+                    return false;
+                };
+                let Some(rest) = rest.strip_prefix(filetype) else {
+                    return false;
+                };
+                // `<ft>/…`、`<ft>.vim`、`<ft>_extra.lua` の 3 形式。
+                rest.starts_with('/')
+                    || SOURCE_EXTENSIONS
+                        .iter()
+                        .any(|ext| rest.strip_prefix('.') == Some(*ext))
+                    || rest.starts_with('_')
+            })
+        }) || self
+            .sources
+            .iter()
+            .any(|source| handles_filetype(source, filetype))
+    }
+}
+
+/// `:command[!] {attr}... {name}`（`:com` までの省略形を含む）か、
+/// `nvim_create_user_command` と同じファイルに引用されたコマンド名があるか。
+fn defines_command(source: &str, name: &str) -> bool {
+    if source.contains("create_user_command")
+        && (source.contains(format!("\"{name}\"").as_str())
+            || source.contains(format!("'{name}'").as_str()))
+    {
+        return true;
+    }
+    source.lines().any(|line| {
+        let mut words = line.split_whitespace();
+        while let Some(word) = words.next() {
+            // `vim.cmd("command! …")` のように前に何か付いていてもよい。
+            let word = word
+                .rsplit(|c: char| !c.is_ascii_alphabetic() && c != '!')
+                .next()
+                .unwrap_or(word);
+            let word = word.strip_suffix('!').unwrap_or(word);
+            if word.len() >= 3 && "command".starts_with(word) {
+                return words
+                    .find(|word| !word.starts_with('-'))
+                    .is_some_and(|word| word == name);
+            }
+        }
+        false
+    })
+}
+
+/// `FileType` 自動コマンドのパターンに `filetype` があるか、
+/// filetype を扱うファイルに引用された `filetype` があるか。
+fn handles_filetype(source: &str, filetype: &str) -> bool {
+    source.lines().any(|line| {
+        line.contains("FileType")
+            && line
+                .split(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-')))
+                .any(|word| word == filetype)
+    }) || (source.contains("FileType") || source.contains("filetype"))
+        && (source.contains(format!("\"{filetype}\"").as_str())
+            || source.contains(format!("'{filetype}'").as_str()))
+}
+
+/// プラグインの `on_cmd`・`on_ft` が、インストール済み snapshot で定義されているかを調べる。
+/// パターンや複合 filetype は照合できないので対象外。
+fn lint_triggers(
+    contents: &RepoContents,
+    repo: &str,
+    triggers: &[String],
+) -> Vec<(&'static str, String)> {
+    let mut findings = Vec::new();
+    for trigger in triggers {
+        if let Some(name) = trigger.strip_prefix("on_cmd:") {
+            if !contents.defines_command(name) {
+                findings.push((
+                    "on_cmd",
+                    format!("command `{name}` is not defined anywhere in the installed {repo}"),
+                ));
+            }
+        } else if let Some(filetype) = trigger.strip_prefix("on_ft:")
+            && !filetype.contains(['.', '*', '?', '['])
+            && !contents.handles_filetype(filetype)
+        {
+            findings.push((
+                "on_ft",
+                format!(
+                    "the installed {repo} has no ftplugin, indent, syntax, queries, or FileType handler for `{filetype}`"
+                ),
+            ));
+        }
+    }
+    findings
+}
+
+/// `rsplug validate`: 設定ファイルを読み込み、効かないパターンを報告する。
+/// インストール済みなら遅延トリガーも照合する。指摘があれば [`Error::Validation`] で終了する。
+pub(crate) async fn validate(ctx: &AppContext, config_files: Vec<String>) -> Result<(), Error> {
+    let mut warnings: Vec<Warning> = lint_config_globs(&config_files)?
+        .into_iter()
+        .map(|message| Warning {
+            location: "config file patterns".to_string(),
+            message,
+        })
+        .collect();
+
+    let mut walker = ConfigWalker::new(config_files).await?;
+    let mut config_paths = Vec::new();
+    while let Some(path) = walker.recv().await {
+        config_paths.push(path?);
+    }
+    config_paths.sort();
+
+    // 多くのプラグインは既定の ignore を共有するので、同じ内容は 1 度だけ解析する。
+    let mut ignore_lints: HashMap<String, Vec<String>> = HashMap::new();
+    // 未インストールなら遅延トリガーの照合は飛ばす。
+    let snapshots = rsplug::pack_plan::installed_snapshots(ctx.packpath())
+        .await
+        .unwrap_or_default();
+    let mut repo_contents: HashMap<String, RepoContents> = HashMap::new();
+    for path in &config_paths {
+        let input = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|source| Error::ConfigRead {
+                path: path.clone(),
+                source,
+            })?;
+        let config = match from_toml(path, &input) {
+            Ok(config) => config,
+            Err(source) => {
+                return Err(Error::Parse {
+                    source: Box::new(source),
+                    path: path.clone(),
+                    input,
+                });
+            }
+        };
+        for plugin in &config.plugins {
+            let ignore = &plugin.merge.ignore;
+            let messages = ignore_lints
+                .entry(ignore.as_str().to_string())
+                .or_insert_with(|| lint_ignore(ignore));
+            let name = plugin.dep_name().unwrap_or("<unnamed>");
+            warnings.extend(messages.iter().map(|message| Warning {
+                location: format!("{}: {name}: merge.ignore", path.display()),
+                message: message.clone(),
+            }));
+            for patch in &plugin.cache.patches {
+                if !tokio::fs::try_exists(patch).await.unwrap_or(false) {
+                    warnings.push(Warning {
+                        location: format!("{}: {name}: patches", path.display()),
+                        message: format!("patch file {} does not exist", patch.display()),
+                    });
+                }
+            }
+            let triggers = plugin.lazy_type.describe();
+            if !triggers
+                .iter()
+                .any(|trigger| trigger.starts_with("on_cmd:") || trigger.starts_with("on_ft:"))
+            {
+                continue;
+            }
+            let Some(repo) = &plugin.cache.repo else {
+                continue;
+            };
+            let repo = repo.default_cachedir().to_string_lossy().into_owned();
+            let Some(snapshot) = snapshots.get(&repo) else {
+                continue;
+            };
+            if !repo_contents.contains_key(&repo) {
+                let contents = RepoContents::read(snapshot).await?;
+                repo_contents.insert(repo.clone(), contents);
+            }
+            let findings = lint_triggers(&repo_contents[&repo], &repo, &triggers);
+            warnings.extend(findings.into_iter().map(|(field, message)| Warning {
+                location: format!("{}: {name}: {field}", path.display()),
+                message,
+            }));
+        }
+    }
+
+    for warning in &warnings {
+        println!(
+            "{}: {}: {}",
+            style("warning").yellow().bold(),
+            warning.location,
+            warning.message
+        );
+    }
+    if !warnings.is_empty() {
+        return Err(Error::Validation {
+            count: warnings.len(),
+        });
+    }
+    println!(
+        "{} config files checked, no problems found",
+        config_paths.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(spec: &str) -> Vec<String> {
+        lint_ignore(&spec.parse().unwrap())
+    }
+
+    #[test]
+    fn default_ignore_has_no_findings() {
+        assert!(lint(include_str!("../templates/ignore.gitignore")).is_empty());
+        assert!(lint("*.md\n!README.md\n/doc/*.txt\ntests/").is_empty());
+    }
+
+    #[test]
+    fn reports_rules_overridden_by_later_rules() {
+        let messages = lint("doc/help.txt\ndoc/");
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].starts_with("`doc/help.txt` never takes effect"));
+        assert!(messages[0].contains("`doc/`"));
+
+        let messages = lint("!README.md\n*.md");
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].starts_with("`!README.md`"));
+    }
+
+    #[test]
+    fn reports_excludes_without_earlier_includes() {
+        let messages = lint("!README.md");
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].starts_with("`!README.md` excludes nothing"));
+    }
+
+    #[test]
+    fn reports_host_paths() {
+        for spec in ["/home/me/notes", "~/.cache", "C:/Users/me"] {
+            let messages = lint(spec);
+            assert!(
+                messages
+                    .iter()
+                    .any(|message| message.contains("looks like a host path")),
+                "{spec}: {messages:?}"
+            );
+        }
+        assert!(lint("/tmp/").is_empty());
+    }
+
+    #[test]
+    fn commands_are_found_in_vim_script_and_lua() {
+        assert!(defines_command(
+            "command! -nargs=* Foo call foo#run()",
+            "Foo"
+        ));
+        assert!(defines_command("com -bang Foo echo", "Foo"));
+        assert!(defines_command(r#"vim.cmd("command! Foo lua x()")"#, "Foo"));
+        assert!(defines_command(
+            "vim.api.nvim_create_user_command(\n  'Foo',\n  run,\n  {})",
+            "Foo"
+        ));
+        assert!(!defines_command("command! FooBar echo", "Foo"));
+        assert!(!defines_command("local name = 'Foo'", "Foo"));
+    }
+
+    #[test]
+    fn triggers_missing_from_the_snapshot_are_reported() {
+        let contents = RepoContents {
+            files: vec![
+                "ftplugin/rust.vim".to_string(),
+                "after/queries/toml/highlights.scm".to_string(),
+                "syntax/markdown_extra.lua".to_string(),
+                "plugin/x.lua".to_string(),
+            ],
+            sources: vec![
+                "autocmd FileType python,lua setlocal sw=4".to_string(),
+                "vim.api.nvim_create_user_command('Run', run, {})".to_string(),
+            ],
+        };
+        let triggers = [
+            "on_cmd:Run",
+            "on_cmd:Stale",
+            "on_ft:rust",
+            "on_ft:toml",
+            "on_ft:markdown",
+            "on_ft:python",
+            "on_ft:go",
+            "on_ft:*.go",
+            "on_event:BufRead",
+        ]
+        .map(String::from);
+        let findings = lint_triggers(&contents, "github.com/o/r", &triggers);
+        let fields: Vec<_> = findings.iter().map(|(field, _)| *field).collect();
+        assert_eq!(fields, ["on_cmd", "on_ft"]);
+        assert!(findings[0].1.contains("`Stale`"));
+        assert!(findings[1].1.contains("`go`"));
+    }
+}
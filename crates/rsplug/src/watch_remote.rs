@@ -0,0 +1,291 @@
+//! Notifications about upstream changes (`rsplug watch-remote`).
+//!
+//! The head each configured repository's `rev` resolves to is looked up with
+//! ls-remote and compared with the revision in the lockfile. Every plugin that
+//! is behind is reported once per new head: as a line of text, as one JSON
+//! object per line with `--json`, and optionally as a desktop notification
+//! (`notify-send`, or `osascript` on macOS). Without `--interval` the check
+//! runs once, which suits a systemd user timer; with it the command keeps
+//! checking at that interval. The lockfile and config files are read again
+//! for every check, so an `rsplug update` in between is picked up.
+
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use git2::Oid;
+
+use super::*;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct WatchRemoteArgs {
+    /// Check again every this many seconds instead of once
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub(crate) interval: Option<u64>,
+    /// Print one JSON object per line
+    #[arg(long)]
+    pub(crate) json: bool,
+    /// Also show a desktop notification when updates are found
+    #[arg(long)]
+    pub(crate) notify: bool,
+}
+
+/// 1 回の確認で見つかったこと。
+#[derive(Debug, PartialEq, Eq)]
+enum Event {
+    /// lockfile の rev より新しい head がある。
+    Update {
+        name: String,
+        repo: String,
+        installed: Oid,
+        latest: Oid,
+    },
+    /// head を解決できなかった。
+    Error { name: String, error: String },
+}
+
+impl Event {
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Event::Update {
+                name,
+                repo,
+                installed,
+                latest,
+            } => serde_json::json!({
+                "event": "update",
+                "plugin": name,
+                "repo": repo,
+                "installed": installed.to_string(),
+                "latest": latest.to_string(),
+            }),
+            Event::Error { name, error } => serde_json::json!({
+                "event": "error",
+                "plugin": name,
+                "error": error,
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::Update {
+                name,
+                installed,
+                latest,
+                ..
+            } => write!(
+                f,
+                "{name}: update available ({:.7} -> {:.7})",
+                installed.to_string(),
+                latest.to_string()
+            ),
+            Event::Error { name, error } => write!(f, "{name}: could not check ({error})"),
+        }
+    }
+}
+
+/// 設定中の repo プラグインの head を解決し、lockfile の rev と違うものを返す。
+async fn check(lockfile: &Path, config_files: Vec<String>) -> Result<Vec<Event>, Error> {
+    let locked = match rsplug::LockFile::read(lockfile).await {
+        Ok(lock) => lock.normalize_keys()?.locked,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => return Err(e.into()),
+    };
+    // canonical → (プラグイン名, lockfile の rev)。lockfile にない repo は確認しない。
+    let mut plugins = HashMap::new();
+    let heads = resolve::ls_remote_heads(config_files, |plugin, repo| {
+        let canonical = repo.canonical();
+        if plugins.contains_key(&canonical) {
+            return false;
+        }
+        let Some(installed) = locked
+            .get(&canonical)
+            .and_then(|entry| Oid::from_str(&entry.rev).ok())
+        else {
+            return false;
+        };
+        let name = plugin.dep_name().unwrap_or(repo.basename()).to_string();
+        plugins.insert(canonical, (name, installed));
+        true
+    })
+    .await?;
+    let mut events = Vec::new();
+    for (canonical, _, latest) in heads {
+        let (name, installed) = plugins[&canonical].clone();
+        match latest {
+            Ok(latest) if latest == installed => {}
+            Ok(latest) => events.push(Event::Update {
+                name,
+                repo: canonical,
+                installed,
+                latest,
+            }),
+            Err(e) => events.push(Event::Error {
+                name,
+                error: e.to_string(),
+            }),
+        }
+    }
+    events.sort_by(|a, b| event_name(a).cmp(event_name(b)));
+    Ok(events)
+}
+
+fn event_name(event: &Event) -> &str {
+    match event {
+        Event::Update { name, .. } | Event::Error { name, .. } => name,
+    }
+}
+
+/// 前回までに知らせた head（repo → head）と比べ、新しく知らせる更新だけを残す。
+/// 追いついた repo は忘れ、次に遅れたときにまた知らせる。
+fn unreported(events: Vec<Event>, reported: &mut HashMap<String, Oid>) -> Vec<Event> {
+    let behind: HashSet<&str> = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::Update { repo, .. } => Some(repo.as_str()),
+            Event::Error { .. } => None,
+        })
+        .collect();
+    let errored = events
+        .iter()
+        .any(|event| matches!(event, Event::Error { .. }));
+    // 確認できなかった repo の記録は残す（エラーが直ったときに二重に知らせない）。
+    if !errored {
+        reported.retain(|repo, _| behind.contains(repo.as_str()));
+    }
+    events
+        .into_iter()
+        .filter(|event| match event {
+            Event::Update { repo, latest, .. } => {
+                reported.insert(repo.clone(), *latest) != Some(*latest)
+            }
+            Event::Error { .. } => true,
+        })
+        .collect()
+}
+
+/// デスクトップ通知を出す。失敗しても確認は続ける。
+async fn notify(updates: &[&Event]) {
+    let title = match updates.len() {
+        1 => "rsplug: 1 plugin update available".to_string(),
+        count => format!("rsplug: {count} plugin updates available"),
+    };
+    let body = updates
+        .iter()
+        .map(|event| event_name(event))
+        .collect::<Vec<_>>()
+        .join(", ");
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        // AppleScript の文字列は `"` と `\` をエスケープする。
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut command = tokio::process::Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification {} with title {}",
+            quote(&body),
+            quote(&title)
+        ));
+        command
+    };
+    #[cfg(not(target_os = "macos"))]
+    let mut command = {
+        let mut command = tokio::process::Command::new("notify-send");
+        command.arg("--app-name=rsplug").arg(&title).arg(&body);
+        command
+    };
+    let status = command.stdin(std::process::Stdio::null()).status().await;
+    if !status.is_ok_and(|status| status.success()) {
+        eprintln!(
+            "{} could not show a desktop notification",
+            style("warning:").yellow().bold()
+        );
+    }
+}
+
+/// `rsplug watch-remote`: upstream の更新を確認して知らせる。`--interval` があれば繰り返す。
+pub(crate) async fn watch_remote(
+    lockfile: &Path,
+    config_files: Vec<String>,
+    args: &WatchRemoteArgs,
+) -> Result<(), Error> {
+    let mut reported = HashMap::new();
+    loop {
+        let events = unreported(check(lockfile, config_files.clone()).await?, &mut reported);
+        for event in &events {
+            if args.json {
+                println!("{}", event.to_json());
+            } else {
+                println!("{event}");
+            }
+        }
+        let updates: Vec<&Event> = events
+            .iter()
+            .filter(|event| matches!(event, Event::Update { .. }))
+            .collect();
+        if args.notify && !updates.is_empty() {
+            notify(&updates).await;
+        }
+        let Some(interval) = args.interval else {
+            return Ok(());
+        };
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oid(digit: char) -> Oid {
+        Oid::from_str(&digit.to_string().repeat(40)).unwrap()
+    }
+
+    fn update(repo: &str, latest: char) -> Event {
+        Event::Update {
+            name: repo.into(),
+            repo: repo.into(),
+            installed: oid('1'),
+            latest: oid(latest),
+        }
+    }
+
+    #[test]
+    fn each_new_head_is_reported_once() {
+        let mut reported = HashMap::new();
+        assert_eq!(
+            unreported(vec![update("a", '2'), update("b", '2')], &mut reported),
+            [update("a", '2'), update("b", '2')]
+        );
+        // 同じ head は知らせない。新しい head は知らせる。
+        assert_eq!(
+            unreported(vec![update("a", '2'), update("b", '3')], &mut reported),
+            [update("b", '3')]
+        );
+        // 追いついた `a` が再び遅れたら、同じ head でも知らせる。
+        assert!(unreported(vec![update("b", '3')], &mut reported).is_empty());
+        assert_eq!(
+            unreported(vec![update("a", '2'), update("b", '3')], &mut reported),
+            [update("a", '2')]
+        );
+    }
+
+    #[test]
+    fn events_render_as_text_and_json() {
+        let event = update("foo.nvim", '2');
+        assert_eq!(
+            event.to_string(),
+            "foo.nvim: update available (1111111 -> 2222222)"
+        );
+        assert_eq!(
+            event.to_json(),
+            serde_json::json!({
+                "event": "update",
+                "plugin": "foo.nvim",
+                "repo": "foo.nvim",
+                "installed": "1".repeat(40),
+                "latest": "2".repeat(40),
+            })
+        );
+    }
+}
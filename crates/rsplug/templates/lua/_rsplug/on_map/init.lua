@@ -1,3 +1,4 @@
+-- rsplug-template: 2
 --- mode() 準拠文字列に従いマップするモード文字を返す。
 ---@param mode string
 ---@return string[]
@@ -77,6 +78,12 @@ local M = {}
 -- 到達可能モードのうち未 setup のもの。plugin/on_map.stpl が設定する。
 M.pending_modes = {}
 
+---lhs を先読み中の入力より前に差し戻す。typed 扱いにしないのは、マクロの記録中に
+---lhs が 2 回記録されないようにするため（記録されるのは利用者が打った分だけ）。
+local function feed(replay)
+	vim.api.nvim_feedkeys(replay, 'im', true)
+end
+
 ---pattern 関連の追跡を一括クリアする（pattern_modes / pattern_ids / 当該 pattern を含む id_patterns エントリ）。
 local function remove_pattern(pattern)
 	for _, id in ipairs(pattern_ids[pattern] or {}) do
@@ -107,6 +114,17 @@ function M.retire()
 	pcall(vim.api.nvim_del_augroup_by_name, 'rsplug.runtime.on_map')
 end
 
+---`_rsplug.reset`: 残っている遅延マッピングを削除し、以降の setup を止める。
+function M.reset()
+	for pattern, modes in pairs(pattern_modes) do
+		for _, mode in ipairs(modes) do
+			pcall(vim.keymap.del, mode, pattern, {})
+		end
+		remove_pattern(pattern)
+	end
+	M.pending_modes = {}
+end
+
 ---@param mode string
 function M.setup(mode)
 	for _, mode_char in ipairs(parse_mode(mode)) do
@@ -148,6 +166,9 @@ function M.setup(mode)
 				end
 
 				local replay = vim.api.nvim_replace_termcodes(pattern, true, false, true)
+				-- expr マッピングにして '' を返すのは、lhs の前に入力されたカウント・レジスタ指定・
+				-- 待機中のオペレータ・Visual 選択を消費せずに残すため。replay した lhs は
+				-- それらをそのまま引き継いで packadd 後の本物のマッピングに届く。
 				vim.keymap.set(mode_char, pattern, function()
 					-- Get all plugin IDs for this pattern
 					local all_ids = pattern_ids[pattern] or ids
@@ -155,7 +176,7 @@ function M.setup(mode)
 					-- If plugin is already loaded, delete only this mapping and feed keys
 					if all_loaded(all_ids) then
 						pcall(vim.keymap.del, mode_char, pattern, {})
-						vim.api.nvim_feedkeys(replay, 'imt', true)
+						feed(replay)
 						return ''
 					end
 
@@ -180,10 +201,10 @@ function M.setup(mode)
 
 					-- Load all plugins that registered this pattern
 					for _, id in ipairs(all_ids) do
-						rsplug.packadd(id)
+						rsplug.packadd(id, nil, 'on_map:' .. mode_char .. ':' .. pattern)
 					end
 
-					vim.api.nvim_feedkeys(replay, 'imt', true)
+					feed(replay)
 					return ''
 				end, { expr = true, silent = true })
 
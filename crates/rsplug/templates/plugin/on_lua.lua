@@ -1,4 +1,5 @@
 -- Auto generated by rsplug
+-- rsplug-template: 2
 local state = require '_rsplug/on_lua'
 local rsplug = require '_rsplug'
 
@@ -43,7 +44,7 @@ searcher = function(mod_name)
 	in_progress[mod_root] = true
 	local ok, err = pcall(function()
 		for _, id in ipairs(state.luam2pkgid[mod_root] or {}) do
-			rsplug.packadd(id)
+			rsplug.packadd(id, nil, 'require:' .. mod_root)
 		end
 	end)
 	-- 成功・エラー双方でガードを解除し、元のエラーを再送する。
@@ -66,6 +67,15 @@ searcher = function(mod_name)
 end
 
 table.insert(package.loaders, 1, searcher)
+-- `_rsplug.reset`: 満足していない root が残っていても searcher を外す。
+rsplug.on_reset(function()
+	for i = #package.loaders, 1, -1 do
+		if package.loaders[i] == searcher then
+			table.remove(package.loaders, i)
+		end
+	end
+	state.on_packadd = nil
+end)
 
 -- L1: central on_loaded から呼ばれる。pkgid2luam[id] のみを調べて reconcile する。
 -- core の packadd 成功末尾から retire_all(id) 経由でこの関数が呼ばれる。
@@ -20,7 +20,11 @@ fn run_rsplug(home: &Path, lockfile: &Path, config: &Path, mode: Option<&str>) {
     if let Some(mode) = mode {
         command.arg(mode);
     }
-    let output = command.arg(config).output().expect("rsplug must run");
+    let output = command
+        .arg("--config")
+        .arg(config)
+        .output()
+        .expect("rsplug must run");
     assert!(
         output.status.success(),
         "rsplug {:?} failed:\nstdout={}\nstderr={}",
@@ -67,14 +71,14 @@ fn isolated_local_git_install_refresh_and_locked_are_deterministic() {
     let repo = format!("file://{}", bare.display());
     fs::write(&config, format!("[[plugins]]\nrepo = {:?}\n", repo)).unwrap();
 
-    run_rsplug(&home, &lockfile, &config, Some("--install"));
+    run_rsplug(&home, &lockfile, &config, Some("install"));
     let app = home.join(".cache/rsplug");
     let init = app.join("init.lua");
     assert!(init.is_file(), "cold install must publish init.lua");
     assert!(lockfile.is_file(), "cold install must write the lockfile");
     let first_mtime = fs::metadata(&init).unwrap().modified().unwrap();
 
-    run_rsplug(&home, &lockfile, &config, Some("--install"));
+    run_rsplug(&home, &lockfile, &config, Some("install"));
     assert_eq!(
         fs::metadata(&init).unwrap().modified().unwrap(),
         first_mtime,
@@ -105,11 +109,11 @@ fn isolated_local_git_install_refresh_and_locked_are_deterministic() {
     git(&work, &["add", "."]);
     git(&work, &["commit", "-m", "small update"]);
     git(&work, &["push", "origin", "main"]);
-    run_rsplug(&home, &lockfile, &config, Some("--update"));
+    run_rsplug(&home, &lockfile, &config, Some("update"));
     let updated_mtime = fs::metadata(&init).unwrap().modified().unwrap();
     assert_ne!(updated_mtime, first_mtime, "changed snapshot must publish");
 
-    run_rsplug(&home, &lockfile, &config, Some("--update"));
+    run_rsplug(&home, &lockfile, &config, Some("update"));
     assert_eq!(
         fs::metadata(&init).unwrap().modified().unwrap(),
         updated_mtime,
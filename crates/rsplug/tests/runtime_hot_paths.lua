@@ -363,6 +363,61 @@ scenarios.map_special_key_replay = function()
 	return nil
 end
 
+-- R5: replay した lhs がカウント・レジスタ・待機中のオペレータ・Visual モードを引き継ぐ。
+-- マクロの記録には利用者が打った lhs だけが残る。
+scenarios.map_replay_keeps_pending_state = function()
+	boot()
+	scratch_buf()
+	vim.cmd 'stopinsert'
+	local on_map = require '_rsplug/on_map'
+	on_map.setup 'n'
+	on_map.setup 'no'
+	on_map.setup 'v'
+	local function press(keys)
+		vim.api.nvim_feedkeys(vim.api.nvim_replace_termcodes(keys, true, true, true), 'xt', false)
+	end
+	local function settled(name)
+		return vim.wait(300, function()
+			return vim.g[name] ~= nil
+		end, 10)
+	end
+
+	press 'qq2"azLq'
+	if not settled 'pending_n' then
+		return 'zL did not reach the plugin mapping'
+	end
+	if not vim.deep_equal(vim.g.pending_n, { 2, 'a' }) then
+		return 'count/register lost in normal mode: ' .. vim.inspect(vim.g.pending_n)
+	end
+	if vim.fn.getreg 'q' ~= '2"azL' then
+		return 'macro recorded the lhs twice: ' .. vim.inspect(vim.fn.getreg 'q')
+	end
+
+	vim.api.nvim_buf_set_lines(0, 0, -1, false, { 'abc def ghi' })
+	vim.api.nvim_win_set_cursor(0, { 1, 0 })
+	press '"bd2zK'
+	if not vim.wait(300, function()
+		return vim.api.nvim_get_current_line() ~= 'abc def ghi'
+	end, 10) then
+		return 'd2zK did not delete'
+	end
+	if vim.api.nvim_get_current_line() ~= 'def ghi' or vim.fn.getreg 'b' ~= 'abc ' then
+		return ('operator lost: line=%s reg_b=%s'):format(
+			vim.inspect(vim.api.nvim_get_current_line()),
+			vim.inspect(vim.fn.getreg 'b')
+		)
+	end
+
+	press 'v3zV<Esc>'
+	if not settled 'pending_x' then
+		return 'zV did not reach the plugin mapping'
+	end
+	if not vim.deep_equal(vim.g.pending_x, { 'v', 3 }) then
+		return 'visual mode/count lost: ' .. vim.inspect(vim.g.pending_x)
+	end
+	return check_expect()
+end
+
 -- Validation gate: 10,000 件の無関係 require でも pending 状態は成長しない。
 scenarios.lua_10k_unrelated_no_state_growth = function()
 	boot()
@@ -643,6 +698,39 @@ scenarios.duplicate_maps = function()
 	return check_expect()
 end
 
+-- reset() 後に init.lua を読み直しても autocmd・stub は重複せず、トリガーが再び働く。
+scenarios.reset_then_resource = function()
+	boot()
+	local function count(group)
+		local ok, items = pcall(vim.api.nvim_get_autocmds, { group = group })
+		return ok and #items or 0
+	end
+	local events = count 'rsplug.runtime.on_event'
+	if events == 0 then
+		return 'no on_event autocmds before reset'
+	end
+	require('_rsplug').reset()
+	if vim.g.rsplug_loaded then
+		return 'reset kept g:rsplug_loaded'
+	end
+	if count 'rsplug.runtime.on_event' ~= 0 then
+		return 'reset kept the on_event autocmds'
+	end
+	if vim.fn.exists ':RsResetCmd' ~= 0 then
+		return 'reset kept the on_cmd stub'
+	end
+	if package.loaded['_rsplug'] ~= nil then
+		return 'reset kept the runtime module'
+	end
+	boot()
+	if count 'rsplug.runtime.on_event' ~= events then
+		return ('on_event autocmds after re-source: %d, expected %d'):format(count 'rsplug.runtime.on_event', events)
+	end
+	vim.api.nvim_exec_autocmds('User', { pattern = 'RsResetEv', modeline = false })
+	vim.cmd 'RsResetCmd'
+	return check_expect()
+end
+
 -- L1: ユーザパッケージの opt id を1つ取り出す（control 生成 id を除外）。
 -- 1ユーザパッケージのフィクスチャで、control パッケージ以外の opt ディレクトリを返す。
 local function user_opt_id()
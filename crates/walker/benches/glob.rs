@@ -0,0 +1,79 @@
+//! Glob compilation and matching.
+//!
+//! `match` drives the same per-segment state advancement the walker performs
+//! for every directory entry, so it tracks the matcher's hot path without a
+//! file system in the loop.
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use walker::compiled_glob::CompiledGlob;
+
+/// Rule sets sized like the config globs and `merge.ignore` lists of small to
+/// large setups.
+const RULE_COUNTS: &[usize] = &[8, 64, 256];
+
+fn patterns(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|i| match i % 4 {
+            0 => format!("/bench/repos/plugin{i}/**/*.lua"),
+            1 => format!("/bench/repos/plugin{i}/plugin/*"),
+            2 => format!("!/bench/repos/plugin{}/**/test/", i - 2),
+            _ => format!("/bench/repos/plugin{i}/doc/?*.txt"),
+        })
+        .collect()
+}
+
+fn paths() -> Vec<String> {
+    let mut paths = Vec::new();
+    for plugin in 0..256 {
+        for dir in ["lua/mod", "lua/mod/test", "plugin", "doc", "after/ftplugin"] {
+            for file in ["init.lua", "util.lua", "main.vim", "tags.txt"] {
+                paths.push(format!("/bench/repos/plugin{plugin}/{dir}/{file}"));
+            }
+        }
+    }
+    paths
+}
+
+fn compile(patterns: &[String]) -> CompiledGlob {
+    CompiledGlob::merge_many(
+        patterns
+            .iter()
+            .map(|pattern| CompiledGlob::new(pattern).expect("glob must parse")),
+    )
+    .expect("globs must merge")
+}
+
+fn compile_globs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compile");
+    for &count in RULE_COUNTS {
+        let patterns = patterns(count);
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &patterns, |b, p| {
+            b.iter(|| compile(black_box(p)))
+        });
+    }
+    group.finish();
+}
+
+fn match_paths(c: &mut Criterion) {
+    let paths = paths();
+    let mut group = c.benchmark_group("match");
+    group.throughput(Throughput::Elements(paths.len() as u64));
+    for &count in RULE_COUNTS {
+        let glob = compile(&patterns(count));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &glob, |b, glob| {
+            b.iter(|| {
+                paths
+                    .iter()
+                    .filter(|path| glob.r#match(black_box(path.as_ref())))
+                    .count()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, compile_globs, match_paths);
+criterion_main!(benches);
@@ -0,0 +1,91 @@
+//! Walking a synthetic plugin tree.
+//!
+//! The tree is created once under the temporary directory and removed when the
+//! benchmarks finish. Build with `--features bench-persistent-workers` to
+//! measure walks on the reused worker pool.
+
+use std::fs;
+use std::path::Path;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use walker::compiled_glob::CompiledGlob;
+use walker::walker::{Walker, WalkerOptions};
+
+const PLUGINS: usize = 64;
+
+/// Creates `PLUGINS` repositories shaped like Neovim plugins and returns the
+/// number of files.
+fn synthetic_tree(root: &Path) -> usize {
+    let mut files = 0;
+    for plugin in 0..PLUGINS {
+        let repo = root.join(format!("plugin{plugin}"));
+        for dir in [
+            "lua/mod/sub",
+            "lua/mod/test",
+            "plugin",
+            "doc",
+            "after/ftplugin",
+        ] {
+            fs::create_dir_all(repo.join(dir)).expect("create tree");
+            for file in ["init.lua", "util.lua", "main.vim", "tags.txt"] {
+                fs::write(repo.join(dir).join(file), b"").expect("write file");
+                files += 1;
+            }
+        }
+    }
+    files
+}
+
+fn compile(root: &Path, patterns: &[&str]) -> CompiledGlob {
+    CompiledGlob::merge_many(patterns.iter().map(|pattern| {
+        CompiledGlob::new(&format!("{}{}", root.display(), pattern)).expect("glob must parse")
+    }))
+    .expect("globs must merge")
+}
+
+async fn walk(glob: CompiledGlob, options: WalkerOptions) -> usize {
+    let mut rx = Walker::spawn_with_options(glob, options);
+    let mut count = 0;
+    while let Some(msg) = rx.recv().await {
+        if msg.is_ok() {
+            count += 1;
+        }
+    }
+    count
+}
+
+fn walk_tree(c: &mut Criterion) {
+    let root = std::env::temp_dir().join(format!("walker-bench-{}", std::process::id()));
+    let files = synthetic_tree(&root);
+    let root = root.canonicalize().expect("canonical root");
+    let runtime = tokio::runtime::Runtime::new().expect("start runtime");
+
+    let cases: &[(&str, &[&str])] = &[
+        ("everything", &["/**"]),
+        ("lua_files", &["/**/*.lua"]),
+        ("excludes", &["/**", "!/*/lua/**/test/", "!/**/doc/"]),
+        ("literal_dirs", &["/*/plugin/*", "/*/after/ftplugin/*.lua"]),
+    ];
+    let mut group = c.benchmark_group("walk");
+    group.throughput(Throughput::Elements(files as u64));
+    for (name, patterns) in cases {
+        let glob = compile(&root, patterns);
+        group.bench_function(*name, |b| {
+            b.iter(|| runtime.block_on(walk(glob.clone(), WalkerOptions::default())));
+        });
+    }
+    let glob = compile(&root, &["/**"]);
+    group.bench_function("everything_adaptive_split", |b| {
+        let options = WalkerOptions {
+            adaptive_split: true,
+            ..WalkerOptions::default()
+        };
+        b.iter(|| runtime.block_on(walk(glob.clone(), options.clone())));
+    });
+    group.finish();
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+criterion_group!(benches, walk_tree);
+criterion_main!(benches);
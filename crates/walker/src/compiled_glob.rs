@@ -5,7 +5,7 @@ use std::ffi::OsStr;
 use std::fmt::Debug;
 use std::io;
 use std::ops::Range;
-use std::path::{MAIN_SEPARATOR, Path, PathBuf};
+use std::path::{Component, MAIN_SEPARATOR, MAIN_SEPARATOR_STR, Path, PathBuf};
 use std::sync::Arc;
 use wildmatch::WildMatch;
 
@@ -54,14 +54,56 @@ struct CompiledRule {
     rule_index: usize,
     is_exclude: bool,
     is_absolute: bool,
+    /// 末尾が区切り文字のパターン（gitignore と同じくディレクトリにだけマッチ）。
+    dir_only: bool,
+    /// ユーザーが渡した元のパターン（`!` を含む）。
+    source: Arc<str>,
     segments: Vec<SegmentMatcher>,
 }
 
+/// [`CompiledGlob::explain`] の結果。どのルールが判定を決めたかを表します。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchExplanation {
+    /// [`CompiledGlob::r#match`] と同じ判定結果。
+    pub matched: bool,
+    /// 判定を決めたルール。どのルールにも該当しなければ `None`。
+    pub rule: Option<DecidingRule>,
+}
+
+/// 判定を決めたルール。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecidingRule {
+    /// マージ後のルール順での位置。
+    pub index: usize,
+    /// 元のパターン文字列。
+    pub pattern: String,
+    pub is_exclude: bool,
+}
+
+/// [`CompiledGlob::lint`] が報告する、判定に効かないルール。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleLint {
+    /// マージ後のルール順での位置。
+    pub index: usize,
+    /// 元のパターン文字列。
+    pub pattern: String,
+    pub kind: RuleLintKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleLintKind {
+    /// このルールに該当するパスは、常に後のルールが判定を上書きする。
+    ShadowedBy { index: usize, pattern: String },
+    /// 除外ルールだが、それより前のルールで含まれるパスを除外しない。
+    ExcludesNothing,
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Default, Clone)]
 struct RuleTerminal {
     rule_index: usize,
     is_exclude: bool,
+    dir_only: bool,
 }
 
 type NodeId = usize;
@@ -82,6 +124,8 @@ struct TrieNode {
     wild_edges_prefix: hashbrown::HashMap<String, Vec<NodeId>>,
     wild_edges_exact1: Vec<NodeId>,
     descend_edge: Option<NodeId>,
+    /// `**` の直後のノード。任意のセグメントを読んでもこのノードに留まる。
+    descends: bool,
     terminals: Vec<RuleTerminal>,
 }
 
@@ -140,6 +184,7 @@ impl GlobTrie {
                         existing
                     } else {
                         let created = self.add_node();
+                        self.nodes[created].descends = true;
                         self.nodes[node].descend_edge = Some(created);
                         created
                     };
@@ -150,7 +195,35 @@ impl GlobTrie {
         self.nodes[node].terminals.push(RuleTerminal {
             rule_index: rule.rule_index,
             is_exclude: rule.is_exclude,
+            dir_only: rule.dir_only,
         });
+        // gitignore と同じく、除外したディレクトリはその中身ごと除外する（`dir/**/*` の末端）。
+        if rule.dir_only && rule.is_exclude {
+            // 固定部分の直後の `**` が 0 セグメントに一致するのは基点そのもの。gitignore が
+            // 基点を対象にしないのと同じく、基点直下は中身として除外しない（`base/*/**/*`）。
+            if let [prefix @ .., SegmentMatcher::Descend] = rule.segments.as_slice()
+                && prefix
+                    .iter()
+                    .all(|segment| matches!(segment, SegmentMatcher::AnyPath(_)))
+            {
+                node = self.insert_wild_edge(node, "*");
+            }
+            let descend = match self.nodes[node].descend_edge {
+                Some(existing) => existing,
+                None => {
+                    let created = self.add_node();
+                    self.nodes[created].descends = true;
+                    self.nodes[node].descend_edge = Some(created);
+                    created
+                }
+            };
+            let contents = self.insert_wild_edge(descend, "*");
+            self.nodes[contents].terminals.push(RuleTerminal {
+                rule_index: rule.rule_index,
+                is_exclude: true,
+                dir_only: false,
+            });
+        }
     }
 
     fn insert_wild_edge(&mut self, node: NodeId, pattern: &str) -> NodeId {
@@ -219,12 +292,16 @@ pub struct CompiledGlob {
     trie: GlobTrie,
     epsilon_closures: Vec<Vec<usize>>,
     node_can_scan: Vec<bool>,
+    /// ディレクトリに対して判定を決める末端 `(rule_index, include)`。
     node_best_terminal: Vec<Option<(usize, bool)>>,
+    /// ディレクトリ以外に対する末端。ディレクトリ専用ルールを除きます。
+    node_best_file_terminal: Vec<Option<(usize, bool)>>,
 }
 
 impl CompiledGlob {
     /// 文字列をパースしてCompiledGlobを生成します。
     pub fn new(pattern: &str) -> io::Result<Self> {
+        let source = Arc::<str>::from(pattern);
         if pattern.is_empty() {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
@@ -240,7 +317,9 @@ impl CompiledGlob {
             ));
         }
 
-        let parsed = Path::new(pattern_body).parse_dot()?;
+        // gitignore と同様、末尾の区切り文字はディレクトリ専用を意味する。
+        let dir_only = pattern_body.len() > 1 && pattern_body.ends_with(MAIN_SEPARATOR);
+        let parsed = parse_dot(Path::new(pattern_body))?;
         let is_absolute = parsed.is_absolute();
         let pattern = parsed.to_str().unwrap().to_string();
         let pattern = Arc::new(pattern);
@@ -256,50 +335,17 @@ impl CompiledGlob {
                 return;
             }
             let seg = &pattern[range.clone()];
-            if let Some(rel_pos) = seg.find("**") {
-                if seg == "**" {
-                    segments.push(SegmentMatcher::Descend);
-                    return;
-                }
-                let abs_pos = range.start + rel_pos;
-                let pre = range.start..abs_pos;
-                let post = (abs_pos + 2)..range.end;
-                if pre.is_empty() && !post.is_empty() {
-                    segments.push(SegmentMatcher::Descend);
-                    let mut tail = String::from("*");
-                    tail.push_str(&pattern[post]);
-                    segments.push(SegmentMatcher::WildMatch {
-                        pattern: tail.clone(),
-                        matcher: WildMatch::new(&tail),
-                    });
-                    return;
-                }
-                if !pre.is_empty() && post.is_empty() {
-                    let mut head = pattern[pre].to_string();
-                    head.push('*');
-                    segments.push(SegmentMatcher::WildMatch {
-                        pattern: head.clone(),
-                        matcher: WildMatch::new(&head),
-                    });
-                    segments.push(SegmentMatcher::Descend);
-                    return;
-                }
-                if !pre.is_empty() && !post.is_empty() {
-                    let mut head = pattern[pre].to_string();
-                    head.push('*');
-                    segments.push(SegmentMatcher::WildMatch {
-                        pattern: head.clone(),
-                        matcher: WildMatch::new(&head),
-                    });
-                    segments.push(SegmentMatcher::Descend);
-                    let mut tail = String::from("*");
-                    tail.push_str(&pattern[post]);
-                    segments.push(SegmentMatcher::WildMatch {
-                        pattern: tail.clone(),
-                        matcher: WildMatch::new(&tail),
-                    });
-                    return;
-                }
+            if seg == "**" {
+                segments.push(SegmentMatcher::Descend);
+                return;
+            }
+            if seg.contains("**") {
+                // gitignore と同じく、セグメント全体でない `**` は `*` と同じ。
+                let collapsed = collapse_stars(seg);
+                segments.push(SegmentMatcher::WildMatch {
+                    matcher: WildMatch::new(&collapsed),
+                    pattern: collapsed,
+                });
                 return;
             }
 
@@ -336,15 +382,35 @@ impl CompiledGlob {
                 SegmentMatcher::AnyPath(PathInner { pathbase, range }),
             );
         }
-        let mut compiled = CompiledGlob {
+        let mut compiled = CompiledGlob::empty();
+        compiled.push_rule(segments, is_exclude, is_absolute, dir_only, source);
+        Ok(compiled)
+    }
+
+    fn empty() -> CompiledGlob {
+        CompiledGlob {
             ordered_rules: Vec::new(),
             trie: GlobTrie::new(),
             epsilon_closures: Vec::new(),
             node_can_scan: Vec::new(),
             node_best_terminal: Vec::new(),
-        };
-        compiled.push_rule(segments, is_exclude, is_absolute);
-        Ok(compiled)
+            node_best_file_terminal: Vec::new(),
+        }
+    }
+
+    /// 既存のルールだけからなる CompiledGlob を作ります。ルール番号は振り直します。
+    fn from_rules(rules: &[CompiledRule]) -> CompiledGlob {
+        let mut compiled = CompiledGlob::empty();
+        for (rule_index, rule) in rules.iter().enumerate() {
+            let rule = CompiledRule {
+                rule_index,
+                ..rule.clone()
+            };
+            compiled.trie.insert_rule(&rule);
+            compiled.ordered_rules.push(rule);
+        }
+        compiled.rebuild_epsilon_closure_cache();
+        compiled
     }
 
     pub fn merge(mut self, other: CompiledGlob) -> CompiledGlob {
@@ -431,6 +497,38 @@ impl CompiledGlob {
         out
     }
 
+    /// [`Self::start_paths`] を `roots` の配下に絞ります。`roots` が空なら絞りません。
+    ///
+    /// 開始パスが root の配下ならそのまま、root が開始パスの配下なら root から辿ります。
+    /// 相対パスの root は作業ディレクトリから解決します。
+    pub(crate) fn start_paths_within(&self, roots: &[PathBuf]) -> Vec<PathBuf> {
+        let starts = self.start_paths();
+        if roots.is_empty() {
+            return starts;
+        }
+        let roots: Vec<PathBuf> = roots
+            .iter()
+            .filter_map(|root| parse_dot(&CWD.join(root)).ok().map(Cow::into_owned))
+            .collect();
+        let mut out = Vec::new();
+        let mut seen = hashbrown::HashSet::new();
+        for start in &starts {
+            for root in &roots {
+                let candidate = if start.starts_with(root) {
+                    start
+                } else if root.starts_with(start) {
+                    root
+                } else {
+                    continue;
+                };
+                if seen.insert(candidate.clone()) {
+                    out.push(candidate.clone());
+                }
+            }
+        }
+        out
+    }
+
     pub(crate) fn advance_states(&self, current: &[usize], part: &str) -> Vec<usize> {
         let mut out = Vec::new();
         self.advance_states_into(current, part, &mut out);
@@ -478,7 +576,7 @@ impl CompiledGlob {
                     push_unique_state(out, &mut overflow_seen, *next_idx);
                 }
             }
-            if node.descend_edge.is_some() {
+            if node.descends {
                 push_unique_state(out, &mut overflow_seen, *node_idx);
             }
         }
@@ -487,8 +585,15 @@ impl CompiledGlob {
         std::mem::swap(out, &mut scratch_b);
     }
 
-    pub(crate) fn is_match_state(&self, current: &[usize]) -> bool {
-        matches!(self.match_decision(current), Some(true))
+    #[allow(dead_code)]
+    pub(crate) fn is_match_state(&self, current: &[usize], is_dir: bool) -> bool {
+        matches!(self.match_decision(current, is_dir), Some(true))
+    }
+
+    /// 判定が「含む」なら、それを決めたルールのマージ後の位置を返します。
+    pub(crate) fn matched_rule(&self, current: &[usize], is_dir: bool) -> Option<usize> {
+        self.deciding_terminal(current, is_dir)
+            .and_then(|(rule_index, include)| include.then_some(rule_index))
     }
 
     #[allow(dead_code)]
@@ -509,11 +614,20 @@ impl CompiledGlob {
             .any(|node_idx| self.node_can_scan.get(*node_idx).copied().unwrap_or(false))
     }
 
-    fn push_rule(&mut self, segments: Vec<SegmentMatcher>, is_exclude: bool, is_absolute: bool) {
+    fn push_rule(
+        &mut self,
+        segments: Vec<SegmentMatcher>,
+        is_exclude: bool,
+        is_absolute: bool,
+        dir_only: bool,
+        source: Arc<str>,
+    ) {
         let rule = CompiledRule {
             rule_index: self.ordered_rules.len(),
             is_exclude,
             is_absolute,
+            dir_only,
+            source,
             segments,
         };
         self.trie.insert_rule(&rule);
@@ -576,6 +690,7 @@ impl CompiledGlob {
         self.epsilon_closures = vec![Vec::new(); node_count];
         self.node_can_scan = vec![false; node_count];
         self.node_best_terminal = vec![None; node_count];
+        self.node_best_file_terminal = vec![None; node_count];
         for node_idx in 0..node_count {
             let mut closure = Vec::new();
             let mut cursor = Some(node_idx);
@@ -588,57 +703,170 @@ impl CompiledGlob {
             self.epsilon_closures[node_idx] = closure;
 
             let node = &self.trie.nodes[node_idx];
-            self.node_can_scan[node_idx] = !node.wild_edges_general.is_empty()
+            self.node_can_scan[node_idx] = !node.literal_edges.is_empty()
+                || !node.wild_edges_general.is_empty()
                 || !node.wild_edges_suffix.is_empty()
                 || !node.wild_edges_prefix.is_empty()
                 || !node.wild_edges_exact1.is_empty()
-                || node.descend_edge.is_some();
-
-            let mut selected: Option<(usize, bool)> = None;
-            for terminal in &node.terminals {
-                if selected
-                    .as_ref()
-                    .is_none_or(|(idx, _)| terminal.rule_index >= *idx)
-                {
-                    selected = Some((terminal.rule_index, !terminal.is_exclude));
+                || node.descends;
+
+            let best = |terminals: &mut dyn Iterator<Item = &RuleTerminal>| {
+                let mut selected: Option<(usize, bool)> = None;
+                for terminal in terminals {
+                    if selected
+                        .as_ref()
+                        .is_none_or(|(idx, _)| terminal.rule_index >= *idx)
+                    {
+                        selected = Some((terminal.rule_index, !terminal.is_exclude));
+                    }
                 }
-            }
-            self.node_best_terminal[node_idx] = selected;
+                selected
+            };
+            self.node_best_terminal[node_idx] = best(&mut node.terminals.iter());
+            self.node_best_file_terminal[node_idx] =
+                best(&mut node.terminals.iter().filter(|terminal| !terminal.dir_only));
         }
     }
 
-    fn match_decision(&self, current: &[usize]) -> Option<bool> {
+    fn match_decision(&self, current: &[usize], is_dir: bool) -> Option<bool> {
+        self.deciding_terminal(current, is_dir)
+            .map(|(_, include)| include)
+    }
+
+    /// 最後にマッチしたルールの `(rule_index, include)` を返します。
+    fn deciding_terminal(&self, current: &[usize], is_dir: bool) -> Option<(usize, bool)> {
+        let terminals = if is_dir {
+            &self.node_best_terminal
+        } else {
+            &self.node_best_file_terminal
+        };
         let expanded = self.expand_epsilon_nodes_borrowed(current);
         let mut selected: Option<(usize, bool)> = None;
         for node_idx in expanded.iter() {
-            if let Some((rule_index, include)) =
-                self.node_best_terminal.get(*node_idx).copied().flatten()
+            if let Some((rule_index, include)) = terminals.get(*node_idx).copied().flatten()
                 && selected.as_ref().is_none_or(|(idx, _)| rule_index >= *idx)
             {
                 selected = Some((rule_index, include));
             }
         }
-        selected.map(|(_, include)| include)
+        selected
     }
 
-    /// 固定文字列がマッチするかどうかを判定します。
-    pub fn r#match(&self, path: &OsStr) -> bool {
-        let normalized = match Path::new(path).parse_dot() {
-            Ok(v) => v,
-            Err(_) => return false,
-        };
-        let normalized = match normalized.to_str() {
-            Some(v) => v,
-            None => return false,
-        };
+    /// 正規化したパスを辿った末尾の状態を返します。途中で状態が尽きたら `None`。
+    fn states_for_match(&self, path: &OsStr) -> Option<Vec<usize>> {
+        let normalized = parse_dot(Path::new(path)).ok()?;
+        let normalized = normalized.to_str()?;
         let mut states = self.initial_states();
         for part in normalized.split(MAIN_SEPARATOR).filter(|s| !s.is_empty()) {
             states = self.advance_states(&states, part);
             if states.is_empty() {
-                return false;
+                return None;
             }
         }
-        self.match_decision(&states).unwrap_or(false)
+        Some(states)
+    }
+
+    /// 固定文字列がマッチするかどうかを判定します。
+    ///
+    /// 末尾が区切り文字のパス（`src/`）はディレクトリとして扱い、ディレクトリ専用ルールの対象になります。
+    pub fn r#match(&self, path: &OsStr) -> bool {
+        self.states_for_match(path)
+            .and_then(|states| self.match_decision(&states, names_directory(path)))
+            .unwrap_or(false)
+    }
+
+    /// [`Self::r#match`] の判定を決めたルールを返します。
+    ///
+    /// 複数のルールに該当する場合は後勝ちで、最後に該当したルールが返ります。
+    pub fn explain(&self, path: &OsStr) -> MatchExplanation {
+        let decided = self
+            .states_for_match(path)
+            .and_then(|states| self.deciding_terminal(&states, names_directory(path)));
+        let Some((index, include)) = decided else {
+            return MatchExplanation {
+                matched: false,
+                rule: None,
+            };
+        };
+        let rule = &self.ordered_rules[index];
+        MatchExplanation {
+            matched: include,
+            rule: Some(DecidingRule {
+                index,
+                pattern: rule.source.to_string(),
+                is_exclude: rule.is_exclude,
+            }),
+        }
+    }
+
+    /// 判定に効かないルールを検出します。
+    ///
+    /// 各ルールから代表パスを組み立てて [`Self::explain`] で判定し、どの代表パスでも後の
+    /// ルールが判定を決めるものと、それより前のルールで含まれないパスしか除外しない除外ルールを
+    /// 報告します。代表パスによる推定なので、部分的にしか重ならないルールは報告されないことがあります。
+    pub fn lint(&self) -> Vec<RuleLint> {
+        let alone: Vec<CompiledGlob> = self
+            .ordered_rules
+            .iter()
+            .map(|rule| CompiledGlob::from_rules(std::slice::from_ref(rule)))
+            .collect();
+        let samples: Vec<Vec<String>> = self
+            .ordered_rules
+            .iter()
+            .zip(&alone)
+            .map(|(rule, alone)| {
+                LINT_SAMPLE_FILLS
+                    .iter()
+                    .map(|(fill, descend)| rule.sample_path(fill, *descend))
+                    .filter(|sample| alone.explain(OsStr::new(sample)).rule.is_some())
+                    .collect()
+            })
+            .collect();
+
+        let mut lints = Vec::new();
+        for (index, rule) in self.ordered_rules.iter().enumerate() {
+            if samples[index].is_empty() {
+                continue;
+            }
+
+            let mut deciders = samples[index]
+                .iter()
+                .map(|sample| self.explain(OsStr::new(sample)).rule);
+            if let Some(Some(first)) = deciders.next()
+                && first.index > index
+                && deciders.all(|decider| decider.is_some_and(|decider| decider.index > index))
+            {
+                lints.push(RuleLint {
+                    index,
+                    pattern: rule.source.to_string(),
+                    kind: RuleLintKind::ShadowedBy {
+                        index: first.index,
+                        pattern: first.pattern,
+                    },
+                });
+                continue;
+            }
+
+            if rule.is_exclude {
+                // 除外ルールの代表パスが前で含まれるか、前の包含ルールの代表パスを除外するなら効いている。
+                let earlier = CompiledGlob::from_rules(&self.ordered_rules[..index]);
+                let removes_own = samples[index]
+                    .iter()
+                    .any(|sample| earlier.r#match(OsStr::new(sample)));
+                let removes_earlier = (0..index)
+                    .filter(|earlier_index| !self.ordered_rules[*earlier_index].is_exclude)
+                    .flat_map(|earlier_index| &samples[earlier_index])
+                    .any(|sample| alone[index].explain(OsStr::new(sample)).rule.is_some());
+                if !removes_own && !removes_earlier {
+                    lints.push(RuleLint {
+                        index,
+                        pattern: rule.source.to_string(),
+                        kind: RuleLintKind::ExcludesNothing,
+                    });
+                }
+            }
+        }
+        lints
     }
 
     #[allow(dead_code)]
@@ -664,6 +892,81 @@ impl CompiledGlob {
 
 const INLINE_STATE_DEDUP_LIMIT: usize = 16;
 
+/// [`CompiledGlob::lint`] の代表パスの作り方 `(ワイルドカードの埋め字, ** を 1 段とするか)`。
+/// 埋め字を変えた複数のパスで判定し、たまたま後のルールにも合う埋め方による誤検出を避けます。
+const LINT_SAMPLE_FILLS: [(&str, bool); 2] = [("x", false), ("rsplug-lint", true)];
+
+impl CompiledRule {
+    /// このルールに該当するはずの代表パスを組み立てます。
+    ///
+    /// `*` は `fill`、`?` は 1 文字で埋め、`**` は `descend` なら `fill` というディレクトリ 1 段、
+    /// そうでなければ 0 段とします。ディレクトリ専用ルールでは末尾に区切り文字を付けます。
+    fn sample_path(&self, fill: &str, descend: bool) -> String {
+        let mut parts: Vec<Cow<'_, str>> = Vec::with_capacity(self.segments.len());
+        for segment in &self.segments {
+            match segment {
+                SegmentMatcher::AnyPath(inner) => parts.push(Cow::Borrowed(inner.as_str())),
+                SegmentMatcher::WildMatch { pattern, .. } => {
+                    parts.push(Cow::Owned(pattern.replace('*', fill).replace('?', "x")))
+                }
+                SegmentMatcher::Descend if descend => parts.push(Cow::Borrowed(fill)),
+                SegmentMatcher::Descend => {}
+            }
+        }
+        let mut sample = parts.join(MAIN_SEPARATOR_STR);
+        if !cfg!(windows) && !sample.starts_with(MAIN_SEPARATOR) {
+            sample.insert(0, MAIN_SEPARATOR);
+        }
+        if self.dir_only {
+            sample.push(MAIN_SEPARATOR);
+        }
+        sample
+    }
+}
+
+/// 連続する `*` を 1 個にまとめます。
+fn collapse_stars(seg: &str) -> String {
+    let mut out = String::with_capacity(seg.len());
+    for ch in seg.chars() {
+        if ch != '*' || !out.ends_with('*') {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+/// `.` と `..` を取り除きます。
+///
+/// `a/..` のように `..` が相対パスの成分を使い切ると `ParseDot` は panic するため、
+/// その場合は `./a/..` として作業ディレクトリから辿ります。
+fn parse_dot(path: &Path) -> io::Result<Cow<'_, Path>> {
+    let mut depth = 0usize;
+    let mut escapes = false;
+    if matches!(path.components().next(), Some(Component::Normal(_))) {
+        for component in path.components() {
+            match component {
+                Component::Normal(_) => depth += 1,
+                Component::ParentDir => {
+                    escapes |= depth == 0;
+                    depth = depth.saturating_sub(1);
+                }
+                _ => {}
+            }
+        }
+        escapes |= depth == 0;
+    }
+    if escapes {
+        let parsed = Path::new(".").join(path).parse_dot()?.into_owned();
+        return Ok(Cow::Owned(parsed));
+    }
+    path.parse_dot()
+}
+
+/// 末尾が区切り文字ならディレクトリを指すパスとみなします。
+fn names_directory(path: &OsStr) -> bool {
+    path.to_string_lossy().ends_with(MAIN_SEPARATOR)
+}
+
 fn push_unique_state(
     out: &mut Vec<usize>,
     overflow_seen: &mut Option<HashSet<usize>>,
@@ -724,24 +1027,26 @@ fn classify_wild_edge(pattern: &str) -> WildEdgeKind {
 
 #[cfg(test)]
 mod tests {
-    use super::{CompiledGlob, SegmentMatcher};
+    use super::{
+        CompiledGlob, DecidingRule, MatchExplanation, RuleLint, RuleLintKind, SegmentMatcher,
+    };
     use path_dedot::CWD;
     use std::io;
     use std::path::Path;
 
     #[test]
-    fn expands_leading_double_star_in_segment() {
+    fn leading_double_star_in_segment_stays_in_the_segment() {
         let glob = CompiledGlob::new("/tmp/**.rs").expect("glob must parse");
         assert!(glob.r#match("/tmp/main.rs".as_ref()));
-        assert!(glob.r#match("/tmp/src/lib.rs".as_ref()));
-        assert!(!glob.r#match("/tmp/src/lib.ts".as_ref()));
+        assert!(!glob.r#match("/tmp/src/lib.rs".as_ref()));
+        assert!(!glob.r#match("/tmp/lib.ts".as_ref()));
     }
 
     #[test]
-    fn expands_trailing_double_star_in_segment() {
+    fn trailing_double_star_in_segment_stays_in_the_segment() {
         let glob = CompiledGlob::new("/tmp/tag-**").expect("glob must parse");
         assert!(glob.r#match("/tmp/tag-a".as_ref()));
-        assert!(glob.r#match("/tmp/tag-a/b".as_ref()));
+        assert!(!glob.r#match("/tmp/tag-a/b".as_ref()));
         assert!(!glob.r#match("/tmp/taga".as_ref()));
     }
 
@@ -786,6 +1091,19 @@ mod tests {
         assert!(!glob.r#match(Path::new("/target/file.txt").as_os_str()));
     }
 
+    #[test]
+    fn relative_paths_whose_parent_dirs_cancel_out_resolve_from_cwd() {
+        let glob = CompiledGlob::new("a/..").expect("glob must parse");
+        assert!(glob.r#match(CWD.as_os_str()));
+        assert!(glob.r#match("b/..".as_ref()));
+
+        let glob = CompiledGlob::new("x/../../*").expect("glob must parse");
+        if let Some(parent) = CWD.parent() {
+            assert!(glob.r#match(parent.join("sibling").as_os_str()));
+        }
+        assert!(!glob.r#match("x/../..".as_ref()));
+    }
+
     #[test]
     fn merge_many_or_union_matches() {
         let one = CompiledGlob::new("/tmp/**/*.rs").expect("glob must parse");
@@ -850,6 +1168,145 @@ mod tests {
         assert!(!merged.r#match("/tmp/a/ignore.txt".as_ref()));
     }
 
+    #[test]
+    fn explain_reports_last_matching_rule() {
+        let include = CompiledGlob::new("/tmp/**/*.txt").expect("glob must parse");
+        let exclude = CompiledGlob::new("!/tmp/**/ignore.txt").expect("glob must parse");
+        let merged = CompiledGlob::merge_many(vec![include, exclude]).expect("must merge");
+
+        assert_eq!(
+            merged.explain("/tmp/a/ignore.txt".as_ref()),
+            MatchExplanation {
+                matched: false,
+                rule: Some(DecidingRule {
+                    index: 1,
+                    pattern: "!/tmp/**/ignore.txt".to_string(),
+                    is_exclude: true,
+                }),
+            }
+        );
+        let keep = merged.explain("/tmp/a/keep.txt".as_ref());
+        assert!(keep.matched);
+        assert_eq!(keep.rule.map(|rule| rule.index), Some(0));
+        assert_eq!(
+            merged.explain("/tmp/a/readme.md".as_ref()),
+            MatchExplanation {
+                matched: false,
+                rule: None,
+            }
+        );
+        for path in ["/tmp/a/ignore.txt", "/tmp/a/keep.txt", "/var/x.txt"] {
+            assert_eq!(
+                merged.explain(path.as_ref()).matched,
+                merged.r#match(path.as_ref())
+            );
+        }
+    }
+
+    #[test]
+    fn lint_reports_shadowed_rules_and_empty_excludes() {
+        let lint = |patterns: &[&str]| {
+            let globs = patterns
+                .iter()
+                .map(|pattern| CompiledGlob::new(pattern).expect("glob must parse"));
+            CompiledGlob::merge_many(globs).expect("must merge").lint()
+        };
+
+        assert_eq!(
+            lint(&["/tmp/a/foo.txt", "/tmp/**/*.txt"]),
+            vec![RuleLint {
+                index: 0,
+                pattern: "/tmp/a/foo.txt".to_string(),
+                kind: RuleLintKind::ShadowedBy {
+                    index: 1,
+                    pattern: "/tmp/**/*.txt".to_string(),
+                },
+            }]
+        );
+        assert_eq!(
+            lint(&["/tmp/**/*.txt", "!/tmp/**"])
+                .into_iter()
+                .map(|lint| lint.index)
+                .collect::<Vec<_>>(),
+            vec![0]
+        );
+        assert_eq!(
+            lint(&["/tmp/**/*.txt", "!/var/**"]),
+            vec![RuleLint {
+                index: 1,
+                pattern: "!/var/**".to_string(),
+                kind: RuleLintKind::ExcludesNothing,
+            }]
+        );
+        assert!(lint(&["/tmp/a*", "/tmp/*x"]).is_empty());
+        assert!(lint(&["/tmp/**/*.txt", "!/tmp/**/ignore.txt"]).is_empty());
+        // ディレクトリ専用ルールはファイルに対する判定を上書きしない。
+        assert_eq!(lint(&["/tmp/src/", "/tmp/src"]).len(), 1);
+        assert!(lint(&["/tmp/src", "/tmp/src/"]).is_empty());
+    }
+
+    #[test]
+    fn trailing_separator_matches_directories_only() {
+        let glob = CompiledGlob::new("/tmp/src/**/").expect("glob must parse");
+        assert!(glob.r#match("/tmp/src/a/".as_ref()));
+        assert!(glob.r#match("/tmp/src/a/b/".as_ref()));
+        assert!(!glob.r#match("/tmp/src/a".as_ref()));
+        assert!(!glob.r#match("/tmp/src/a/main.rs".as_ref()));
+
+        let states = glob.states_for_path(Path::new("/tmp/src/a"));
+        assert!(glob.is_match_state(&states, true));
+        assert!(!glob.is_match_state(&states, false));
+    }
+
+    #[test]
+    fn directory_only_exclude_removes_the_contents() {
+        let include = CompiledGlob::new("/tmp/**").expect("glob must parse");
+        let exclude = CompiledGlob::new("!/tmp/**/build/").expect("glob must parse");
+        let merged = CompiledGlob::merge_many(vec![include, exclude]).expect("must merge");
+        assert!(!merged.r#match("/tmp/a/build/".as_ref()));
+        assert!(!merged.r#match("/tmp/a/build/out.o".as_ref()));
+        assert!(!merged.r#match("/tmp/a/build/sub/".as_ref()));
+        assert!(!merged.r#match("/tmp/a/build/sub/out.o".as_ref()));
+        // `build` という名前のファイルはディレクトリ専用ルールの対象外。
+        assert!(merged.r#match("/tmp/a/build".as_ref()));
+        assert!(merged.r#match("/tmp/a/src/main.rs".as_ref()));
+        assert_eq!(
+            merged.explain("/tmp/a/build/out.o".as_ref()).rule,
+            Some(DecidingRule {
+                index: 1,
+                pattern: "!/tmp/**/build/".to_string(),
+                is_exclude: true,
+            })
+        );
+    }
+
+    #[test]
+    fn directory_only_exclude_of_every_directory_keeps_files_in_the_base() {
+        let include = CompiledGlob::new("/tmp/**").expect("glob must parse");
+        let exclude = CompiledGlob::new("!/tmp/**/").expect("glob must parse");
+        let merged = CompiledGlob::merge_many(vec![include, exclude]).expect("must merge");
+        assert!(merged.r#match("/tmp/file".as_ref()));
+        assert!(!merged.r#match("/tmp/a/".as_ref()));
+        assert!(!merged.r#match("/tmp/a/file".as_ref()));
+    }
+
+    #[test]
+    fn double_star_inside_a_segment_is_a_single_star() {
+        let glob = CompiledGlob::new("/tmp/a/**b/**").expect("glob must parse");
+        assert!(glob.r#match("/tmp/a/xb/file".as_ref()));
+        assert!(glob.r#match("/tmp/a/b/c/file".as_ref()));
+        assert!(!glob.r#match("/tmp/a/x/y/b/file".as_ref()));
+
+        let glob = CompiledGlob::new("/tmp/**test**").expect("glob must parse");
+        assert!(glob.r#match("/tmp/unit_test_case".as_ref()));
+        assert!(glob.r#match("/tmp/test".as_ref()));
+        assert!(!glob.r#match("/tmp/x/unit_test_case".as_ref()));
+
+        let glob = CompiledGlob::new("/tmp/a**b**c").expect("glob must parse");
+        assert!(glob.r#match("/tmp/axybzc".as_ref()));
+        assert!(!glob.r#match("/tmp/ax/yb/z/wc".as_ref()));
+    }
+
     #[test]
     fn reject_empty_pattern_and_bare_exclude() {
         assert!(matches!(
@@ -864,18 +1321,18 @@ mod tests {
 
     #[test]
     fn start_paths_include_static_prefix_of_include_rules() {
-        let glob = CompiledGlob::new("/tmp/root/**.rs").expect("glob must parse");
+        let glob = CompiledGlob::new("/tmp/root/**/*.rs").expect("glob must parse");
         let starts = glob.start_paths();
         assert!(starts.iter().any(|p| p == Path::new("/tmp/root")));
     }
 
     #[test]
     fn states_for_path_keeps_descend_capability() {
-        let glob = CompiledGlob::new("/tmp/root/**.rs").expect("glob must parse");
+        let glob = CompiledGlob::new("/tmp/root/**/*.rs").expect("glob must parse");
         let states = glob.states_for_path(Path::new("/tmp/root"));
         assert!(!states.is_empty());
         let leaf_states = glob.advance_states(&states, "main.rs");
-        assert!(glob.is_match_state(&leaf_states));
+        assert!(glob.is_match_state(&leaf_states, false));
     }
 
     #[test]
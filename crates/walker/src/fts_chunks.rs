@@ -0,0 +1,184 @@
+//! Chunked, backpressured driving of blocking fts traversals.
+//!
+//! `fts_read` is a blocking call and its handle cannot be shared, so a traversal is wrapped as a
+//! [`ChunkSource`] that advances a bounded number of reads per call. [`ChunkedReader`] runs each
+//! chunk on `spawn_blocking` and only starts the next one after the previous output has been
+//! accepted by the channel, so a slow consumer parks an async task instead of a blocking thread.
+
+use std::io;
+use tokio::sync::mpsc;
+
+/// Whether a [`ChunkSource`] has more entries to read.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Step {
+    Continue,
+    Done,
+}
+
+/// Blocking traversal state that can be advanced a bounded number of reads at a time.
+pub(crate) trait ChunkSource: Send + 'static {
+    type Item: Send + 'static;
+
+    /// Performs at most `budget` reads, pushing produced items into `out`.
+    fn fill(&mut self, budget: usize, out: &mut Vec<Self::Item>) -> Step;
+}
+
+pub(crate) struct ChunkedReader<S: ChunkSource> {
+    source: Option<S>,
+    chunk_size: usize,
+}
+
+impl<S: ChunkSource> ChunkedReader<S> {
+    pub(crate) fn new(source: S, chunk_size: usize) -> Self {
+        Self {
+            source: Some(source),
+            chunk_size: chunk_size.max(1),
+        }
+    }
+
+    /// Reads the next chunk on the blocking pool. Returns `None` once the source is exhausted.
+    pub(crate) async fn next_chunk(&mut self) -> Option<io::Result<Vec<S::Item>>> {
+        let mut source = self.source.take()?;
+        let budget = self.chunk_size;
+        let joined = tokio::task::spawn_blocking(move || {
+            let mut out = Vec::new();
+            let step = source.fill(budget, &mut out);
+            (source, out, step)
+        })
+        .await;
+
+        match joined {
+            Ok((source, out, step)) => {
+                if step == Step::Continue {
+                    self.source = Some(source);
+                }
+                Some(Ok(out))
+            }
+            Err(err) => Some(Err(io::Error::other(err.to_string()))),
+        }
+    }
+
+    /// Drives the source to completion, waiting for channel capacity between chunks.
+    ///
+    /// Returns `Ok(false)` when the receiver has been dropped.
+    pub(crate) async fn forward(mut self, tx: &mpsc::Sender<S::Item>) -> io::Result<bool> {
+        while let Some(chunk) = self.next_chunk().await {
+            for item in chunk? {
+                if tx.send(item).await.is_err() {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Same as [`Self::forward`] for callers that already run on a dedicated blocking thread.
+    ///
+    /// Must not be called from an async context.
+    pub(crate) fn blocking_forward(self, tx: &mpsc::Sender<S::Item>) -> bool {
+        let Some(mut source) = self.source else {
+            return true;
+        };
+        let mut out = Vec::new();
+        loop {
+            let step = source.fill(self.chunk_size, &mut out);
+            for item in out.drain(..) {
+                if tx.blocking_send(item).is_err() {
+                    return false;
+                }
+            }
+            if step == Step::Done {
+                return true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    struct Counter {
+        next: usize,
+        end: usize,
+        reads: Arc<AtomicUsize>,
+    }
+
+    impl ChunkSource for Counter {
+        type Item = usize;
+
+        fn fill(&mut self, budget: usize, out: &mut Vec<usize>) -> Step {
+            for _ in 0..budget {
+                if self.next == self.end {
+                    return Step::Done;
+                }
+                self.reads.fetch_add(1, Ordering::SeqCst);
+                out.push(self.next);
+                self.next += 1;
+            }
+            Step::Continue
+        }
+    }
+
+    fn counter(end: usize) -> (Counter, Arc<AtomicUsize>) {
+        let reads = Arc::new(AtomicUsize::new(0));
+        let source = Counter {
+            next: 0,
+            end,
+            reads: Arc::clone(&reads),
+        };
+        (source, reads)
+    }
+
+    #[tokio::test]
+    async fn chunks_are_bounded_by_chunk_size() {
+        let (source, _) = counter(10);
+        let mut reader = ChunkedReader::new(source, 4);
+
+        let mut sizes = Vec::new();
+        let mut items = Vec::new();
+        while let Some(chunk) = reader.next_chunk().await {
+            let chunk = chunk.expect("chunk should be read");
+            sizes.push(chunk.len());
+            items.extend(chunk);
+        }
+
+        assert_eq!(sizes, vec![4, 4, 2]);
+        assert_eq!(items, (0..10).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn forward_stops_reading_while_consumer_is_slow() {
+        let (source, reads) = counter(1000);
+        let (tx, mut rx) = mpsc::channel(1);
+        let task = tokio::spawn(async move { ChunkedReader::new(source, 8).forward(&tx).await });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // One chunk is in flight while the channel holds a single item.
+        assert!(reads.load(Ordering::SeqCst) <= 8);
+
+        let mut got = 0usize;
+        while rx.recv().await.is_some() {
+            got += 1;
+        }
+        assert_eq!(got, 1000);
+        assert!(task.await.expect("forward task").expect("forward"));
+    }
+
+    #[tokio::test]
+    async fn forward_reports_dropped_receiver() {
+        let (source, reads) = counter(1000);
+        let (tx, rx) = mpsc::channel(1);
+        drop(rx);
+
+        let delivered = ChunkedReader::new(source, 8)
+            .forward(&tx)
+            .await
+            .expect("forward");
+        assert!(!delivered);
+        assert_eq!(reads.load(Ordering::SeqCst), 8);
+    }
+}
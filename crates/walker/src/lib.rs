@@ -1,2 +1,4 @@
 pub mod compiled_glob;
+#[cfg(all(unix, not(windows)))]
+mod fts_chunks;
 pub mod walker;
@@ -1,7 +1,11 @@
 use crate::compiled_glob::CompiledGlob;
+use std::collections::HashSet;
 use std::fmt;
+use std::fs::Metadata;
 use std::io;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use tokio::sync::mpsc;
 
 #[cfg(not(windows))]
@@ -23,6 +27,28 @@ pub enum EntryKind {
 pub struct WalkEvent {
     pub path: PathBuf,
     pub kind: EntryKind,
+    /// Position of the include rule that matched `path`, in merged rule order: the globs given
+    /// to [`Walker::spawn_many`] in turn, each contributing its rules.
+    pub rule: usize,
+    /// `lstat` data of `path`, read only with [`WalkerOptions::metadata`]. `None` otherwise or
+    /// when the entry could not be stat'ed anymore.
+    pub metadata: Option<Metadata>,
+}
+
+impl WalkEvent {
+    pub(crate) fn new(path: PathBuf, kind: EntryKind, rule: usize) -> Self {
+        Self {
+            path,
+            kind,
+            rule,
+            metadata: None,
+        }
+    }
+
+    /// Fills [`Self::metadata`] without following a symlink at `path`.
+    pub(crate) fn load_metadata(&mut self) {
+        self.metadata = std::fs::symlink_metadata(&self.path).ok();
+    }
 }
 
 #[derive(Debug)]
@@ -54,10 +80,114 @@ impl std::error::Error for WalkError {}
 
 pub type WalkMessage = Result<WalkEvent, WalkError>;
 
+/// A set of `io::ErrorKind`s whose walk errors are counted instead of being emitted.
+///
+/// Clones share the counter, so the count can be read from the options after the walk.
+#[derive(Clone, Debug, Default)]
+pub struct SuppressErrors {
+    kinds: HashSet<io::ErrorKind>,
+    count: Arc<AtomicUsize>,
+}
+
+impl SuppressErrors {
+    pub fn new(kinds: impl IntoIterator<Item = io::ErrorKind>) -> Self {
+        Self {
+            kinds: kinds.into_iter().collect(),
+            count: Arc::default(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty()
+    }
+
+    pub fn contains(&self, kind: io::ErrorKind) -> bool {
+        self.kinds.contains(&kind)
+    }
+
+    /// Number of errors suppressed so far.
+    pub fn suppressed(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Counts `err` and returns `true` if its kind is suppressed.
+    fn suppress(&self, err: &WalkError) -> bool {
+        let WalkError::Io { source, .. } = err else {
+            return false;
+        };
+        if !self.contains(source.kind()) {
+            return false;
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// Sends `err` unless it is suppressed. The backends report errors through this, so a
+    /// suppressed error never reaches the channel. Returns `false` once the receiver is gone.
+    pub(crate) async fn send(&self, tx: &mpsc::Sender<WalkMessage>, err: WalkError) -> bool {
+        self.suppress(&err) || tx.send(Err(err)).await.is_ok()
+    }
+}
+
+impl FromIterator<io::ErrorKind> for SuppressErrors {
+    fn from_iter<T: IntoIterator<Item = io::ErrorKind>>(iter: T) -> Self {
+        Self::new(iter)
+    }
+}
+
+/// How a walk keeps one file from being reported more than once.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Dedup {
+    /// Each path is reported once. Symlinks are never followed, so this needs no identity
+    /// checks on Unix; Windows keeps a set of visited directories at one metadata call each.
+    #[default]
+    Path,
+    /// No identity checks at all. Cheapest on Windows; on Unix the same as [`Dedup::Path`].
+    None,
+    /// Report each `(device, inode)` once, so hard links and overlapping mounts show up under
+    /// a single path. The fts backend takes the ids from the stat data it already has; the
+    /// Windows backend treats this as [`Dedup::Path`].
+    Inode,
+}
+
 #[derive(Clone, Debug)]
 pub struct WalkerOptions {
     pub channel_capacity: usize,
     pub files_only: bool,
+    /// Run traversal workers on a process-wide thread pool that outlives the walk, so repeated
+    /// walks in a long-lived process don't spawn and tear down threads each time. The pool
+    /// grows so that concurrent walks each get a full set of workers.
+    /// Release the pool with [`Walker::shutdown_workers`].
+    pub reuse_workers: bool,
+    /// I/O errors of these kinds are counted instead of being sent on the channel.
+    pub suppress_errors: SuppressErrors,
+    /// Directories the walk is limited to. Each pattern is still matched against full paths;
+    /// only the traversal is scoped, so patterns need not repeat the root. Relative roots are
+    /// resolved from the working directory. Empty walks from each pattern's literal prefix.
+    pub roots: Vec<PathBuf>,
+    /// Stay on the file system each walk starts on, like `find -xdev`: mount points are still
+    /// matched but not descended into, so network mounts and overlays can't stall the walk.
+    /// On Windows, directory reparse points (mounted volumes, junctions) are the boundary.
+    pub same_file_system: bool,
+    /// Upper bound on initial jobs per worker when the start directories are sharded. This and
+    /// the splitting fields below tune the fts backend; the Windows backend ignores them.
+    pub shard_factor: usize,
+    /// Deepest level (from a job's root) at which a large directory becomes a job of its own.
+    pub split_depth_limit: usize,
+    /// Child count from which a directory is split off; the base value under `adaptive_split`.
+    pub split_min_children: usize,
+    /// Events a worker collects before handing them to the channel.
+    pub emit_batch_size: usize,
+    /// Move the split threshold with the mean size of the directories read so far, so walks
+    /// over many small directories and over a few huge ones both split where it pays off.
+    pub adaptive_split: bool,
+    pub dedup: Dedup,
+    /// Deepest level reported, counted from the directory each walk starts at (its entries are
+    /// at depth 1). Directories at this depth are still matched but not read.
+    pub max_depth: Option<usize>,
+    /// Read each reported entry's `lstat` data into [`WalkEvent::metadata`], at one extra
+    /// metadata call per event.
+    pub metadata: bool,
 }
 
 impl Default for WalkerOptions {
@@ -65,6 +195,18 @@ impl Default for WalkerOptions {
         Self {
             channel_capacity: 1024,
             files_only: false,
+            reuse_workers: cfg!(feature = "bench-persistent-workers"),
+            suppress_errors: SuppressErrors::default(),
+            roots: Vec::new(),
+            same_file_system: false,
+            shard_factor: 6,
+            split_depth_limit: 2,
+            split_min_children: 24,
+            emit_batch_size: 128,
+            adaptive_split: false,
+            dedup: Dedup::Path,
+            max_depth: None,
+            metadata: false,
         }
     }
 }
@@ -98,19 +240,24 @@ impl Walker {
             Err(err) => {
                 let (tx, rx) = mpsc::channel(options.channel_capacity.max(1));
                 tokio::spawn(async move {
-                    let _ = tx
-                        .send(Err(WalkError::Io {
-                            path: PathBuf::from("<spawn_many>"),
-                            source: err,
-                        }))
-                        .await;
+                    let err = WalkError::Io {
+                        path: PathBuf::from("<spawn_many>"),
+                        source: err,
+                    };
+                    options.suppress_errors.send(&tx, err).await;
                 });
                 return rx;
             }
         };
-
         backend::spawn_single_with_options(merged, options)
     }
+
+    /// Stops the worker pool used by `reuse_workers` walks, waiting for queued work to finish.
+    ///
+    /// Returns `false` if no pool was running. A later `reuse_workers` walk starts a new pool.
+    pub async fn shutdown_workers() -> bool {
+        backend::shutdown_workers().await
+    }
 }
 
 #[cfg(test)]
@@ -119,7 +266,7 @@ mod tests {
     use super::*;
     use crate::compiled_glob::CompiledGlob;
     #[cfg(all(unix, not(windows)))]
-    use std::collections::BTreeSet;
+    use std::collections::{BTreeMap, BTreeSet};
     #[cfg(all(unix, not(windows)))]
     use std::fs;
     #[cfg(all(unix, not(windows)))]
@@ -243,7 +390,7 @@ mod tests {
         fs::set_permissions(root.join("blocked"), fs::Permissions::from_mode(0o0))
             .expect("chmod blocked");
 
-        let pattern = format!("{}/**.rs", root.display());
+        let pattern = format!("{}/**/*.rs", root.display());
         let glob = CompiledGlob::new(&pattern).expect("glob must parse");
         let mut rx = Walker::spawn(glob);
 
@@ -270,6 +417,52 @@ mod tests {
         let _ = fs::remove_dir_all(&root);
     }
 
+    #[tokio::test]
+    #[cfg(all(unix, not(windows)))]
+    async fn suppressed_errors_are_counted_not_emitted() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = test_root("suppress");
+        fs::create_dir_all(root.join("ok")).expect("create tree");
+        fs::create_dir_all(root.join("blocked/inner")).expect("create tree");
+        fs::write(root.join("ok/keep.rs"), b"fn main(){}").expect("write file");
+        fs::set_permissions(root.join("blocked"), fs::Permissions::from_mode(0o0))
+            .expect("chmod blocked");
+        // Privileged users can still read the directory, so nothing is denied.
+        let denied = fs::read_dir(root.join("blocked")).is_err();
+
+        let glob =
+            CompiledGlob::new(&format!("{}/**/*.rs", root.display())).expect("glob must parse");
+        let options = WalkerOptions {
+            suppress_errors: [io::ErrorKind::PermissionDenied].into_iter().collect(),
+            ..WalkerOptions::default()
+        };
+        let mut rx = Walker::spawn_with_options(glob, options.clone());
+
+        let mut got_ok = false;
+        while let Some(msg) = tokio::time::timeout(Duration::from_secs(3), rx.recv())
+            .await
+            .expect("channel should respond")
+        {
+            match msg {
+                Ok(ev) => got_ok |= ev.path == root.join("ok/keep.rs"),
+                Err(WalkError::Io { source, .. }) => {
+                    assert_ne!(source.kind(), io::ErrorKind::PermissionDenied);
+                }
+                Err(WalkError::Unsupported { .. }) => {}
+            }
+        }
+
+        assert!(got_ok, "accessible matches should still be emitted");
+        if denied {
+            assert!(options.suppress_errors.suppressed() > 0);
+        }
+
+        fs::set_permissions(root.join("blocked"), fs::Permissions::from_mode(0o755))
+            .expect("restore perms");
+        let _ = fs::remove_dir_all(&root);
+    }
+
     #[tokio::test]
     #[cfg(all(unix, not(windows)))]
     async fn spawn_many_matches_union_of_patterns() {
@@ -308,6 +501,57 @@ mod tests {
         let _ = fs::remove_dir_all(&root);
     }
 
+    #[tokio::test]
+    #[cfg(all(unix, not(windows)))]
+    async fn events_carry_the_matched_rule_and_optional_metadata() {
+        let root = test_root("rule_meta");
+        fs::create_dir_all(root.join("src")).expect("create tree");
+        fs::write(root.join("src/main.rs"), b"fn main(){}").expect("write file");
+        fs::write(root.join("readme.md"), b"# hi").expect("write file");
+
+        let walk = |metadata: bool| {
+            let globs = ["**/*.rs", "**/*.md"]
+                .map(|p| CompiledGlob::new(&format!("{}/{p}", root.display())).expect("parse"));
+            let mut rx = Walker::spawn_many_with_options(
+                globs,
+                WalkerOptions {
+                    metadata,
+                    ..WalkerOptions::default()
+                },
+            );
+            let root = root.clone();
+            async move {
+                let mut got = BTreeMap::new();
+                while let Some(msg) = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+                    .await
+                    .expect("channel should respond")
+                {
+                    let ev = msg.expect("walk should not fail");
+                    let rel = ev.path.strip_prefix(&root).expect("path under root");
+                    let len = ev.metadata.map(|metadata| metadata.len());
+                    got.insert(rel.to_string_lossy().into_owned(), (ev.rule, len));
+                }
+                got
+            }
+        };
+
+        assert_eq!(
+            walk(false).await,
+            BTreeMap::from([
+                ("src/main.rs".to_string(), (0, None)),
+                ("readme.md".to_string(), (1, None)),
+            ])
+        );
+        assert_eq!(
+            walk(true).await,
+            BTreeMap::from([
+                ("src/main.rs".to_string(), (0, Some(11))),
+                ("readme.md".to_string(), (1, Some(4))),
+            ])
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+
     #[tokio::test]
     #[cfg(all(unix, not(windows)))]
     async fn spawn_many_single_equivalent_to_spawn() {
@@ -463,6 +707,39 @@ mod tests {
         let _ = fs::remove_dir_all(&root);
     }
 
+    #[tokio::test]
+    #[cfg(all(unix, not(windows)))]
+    async fn trailing_separator_emits_directories_only() {
+        let root = test_root("dir_only");
+        fs::create_dir_all(root.join("src/bin")).expect("create tree");
+        fs::write(root.join("src/main.rs"), b"fn main(){}").expect("write file");
+        fs::write(root.join("src/bin/tool.rs"), b"fn main(){}").expect("write file");
+
+        let glob =
+            CompiledGlob::new(&format!("{}/src/**/", root.display())).expect("glob must parse");
+        let mut rx = Walker::spawn(glob);
+
+        let mut got = BTreeSet::new();
+        while let Some(msg) = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .expect("channel should respond")
+        {
+            if let Ok(ev) = msg {
+                assert_eq!(ev.kind, EntryKind::Dir);
+                got.insert(
+                    ev.path
+                        .strip_prefix(&root)
+                        .expect("path under root")
+                        .to_path_buf(),
+                );
+            }
+        }
+
+        let expected: BTreeSet<PathBuf> = ["src", "src/bin"].iter().map(PathBuf::from).collect();
+        assert_eq!(got, expected);
+        let _ = fs::remove_dir_all(&root);
+    }
+
     #[tokio::test]
     #[cfg(all(unix, not(windows)))]
     async fn shard_capacity_does_not_drop_late_directories() {
@@ -527,6 +804,326 @@ mod tests {
         let _ = fs::remove_dir_all(&root);
     }
 
+    #[tokio::test]
+    #[cfg(all(unix, not(windows)))]
+    async fn roots_scope_the_walk() {
+        let root = test_root("roots");
+        fs::create_dir_all(root.join("a/lua")).expect("create tree");
+        fs::create_dir_all(root.join("b")).expect("create tree");
+        fs::write(root.join("a/lua/x.lua"), b"").expect("write file");
+        fs::write(root.join("b/y.lua"), b"").expect("write file");
+
+        let walk = |roots: Vec<PathBuf>| {
+            let glob = CompiledGlob::new("/**/*.lua").expect("glob must parse");
+            let mut rx = Walker::spawn_with_options(
+                glob,
+                WalkerOptions {
+                    roots,
+                    ..WalkerOptions::default()
+                },
+            );
+            let root = root.clone();
+            async move {
+                let mut got = BTreeSet::new();
+                while let Some(msg) = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+                    .await
+                    .expect("channel should respond")
+                {
+                    let ev = msg.expect("walk should not fail");
+                    got.insert(
+                        ev.path
+                            .strip_prefix(&root)
+                            .expect("path under root")
+                            .to_path_buf(),
+                    );
+                }
+                got
+            }
+        };
+
+        assert_eq!(
+            walk(vec![root.join("a")]).await,
+            BTreeSet::from([PathBuf::from("a/lua/x.lua")])
+        );
+        assert_eq!(
+            walk(vec![root.join("a"), root.join("b")]).await,
+            BTreeSet::from([PathBuf::from("a/lua/x.lua"), PathBuf::from("b/y.lua")])
+        );
+        assert!(walk(vec![root.join("missing")]).await.is_empty());
+
+        // A root outside every pattern's prefix walks nothing.
+        let glob = CompiledGlob::new(&format!("{}/a/**", root.display())).expect("glob must parse");
+        let mut rx = Walker::spawn_with_options(
+            glob,
+            WalkerOptions {
+                roots: vec![root.join("b")],
+                ..WalkerOptions::default()
+            },
+        );
+        assert!(rx.recv().await.is_none());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    #[cfg(all(unix, not(windows)))]
+    async fn same_file_system_stops_at_mount_points() {
+        async fn collect(pattern: &str, same_file_system: bool) -> BTreeSet<PathBuf> {
+            let glob = CompiledGlob::new(pattern).expect("glob must parse");
+            let mut rx = Walker::spawn_with_options(
+                glob,
+                WalkerOptions {
+                    same_file_system,
+                    ..WalkerOptions::default()
+                },
+            );
+            let mut got = BTreeSet::new();
+            while let Some(msg) = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .expect("channel should respond")
+            {
+                // Unrelated top-level directories may be unreadable.
+                if let Ok(ev) = msg {
+                    got.insert(ev.path);
+                }
+            }
+            got
+        }
+
+        let root = test_root("xdev");
+        fs::create_dir_all(root.join("src/bin")).expect("create tree");
+        fs::write(root.join("src/main.rs"), b"fn main(){}").expect("write file");
+        fs::write(root.join("src/bin/tool.rs"), b"fn main(){}").expect("write file");
+        let pattern = format!("{}/**", root.display());
+        assert_eq!(
+            collect(&pattern, true).await,
+            collect(&pattern, false).await
+        );
+        let _ = fs::remove_dir_all(&root);
+
+        // `/proc` is usually its own mount; skip where it is not.
+        use std::os::unix::fs::MetadataExt;
+        let (Ok(top), Ok(proc)) = (fs::metadata("/"), fs::metadata("/proc")) else {
+            return;
+        };
+        if top.dev() == proc.dev() {
+            return;
+        }
+        let cpuinfo = PathBuf::from("/proc/cpuinfo");
+        assert!(collect("/*/cpuinfo", false).await.contains(&cpuinfo));
+        assert!(!collect("/*/cpuinfo", true).await.contains(&cpuinfo));
+    }
+
+    #[tokio::test]
+    #[cfg(all(unix, not(windows)))]
+    async fn split_tuning_does_not_change_results() {
+        let root = test_root("tuning");
+        for i in 0..40 {
+            let dir = root.join(format!("d{i}/sub"));
+            fs::create_dir_all(&dir).expect("create tree");
+            for j in 0..(i % 7) * 8 {
+                fs::write(dir.join(format!("f{j}.rs")), b"").expect("write file");
+            }
+        }
+
+        let walk = |options: WalkerOptions| {
+            let glob =
+                CompiledGlob::new(&format!("{}/**/*.rs", root.display())).expect("glob must parse");
+            let mut rx = Walker::spawn_with_options(glob, options);
+            async move {
+                let mut got = Vec::new();
+                while let Some(msg) = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                    .await
+                    .expect("channel should respond")
+                {
+                    got.push(msg.expect("walk should not fail").path);
+                }
+                let unique: BTreeSet<PathBuf> = got.iter().cloned().collect();
+                assert_eq!(unique.len(), got.len(), "no path is emitted twice");
+                unique
+            }
+        };
+
+        let expected = walk(WalkerOptions::default()).await;
+        assert_eq!(expected.len(), (0..40).map(|i| (i % 7) * 8).sum::<usize>());
+        let eager = WalkerOptions {
+            shard_factor: 1,
+            split_depth_limit: 8,
+            split_min_children: 1,
+            emit_batch_size: 1,
+            ..WalkerOptions::default()
+        };
+        assert_eq!(walk(eager.clone()).await, expected);
+        assert_eq!(
+            walk(WalkerOptions {
+                adaptive_split: true,
+                ..eager
+            })
+            .await,
+            expected
+        );
+        assert_eq!(
+            walk(WalkerOptions {
+                shard_factor: 0,
+                split_min_children: 0,
+                emit_batch_size: 0,
+                adaptive_split: true,
+                ..WalkerOptions::default()
+            })
+            .await,
+            expected
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    #[cfg(all(unix, not(windows)))]
+    async fn inode_dedup_reports_hard_links_once() {
+        let root = test_root("dedup");
+        fs::create_dir_all(root.join("a/deep")).expect("create tree");
+        fs::create_dir_all(root.join("b")).expect("create tree");
+        fs::write(root.join("a/deep/x.lua"), b"").expect("write file");
+        fs::hard_link(root.join("a/deep/x.lua"), root.join("b/x.lua")).expect("hard link");
+        fs::hard_link(root.join("a/deep/x.lua"), root.join("y.lua")).expect("hard link");
+        fs::write(root.join("b/z.lua"), b"").expect("write file");
+
+        let walk = |dedup: Dedup| {
+            let glob = CompiledGlob::new(&format!("{}/**/*.lua", root.display()))
+                .expect("glob must parse");
+            let mut rx = Walker::spawn_with_options(
+                glob,
+                WalkerOptions {
+                    dedup,
+                    ..WalkerOptions::default()
+                },
+            );
+            async move {
+                let mut got = Vec::new();
+                while let Some(msg) = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+                    .await
+                    .expect("channel should respond")
+                {
+                    got.push(msg.expect("walk should not fail").path);
+                }
+                got
+            }
+        };
+
+        assert_eq!(walk(Dedup::Path).await.len(), 4);
+        assert_eq!(walk(Dedup::None).await.len(), 4);
+        let got = walk(Dedup::Inode).await;
+        assert_eq!(got.len(), 2, "{got:?}");
+        assert!(got.contains(&root.join("b/z.lua")));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    #[cfg(all(unix, not(windows)))]
+    async fn max_depth_limits_the_walk() {
+        let root = test_root("depth");
+        fs::create_dir_all(root.join("a/b/c")).expect("create tree");
+        fs::write(root.join("top.rs"), b"").expect("write file");
+        fs::write(root.join("a/one.rs"), b"").expect("write file");
+        fs::write(root.join("a/b/c/three.rs"), b"").expect("write file");
+
+        let walk = |max_depth: Option<usize>| {
+            let glob =
+                CompiledGlob::new(&format!("{}/**", root.display())).expect("glob must parse");
+            let mut rx = Walker::spawn_with_options(
+                glob,
+                WalkerOptions {
+                    max_depth,
+                    ..WalkerOptions::default()
+                },
+            );
+            let root = root.clone();
+            async move {
+                let mut got = BTreeSet::new();
+                while let Some(msg) = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+                    .await
+                    .expect("channel should respond")
+                {
+                    let ev = msg.expect("walk should not fail");
+                    got.insert(
+                        ev.path
+                            .strip_prefix(&root)
+                            .expect("path under root")
+                            .to_string_lossy()
+                            .into_owned(),
+                    );
+                }
+                got
+            }
+        };
+
+        assert_eq!(walk(Some(0)).await, BTreeSet::from([String::new()]));
+        assert_eq!(
+            walk(Some(1)).await,
+            BTreeSet::from(["", "a", "top.rs"].map(String::from))
+        );
+        assert_eq!(
+            walk(Some(2)).await,
+            BTreeSet::from(["", "a", "a/b", "a/one.rs", "top.rs"].map(String::from))
+        );
+        // Past the sharded levels the limit applies inside fts jobs.
+        let three = walk(Some(3)).await;
+        assert!(three.contains("a/b/c") && !three.contains("a/b/c/three.rs"));
+        assert_eq!(walk(None).await.len(), 7);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    #[cfg(all(unix, not(windows)))]
+    async fn reuse_workers_survives_shutdown() {
+        let root = test_root("reuse_workers");
+        fs::create_dir_all(root.join("src/bin")).expect("create tree");
+        fs::write(root.join("src/main.rs"), b"fn main(){}").expect("write file");
+        fs::write(root.join("src/bin/tool.rs"), b"fn main(){}").expect("write file");
+        let glob =
+            CompiledGlob::new(&format!("{}/**/*.rs", root.display())).expect("glob must parse");
+        let options = WalkerOptions {
+            reuse_workers: true,
+            ..WalkerOptions::default()
+        };
+
+        let expected: BTreeSet<PathBuf> = ["src/main.rs", "src/bin/tool.rs"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        for round in 0..3usize {
+            let mut rx = Walker::spawn_with_options(glob.clone(), options.clone());
+            let mut got = BTreeSet::new();
+            while let Some(msg) = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+                .await
+                .expect("channel should respond")
+            {
+                if let Ok(ev) = msg {
+                    got.insert(
+                        ev.path
+                            .strip_prefix(&root)
+                            .expect("path under root")
+                            .to_path_buf(),
+                    );
+                }
+            }
+            assert_eq!(got, expected);
+
+            // A walk after shutdown must lazily start a fresh pool.
+            if round == 1 {
+                let stopped =
+                    tokio::time::timeout(Duration::from_secs(5), Walker::shutdown_workers())
+                        .await
+                        .expect("shutdown should finish");
+                assert!(stopped);
+            }
+        }
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
     #[tokio::test]
     #[cfg(all(unix, not(windows)))]
     async fn repeated_spawn_with_completion_is_stable() {
@@ -555,4 +1152,39 @@ mod tests {
 
         let _ = fs::remove_dir_all(&root);
     }
+
+    #[test]
+    #[cfg(all(unix, not(windows)))]
+    fn walks_with_a_single_blocking_thread() {
+        // Idle workers must not hold the only blocking thread the chunk reads need.
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .max_blocking_threads(1)
+            .enable_all()
+            .build()
+            .expect("build runtime");
+        let root = test_root("one_blocking_thread");
+        for dir in ["a/x", "a/y", "b/x", "b/y", "c/x"] {
+            fs::create_dir_all(root.join(dir)).expect("create tree");
+            fs::write(root.join(dir).join("f.rs"), b"").expect("write file");
+        }
+        let glob =
+            CompiledGlob::new(&format!("{}/**/*.rs", root.display())).expect("glob must parse");
+
+        let files = runtime.block_on(async {
+            let mut rx = Walker::spawn(glob);
+            let mut files = 0usize;
+            while let Some(msg) = tokio::time::timeout(Duration::from_secs(10), rx.recv())
+                .await
+                .expect("walk should not stall")
+            {
+                if msg.is_ok() {
+                    files += 1;
+                }
+            }
+            files
+        });
+        assert_eq!(files, 5);
+        let _ = fs::remove_dir_all(&root);
+    }
 }
@@ -1,28 +1,30 @@
 use crate::compiled_glob::CompiledGlob;
-use crate::walker::{EntryKind, WalkError, WalkEvent, WalkMessage, WalkerOptions};
+use crate::fts_chunks::{ChunkSource, ChunkedReader, Step};
+use crate::walker::{
+    Dedup, EntryKind, SuppressErrors, WalkError, WalkEvent, WalkMessage, WalkerOptions,
+};
 use adaptive_semaphore::AdaptiveSemaphore;
-use fts::fts::{Fts, FtsInfo, FtsSetOption, fts_option};
+use fts::fts::{Fts, FtsEntry, FtsInfo, FtsSetOption, fts_option};
 use hashbrown::HashMap;
 use std::collections::{HashSet, VecDeque};
 use std::hash::{Hash, Hasher};
 use std::io;
 use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{DirEntryExt, MetadataExt};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Condvar, Mutex};
+use std::task::Poll;
 use std::time::Duration;
-use tokio::sync::mpsc;
-#[cfg(not(feature = "bench-persistent-workers"))]
+use tokio::sync::{Notify, mpsc};
 use tokio::task::JoinSet;
 
 const TRANSITION_CACHE_CAPACITY: usize = 64 * 1024;
 const STATE_CACHE_CAPACITY: usize = 64 * 1024;
-const EMIT_BATCH_SIZE: usize = 128;
-const SHARD_FACTOR: usize = 6;
+const FTS_CHUNK_READS: usize = 512;
 const SHARD_DEPTH: usize = 2;
-const SPLIT_DEPTH_LIMIT: usize = 2;
 const SPLIT_BACKLOG_FACTOR: usize = 4;
-const SPLIT_MIN_CHILDREN: usize = 24;
+const ADAPTIVE_SPLIT_MIN_SAMPLES: usize = 32;
 const QUEUE_WAIT_MILLIS: u64 = 5;
 const ADAPTIVE_MAX_PARALLELISM: usize = 256;
 const ADAPTIVE_ADJUST_INTERVAL: Duration = Duration::from_millis(64);
@@ -31,6 +33,24 @@ const ADAPTIVE_ADJUST_INTERVAL: Duration = Duration::from_millis(64);
 struct RootJob {
     path: PathBuf,
     root_states: Vec<usize>,
+    /// Device the walk must stay on, set with `WalkerOptions::same_file_system`.
+    dev: Option<u64>,
+    /// Depth of `path` below the directory the walk started at.
+    depth: usize,
+}
+
+/// `(dev, ino)` of an entry.
+type FileId = (u64, u64);
+
+/// Files already emitted by a walk with `Dedup::Inode`.
+#[derive(Default)]
+struct SeenFiles(Mutex<HashSet<FileId>>);
+
+impl SeenFiles {
+    /// Records `id`, returning `false` if it was emitted before.
+    fn first(&self, id: FileId) -> bool {
+        self.0.lock().expect("seen files lock").insert(id)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
@@ -49,7 +69,7 @@ struct TransitionValue {
 
 #[derive(Default)]
 struct StateEvalCache {
-    match_cache: HashMap<u64, bool>,
+    match_cache: HashMap<(u64, bool), Option<usize>>,
     scan_cache: HashMap<u64, bool>,
 }
 
@@ -66,7 +86,11 @@ struct JobQueueInner {
 
 struct JobQueue {
     inner: Mutex<JobQueueInner>,
+    /// Wakes the persistent-pool workers, which wait on their own threads.
     cv: Condvar,
+    /// Wakes the async workers, which must not hold a blocking thread while they wait: the
+    /// chunk reads of the other workers run on the same limited blocking pool.
+    notify: Notify,
 }
 
 impl JobQueue {
@@ -77,6 +101,7 @@ impl JobQueue {
                 closed: false,
             }),
             cv: Condvar::new(),
+            notify: Notify::new(),
         }
     }
 
@@ -87,33 +112,65 @@ impl JobQueue {
         }
         inner.queue.push_back(job);
         self.cv.notify_one();
+        self.notify.notify_one();
         true
     }
 
+    /// Takes the next job, or `Ready(None)` once the walk is cancelled, closed, or out of work.
+    fn poll_job(
+        inner: &mut JobQueueInner,
+        cancel: &AtomicBool,
+        active_jobs: &AtomicUsize,
+    ) -> Poll<Option<RootJob>> {
+        if cancel.load(Ordering::Relaxed) {
+            return Poll::Ready(None);
+        }
+        if let Some(job) = inner.queue.pop_front() {
+            return Poll::Ready(Some(job));
+        }
+        if inner.closed || active_jobs.load(Ordering::Relaxed) == 0 {
+            return Poll::Ready(None);
+        }
+        Poll::Pending
+    }
+
     fn pop(&self, cancel: &AtomicBool, active_jobs: &AtomicUsize) -> Option<RootJob> {
         let mut inner = self.inner.lock().expect("job queue lock");
         loop {
-            if cancel.load(Ordering::Relaxed) {
-                return None;
-            }
-            if let Some(job) = inner.queue.pop_front() {
-                return Some(job);
-            }
-            if inner.closed || active_jobs.load(Ordering::Relaxed) == 0 {
-                return None;
+            if let Poll::Ready(job) = Self::poll_job(&mut inner, cancel, active_jobs) {
+                return job;
             }
             let (guard, _) = self
                 .cv
-                .wait_timeout(inner, std::time::Duration::from_millis(QUEUE_WAIT_MILLIS))
+                .wait_timeout(inner, Duration::from_millis(QUEUE_WAIT_MILLIS))
                 .expect("job queue wait");
             inner = guard;
         }
     }
 
+    /// [`Self::pop`] for async workers.
+    async fn pop_async(&self, cancel: &AtomicBool, active_jobs: &AtomicUsize) -> Option<RootJob> {
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            // Register before looking at the queue so a push in between is not missed.
+            notified.as_mut().enable();
+            {
+                let mut inner = self.inner.lock().expect("job queue lock");
+                if let Poll::Ready(job) = Self::poll_job(&mut inner, cancel, active_jobs) {
+                    return job;
+                }
+            }
+            // Finishing the last job and cancelling do not notify, so look again periodically.
+            let _ = tokio::time::timeout(Duration::from_millis(QUEUE_WAIT_MILLIS), notified).await;
+        }
+    }
+
     fn close(&self) {
         let mut inner = self.inner.lock().expect("job queue lock");
         inner.closed = true;
         self.cv.notify_all();
+        self.notify.notify_waiters();
     }
 }
 
@@ -125,29 +182,108 @@ struct WorkerCtx {
     queue: Arc<JobQueue>,
     worker_tx: mpsc::Sender<WorkerMessage>,
     split_backlog_limit: usize,
+    split_depth_limit: usize,
+    split_min_children: usize,
+    emit_batch_size: usize,
+    /// Sizes of fully read directories, kept with `WalkerOptions::adaptive_split`.
+    dir_sizes: Option<DirSizeStats>,
+    seen: Option<Arc<SeenFiles>>,
+    max_depth: Option<usize>,
+    /// `WalkerOptions::metadata`: events are stat'ed on the worker before they are handed over.
+    metadata: bool,
     traversal_semaphore: AdaptiveSemaphore,
 }
 
-#[cfg(feature = "bench-persistent-workers")]
+impl WorkerCtx {
+    /// Child count from which a directory is split into its own job.
+    fn split_threshold(&self) -> usize {
+        match &self.dir_sizes {
+            Some(sizes) => sizes.split_threshold(self.split_min_children),
+            None => self.split_min_children,
+        }
+    }
+
+    /// Whether `entry` is emitted for the first time, using the stat data fts already has.
+    fn first_visit(&self, entry: &FtsEntry) -> bool {
+        self.seen
+            .as_ref()
+            .is_none_or(|seen| seen.first((entry.dev, entry.ino)))
+    }
+}
+
+/// Running mean of directory sizes seen by the workers of one walk.
+#[derive(Default)]
+struct DirSizeStats {
+    dirs: AtomicUsize,
+    entries: AtomicUsize,
+}
+
+impl DirSizeStats {
+    fn record(&self, children: usize) {
+        self.dirs.fetch_add(1, Ordering::Relaxed);
+        self.entries.fetch_add(children, Ordering::Relaxed);
+    }
+
+    /// Splits only directories well above the mean, within `base / 4 ..= base * 8`: many small
+    /// directories lower the threshold so the odd large one is shared out, while uniformly large
+    /// ones raise it so they don't all turn into jobs.
+    fn split_threshold(&self, base: usize) -> usize {
+        let dirs = self.dirs.load(Ordering::Relaxed);
+        if dirs < ADAPTIVE_SPLIT_MIN_SAMPLES {
+            return base;
+        }
+        let mean = self.entries.load(Ordering::Relaxed) / dirs;
+        mean.saturating_mul(2)
+            .clamp((base / 4).max(2), base.saturating_mul(8).max(2))
+    }
+}
+
+/// Long-lived worker threads shared by walks with `WalkerOptions::reuse_workers`.
+///
+/// The pool is created lazily and lives until [`shutdown_workers`] takes it down; a later walk
+/// creates a fresh one. Each running walk reserves the workers it submits, and the pool grows
+/// to the sum of the reservations, so concurrent walks do not queue behind each other. Threads
+/// are not retired when a walk ends; they wait for the next walk.
 mod persistent_pool {
-    use std::sync::{Arc, Mutex, OnceLock, mpsc};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex, mpsc};
+    use std::thread::JoinHandle;
     use tokio::sync::oneshot;
 
     type Job = Box<dyn FnOnce() + Send + 'static>;
 
-    pub(super) struct PersistentPool {
+    struct PersistentPool {
+        /// Tells a submitter's reservation apart from one made on an earlier pool.
+        id: u64,
         tx: mpsc::Sender<Job>,
+        rx: Arc<Mutex<mpsc::Receiver<Job>>>,
+        threads: Vec<JoinHandle<()>>,
+        /// Workers reserved by the walks that are running.
+        reserved: usize,
     }
 
+    static POOL: Mutex<Option<PersistentPool>> = Mutex::new(None);
+    static NEXT_POOL_ID: AtomicU64 = AtomicU64::new(0);
+
     impl PersistentPool {
-        fn new(thread_count: usize) -> Self {
+        fn new() -> Self {
             let (tx, rx) = mpsc::channel::<Job>();
-            let rx = Arc::new(Mutex::new(rx));
+            Self {
+                id: NEXT_POOL_ID.fetch_add(1, Ordering::Relaxed),
+                tx,
+                rx: Arc::new(Mutex::new(rx)),
+                threads: Vec::new(),
+                reserved: 0,
+            }
+        }
 
-            for idx in 0..thread_count.max(1) {
-                let rx = Arc::clone(&rx);
-                std::thread::Builder::new()
-                    .name(format!("walker-bench-{idx}"))
+        /// Reserves `count` workers for a walk, starting threads until every reservation has one.
+        fn reserve(&mut self, count: usize) {
+            self.reserved += count.max(1);
+            while self.threads.len() < self.reserved {
+                let rx = Arc::clone(&self.rx);
+                let handle = std::thread::Builder::new()
+                    .name(format!("walker-worker-{}", self.threads.len()))
                     .spawn(move || {
                         loop {
                             let job = {
@@ -160,12 +296,46 @@ mod persistent_pool {
                             }
                         }
                     })
-                    .expect("failed to spawn persistent bench worker");
+                    .expect("failed to spawn persistent walker worker");
+                self.threads.push(handle);
             }
+        }
+
+        fn release(&mut self, count: usize) {
+            self.reserved = self.reserved.saturating_sub(count.max(1));
+        }
 
-            Self { tx }
+        /// Waits for the threads to exit. They exit once every sender, including those held by
+        /// in-flight walks, is gone.
+        fn join(self) {
+            let PersistentPool { tx, threads, .. } = self;
+            drop(tx);
+            for handle in threads {
+                let _ = handle.join();
+            }
         }
+    }
 
+    /// Returns a submitter for the shared pool that holds `thread_count` workers until it is
+    /// dropped, creating or growing the pool as needed.
+    pub(super) fn sender(thread_count: usize) -> Submitter {
+        let mut pool = POOL.lock().expect("persistent pool lock");
+        let pool = pool.get_or_insert_with(PersistentPool::new);
+        pool.reserve(thread_count);
+        Submitter {
+            tx: pool.tx.clone(),
+            pool_id: pool.id,
+            reserved: thread_count,
+        }
+    }
+
+    pub(super) struct Submitter {
+        tx: mpsc::Sender<Job>,
+        pool_id: u64,
+        reserved: usize,
+    }
+
+    impl Submitter {
         pub(super) fn spawn<F>(&self, f: F) -> oneshot::Receiver<bool>
         where
             F: FnOnce() + Send + 'static,
@@ -179,10 +349,52 @@ mod persistent_pool {
         }
     }
 
-    pub(super) fn global(thread_count: usize) -> &'static PersistentPool {
-        static POOL: OnceLock<PersistentPool> = OnceLock::new();
-        POOL.get_or_init(|| PersistentPool::new(thread_count))
+    impl Drop for Submitter {
+        fn drop(&mut self) {
+            let mut pool = POOL.lock().expect("persistent pool lock");
+            if let Some(pool) = pool.as_mut().filter(|pool| pool.id == self.pool_id) {
+                pool.release(self.reserved);
+            }
+        }
     }
+
+    /// Detaches the shared pool and waits for its threads to exit.
+    ///
+    /// Jobs already submitted run to completion first. Returns `false` if no pool was running.
+    pub(super) fn shutdown() -> bool {
+        let Some(pool) = POOL.lock().expect("persistent pool lock").take() else {
+            return false;
+        };
+        pool.join();
+        true
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn pool_grows_to_the_workers_of_the_running_walks() {
+            let mut pool = PersistentPool::new();
+            pool.reserve(2);
+            assert_eq!(pool.threads.len(), 2);
+            // A second walk running at the same time gets its own workers.
+            pool.reserve(3);
+            assert_eq!(pool.threads.len(), 5);
+            // Once a walk ends its threads serve the next one.
+            pool.release(3);
+            pool.reserve(1);
+            assert_eq!(pool.threads.len(), 5);
+            assert_eq!(pool.reserved, 3);
+            pool.join();
+        }
+    }
+}
+
+pub(super) async fn shutdown_workers() -> bool {
+    tokio::task::spawn_blocking(persistent_pool::shutdown)
+        .await
+        .unwrap_or(false)
 }
 
 pub(super) fn spawn_single_with_options(
@@ -194,9 +406,15 @@ pub(super) fn spawn_single_with_options(
     tokio::spawn(async move {
         let compiled = Arc::new(compiled);
         let files_only = options.files_only;
+        let roots = options.roots;
+        let same_file_system = options.same_file_system;
+        let seen = (options.dedup == Dedup::Inode).then(|| Arc::new(SeenFiles::default()));
+        let max_depth = options.max_depth;
+        let metadata = options.metadata;
+        let suppress = options.suppress_errors;
         let initial_parallelism = default_parallelism().max(1);
         let worker_count = ADAPTIVE_MAX_PARALLELISM;
-        let max_jobs = worker_count.saturating_mul(SHARD_FACTOR).max(1);
+        let max_jobs = worker_count.saturating_mul(options.shard_factor).max(1);
         let traversal_semaphore = AdaptiveSemaphore::with_limits(
             initial_parallelism,
             1,
@@ -206,19 +424,33 @@ pub(super) fn spawn_single_with_options(
 
         let prepared = tokio::task::spawn_blocking({
             let compiled = Arc::clone(&compiled);
-            move || prepare_jobs(compiled.as_ref(), files_only, max_jobs)
+            let seen = seen.clone();
+            move || {
+                let (jobs, mut initial_events) = prepare_jobs(
+                    compiled.as_ref(),
+                    &roots,
+                    files_only,
+                    same_file_system,
+                    seen.as_deref(),
+                    max_depth,
+                    max_jobs,
+                );
+                if metadata {
+                    initial_events.iter_mut().for_each(WalkEvent::load_metadata);
+                }
+                (jobs, initial_events)
+            }
         })
         .await;
 
         let (jobs, initial_events) = match prepared {
             Ok(value) => value,
             Err(err) => {
-                let _ = tx
-                    .send(Err(WalkError::Io {
-                        path: PathBuf::from("<prepare_jobs>"),
-                        source: io::Error::other(err.to_string()),
-                    }))
-                    .await;
+                let err = WalkError::Io {
+                    path: PathBuf::from("<prepare_jobs>"),
+                    source: io::Error::other(err.to_string()),
+                };
+                suppress.send(&tx, err).await;
                 return;
             }
         };
@@ -243,64 +475,81 @@ pub(super) fn spawn_single_with_options(
             mpsc::channel::<WorkerMessage>(options.channel_capacity.max(1));
         let forward_cancel = Arc::clone(&cancel);
         let tx_forward = tx.clone();
+        let forward_suppress = suppress.clone();
         let forwarder = tokio::spawn(async move {
-            forward_worker_messages(worker_rx, tx_forward, forward_cancel).await;
+            forward_worker_messages(worker_rx, tx_forward, forward_cancel, forward_suppress).await;
         });
 
-        #[cfg(not(feature = "bench-persistent-workers"))]
         let mut worker_set = JoinSet::new();
-        #[cfg(feature = "bench-persistent-workers")]
-        let mut waiters = Vec::with_capacity(worker_count);
-
+        let mut waiters = Vec::new();
+        let pool = options
+            .reuse_workers
+            .then(|| persistent_pool::sender(worker_count));
+
+        let ctx = Arc::new(WorkerCtx {
+            compiled: Arc::clone(&compiled),
+            files_only,
+            cancel: Arc::clone(&cancel),
+            active_jobs: Arc::clone(&active_jobs),
+            queue: Arc::clone(&queue),
+            worker_tx,
+            split_backlog_limit,
+            split_depth_limit: options.split_depth_limit,
+            split_min_children: options.split_min_children.max(1),
+            emit_batch_size: options.emit_batch_size.max(1),
+            dir_sizes: options.adaptive_split.then(DirSizeStats::default),
+            seen,
+            max_depth,
+            metadata,
+            traversal_semaphore: traversal_semaphore.clone(),
+        });
         for _ in 0..worker_count {
-            let ctx = WorkerCtx {
-                compiled: Arc::clone(&compiled),
-                files_only,
-                cancel: Arc::clone(&cancel),
-                active_jobs: Arc::clone(&active_jobs),
-                queue: Arc::clone(&queue),
-                worker_tx: worker_tx.clone(),
-                split_backlog_limit,
-                traversal_semaphore: traversal_semaphore.clone(),
-            };
-            #[cfg(not(feature = "bench-persistent-workers"))]
-            worker_set.spawn_blocking(move || run_worker(ctx));
-            #[cfg(feature = "bench-persistent-workers")]
-            waiters.push(persistent_pool::global(worker_count).spawn(move || run_worker(ctx)));
+            let ctx = Arc::clone(&ctx);
+            match &pool {
+                Some(pool) => waiters.push(pool.spawn(move || run_worker_blocking(ctx))),
+                None => {
+                    worker_set.spawn(run_worker(ctx));
+                }
+            }
         }
-        drop(worker_tx);
+        // The forwarder finishes once every worker (and its jobs) has released the context.
+        drop(ctx);
 
-        #[cfg(not(feature = "bench-persistent-workers"))]
         while let Some(joined) = worker_set.join_next().await {
-            if let Err(err) = joined {
+            let failure = match joined {
+                Ok(Ok(())) => None,
+                Ok(Err(err)) => Some(err),
+                Err(err) => Some(io::Error::other(err.to_string())),
+            };
+            if let Some(source) = failure {
                 cancel.store(true, Ordering::Relaxed);
                 queue.close();
-                let _ = tx
-                    .send(Err(WalkError::Io {
-                        path: PathBuf::from("<join_worker>"),
-                        source: io::Error::other(err.to_string()),
-                    }))
-                    .await;
+                let err = WalkError::Io {
+                    path: PathBuf::from("<join_worker>"),
+                    source,
+                };
+                suppress.send(&tx, err).await;
             }
         }
 
-        #[cfg(feature = "bench-persistent-workers")]
         for waiter in waiters {
             match waiter.await {
                 Ok(true) => {}
                 Ok(false) | Err(_) => {
                     cancel.store(true, Ordering::Relaxed);
                     queue.close();
-                    let _ = tx
-                        .send(Err(WalkError::Io {
-                            path: PathBuf::from("<join_worker>"),
-                            source: io::Error::other("persistent worker panicked"),
-                        }))
-                        .await;
+                    let err = WalkError::Io {
+                        path: PathBuf::from("<join_worker>"),
+                        source: io::Error::other("persistent worker panicked"),
+                    };
+                    suppress.send(&tx, err).await;
                 }
             }
         }
 
+        // The workers stay reserved until they are done.
+        drop(pool);
+
         // Wait for the forwarder to drain all pending events before finishing.
         let _ = forwarder.await;
     });
@@ -308,7 +557,54 @@ pub(super) fn spawn_single_with_options(
     rx
 }
 
-fn run_worker(ctx: WorkerCtx) {
+/// Cancels the walk if a worker future is dropped before it finishes, e.g. on runtime shutdown,
+/// so blocking queue waits and in-flight chunks observe it instead of spinning forever.
+struct CancelOnDrop<'a> {
+    cancel: &'a AtomicBool,
+    armed: bool,
+}
+
+impl Drop for CancelOnDrop<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            self.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+async fn run_worker(ctx: Arc<WorkerCtx>) -> io::Result<()> {
+    let mut guard = CancelOnDrop {
+        cancel: &ctx.cancel,
+        armed: true,
+    };
+    let result = run_worker_jobs(&ctx).await;
+    guard.armed = result.is_err();
+    result
+}
+
+async fn run_worker_jobs(ctx: &Arc<WorkerCtx>) -> io::Result<()> {
+    loop {
+        if ctx.cancel.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let popped = ctx.queue.pop_async(&ctx.cancel, &ctx.active_jobs).await;
+        let Some(job) = popped else {
+            return Ok(());
+        };
+
+        let reader = ChunkedReader::new(FtsJob::new(Arc::clone(ctx), job), FTS_CHUNK_READS);
+        if !reader.forward(&ctx.worker_tx).await? {
+            ctx.cancel.store(true, Ordering::Relaxed);
+        }
+
+        if finish_job(ctx) {
+            return Ok(());
+        }
+    }
+}
+
+fn run_worker_blocking(ctx: Arc<WorkerCtx>) {
     loop {
         if ctx.cancel.load(Ordering::Relaxed) {
             return;
@@ -318,259 +614,349 @@ fn run_worker(ctx: WorkerCtx) {
             return;
         };
 
-        run_fts_job(&ctx, job);
+        let reader = ChunkedReader::new(FtsJob::new(Arc::clone(&ctx), job), FTS_CHUNK_READS);
+        if !reader.blocking_forward(&ctx.worker_tx) {
+            ctx.cancel.store(true, Ordering::Relaxed);
+        }
 
-        if ctx.active_jobs.fetch_sub(1, Ordering::AcqRel) == 1 {
-            ctx.queue.close();
+        if finish_job(&ctx) {
             return;
         }
     }
 }
 
-fn run_fts_job(ctx: &WorkerCtx, job: RootJob) {
-    if ctx.cancel.load(Ordering::Relaxed) {
-        return;
+/// Marks a job as finished. Returns `true` when it was the last one and the queue is closed.
+fn finish_job(ctx: &WorkerCtx) -> bool {
+    if ctx.active_jobs.fetch_sub(1, Ordering::AcqRel) == 1 {
+        ctx.queue.close();
+        return true;
     }
+    false
+}
 
-    let root_string = job.path.to_string_lossy().to_string();
-    let mut fts = match Fts::new(
-        vec![root_string],
-        fts_option::Flags::PHYSICAL | fts_option::Flags::NOCHDIR,
-        None,
-    ) {
-        Ok(fts) => fts,
-        Err(err) => {
-            let _ = ctx
-                .worker_tx
-                .blocking_send(WorkerMessage::Error(WalkError::Io {
-                    path: job.path,
-                    source: io::Error::other(format!("failed to initialize fts: {err:?}")),
-                }));
-            return;
-        }
-    };
-
-    let mut level_states: Vec<Arc<[usize]>> = Vec::new();
-    let mut transition_cache: HashMap<TransitionKey, TransitionValue> = HashMap::new();
-    let mut transition_cache_len = 0usize;
-    let mut state_cache = StateEvalCache::default();
-    let mut pending_events = Vec::with_capacity(EMIT_BATCH_SIZE);
-    let mut next_states_scratch = Vec::new();
+/// A single fts traversal of a root job, advanced chunk by chunk by [`ChunkedReader`].
+struct FtsJob {
+    ctx: Arc<WorkerCtx>,
+    job: RootJob,
+    fts: Option<Fts>,
+    level_states: Vec<Arc<[usize]>>,
+    transition_cache: HashMap<TransitionKey, TransitionValue>,
+    transition_cache_len: usize,
+    state_cache: StateEvalCache,
+    pending_events: Vec<WalkEvent>,
+    next_states_scratch: Vec<usize>,
+    /// Entries read so far in each open directory, by level; only with adaptive splitting.
+    child_counts: Vec<usize>,
+}
 
-    loop {
-        if ctx.cancel.load(Ordering::Relaxed) {
-            return;
+impl FtsJob {
+    fn new(ctx: Arc<WorkerCtx>, job: RootJob) -> Self {
+        let emit_batch_size = ctx.emit_batch_size;
+        Self {
+            ctx,
+            job,
+            fts: None,
+            level_states: Vec::new(),
+            transition_cache: HashMap::new(),
+            transition_cache_len: 0,
+            state_cache: StateEvalCache::default(),
+            pending_events: Vec::with_capacity(emit_batch_size),
+            next_states_scratch: Vec::new(),
+            child_counts: Vec::new(),
         }
+    }
+}
 
-        let permit = ctx.traversal_semaphore.blocking_acquire();
-        let entry = fts.read();
-        let is_error = entry.as_ref().is_some_and(|entry| {
-            matches!(
-                entry.info,
-                FtsInfo::IsErr | FtsInfo::IsDontRead | FtsInfo::IsNoStat
-            )
-        });
-        permit.finish(is_error);
-        let Some(entry) = entry else {
-            break;
+impl ChunkSource for FtsJob {
+    type Item = WorkerMessage;
+
+    fn fill(&mut self, budget: usize, out: &mut Vec<WorkerMessage>) -> Step {
+        let FtsJob {
+            ctx,
+            job,
+            fts,
+            level_states,
+            transition_cache,
+            transition_cache_len,
+            state_cache,
+            pending_events,
+            next_states_scratch,
+            child_counts,
+        } = self;
+
+        if fts.is_none() {
+            let root_string = job.path.to_string_lossy().to_string();
+            let mut flags = fts_option::Flags::PHYSICAL | fts_option::Flags::NOCHDIR;
+            if job.dev.is_some() {
+                flags |= fts_option::Flags::XDEV;
+            }
+            match Fts::new(vec![root_string], flags, None) {
+                Ok(opened) => *fts = Some(opened),
+                Err(err) => {
+                    out.push(WorkerMessage::Error(WalkError::Io {
+                        path: job.path.clone(),
+                        source: io::Error::other(format!("failed to initialize fts: {err:?}")),
+                    }));
+                    return Step::Done;
+                }
+            }
+        }
+        let Some(fts) = fts.as_mut() else {
+            return Step::Done;
         };
 
-        let level = match usize::try_from(entry.level) {
-            Ok(level) => level,
-            Err(_) => continue,
-        };
+        for _ in 0..budget {
+            if ctx.cancel.load(Ordering::Relaxed) {
+                pending_events.clear();
+                return Step::Done;
+            }
 
-        match entry.info {
-            FtsInfo::IsDot | FtsInfo::IsDirPost => {
-                flush_events(&ctx.worker_tx, &mut pending_events, &ctx.cancel);
-                if level < level_states.len() {
-                    level_states.truncate(level);
+            let permit = ctx.traversal_semaphore.blocking_acquire();
+            let entry = fts.read();
+            let is_error = entry.as_ref().is_some_and(|entry| {
+                matches!(
+                    entry.info,
+                    FtsInfo::IsErr | FtsInfo::IsDontRead | FtsInfo::IsNoStat
+                )
+            });
+            permit.finish(is_error);
+            let Some(entry) = entry else {
+                flush_events(pending_events, out, ctx.metadata);
+                return Step::Done;
+            };
+
+            let level = match usize::try_from(entry.level) {
+                Ok(level) => level,
+                Err(_) => continue,
+            };
+
+            if let Some(sizes) = &ctx.dir_sizes {
+                match entry.info {
+                    FtsInfo::IsDirPost => {
+                        if let Some(&children) = child_counts.get(level) {
+                            sizes.record(children);
+                        }
+                    }
+                    FtsInfo::IsDot => {}
+                    _ => {
+                        if level > 0
+                            && let Some(count) = child_counts.get_mut(level - 1)
+                        {
+                            *count += 1;
+                        }
+                        if entry.info == FtsInfo::IsDir {
+                            child_counts.truncate(level);
+                            child_counts.resize(level + 1, 0);
+                        }
+                    }
                 }
-                continue;
             }
-            FtsInfo::IsErr | FtsInfo::IsDontRead | FtsInfo::IsNoStat => {
-                flush_events(&ctx.worker_tx, &mut pending_events, &ctx.cancel);
-                let source = if entry.error == 0 {
-                    io::Error::other("fts reported an unreadable entry")
-                } else {
-                    io::Error::from_raw_os_error(entry.error)
-                };
-                let _ = ctx
-                    .worker_tx
-                    .blocking_send(WorkerMessage::Error(WalkError::Io {
-                        path: entry.path.clone(),
-                        source,
-                    }));
-                continue;
-            }
-            _ => {}
-        }
 
-        let is_dir = matches!(entry.info, FtsInfo::IsDir | FtsInfo::IsDirCyclic);
-        let (states, states_sig) = if level == 0 {
-            let states = Arc::<[usize]>::from(job.root_states.clone());
-            let signature = states_signature(states.as_ref());
-            (states, signature)
-        } else {
-            let parent = match level_states.get(level.saturating_sub(1)) {
-                Some(parent) => parent,
-                None => {
-                    if is_dir {
-                        let _ = fts.set(&entry, FtsSetOption::Skip);
+            match entry.info {
+                FtsInfo::IsDot | FtsInfo::IsDirPost => {
+                    flush_events(pending_events, out, ctx.metadata);
+                    if level < level_states.len() {
+                        level_states.truncate(level);
                     }
                     continue;
                 }
-            };
-            if parent.is_empty() {
-                if is_dir {
-                    let _ = fts.set(&entry, FtsSetOption::Skip);
+                FtsInfo::IsErr | FtsInfo::IsDontRead | FtsInfo::IsNoStat => {
+                    flush_events(pending_events, out, ctx.metadata);
+                    let source = if entry.error == 0 {
+                        io::Error::other("fts reported an unreadable entry")
+                    } else {
+                        io::Error::from_raw_os_error(entry.error)
+                    };
+                    out.push(WorkerMessage::Error(WalkError::Io {
+                        path: entry.path.clone(),
+                        source,
+                    }));
+                    continue;
                 }
-                continue;
+                _ => {}
             }
 
-            let name_bytes = entry.name.as_os_str().as_bytes();
-            let name_len = match u16::try_from(name_bytes.len()) {
-                Ok(v) => v,
-                Err(_) => {
+            let is_dir = matches!(entry.info, FtsInfo::IsDir | FtsInfo::IsDirCyclic);
+            let (states, states_sig) = if level == 0 {
+                let states = Arc::<[usize]>::from(job.root_states.clone());
+                let signature = states_signature(states.as_ref());
+                (states, signature)
+            } else {
+                let parent = match level_states.get(level.saturating_sub(1)) {
+                    Some(parent) => parent,
+                    None => {
+                        if is_dir {
+                            let _ = fts.set(&entry, FtsSetOption::Skip);
+                        }
+                        continue;
+                    }
+                };
+                if parent.is_empty() {
                     if is_dir {
                         let _ = fts.set(&entry, FtsSetOption::Skip);
                     }
                     continue;
                 }
-            };
 
-            let key = TransitionKey {
-                state_sig: states_signature(parent.as_ref()),
-                name_sig: bytes_signature(name_bytes),
-                name_len,
-            };
-
-            if let Some(cached) = transition_cache.get(&key)
-                && cached.name.as_slice() == name_bytes
-                && cached.parent_states.as_ref() == parent.as_ref()
-            {
-                (Arc::clone(&cached.states), cached.next_sig)
-            } else {
-                let Some(name) = entry.name.to_str() else {
-                    if is_dir {
-                        let _ = fts.set(&entry, FtsSetOption::Skip);
+                let name_bytes = entry.name.as_os_str().as_bytes();
+                let name_len = match u16::try_from(name_bytes.len()) {
+                    Ok(v) => v,
+                    Err(_) => {
+                        if is_dir {
+                            let _ = fts.set(&entry, FtsSetOption::Skip);
+                        }
+                        continue;
                     }
-                    continue;
                 };
 
-                ctx.compiled
-                    .advance_states_into(parent.as_ref(), name, &mut next_states_scratch);
-                let next_sig = states_signature(&next_states_scratch);
-                let next_states = Arc::<[usize]>::from(next_states_scratch.as_slice());
+                let key = TransitionKey {
+                    state_sig: states_signature(parent.as_ref()),
+                    name_sig: bytes_signature(name_bytes),
+                    name_len,
+                };
 
-                if transition_cache_len >= TRANSITION_CACHE_CAPACITY {
-                    transition_cache.clear();
-                    transition_cache_len = 0;
-                }
-                if transition_cache
-                    .insert(
-                        key,
-                        TransitionValue {
-                            name: name_bytes.to_vec(),
-                            parent_states: Arc::clone(parent),
-                            states: Arc::clone(&next_states),
-                            next_sig,
-                        },
-                    )
-                    .is_none()
+                if let Some(cached) = transition_cache.get(&key)
+                    && cached.name.as_slice() == name_bytes
+                    && cached.parent_states.as_ref() == parent.as_ref()
                 {
-                    transition_cache_len += 1;
+                    (Arc::clone(&cached.states), cached.next_sig)
+                } else {
+                    let Some(name) = entry.name.to_str() else {
+                        if is_dir {
+                            let _ = fts.set(&entry, FtsSetOption::Skip);
+                        }
+                        continue;
+                    };
+
+                    ctx.compiled
+                        .advance_states_into(parent.as_ref(), name, next_states_scratch);
+                    let next_sig = states_signature(next_states_scratch);
+                    let next_states = Arc::<[usize]>::from(next_states_scratch.as_slice());
+
+                    if *transition_cache_len >= TRANSITION_CACHE_CAPACITY {
+                        transition_cache.clear();
+                        *transition_cache_len = 0;
+                    }
+                    if transition_cache
+                        .insert(
+                            key,
+                            TransitionValue {
+                                name: name_bytes.to_vec(),
+                                parent_states: Arc::clone(parent),
+                                states: Arc::clone(&next_states),
+                                next_sig,
+                            },
+                        )
+                        .is_none()
+                    {
+                        *transition_cache_len += 1;
+                    }
+
+                    (next_states, next_sig)
                 }
+            };
 
-                (next_states, next_sig)
+            if level_states.len() <= level {
+                level_states.resize(level + 1, Arc::<[usize]>::from(Vec::<usize>::new()));
             }
-        };
+            level_states[level] = Arc::clone(&states);
+            level_states.truncate(level + 1);
+            let states = level_states[level].as_ref();
 
-        if level_states.len() <= level {
-            level_states.resize(level + 1, Arc::<[usize]>::from(Vec::<usize>::new()));
-        }
-        level_states[level] = Arc::clone(&states);
-        level_states.truncate(level + 1);
-        let states = level_states[level].as_ref();
-
-        if states.is_empty() {
-            if is_dir {
-                let _ = fts.set(&entry, FtsSetOption::Skip);
+            if states.is_empty() {
+                if is_dir {
+                    let _ = fts.set(&entry, FtsSetOption::Skip);
+                }
+                continue;
             }
-            continue;
-        }
 
-        if is_dir
-            && !cached_needs_directory_scan(
-                &mut state_cache,
+            let matched_rule = cached_matched_rule(
+                state_cache,
                 ctx.compiled.as_ref(),
                 states_sig,
                 states,
-            )
-        {
-            let _ = fts.set(&entry, FtsSetOption::Skip);
-            continue;
-        }
+                is_dir,
+            );
 
-        let is_match =
-            cached_is_match_state(&mut state_cache, ctx.compiled.as_ref(), states_sig, states);
+            let depth = job.depth + level;
+            if is_dir
+                && (ctx.max_depth.is_some_and(|max| depth >= max)
+                    || !cached_needs_directory_scan(
+                        state_cache,
+                        ctx.compiled.as_ref(),
+                        states_sig,
+                        states,
+                    ))
+            {
+                // The directory itself may still match even though nothing below it can.
+                let _ = fts.set(&entry, FtsSetOption::Skip);
+                if let Some(rule) = matched_rule
+                    && !ctx.files_only
+                    && ctx.first_visit(&entry)
+                {
+                    pending_events.push(WalkEvent::new(entry.path.clone(), EntryKind::Dir, rule));
+                }
+                continue;
+            }
 
-        if is_dir
-            && level > 0
-            && should_split_directory(
-                entry.path.as_path(),
-                level,
-                &ctx.active_jobs,
-                ctx.split_backlog_limit,
-                &ctx.traversal_semaphore,
-            )
-        {
-            if is_match && !ctx.files_only {
-                pending_events.push(WalkEvent {
+            // A mount point is left to fts, which does not descend into it under XDEV.
+            if is_dir
+                && level > 0
+                && job
+                    .dev
+                    .is_none_or(|dev| same_device(entry.path.as_path(), dev))
+                && should_split_directory(ctx, entry.path.as_path(), level)
+            {
+                if let Some(rule) = matched_rule
+                    && !ctx.files_only
+                    && ctx.first_visit(&entry)
+                {
+                    pending_events.push(WalkEvent::new(entry.path.clone(), EntryKind::Dir, rule));
+                }
+
+                ctx.active_jobs.fetch_add(1, Ordering::AcqRel);
+                let enqueued = ctx.queue.push(RootJob {
                     path: entry.path.clone(),
-                    kind: EntryKind::Dir,
+                    root_states: states.to_vec(),
+                    dev: job.dev,
+                    depth,
                 });
-            }
+                if !enqueued {
+                    ctx.active_jobs.fetch_sub(1, Ordering::AcqRel);
+                }
+                let _ = fts.set(&entry, FtsSetOption::Skip);
 
-            ctx.active_jobs.fetch_add(1, Ordering::AcqRel);
-            let enqueued = ctx.queue.push(RootJob {
-                path: entry.path.clone(),
-                root_states: states.to_vec(),
-            });
-            if !enqueued {
-                ctx.active_jobs.fetch_sub(1, Ordering::AcqRel);
+                if pending_events.len() >= ctx.emit_batch_size {
+                    flush_events(pending_events, out, ctx.metadata);
+                }
+                continue;
             }
-            let _ = fts.set(&entry, FtsSetOption::Skip);
 
-            if pending_events.len() >= EMIT_BATCH_SIZE {
-                flush_events(&ctx.worker_tx, &mut pending_events, &ctx.cancel);
+            if ctx.files_only && is_dir {
+                continue;
             }
-            continue;
-        }
 
-        if ctx.files_only && is_dir {
-            continue;
-        }
-
-        if is_match {
-            let kind = entry_kind(entry.info.clone());
-            pending_events.push(WalkEvent {
-                path: entry.path.clone(),
-                kind,
-            });
-            if pending_events.len() >= EMIT_BATCH_SIZE {
-                flush_events(&ctx.worker_tx, &mut pending_events, &ctx.cancel);
+            if let Some(rule) = matched_rule
+                && ctx.first_visit(&entry)
+            {
+                let kind = entry_kind(entry.info.clone());
+                pending_events.push(WalkEvent::new(entry.path.clone(), kind, rule));
+                if pending_events.len() >= ctx.emit_batch_size {
+                    flush_events(pending_events, out, ctx.metadata);
+                }
             }
         }
-    }
 
-    flush_events(&ctx.worker_tx, &mut pending_events, &ctx.cancel);
+        flush_events(pending_events, out, ctx.metadata);
+        Step::Continue
+    }
 }
 
 async fn forward_worker_messages(
     mut rx: mpsc::Receiver<WorkerMessage>,
     tx: mpsc::Sender<WalkMessage>,
     cancel: Arc<AtomicBool>,
+    suppress: SuppressErrors,
 ) {
     while let Some(msg) = rx.recv().await {
         if cancel.load(Ordering::Relaxed) {
@@ -586,7 +972,7 @@ async fn forward_worker_messages(
                 }
             }
             WorkerMessage::Error(err) => {
-                if tx.send(Err(err)).await.is_err() {
+                if !suppress.send(&tx, err).await {
                     cancel.store(true, Ordering::Relaxed);
                     return;
                 }
@@ -595,20 +981,14 @@ async fn forward_worker_messages(
     }
 }
 
-fn should_split_directory(
-    path: &Path,
-    depth: usize,
-    active_jobs: &AtomicUsize,
-    split_backlog_limit: usize,
-    traversal_semaphore: &AdaptiveSemaphore,
-) -> bool {
-    if depth > SPLIT_DEPTH_LIMIT {
+fn should_split_directory(ctx: &WorkerCtx, path: &Path, depth: usize) -> bool {
+    if depth > ctx.split_depth_limit {
         return false;
     }
-    if active_jobs.load(Ordering::Relaxed) >= split_backlog_limit {
+    if ctx.active_jobs.load(Ordering::Relaxed) >= ctx.split_backlog_limit {
         return false;
     }
-    has_min_children(path, SPLIT_MIN_CHILDREN, traversal_semaphore)
+    has_min_children(path, ctx.split_threshold(), &ctx.traversal_semaphore)
 }
 
 fn has_min_children(
@@ -638,32 +1018,34 @@ fn has_min_children_inner(path: &Path, min_children: usize) -> io::Result<bool>
     Ok(false)
 }
 
-fn flush_events(
-    tx: &mpsc::Sender<WorkerMessage>,
-    pending: &mut Vec<WalkEvent>,
-    cancel: &AtomicBool,
-) {
-    if pending.is_empty() || cancel.load(Ordering::Relaxed) {
-        pending.clear();
+fn flush_events(pending: &mut Vec<WalkEvent>, out: &mut Vec<WorkerMessage>, metadata: bool) {
+    if pending.is_empty() {
         return;
     }
-    let chunk = std::mem::take(pending);
-    if tx.blocking_send(WorkerMessage::Events(chunk)).is_err() {
-        cancel.store(true, Ordering::Relaxed);
+    if metadata {
+        pending.iter_mut().for_each(WalkEvent::load_metadata);
     }
+    out.push(WorkerMessage::Events(std::mem::take(pending)));
 }
 
 fn prepare_jobs(
     compiled: &CompiledGlob,
+    roots: &[PathBuf],
     files_only: bool,
+    same_file_system: bool,
+    seen: Option<&SeenFiles>,
+    max_depth: Option<usize>,
     max_jobs: usize,
 ) -> (Vec<RootJob>, Vec<WalkEvent>) {
-    let roots = normalize_roots(compiled.start_paths());
+    let roots = normalize_roots(compiled.start_paths_within(roots));
     let mut jobs = Vec::new();
     let mut initial_events = Vec::new();
     let mut ctx = ShardCtx {
         compiled,
         files_only,
+        same_file_system,
+        dedup_inodes: seen.is_some(),
+        max_depth,
         max_jobs,
         state_cache: StateEvalCache::default(),
     };
@@ -678,12 +1060,37 @@ fn prepare_jobs(
             Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
             Err(_) => continue,
         };
+        let root_states = compiled.states_for_path(root.as_path());
+        if root_states.is_empty() {
+            continue;
+        }
+
+        // A rule naming a file literally starts the walk at that file.
         if !metadata.is_dir() {
+            let link_metadata = std::fs::symlink_metadata(root.as_path()).ok();
+            let kind = classify_entry(
+                root.as_path(),
+                link_metadata.as_ref().map(|metadata| metadata.file_type()),
+            );
+            if let Some(rule) = compiled.matched_rule(&root_states, false)
+                && let Some(kind) = kind
+                && (!files_only || kind == EntryKind::File)
+            {
+                let id = link_metadata.as_ref().unwrap_or(&metadata);
+                initial_events.push((WalkEvent::new(root, kind, rule), (id.dev(), id.ino())));
+            }
             continue;
         }
 
-        let root_states = compiled.states_for_path(root.as_path());
-        if root_states.is_empty() {
+        if max_depth == Some(0) {
+            if let Some(rule) = compiled.matched_rule(&root_states, true)
+                && !files_only
+            {
+                initial_events.push((
+                    WalkEvent::new(root, EntryKind::Dir, rule),
+                    (metadata.dev(), metadata.ino()),
+                ));
+            }
             continue;
         }
 
@@ -691,6 +1098,7 @@ fn prepare_jobs(
             &mut ctx,
             root.as_path(),
             &root_states,
+            metadata.dev(),
             SHARD_DEPTH,
             &mut jobs,
             &mut initial_events,
@@ -700,38 +1108,52 @@ fn prepare_jobs(
             jobs.push(RootJob {
                 path: root,
                 root_states,
+                dev: same_file_system.then(|| metadata.dev()),
+                depth: 0,
             });
-        } else if cached_is_match_state(
+        } else if let Some(rule) = cached_matched_rule(
             &mut ctx.state_cache,
             ctx.compiled,
             states_signature(&root_states),
             &root_states,
+            true,
         ) && !ctx.files_only
         {
-            initial_events.push(WalkEvent {
-                path: root,
-                kind: EntryKind::Dir,
-            });
+            initial_events.push((
+                WalkEvent::new(root, EntryKind::Dir, rule),
+                (metadata.dev(), metadata.ino()),
+            ));
         }
     }
 
+    let initial_events = initial_events
+        .into_iter()
+        .filter(|(_, id)| seen.is_none_or(|seen| seen.first(*id)))
+        .map(|(event, _)| event)
+        .collect();
     (jobs, initial_events)
 }
 
 struct ShardCtx<'a> {
     compiled: &'a CompiledGlob,
     files_only: bool,
+    same_file_system: bool,
+    dedup_inodes: bool,
+    max_depth: Option<usize>,
     max_jobs: usize,
     state_cache: StateEvalCache,
 }
 
+/// `dev` is the device of `root`; events carry the [`FileId`] of their entry. `root` lies
+/// `SHARD_DEPTH - depth` levels below the start of the walk.
 fn shard_root_jobs(
     ctx: &mut ShardCtx<'_>,
     root: &Path,
     root_states: &[usize],
+    dev: u64,
     depth: usize,
     jobs: &mut Vec<RootJob>,
-    initial_events: &mut Vec<WalkEvent>,
+    initial_events: &mut Vec<(WalkEvent, FileId)>,
 ) -> bool {
     if depth == 0 || jobs.len() >= ctx.max_jobs {
         return false;
@@ -745,6 +1167,8 @@ fn shard_root_jobs(
     let mut local_jobs = Vec::new();
     let mut local_events = Vec::new();
     let mut split_happened = false;
+    let entry_depth = SHARD_DEPTH - depth + 1;
+    let at_max_depth = ctx.max_depth.is_some_and(|max| entry_depth >= max);
 
     let mut capacity_exhausted = false;
 
@@ -768,6 +1192,8 @@ fn shard_root_jobs(
         let kind = classify_entry(path.as_path(), entry.file_type().ok());
         let next_signature = states_signature(&next_states);
         if kind != Some(EntryKind::Dir)
+            || at_max_depth
+            || (ctx.same_file_system && !same_device(path.as_path(), dev))
             || !cached_needs_directory_scan(
                 &mut ctx.state_cache,
                 ctx.compiled,
@@ -775,15 +1201,16 @@ fn shard_root_jobs(
                 &next_states,
             )
         {
-            if cached_is_match_state(
+            if let Some(rule) = cached_matched_rule(
                 &mut ctx.state_cache,
                 ctx.compiled,
                 next_signature,
                 &next_states,
+                kind == Some(EntryKind::Dir),
             ) && let Some(kind) = kind
                 && (!ctx.files_only || kind == EntryKind::File)
             {
-                local_events.push(WalkEvent { path, kind });
+                local_events.push((WalkEvent::new(path, kind, rule), (dev, entry.ino())));
             }
             continue;
         }
@@ -794,16 +1221,37 @@ fn shard_root_jobs(
             let mut child_events = Vec::new();
             let old_max_jobs = ctx.max_jobs;
             ctx.max_jobs = ctx.max_jobs.saturating_sub(jobs.len());
+            // Past a mount point the children carry the device of the mounted file system.
+            let child_dev = if ctx.dedup_inodes && !ctx.same_file_system {
+                entry.metadata().map_or(dev, |metadata| metadata.dev())
+            } else {
+                dev
+            };
             let child_split = shard_root_jobs(
                 ctx,
                 path.as_path(),
                 &next_states,
+                child_dev,
                 depth - 1,
                 &mut child_jobs,
                 &mut child_events,
             );
             ctx.max_jobs = old_max_jobs;
             if child_split {
+                // The children became jobs of their own, so the directory is reported here.
+                if let Some(rule) = cached_matched_rule(
+                    &mut ctx.state_cache,
+                    ctx.compiled,
+                    next_signature,
+                    &next_states,
+                    true,
+                ) && !ctx.files_only
+                {
+                    local_events.push((
+                        WalkEvent::new(path, EntryKind::Dir, rule),
+                        (dev, entry.ino()),
+                    ));
+                }
                 local_jobs.extend(child_jobs);
                 local_events.extend(child_events);
                 split_happened = true;
@@ -817,6 +1265,8 @@ fn shard_root_jobs(
         local_jobs.push(RootJob {
             path,
             root_states: next_states,
+            dev: ctx.same_file_system.then_some(dev),
+            depth: entry_depth,
         });
         split_happened = true;
     }
@@ -860,6 +1310,11 @@ fn symlink_kind(path: &Path) -> Option<EntryKind> {
     }
 }
 
+/// Whether `path` itself (not a symlink target) lives on device `dev`.
+fn same_device(path: &Path, dev: u64) -> bool {
+    std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.dev() == dev)
+}
+
 fn normalize_roots(mut roots: Vec<PathBuf>) -> Vec<PathBuf> {
     roots.sort();
     roots.dedup();
@@ -913,20 +1368,21 @@ fn default_parallelism() -> usize {
     std::cmp::max(4, cores.saturating_mul(2))
 }
 
-fn cached_is_match_state(
+fn cached_matched_rule(
     cache: &mut StateEvalCache,
     compiled: &CompiledGlob,
     signature: u64,
     states: &[usize],
-) -> bool {
-    if let Some(cached) = cache.match_cache.get(&signature) {
+    is_dir: bool,
+) -> Option<usize> {
+    if let Some(cached) = cache.match_cache.get(&(signature, is_dir)) {
         return *cached;
     }
-    let value = compiled.is_match_state(states);
+    let value = compiled.matched_rule(states, is_dir);
     if cache.match_cache.len() >= STATE_CACHE_CAPACITY {
         cache.match_cache.clear();
     }
-    cache.match_cache.insert(signature, value);
+    cache.match_cache.insert((signature, is_dir), value);
     value
 }
 
@@ -1,5 +1,7 @@
 use crate::compiled_glob::CompiledGlob;
-use crate::walker::{EntryKind, WalkError, WalkEvent, WalkMessage, WalkerOptions};
+use crate::walker::{
+    Dedup, EntryKind, SuppressErrors, WalkError, WalkEvent, WalkMessage, WalkerOptions,
+};
 use adaptive_semaphore::{AdaptiveSemaphore, AdaptiveSemaphorePermit};
 use hashbrown::HashSet;
 use std::cmp::max;
@@ -33,8 +35,17 @@ impl MatchProgram {
         self.compiled.advance_states(current, part)
     }
 
-    fn is_match_state(&self, current: &[usize]) -> bool {
-        self.compiled.is_match_state(current)
+    fn is_match_state(&self, current: &[usize], is_dir: bool) -> bool {
+        self.compiled.is_match_state(current, is_dir)
+    }
+
+    fn matched_rule(&self, current: &[usize], is_dir: bool) -> Option<usize> {
+        self.compiled.matched_rule(current, is_dir)
+    }
+
+    /// Whether `current` matches as either a directory or a non-directory.
+    fn may_match_state(&self, current: &[usize]) -> bool {
+        self.is_match_state(current, true) || self.is_match_state(current, false)
     }
 
     fn literal_candidates(&self, current: &[usize]) -> Vec<String> {
@@ -52,6 +63,13 @@ struct TraversalCtx {
     visited: Arc<Mutex<HashSet<VisitKey>>>,
     tx: mpsc::Sender<WalkMessage>,
     files_only: bool,
+    same_file_system: bool,
+    /// Skip the visited-directory set (`Dedup::None`).
+    skip_visited: bool,
+    max_depth: Option<usize>,
+    /// `WalkerOptions::metadata`.
+    metadata: bool,
+    suppress_errors: SuppressErrors,
 }
 
 #[derive(Clone)]
@@ -59,11 +77,20 @@ struct State {
     path: PathBuf,
     match_states: Vec<usize>,
     kind_hint: Option<EntryKind>,
+    /// `false` for a mount boundary under `WalkerOptions::same_file_system` or at
+    /// `WalkerOptions::max_depth`: matched, not read.
+    descend: bool,
+    depth: usize,
 }
 
 type DirIdentity = PathBuf;
 type VisitKey = (DirIdentity, u64);
 
+/// The Windows backend has no persistent workers; `WalkerOptions::reuse_workers` is ignored.
+pub(super) async fn shutdown_workers() -> bool {
+    false
+}
+
 pub(super) fn spawn_single_with_options(
     compiled: CompiledGlob,
     options: WalkerOptions,
@@ -76,9 +103,14 @@ pub(super) fn spawn_single_with_options(
         visited: Arc::new(Mutex::new(HashSet::new())),
         tx,
         files_only: options.files_only,
+        same_file_system: options.same_file_system,
+        skip_visited: options.dedup == Dedup::None,
+        max_depth: options.max_depth,
+        metadata: options.metadata,
+        suppress_errors: options.suppress_errors,
     };
 
-    let seed_paths = ctx.program.compiled.start_paths();
+    let seed_paths = ctx.program.compiled.start_paths_within(&options.roots);
     let mut seeded = Vec::new();
     for path in seed_paths {
         let states = ctx.program.states_for_path(path.as_path());
@@ -89,14 +121,18 @@ pub(super) fn spawn_single_with_options(
             path,
             match_states: states,
             kind_hint: Some(EntryKind::Dir),
+            descend: options.max_depth != Some(0),
+            depth: 0,
         });
     }
 
-    if seeded.is_empty() {
+    if seeded.is_empty() && options.roots.is_empty() {
         seeded.push(State {
             path: PathBuf::from(std::path::MAIN_SEPARATOR.to_string()),
             match_states: ctx.program.initial_states(),
             kind_hint: None,
+            descend: options.max_depth != Some(0),
+            depth: 0,
         });
     }
 
@@ -120,13 +156,12 @@ pub(super) fn spawn_single_with_options(
                 match joined {
                     Ok(next_states) => frontier.extend(next_states),
                     Err(err) => {
-                        let _ = ctx
-                            .tx
-                            .send(Err(WalkError::Io {
-                                path: PathBuf::from("<join>"),
-                                source: io::Error::other(err.to_string()),
-                            }))
-                            .await;
+                        send_error(
+                            &ctx,
+                            PathBuf::from("<join>"),
+                            io::Error::other(err.to_string()),
+                        )
+                        .await;
                     }
                 }
             }
@@ -167,16 +202,19 @@ async fn process_state_inner(ctx: TraversalCtx, state: State) -> Vec<State> {
     }
 
     if !ctx.files_only || !matches!(state.kind_hint, Some(EntryKind::Dir | EntryKind::Other)) {
-        if ctx.program.is_match_state(&state.match_states) {
-            finalize_match(&ctx, state.path.clone(), state.kind_hint).await;
+        let is_dir = state.kind_hint == Some(EntryKind::Dir);
+        if let Some(rule) = ctx.program.matched_rule(&state.match_states, is_dir) {
+            finalize_match(&ctx, state.path.clone(), state.kind_hint, rule).await;
         }
     }
 
-    if matches!(state.kind_hint, Some(EntryKind::File | EntryKind::Other)) {
+    if !state.descend || matches!(state.kind_hint, Some(EntryKind::File | EntryKind::Other)) {
         return Vec::new();
     }
 
     let signature = states_signature(&state.match_states);
+    let depth = state.depth + 1;
+    let within_depth = ctx.max_depth.is_none_or(|max| depth < max);
     let mut out = Vec::new();
     let literal_candidates = ctx.program.literal_candidates(&state.match_states);
     let mut handled_names = HashSet::new();
@@ -192,6 +230,8 @@ async fn process_state_inner(ctx: TraversalCtx, state: State) -> Vec<State> {
                 path: candidate_path,
                 match_states: next_states,
                 kind_hint: Some(entry_kind_from_file_type(metadata.file_type())),
+                descend: within_depth && (!ctx.same_file_system || !is_reparse_point(&metadata)),
+                depth,
             }),
             Err(err)
                 if matches!(
@@ -201,7 +241,7 @@ async fn process_state_inner(ctx: TraversalCtx, state: State) -> Vec<State> {
                         | io::ErrorKind::NotADirectory
                 ) => {}
             Err(err) => {
-                send_error(&ctx.tx, candidate_path, err).await;
+                send_error(&ctx, candidate_path, err).await;
             }
         }
     }
@@ -210,7 +250,9 @@ async fn process_state_inner(ctx: TraversalCtx, state: State) -> Vec<State> {
         return out;
     }
 
-    if !mark_dir_visited(&ctx.visited, &state.path, state.kind_hint, signature).await {
+    if !ctx.skip_visited
+        && !mark_dir_visited(&ctx.visited, &state.path, state.kind_hint, signature).await
+    {
         return out;
     }
 
@@ -220,7 +262,7 @@ async fn process_state_inner(ctx: TraversalCtx, state: State) -> Vec<State> {
             return out;
         }
         Err(err) => {
-            send_error(&ctx.tx, state.path, err).await;
+            send_error(&ctx, state.path, err).await;
             return out;
         }
     };
@@ -245,26 +287,39 @@ async fn process_state_inner(ctx: TraversalCtx, state: State) -> Vec<State> {
             if let Ok(file_type) = entry.file_type().await {
                 let kind = entry_kind_from_file_type(file_type);
                 kind_hint = Some(kind);
-                if kind == EntryKind::File && !ctx.program.is_match_state(&next_states) {
+                if kind == EntryKind::File && !ctx.program.is_match_state(&next_states, false) {
                     kind_hint = None;
                 }
             }
-        } else if ctx.program.is_match_state(&next_states)
+        } else if ctx.program.may_match_state(&next_states)
             && let Ok(file_type) = entry.file_type().await
         {
             kind_hint = Some(entry_kind_from_file_type(file_type));
         }
+        let descend = within_depth
+            && (!ctx.same_file_system
+                || !entry
+                    .metadata()
+                    .await
+                    .is_ok_and(|metadata| is_reparse_point(&metadata)));
         out.push(State {
             path: entry.path(),
             match_states: next_states,
             kind_hint,
+            descend,
+            depth,
         });
     }
 
     out
 }
 
-async fn finalize_match(ctx: &TraversalCtx, path: PathBuf, kind_hint: Option<EntryKind>) {
+async fn finalize_match(
+    ctx: &TraversalCtx,
+    path: PathBuf,
+    kind_hint: Option<EntryKind>,
+    rule: usize,
+) {
     let kind = match kind_hint {
         Some(kind) => Ok(kind),
         None => entry_kind(&path).await,
@@ -274,10 +329,14 @@ async fn finalize_match(ctx: &TraversalCtx, path: PathBuf, kind_hint: Option<Ent
             if ctx.files_only && kind != EntryKind::File {
                 return;
             }
-            let _ = ctx.tx.send(Ok(WalkEvent { path, kind })).await;
+            let mut event = WalkEvent::new(path, kind, rule);
+            if ctx.metadata {
+                event.metadata = tokio::fs::symlink_metadata(&event.path).await.ok();
+            }
+            let _ = ctx.tx.send(Ok(event)).await;
         }
         Err(err) => {
-            send_error(&ctx.tx, path, err).await;
+            send_error(&ctx, path, err).await;
         }
     }
 }
@@ -287,6 +346,13 @@ async fn entry_kind(path: &Path) -> io::Result<EntryKind> {
     Ok(entry_kind_from_file_type(symlink_meta.file_type()))
 }
 
+/// Mounted volumes and junctions are directory reparse points.
+fn is_reparse_point(metadata: &std::fs::Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+    metadata.file_attributes() & FILE_ATTRIBUTE_REPARSE_POINT != 0
+}
+
 fn entry_kind_from_file_type(file_type: FileType) -> EntryKind {
     if file_type.is_symlink() {
         return EntryKind::Symlink;
@@ -300,8 +366,9 @@ fn entry_kind_from_file_type(file_type: FileType) -> EntryKind {
     EntryKind::Other
 }
 
-async fn send_error(tx: &mpsc::Sender<WalkMessage>, path: PathBuf, source: io::Error) {
-    let _ = tx.send(Err(WalkError::Io { path, source })).await;
+async fn send_error(ctx: &TraversalCtx, path: PathBuf, source: io::Error) {
+    let err = WalkError::Io { path, source };
+    ctx.suppress_errors.send(&ctx.tx, err).await;
 }
 
 async fn mark_dir_visited(
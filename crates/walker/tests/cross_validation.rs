@@ -0,0 +1,222 @@
+//! Cross-validation of the walker against independent matchers.
+//!
+//! Random directory trees and rule sets are generated from fixed seeds. For each
+//! case, the paths emitted by [`Walker`] must equal both the entries of a plain
+//! recursive `std::fs` listing accepted by [`CompiledGlob::r#match`], and the
+//! entries accepted by a small reference matcher written here from the rule
+//! semantics (segment-wise matching, `**` spanning zero or more segments,
+//! last-match-wins, trailing `/` for directories only, a directory-only exclude
+//! covering the directory's contents). A failure prints the seed,
+//! the rules and the tree so the case can be replayed.
+#![cfg(all(unix, not(windows)))]
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use walker::compiled_glob::CompiledGlob;
+use walker::walker::Walker;
+use wildmatch::WildMatch;
+
+const CASES: u64 = 256;
+
+const NAMES: &[&str] = &["a", "b", "ab", "a.rs", "b.md", "x.lua", ".hidden"];
+const SEGMENTS: &[&str] = &[
+    "a", "b", "ab", "a.rs", "x.lua", "*", "?", "*.rs", "a*", "*b", "**",
+];
+
+/// xorshift64*; enough to spread cases without another dependency.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+struct Rule {
+    exclude: bool,
+    dir_only: bool,
+    segments: Vec<&'static str>,
+}
+
+impl Rule {
+    fn pattern(&self, root: &Path) -> String {
+        format!(
+            "{}{}/{}{}",
+            if self.exclude { "!" } else { "" },
+            root.display(),
+            self.segments.join("/"),
+            if self.dir_only { "/" } else { "" },
+        )
+    }
+}
+
+/// Relative paths of a random tree, each with whether it is a directory.
+fn random_tree(rng: &mut Rng) -> BTreeSet<(PathBuf, bool)> {
+    fn fill(rng: &mut Rng, dir: PathBuf, depth: usize, out: &mut BTreeSet<(PathBuf, bool)>) {
+        for _ in 0..1 + rng.below(4) {
+            let path = dir.join(rng.pick(NAMES));
+            if out.iter().any(|(known, _)| *known == path) {
+                continue;
+            }
+            let is_dir = depth < 3 && rng.below(2) == 0;
+            out.insert((path.clone(), is_dir));
+            if is_dir {
+                fill(rng, path, depth + 1, out);
+            }
+        }
+    }
+    let mut out = BTreeSet::new();
+    fill(rng, PathBuf::new(), 0, &mut out);
+    out
+}
+
+fn random_rules(rng: &mut Rng) -> Vec<Rule> {
+    (0..1 + rng.below(3))
+        .map(|i| Rule {
+            exclude: i > 0 && rng.below(3) == 0,
+            dir_only: rng.below(4) == 0,
+            segments: (0..1 + rng.below(3)).map(|_| rng.pick(SEGMENTS)).collect(),
+        })
+        .collect()
+}
+
+fn segments_match(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| segments_match(rest, &path[skip..])),
+        Some((segment, rest)) => path.split_first().is_some_and(|(part, path)| {
+            WildMatch::new(segment).matches(part) && segments_match(rest, path)
+        }),
+    }
+}
+
+/// The decision of the last rule that applies to `path`, or no match. A
+/// directory-only exclude also applies to everything below a directory it
+/// matches; the root itself is never matched, as in gitignore.
+fn reference_match(rules: &[Rule], path: &Path, is_dir: bool) -> bool {
+    let parts: Vec<&str> = path.iter().map(|part| part.to_str().unwrap()).collect();
+    rules
+        .iter()
+        .rev()
+        .find(|rule| {
+            ((!rule.dir_only || is_dir) && segments_match(&rule.segments, &parts))
+                || (rule.dir_only
+                    && rule.exclude
+                    && (1..parts.len()).any(|len| segments_match(&rule.segments, &parts[..len])))
+        })
+        .is_some_and(|rule| !rule.exclude)
+}
+
+fn test_root(seed: u64) -> PathBuf {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("clock should be valid")
+        .as_nanos();
+    std::env::temp_dir().join(format!("walker-cross-{seed}-{stamp}"))
+}
+
+async fn walked(glob: CompiledGlob, root: &Path) -> BTreeSet<PathBuf> {
+    let mut rx = Walker::spawn(glob);
+    let mut got = BTreeSet::new();
+    while let Some(msg) = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("channel should respond")
+    {
+        let event = msg.expect("walk should not fail");
+        let path = event.path.strip_prefix(root).expect("path under root");
+        // `**` also matches the root itself, which is not part of the generated tree.
+        if !path.as_os_str().is_empty() {
+            got.insert(path.to_path_buf());
+        }
+    }
+    got
+}
+
+fn listed(root: &Path, rel: &Path, out: &mut Vec<(PathBuf, bool)>) {
+    for entry in fs::read_dir(root.join(rel)).expect("read tree") {
+        let entry = entry.expect("read entry");
+        let path = rel.join(entry.file_name());
+        let is_dir = entry.file_type().expect("file type").is_dir();
+        out.push((path.clone(), is_dir));
+        if is_dir {
+            listed(root, &path, out);
+        }
+    }
+}
+
+#[tokio::test]
+async fn walker_agrees_with_match_and_reference() {
+    for seed in 0..CASES {
+        let mut rng = Rng::new(seed);
+        let tree = random_tree(&mut rng);
+        let rules = random_rules(&mut rng);
+        let root = test_root(seed);
+        fs::create_dir_all(&root).expect("create root");
+        for (path, is_dir) in &tree {
+            if *is_dir {
+                fs::create_dir_all(root.join(path)).expect("create dir");
+            } else {
+                fs::write(root.join(path), b"").expect("write file");
+            }
+        }
+        let root = root.canonicalize().expect("canonical root");
+
+        let glob = CompiledGlob::merge_many(
+            rules
+                .iter()
+                .map(|rule| CompiledGlob::new(&rule.pattern(&root)).expect("glob must parse")),
+        )
+        .expect("globs must merge");
+
+        let mut entries = Vec::new();
+        listed(&root, Path::new(""), &mut entries);
+        let by_match: BTreeSet<PathBuf> = entries
+            .iter()
+            .filter(|(path, is_dir)| {
+                let mut full = root.join(path).into_os_string();
+                if *is_dir {
+                    full.push("/");
+                }
+                glob.r#match(&full)
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        let by_reference: BTreeSet<PathBuf> = entries
+            .iter()
+            .filter(|(path, is_dir)| reference_match(&rules, path, *is_dir))
+            .map(|(path, _)| path.clone())
+            .collect();
+        let by_walker = walked(glob, &root).await;
+
+        let patterns: Vec<String> = rules
+            .iter()
+            .map(|rule| rule.pattern(Path::new("<root>")))
+            .collect();
+        let listing: Vec<String> = tree
+            .iter()
+            .map(|(path, is_dir)| format!("{}{}", path.display(), if *is_dir { "/" } else { "" }))
+            .collect();
+        let context = format!("seed {seed}\nrules: {patterns:?}\ntree: {listing:?}");
+        assert_eq!(by_match, by_reference, "match vs reference, {context}");
+        assert_eq!(by_walker, by_reference, "walker vs reference, {context}");
+        let _ = fs::remove_dir_all(&root);
+    }
+}
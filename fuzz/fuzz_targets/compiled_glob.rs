@@ -0,0 +1,22 @@
+//! `CompiledGlob::new` and matching must not panic on any pattern or path, and
+//! `explain` must agree with `match`.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use walker::compiled_glob::CompiledGlob;
+
+fuzz_target!(|input: (Vec<String>, Vec<String>)| {
+    let (patterns, paths) = input;
+    let globs: Vec<CompiledGlob> = patterns
+        .iter()
+        .filter_map(|pattern| CompiledGlob::new(pattern).ok())
+        .collect();
+    let Ok(glob) = CompiledGlob::merge_many(globs) else {
+        return;
+    };
+    let _ = glob.lint();
+    for path in &paths {
+        let matched = glob.r#match(path.as_ref());
+        assert_eq!(glob.explain(path.as_ref()).matched, matched, "{path:?}");
+    }
+});
@@ -0,0 +1,9 @@
+//! Config files are user input: deserializing any TOML document into the
+//! plugin config must report errors instead of panicking.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|text: &str| {
+    let _ = rsplug_parsers::parse_config(text);
+});
@@ -0,0 +1,19 @@
+//! `ignore` specs are read from user configs: parsing and matching must not
+//! panic, and the parsed rules must survive a round trip through their lines.
+#![no_main]
+
+use std::str::FromStr;
+
+use file_specifier::FileSpecifier;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (String, Vec<String>)| {
+    let (spec, paths) = input;
+    let Ok(parsed) = FileSpecifier::from_str(&spec);
+    let lines: Vec<&str> = parsed.rules().map(|rule| rule.line.as_str()).collect();
+    let Ok(reparsed) = FileSpecifier::from_str(&lines.join("\n"));
+    assert!(parsed.rules().eq(reparsed.rules()));
+    for path in &paths {
+        assert_eq!(parsed.matched(path), reparsed.matched(path), "{path:?}");
+    }
+});
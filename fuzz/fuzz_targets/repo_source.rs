@@ -0,0 +1,9 @@
+//! `repo` values (`owner/repo[@rev]` or URLs) come straight from configs:
+//! parsing them must never panic.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    let _ = rsplug_parsers::parse_repo_source(source);
+});
@@ -0,0 +1,39 @@
+#!/usr/bin/env bash
+# Run the criterion benches against a stored baseline.
+#
+#   scripts/bench.sh save [NAME]   record a baseline (default: main)
+#   scripts/bench.sh check [NAME]  compare with it; exit 1 on a regression
+#
+# Baselines live in target/criterion, so they are local to the machine that
+# recorded them. Record one on the base branch, then check the change.
+set -euo pipefail
+
+ROOT="$(cd "$(dirname "$0")/.." && pwd)"
+MODE="${1:?Usage: $0 save|check [baseline]}"
+BASELINE="${2:-main}"
+# Changes smaller than this are treated as noise.
+NOISE_THRESHOLD="${BENCH_NOISE_THRESHOLD:-0.05}"
+
+cd "$ROOT"
+case "$MODE" in
+  save)
+    cargo bench -p rsplug-walker --benches -- --save-baseline "$BASELINE" --noise-threshold "$NOISE_THRESHOLD"
+    ;;
+  check)
+    if [[ ! -d target/criterion ]] || ! find target/criterion -type d -name "$BASELINE" | grep -q .; then
+      echo "error: no baseline \`$BASELINE\`; record one with: $0 save $BASELINE" >&2
+      exit 2
+    fi
+    log="$(mktemp)"
+    trap 'rm -f "$log"' EXIT
+    cargo bench -p rsplug-walker --benches -- --baseline "$BASELINE" --noise-threshold "$NOISE_THRESHOLD" | tee "$log"
+    if grep -q "Performance has regressed" "$log"; then
+      echo "error: benchmarks regressed against baseline \`$BASELINE\`" >&2
+      exit 1
+    fi
+    ;;
+  *)
+    echo "Usage: $0 save|check [baseline]" >&2
+    exit 2
+    ;;
+esac